@@ -0,0 +1,46 @@
+//! Bulk triage throughput
+//!
+//! Triages 1 MiB documents from a warm page cache, one thread and all
+//! threads, and reports documents per second. Record the numbers with
+//! the CPU, core count and storage of the machine that produced them.
+
+use std::path::PathBuf;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use pdf_engine::antiforensics::scanner::triage::{BulkTriage, TriageProfile, REFERENCE_DOC_BYTES};
+
+const DOCUMENTS: usize = 64;
+
+/// A PDF-like file of `size` bytes with an action at the start
+fn document(size: usize) -> Vec<u8> {
+    let mut data = b"%PDF-1.7\n1 0 obj << /OpenAction << /S /JavaScript /JS (x) >> >> endobj\n".to_vec();
+    data.resize(size - 6, b' ');
+    data.extend_from_slice(b"%%EOF\n");
+    data
+}
+
+fn bulk_triage(c: &mut Criterion) {
+    let dir = tempfile::tempdir().expect("temporary directory");
+    let files: Vec<PathBuf> = (0..DOCUMENTS)
+        .map(|i| {
+            let path = dir.path().join(format!("{}.pdf", i));
+            std::fs::write(&path, document(REFERENCE_DOC_BYTES as usize)).expect("benchmark document");
+            path
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("bulk_triage");
+    group.throughput(Throughput::Elements(DOCUMENTS as u64));
+    for threads in [1, rayon::current_num_threads()] {
+        let triage = BulkTriage::new(TriageProfile { threads, ..TriageProfile::bulk() });
+        // Warm the page cache
+        triage.run(&files).expect("triage");
+        group.bench_with_input(BenchmarkId::new("threads", threads), &triage, |b, triage| {
+            b.iter(|| triage.run(&files).expect("triage"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bulk_triage);
+criterion_main!(benches);
@@ -0,0 +1,219 @@
+//! Anonymized fleet statistics
+//!
+//! Sidecars, evidence logs and run histories name documents and list
+//! what was found in each, which keeps them inside the security team.
+//! `FleetAggregator` reduces a batch to counts that can be shared beyond
+//! it: documents, documents containing each artifact type and documents
+//! by their highest risk, with no paths, hashes or identifiers.
+//!
+//! Counts are released in the manner of differential privacy. Each
+//! document adds at most one to the document count, one to the risk
+//! distribution and `max_types_per_document` to the type counts, and each
+//! group gets Laplace noise calibrated to that contribution and a third
+//! of `epsilon`. Noisy counts below `min_count` are suppressed, so rare
+//! artifact types cannot single out the few documents that have them.
+//! Smaller `epsilon` means more noise and stronger protection.
+//!
+//! Noise and the threshold apply to a fixed set of groups, every
+//! `ArtifactType` and every `RiskLevel`, whether or not the batch had any
+//! documents in them: a group that appeared only when some document fell
+//! into it would reveal that document. Custom types are pooled under
+//! `Custom`, since their names are free text.
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+use chrono::{NaiveDate, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::antiforensics::{report::sidecar::ScanSummary, ArtifactType, RiskLevel};
+
+/// Group custom artifact types are counted under
+const CUSTOM: &str = "Custom";
+
+/// Privacy parameters of a release
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AggregateConfig {
+    /// Privacy budget of the whole release
+    pub epsilon: f64,
+    /// Noisy counts below this are withheld
+    pub min_count: u64,
+    /// Artifact types a single document is counted under, most frequent first
+    pub max_types_per_document: usize,
+}
+
+impl Default for AggregateConfig {
+    fn default() -> Self {
+        Self { epsilon: 1.0, min_count: 5, max_types_per_document: 5 }
+    }
+}
+
+/// Released statistics of a batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FleetStatistics {
+    /// Day of the release; finer times would help link it to a batch
+    pub date: NaiveDate,
+    pub epsilon: f64,
+    pub documents: u64,
+    /// Documents with at least one artifact of each type
+    pub documents_by_type: BTreeMap<String, u64>,
+    /// Documents by the highest risk found in them, `None` for clean ones
+    pub documents_by_risk: BTreeMap<String, u64>,
+    /// Categories withheld for falling below the minimum count
+    pub suppressed: usize,
+}
+
+impl FleetStatistics {
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// Exact per-batch counts, kept only in memory until noise is added
+#[derive(Debug, Default)]
+pub struct FleetAggregator {
+    config: AggregateConfig,
+    documents: u64,
+    by_type: BTreeMap<String, u64>,
+    by_risk: BTreeMap<String, u64>,
+}
+
+impl FleetAggregator {
+    pub fn new(config: AggregateConfig) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    /// Counts one document by the summary of its scan
+    pub fn add(&mut self, scan: &ScanSummary) {
+        self.documents += 1;
+        let domain = type_domain();
+        let mut grouped: BTreeMap<&str, usize> = BTreeMap::new();
+        for (artifact_type, &count) in scan.by_type.iter().filter(|&(_, &count)| count > 0) {
+            let group = domain.iter().find(|name| *name == artifact_type).map_or(CUSTOM, String::as_str);
+            *grouped.entry(group).or_default() += count;
+        }
+        let mut types: Vec<(&str, usize)> = grouped.into_iter().collect();
+        types.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (artifact_type, _) in types.into_iter().take(self.config.max_types_per_document) {
+            *self.by_type.entry(artifact_type.to_string()).or_default() += 1;
+        }
+        *self.by_risk.entry(highest_risk(scan)).or_default() += 1;
+    }
+
+    /// Noisy, thresholded statistics; the exact counts are consumed
+    pub fn release(self) -> FleetStatistics {
+        self.release_with(&mut rand::thread_rng())
+    }
+
+    pub fn release_with(self, rng: &mut impl Rng) -> FleetStatistics {
+        let group_epsilon = self.config.epsilon / 3.0;
+        let type_sensitivity = self.config.max_types_per_document.max(1) as f64;
+        let mut suppressed = 0;
+        // Every group of the domain, empty ones included
+        let mut noisy = |counts: BTreeMap<String, u64>, domain: Vec<String>, sensitivity: f64| -> BTreeMap<String, u64> {
+            domain.into_iter()
+                .filter_map(|key| {
+                    let count = counts.get(&key).copied().unwrap_or(0);
+                    let released = noisy_count(count, sensitivity / group_epsilon, rng);
+                    if released < self.config.min_count {
+                        suppressed += 1;
+                        return None;
+                    }
+                    Some((key, released))
+                })
+                .collect()
+        };
+        let documents_by_type = noisy(self.by_type, type_domain(), type_sensitivity);
+        let documents_by_risk = noisy(self.by_risk, risk_domain(), 1.0);
+        FleetStatistics {
+            date: Utc::now().date_naive(),
+            epsilon: self.config.epsilon,
+            documents: noisy_count(self.documents, 1.0 / group_epsilon, rng),
+            documents_by_type,
+            documents_by_risk,
+            suppressed,
+        }
+    }
+}
+
+/// Every artifact type group, as `ScanSummary` names them
+fn type_domain() -> Vec<String> {
+    let mut domain: Vec<String> = [
+        ArtifactType::Metadata,
+        ArtifactType::Content,
+        ArtifactType::Structure,
+        ArtifactType::JavaScript,
+        ArtifactType::Binary,
+        ArtifactType::Signature,
+        ArtifactType::EmbeddedFile,
+    ]
+    .iter()
+    .map(|artifact_type| format!("{:?}", artifact_type))
+    .collect();
+    domain.push(CUSTOM.to_string());
+    domain
+}
+
+/// Every risk level, as `ScanSummary` names them
+fn risk_domain() -> Vec<String> {
+    [RiskLevel::Critical, RiskLevel::High, RiskLevel::Medium, RiskLevel::Low, RiskLevel::None]
+        .iter()
+        .map(|risk| format!("{:?}", risk))
+        .collect()
+}
+
+/// Highest risk in a scan summary, as `ScanSummary` names it
+fn highest_risk(scan: &ScanSummary) -> String {
+    [RiskLevel::Critical, RiskLevel::High, RiskLevel::Medium, RiskLevel::Low]
+        .into_iter()
+        .map(|risk| format!("{:?}", risk))
+        .find(|risk| scan.by_risk.get(risk).is_some_and(|&count| count > 0))
+        .unwrap_or_else(|| format!("{:?}", RiskLevel::None))
+}
+
+/// `count` plus Laplace noise of scale `scale`, rounded and clamped at zero
+fn noisy_count(count: u64, scale: f64, rng: &mut impl Rng) -> u64 {
+    let u: f64 = rng.gen_range(-0.5..0.5);
+    let noise = -scale * u.signum() * (1.0 - 2.0 * u.abs()).max(f64::MIN_POSITIVE).ln();
+    (count as f64 + noise).round().max(0.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_release_is_noisy_thresholded_and_anonymous() {
+        let summary = |types: &[(&str, usize)], risk: &str| ScanSummary {
+            total: types.iter().map(|(_, count)| count).sum(),
+            by_type: types.iter().map(|(t, c)| (t.to_string(), *c)).collect(),
+            by_risk: BTreeMap::from([(risk.to_string(), 1)]),
+            ..Default::default()
+        };
+        let config = AggregateConfig { max_types_per_document: 2, ..Default::default() };
+        let mut aggregator = FleetAggregator::new(config);
+        for _ in 0..2000 {
+            aggregator.add(&summary(&[("JavaScript", 3), ("Metadata", 1), ("EmbeddedFile", 1)], "High"));
+        }
+        aggregator.add(&summary(&[("Custom(\"jdoe-watermark\")", 2)], "Critical"));
+        aggregator.add(&ScanSummary::default());
+        // Capped at two types per document, the most frequent first
+        assert_eq!(aggregator.by_type.get("JavaScript"), Some(&2000));
+        assert_eq!(aggregator.by_type.get("EmbeddedFile"), Some(&2000));
+        assert_eq!(aggregator.by_type.get("Metadata"), None);
+        assert_eq!(aggregator.by_type.get("Custom"), Some(&1));
+        assert_eq!(aggregator.by_risk.get("None"), Some(&1));
+
+        // Default epsilon: type counts get Laplace noise of scale 2 / (1/3) = 6
+        let stats = aggregator.release_with(&mut StdRng::seed_from_u64(7));
+        assert!(stats.documents.abs_diff(2002) <= 60, "{}", stats.documents);
+        assert!(stats.documents_by_type["JavaScript"].abs_diff(2000) <= 120);
+        assert!(stats.documents_by_risk["High"].abs_diff(2000) <= 60);
+        // Every group is either released or suppressed, empty ones included,
+        // so which groups appear says nothing about any one document
+        assert_eq!(stats.documents_by_type.len() + stats.documents_by_risk.len() + stats.suppressed, 8 + 5);
+        assert!(stats.documents_by_type.keys().all(|key| type_domain().contains(key)));
+        let json = serde_json::to_string(&stats).unwrap();
+        assert!(!json.contains("jdoe") && !json.contains(".pdf") && !json.contains("run_id"));
+    }
+}
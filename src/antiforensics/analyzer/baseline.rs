@@ -0,0 +1,267 @@
+//! Structural anomaly scoring against a learned baseline
+//! Author: kartik4091
+//! Created: 2025-06-04 23:57:14 UTC
+//! Documents from one source share a structure: the same object types in
+//! similar proportions, the same filters, streams of similar size. A
+//! baseline learned from a corpus of known-good documents records the
+//! mean and spread of those statistics; a new document is scored by how
+//! far its own statistics fall outside them. Object types and filters the
+//! corpus never used count as strong deviations.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use lopdf::Object;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use super::{xref_integrity, AnalyzerError, Result};
+
+/// Standard deviations beyond which a feature counts as fully anomalous
+const MAX_DEVIATION: f64 = 10.0;
+
+/// Deviations listed in a score, largest first
+const REPORTED_DEVIATIONS: usize = 10;
+
+/// Structural statistics of one document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StructuralProfile {
+    pub objects: usize,
+    /// Objects per `/Type` (`Stream`, `Dictionary` and so on when untyped)
+    pub types: BTreeMap<String, usize>,
+    pub streams: usize,
+    /// Streams per filter; a stream with two filters counts for both
+    pub filters: BTreeMap<String, usize>,
+    /// Total encoded stream length
+    pub stream_bytes: u64,
+}
+
+impl StructuralProfile {
+    pub fn of(doc: &lopdf::Document) -> Self {
+        let mut profile = Self { objects: doc.objects.len(), ..Default::default() };
+        for object in doc.objects.values() {
+            let (dict, untyped) = match object {
+                Object::Dictionary(dict) => (dict, "Dictionary"),
+                Object::Stream(stream) => {
+                    profile.streams += 1;
+                    profile.stream_bytes += stream.content.len() as u64;
+                    for filter in stream.filters().unwrap_or_default() {
+                        *profile.filters.entry(filter).or_default() += 1;
+                    }
+                    (&stream.dict, "Stream")
+                }
+                Object::Array(_) => {
+                    *profile.types.entry("Array".into()).or_default() += 1;
+                    continue;
+                }
+                _ => {
+                    *profile.types.entry("Value".into()).or_default() += 1;
+                    continue;
+                }
+            };
+            let kind = dict.get(b"Type").and_then(Object::as_name_str).unwrap_or(untyped);
+            *profile.types.entry(kind.to_string()).or_default() += 1;
+        }
+        profile
+    }
+
+    /// Features compared against the baseline, all scaled to be comparable across document sizes
+    fn features(&self) -> BTreeMap<String, f64> {
+        let mut features = BTreeMap::new();
+        features.insert("log_objects".into(), (self.objects as f64 + 1.0).ln());
+        features.insert("stream_share".into(), ratio(self.streams, self.objects));
+        let average = if self.streams == 0 { 0.0 } else { self.stream_bytes as f64 / self.streams as f64 };
+        features.insert("log_avg_stream_size".into(), (average + 1.0).ln());
+        for (kind, &count) in &self.types {
+            features.insert(format!("type:{}", kind), ratio(count, self.objects));
+        }
+        for (filter, &count) in &self.filters {
+            features.insert(format!("filter:{}", filter), ratio(count, self.streams));
+        }
+        features
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 { 0.0 } else { count as f64 / total as f64 }
+}
+
+/// Running mean and variance of one feature (Welford)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct FeatureStats {
+    mean: f64,
+    m2: f64,
+}
+
+impl FeatureStats {
+    fn std_dev(&self, documents: usize) -> f64 {
+        if documents < 2 { 0.0 } else { (self.m2 / (documents - 1) as f64).sqrt() }
+    }
+}
+
+/// One feature of a scored document and how far it is from the corpus
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FeatureDeviation {
+    pub feature: String,
+    pub value: f64,
+    /// Corpus mean; 0 for features the corpus never had
+    pub mean: f64,
+    /// Standard deviations from the mean, capped at 10
+    pub deviation: f64,
+}
+
+/// Structural anomaly of one document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AnomalyScore {
+    /// 0 for a document typical of the corpus, approaching 1 as it deviates
+    pub score: f64,
+    /// Largest deviations first
+    pub deviations: Vec<FeatureDeviation>,
+}
+
+/// One line of `baseline score --json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BaselineScoreLine {
+    pub path: PathBuf,
+    pub anomaly: AnomalyScore,
+}
+
+/// Structural statistics learned from known-good documents
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StructuralBaseline {
+    documents: usize,
+    features: BTreeMap<String, FeatureStats>,
+}
+
+impl StructuralBaseline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Documents learned so far
+    pub fn documents(&self) -> usize {
+        self.documents
+    }
+
+    /// Adds one known-good document to the baseline
+    pub fn learn(&mut self, profile: &StructuralProfile) {
+        let values = profile.features();
+        self.documents += 1;
+        let n = self.documents as f64;
+        // A feature first seen now was zero in every earlier document,
+        // which is exactly what zeroed stats stand for
+        for name in values.keys() {
+            self.features.entry(name.clone()).or_default();
+        }
+        for (name, stats) in self.features.iter_mut() {
+            let value = values.get(name).copied().unwrap_or(0.0);
+            let delta = value - stats.mean;
+            stats.mean += delta / n;
+            stats.m2 += delta * (value - stats.mean);
+        }
+    }
+
+    /// Loads and learns every file in `paths`, returning the ones that failed to parse
+    pub fn learn_files(&mut self, paths: &[impl AsRef<Path>]) -> Vec<(String, lopdf::Error)> {
+        let mut failed = Vec::new();
+        for path in paths {
+            let loaded = std::fs::read(crate::paths::extended(path.as_ref()))
+                .map_err(lopdf::Error::from)
+                .and_then(|data| xref_integrity::load_checked(&data));
+            match loaded {
+                Ok(doc) => self.learn(&StructuralProfile::of(&doc)),
+                Err(e) => failed.push((path.as_ref().display().to_string(), e)),
+            }
+        }
+        failed
+    }
+
+    /// Scores a document by its deviation from the baseline
+    ///
+    /// Each feature's deviation is its distance from the corpus mean in
+    /// standard deviations; spreads below a small floor are raised to it,
+    /// so a feature every corpus document shares exactly does not turn
+    /// the slightest difference into an infinite deviation.
+    pub fn score(&self, profile: &StructuralProfile) -> AnomalyScore {
+        let values = profile.features();
+        let mut names: Vec<&String> = self.features.keys().chain(values.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        let mut deviations: Vec<FeatureDeviation> = names.into_iter()
+            .map(|name| {
+                let value = values.get(name).copied().unwrap_or(0.0);
+                let (mean, deviation) = match self.features.get(name) {
+                    Some(stats) => {
+                        let floor = if name.starts_with("log_") { 0.25 } else { 0.02 };
+                        let spread = stats.std_dev(self.documents).max(floor);
+                        (stats.mean, ((value - stats.mean).abs() / spread).min(MAX_DEVIATION))
+                    }
+                    None => (0.0, if value > 0.0 { MAX_DEVIATION } else { 0.0 }),
+                };
+                FeatureDeviation { feature: name.clone(), value, mean, deviation }
+            })
+            .collect();
+
+        let score = if deviations.is_empty() {
+            0.0
+        } else {
+            let rms = (deviations.iter().map(|d| d.deviation.powi(2)).sum::<f64>() / deviations.len() as f64).sqrt();
+            1.0 - (-rms / 3.0).exp()
+        };
+        deviations.sort_by(|a, b| b.deviation.total_cmp(&a.deviation).then_with(|| a.feature.cmp(&b.feature)));
+        deviations.retain(|d| d.deviation > 0.0);
+        deviations.truncate(REPORTED_DEVIATIONS);
+        AnomalyScore { score, deviations }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| AnalyzerError::InvalidInput(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self).map_err(|e| AnalyzerError::ProcessingError(e.to_string()))?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Document, Stream};
+
+    fn document(pages: usize, filter: &str, scripts: usize) -> Document {
+        let mut doc = Document::with_version("1.7");
+        for page in 0..pages {
+            let mut content = Stream::new(dictionary! {}, format!("BT ({}) Tj ET", page).repeat(20).into_bytes());
+            content.dict.set("Filter", lopdf::Object::Name(filter.as_bytes().to_vec()));
+            let content = doc.add_object(content);
+            doc.add_object(dictionary! { "Type" => "Page", "Contents" => content });
+        }
+        for _ in 0..scripts {
+            doc.add_object(dictionary! { "Type" => "Action", "S" => "JavaScript" });
+        }
+        doc
+    }
+
+    #[test]
+    fn test_scores_grow_with_deviation() {
+        let mut baseline = StructuralBaseline::new();
+        for pages in 3..9 {
+            baseline.learn(&StructuralProfile::of(&document(pages, "FlateDecode", 0)));
+        }
+        assert_eq!(baseline.documents(), 6);
+
+        let typical = baseline.score(&StructuralProfile::of(&document(5, "FlateDecode", 0)));
+        let unusual = baseline.score(&StructuralProfile::of(&document(5, "ASCIIHexDecode", 3)));
+        assert!(typical.score < 0.2, "typical document scored {}", typical.score);
+        assert!(unusual.score > typical.score + 0.3, "unusual document scored {}", unusual.score);
+        let top: Vec<&str> = unusual.deviations.iter().take(3).map(|d| d.feature.as_str()).collect();
+        assert!(top.contains(&"filter:ASCIIHexDecode") && top.contains(&"type:Action"));
+
+        let path = tempfile::NamedTempFile::new().unwrap();
+        baseline.save(path.path()).unwrap();
+        assert_eq!(StructuralBaseline::load(path.path()).unwrap(), baseline);
+    }
+}
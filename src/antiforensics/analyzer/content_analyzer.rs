@@ -253,6 +253,7 @@ impl Analyzer for ContentAnalyzer {
                     operation_count: 1,
                 },
                 processing_time: Duration::from_secs(0),
+                structural_anomaly: None,
             });
         }
 
@@ -297,6 +298,7 @@ impl Analyzer for ContentAnalyzer {
                 operation_count: 1,
             },
             processing_time: duration,
+            structural_anomaly: None,
         })
     }
 
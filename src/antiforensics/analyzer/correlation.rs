@@ -0,0 +1,300 @@
+//! Cross-document correlation for batch scans
+//! Author: kartik4091
+//! Created: 2025-06-04 17:26:53 UTC
+//! Collects shared traits across every document in a batch: identical
+//! embedded files, uncommon producer or creator strings, and URLs,
+//! domains or IPs. Documents linked by shared traits are grouped into
+//! campaigns, which says far more during incident response than the same
+//! finding repeated in hundreds of per-file reports.
+
+use std::collections::{BTreeMap, BTreeSet};
+use lopdf::Object;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, instrument};
+
+use crate::antiforensics::{
+    report::ioc::{IndicatorKind, IocError, IocExportConfig, IocExporter},
+    types::Document,
+    ForensicArtifact,
+    RiskLevel,
+};
+
+/// Kind of trait shared between documents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CorrelationKind {
+    /// SHA-256 of an embedded file's decoded content
+    EmbeddedFile,
+    Producer,
+    Creator,
+    Url,
+    Domain,
+    Ipv4,
+}
+
+/// Correlation configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrelationConfig {
+    /// Documents that must share a trait before it is reported
+    pub min_documents: usize,
+
+    /// Producer/Creator substrings too common to link documents (case-insensitive)
+    pub common_tools: Vec<String>,
+
+    /// Indicator extraction settings for URLs, domains and IPs
+    pub ioc: IocExportConfig,
+}
+
+/// Trait shared by several documents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Correlation {
+    pub kind: CorrelationKind,
+    pub value: String,
+    /// Documents exhibiting the trait, in name order
+    pub documents: Vec<String>,
+    /// Highest risk level of the artifacts the trait came from
+    pub risk_level: RiskLevel,
+}
+
+/// Documents connected through shared traits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Campaign {
+    pub documents: Vec<String>,
+    /// Shared traits linking the documents
+    pub traits: Vec<(CorrelationKind, String)>,
+    pub risk_level: RiskLevel,
+}
+
+/// Campaign-level summary of a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampaignReport {
+    pub documents_analyzed: usize,
+    /// Shared traits, most widespread first
+    pub correlations: Vec<Correlation>,
+    /// Connected groups of documents, largest first
+    pub campaigns: Vec<Campaign>,
+}
+
+#[derive(Debug, Default)]
+struct Observation {
+    documents: BTreeSet<String>,
+    risk_level: Option<RiskLevel>,
+}
+
+/// Accumulates documents of a batch and correlates them
+pub struct CorrelationAnalyzer {
+    config: CorrelationConfig,
+    exporter: IocExporter,
+    observations: BTreeMap<(CorrelationKind, String), Observation>,
+    documents: BTreeSet<String>,
+}
+
+impl CorrelationAnalyzer {
+    /// Creates a new analyzer
+    pub fn new(config: CorrelationConfig) -> Result<Self, IocError> {
+        Ok(Self {
+            exporter: IocExporter::new(config.ioc.clone())?,
+            config,
+            observations: BTreeMap::new(),
+            documents: BTreeSet::new(),
+        })
+    }
+
+    /// Records one document and the artifacts found in it
+    #[instrument(skip(self, doc, artifacts))]
+    pub fn add_document(&mut self, name: &str, doc: &Document, artifacts: &[ForensicArtifact]) {
+        self.documents.insert(name.to_string());
+
+        for (_, stream) in doc.streams() {
+            let is_embedded = stream.dict.get(b"Type").and_then(Object::as_name).ok() == Some(b"EmbeddedFile".as_slice());
+            if is_embedded {
+                let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                let hash = format!("{:x}", Sha256::digest(&data));
+                self.observe(CorrelationKind::EmbeddedFile, hash, name, Some(RiskLevel::High));
+            }
+        }
+
+        if let Some(info) = doc.info() {
+            for (key, kind) in [(b"Producer".as_slice(), CorrelationKind::Producer), (b"Creator".as_slice(), CorrelationKind::Creator)] {
+                if let Ok(Object::String(bytes, _)) = info.get(key) {
+                    let value = String::from_utf8_lossy(bytes).trim().to_string();
+                    if !value.is_empty() && !self.is_common_tool(&value) {
+                        self.observe(kind, value, name, None);
+                    }
+                }
+            }
+        }
+
+        for indicator in self.exporter.extract(artifacts) {
+            let kind = match indicator.kind {
+                IndicatorKind::Url => CorrelationKind::Url,
+                IndicatorKind::Domain => CorrelationKind::Domain,
+                IndicatorKind::Ipv4 => CorrelationKind::Ipv4,
+                // Artifact hashes are of references, not file content; embedded files are hashed above
+                IndicatorKind::Md5 | IndicatorKind::Sha1 | IndicatorKind::Sha256 => continue,
+            };
+            self.observe(kind, indicator.value, name, Some(indicator.risk_level));
+        }
+    }
+
+    fn is_common_tool(&self, value: &str) -> bool {
+        let value = value.to_lowercase();
+        self.config.common_tools.iter().any(|tool| value.contains(&tool.to_lowercase()))
+    }
+
+    fn observe(&mut self, kind: CorrelationKind, value: String, document: &str, risk_level: Option<RiskLevel>) {
+        let observation = self.observations.entry((kind, value)).or_default();
+        observation.documents.insert(document.to_string());
+        if let Some(level) = risk_level {
+            if observation.risk_level.map_or(true, |current| level.severity() > current.severity()) {
+                observation.risk_level = Some(level);
+            }
+        }
+    }
+
+    /// Builds the campaign report for everything recorded so far
+    pub fn report(&self) -> CampaignReport {
+        let mut correlations: Vec<Correlation> = self.observations.iter()
+            .filter(|(_, o)| o.documents.len() >= self.config.min_documents.max(2))
+            .map(|((kind, value), o)| Correlation {
+                kind: *kind,
+                value: value.clone(),
+                documents: o.documents.iter().cloned().collect(),
+                risk_level: o.risk_level.unwrap_or(RiskLevel::Low),
+            })
+            .collect();
+        correlations.sort_by(|a, b| b.documents.len().cmp(&a.documents.len())
+            .then(b.risk_level.severity().cmp(&a.risk_level.severity())));
+
+        let campaigns = Self::campaigns(&correlations);
+        debug!("Correlated {} documents into {} campaigns", self.documents.len(), campaigns.len());
+
+        CampaignReport {
+            documents_analyzed: self.documents.len(),
+            correlations,
+            campaigns,
+        }
+    }
+
+    /// Groups documents connected by any shared trait
+    fn campaigns(correlations: &[Correlation]) -> Vec<Campaign> {
+        let names: Vec<&String> = correlations.iter()
+            .flat_map(|c| c.documents.iter())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let index = |name: &String| names.binary_search(&name).unwrap_or_default();
+
+        // Union-find over document indices
+        let mut parent: Vec<usize> = (0..names.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for correlation in correlations {
+            let first = index(&correlation.documents[0]);
+            for document in &correlation.documents[1..] {
+                let (a, b) = (root(&mut parent, first), root(&mut parent, index(document)));
+                parent[a] = b;
+            }
+        }
+
+        let mut groups: BTreeMap<usize, Campaign> = BTreeMap::new();
+        for (i, name) in names.iter().enumerate() {
+            let r = root(&mut parent, i);
+            groups.entry(r).or_insert_with(|| Campaign {
+                documents: Vec::new(),
+                traits: Vec::new(),
+                risk_level: RiskLevel::Low,
+            }).documents.push((*name).clone());
+        }
+        for correlation in correlations {
+            let r = root(&mut parent, index(&correlation.documents[0]));
+            let campaign = groups.get_mut(&r).expect("every correlated document has a group");
+            campaign.traits.push((correlation.kind, correlation.value.clone()));
+            if correlation.risk_level.severity() > campaign.risk_level.severity() {
+                campaign.risk_level = correlation.risk_level;
+            }
+        }
+
+        let mut campaigns: Vec<Campaign> = groups.into_values().collect();
+        campaigns.sort_by(|a, b| b.documents.len().cmp(&a.documents.len()));
+        campaigns
+    }
+}
+
+impl Default for CorrelationConfig {
+    fn default() -> Self {
+        Self {
+            min_documents: 2,
+            common_tools: [
+                "Microsoft", "Acrobat", "Adobe PDF Library", "LibreOffice", "OpenOffice",
+                "Ghostscript", "Quartz PDFContext", "Skia/PDF", "pdfTeX", "iText",
+            ].iter().map(|s| s.to_string()).collect(),
+            ioc: IocExportConfig::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+    use std::{collections::HashMap, path::PathBuf};
+
+    fn document(producer: &str, attachment: &[u8]) -> Document {
+        let mut doc = Document::from_pdf(PathBuf::from("batch.pdf"), lopdf::Document::with_version("1.7"));
+        doc.structure.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, attachment.to_vec()));
+        let info = doc.structure.add_object(dictionary! { "Producer" => Object::string_literal(producer) });
+        doc.structure.trailer.set("Info", info);
+        doc
+    }
+
+    fn uri_artifact(url: &str) -> ForensicArtifact {
+        let mut metadata = HashMap::new();
+        metadata.insert("value".into(), url.to_string());
+        ForensicArtifact { risk_level: RiskLevel::Medium, metadata, ..Default::default() }
+    }
+
+    #[test]
+    fn test_shared_attachment_and_producer_form_campaign() {
+        let mut analyzer = CorrelationAnalyzer::new(CorrelationConfig::default()).unwrap();
+        analyzer.add_document("a.pdf", &document("EvilGen 1.2", b"MZ payload"), &[]);
+        analyzer.add_document("b.pdf", &document("EvilGen 1.2", b"MZ payload"), &[]);
+        analyzer.add_document("c.pdf", &document("Microsoft Word", b"unrelated"), &[]);
+
+        let report = analyzer.report();
+        assert_eq!(report.documents_analyzed, 3);
+        assert!(report.correlations.iter().any(|c| c.kind == CorrelationKind::EmbeddedFile && c.documents == ["a.pdf", "b.pdf"]));
+        assert!(report.correlations.iter().any(|c| c.kind == CorrelationKind::Producer && c.value == "EvilGen 1.2"));
+        assert_eq!(report.campaigns.len(), 1);
+        assert_eq!(report.campaigns[0].documents, vec!["a.pdf", "b.pdf"]);
+        assert_eq!(report.campaigns[0].risk_level, RiskLevel::High);
+    }
+
+    #[test]
+    fn test_common_producers_ignored() {
+        let mut analyzer = CorrelationAnalyzer::new(CorrelationConfig::default()).unwrap();
+        analyzer.add_document("a.pdf", &document("Microsoft Word", b"x"), &[]);
+        analyzer.add_document("b.pdf", &document("Microsoft Word", b"y"), &[]);
+
+        assert!(analyzer.report().correlations.is_empty());
+    }
+
+    #[test]
+    fn test_shared_url_links_documents_transitively() {
+        let mut analyzer = CorrelationAnalyzer::new(CorrelationConfig::default()).unwrap();
+        analyzer.add_document("a.pdf", &document("", b"1"), &[uri_artifact("https://evil.example.com/x")]);
+        analyzer.add_document("b.pdf", &document("", b"2"), &[uri_artifact("https://evil.example.com/y")]);
+        analyzer.add_document("c.pdf", &document("", b"3"), &[uri_artifact("https://other.example.net/y")]);
+        analyzer.add_document("d.pdf", &document("", b"4"), &[uri_artifact("https://other.example.net/y")]);
+        analyzer.add_document("e.pdf", &document("", b"5"), &[uri_artifact("https://evil.example.com/z https://other.example.net/z")]);
+
+        let report = analyzer.report();
+        assert_eq!(report.campaigns.len(), 1);
+        assert_eq!(report.campaigns[0].documents.len(), 5);
+    }
+}
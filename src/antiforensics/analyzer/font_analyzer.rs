@@ -0,0 +1,528 @@
+//! Glyph-level font anomaly analysis
+//! Author: kartik4091
+//! Created: 2025-06-04 15:31:44 UTC
+//! Compares each font's ToUnicode CMap against the glyphs its encoding
+//! actually draws. Remapped CMaps make extracted text differ from the text a
+//! reader sees, which defeats keyword scanners and redaction review.
+
+use std::collections::HashMap;
+use lopdf::{Dictionary, Object, ObjectId};
+use sha2::{Sha256, Digest};
+use tracing::{debug, instrument, warn};
+
+use crate::antiforensics::{types::Document, ArtifactType, ForensicArtifact, RiskLevel};
+
+/// Most entries a ToUnicode CMap may map, one for every two-byte code
+const MAX_CMAP_ENTRIES: usize = 0x10000;
+
+/// Font analysis configuration
+#[derive(Debug, Clone)]
+pub struct FontAnalyzerConfig {
+    /// Minimum number of codes sharing one Unicode value before it is reported
+    pub many_to_one_threshold: usize,
+
+    /// Fraction of comparable codes that must disagree with the encoding
+    pub shuffle_ratio: f64,
+
+    /// Minimum number of comparable codes before the shuffle ratio is trusted
+    pub min_comparable: usize,
+}
+
+/// Mapping anomaly detected in a font
+#[derive(Debug, Clone, PartialEq)]
+pub enum CmapAnomaly {
+    /// Several character codes extract to the same text
+    ManyToOne {
+        /// Extracted text shared by the codes
+        target: String,
+        /// Codes mapped to it
+        codes: Vec<u32>,
+    },
+    /// ToUnicode disagrees with the glyphs the encoding draws
+    Shuffled {
+        /// Codes compared against the encoding
+        compared: usize,
+        /// `(code, drawn, extracted)` for disagreeing codes
+        mismatches: Vec<(u32, char, String)>,
+    },
+}
+
+/// Analyzes font dictionaries for remapped glyphs
+#[derive(Debug)]
+pub struct FontAnalyzer {
+    config: FontAnalyzerConfig,
+}
+
+impl FontAnalyzer {
+    /// Creates a new font analyzer
+    pub fn new(config: FontAnalyzerConfig) -> Self {
+        Self { config }
+    }
+
+    /// Analyzes every font dictionary in a document
+    #[instrument(skip(self, doc))]
+    pub fn analyze_document(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        let mut artifacts = Vec::new();
+
+        for (id, object) in doc.objects() {
+            let dict = match object {
+                Object::Dictionary(dict) => dict,
+                _ => continue,
+            };
+            if dict.get(b"Type").and_then(Object::as_name).ok() != Some(b"Font".as_slice()) {
+                continue;
+            }
+
+            for anomaly in self.analyze_font(doc, dict) {
+                artifacts.push(Self::artifact(id, dict, &anomaly));
+            }
+        }
+
+        debug!("Font analysis produced {} artifacts", artifacts.len());
+        artifacts
+    }
+
+    /// Analyzes a single font dictionary
+    pub fn analyze_font(&self, doc: &Document, font: &Dictionary) -> Vec<CmapAnomaly> {
+        let cmap = match Self::to_unicode(doc, font) {
+            Some(cmap) => cmap,
+            None => return Vec::new(),
+        };
+
+        let mut anomalies = Vec::new();
+
+        let mut by_target: HashMap<&str, Vec<u32>> = HashMap::new();
+        for (code, target) in cmap.iter() {
+            if !target.trim().is_empty() {
+                by_target.entry(target.as_str()).or_default().push(*code);
+            }
+        }
+        let mut shared: Vec<_> = by_target.into_iter()
+            .filter(|(_, codes)| codes.len() >= self.config.many_to_one_threshold)
+            .collect();
+        shared.sort_by(|a, b| a.0.cmp(b.0));
+        for (target, mut codes) in shared {
+            codes.sort_unstable();
+            anomalies.push(CmapAnomaly::ManyToOne { target: target.to_string(), codes });
+        }
+
+        if let Some(encoding) = Self::simple_encoding(doc, font) {
+            let mut compared = 0;
+            let mut mismatches = Vec::new();
+
+            for (code, drawn) in encoding.iter() {
+                if let Some(extracted) = cmap.get(code) {
+                    compared += 1;
+                    if extracted.chars().count() != 1 || !extracted.starts_with(*drawn) {
+                        mismatches.push((*code, *drawn, extracted.clone()));
+                    }
+                }
+            }
+
+            if compared >= self.config.min_comparable
+                && mismatches.len() as f64 / compared as f64 >= self.config.shuffle_ratio
+            {
+                mismatches.sort_by_key(|m| m.0);
+                anomalies.push(CmapAnomaly::Shuffled { compared, mismatches });
+            }
+        }
+
+        anomalies
+    }
+
+    /// Loads and parses a font's ToUnicode CMap
+    fn to_unicode(doc: &Document, font: &Dictionary) -> Option<HashMap<u32, String>> {
+        let id = font.get(b"ToUnicode").and_then(Object::as_reference).ok()?;
+        let stream = doc.object(id)?.as_stream().ok()?;
+        let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+        let cmap = parse_to_unicode(&data);
+        if cmap.is_empty() {
+            warn!("ToUnicode CMap {} {} R contains no mappings", id.0, id.1);
+            return None;
+        }
+        Some(cmap)
+    }
+
+    /// Letters and digits drawn by a simple font's encoding, keyed by code
+    ///
+    /// Composite (Type0) fonts draw CIDs with no standard meaning, so only
+    /// the many-to-one check applies to them.
+    fn simple_encoding(doc: &Document, font: &Dictionary) -> Option<HashMap<u32, char>> {
+        if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0".as_slice()) {
+            return None;
+        }
+
+        // Latin text encodings agree on ASCII letters and digits
+        let mut encoding: HashMap<u32, char> = (0x20u32..0x7F)
+            .filter_map(|code| char::from_u32(code).filter(char::is_ascii_alphanumeric).map(|c| (code, c)))
+            .collect();
+
+        let encoding_dict = match font.get(b"Encoding") {
+            Ok(Object::Reference(id)) => doc.dictionary(*id),
+            Ok(Object::Dictionary(dict)) => Some(dict),
+            _ => None,
+        };
+        let differences = encoding_dict.and_then(|d| d.get(b"Differences").and_then(Object::as_array).ok());
+
+        if let Some(differences) = differences {
+            let mut code = 0u32;
+            for item in differences {
+                match item {
+                    Object::Integer(start) => code = *start as u32,
+                    Object::Name(name) => {
+                        match glyph_char(name) {
+                            Some(c) => encoding.insert(code, c),
+                            None => encoding.remove(&code),
+                        };
+                        code += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Some(encoding)
+    }
+
+    /// Builds an artifact for an anomaly
+    fn artifact(id: ObjectId, font: &Dictionary, anomaly: &CmapAnomaly) -> ForensicArtifact {
+        let base_font = font.get(b"BaseFont")
+            .and_then(Object::as_name)
+            .map(|n| String::from_utf8_lossy(n).into_owned())
+            .unwrap_or_else(|_| "unnamed".into());
+
+        let mut metadata = HashMap::new();
+        metadata.insert("object_id".into(), format!("{} {}", id.0, id.1));
+        metadata.insert("base_font".into(), base_font.clone());
+
+        let (description, risk_level, detail, confidence) = match anomaly {
+            CmapAnomaly::ManyToOne { target, codes } => {
+                metadata.insert("anomaly".into(), "many_to_one".into());
+                metadata.insert("codes".into(), codes.iter().map(|c| format!("{:#06x}", c)).collect::<Vec<_>>().join(","));
+                (
+                    format!("Font {} maps {} codes to {:?}", base_font, codes.len(), target),
+                    RiskLevel::Medium,
+                    format!("{}:{:?}", target, codes),
+                    // Ligatures legitimately share a target; many codes rarely do
+                    1.0 - 1.0 / codes.len().max(1) as f64,
+                )
+            }
+            CmapAnomaly::Shuffled { compared, mismatches } => {
+                metadata.insert("anomaly".into(), "shuffled".into());
+                metadata.insert("compared".into(), compared.to_string());
+                metadata.insert("mismatches".into(), mismatches.iter()
+                    .map(|(code, drawn, extracted)| format!("{:#04x}:{}->{}", code, drawn, extracted))
+                    .collect::<Vec<_>>()
+                    .join(","));
+                (
+                    format!(
+                        "Font {} ToUnicode disagrees with drawn glyphs for {} of {} codes",
+                        base_font, mismatches.len(), compared
+                    ),
+                    RiskLevel::High,
+                    format!("{:?}", mismatches),
+                    mismatches.len() as f64 / (*compared).max(1) as f64,
+                )
+            }
+        };
+
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type: ArtifactType::Content,
+            location: format!("{} {} R:/ToUnicode", id.0, id.1),
+            description,
+            risk_level,
+            remediation: "Compare rendered pages against extracted text; regenerate the ToUnicode CMap or rasterize affected text".into(),
+            metadata,
+            detection_timestamp: chrono::Utc::now(),
+            hash: format!("{:x}", Sha256::digest(detail.as_bytes())),
+            confidence,
+        }
+    }
+}
+
+/// Parses `bfchar` and `bfrange` sections of a ToUnicode CMap
+///
+/// Parsing stops once [`MAX_CMAP_ENTRIES`] codes are mapped, so a hostile
+/// CMap listing many wide ranges cannot grow the map without bound.
+pub fn parse_to_unicode(data: &[u8]) -> HashMap<u32, String> {
+    let tokens = tokenize(data);
+    let mut map = HashMap::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match tokens[i] {
+            CmapToken::Word(ref w) if w == "beginbfchar" => {
+                i += 1;
+                while i + 1 < tokens.len() {
+                    match (&tokens[i], &tokens[i + 1]) {
+                        (CmapToken::Hex(src), CmapToken::Hex(dst)) => {
+                            map.insert(code_value(src), utf16_text(dst));
+                            if map.len() >= MAX_CMAP_ENTRIES {
+                                return map;
+                            }
+                            i += 2;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            CmapToken::Word(ref w) if w == "beginbfrange" => {
+                i += 1;
+                while i + 2 < tokens.len() {
+                    let (lo, hi) = match (&tokens[i], &tokens[i + 1]) {
+                        (CmapToken::Hex(lo), CmapToken::Hex(hi)) => (code_value(lo), code_value(hi)),
+                        _ => break,
+                    };
+                    // Guard against absurd ranges in hostile CMaps
+                    let hi = hi.min(lo.saturating_add(0xFFFF));
+
+                    match &tokens[i + 2] {
+                        CmapToken::Hex(dst) => {
+                            let base = utf16_units(dst);
+                            for (offset, code) in (lo..=hi).enumerate() {
+                                let mut units = base.clone();
+                                if let Some(last) = units.last_mut() {
+                                    *last = last.wrapping_add(offset as u16);
+                                }
+                                map.insert(code, String::from_utf16_lossy(&units));
+                                if map.len() >= MAX_CMAP_ENTRIES {
+                                    return map;
+                                }
+                            }
+                            i += 3;
+                        }
+                        CmapToken::ArrayStart => {
+                            let mut j = i + 3;
+                            let mut code = lo;
+                            while let Some(CmapToken::Hex(dst)) = tokens.get(j) {
+                                if code <= hi {
+                                    map.insert(code, utf16_text(dst));
+                                    if map.len() >= MAX_CMAP_ENTRIES {
+                                        return map;
+                                    }
+                                }
+                                code = code.saturating_add(1);
+                                j += 1;
+                            }
+                            // Skip the closing bracket
+                            i = j + 1;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    map
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CmapToken {
+    Hex(Vec<u8>),
+    Word(String),
+    ArrayStart,
+    ArrayEnd,
+}
+
+fn tokenize(data: &[u8]) -> Vec<CmapToken> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        match data[i] {
+            b'%' => {
+                while i < data.len() && data[i] != b'\n' && data[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            // Dictionary delimiters carry no mapping data
+            b'<' | b'>' if data.get(i + 1) == Some(&data[i]) => i += 2,
+            b'<' => {
+                let end = data[i..].iter().position(|b| *b == b'>').map(|p| i + p).unwrap_or(data.len());
+                let digits: Vec<u8> = data[i + 1..end].iter().copied().filter(u8::is_ascii_hexdigit).collect();
+                let bytes = digits.chunks(2)
+                    .map(|pair| {
+                        let hex = std::str::from_utf8(pair).unwrap_or("0");
+                        let value = u8::from_str_radix(hex, 16).unwrap_or(0);
+                        if pair.len() == 1 { value << 4 } else { value }
+                    })
+                    .collect();
+                tokens.push(CmapToken::Hex(bytes));
+                i = end + 1;
+            }
+            b'[' => {
+                tokens.push(CmapToken::ArrayStart);
+                i += 1;
+            }
+            b']' => {
+                tokens.push(CmapToken::ArrayEnd);
+                i += 1;
+            }
+            b if b.is_ascii_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                while i < data.len()
+                    && !data[i].is_ascii_whitespace()
+                    && !matches!(data[i], b'<' | b'>' | b'[' | b']' | b'%')
+                {
+                    i += 1;
+                }
+                if i == start {
+                    i += 1;
+                    continue;
+                }
+                tokens.push(CmapToken::Word(String::from_utf8_lossy(&data[start..i]).into_owned()));
+            }
+        }
+    }
+
+    tokens
+}
+
+fn code_value(bytes: &[u8]) -> u32 {
+    bytes.iter().take(4).fold(0u32, |acc, b| (acc << 8) | *b as u32)
+}
+
+fn utf16_units(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+        .collect()
+}
+
+fn utf16_text(bytes: &[u8]) -> String {
+    String::from_utf16_lossy(&utf16_units(bytes))
+}
+
+/// Resolves single-character glyph names (`a`, `Z`, `five`, `uni0041`)
+fn glyph_char(name: &[u8]) -> Option<char> {
+    const DIGITS: [&str; 10] = ["zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine"];
+
+    let name = std::str::from_utf8(name).ok()?;
+    if name.len() == 1 && name.as_bytes()[0].is_ascii_alphabetic() {
+        return name.chars().next();
+    }
+    if let Some(digit) = DIGITS.iter().position(|d| *d == name) {
+        return char::from_digit(digit as u32, 10);
+    }
+    if let Some(hex) = name.strip_prefix("uni").filter(|h| h.len() == 4) {
+        return u32::from_str_radix(hex, 16).ok()
+            .and_then(char::from_u32)
+            .filter(char::is_ascii_alphanumeric);
+    }
+    None
+}
+
+impl Default for FontAnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            many_to_one_threshold: 3,
+            shuffle_ratio: 0.2,
+            min_comparable: 5,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    fn font_with_cmap(cmap: &str, font: Dictionary) -> (Document, Dictionary) {
+        let mut doc = Document::default();
+        let cmap_id = doc.structure.add_object(Stream::new(dictionary! {}, cmap.as_bytes().to_vec()));
+        let mut font = font;
+        font.set("Type", "Font");
+        font.set("ToUnicode", cmap_id);
+        doc.structure.add_object(font.clone());
+        (doc, font)
+    }
+
+    fn identity_cmap(letters: &str) -> String {
+        let entries: String = letters.chars()
+            .map(|c| format!("<{:02X}> <{:04X}>\n", c as u32, c as u32))
+            .collect();
+        format!("begincmap\n{} beginbfchar\n{}endbfchar\nendcmap", letters.len(), entries)
+    }
+
+    #[test]
+    fn test_parse_bfchar_and_bfrange() {
+        let cmap = parse_to_unicode(b"2 beginbfchar <01> <0041> <02> <00660069> endbfchar\n\
+            2 beginbfrange <10> <12> <0061> <20> <21> [<0058> <0059>] endbfrange");
+        assert_eq!(cmap[&0x01], "A");
+        assert_eq!(cmap[&0x02], "fi");
+        assert_eq!(cmap[&0x11], "b");
+        assert_eq!(cmap[&0x12], "c");
+        assert_eq!(cmap[&0x21], "Y");
+    }
+
+    #[test]
+    fn test_wide_ranges_capped_in_total() {
+        // Each range alone is within the per-range guard; together they are not
+        let ranges: String = (0..64u32)
+            .map(|n| format!("<{:08X}> <{:08X}> <0041>\n", n << 16, (n << 16) | 0xFFFF))
+            .collect();
+        let cmap = parse_to_unicode(format!("64 beginbfrange\n{}endbfrange", ranges).as_bytes());
+        assert_eq!(cmap.len(), MAX_CMAP_ENTRIES);
+        // Parsing stopped once the first range filled the map
+        assert!(cmap.keys().all(|&code| code <= 0xFFFF));
+    }
+
+    #[test]
+    fn test_faithful_cmap_is_clean() {
+        let (doc, font) = font_with_cmap(&identity_cmap("Helloworld"), dictionary! { "Subtype" => "TrueType" });
+        assert!(FontAnalyzer::new(FontAnalyzerConfig::default()).analyze_font(&doc, &font).is_empty());
+    }
+
+    #[test]
+    fn test_shuffled_cmap_detected() {
+        // Glyphs a..f extract as f..a
+        let cmap = "beginbfchar <61> <0066> <62> <0065> <63> <0064> <64> <0063> <65> <0062> <66> <0061> endbfchar";
+        let (doc, font) = font_with_cmap(cmap, dictionary! { "Subtype" => "Type1" });
+
+        let anomalies = FontAnalyzer::new(FontAnalyzerConfig::default()).analyze_font(&doc, &font);
+        match &anomalies[..] {
+            [CmapAnomaly::Shuffled { compared, mismatches }] => {
+                assert_eq!(*compared, 6);
+                assert_eq!(mismatches[0], (0x61, 'a', "f".to_string()));
+            }
+            other => panic!("unexpected anomalies: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_many_to_one_on_composite_font() {
+        let cmap = "beginbfchar <0001> <0078> <0002> <0078> <0003> <0078> <0004> <0079> endbfchar";
+        let (doc, font) = font_with_cmap(cmap, dictionary! { "Subtype" => "Type0" });
+
+        let anomalies = FontAnalyzer::new(FontAnalyzerConfig::default()).analyze_font(&doc, &font);
+        assert_eq!(anomalies, vec![CmapAnomaly::ManyToOne { target: "x".into(), codes: vec![1, 2, 3] }]);
+    }
+
+    #[test]
+    fn test_differences_respected() {
+        // Code 0x01 draws "a" via Differences and extracts as "a"
+        let cmap = "beginbfchar <01> <0061> <02> <0062> endbfchar";
+        let (doc, font) = font_with_cmap(cmap, dictionary! {
+            "Subtype" => "Type1",
+            "Encoding" => dictionary! {
+                "Differences" => vec![1.into(), Object::Name(b"a".to_vec()), Object::Name(b"b".to_vec())],
+            },
+        });
+        let analyzer = FontAnalyzer::new(FontAnalyzerConfig { min_comparable: 1, ..Default::default() });
+        assert!(analyzer.analyze_font(&doc, &font).is_empty());
+    }
+
+    #[test]
+    fn test_document_artifacts() {
+        let cmap = "beginbfchar <61> <0066> <62> <0065> <63> <0064> <64> <0063> <65> <0062> <66> <0061> endbfchar";
+        let (doc, _) = font_with_cmap(cmap, dictionary! { "Subtype" => "Type1", "BaseFont" => "Tricky" });
+
+        let artifacts = FontAnalyzer::new(FontAnalyzerConfig::default()).analyze_document(&doc);
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].risk_level, RiskLevel::High);
+        assert_eq!(artifacts[0].metadata["base_font"], "Tricky");
+    }
+}
@@ -0,0 +1,674 @@
+//! Info dictionary and XMP consistency
+//! Author: kartik4091
+//! Created: 2025-06-04 23:36:05 UTC
+//! A document describes itself twice: in the trailer /Info dictionary and
+//! in the catalog's XMP packet. Tools that edit one and not the other
+//! leave the old title, author or dates behind in the second, where a
+//! cleaned Info dictionary no longer hints at them. The analyzer reports
+//! fields the two disagree on; `synchronize` makes them agree again,
+//! with Info or XMP taking precedence, or clears the fields from both.
+//!
+//! XMP is read and edited textually, as elsewhere in the crate, so other
+//! properties of the packet are kept byte for byte. Dates are compared
+//! as instants, so `D:20240102030405Z` matches `2024-01-02T03:04:05Z`;
+//! Info dates that are not valid PDF dates are reported as well.
+
+use std::{collections::HashMap, sync::OnceLock};
+use lopdf::{dictionary, Dictionary, Object, ObjectId};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use tracing::instrument;
+
+use crate::antiforensics::{
+    cleaner::form_data::text_string,
+    scanner::unicode_spoof::decode_pdf_string,
+    types::Document,
+    ArtifactType, ForensicArtifact, RiskLevel,
+};
+use crate::pdf_date::PdfDate;
+
+/// Field present in both the Info dictionary and XMP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MetadataField {
+    Title,
+    Author,
+    Subject,
+    Keywords,
+    Creator,
+    Producer,
+    CreationDate,
+    ModDate,
+}
+
+/// How an XMP property holds its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum XmpShape {
+    /// Plain text
+    Simple,
+    /// Language alternatives (`rdf:Alt`); the first is used
+    Alt,
+    /// Ordered list (`rdf:Seq`); items are joined with `; `
+    Seq,
+    /// ISO 8601 date
+    Date,
+}
+
+impl MetadataField {
+    pub const ALL: [MetadataField; 8] = [
+        MetadataField::Title,
+        MetadataField::Author,
+        MetadataField::Subject,
+        MetadataField::Keywords,
+        MetadataField::Creator,
+        MetadataField::Producer,
+        MetadataField::CreationDate,
+        MetadataField::ModDate,
+    ];
+
+    /// Info dictionary key
+    pub fn info_key(self) -> &'static str {
+        match self {
+            MetadataField::Title => "Title",
+            MetadataField::Author => "Author",
+            MetadataField::Subject => "Subject",
+            MetadataField::Keywords => "Keywords",
+            MetadataField::Creator => "Creator",
+            MetadataField::Producer => "Producer",
+            MetadataField::CreationDate => "CreationDate",
+            MetadataField::ModDate => "ModDate",
+        }
+    }
+
+    /// XMP property names, the one written first; older writers use `xap:` for `xmp:`
+    pub fn xmp_names(self) -> &'static [&'static str] {
+        match self {
+            MetadataField::Title => &["dc:title"],
+            MetadataField::Author => &["dc:creator"],
+            MetadataField::Subject => &["dc:description"],
+            MetadataField::Keywords => &["pdf:Keywords"],
+            MetadataField::Creator => &["xmp:CreatorTool", "xap:CreatorTool"],
+            MetadataField::Producer => &["pdf:Producer"],
+            MetadataField::CreationDate => &["xmp:CreateDate", "xap:CreateDate"],
+            MetadataField::ModDate => &["xmp:ModifyDate", "xap:ModifyDate"],
+        }
+    }
+
+    fn shape(self) -> XmpShape {
+        match self {
+            MetadataField::Title | MetadataField::Subject => XmpShape::Alt,
+            MetadataField::Author => XmpShape::Seq,
+            MetadataField::CreationDate | MetadataField::ModDate => XmpShape::Date,
+            _ => XmpShape::Simple,
+        }
+    }
+}
+
+/// Namespaces of the prefixes written by `synchronize`
+const NAMESPACES: [(&str, &str); 3] = [
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("pdf", "http://ns.adobe.com/pdf/1.3/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+];
+
+/// A field the Info dictionary and XMP disagree on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataMismatch {
+    pub field: MetadataField,
+    /// Value in the Info dictionary, if set
+    pub info: Option<String>,
+    /// Value in XMP, if set
+    pub xmp: Option<String>,
+}
+
+/// Which side wins when synchronizing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SyncPrecedence {
+    /// XMP takes the Info values; fields Info lacks are removed from XMP
+    #[default]
+    InfoWins,
+    /// Info takes the XMP values; fields XMP lacks are removed from Info
+    XmpWins,
+    /// The fields are removed from both
+    ClearBoth,
+}
+
+/// What synchronizing changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Fields written or removed in the Info dictionary
+    pub info_changed: Vec<MetadataField>,
+    /// Fields written or removed in XMP
+    pub xmp_changed: Vec<MetadataField>,
+}
+
+/// Info dictionary values of the compared fields
+pub fn info_values(doc: &lopdf::Document) -> HashMap<MetadataField, String> {
+    let Some(info) = info_dict(doc) else { return HashMap::new() };
+    MetadataField::ALL.iter()
+        .filter_map(|&field| match info.get(field.info_key().as_bytes()) {
+            Ok(Object::String(bytes, _)) => Some((field, decode_pdf_string(bytes))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// XMP values of the compared fields; `None` when the document has no XMP
+pub fn xmp_values(doc: &lopdf::Document) -> Option<HashMap<MetadataField, String>> {
+    let (_, packet) = xmp_packet(doc)?;
+    Some(MetadataField::ALL.iter()
+        .filter_map(|&field| Some((field, read_property(&packet, field)?)))
+        .collect())
+}
+
+/// Fields set differently, or set on one side only, in the Info dictionary and XMP
+///
+/// Documents without XMP have nothing to disagree with.
+pub fn check(doc: &lopdf::Document) -> Vec<MetadataMismatch> {
+    let Some(xmp) = xmp_values(doc) else { return Vec::new() };
+    let info = info_values(doc);
+    MetadataField::ALL.iter()
+        .filter_map(|&field| {
+            let (info, xmp) = (info.get(&field), xmp.get(&field));
+            (!agree(field, info, xmp)).then(|| MetadataMismatch { field, info: info.cloned(), xmp: xmp.cloned() })
+        })
+        .collect()
+}
+
+/// Makes the Info dictionary and XMP agree on every compared field
+///
+/// With `InfoWins`, a document without XMP gets a packet holding the Info
+/// values. Fields already in agreement are left untouched.
+pub fn synchronize(doc: &mut lopdf::Document, precedence: SyncPrecedence) -> Result<SyncReport, lopdf::Error> {
+    let info = info_values(doc);
+    let xmp = xmp_values(doc);
+    let mut report = SyncReport::default();
+
+    let mut info_updates: Vec<(MetadataField, Option<String>)> = Vec::new();
+    let mut xmp_updates: Vec<(MetadataField, Option<String>)> = Vec::new();
+    for field in MetadataField::ALL {
+        let (info_value, xmp_value) = (info.get(&field), xmp.as_ref().and_then(|xmp| xmp.get(&field)));
+        match precedence {
+            SyncPrecedence::InfoWins => {
+                if !agree(field, info_value, xmp_value) {
+                    xmp_updates.push((field, info_value.map(|v| to_xmp(field, v))));
+                }
+            }
+            SyncPrecedence::XmpWins => {
+                // Without XMP there is nothing to take values from
+                if xmp.is_some() && !agree(field, info_value, xmp_value) {
+                    info_updates.push((field, xmp_value.map(|v| to_info(field, v))));
+                }
+            }
+            SyncPrecedence::ClearBoth => {
+                if info_value.is_some() {
+                    info_updates.push((field, None));
+                }
+                if xmp_value.is_some() {
+                    xmp_updates.push((field, None));
+                }
+            }
+        }
+    }
+
+    if !info_updates.is_empty() {
+        let dict = info_dict_mut(doc)?;
+        for (field, value) in info_updates {
+            match value {
+                Some(value) => dict.set(field.info_key(), text_string(&value)),
+                None => {
+                    dict.remove(field.info_key().as_bytes());
+                }
+            }
+            report.info_changed.push(field);
+        }
+    }
+
+    if !xmp_updates.is_empty() {
+        let (id, mut packet) = match xmp_packet(doc) {
+            Some(found) => found,
+            None => (add_xmp_stream(doc)?, EMPTY_PACKET.to_string()),
+        };
+        for (field, value) in xmp_updates {
+            packet = remove_property(&packet, field);
+            if let Some(value) = value {
+                packet = insert_property(&packet, field, &value);
+            }
+            report.xmp_changed.push(field);
+        }
+        let stream = doc.get_object_mut(id)?.as_stream_mut()?;
+        stream.dict.remove(b"Filter");
+        stream.dict.remove(b"DecodeParms");
+        stream.set_content(packet.into_bytes());
+    }
+    Ok(report)
+}
+
+/// XMP properties kept and removed by [`retain_xmp`], by qualified name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmpRetainReport {
+    pub kept: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Removes the XMP properties `keep` rejects, by qualified name like `dc:title`
+///
+/// Properties are the attributes and child elements of each top-level
+/// `rdf:Description`; namespace declarations and `rdf:about` stay. `None`
+/// when the document has no XMP.
+pub fn retain_xmp(doc: &mut lopdf::Document, mut keep: impl FnMut(&str) -> bool) -> Result<Option<XmpRetainReport>, lopdf::Error> {
+    let Some((id, packet)) = xmp_packet(doc) else { return Ok(None) };
+    let mut report = XmpRetainReport::default();
+    let packet = retain_properties(&packet, |name| {
+        let kept = keep(name);
+        if kept {
+            report.kept.push(name.to_string());
+        } else {
+            report.removed.push(name.to_string());
+        }
+        kept
+    });
+    if !report.removed.is_empty() {
+        let stream = doc.get_object_mut(id)?.as_stream_mut()?;
+        stream.dict.remove(b"Filter");
+        stream.dict.remove(b"DecodeParms");
+        stream.set_content(packet.into_bytes());
+    }
+    Ok(Some(report))
+}
+
+/// `packet` with only the description properties `keep` accepts
+fn retain_properties(packet: &str, mut keep: impl FnMut(&str) -> bool) -> String {
+    static DESCRIPTION: OnceLock<Regex> = OnceLock::new();
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let description = DESCRIPTION.get_or_init(|| Regex::new(r"(?s)<rdf:Description(\s[^>]*?)?(/?)>").expect("valid regex"));
+    let attribute = ATTRIBUTE.get_or_init(|| {
+        Regex::new(r#"\s([A-Za-z_][\w.-]*):([A-Za-z_][\w.-]*)\s*=\s*(?:"[^"]*"|'[^']*')"#).expect("valid regex")
+    });
+
+    let mut out = String::with_capacity(packet.len());
+    let mut rest = packet;
+    while let Some(captures) = description.captures(rest) {
+        let whole = captures.get(0).expect("match");
+        out.push_str(&rest[..whole.start()]);
+        let attributes = attribute.replace_all(captures.get(1).map_or("", |m| m.as_str()), |c: &regex::Captures| {
+            let structural = matches!(&c[1], "xmlns" | "rdf" | "xml");
+            if structural || keep(&format!("{}:{}", &c[1], &c[2])) { c[0].to_string() } else { String::new() }
+        });
+        out.push_str(&format!("<rdf:Description{}{}>", attributes, &captures[2]));
+        rest = &rest[whole.end()..];
+        if !captures[2].is_empty() {
+            continue;
+        }
+
+        // Child elements up to the closing tag; anything unexpected is kept as is
+        loop {
+            let body = rest.trim_start();
+            let space = rest.len() - body.len();
+            let length = if body.starts_with("<!--") {
+                body.find("-->").map(|end| (None, end + 3))
+            } else {
+                child_element(body).map(|(name, length)| (Some(name), length))
+            };
+            let Some((name, length)) = length else { break };
+            if name.is_none_or(&mut keep) {
+                out.push_str(&rest[..space + length]);
+            }
+            rest = &rest[space + length..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Qualified name and length of the property element `body` starts with
+///
+/// Property values do not nest elements of their own name, so the first
+/// closing tag ends the element.
+fn child_element(body: &str) -> Option<(&str, usize)> {
+    let tag = body.strip_prefix('<')?;
+    let name = &tag[..tag.find(|c: char| !(c.is_alphanumeric() || matches!(c, ':' | '_' | '.' | '-')))?];
+    if !name.contains(':') || name == "rdf:Description" {
+        return None;
+    }
+    let start_tag = body.find('>')? + 1;
+    if body[..start_tag].ends_with("/>") {
+        return Some((name, start_tag));
+    }
+    let close = format!("</{}>", name);
+    Some((name, start_tag + body[start_tag..].find(&close)? + close.len()))
+}
+
+/// Whether the Info and XMP values of `field`, either possibly unset, agree
+fn agree(field: MetadataField, info: Option<&String>, xmp: Option<&String>) -> bool {
+    match (info, xmp) {
+        (Some(info), Some(xmp)) => same_value(field, info, xmp),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Whether an Info and an XMP value say the same thing
+fn same_value(field: MetadataField, info: &str, xmp: &str) -> bool {
+    if field.shape() == XmpShape::Date {
+        if let (Ok(a), Ok(b)) = (PdfDate::parse(info), PdfDate::from_xmp(xmp)) {
+            return a.same_instant(&b);
+        }
+    }
+    info.trim() == xmp.trim()
+}
+
+/// Info value as written to XMP
+fn to_xmp(field: MetadataField, value: &str) -> String {
+    match (field.shape(), PdfDate::parse(value)) {
+        (XmpShape::Date, Ok(date)) => date.to_xmp_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// XMP value as written to the Info dictionary
+fn to_info(field: MetadataField, value: &str) -> String {
+    match (field.shape(), PdfDate::from_xmp(value)) {
+        (XmpShape::Date, Ok(date)) => date.to_pdf_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Info date fields that are not valid PDF dates, with their values
+pub fn malformed_dates(doc: &lopdf::Document) -> Vec<(MetadataField, String)> {
+    let info = info_values(doc);
+    [MetadataField::CreationDate, MetadataField::ModDate].into_iter()
+        .filter_map(|field| Some((field, info.get(&field)?.clone())))
+        .filter(|(_, value)| PdfDate::parse_strict(value).is_err())
+        .collect()
+}
+
+fn info_dict(doc: &lopdf::Document) -> Option<&Dictionary> {
+    match doc.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => doc.get_dictionary(*id).ok(),
+        Object::Dictionary(dict) => Some(dict),
+        _ => None,
+    }
+}
+
+/// The Info dictionary, created when missing
+fn info_dict_mut(doc: &mut lopdf::Document) -> Result<&mut Dictionary, lopdf::Error> {
+    let id = match doc.trailer.get(b"Info") {
+        Ok(Object::Reference(id)) => Some(*id),
+        Ok(Object::Dictionary(_)) => None,
+        _ => {
+            let id = doc.add_object(Dictionary::new());
+            doc.trailer.set("Info", id);
+            Some(id)
+        }
+    };
+    match id {
+        Some(id) => doc.get_object_mut(id)?.as_dict_mut(),
+        None => doc.trailer.get_mut(b"Info")?.as_dict_mut(),
+    }
+}
+
+/// Catalog XMP stream and its text
+fn xmp_packet(doc: &lopdf::Document) -> Option<(ObjectId, String)> {
+    let root = doc.catalog().ok()?;
+    let id = root.get(b"Metadata").ok()?.as_reference().ok()?;
+    let stream = doc.get_object(id).ok()?.as_stream().ok()?;
+    let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+    Some((id, String::from_utf8_lossy(&content).into_owned()))
+}
+
+/// Empty packet that properties are inserted into
+const EMPTY_PACKET: &str = concat!(
+    "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+    "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+    "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+    "<rdf:Description rdf:about=\"\">\n",
+    "</rdf:Description>\n",
+    "</rdf:RDF>\n",
+    "</x:xmpmeta>\n",
+    "<?xpacket end=\"w\"?>",
+);
+
+fn add_xmp_stream(doc: &mut lopdf::Document) -> Result<ObjectId, lopdf::Error> {
+    let id = doc.add_object(lopdf::Stream::new(dictionary! { "Type" => "Metadata", "Subtype" => "XML" }, Vec::new()));
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    doc.get_object_mut(root)?.as_dict_mut()?.set("Metadata", id);
+    Ok(id)
+}
+
+fn element_pattern(name: &str) -> Regex {
+    Regex::new(&format!(r"(?s)<{0}(?:\s[^>]*)?>(.*?)</{0}>|<{0}\s*/>", regex::escape(name))).expect("valid regex")
+}
+
+fn attribute_pattern(name: &str) -> Regex {
+    Regex::new(&format!(r#"\s{}\s*=\s*(?:"([^"]*)"|'([^']*)')"#, regex::escape(name))).expect("valid regex")
+}
+
+fn list_item() -> &'static Regex {
+    static ITEM: OnceLock<Regex> = OnceLock::new();
+    ITEM.get_or_init(|| Regex::new(r"(?s)<rdf:li(?:\s[^>]*)?>(.*?)</rdf:li>").expect("valid regex"))
+}
+
+/// Value of `field` in an XMP packet
+fn read_property(packet: &str, field: MetadataField) -> Option<String> {
+    for name in field.xmp_names() {
+        if let Some(captures) = element_pattern(name).captures(packet) {
+            let Some(inner) = captures.get(1) else { return Some(String::new()) };
+            let items: Vec<String> = list_item().captures_iter(inner.as_str()).map(|c| unescape(&c[1])).collect();
+            return Some(match (field.shape(), items.is_empty()) {
+                (_, true) => unescape(inner.as_str().trim()),
+                (XmpShape::Seq, false) => items.join("; "),
+                (_, false) => items.into_iter().next().unwrap_or_default(),
+            });
+        }
+        if let Some(captures) = attribute_pattern(name).captures(packet) {
+            let value = captures.get(1).or_else(|| captures.get(2)).map_or("", |m| m.as_str());
+            return Some(unescape(value));
+        }
+    }
+    None
+}
+
+/// `packet` without `field`, in element or attribute form
+fn remove_property(packet: &str, field: MetadataField) -> String {
+    field.xmp_names().iter().fold(packet.to_string(), |packet, name| {
+        let packet = element_pattern(name).replace_all(&packet, "").into_owned();
+        attribute_pattern(name).replace_all(&packet, "").into_owned()
+    })
+}
+
+/// `packet` with `field` set to `value` in its first description
+fn insert_property(packet: &str, field: MetadataField, value: &str) -> String {
+    static DESCRIPTION: OnceLock<Regex> = OnceLock::new();
+    let description = DESCRIPTION.get_or_init(|| Regex::new(r"(?s)<rdf:Description(\s[^>]*?)?(/?)>").expect("valid regex"));
+
+    let name = field.xmp_names()[0];
+    let prefix = name.split(':').next().unwrap_or_default();
+    let escaped = escape(value);
+    let element = match field.shape() {
+        XmpShape::Alt => format!("<{0}><rdf:Alt><rdf:li xml:lang=\"x-default\">{1}</rdf:li></rdf:Alt></{0}>", name, escaped),
+        XmpShape::Seq => format!("<{0}><rdf:Seq><rdf:li>{1}</rdf:li></rdf:Seq></{0}>", name, escaped),
+        XmpShape::Simple | XmpShape::Date => format!("<{0}>{1}</{0}>", name, escaped),
+    };
+
+    let Some(captures) = description.captures(packet) else {
+        // No description to extend; start from an empty packet holding just this property
+        return insert_property(EMPTY_PACKET, field, value);
+    };
+    let whole = captures.get(0).expect("match");
+    let mut attributes = captures.get(1).map_or("", |m| m.as_str()).to_string();
+    let declaration = format!("xmlns:{}=", prefix);
+    if !packet.contains(&declaration) {
+        let uri = NAMESPACES.iter().find(|(p, _)| *p == prefix).map_or("", |(_, uri)| *uri);
+        attributes.push_str(&format!(" xmlns:{}=\"{}\"", prefix, uri));
+    }
+    let self_closing = !captures[2].is_empty();
+    let replacement = if self_closing {
+        format!("<rdf:Description{}>\n{}\n</rdf:Description>", attributes, element)
+    } else {
+        format!("<rdf:Description{}>\n{}", attributes, element)
+    };
+    format!("{}{}{}", &packet[..whole.start()], replacement, &packet[whole.end()..])
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn unescape(text: &str) -> String {
+    static ENTITY: OnceLock<Regex> = OnceLock::new();
+    let entity = ENTITY.get_or_init(|| Regex::new(r"&(#x[0-9a-fA-F]+|#[0-9]+|amp|lt|gt|quot|apos);").expect("valid regex"));
+    entity.replace_all(text, |captures: &regex::Captures| {
+        let name = &captures[1];
+        let code = match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => name.strip_prefix("#x").map_or_else(|| name[1..].parse().ok(), |hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(char::from_u32),
+        };
+        code.map_or_else(|| captures[0].to_string(), String::from)
+    }).into_owned()
+}
+
+/// Reports fields the Info dictionary and XMP disagree on
+#[derive(Debug, Clone, Default)]
+pub struct InfoXmpAnalyzer;
+
+impl InfoXmpAnalyzer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        let mismatches = check(&doc.structure).into_iter().map(|mismatch| {
+            let key = mismatch.field.info_key();
+            let (description, risk_level) = match (&mismatch.info, &mismatch.xmp) {
+                (Some(info), Some(xmp)) => (format!("{} differs: Info {:?}, XMP {:?}", key, info, xmp), RiskLevel::Medium),
+                // Values left behind in XMP are what Info cleaning missed
+                (None, Some(xmp)) => (format!("{} only in XMP: {:?}", key, xmp), RiskLevel::Medium),
+                (Some(info), None) => (format!("{} only in Info: {:?}", key, info), RiskLevel::Low),
+                (None, None) => unreachable!("mismatches have a value on one side"),
+            };
+            let mut metadata = HashMap::new();
+            metadata.insert("field".to_string(), key.to_string());
+            metadata.extend(mismatch.info.map(|v| ("info".to_string(), v)));
+            metadata.extend(mismatch.xmp.map(|v| ("xmp".to_string(), v)));
+            ForensicArtifact {
+                id: uuid::Uuid::new_v4().to_string(),
+                artifact_type: ArtifactType::Metadata,
+                location: format!("/Info/{}", key),
+                description,
+                risk_level,
+                remediation: "Synchronize the Info dictionary and XMP, or clear the field from both".into(),
+                metadata,
+                ..Default::default()
+            }
+        });
+        // Malformed dates point at the tool that wrote them, and break strict readers
+        let malformed = malformed_dates(&doc.structure).into_iter().map(|(field, value)| {
+            let key = field.info_key();
+            let mut metadata = HashMap::new();
+            metadata.insert("field".to_string(), key.to_string());
+            metadata.insert("info".to_string(), value.clone());
+            ForensicArtifact {
+                id: uuid::Uuid::new_v4().to_string(),
+                artifact_type: ArtifactType::Metadata,
+                location: format!("/Info/{}", key),
+                description: format!("{} is not a valid PDF date: {:?}", key, value),
+                risk_level: RiskLevel::Low,
+                remediation: "Rewrite the date in D:YYYYMMDDHHmmSSOHH'mm' form, or remove it".into(),
+                metadata,
+                ..Default::default()
+            }
+        });
+        mismatches.chain(malformed).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(info: Dictionary, xmp: Option<&str>) -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let info = doc.add_object(info);
+        let mut catalog = dictionary! { "Type" => "Catalog" };
+        if let Some(xmp) = xmp {
+            let stream = doc.add_object(lopdf::Stream::new(dictionary! { "Type" => "Metadata" }, xmp.as_bytes().to_vec()));
+            catalog.set("Metadata", stream);
+        }
+        let catalog = doc.add_object(catalog);
+        doc.trailer.set("Root", catalog);
+        doc.trailer.set("Info", info);
+        doc
+    }
+
+    const XMP: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+ xmp:CreateDate="2024-01-02T04:04:05+01:00" xmp:CreatorTool="Writer">
+<dc:title><rdf:Alt><rdf:li xml:lang="x-default">Q3 &amp; Q4 plan</rdf:li></rdf:Alt></dc:title>
+<dc:creator><rdf:Seq><rdf:li>Alice</rdf:li><rdf:li>Bob</rdf:li></rdf:Seq></dc:creator>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#;
+
+    #[test]
+    fn test_check_reports_disagreements() {
+        let doc = document(dictionary! {
+            "Title" => Object::string_literal("Q3 & Q4 plan"),
+            "CreationDate" => Object::string_literal("D:20240102030405Z"),
+            "Producer" => Object::string_literal("Engine 2"),
+        }, Some(XMP));
+
+        let fields: Vec<_> = check(&doc).into_iter().map(|m| m.field).collect();
+        // Title and the creation instant agree; author, creator tool and producer are one-sided
+        assert_eq!(fields, vec![MetadataField::Author, MetadataField::Creator, MetadataField::Producer]);
+        assert_eq!(xmp_values(&doc).unwrap()[&MetadataField::Author], "Alice; Bob");
+        assert!(malformed_dates(&doc).is_empty());
+    }
+
+    #[test]
+    fn test_info_wins_rewrites_xmp() {
+        let mut doc = document(dictionary! {
+            "Title" => Object::string_literal("Final <v2>"),
+            "ModDate" => Object::string_literal("D:20240301120000+02'00'"),
+        }, Some(XMP));
+
+        let report = synchronize(&mut doc, SyncPrecedence::InfoWins).unwrap();
+        assert!(report.info_changed.is_empty());
+        assert!(check(&doc).is_empty());
+        let xmp = xmp_values(&doc).unwrap();
+        assert_eq!(xmp[&MetadataField::Title], "Final <v2>");
+        assert_eq!(xmp[&MetadataField::ModDate], "2024-03-01T12:00:00+02:00");
+        assert!(!xmp.contains_key(&MetadataField::Author));
+    }
+
+    #[test]
+    fn test_xmp_wins_and_clear_both() {
+        let mut doc = document(dictionary! { "Author" => Object::string_literal("Mallory") }, Some(XMP));
+        synchronize(&mut doc, SyncPrecedence::XmpWins).unwrap();
+        let info = info_values(&doc);
+        assert_eq!(info[&MetadataField::Author], "Alice; Bob");
+        assert_eq!(info[&MetadataField::CreationDate], "D:20240102040405+01'00'");
+        assert!(check(&doc).is_empty());
+
+        synchronize(&mut doc, SyncPrecedence::ClearBoth).unwrap();
+        assert!(info_values(&doc).is_empty());
+        assert!(xmp_values(&doc).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_info_wins_creates_missing_xmp() {
+        let mut doc = document(dictionary! {
+            "Title" => Object::string_literal("Report"),
+            "ModDate" => Object::string_literal("2024-03-01T12:00:00Z"),
+        }, None);
+        assert_eq!(malformed_dates(&doc), vec![(MetadataField::ModDate, "2024-03-01T12:00:00Z".to_string())]);
+        assert!(check(&doc).is_empty());
+        synchronize(&mut doc, SyncPrecedence::InfoWins).unwrap();
+        let xmp = xmp_values(&doc).unwrap();
+        assert_eq!(xmp[&MetadataField::Title], "Report");
+        assert_eq!(xmp[&MetadataField::ModDate], "2024-03-01T12:00:00Z");
+    }
+}
@@ -295,6 +295,7 @@ impl Analyzer for MetadataAnalyzer {
                 operation_count: 1,
             },
             processing_time: duration,
+            structural_anomaly: None,
         })
     }
 
@@ -15,15 +15,31 @@ use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
 use tracing::{info, warn, error, debug, instrument};
 
+use crate::antiforensics::ForensicArtifact;
+
 // Re-export submodules
 pub mod pdf_analyzer;
 pub mod metadata_analyzer;
 pub mod content_analyzer;
+pub mod font_analyzer;
+pub mod correlation;
+pub mod object_numbering;
+pub mod trailer;
+pub mod info_xmp;
+pub mod baseline;
+pub mod xref_integrity;
 
 pub use self::{
     pdf_analyzer::PdfAnalyzer,
     metadata_analyzer::MetadataAnalyzer,
     content_analyzer::ContentAnalyzer,
+    font_analyzer::{CmapAnomaly, FontAnalyzer, FontAnalyzerConfig},
+    correlation::{CampaignReport, CorrelationAnalyzer, CorrelationConfig},
+    object_numbering::{NumberingAnalysis, NumberingConfig, ObjectNumberingAnalyzer},
+    trailer::{IdIssue, TrailerAnalysis, TrailerAnalyzer},
+    info_xmp::{InfoXmpAnalyzer, MetadataField, MetadataMismatch, SyncPrecedence, SyncReport},
+    baseline::{AnomalyScore, BaselineScoreLine, FeatureDeviation, StructuralBaseline, StructuralProfile},
+    xref_integrity::{XrefIntegrity, XrefIntegrityAnalyzer, XrefIssue},
 };
 
 /// Custom error types for the analyzer module
@@ -60,6 +76,18 @@ pub struct AnalyzerConfig {
     pub timeout: Duration,
     pub max_file_size: usize,
     pub resource_limits: ResourceLimits,
+    /// Artifacts below this confidence are dropped or annotated
+    pub min_confidence: f64,
+    pub low_confidence: LowConfidenceAction,
+}
+
+/// Treatment of artifacts below `AnalyzerConfig::min_confidence`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LowConfidenceAction {
+    /// Remove them from results
+    Drop,
+    /// Keep them, marked with a `low_confidence` metadata entry
+    Annotate,
 }
 
 /// Resource limits configuration
@@ -78,6 +106,8 @@ pub struct AnalysisResult {
     pub risks: Vec<RiskFinding>,
     pub stats: AnalysisStats,
     pub processing_time: Duration,
+    /// Deviation from a learned structural baseline, when the analyzer has one
+    pub structural_anomaly: Option<AnomalyScore>,
 }
 
 /// Statistical information about the analysis
@@ -164,7 +194,7 @@ pub trait Analyzer: Send + Sync {
 impl Default for AnalyzerConfig {
     fn default() -> Self {
         Self {
-            max_concurrent_ops: 4,
+            max_concurrent_ops: crate::concurrency::ConcurrencyConfig::default().limits().scan,
             batch_size: 1024 * 1024, // 1MB
             cache_size: 100,
             timeout: Duration::from_secs(30),
@@ -174,6 +204,27 @@ impl Default for AnalyzerConfig {
                 max_threads: 8,
                 max_processing_time: Duration::from_secs(300), // 5 minutes
             },
+            min_confidence: 0.5,
+            low_confidence: LowConfidenceAction::Annotate,
+        }
+    }
+}
+
+impl AnalyzerConfig {
+    /// Applies the minimum-confidence policy to a set of artifacts
+    pub fn apply_min_confidence(&self, artifacts: Vec<ForensicArtifact>) -> Vec<ForensicArtifact> {
+        match self.low_confidence {
+            LowConfidenceAction::Drop => artifacts.into_iter()
+                .filter(|a| a.confidence >= self.min_confidence)
+                .collect(),
+            LowConfidenceAction::Annotate => artifacts.into_iter()
+                .map(|mut a| {
+                    if a.confidence < self.min_confidence {
+                        a.metadata.insert("low_confidence".into(), format!("{:.2}", a.confidence));
+                    }
+                    a
+                })
+                .collect(),
         }
     }
 }
@@ -255,6 +306,31 @@ mod tests {
         assert!(analyzer.validate_limits(&valid_data).await.is_ok());
     }
 
+    #[test]
+    fn test_min_confidence_policy() {
+        let artifacts = vec![
+            ForensicArtifact { id: "weak".into(), confidence: 0.3, ..Default::default() },
+            ForensicArtifact { id: "strong".into(), confidence: 0.9, ..Default::default() },
+        ];
+
+        let annotated = AnalyzerConfig::default().apply_min_confidence(artifacts.clone());
+        assert_eq!(annotated.len(), 2);
+        assert_eq!(annotated[0].metadata["low_confidence"], "0.30");
+        assert!(!annotated[1].metadata.contains_key("low_confidence"));
+
+        let config = AnalyzerConfig { low_confidence: LowConfidenceAction::Drop, ..Default::default() };
+        let kept = config.apply_min_confidence(artifacts);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].id, "strong");
+    }
+
+    #[test]
+    fn test_corroborating_signals_raise_confidence() {
+        let combined = ForensicArtifact::combine_confidence([0.6, 0.5]);
+        assert!((combined - 0.8).abs() < 1e-9);
+        assert_eq!(ForensicArtifact::combine_confidence([]), 0.0);
+    }
+
     #[tokio::test]
     async fn test_stats_update() {
         let analyzer = BaseAnalyzer::new(AnalyzerConfig::default());
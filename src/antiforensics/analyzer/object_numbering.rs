@@ -0,0 +1,222 @@
+//! Object and generation number analysis
+//! Author: kartik4091
+//! Created: 2025-06-04 20:31:12 UTC
+//! Writers number objects densely from 1 and leave generation numbers at
+//! 0; only incremental updates that reuse a freed number bump them, and
+//! then by one. Generation numbers far above that, numbering gaps out of
+//! proportion to the object count, or gap and generation sequences that
+//! spell out printable text are channels for hidden data or a producer
+//! fingerprint.
+
+use std::collections::BTreeMap;
+use lopdf::ObjectId;
+use tracing::instrument;
+
+use crate::antiforensics::{types::Document, utils::FindingTemplate, ArtifactType, ForensicArtifact, RiskLevel};
+
+const FINDING: FindingTemplate = FindingTemplate {
+    artifact_type: ArtifactType::Structure,
+    location_prefix: "",
+    remediation: "Renumber objects densely with generation 0",
+};
+
+/// Thresholds for numbering anomalies
+#[derive(Debug, Clone)]
+pub struct NumberingConfig {
+    /// Generation numbers above this are never produced by normal updates
+    pub max_normal_generation: u16,
+    /// Share of unused numbers in `1..=max` above which numbering is reported
+    pub max_gap_ratio: f64,
+    /// Printable bytes in a row, taken from gap or generation sequences,
+    /// that count as encoded text
+    pub min_text_run: usize,
+}
+
+impl Default for NumberingConfig {
+    fn default() -> Self {
+        Self {
+            max_normal_generation: 10,
+            max_gap_ratio: 0.25,
+            min_text_run: 4,
+        }
+    }
+}
+
+/// Numbering statistics of one document
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NumberingAnalysis {
+    pub objects: usize,
+    pub max_number: u32,
+    /// Unused numbers in `1..=max_number`
+    pub missing: usize,
+    /// Objects by generation number, generation 0 excluded
+    pub generations: BTreeMap<u16, usize>,
+    /// Objects whose generation exceeds the configured maximum
+    pub abnormal_generations: Vec<ObjectId>,
+    /// Printable text read from gap lengths or generation numbers in object order
+    pub encoded_text: Option<String>,
+}
+
+impl NumberingAnalysis {
+    pub fn gap_ratio(&self) -> f64 {
+        if self.max_number == 0 { 0.0 } else { self.missing as f64 / self.max_number as f64 }
+    }
+
+    /// Whether the numbering is already dense and generation-free
+    pub fn is_canonical(&self) -> bool {
+        self.missing == 0 && self.generations.is_empty()
+    }
+}
+
+/// Measures the numbering of `doc`
+pub fn analyze(doc: &lopdf::Document, config: &NumberingConfig) -> NumberingAnalysis {
+    let ids: Vec<ObjectId> = doc.objects.keys().copied().collect();
+    let max_number = ids.iter().map(|id| id.0).max().unwrap_or(0);
+
+    let mut generations = BTreeMap::new();
+    for id in ids.iter().filter(|id| id.1 > 0) {
+        *generations.entry(id.1).or_insert(0) += 1;
+    }
+
+    // Several generations of one number cannot coexist in a parsed file,
+    // so the distinct numbers are what the gaps are measured against
+    let mut numbers: Vec<u32> = ids.iter().map(|id| id.0).collect();
+    numbers.dedup();
+    let gaps: Vec<u32> = std::iter::once(0).chain(numbers.iter().copied())
+        .zip(numbers.iter())
+        .map(|(previous, &current)| current.saturating_sub(previous).saturating_sub(1))
+        .filter(|&gap| gap > 0)
+        .collect();
+    let generation_values: Vec<u32> = ids.iter().map(|id| id.1 as u32).filter(|&g| g > 0).collect();
+
+    NumberingAnalysis {
+        objects: ids.len(),
+        max_number,
+        missing: (max_number as usize).saturating_sub(numbers.len()),
+        abnormal_generations: ids.iter().copied().filter(|id| id.1 > config.max_normal_generation).collect(),
+        encoded_text: printable_run(&gaps, config.min_text_run)
+            .or_else(|| printable_run(&generation_values, config.min_text_run)),
+        generations,
+    }
+}
+
+/// Longest run of values that are all printable ASCII, as text
+fn printable_run(values: &[u32], min_len: usize) -> Option<String> {
+    values.split(|&v| !(0x20..0x7f).contains(&v))
+        .filter(|run| run.len() >= min_len)
+        .max_by_key(|run| run.len())
+        .map(|run| run.iter().map(|&v| v as u8 as char).collect())
+}
+
+/// Reports numbering patterns normal producers do not write
+#[derive(Debug, Clone, Default)]
+pub struct ObjectNumberingAnalyzer {
+    config: NumberingConfig,
+}
+
+impl ObjectNumberingAnalyzer {
+    pub fn new(config: NumberingConfig) -> Self {
+        Self { config }
+    }
+
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        let analysis = analyze(&doc.structure, &self.config);
+        let mut artifacts = Vec::new();
+
+        if !analysis.abnormal_generations.is_empty() {
+            let ids: Vec<String> = analysis.abnormal_generations.iter().take(20).map(|(n, g)| format!("{} {} R", n, g)).collect();
+            artifacts.push(FINDING.artifact(
+                "xref",
+                format!(
+                    "{} object(s) with generation numbers above {}",
+                    analysis.abnormal_generations.len(),
+                    self.config.max_normal_generation,
+                ),
+                RiskLevel::Medium,
+                [("objects", ids.join(","))],
+            ));
+        }
+
+        if analysis.max_number > 16 && analysis.gap_ratio() > self.config.max_gap_ratio {
+            artifacts.push(FINDING.artifact(
+                "xref",
+                format!(
+                    "{} of {} object numbers unused ({:.0}%)",
+                    analysis.missing,
+                    analysis.max_number,
+                    analysis.gap_ratio() * 100.0,
+                ),
+                RiskLevel::Low,
+                [("missing", analysis.missing.to_string())],
+            ));
+        }
+
+        if let Some(text) = &analysis.encoded_text {
+            artifacts.push(FINDING.artifact(
+                "xref",
+                format!("Object numbering gaps or generations spell out text ({} chars)", text.chars().count()),
+                RiskLevel::High,
+                [("decoded", text.clone())],
+            ));
+        }
+
+        artifacts
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lopdf::{dictionary, Object};
+
+    /// Document whose generation numbers spell "LEAK"
+    pub(crate) fn covert_numbering() -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let pages = (3, u16::from(b'L'));
+        let page = (5, u16::from(b'E'));
+        let content = (8, u16::from(b'A'));
+        let info = (12, u16::from(b'K'));
+        doc.objects.insert(content, Object::Stream(lopdf::Stream::new(dictionary! {}, b"0 0 m".to_vec())));
+        doc.objects.insert(page, Object::Dictionary(dictionary! {
+            "Type" => "Page", "Parent" => pages, "Contents" => content,
+        }));
+        doc.objects.insert(pages, Object::Dictionary(dictionary! {
+            "Type" => "Pages", "Kids" => vec![Object::Reference(page)], "Count" => 1,
+        }));
+        doc.objects.insert((1, 0), Object::Dictionary(dictionary! { "Type" => "Catalog", "Pages" => pages }));
+        doc.objects.insert(info, Object::Dictionary(dictionary! { "Producer" => Object::string_literal("x") }));
+        doc.trailer.set("Root", (1, 0));
+        doc.trailer.set("Info", info);
+        doc.max_id = 12;
+        doc
+    }
+
+    #[test]
+    fn test_covert_numbering_detected() {
+        let analysis = analyze(&covert_numbering(), &NumberingConfig::default());
+        assert_eq!(analysis.objects, 5);
+        assert_eq!(analysis.missing, 7);
+        assert_eq!(analysis.abnormal_generations.len(), 4);
+        assert_eq!(analysis.encoded_text.as_deref(), Some("LEAK"));
+    }
+
+    #[test]
+    fn test_dense_numbering_is_canonical() {
+        let mut doc = lopdf::Document::with_version("1.7");
+        for _ in 0..5 {
+            doc.add_object(dictionary! {});
+        }
+        let analysis = analyze(&doc, &NumberingConfig::default());
+        assert!(analysis.is_canonical());
+        assert_eq!(analysis.encoded_text, None);
+
+        // Object 0 and several generations of one number, as crafted input may have
+        doc.objects.insert((0, 0), Object::Null);
+        doc.objects.insert((2, 1), Object::Null);
+        doc.objects.insert((2, 7), Object::Null);
+        let analysis = analyze(&doc, &NumberingConfig::default());
+        assert_eq!(analysis.missing, 0);
+        assert_eq!(analysis.generations.get(&1), Some(&1));
+    }
+}
@@ -4,6 +4,7 @@
 
 use super::*;
 use crate::utils::{metrics::Metrics, cache::Cache};
+use crate::antiforensics::patterns::canonical;
 use std::{
     sync::Arc,
     time::{Duration, Instant},
@@ -18,6 +19,8 @@ pub struct PdfAnalyzer {
     metrics: Arc<Metrics>,
     state: Arc<RwLock<PdfAnalyzerState>>,
     cache: Arc<Cache<Vec<u8>>>,
+    /// Corpus statistics documents are scored against, if any
+    baseline: Option<Arc<StructuralBaseline>>,
 }
 
 #[derive(Debug)]
@@ -50,6 +53,25 @@ impl PdfAnalyzer {
                 stats: PdfAnalyzerStats::default(),
             })),
             cache: Arc::new(Cache::new(Duration::from_secs(3600))), // 1 hour cache
+            baseline: None,
+        }
+    }
+
+    /// Scores every analyzed document against `baseline`
+    pub fn with_baseline(mut self, baseline: Arc<StructuralBaseline>) -> Self {
+        self.baseline = Some(baseline);
+        self
+    }
+
+    /// Structural deviation from the baseline; `None` without one or when the document does not parse
+    fn score_structure(&self, data: &[u8]) -> Option<AnomalyScore> {
+        let baseline = self.baseline.as_ref()?;
+        match super::xref_integrity::load_checked(data) {
+            Ok(doc) => Some(baseline.score(&StructuralProfile::of(&doc))),
+            Err(e) => {
+                debug!("No structural score, document does not parse: {}", e);
+                None
+            }
         }
     }
 
@@ -141,17 +163,20 @@ impl PdfAnalyzer {
 
     /// Checks for JavaScript content
     async fn check_for_javascript(&self, data: &[u8]) -> Result<bool> {
-        // Implementation for JavaScript detection
-        Ok(data.windows(10).any(|w| w == b"/JavaScript"))
+        Ok(has_name(data, &[b"JavaScript", b"JS"]))
     }
 
     /// Checks for encryption
     async fn check_for_encryption(&self, data: &[u8]) -> Result<bool> {
-        // Implementation for encryption detection
-        Ok(data.windows(10).any(|w| w == b"/Encrypt"))
+        Ok(has_name(data, &[b"Encrypt"]))
     }
 }
 
+/// Whether any of `names` appears in `data`, `#xx` escapes decoded
+fn has_name(data: &[u8], names: &[&[u8]]) -> bool {
+    canonical::raw_names(data).any(|(_, raw)| names.contains(&&*canonical::decode_name(raw)))
+}
+
 #[async_trait]
 impl Analyzer for PdfAnalyzer {
     #[instrument(skip(self, data))]
@@ -168,6 +193,7 @@ impl Analyzer for PdfAnalyzer {
         // Analyze metadata and content
         let metadata = self.analyze_metadata(data).await?;
         let risks = self.analyze_content(data).await?;
+        let structural_anomaly = self.score_structure(data);
 
         // Update statistics
         let duration = start.elapsed();
@@ -197,6 +223,7 @@ impl Analyzer for PdfAnalyzer {
                 operation_count: 1,
             },
             processing_time: duration,
+            structural_anomaly,
         })
     }
 
@@ -276,6 +303,9 @@ mod tests {
         let risks = analyzer.analyze_content(data).await.unwrap();
         assert!(!risks.is_empty());
         assert_eq!(risks[0].severity, RiskSeverity::High);
+
+        let obfuscated = analyzer.analyze_content(b"%PDF-1.7\n<< /S /J#61vaScript >>").await.unwrap();
+        assert_eq!(obfuscated.len(), 1);
     }
 
     #[tokio::test]
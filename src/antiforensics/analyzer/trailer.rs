@@ -0,0 +1,180 @@
+//! Trailer and document ID analysis
+//! Author: kartik4091
+//! Created: 2025-06-04 20:58:40 UTC
+//! The trailer is rarely looked at, which makes it a convenient place for
+//! private keys and for document IDs that carry text, counters or a
+//! producer fingerprint instead of a hash. Writers derive the ID from an
+//! MD5 or similar digest, so a healthy ID is 16 or so bytes that look random.
+
+use lopdf::Object;
+use tracing::instrument;
+
+use crate::antiforensics::{
+    types::Document,
+    utils::{entropy, FindingTemplate},
+    ArtifactType,
+    ForensicArtifact,
+    RiskLevel,
+};
+
+/// Keys the specification defines for trailers and cross-reference streams
+pub const STANDARD_TRAILER_KEYS: [&[u8]; 13] = [
+    b"Size", b"Prev", b"Root", b"Encrypt", b"Info", b"ID", b"XRefStm",
+    b"Type", b"W", b"Index", b"Filter", b"DecodeParms", b"Length",
+];
+
+/// Bits of entropy per byte below which an ID part is reported
+const MIN_ID_ENTROPY: f64 = 2.5;
+
+const NONSTANDARD_KEYS: FindingTemplate = FindingTemplate {
+    artifact_type: ArtifactType::Metadata,
+    location_prefix: "",
+    remediation: "Remove keys the specification does not define",
+};
+
+const SUSPICIOUS_ID: FindingTemplate = FindingTemplate {
+    artifact_type: ArtifactType::Metadata,
+    location_prefix: "",
+    remediation: "Regenerate the document ID",
+};
+
+/// Something unusual about one part of the /ID array
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdIssue {
+    /// Not a byte string
+    NotAString,
+    /// Digests are 16 to 32 bytes; anything else was not produced by hashing
+    UnusualLength(usize),
+    /// Entirely printable ASCII, e.g. a name, a path or a UUID
+    PrintableText(String),
+    /// Too few distinct byte values for a digest
+    LowEntropy(f64),
+}
+
+/// Findings in a document's trailer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrailerAnalysis {
+    /// Trailer keys outside [`STANDARD_TRAILER_KEYS`]
+    pub nonstandard_keys: Vec<String>,
+    /// Raw /ID parts, empty for parts that are not strings
+    pub ids: Vec<Vec<u8>>,
+    /// Issues by /ID part index
+    pub id_issues: Vec<(usize, IdIssue)>,
+}
+
+/// Analyzes the trailer of `doc`
+pub fn analyze(doc: &lopdf::Document) -> TrailerAnalysis {
+    let nonstandard_keys = doc.trailer.iter()
+        .map(|(key, _)| key)
+        .filter(|key| !STANDARD_TRAILER_KEYS.contains(&key.as_slice()))
+        .map(|key| String::from_utf8_lossy(key).into_owned())
+        .collect();
+
+    let mut analysis = TrailerAnalysis { nonstandard_keys, ..Default::default() };
+    let Ok(Object::Array(parts)) = doc.trailer.get(b"ID") else { return analysis };
+
+    for (index, part) in parts.iter().enumerate() {
+        let Object::String(bytes, _) = part else {
+            analysis.ids.push(Vec::new());
+            analysis.id_issues.push((index, IdIssue::NotAString));
+            continue;
+        };
+        analysis.ids.push(bytes.clone());
+        analysis.id_issues.extend(id_issues(bytes).into_iter().map(|issue| (index, issue)));
+    }
+    analysis
+}
+
+fn id_issues(id: &[u8]) -> Vec<IdIssue> {
+    let mut issues = Vec::new();
+    if !(16..=32).contains(&id.len()) {
+        issues.push(IdIssue::UnusualLength(id.len()));
+    }
+    if !id.is_empty() && id.iter().all(|b| (0x20..0x7f).contains(b)) {
+        issues.push(IdIssue::PrintableText(String::from_utf8_lossy(id).into_owned()));
+    }
+    let entropy = entropy(id);
+    if id.len() >= 8 && entropy < MIN_ID_ENTROPY {
+        issues.push(IdIssue::LowEntropy(entropy));
+    }
+    issues
+}
+
+/// Reports private trailer keys and document IDs that are not digests
+#[derive(Debug, Clone, Default)]
+pub struct TrailerAnalyzer;
+
+impl TrailerAnalyzer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        let analysis = analyze(&doc.structure);
+        let mut artifacts = Vec::new();
+
+        if !analysis.nonstandard_keys.is_empty() {
+            artifacts.push(NONSTANDARD_KEYS.artifact(
+                "/Trailer",
+                format!("Non-standard trailer keys: {}", analysis.nonstandard_keys.join(", ")),
+                RiskLevel::Medium,
+                [("keys", analysis.nonstandard_keys.join(","))],
+            ));
+        }
+
+        for (index, issue) in &analysis.id_issues {
+            let (description, risk_level) = match issue {
+                IdIssue::NotAString => ("Document ID part is not a string".to_string(), RiskLevel::Low),
+                IdIssue::UnusualLength(len) => (format!("Document ID part is {} bytes, not a digest", len), RiskLevel::Low),
+                IdIssue::PrintableText(text) => (format!("Document ID part is readable text: {:?}", text), RiskLevel::High),
+                IdIssue::LowEntropy(bits) => (format!("Document ID part has low entropy ({:.2} bits/byte)", bits), RiskLevel::Medium),
+            };
+            let hex: String = analysis.ids.get(*index).into_iter().flatten().map(|b| format!("{:02x}", b)).collect();
+            artifacts.push(SUSPICIOUS_ID.artifact(
+                &format!("/Trailer/ID/{}", index),
+                description,
+                risk_level,
+                [("id", hex)],
+            ));
+        }
+
+        artifacts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::StringFormat;
+
+    fn with_ids(ids: [&[u8]; 2]) -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        doc.trailer.set("ID", ids.iter().map(|id| Object::String(id.to_vec(), StringFormat::Hexadecimal)).collect::<Vec<_>>());
+        doc
+    }
+
+    #[test]
+    fn test_digest_ids_are_clean() {
+        let digest: [u8; 16] = [0x8f, 0x1c, 0x02, 0xe7, 0x55, 0xa9, 0x3b, 0xd4, 0x61, 0x0e, 0xc8, 0x97, 0x2a, 0xf3, 0x46, 0xbd];
+        let analysis = analyze(&with_ids([&digest, &digest]));
+        assert_eq!(analysis.ids.len(), 2);
+        assert!(analysis.id_issues.is_empty());
+        assert!(analysis.nonstandard_keys.is_empty());
+    }
+
+    #[test]
+    fn test_text_and_low_entropy_ids_reported() {
+        let analysis = analyze(&with_ids([b"alice@example.org", &[0u8; 16]]));
+        assert!(analysis.id_issues.contains(&(0, IdIssue::PrintableText("alice@example.org".into()))));
+        assert!(analysis.id_issues.iter().any(|(index, issue)| *index == 1 && matches!(issue, IdIssue::LowEntropy(_))));
+    }
+
+    #[test]
+    fn test_nonstandard_trailer_keys_reported() {
+        let mut doc = with_ids([&[1; 16], &[2; 16]]);
+        doc.trailer.set("Tracker", Object::string_literal("campaign-7"));
+        doc.trailer.set("Size", 3);
+        assert_eq!(analyze(&doc).nonstandard_keys, vec!["Tracker"]);
+    }
+}
@@ -0,0 +1,507 @@
+//! Cross-reference and trailer consistency checks
+//! Author: kartik4091
+//! Created: 2025-06-05 00:15:48 UTC
+//! Writers compute `startxref`, the /Prev chain, /Size and every entry
+//! offset from what they have just written, so they agree with the file.
+//! Editing a file by hand, or splicing objects into it, breaks that
+//! agreement in characteristic ways: `startxref` points a few bytes off
+//! the real section, a /Prev chain loops back on itself, /Size disagrees
+//! with the object numbers present, or entries point at the wrong bytes.
+//! The checks work on raw bytes, since the parser either repairs these
+//! inconsistencies silently or fails on them; a looping /Prev chain never
+//! returns from the parser at all, so input is parsed through
+//! [`load_checked`], which checks first.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::OnceLock;
+use regex::bytes::Regex;
+use tracing::instrument;
+
+use crate::antiforensics::{utils::FindingTemplate, ArtifactType, ForensicArtifact, RiskLevel};
+
+/// Bytes before the end of the file searched for `startxref`
+const TAIL_WINDOW: usize = 1024;
+
+/// Misplaced entries listed in an artifact before the rest are only counted
+const LISTED_ENTRIES: usize = 20;
+
+/// Longest trailer or cross-reference stream dictionary read; bounds the
+/// search for the closing `>>` when brackets do not balance
+const MAX_DICTIONARY: usize = 64 * 1024;
+
+const FINDING: FindingTemplate = FindingTemplate {
+    artifact_type: ArtifactType::Structure,
+    location_prefix: "xref:",
+    remediation: "Rewrite the file so its cross-reference data is regenerated",
+};
+
+/// Kind of cross-reference section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XrefKind {
+    /// Classic `xref` table followed by `trailer`
+    Table,
+    /// `/Type /XRef` stream object
+    Stream,
+}
+
+/// One cross-reference section as found in the raw bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XrefSection {
+    pub offset: usize,
+    pub kind: XrefKind,
+    pub prev: Option<u64>,
+    pub size: Option<u64>,
+    /// In-use entries of a table as `(number, generation, offset)`;
+    /// stream entries are compressed and not read here
+    pub entries: Vec<(u32, u16, u64)>,
+}
+
+/// Disagreement between the cross-reference data and the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XrefIssue {
+    /// No `startxref` near the end of the file
+    StartxrefMissing,
+    /// `startxref` does not point at a section; `actual` is the last
+    /// section found by searching the file, from which a reader recovers
+    StartxrefWrong { declared: usize, actual: Option<usize> },
+    /// The /Prev chain returns to the section at `offset`
+    PrevLoop { offset: usize },
+    /// /Prev points where there is no section
+    PrevInvalid { from: usize, prev: u64 },
+    /// Trailer /Size is not the highest object number plus one
+    SizeMismatch { declared: u64, expected: u64 },
+    /// Table entries pointing somewhere other than their object's header,
+    /// as `(number, generation, offset)`
+    MisplacedEntries(Vec<(u32, u16, u64)>),
+}
+
+/// Cross-reference sections of a file, newest first, and their issues
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XrefIntegrity {
+    /// The /Prev chain as followed from `startxref`
+    pub chain: Vec<XrefSection>,
+    pub issues: Vec<XrefIssue>,
+}
+
+impl XrefIntegrity {
+    /// Whether the /Prev chain loops, which the parser cannot survive
+    pub fn has_prev_loop(&self) -> bool {
+        self.issues.iter().any(|issue| matches!(issue, XrefIssue::PrevLoop { .. }))
+    }
+}
+
+fn object_header_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?-u)(?:^|[^0-9])([0-9]{1,10})[ \t\r\n\x0c\x00]+([0-9]{1,5})[ \t\r\n\x0c\x00]+obj\b").expect("valid header pattern"))
+}
+
+fn xref_type_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?-u)/Type[ \t\r\n\x0c\x00]*/XRef").expect("valid type pattern"))
+}
+
+fn xref_keyword_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?-u)(?:^|[\r\n])(xref)[ \t\r\n]+[0-9]").expect("valid xref pattern"))
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0')
+}
+
+fn skip_whitespace(data: &[u8], mut at: usize) -> usize {
+    while data.get(at).is_some_and(|&b| is_whitespace(b)) {
+        at += 1;
+    }
+    at
+}
+
+/// Whitespace-separated token at `at` and the offset after it
+fn token(data: &[u8], at: usize) -> Option<(&[u8], usize)> {
+    let start = skip_whitespace(data, at);
+    let end = data[start..].iter().position(|&b| is_whitespace(b)).map_or(data.len(), |p| p + start);
+    (end > start).then(|| (&data[start..end], end))
+}
+
+fn number<T: std::str::FromStr>(bytes: &[u8]) -> Option<T> {
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+/// The dictionary starting at the first `<<` from `at`, brackets balanced
+/// within [`MAX_DICTIONARY`] bytes
+fn dictionary(data: &[u8], at: usize) -> Option<&[u8]> {
+    let start = at + data.get(at..)?.windows(2).take(64).position(|w| w == b"<<")?;
+    let end = data.len().min(start.saturating_add(MAX_DICTIONARY));
+    let mut depth = 0usize;
+    let mut i = start;
+    while i + 1 < end {
+        match &data[i..i + 2] {
+            b"<<" => {
+                depth += 1;
+                i += 2;
+            }
+            b">>" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Some(&data[start..i]);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Integer value of a top-level-looking `/Key n` in a dictionary
+fn dict_int(dict: &[u8], key: &[u8]) -> Option<u64> {
+    let mut from = 0;
+    while let Some(found) = dict[from..].windows(key.len() + 1).position(|w| w[0] == b'/' && &w[1..] == key) {
+        let after = from + found + key.len() + 1;
+        // `/Prev` must not match `/PrevPage`
+        if dict.get(after).is_some_and(|&b| is_whitespace(b)) {
+            let start = skip_whitespace(dict, after);
+            let digits = dict[start..].iter().take_while(|b| b.is_ascii_digit()).count();
+            return number(&dict[start..start + digits]);
+        }
+        from = after;
+    }
+    None
+}
+
+fn is_xref_stream(dict: &[u8]) -> bool {
+    let mut from = 0;
+    while let Some(found) = dict[from..].windows(5).position(|w| w == b"/Type") {
+        let after = from + found + 5;
+        if dict[skip_whitespace(dict, after)..].starts_with(b"/XRef") {
+            return true;
+        }
+        from = after;
+    }
+    false
+}
+
+/// Parses the section at `offset`, if there is one
+pub fn section_at(data: &[u8], offset: usize) -> Option<XrefSection> {
+    let start = skip_whitespace(data, offset);
+    let rest = data.get(start..)?;
+    if rest.starts_with(b"xref") {
+        return table(data, offset, start + 4);
+    }
+
+    let (num, after) = token(data, start)?;
+    let (generation, after) = token(data, after)?;
+    let (keyword, after) = token(data, after)?;
+    number::<u32>(num)?;
+    number::<u16>(generation)?;
+    if !keyword.starts_with(b"obj") {
+        return None;
+    }
+    let dict = dictionary(data, after - keyword.len() + 3)?;
+    is_xref_stream(dict).then(|| XrefSection {
+        offset,
+        kind: XrefKind::Stream,
+        prev: dict_int(dict, b"Prev"),
+        size: dict_int(dict, b"Size"),
+        entries: Vec::new(),
+    })
+}
+
+fn table(data: &[u8], offset: usize, mut at: usize) -> Option<XrefSection> {
+    let mut entries = Vec::new();
+    loop {
+        let (word, after) = token(data, at)?;
+        if word.starts_with(b"trailer") {
+            let dict = dictionary(data, after - word.len() + 7)?;
+            return Some(XrefSection {
+                offset,
+                kind: XrefKind::Table,
+                prev: dict_int(dict, b"Prev"),
+                size: dict_int(dict, b"Size"),
+                entries,
+            });
+        }
+        let first: u32 = number(word)?;
+        let (count, after) = token(data, after)?;
+        let count: u32 = number(count)?;
+        at = after;
+        for index in 0..count {
+            let (entry_offset, after) = token(data, at)?;
+            let (generation, after) = token(data, after)?;
+            let (kind, after) = token(data, after)?;
+            at = after;
+            if kind == b"n" {
+                entries.push((first.checked_add(index)?, number(generation)?, number(entry_offset)?));
+            }
+        }
+    }
+}
+
+/// Offset given by the last `startxref` in the file
+pub fn startxref(data: &[u8]) -> Option<usize> {
+    let tail = data.len().saturating_sub(TAIL_WINDOW);
+    let keyword = data[tail..].windows(9).rposition(|w| w == b"startxref")? + tail;
+    let (value, _) = token(data, keyword + 9)?;
+    number(value)
+}
+
+/// Every section in the file, found by searching rather than by offsets
+///
+/// Only object headers followed closely by `/Type /XRef` are parsed, so a
+/// file of many objects, or many unbalanced `<<`, stays linear.
+fn find_sections(data: &[u8]) -> Vec<XrefSection> {
+    let mut offsets: Vec<usize> = xref_keyword_pattern().captures_iter(data)
+        .filter_map(|c| c.get(1).map(|m| m.start()))
+        .collect();
+    let types: Vec<usize> = xref_type_pattern().find_iter(data).map(|m| m.start()).collect();
+    offsets.extend(
+        object_header_pattern().captures_iter(data)
+            .filter_map(|c| c.get(1).map(|m| m.start()))
+            .filter(|&offset| {
+                let next = types.partition_point(|&at| at < offset);
+                types.get(next).is_some_and(|&at| at - offset <= MAX_DICTIONARY)
+            }),
+    );
+    offsets.sort_unstable();
+    offsets.into_iter().filter_map(|offset| section_at(data, offset)).collect()
+}
+
+/// Highest object number with a header in the file, and header offsets by id
+fn object_headers(data: &[u8]) -> HashMap<(u32, u16), Vec<usize>> {
+    let mut headers: HashMap<(u32, u16), Vec<usize>> = HashMap::new();
+    for capture in object_header_pattern().captures_iter(data) {
+        let (Some(num), Some(generation)) = (capture.get(1), capture.get(2)) else { continue };
+        if let (Some(n), Some(g)) = (number(num.as_bytes()), number(generation.as_bytes())) {
+            headers.entry((n, g)).or_default().push(num.start());
+        }
+    }
+    headers
+}
+
+/// Checks the cross-reference data of a raw file
+pub fn check(data: &[u8]) -> XrefIntegrity {
+    let mut integrity = XrefIntegrity::default();
+
+    let start = match startxref(data) {
+        None => {
+            integrity.issues.push(XrefIssue::StartxrefMissing);
+            find_sections(data).pop().map(|section| section.offset)
+        }
+        Some(declared) => match section_at(data, declared) {
+            Some(_) => Some(declared),
+            None => {
+                let actual = find_sections(data).pop().map(|section| section.offset);
+                integrity.issues.push(XrefIssue::StartxrefWrong { declared, actual });
+                actual
+            }
+        },
+    };
+
+    let mut visited = HashSet::new();
+    let mut next = start;
+    while let Some(offset) = next {
+        if !visited.insert(offset) {
+            integrity.issues.push(XrefIssue::PrevLoop { offset });
+            break;
+        }
+        let Some(section) = section_at(data, offset) else { break };
+        next = match section.prev {
+            Some(prev) if usize::try_from(prev).is_ok_and(|p| section_at(data, p).is_some()) => Some(prev as usize),
+            Some(prev) => {
+                integrity.issues.push(XrefIssue::PrevInvalid { from: offset, prev });
+                None
+            }
+            None => None,
+        };
+        integrity.chain.push(section);
+    }
+
+    let headers = object_headers(data);
+    let highest = headers.keys().map(|&(n, _)| u64::from(n)).max();
+    let streams = integrity.chain.iter().any(|s| s.kind == XrefKind::Stream);
+    if let (Some(declared), Some(highest)) = (integrity.chain.first().and_then(|s| s.size), highest) {
+        // Objects inside object streams have no header of their own, so
+        // with cross-reference streams only a /Size too small is certain
+        let expected = highest + 1;
+        if declared < expected || (declared > expected && !streams) {
+            integrity.issues.push(XrefIssue::SizeMismatch { declared, expected });
+        }
+    }
+
+    // Newer sections override older ones, so only the entry in force counts
+    let mut in_force: BTreeMap<u32, (u16, u64)> = BTreeMap::new();
+    for section in integrity.chain.iter().rev() {
+        for &(number, generation, offset) in &section.entries {
+            in_force.insert(number, (generation, offset));
+        }
+    }
+    let misplaced: Vec<(u32, u16, u64)> = in_force.into_iter()
+        .filter(|&(number, _)| number != 0)
+        .filter(|&(number, (generation, offset))| {
+            let start = usize::try_from(offset).map_or(usize::MAX, |o| skip_whitespace(data, o));
+            !headers.get(&(number, generation)).is_some_and(|at| at.contains(&start))
+        })
+        .map(|(number, (generation, offset))| (number, generation, offset))
+        .collect();
+    if !misplaced.is_empty() {
+        integrity.issues.push(XrefIssue::MisplacedEntries(misplaced));
+    }
+
+    integrity
+}
+
+/// Parses untrusted input, refusing it when its /Prev chain loops
+///
+/// Every load of input goes through here rather than `Document::load`,
+/// which would follow the loop forever.
+pub fn load_checked(data: &[u8]) -> lopdf::Result<lopdf::Document> {
+    ensure_parsable(data)?;
+    lopdf::Document::load_mem(data)
+}
+
+/// Fails when the /Prev chain of `data` loops, which the parser cannot survive
+pub fn ensure_parsable(data: &[u8]) -> lopdf::Result<()> {
+    let integrity = check(data);
+    match integrity.issues.iter().find(|issue| matches!(issue, XrefIssue::PrevLoop { .. })) {
+        Some(XrefIssue::PrevLoop { offset }) => Err(lopdf::Error::Syntax(format!(
+            "cross-reference /Prev chain loops back to offset {}; file was edited by hand",
+            offset,
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Reports cross-reference data that disagrees with the file
+#[derive(Debug, Clone, Default)]
+pub struct XrefIntegrityAnalyzer;
+
+impl XrefIntegrityAnalyzer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[instrument(skip(self, data), fields(len = data.len()))]
+    pub fn scan_bytes(&self, data: &[u8]) -> Vec<ForensicArtifact> {
+        Self::artifacts(&check(data))
+    }
+
+    pub fn artifacts(integrity: &XrefIntegrity) -> Vec<ForensicArtifact> {
+        integrity.issues.iter()
+            .map(|issue| match issue {
+                XrefIssue::StartxrefMissing => FINDING.artifact(
+                    "eof",
+                    "No startxref near the end of the file".into(),
+                    RiskLevel::Medium,
+                    [("chain_length", integrity.chain.len().to_string())],
+                ),
+                XrefIssue::StartxrefWrong { declared, actual: Some(actual) } => FINDING.artifact(
+                    &format!("offset:{}", declared),
+                    format!(
+                        "startxref points to offset {}, but the cross-reference section is at {} ({} bytes off)",
+                        declared, actual, actual.abs_diff(*declared),
+                    ),
+                    RiskLevel::High,
+                    [("declared", declared.to_string()), ("actual", actual.to_string())],
+                ),
+                XrefIssue::StartxrefWrong { declared, actual: None } => FINDING.artifact(
+                    &format!("offset:{}", declared),
+                    format!("startxref points to offset {}, and no cross-reference section exists", declared),
+                    RiskLevel::High,
+                    [("declared", declared.to_string()), ("actual", String::new())],
+                ),
+                XrefIssue::PrevLoop { offset } => FINDING.artifact(
+                    &format!("offset:{}", offset),
+                    format!("/Prev chain loops back to the cross-reference section at offset {}", offset),
+                    RiskLevel::High,
+                    [("chain_length", integrity.chain.len().to_string())],
+                ),
+                XrefIssue::PrevInvalid { from, prev } => FINDING.artifact(
+                    &format!("offset:{}", from),
+                    format!("/Prev of the section at offset {} points to {}, where there is no section", from, prev),
+                    RiskLevel::Medium,
+                    [("prev", prev.to_string())],
+                ),
+                XrefIssue::SizeMismatch { declared, expected } => FINDING.artifact(
+                    "trailer/Size",
+                    format!("Trailer /Size is {}, but the highest object number is {}", declared, expected - 1),
+                    if declared < expected { RiskLevel::High } else { RiskLevel::Medium },
+                    [("declared", declared.to_string()), ("expected", expected.to_string())],
+                ),
+                XrefIssue::MisplacedEntries(entries) => {
+                    let listed: Vec<String> = entries.iter()
+                        .take(LISTED_ENTRIES)
+                        .map(|(number, generation, offset)| format!("{} {} R@{}", number, generation, offset))
+                        .collect();
+                    FINDING.artifact(
+                        "xref",
+                        format!("{} cross-reference entr(ies) point away from their object", entries.len()),
+                        RiskLevel::Medium,
+                        [("entries", listed.join(","))],
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn saved() -> Vec<u8> {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog" });
+        doc.add_object(dictionary! { "Producer" => lopdf::Object::string_literal("x") });
+        doc.trailer.set("Root", catalog);
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+        data
+    }
+
+    fn set_startxref(data: &mut Vec<u8>, offset: usize) {
+        let keyword = data.windows(9).rposition(|w| w == b"startxref").unwrap();
+        data.truncate(keyword);
+        data.extend_from_slice(format!("startxref\n{}\n%%EOF\n", offset).as_bytes());
+    }
+
+    #[test]
+    fn test_written_file_is_consistent() {
+        let data = saved();
+        let integrity = check(&data);
+        assert_eq!(integrity.chain.len(), 1);
+        assert_eq!(integrity.chain[0].entries.len(), 2);
+        assert!(integrity.issues.is_empty(), "{:?}", integrity.issues);
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let mut data = saved();
+        let actual = startxref(&data).unwrap();
+        set_startxref(&mut data, actual - 3);
+        let integrity = check(&data);
+        assert_eq!(integrity.issues, vec![XrefIssue::StartxrefWrong { declared: actual - 3, actual: Some(actual) }]);
+
+        // A section whose /Prev is itself
+        let mut data = saved();
+        let trailer = data.windows(7).rposition(|w| w == b"trailer").unwrap();
+        data.splice(trailer + 7..trailer + 7, format!("\n<</Prev {} ", actual).bytes().chain(b">>".iter().copied()));
+        set_startxref(&mut data, actual);
+        assert!(check(&data).has_prev_loop());
+        assert!(matches!(load_checked(&data), Err(lopdf::Error::Syntax(_))));
+        assert!(load_checked(&saved()).is_ok());
+
+        // Size too small, and an entry shifted off its object
+        let mut data = String::from_utf8_lossy(&saved()).replace("/Size 3", "/Size 2").into_bytes();
+        let entry = data.windows(8).position(|w| w == b" 00000 n").unwrap() - 10;
+        data[entry + 9] = if data[entry + 9] == b'9' { b'0' } else { data[entry + 9] + 1 };
+        let issues = check(&data).issues;
+        assert!(issues.contains(&XrefIssue::SizeMismatch { declared: 2, expected: 3 }));
+        assert!(issues.iter().any(|issue| matches!(issue, XrefIssue::MisplacedEntries(entries) if entries.len() == 1)));
+        assert!(XrefIntegrityAnalyzer::artifacts(&check(&data)).iter().all(|a| a.artifact_type == ArtifactType::Structure));
+
+        // Thousands of unclosed dictionaries are searched in linear time
+        let unbalanced = b"1 0 obj << /Type /XRef << ".repeat(20_000);
+        assert!(check(&unbalanced).chain.is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+//! Kept embedded file normalization
+//! Author: kartik4091
+//! Created: 2025-06-05 02:04:29 UTC
+//! An attachment that survives cleaning still describes the system it
+//! came from: /Params carries the file's creation and modification times
+//! and Mac creator codes, and the file specification may hold a full path
+//! with the author's user name, a file identifier and platform-specific
+//! names. /Subtype, /Size and /CheckSum are whatever the producing tool
+//! wrote, and often wrong. [`AttachmentNormalizer`] rewrites every kept
+//! file: /Subtype from its sniffed content, /Size and an MD5 /CheckSum of
+//! the decoded file, dates per [`AttachmentDates`], and file names
+//! without their directories.
+
+use chrono::{FixedOffset, NaiveDate};
+use lopdf::{Dictionary, Object, StringFormat};
+
+use super::form_data::text_string;
+use crate::{
+    antiforensics::{
+        scanner::mime::{embedded_files, EmbeddedFile, FileType},
+        text_encoding::decode_text_string,
+        types::Document,
+    },
+    pdf_date::PdfDate,
+};
+
+/// Dates of an embedded file in its /Params
+const DATE_KEYS: [&[u8]; 2] = [b"CreationDate", b"ModDate"];
+
+/// File specification entries that only describe the author's system
+const PLATFORM_KEYS: [&[u8]; 4] = [b"ID", b"DOS", b"Mac", b"Unix"];
+
+/// What happens to the creation and modification dates of kept files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AttachmentDates {
+    /// Leave them as they are
+    Keep,
+    /// Remove them; associated files get the epoch as /ModDate instead,
+    /// since PDF/A-3 requires one
+    #[default]
+    Strip,
+    /// Set both to this date
+    Fixed(PdfDate),
+}
+
+impl AttachmentDates {
+    /// Both dates set to 1970-01-01 00:00:00 UTC
+    pub fn epoch() -> Self {
+        Self::Fixed(epoch())
+    }
+}
+
+fn epoch() -> PdfDate {
+    let midnight = NaiveDate::from_ymd_opt(1970, 1, 1).and_then(|date| date.and_hms_opt(0, 0, 0)).unwrap_or_default();
+    PdfDate::new(midnight, FixedOffset::east_opt(0))
+}
+
+/// What attachment normalization changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttachmentNormalizeReport {
+    /// Embedded file streams normalized
+    pub files: usize,
+    /// Subtypes corrected, as `(file name, subtype)`
+    pub subtypes: Vec<(String, String)>,
+    /// Entries removed or rewritten, e.g. `12 0 R/Params/ModDate`
+    pub changed: Vec<String>,
+}
+
+/// Rewrites the descriptions of the embedded files left in a document
+#[derive(Debug, Clone, Default)]
+pub struct AttachmentNormalizer {
+    dates: AttachmentDates,
+}
+
+impl AttachmentNormalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_dates(mut self, dates: AttachmentDates) -> Self {
+        self.dates = dates;
+        self
+    }
+
+    /// Normalizes every embedded file; run after attachments are stripped
+    pub fn normalize(&self, doc: &mut Document) -> lopdf::Result<AttachmentNormalizeReport> {
+        let mut report = AttachmentNormalizeReport::default();
+        for file in embedded_files(doc) {
+            // PDF/A-3 associated files are the ones with a relationship
+            let associated = file.filespec_id
+                .and_then(|id| doc.dictionary(id))
+                .is_some_and(|spec| spec.has(b"AFRelationship"));
+            let location = format!("{} {} R", file.stream_id.0, file.stream_id.1);
+
+            let stream = doc.structure.get_object_mut(file.stream_id)?.as_stream_mut()?;
+            let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+            if let Some(subtype) = corrected_subtype(&file) {
+                stream.dict.set("Subtype", Object::Name(subtype.as_bytes().to_vec()));
+                report.subtypes.push((base_name(&file.name).to_string(), subtype.to_string()));
+            }
+            if stream.dict.get(b"DL").and_then(Object::as_i64).is_ok_and(|length| length != content.len() as i64) {
+                stream.dict.set("DL", content.len() as i64);
+                report.changed.push(format!("{}/DL", location));
+            }
+
+            let params = match stream.dict.get(b"Params") {
+                Ok(Object::Reference(id)) => {
+                    let id = *id;
+                    doc.structure.get_dictionary_mut(id)?
+                }
+                Ok(Object::Dictionary(_)) => stream.dict.get_mut(b"Params")?.as_dict_mut()?,
+                _ => {
+                    stream.dict.set("Params", Dictionary::new());
+                    stream.dict.get_mut(b"Params")?.as_dict_mut()?
+                }
+            };
+            self.normalize_params(params, &content, associated, &format!("{}/Params", location), &mut report);
+            report.files += 1;
+        }
+
+        for (&id, object) in doc.structure.objects.iter_mut() {
+            let Object::Dictionary(dict) = object else { continue };
+            let location = format!("{} {} R", id.0, id.1);
+            if dict.has(b"EF") {
+                normalize_filespec(dict, &location, &mut report);
+            }
+            // File attachment annotations often carry the specification inline
+            if let Ok(Object::Dictionary(spec)) = dict.get_mut(b"FS") {
+                if spec.has(b"EF") {
+                    normalize_filespec(spec, &format!("{}/FS", location), &mut report);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn normalize_params(
+        &self,
+        params: &mut Dictionary,
+        content: &[u8],
+        associated: bool,
+        location: &str,
+        report: &mut AttachmentNormalizeReport,
+    ) {
+        if params.get(b"Size").and_then(Object::as_i64).ok() != Some(content.len() as i64) {
+            params.set("Size", content.len() as i64);
+            report.changed.push(format!("{}/Size", location));
+        }
+        let checksum = md5::compute(content).0.to_vec();
+        if params.get(b"CheckSum").and_then(Object::as_str).ok() != Some(checksum.as_slice()) {
+            params.set("CheckSum", Object::String(checksum, StringFormat::Hexadecimal));
+            report.changed.push(format!("{}/CheckSum", location));
+        }
+        if params.remove(b"Mac").is_some() {
+            report.changed.push(format!("{}/Mac", location));
+        }
+
+        for key in DATE_KEYS {
+            let entry = format!("{}/{}", location, String::from_utf8_lossy(key));
+            let date = match self.dates {
+                AttachmentDates::Keep => continue,
+                AttachmentDates::Strip if associated && key == b"ModDate" => epoch(),
+                AttachmentDates::Strip => {
+                    if params.remove(key).is_some() {
+                        report.changed.push(entry);
+                    }
+                    continue;
+                }
+                AttachmentDates::Fixed(date) => date,
+            };
+            let text = date.to_pdf_string();
+            if params.get(key).and_then(Object::as_str).ok() != Some(text.as_bytes()) {
+                params.set(key.to_vec(), date.to_object());
+                report.changed.push(entry);
+            }
+        }
+    }
+}
+
+/// MIME type to declare for `file`, when its /Subtype is missing or wrong
+///
+/// A declared type the sniffer cannot check is only replaced when the
+/// content is recognized as something else.
+fn corrected_subtype(file: &EmbeddedFile) -> Option<&'static str> {
+    let fits = file.declared.as_deref().is_some_and(|declared| match FileType::from_mime(declared) {
+        Some(declared) => file.sniffed.fits(declared),
+        None => file.sniffed == FileType::Unknown,
+    });
+    if fits {
+        return None;
+    }
+    // Office and OpenDocument types are only known by family, not the exact MIME type
+    Some(match file.sniffed {
+        FileType::Ooxml | FileType::OpenDocument => "application/zip",
+        sniffed => sniffed.mime(),
+    })
+}
+
+/// Drops platform entries and directories from a file specification
+fn normalize_filespec(spec: &mut Dictionary, location: &str, report: &mut AttachmentNormalizeReport) {
+    for key in PLATFORM_KEYS {
+        if spec.remove(key).is_some() {
+            report.changed.push(format!("{}/{}", location, String::from_utf8_lossy(key)));
+        }
+    }
+    for key in [&b"F"[..], b"UF"] {
+        let Ok(Object::String(bytes, _)) = spec.get(key) else { continue };
+        let path = decode_text_string(bytes);
+        let name = base_name(&path);
+        if name.len() < path.len() {
+            spec.set(key.to_vec(), text_string(name));
+            report.changed.push(format!("{}/{}", location, String::from_utf8_lossy(key)));
+        }
+    }
+}
+
+/// File name without the directories before it, in either separator style
+fn base_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn test_kept_files_described_by_content_only() {
+        let mut pdf = lopdf::Document::with_version("1.7");
+        let content = b"%PDF-1.7\n%%EOF\n".to_vec();
+        let stream = pdf.add_object(Stream::new(dictionary! {
+            "Type" => "EmbeddedFile",
+            "Subtype" => Object::Name(b"text/plain".to_vec()),
+            "Params" => dictionary! {
+                "Size" => 9999,
+                "CreationDate" => Object::string_literal("D:20240102030405+01'00'"),
+                "ModDate" => Object::string_literal("D:20240102030405+01'00'"),
+                "Mac" => dictionary! { "Creator" => Object::string_literal("CARO") },
+            },
+        }, content.clone()));
+        let spec = pdf.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal("C:\\Users\\jdoe\\Desktop\\report.pdf"),
+            "UF" => Object::string_literal("/home/jdoe/report.pdf"),
+            "ID" => vec![Object::string_literal("abc"), Object::string_literal("abc")],
+            "AFRelationship" => "Source",
+            "EF" => dictionary! { "F" => stream },
+        });
+        let mut doc = Document::from_pdf("attachments.pdf".into(), pdf);
+
+        let report = AttachmentNormalizer::new().normalize(&mut doc).unwrap();
+        assert_eq!(report.files, 1);
+        assert_eq!(report.subtypes, vec![("report.pdf".to_string(), "application/pdf".to_string())]);
+
+        let file = doc.dictionary(stream).unwrap();
+        let params = file.get(b"Params").unwrap().as_dict().unwrap();
+        assert_eq!(params.get(b"Size").unwrap().as_i64().unwrap(), content.len() as i64);
+        assert_eq!(params.get(b"CheckSum").unwrap().as_str().unwrap(), md5::compute(&content).0);
+        assert!(!params.has(b"CreationDate") && !params.has(b"Mac"));
+        // An associated file keeps a /ModDate, with nothing left of the original
+        assert_eq!(params.get(b"ModDate").unwrap().as_str().unwrap(), b"D:19700101000000Z");
+
+        let spec = doc.dictionary(spec).unwrap();
+        assert_eq!(spec.get(b"F").unwrap().as_str().unwrap(), b"report.pdf");
+        assert_eq!(spec.get(b"UF").unwrap().as_str().unwrap(), b"report.pdf");
+        assert!(!spec.has(b"ID"));
+
+        let again = AttachmentNormalizer::new().normalize(&mut doc).unwrap();
+        assert!(again.subtypes.is_empty() && again.changed.is_empty());
+    }
+}
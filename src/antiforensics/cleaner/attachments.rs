@@ -0,0 +1,414 @@
+//! Embedded File Removal
+//! Author: kartik4091
+//! Created: 2025-06-04 21:58:03 UTC
+//! Strips embedded files: the /EmbeddedFiles name tree, the file
+//! specifications and streams it points to, their catalog /AF
+//! associations and FileAttachment annotations on pages. Hybrid
+//! e-invoices can be exempted, keeping compliant ZUGFeRD / Factur-X XML
+//! while everything else goes.
+
+use std::collections::HashSet;
+use lopdf::{dictionary, Dictionary, Object, ObjectId};
+use serde::{Serialize, Deserialize};
+
+use super::scope::{CleanTarget, CleaningScope};
+use crate::antiforensics::{
+    references::Visited,
+    scanner::{invoice::invoice_attachments, portfolio::entries},
+    types::Document,
+    ArtifactType,
+};
+
+/// Nesting limit when collecting name tree nodes
+const MAX_TREE_DEPTH: usize = 32;
+
+/// What happens to embedded files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AttachmentPolicy {
+    /// Leave attachments alone
+    #[default]
+    Keep,
+    /// Remove every attachment
+    Strip,
+    /// Remove every attachment except compliant e-invoice XML
+    KeepInvoices,
+}
+
+/// What attachment stripping changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttachmentCleanReport {
+    /// Names of the attachments removed
+    pub removed: Vec<String>,
+    /// Names of the invoice attachments kept
+    pub kept: Vec<String>,
+    /// FileAttachment annotations removed from pages
+    pub annotations_removed: usize,
+}
+
+/// Applies `policy` to the document's attachments
+///
+/// Kept invoices are relisted in a flat name tree. The XMP metadata
+/// declaring the Factur-X profile is not touched here; metadata removal
+/// must be disabled as well for the invoice to stay valid.
+pub fn strip_attachments(doc: &mut Document, policy: AttachmentPolicy) -> lopdf::Result<AttachmentCleanReport> {
+    strip_attachments_scoped(doc, policy, &CleaningScope::default())
+}
+
+/// Applies `policy` to the attachments and annotations `scope` allows
+///
+/// Attachments outside the scope stay listed; those with an inline file
+/// specification get a minimal indirect one in the rebuilt name tree.
+pub fn strip_attachments_scoped(
+    doc: &mut Document,
+    policy: AttachmentPolicy,
+    scope: &CleaningScope,
+) -> lopdf::Result<AttachmentCleanReport> {
+    let mut report = AttachmentCleanReport::default();
+    if policy == AttachmentPolicy::Keep {
+        return Ok(report);
+    }
+
+    let mut keep: HashSet<ObjectId> = if policy == AttachmentPolicy::KeepInvoices {
+        invoice_attachments(&doc.structure).into_iter()
+            .filter(|invoice| invoice.is_compliant())
+            .filter_map(|invoice| invoice.filespec_id)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    let mut kept = Vec::new();
+    let mut removed_specs = HashSet::new();
+    for entry in entries(&doc.structure) {
+        let location = format!("/Root/Names/EmbeddedFiles/{}", entry.name);
+        let in_scope = scope.allows(&CleanTarget {
+            location: &location,
+            artifact_type: ArtifactType::EmbeddedFile,
+            object: entry.filespec_id,
+            page: None,
+        });
+        match entry.filespec_id {
+            Some(id) if keep.contains(&id) => {
+                kept.push((entry.name.clone(), id));
+                report.kept.push(entry.name);
+            }
+            Some(id) if !in_scope => {
+                keep.insert(id);
+                kept.push((entry.name, id));
+            }
+            None if !in_scope => {
+                let name = Object::string_literal(entry.name.as_str());
+                let id = doc.structure.add_object(dictionary! {
+                    "Type" => "Filespec",
+                    "F" => name.clone(),
+                    "UF" => name,
+                    "EF" => dictionary! { "F" => entry.stream_id },
+                });
+                keep.insert(id);
+                kept.push((entry.name, id));
+            }
+            filespec_id => {
+                doc.structure.objects.remove(&entry.stream_id);
+                if let Some(id) = filespec_id {
+                    doc.structure.objects.remove(&id);
+                    removed_specs.insert(id);
+                }
+                report.removed.push(entry.name);
+            }
+        }
+    }
+
+    report.annotations_removed = remove_annotations(doc, &keep, scope, &mut removed_specs)?;
+    rebuild_name_tree(doc, &mut kept)?;
+
+    let af_id = match doc.catalog().ok_or(lopdf::Error::DictKey)?.get(b"AF") {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+    let af = match af_id {
+        Some(id) => doc.object_mut(id),
+        None => doc.catalog_mut().ok_or(lopdf::Error::DictKey)?.get_mut(b"AF").ok(),
+    };
+    let af_empty = match af {
+        Some(Object::Array(items)) => {
+            items.retain(|item| !item.as_reference().is_ok_and(|id| removed_specs.contains(&id)));
+            items.is_empty()
+        }
+        _ => false,
+    };
+    let catalog = doc.catalog_mut().ok_or(lopdf::Error::DictKey)?;
+    if af_empty {
+        catalog.remove(b"AF");
+    }
+    // A portfolio without files is an empty shell
+    if kept.is_empty() {
+        catalog.remove(b"Collection");
+    }
+    if af_empty {
+        if let Some(id) = af_id {
+            doc.structure.objects.remove(&id);
+        }
+    }
+    Ok(report)
+}
+
+/// Replaces the /EmbeddedFiles tree with a flat one listing `kept`
+fn rebuild_name_tree(doc: &mut Document, kept: &mut [(String, ObjectId)]) -> lopdf::Result<()> {
+    let root_id = doc.root_id().ok_or(lopdf::Error::DictKey)?;
+    let doc = &mut doc.structure;
+    let names = match doc.get_dictionary(root_id)?.get(b"Names") {
+        Ok(Object::Reference(id)) => Some(*id),
+        Ok(Object::Dictionary(_)) => None,
+        _ => return Ok(()),
+    };
+
+    let tree = match names {
+        Some(id) => doc.get_dictionary(id)?.get(b"EmbeddedFiles").ok(),
+        None => doc.get_dictionary(root_id)?.get(b"Names")?.as_dict()?.get(b"EmbeddedFiles").ok(),
+    };
+    let mut nodes = Vec::new();
+    if let Some(tree) = tree.cloned() {
+        collect_nodes(doc, &tree, 0, &mut Visited::new(), &mut nodes);
+    }
+    for id in nodes {
+        doc.objects.remove(&id);
+    }
+
+    let names_dict = match names {
+        Some(id) => doc.get_dictionary_mut(id)?,
+        None => doc.get_dictionary_mut(root_id)?.get_mut(b"Names")?.as_dict_mut()?,
+    };
+    if kept.is_empty() {
+        names_dict.remove(b"EmbeddedFiles");
+    } else {
+        // Name tree keys must be sorted
+        kept.sort();
+        let pairs = kept.iter()
+            .flat_map(|(name, id)| [Object::string_literal(name.as_str()), Object::Reference(*id)])
+            .collect::<Vec<_>>();
+        names_dict.set("EmbeddedFiles", dictionary! { "Names" => pairs });
+    }
+
+    if names_dict.is_empty() {
+        doc.get_dictionary_mut(root_id)?.remove(b"Names");
+        if let Some(id) = names {
+            doc.objects.remove(&id);
+        }
+    }
+    Ok(())
+}
+
+/// Indirect nodes of a name tree, including its root
+fn collect_nodes(doc: &lopdf::Document, node: &Object, depth: usize, visited: &mut Visited, nodes: &mut Vec<ObjectId>) {
+    if depth > MAX_TREE_DEPTH {
+        return;
+    }
+    let (dict, entered) = match node {
+        // A node reached twice is removed once; a loop back to it ends there
+        Object::Reference(id) if !visited.enter(*id) => return,
+        Object::Reference(id) => {
+            nodes.push(*id);
+            match doc.get_dictionary(*id) {
+                Ok(dict) => (dict, true),
+                Err(_) => {
+                    visited.leave();
+                    return;
+                }
+            }
+        }
+        Object::Dictionary(dict) => (dict, false),
+        _ => return,
+    };
+    if let Ok(Object::Array(kids)) = dict.get(b"Kids") {
+        for kid in kids {
+            collect_nodes(doc, kid, depth + 1, visited, nodes);
+        }
+    }
+    if entered {
+        visited.leave();
+    }
+}
+
+/// Removes FileAttachment annotations whose file is not kept, with their files
+fn remove_annotations(
+    doc: &mut Document,
+    keep: &HashSet<ObjectId>,
+    scope: &CleaningScope,
+    removed_specs: &mut HashSet<ObjectId>,
+) -> lopdf::Result<usize> {
+    let mut removed = 0;
+    let in_scope = |location: &str, object, page| scope.allows(&CleanTarget {
+        location,
+        artifact_type: ArtifactType::EmbeddedFile,
+        object,
+        page: Some(page),
+    });
+    let pages: Vec<(u32, ObjectId)> = doc.pages().collect();
+    let doc = &mut doc.structure;
+    for (number, page_id) in pages {
+        let inline_in_scope = in_scope("/Annots", None, number);
+        let annots_id = match doc.get_dictionary(page_id)?.get(b"Annots") {
+            Ok(Object::Reference(id)) => Some(*id),
+            Ok(Object::Array(_)) => None,
+            _ => continue,
+        };
+        let annots = match annots_id {
+            Some(id) => doc.get_object(id)?.as_array()?,
+            None => doc.get_dictionary(page_id)?.get(b"Annots")?.as_array()?,
+        };
+
+        let mut doomed = Vec::new();
+        for annot in annots {
+            let (annot_id, dict) = match annot {
+                Object::Reference(id) => match doc.get_dictionary(*id) {
+                    Ok(dict) => (Some(*id), dict),
+                    Err(_) => continue,
+                },
+                Object::Dictionary(dict) => (None, dict),
+                _ => continue,
+            };
+            let allowed = match annot_id {
+                Some((n, g)) => in_scope(&format!("/Annots/{} {} R", n, g), annot_id, number),
+                None => inline_in_scope,
+            };
+            if allowed && is_stripped(dict, keep) {
+                let spec = dict.get(b"FS").ok().and_then(|fs| fs.as_reference().ok());
+                doomed.push((annot_id, spec, file_streams(doc, dict)));
+            }
+        }
+        if doomed.is_empty() {
+            continue;
+        }
+
+        let annots = match annots_id {
+            Some(id) => doc.get_object_mut(id)?.as_array_mut()?,
+            None => doc.get_dictionary_mut(page_id)?.get_mut(b"Annots")?.as_array_mut()?,
+        };
+        let doomed_refs: HashSet<ObjectId> = doomed.iter().filter_map(|(id, _, _)| *id).collect();
+        annots.retain(|annot| match annot {
+            Object::Reference(id) => !doomed_refs.contains(id),
+            Object::Dictionary(dict) => !(inline_in_scope && is_stripped(dict, keep)),
+            _ => true,
+        });
+
+        for (annot_id, spec, streams) in doomed {
+            removed += 1;
+            if let Some(id) = annot_id {
+                doc.objects.remove(&id);
+            }
+            if let Some(id) = spec {
+                doc.objects.remove(&id);
+                removed_specs.insert(id);
+            }
+            for id in streams {
+                doc.objects.remove(&id);
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Whether `annot` is a FileAttachment annotation for a file not kept
+fn is_stripped(annot: &Dictionary, keep: &HashSet<ObjectId>) -> bool {
+    let attachment = annot.get(b"Subtype").and_then(Object::as_name).is_ok_and(|s| s == b"FileAttachment");
+    let kept = annot.get(b"FS").and_then(Object::as_reference).is_ok_and(|id| keep.contains(&id));
+    attachment && !kept
+}
+
+/// Embedded file streams of an annotation's file specification
+fn file_streams(doc: &lopdf::Document, annot: &Dictionary) -> Vec<ObjectId> {
+    let spec = match annot.get(b"FS") {
+        Ok(Object::Reference(id)) => doc.get_dictionary(*id).ok(),
+        Ok(Object::Dictionary(dict)) => Some(dict),
+        _ => None,
+    };
+    let ef = spec.and_then(|spec| match spec.get(b"EF") {
+        Ok(Object::Reference(id)) => doc.get_dictionary(*id).ok(),
+        Ok(Object::Dictionary(dict)) => Some(dict),
+        _ => None,
+    });
+    ef.map(|ef| ef.iter().filter_map(|(_, value)| value.as_reference().ok()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::Stream;
+    use crate::antiforensics::scanner::invoice::tests::{hybrid_invoice, FACTUR_X};
+
+    fn invoice(xml: &str) -> Document {
+        Document::from_pdf("invoice.pdf".into(), hybrid_invoice(xml))
+    }
+
+    #[test]
+    fn test_keep_invoices_strips_everything_else() {
+        let mut doc = invoice(FACTUR_X);
+        let report = strip_attachments(&mut doc, AttachmentPolicy::KeepInvoices).unwrap();
+        assert_eq!(report.kept, vec!["factur-x.xml"]);
+        assert_eq!(report.removed, vec!["tool.exe"]);
+
+        let remaining = entries(&doc.structure);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].name, "factur-x.xml");
+        assert!(invoice_attachments(&doc.structure)[0].is_compliant());
+        assert!(!doc.streams().any(|(_, s)| s.content.starts_with(b"MZ")));
+    }
+
+    #[test]
+    fn test_non_compliant_invoice_stripped() {
+        let mut doc = invoice(&FACTUR_X.replace("<rsm:ExchangedDocument>", "<rsm:Other>"));
+        let report = strip_attachments(&mut doc, AttachmentPolicy::KeepInvoices).unwrap();
+        assert!(report.kept.is_empty());
+        assert_eq!(report.removed.len(), 2);
+        assert!(!doc.catalog().unwrap().has(b"Names"));
+        assert!(!doc.catalog().unwrap().has(b"AF"));
+    }
+
+    #[test]
+    fn test_strip_removes_invoices_and_annotations() {
+        let mut doc = hybrid_invoice(FACTUR_X);
+        let payload = doc.add_object(Stream::new(dictionary! {}, b"secret".to_vec()));
+        let annot = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "FileAttachment",
+            "FS" => dictionary! { "Type" => "Filespec", "EF" => dictionary! { "F" => payload } },
+        });
+        let link = doc.add_object(dictionary! { "Type" => "Annot", "Subtype" => "Link" });
+        let pages_id = doc.new_object_id();
+        let page = doc.add_object(dictionary! {
+            "Type" => "Page", "Parent" => pages_id, "Annots" => vec![annot.into(), link.into()],
+        });
+        doc.objects.insert(pages_id, dictionary! { "Type" => "Pages", "Kids" => vec![page.into()], "Count" => 1 }.into());
+        let mut doc = Document::from_pdf("invoice.pdf".into(), doc);
+        doc.catalog_mut().unwrap().set("Pages", pages_id);
+
+        let report = strip_attachments(&mut doc, AttachmentPolicy::Strip).unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert_eq!(report.annotations_removed, 1);
+        assert!(entries(&doc.structure).is_empty());
+        assert!(doc.object(payload).is_none());
+        let annots = doc.dictionary(page).unwrap().get(b"Annots").unwrap().as_array().unwrap();
+        assert_eq!(annots.len(), 1);
+    }
+
+    #[test]
+    fn test_keep_leaves_document_unchanged() {
+        let mut doc = invoice(FACTUR_X);
+        let before = doc.objects().count();
+        assert_eq!(strip_attachments(&mut doc, AttachmentPolicy::Keep).unwrap(), AttachmentCleanReport::default());
+        assert_eq!(doc.objects().count(), before);
+    }
+
+    #[test]
+    fn test_scope_limits_stripping() {
+        let mut doc = invoice(FACTUR_X);
+        let scope = CleaningScope::from_filters(["location=*/tool.exe".parse().unwrap()]);
+        let report = strip_attachments_scoped(&mut doc, AttachmentPolicy::Strip, &scope).unwrap();
+        assert_eq!(report.removed, vec!["tool.exe"]);
+
+        let remaining = entries(&doc.structure);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].name, "factur-x.xml");
+    }
+}
@@ -0,0 +1,113 @@
+//! Date string normalization
+//! Author: kartik4091
+//! Created: 2025-06-04 23:41:18 UTC
+//! Rewrites every date in the document (Info dates, annotation and
+//! signature `/M`, `/LastModified` of page-piece data) in canonical
+//! `D:YYYYMMDDHHmmSSOHH'mm'` form. Malformed dates fingerprint the tool
+//! that wrote them; the time zone offset places the author. Optionally
+//! converts every date to UTC, so no offset survives.
+
+use lopdf::{Dictionary, Object};
+
+use crate::{antiforensics::types::Document, pdf_date::PdfDate};
+
+/// Keys whose string values are dates
+const DATE_KEYS: [&[u8]; 4] = [b"CreationDate", b"ModDate", b"M", b"LastModified"];
+
+/// What date normalization changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DateNormalizeReport {
+    /// Dates rewritten, e.g. `/Info/ModDate` or `12 0 R/M`
+    pub rewritten: Vec<String>,
+    /// Date entries that could not be parsed, left as they were
+    pub unparseable: Vec<String>,
+}
+
+/// Rewrites dates in canonical form
+#[derive(Debug, Clone, Default)]
+pub struct DateNormalizer {
+    utc: bool,
+}
+
+impl DateNormalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Converts every date to UTC, dropping the original offsets
+    pub fn with_utc(mut self, utc: bool) -> Self {
+        self.utc = utc;
+        self
+    }
+
+    pub fn normalize(&self, doc: &mut Document) -> DateNormalizeReport {
+        let mut report = DateNormalizeReport::default();
+        let info = doc.info_id();
+        if let Ok(Object::Dictionary(dict)) = doc.structure.trailer.get_mut(b"Info") {
+            self.normalize_dict(dict, "/Info", &mut report);
+        }
+        for (&id, object) in doc.structure.objects.iter_mut() {
+            let dict = match object {
+                Object::Dictionary(dict) => dict,
+                Object::Stream(stream) => &mut stream.dict,
+                _ => continue,
+            };
+            let location = if Some(id) == info { "/Info".to_string() } else { format!("{} {} R", id.0, id.1) };
+            self.normalize_dict(dict, &location, &mut report);
+        }
+        report
+    }
+
+    fn normalize_dict(&self, dict: &mut Dictionary, location: &str, report: &mut DateNormalizeReport) {
+        for key in DATE_KEYS {
+            // Only strings are dates; /M of a transition is a name
+            let Ok(Object::String(bytes, _)) = dict.get(key) else { continue };
+            let text = crate::antiforensics::scanner::unicode_spoof::decode_pdf_string(bytes);
+            let entry = format!("{}/{}", location, String::from_utf8_lossy(key));
+            match PdfDate::parse(&text) {
+                Ok(date) => {
+                    let date = if self.utc { date.in_utc() } else { date };
+                    let canonical = date.to_pdf_string();
+                    if canonical != text {
+                        dict.set(key.to_vec(), date.to_object());
+                        report.rewritten.push(entry);
+                    }
+                }
+                Err(e) => {
+                    tracing::debug!("Cannot normalize {}: {}", entry, e);
+                    report.unparseable.push(entry);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_dates_rewritten_in_canonical_form() {
+        let mut pdf = lopdf::Document::with_version("1.7");
+        let info = pdf.add_object(dictionary! {
+            "CreationDate" => Object::string_literal("D:191240102030405+05:30"),
+            "ModDate" => Object::string_literal("D:20240102030405Z"),
+        });
+        let annot = pdf.add_object(dictionary! { "Type" => "Annot", "M" => Object::string_literal("someday") });
+        let transition = pdf.add_object(dictionary! { "Type" => "Trans", "M" => "O" });
+        let mut doc = Document::from_pdf("dates.pdf".into(), pdf);
+        doc.set_info_id(info);
+
+        let report = DateNormalizer::new().normalize(&mut doc);
+        assert_eq!(report.rewritten, vec!["/Info/CreationDate"]);
+        assert_eq!(report.unparseable, vec![format!("{} 0 R/M", annot.0)]);
+        let info_dict = doc.info().unwrap();
+        assert_eq!(info_dict.get(b"CreationDate").unwrap().as_str().unwrap(), b"D:20240102030405+05'30'");
+        assert_eq!(doc.dictionary(transition).unwrap().get(b"M").unwrap().as_name().unwrap(), b"O");
+
+        let report = DateNormalizer::new().with_utc(true).normalize(&mut doc);
+        assert_eq!(report.rewritten, vec!["/Info/CreationDate"]);
+        assert_eq!(doc.info().unwrap().get(b"CreationDate").unwrap().as_str().unwrap(), b"D:20240101213405Z");
+    }
+}
@@ -118,6 +118,9 @@ pub struct CleaningResult {
     
     /// Issues encountered
     pub issues: Vec<CleaningIssue>,
+
+    /// CPU time, peak memory and IO of the cleaning
+    pub resource_usage: crate::usage::ResourceUsage,
 }
 
 /// Cleaning issue severity
@@ -163,6 +166,13 @@ impl DeepCleaner {
     /// Clean document
     #[instrument(skip(self, document, config))]
     pub async fn clean(&mut self, document: Document, config: CleaningConfig) -> Result<CleaningResult> {
+        let meter = crate::usage::UsageMeter::new();
+        let mut result = meter.measure(self.clean_measured(document, config)).await?;
+        result.resource_usage = meter.usage();
+        Ok(result)
+    }
+
+    async fn clean_measured(&mut self, document: Document, config: CleaningConfig) -> Result<CleaningResult> {
         info!("Starting deep cleaning process");
         let start_time = std::time::Instant::now();
         
@@ -213,6 +223,7 @@ impl DeepCleaner {
             document: cleaned_doc,
             statistics: self.stats.clone(),
             issues,
+            resource_usage: Default::default(),
         })
     }
     
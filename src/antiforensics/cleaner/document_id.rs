@@ -0,0 +1,85 @@
+//! Document ID regeneration
+//! Author: kartik4091
+//! Created: 2025-06-05 02:31:10 UTC
+//! The trailer /ID identifies a file across revisions, so a cleaned file
+//! that keeps it can be matched to its source, and an ID that carries
+//! text or a counter fingerprints the producing system. Regeneration
+//! replaces both parts with a new ID: derived from the document's objects,
+//! so cleaning the same input twice yields the same ID and nothing of the
+//! old one remains, or random, so no two outputs share one.
+
+use lopdf::{Object, StringFormat};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::antiforensics::{hash::object_digest, types::Document};
+
+/// How a new document ID is made
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IdRegeneration {
+    /// First 16 bytes of a digest over every object, in object number order
+    #[default]
+    ContentHash,
+    /// 16 random bytes
+    Random,
+}
+
+/// First 16 bytes of a SHA-256 over every object's digest, in object number order
+///
+/// The trailer is left out, so the old ID does not feed into the new one.
+pub fn content_id(doc: &Document) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for ((number, generation), object) in doc.objects() {
+        hasher.update(format!("{} {} {}\n", number, generation, object_digest(object)));
+    }
+    hasher.finalize()[..16].to_vec()
+}
+
+/// Replaces the trailer /ID of a document
+#[derive(Debug, Clone, Default)]
+pub struct DocumentIdRegenerator {
+    method: IdRegeneration,
+}
+
+impl DocumentIdRegenerator {
+    pub fn new(method: IdRegeneration) -> Self {
+        Self { method }
+    }
+
+    /// Sets both /ID parts to a new ID, as for a file written for the first time, and returns it
+    pub fn regenerate(&self, doc: &mut Document) -> Vec<u8> {
+        let id = match self.method {
+            IdRegeneration::ContentHash => content_id(doc),
+            IdRegeneration::Random => rand::random::<[u8; 16]>().to_vec(),
+        };
+        let part = Object::String(id.clone(), StringFormat::Hexadecimal);
+        doc.set_trailer_ids(vec![part.clone(), part]);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::Fixture;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_regenerated_ids_follow_content_or_chance() {
+        let mut doc = Document::from_pdf("clean.pdf".into(), Fixture::Clean.document().unwrap());
+        doc.set_trailer_ids(vec![Object::string_literal("jdoe-laptop-0001"); 2]);
+
+        let id = DocumentIdRegenerator::default().regenerate(&mut doc);
+        assert_eq!(id.len(), 16);
+        assert_eq!(doc.trailer_ids().unwrap()[1].as_str().unwrap(), id.as_slice());
+        // The old ID is not part of the digest
+        doc.set_trailer_ids(vec![Object::string_literal("other"); 2]);
+        assert_eq!(content_id(&doc), id);
+
+        doc.structure.add_object(dictionary! { "Producer" => Object::string_literal("x") });
+        assert_ne!(content_id(&doc), id);
+
+        let random = DocumentIdRegenerator::new(IdRegeneration::Random);
+        assert_ne!(random.regenerate(&mut doc), random.regenerate(&mut doc));
+    }
+}
@@ -31,6 +31,10 @@ pub struct FileCleanerConfig {
     pub zero_slack: bool,
     /// Truncate after cleaning
     pub truncate: bool,
+    /// Alternate data streams / extended attributes to scrub
+    pub platform_metadata: PlatformMetadataConfig,
+    /// Access/modification times given to the file after cleaning
+    pub timestamps: TimestampPolicy,
 }
 
 /// File cleaner state
@@ -48,6 +52,7 @@ struct FileCleanStats {
     files_cleaned: u64,
     bytes_cleaned: u64,
     patterns_replaced: u64,
+    platform_entries_removed: u64,
     avg_clean_time: Duration,
 }
 
@@ -220,8 +225,10 @@ impl Cleaner for FileCleaner {
             .open(path)
             .await?;
 
-        // Get original size
-        let original_size = file.metadata().await?.len();
+        // Get original size and times
+        let original_metadata = file.metadata().await?;
+        let original_size = original_metadata.len();
+        let original_times = FileTimestamps::from_metadata(&original_metadata);
 
         // Clean content
         let cleaned_size = self.clean_content(&mut file, original_size).await?;
@@ -230,6 +237,24 @@ impl Cleaner for FileCleaner {
         if self.config.base.passes > 0 {
             self.base.secure_overwrite(&mut file, cleaned_size).await?;
         }
+        drop(file);
+
+        // Scrub streams and attributes stored beside the content
+        let scrub_path = path.clone();
+        let scrub_config = self.config.platform_metadata.clone();
+        let timestamp_policy = self.config.timestamps.clone();
+        let scrubbed = tokio::task::spawn_blocking(move || {
+            let scrubbed = platform_metadata::scrub(&scrub_path, &scrub_config)?;
+            // Last, so no later write records the cleaning time
+            timestamps::apply(&scrub_path, &timestamp_policy, Some(original_times))?;
+            Ok::<_, io::Error>(scrubbed)
+        }).await.map_err(|e| CleanerError::Internal(e.to_string()))??;
+        for (name, reason) in &scrubbed.failed {
+            warn!("Could not remove {} from {}: {}", name, path.display(), reason);
+        }
+        if !scrubbed.removed.is_empty() {
+            debug!("Removed platform metadata from {}: {:?}", path.display(), scrubbed.removed);
+        }
 
         // Update statistics
         let duration = start.elapsed();
@@ -238,6 +263,7 @@ impl Cleaner for FileCleaner {
         let mut state = self.state.write().await;
         state.stats.files_cleaned += 1;
         state.stats.bytes_cleaned += cleaned_size;
+        state.stats.platform_entries_removed += scrubbed.removed.len() as u64;
         state.stats.avg_clean_time = (state.stats.avg_clean_time + duration) / 2;
 
         let result = CleanResult {
@@ -323,6 +349,8 @@ mod tests {
             ].iter().cloned().collect(),
             zero_slack: true,
             truncate: true,
+            platform_metadata: PlatformMetadataConfig::default(),
+            timestamps: TimestampPolicy::CopyFromInput,
         }
     }
 
@@ -0,0 +1,465 @@
+//! Form Data Interchange (FDF / XFDF)
+//! Author: kartik4091
+//! Created: 2025-06-04 22:07:41 UTC
+//! Field values in the two Acrobat interchange formats, so they can be
+//! saved before a form is flattened and poured back into a clean copy of
+//! the blank form later. Names are fully qualified (`address.city`);
+//! both formats nest them as a field hierarchy on the wire.
+//!
+//! FDF shares PDF's object syntax and is written and read through lopdf.
+//! XFDF is read by a minimal scanner that only understands `<field>` and
+//! `<value>` and refuses DTDs.
+
+use std::path::Path;
+use lopdf::{dictionary, Dictionary, Object};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use crate::antiforensics::{
+    analyzer::xref_integrity,
+    references::Visited,
+    scanner::unicode_spoof::decode_pdf_string,
+    utils::resolve_dict,
+};
+
+/// Field hierarchies deeper than this are not followed
+const MAX_FIELD_DEPTH: usize = 32;
+
+const XFDF_NAMESPACE: &str = "http://ns.adobe.com/xfdf/";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FormDataError {
+    #[error("PDF error: {0}")]
+    Pdf(#[from] lopdf::Error),
+    #[error("Malformed form data: {0}")]
+    Malformed(String),
+}
+
+/// Interchange format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FormDataFormat {
+    Fdf,
+    Xfdf,
+}
+
+impl FormDataFormat {
+    /// Format named by a `.fdf` or `.xfdf` extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "fdf" => Some(FormDataFormat::Fdf),
+            "xfdf" => Some(FormDataFormat::Xfdf),
+            _ => None,
+        }
+    }
+}
+
+/// How a value is typed in the PDF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FieldKind {
+    #[default]
+    Text,
+    /// Check box or radio button; the value is an appearance state name
+    Button,
+    Choice,
+}
+
+/// One field's value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormField {
+    /// Fully qualified name
+    pub name: String,
+    pub kind: FieldKind,
+    /// Several for multi-select lists, otherwise one
+    pub values: Vec<String>,
+}
+
+/// Values of a form's fields, in form order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormData {
+    pub fields: Vec<FormField>,
+}
+
+impl FormData {
+    pub fn get(&self, name: &str) -> Option<&FormField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    pub fn to_bytes(&self, format: FormDataFormat) -> Result<Vec<u8>, FormDataError> {
+        match format {
+            FormDataFormat::Fdf => self.to_fdf(),
+            FormDataFormat::Xfdf => Ok(self.to_xfdf().into_bytes()),
+        }
+    }
+
+    /// Parses FDF or XFDF, told apart by content
+    pub fn parse(data: &[u8]) -> Result<Self, FormDataError> {
+        if data.starts_with(b"%FDF-") {
+            Self::from_fdf(data)
+        } else {
+            let text = std::str::from_utf8(data)
+                .map_err(|_| FormDataError::Malformed("neither FDF nor UTF-8 XFDF".into()))?;
+            Self::from_xfdf(text)
+        }
+    }
+
+    /// Serializes as FDF 1.2
+    pub fn to_fdf(&self) -> Result<Vec<u8>, FormDataError> {
+        let mut doc = lopdf::Document::with_version("1.2");
+        let fields = to_objects(&hierarchy(&self.fields), |node| {
+            let mut dict = Dictionary::new();
+            if let Some(field) = node.field {
+                dict.set("V", fdf_value(field));
+            }
+            dict
+        });
+        let catalog = doc.add_object(dictionary! { "FDF" => dictionary! { "Fields" => fields } });
+        doc.trailer.set("Root", catalog);
+
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes)?;
+        // Same length as the PDF header, so the cross-reference offsets hold
+        bytes[..5].copy_from_slice(b"%FDF-");
+        Ok(bytes)
+    }
+
+    pub fn from_fdf(data: &[u8]) -> Result<Self, FormDataError> {
+        if !data.starts_with(b"%FDF-") {
+            return Err(FormDataError::Malformed("missing %FDF header".into()));
+        }
+        let mut bytes = data.to_vec();
+        bytes[..5].copy_from_slice(b"%PDF-");
+        let doc = xref_integrity::load_checked(&bytes)?;
+        let root = doc.trailer.get(b"Root")?.as_reference()?;
+        let fdf = resolve_dict(&doc, doc.get_dictionary(root)?.get(b"FDF")?)
+            .ok_or_else(|| FormDataError::Malformed("/FDF is not a dictionary".into()))?;
+
+        let mut data = FormData::default();
+        let mut visited = Visited::new();
+        if let Ok(Object::Array(fields)) = fdf.get(b"Fields") {
+            for field in fields {
+                collect_fdf(&doc, field, "", 0, &mut visited, &mut data.fields);
+            }
+        }
+        if let Some(cycle) = visited.cycles.first() {
+            return Err(FormDataError::Malformed(format!("field /Kids loop through {}", cycle)));
+        }
+        Ok(data)
+    }
+
+    pub fn to_xfdf(&self) -> String {
+        fn write(nodes: &[Node<'_>], depth: usize, out: &mut String) {
+            for node in nodes {
+                let indent = "  ".repeat(depth + 2);
+                out.push_str(&format!("{}<field name=\"{}\">\n", indent, escape(&node.name)));
+                for value in node.field.iter().flat_map(|field| &field.values) {
+                    out.push_str(&format!("{}  <value>{}</value>\n", indent, escape(value)));
+                }
+                write(&node.kids, depth + 1, out);
+                out.push_str(&format!("{}</field>\n", indent));
+            }
+        }
+
+        let mut out = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xfdf xmlns=\"{}\" xml:space=\"preserve\">\n  <fields>\n",
+            XFDF_NAMESPACE,
+        );
+        write(&hierarchy(&self.fields), 0, &mut out);
+        out.push_str("  </fields>\n</xfdf>\n");
+        out
+    }
+
+    pub fn from_xfdf(text: &str) -> Result<Self, FormDataError> {
+        let mut data = FormData::default();
+        let mut open: Vec<String> = Vec::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find('<') {
+            let end = rest[start..].find('>')
+                .ok_or_else(|| FormDataError::Malformed("unterminated tag".into()))? + start;
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+
+            if tag.starts_with("!DOCTYPE") || tag.starts_with("!ENTITY") {
+                return Err(FormDataError::Malformed("DTDs are not accepted".into()));
+            }
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            if let Some(closing) = tag.strip_prefix('/') {
+                if local_name(closing.trim()) == "field" {
+                    open.pop();
+                }
+                continue;
+            }
+
+            let self_closing = tag.ends_with('/');
+            match local_name(tag.split_whitespace().next().unwrap_or_default().trim_end_matches('/')) {
+                "field" => {
+                    let name = attribute(tag, "name")
+                        .ok_or_else(|| FormDataError::Malformed("<field> without a name".into()))?;
+                    if open.len() >= MAX_FIELD_DEPTH {
+                        return Err(FormDataError::Malformed("field nesting too deep".into()));
+                    }
+                    if !self_closing {
+                        open.push(name);
+                    }
+                }
+                "value" if !self_closing => {
+                    let close = rest.find("</")
+                        .ok_or_else(|| FormDataError::Malformed("unterminated <value>".into()))?;
+                    let value = unescape(&rest[..close]);
+                    rest = &rest[close..];
+                    if open.is_empty() {
+                        return Err(FormDataError::Malformed("<value> outside a <field>".into()));
+                    }
+                    let name = open.join(".");
+                    match data.fields.iter_mut().find(|field| field.name == name) {
+                        Some(field) => field.values.push(value),
+                        None => data.fields.push(FormField { name, kind: FieldKind::Text, values: vec![value] }),
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Fields arranged by name part, in first-seen order
+struct Node<'a> {
+    name: String,
+    field: Option<&'a FormField>,
+    kids: Vec<Node<'a>>,
+}
+
+fn hierarchy(fields: &[FormField]) -> Vec<Node<'_>> {
+    let mut roots: Vec<Node<'_>> = Vec::new();
+    for field in fields {
+        let mut level = &mut roots;
+        let parts: Vec<&str> = field.name.split('.').collect();
+        for (i, part) in parts.iter().enumerate() {
+            let index = match level.iter().position(|node| node.name == *part) {
+                Some(index) => index,
+                None => {
+                    level.push(Node { name: part.to_string(), field: None, kids: Vec::new() });
+                    level.len() - 1
+                }
+            };
+            if i + 1 == parts.len() {
+                level[index].field = Some(field);
+            }
+            level = &mut level[index].kids;
+        }
+    }
+    roots
+}
+
+fn to_objects(nodes: &[Node<'_>], fill: impl Fn(&Node<'_>) -> Dictionary + Copy) -> Vec<Object> {
+    nodes.iter()
+        .map(|node| {
+            let mut dict = fill(node);
+            dict.set("T", text_string(&node.name));
+            if !node.kids.is_empty() {
+                dict.set("Kids", to_objects(&node.kids, fill));
+            }
+            Object::Dictionary(dict)
+        })
+        .collect()
+}
+
+fn fdf_value(field: &FormField) -> Object {
+    match (field.kind, field.values.as_slice()) {
+        (FieldKind::Button, [value, ..]) => Object::Name(value.as_bytes().to_vec()),
+        (_, [value]) => text_string(value),
+        (_, values) => Object::Array(values.iter().map(|v| text_string(v)).collect()),
+    }
+}
+
+fn collect_fdf(
+    doc: &lopdf::Document,
+    field: &Object,
+    parent: &str,
+    depth: usize,
+    visited: &mut Visited,
+    out: &mut Vec<FormField>,
+) {
+    let Some(dict) = resolve_dict(doc, field) else { return };
+    if depth > MAX_FIELD_DEPTH {
+        return;
+    }
+    let entered = match field.as_reference() {
+        Ok(id) if !visited.enter(id) => return,
+        Ok(_) => true,
+        Err(_) => false,
+    };
+    let partial = match dict.get(b"T") {
+        Ok(Object::String(bytes, _)) => decode_pdf_string(bytes),
+        _ => String::new(),
+    };
+    let name = match (parent.is_empty(), partial.is_empty()) {
+        (true, _) => partial,
+        (false, true) => parent.to_string(),
+        (false, false) => format!("{}.{}", parent, partial),
+    };
+
+    if let Ok(value) = dict.get(b"V") {
+        if let Some((kind, values)) = field_value(doc, value) {
+            out.push(FormField { name: name.clone(), kind, values });
+        }
+    }
+    if let Ok(Object::Array(kids)) = dict.get(b"Kids") {
+        for kid in kids {
+            collect_fdf(doc, kid, &name, depth + 1, visited, out);
+        }
+    }
+    if entered {
+        visited.leave();
+    }
+}
+
+/// A field's /V as strings; `None` for values that are not plain data, like signatures
+pub(crate) fn field_value(doc: &lopdf::Document, value: &Object) -> Option<(FieldKind, Vec<String>)> {
+    match value {
+        Object::String(bytes, _) => Some((FieldKind::Text, vec![decode_pdf_string(bytes)])),
+        Object::Name(name) => Some((FieldKind::Button, vec![String::from_utf8_lossy(name).into_owned()])),
+        Object::Array(items) => Some((
+            FieldKind::Choice,
+            items.iter()
+                .filter_map(|item| match item {
+                    Object::String(bytes, _) => Some(decode_pdf_string(bytes)),
+                    _ => None,
+                })
+                .collect(),
+        )),
+        Object::Reference(id) => match doc.get_object(*id).ok()? {
+            Object::Stream(stream) => {
+                // Long text values may be stored as streams
+                let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                Some((FieldKind::Text, vec![decode_pdf_string(&content)]))
+            }
+            Object::Dictionary(_) => None,
+            other => field_value(doc, other),
+        },
+        _ => None,
+    }
+}
+
+/// PDF text string: literal when ASCII, UTF-16BE with byte order mark otherwise
+pub(crate) fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        Object::string_literal(text)
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        Object::String(bytes, lopdf::StringFormat::Hexadecimal)
+    }
+}
+
+fn local_name(tag: &str) -> &str {
+    tag.rsplit(':').next().unwrap_or(tag)
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = Regex::new(&format!(r#"\s{}\s*=\s*(?:"([^"]*)"|'([^']*)')"#, regex::escape(name))).ok()?;
+    let captures = pattern.captures(tag)?;
+    captures.get(1).or_else(|| captures.get(2)).map(|m| unescape(m.as_str()))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else { break };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> FormData {
+        FormData {
+            fields: vec![
+                FormField { name: "name".into(), kind: FieldKind::Text, values: vec!["Jürgen & Co <GmbH>".into()] },
+                FormField { name: "address.city".into(), kind: FieldKind::Text, values: vec!["Köln".into()] },
+                FormField { name: "address.zip".into(), kind: FieldKind::Text, values: vec!["50667".into()] },
+                FormField { name: "agree".into(), kind: FieldKind::Button, values: vec!["Yes".into()] },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_fdf_round_trip() {
+        let bytes = sample().to_fdf().unwrap();
+        assert!(bytes.starts_with(b"%FDF-1.2"));
+        assert_eq!(FormData::parse(&bytes).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_xfdf_round_trip() {
+        let xml = sample().to_xfdf();
+        assert!(xml.contains("<field name=\"address\">"));
+        assert!(xml.contains("Jürgen &amp; Co &lt;GmbH&gt;"));
+
+        let parsed = FormData::parse(xml.as_bytes()).unwrap();
+        // XFDF carries no types; importing takes them from the form
+        let names: Vec<&str> = parsed.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["name", "address.city", "address.zip", "agree"]);
+        assert_eq!(parsed.get("name").unwrap().values, vec!["Jürgen & Co <GmbH>"]);
+    }
+
+    #[test]
+    fn test_xfdf_multiple_values_and_dtd() {
+        let xml = r#"<?xml version="1.0"?><xfdf xmlns="http://ns.adobe.com/xfdf/"><fields>
+            <field name='colors'><value>red</value><value>blue&#x21;</value></field>
+            <field name="empty"/>
+        </fields></xfdf>"#;
+        let parsed = FormData::from_xfdf(xml).unwrap();
+        assert_eq!(parsed.fields.len(), 1);
+        assert_eq!(parsed.fields[0].values, vec!["red", "blue!"]);
+
+        let hostile = "<?xml version=\"1.0\"?><!DOCTYPE x [<!ENTITY e SYSTEM \"file:///etc/passwd\">]><xfdf/>";
+        assert!(matches!(FormData::from_xfdf(hostile), Err(FormDataError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(FormDataFormat::from_path(Path::new("out/data.XFDF")), Some(FormDataFormat::Xfdf));
+        assert_eq!(FormDataFormat::from_path(Path::new("data.fdf")), Some(FormDataFormat::Fdf));
+        assert_eq!(FormDataFormat::from_path(Path::new("data.json")), None);
+    }
+}
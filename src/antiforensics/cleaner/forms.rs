@@ -0,0 +1,545 @@
+//! Form Flattening and Form Data Transfer
+//! Author: kartik4091
+//! Created: 2025-06-04 22:14:09 UTC
+//! Flattening draws every visible widget's current appearance into its
+//! page and removes the AcroForm, so field names, hidden fields, scripts
+//! and submit targets go while the page looks the same. The values are
+//! exported first and returned with the report, ready to be written as
+//! FDF or XFDF and imported into a clean copy of the blank form.
+//!
+//! Appearance matrices other than the identity are not honoured when
+//! placing a widget; such widgets are rare outside rotated fields.
+
+use std::collections::{HashMap, HashSet};
+use lopdf::{Dictionary, Object, ObjectId, Stream};
+
+use super::form_data::{field_value, text_string, FieldKind, FormData, FormField};
+use crate::antiforensics::{scanner::unicode_spoof::decode_pdf_string, types::Document, utils::resolve_dict};
+
+/// Field hierarchies deeper than this are not followed
+const MAX_FIELD_DEPTH: usize = 32;
+
+/// Annotation flag marking a widget as hidden
+const HIDDEN_FLAG: i64 = 1 << 1;
+
+/// A field that holds a value, with the widgets showing it
+#[derive(Debug, Clone)]
+struct TerminalField {
+    id: ObjectId,
+    name: String,
+    /// /FT, inherited from ancestors when absent
+    field_type: Option<Vec<u8>>,
+    widgets: Vec<ObjectId>,
+}
+
+/// Every field object and the terminal fields among them
+#[derive(Debug, Default)]
+struct FieldTree {
+    all: Vec<ObjectId>,
+    terminals: Vec<TerminalField>,
+}
+
+/// What flattening changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlattenReport {
+    /// Field values as they were before flattening
+    pub data: FormData,
+    /// Widgets drawn into their page
+    pub flattened: usize,
+    /// Widgets removed without a trace: hidden, or without an appearance
+    pub dropped: usize,
+}
+
+/// What importing form data changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Fields given a value
+    pub filled: usize,
+    /// Names in the data that the form has no field for
+    pub unknown: Vec<String>,
+}
+
+/// Whether the document has an interactive form with fields
+pub fn has_form(doc: &Document) -> bool {
+    !fields(&doc.structure).all.is_empty()
+}
+
+/// Values of every field that has one; signature values are left out
+pub fn export_form_data(doc: &Document) -> FormData {
+    let mut data = FormData::default();
+    for field in fields(&doc.structure).terminals {
+        if field.field_type.as_deref() == Some(b"Sig") {
+            continue;
+        }
+        let Some(dict) = doc.dictionary(field.id) else { continue };
+        let Some((kind, values)) = dict.get(b"V").ok().and_then(|v| field_value(&doc.structure, v)) else { continue };
+        let kind = match field.field_type.as_deref() {
+            Some(b"Btn") => FieldKind::Button,
+            Some(b"Ch") => FieldKind::Choice,
+            _ => kind,
+        };
+        data.fields.push(FormField { name: field.name, kind, values });
+    }
+    data
+}
+
+/// Sets field values from `data`, matching fields by qualified name
+///
+/// Check boxes and radio buttons are switched to the matching appearance
+/// state. Text appearances are not regenerated; `/NeedAppearances` asks
+/// the viewer to do that, so import into a form that is not flattened
+/// afterwards.
+pub fn import_form_data(doc: &mut Document, data: &FormData) -> lopdf::Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let terminals: HashMap<String, TerminalField> = fields(&doc.structure).terminals.into_iter()
+        .map(|field| (field.name.clone(), field))
+        .collect();
+
+    for entry in &data.fields {
+        let Some(field) = terminals.get(&entry.name) else {
+            report.unknown.push(entry.name.clone());
+            continue;
+        };
+        match field.field_type.as_deref() {
+            Some(b"Sig") => {
+                report.unknown.push(entry.name.clone());
+                continue;
+            }
+            Some(b"Btn") => {
+                let state = entry.values.first().cloned().unwrap_or_else(|| "Off".into());
+                doc.structure.get_dictionary_mut(field.id)?.set("V", Object::Name(state.as_bytes().to_vec()));
+                for widget in &field.widgets {
+                    let has_state = doc.dictionary(*widget)
+                        .and_then(|w| appearance_states(&doc.structure, w))
+                        .is_some_and(|states| states.iter().any(|s| s == state.as_bytes()));
+                    let shown = if has_state { state.as_bytes().to_vec() } else { b"Off".to_vec() };
+                    doc.structure.get_dictionary_mut(*widget)?.set("AS", Object::Name(shown));
+                }
+            }
+            _ => {
+                let value = match entry.values.as_slice() {
+                    [value] => text_string(value),
+                    values => Object::Array(values.iter().map(|v| text_string(v)).collect()),
+                };
+                doc.structure.get_dictionary_mut(field.id)?.set("V", value);
+            }
+        }
+        report.filled += 1;
+    }
+
+    if report.filled > 0 {
+        if let Some(form) = acroform_mut(&mut doc.structure)? {
+            form.set("NeedAppearances", true);
+        }
+    }
+    Ok(report)
+}
+
+/// Draws widget appearances into their pages and removes the form
+pub fn flatten_forms(doc: &mut Document) -> lopdf::Result<FlattenReport> {
+    let mut report = FlattenReport { data: export_form_data(doc), ..Default::default() };
+    let tree = fields(&doc.structure);
+    if tree.all.is_empty() && acroform_mut(&mut doc.structure)?.is_none() {
+        return Ok(report);
+    }
+
+    let pages: Vec<ObjectId> = doc.pages().map(|(_, id)| id).collect();
+    let mut widgets_removed = HashSet::new();
+    for page_id in pages {
+        let mut placements = Vec::new();
+        let mut widgets = 0;
+        for annot in page_annotations(&doc.structure, page_id) {
+            let Some(widget) = doc.dictionary(annot).filter(|a| is_widget(a)) else { continue };
+            widgets += 1;
+            widgets_removed.insert(annot);
+            match placement(&doc.structure, widget) {
+                Some(placed) => placements.push(placed),
+                None => report.dropped += 1,
+            }
+        }
+        if widgets == 0 {
+            continue;
+        }
+
+        report.flattened += placements.len();
+        draw(&mut doc.structure, page_id, &placements)?;
+        remove_annotations(&mut doc.structure, page_id, &widgets_removed)?;
+    }
+
+    for id in widgets_removed.iter().chain(&tree.all) {
+        doc.structure.objects.remove(id);
+    }
+    let catalog = doc.catalog().ok_or(lopdf::Error::DictKey)?;
+    if let Ok(Object::Reference(form_id)) = catalog.get(b"AcroForm") {
+        let form_id = *form_id;
+        doc.structure.objects.remove(&form_id);
+    }
+    doc.catalog_mut().ok_or(lopdf::Error::DictKey)?.remove(b"AcroForm");
+    Ok(report)
+}
+
+/// Appearance stream of a widget and where it goes on the page
+struct Placement {
+    appearance: ObjectId,
+    /// `cm` operands mapping the appearance's bounding box onto the widget's rectangle
+    matrix: [f32; 6],
+}
+
+fn placement(doc: &lopdf::Document, widget: &Dictionary) -> Option<Placement> {
+    let flags = widget.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+    if flags & HIDDEN_FLAG != 0 {
+        return None;
+    }
+
+    let normal = resolve_dict(doc, widget.get(b"AP").ok()?)?.get(b"N").ok()?;
+    let appearance = match normal {
+        Object::Reference(id) if doc.get_object(*id).is_ok_and(|o| o.as_stream().is_ok()) => *id,
+        other => {
+            let state = widget.get(b"AS").and_then(Object::as_name).ok()?;
+            resolve_dict(doc, other)?.get(state).ok()?.as_reference().ok()?
+        }
+    };
+    let stream = doc.get_object(appearance).ok()?.as_stream().ok()?;
+
+    let rect = numbers(widget.get(b"Rect").ok()?)?;
+    let bbox = stream.dict.get(b"BBox").ok().and_then(numbers).unwrap_or(rect);
+    let (x1, y1, x2, y2) = (rect[0].min(rect[2]), rect[1].min(rect[3]), rect[0].max(rect[2]), rect[1].max(rect[3]));
+    let (bx1, by1, bx2, by2) = (bbox[0].min(bbox[2]), bbox[1].min(bbox[3]), bbox[0].max(bbox[2]), bbox[1].max(bbox[3]));
+    if bx2 - bx1 <= 0.0 || by2 - by1 <= 0.0 {
+        return None;
+    }
+    let sx = (x2 - x1) / (bx2 - bx1);
+    let sy = (y2 - y1) / (by2 - by1);
+    Some(Placement { appearance, matrix: [sx, 0.0, 0.0, sy, x1 - bx1 * sx, y1 - by1 * sy] })
+}
+
+/// Adds the appearances as XObjects and paints them after the page's own content
+fn draw(doc: &mut lopdf::Document, page_id: ObjectId, placements: &[Placement]) -> lopdf::Result<()> {
+    if placements.is_empty() {
+        return Ok(());
+    }
+
+    let mut names = Vec::new();
+    {
+        let xobjects = xobjects_mut(doc, page_id)?;
+        let mut counter = 0;
+        for placed in placements {
+            let name = loop {
+                counter += 1;
+                let candidate = format!("Flat{}", counter);
+                if !xobjects.has(candidate.as_bytes()) {
+                    break candidate;
+                }
+            };
+            xobjects.set(name.as_bytes(), placed.appearance);
+            names.push(name);
+        }
+    }
+    for placed in placements {
+        if let Ok(Object::Stream(stream)) = doc.get_object_mut(placed.appearance) {
+            stream.dict.set("Type", "XObject");
+            stream.dict.set("Subtype", "Form");
+        }
+    }
+
+    let mut painted = String::from("Q\n");
+    for (name, placed) in names.iter().zip(placements) {
+        let m = placed.matrix;
+        painted.push_str(&format!("q {} {} {} {} {} {} cm /{} Do Q\n", m[0], m[1], m[2], m[3], m[4], m[5], name));
+    }
+    // Bracket the existing content so its graphics state cannot leak into the widgets
+    let open = doc.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+    let close = doc.add_object(Stream::new(Dictionary::new(), painted.into_bytes()));
+
+    let page = doc.get_dictionary_mut(page_id)?;
+    let mut contents = vec![Object::Reference(open)];
+    match page.get(b"Contents") {
+        Ok(Object::Array(items)) => contents.extend(items.iter().cloned()),
+        Ok(other) => contents.push(other.clone()),
+        Err(_) => {}
+    }
+    contents.push(Object::Reference(close));
+    page.set("Contents", contents);
+    Ok(())
+}
+
+/// The page's /Resources /XObject dictionary, created or copied down as needed
+fn xobjects_mut(doc: &mut lopdf::Document, page_id: ObjectId) -> lopdf::Result<&mut Dictionary> {
+    // Inherited resources are copied so the page's own entry does not hide them
+    if !doc.get_dictionary(page_id)?.has(b"Resources") {
+        let inherited = inherited_resources(doc, page_id).unwrap_or_default();
+        doc.get_dictionary_mut(page_id)?.set("Resources", inherited);
+    }
+    let resources_id = match doc.get_dictionary(page_id)?.get(b"Resources") {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+    let xobject_id = {
+        let resources = match resources_id {
+            Some(id) => doc.get_dictionary(id)?,
+            None => doc.get_dictionary(page_id)?.get(b"Resources")?.as_dict()?,
+        };
+        match resources.get(b"XObject") {
+            Ok(Object::Reference(id)) => Some(*id),
+            _ => None,
+        }
+    };
+    if let Some(id) = xobject_id {
+        return doc.get_dictionary_mut(id);
+    }
+
+    let resources = match resources_id {
+        Some(id) => doc.get_dictionary_mut(id)?,
+        None => doc.get_dictionary_mut(page_id)?.get_mut(b"Resources")?.as_dict_mut()?,
+    };
+    if !matches!(resources.get(b"XObject"), Ok(Object::Dictionary(_))) {
+        resources.set("XObject", Dictionary::new());
+    }
+    resources.get_mut(b"XObject")?.as_dict_mut()
+}
+
+fn inherited_resources(doc: &lopdf::Document, page_id: ObjectId) -> Option<Dictionary> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    for _ in 0..MAX_FIELD_DEPTH {
+        node = doc.get_dictionary(node.get(b"Parent").ok()?.as_reference().ok()?).ok()?;
+        if let Ok(resources) = node.get(b"Resources") {
+            return resolve_dict(doc, resources).cloned();
+        }
+    }
+    None
+}
+
+/// Indirect annotations of a page
+fn page_annotations(doc: &lopdf::Document, page_id: ObjectId) -> Vec<ObjectId> {
+    let annots = doc.get_dictionary(page_id).ok().and_then(|page| match page.get(b"Annots") {
+        Ok(Object::Reference(id)) => doc.get_object(*id).and_then(Object::as_array).ok(),
+        Ok(Object::Array(items)) => Some(items),
+        _ => None,
+    });
+    annots.into_iter().flatten().filter_map(|a| a.as_reference().ok()).collect()
+}
+
+fn remove_annotations(doc: &mut lopdf::Document, page_id: ObjectId, removed: &HashSet<ObjectId>) -> lopdf::Result<()> {
+    let annots_id = match doc.get_dictionary(page_id)?.get(b"Annots") {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+    let annots = match annots_id {
+        Some(id) => doc.get_object_mut(id)?.as_array_mut()?,
+        None => doc.get_dictionary_mut(page_id)?.get_mut(b"Annots")?.as_array_mut()?,
+    };
+    annots.retain(|a| !a.as_reference().is_ok_and(|id| removed.contains(&id)));
+    if annots.is_empty() {
+        doc.get_dictionary_mut(page_id)?.remove(b"Annots");
+        if let Some(id) = annots_id {
+            doc.objects.remove(&id);
+        }
+    }
+    Ok(())
+}
+
+fn is_widget(annot: &Dictionary) -> bool {
+    annot.get(b"Subtype").and_then(Object::as_name).is_ok_and(|s| s == b"Widget")
+}
+
+/// Names of a widget's normal appearance states
+fn appearance_states(doc: &lopdf::Document, widget: &Dictionary) -> Option<Vec<Vec<u8>>> {
+    let normal = resolve_dict(doc, widget.get(b"AP").ok()?)?.get(b"N").ok()?;
+    Some(resolve_dict(doc, normal)?.iter().map(|(key, _)| key.clone()).collect())
+}
+
+fn fields(doc: &lopdf::Document) -> FieldTree {
+    let mut tree = FieldTree::default();
+    let root = doc.trailer.get(b"Root").and_then(Object::as_reference).and_then(|id| doc.get_dictionary(id));
+    let form = root.ok().and_then(|root| resolve_dict(doc, root.get(b"AcroForm").ok()?));
+    if let Some(Ok(Object::Array(items))) = form.map(|form| form.get(b"Fields")) {
+        for item in items {
+            if let Ok(id) = item.as_reference() {
+                collect(doc, id, "", None, 0, &mut tree);
+            }
+        }
+    }
+    tree
+}
+
+fn collect(
+    doc: &lopdf::Document,
+    id: ObjectId,
+    parent: &str,
+    inherited_type: Option<&[u8]>,
+    depth: usize,
+    tree: &mut FieldTree,
+) {
+    if depth > MAX_FIELD_DEPTH || tree.all.contains(&id) {
+        return;
+    }
+    let Ok(dict) = doc.get_dictionary(id) else { return };
+    tree.all.push(id);
+
+    let partial = match dict.get(b"T") {
+        Ok(Object::String(bytes, _)) => decode_pdf_string(bytes),
+        _ => String::new(),
+    };
+    let name = match (parent.is_empty(), partial.is_empty()) {
+        (true, _) => partial,
+        (false, true) => parent.to_string(),
+        (false, false) => format!("{}.{}", parent, partial),
+    };
+    let field_type = dict.get(b"FT").and_then(Object::as_name).ok().or(inherited_type);
+
+    // Kids without a /T are widgets of this field; kids with one are fields
+    let kids: Vec<ObjectId> = match dict.get(b"Kids") {
+        Ok(Object::Array(kids)) => kids.iter().filter_map(|k| k.as_reference().ok()).collect(),
+        _ => Vec::new(),
+    };
+    let (fields, widgets): (Vec<ObjectId>, Vec<ObjectId>) = kids.into_iter()
+        .partition(|kid| doc.get_dictionary(*kid).is_ok_and(|k| k.has(b"T")));
+    if !fields.is_empty() {
+        for kid in fields {
+            collect(doc, kid, &name, field_type, depth + 1, tree);
+        }
+        return;
+    }
+
+    let mut widgets = widgets;
+    if is_widget(dict) {
+        widgets.push(id);
+    }
+    tree.all.extend(widgets.iter().filter(|w| **w != id));
+    tree.terminals.push(TerminalField { id, name, field_type: field_type.map(<[u8]>::to_vec), widgets });
+}
+
+fn acroform_mut(doc: &mut lopdf::Document) -> lopdf::Result<Option<&mut Dictionary>> {
+    let root_id = doc.trailer.get(b"Root")?.as_reference()?;
+    let form_id = match doc.get_dictionary(root_id)?.get(b"AcroForm") {
+        Ok(Object::Reference(id)) => *id,
+        Ok(Object::Dictionary(_)) => {
+            return Ok(doc.get_dictionary_mut(root_id)?.get_mut(b"AcroForm")?.as_dict_mut().ok());
+        }
+        _ => return Ok(None),
+    };
+    Ok(doc.get_dictionary_mut(form_id).ok())
+}
+
+fn numbers(object: &Object) -> Option<[f32; 4]> {
+    let Object::Array(items) = object else { return None };
+    let values: Vec<f32> = items.iter()
+        .filter_map(|item| match item {
+            Object::Integer(i) => Some(*i as f32),
+            Object::Real(r) => Some(*r as f32),
+            _ => None,
+        })
+        .collect();
+    values.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// One page with a text field inside a group, a check box and a hidden field
+    fn form() -> Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.new_object_id();
+
+        let text_ap = doc.add_object(Stream::new(
+            dictionary! { "Subtype" => "Form", "BBox" => vec![0.into(), 0.into(), 100.into(), 20.into()] },
+            b"BT /Helv 12 Tf 2 5 Td (Ada) Tj ET".to_vec(),
+        ));
+        let name = doc.add_object(dictionary! {
+            "Type" => "Annot", "Subtype" => "Widget", "P" => page_id,
+            "T" => Object::string_literal("name"), "FT" => "Tx", "V" => Object::string_literal("Ada"),
+            "Rect" => vec![50.into(), 700.into(), 150.into(), 720.into()],
+            "AP" => dictionary! { "N" => text_ap },
+        });
+        let person = doc.add_object(dictionary! { "T" => Object::string_literal("person"), "Kids" => vec![name.into()] });
+        doc.get_dictionary_mut(name).unwrap().set("Parent", person);
+
+        let on = doc.add_object(Stream::new(dictionary! { "BBox" => vec![0.into(), 0.into(), 10.into(), 10.into()] }, b"0 0 10 10 re f".to_vec()));
+        let off = doc.add_object(Stream::new(dictionary! { "BBox" => vec![0.into(), 0.into(), 10.into(), 10.into()] }, Vec::new()));
+        let agree = doc.add_object(dictionary! {
+            "Type" => "Annot", "Subtype" => "Widget", "P" => page_id,
+            "T" => Object::string_literal("agree"), "FT" => "Btn", "V" => "Off", "AS" => "Off",
+            "Rect" => vec![50.into(), 650.into(), 60.into(), 660.into()],
+            "AP" => dictionary! { "N" => dictionary! { "Yes" => on, "Off" => off } },
+        });
+        let secret = doc.add_object(dictionary! {
+            "Type" => "Annot", "Subtype" => "Widget", "P" => page_id, "F" => 2,
+            "T" => Object::string_literal("tracking"), "FT" => "Tx", "V" => Object::string_literal("id-4711"),
+            "Rect" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+        });
+
+        let content = doc.add_object(Stream::new(Dictionary::new(), b"0 0 1 rg".to_vec()));
+        doc.objects.insert(page_id, Object::Dictionary(dictionary! {
+            "Type" => "Page", "Parent" => pages_id, "Contents" => content,
+            "Annots" => vec![name.into(), agree.into(), secret.into()],
+        }));
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1,
+            "Resources" => dictionary! { "Font" => dictionary! {} },
+        }));
+        let form = doc.add_object(dictionary! { "Fields" => vec![person.into(), agree.into(), secret.into()] });
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id, "AcroForm" => form });
+        doc.trailer.set("Root", catalog);
+        Document::from_pdf("form.pdf".into(), doc)
+    }
+
+    #[test]
+    fn test_export_uses_qualified_names() {
+        let data = export_form_data(&form());
+        assert_eq!(data.get("person.name").unwrap().values, vec!["Ada"]);
+        assert_eq!(data.get("agree").unwrap().kind, FieldKind::Button);
+        assert_eq!(data.get("tracking").unwrap().values, vec!["id-4711"]);
+    }
+
+    #[test]
+    fn test_flatten_draws_visible_widgets_and_removes_form() {
+        let mut doc = form();
+        let report = flatten_forms(&mut doc).unwrap();
+        assert_eq!((report.flattened, report.dropped), (2, 1));
+        assert_eq!(report.data.fields.len(), 3);
+
+        assert!(!has_form(&doc));
+        assert!(!doc.catalog().unwrap().has(b"AcroForm"));
+        let (_, page_id) = doc.pages().next().unwrap();
+        let page = doc.dictionary(page_id).unwrap();
+        assert!(!page.has(b"Annots"));
+
+        // Inherited fonts stay visible next to the new XObjects
+        let resources = page.get(b"Resources").unwrap().as_dict().unwrap();
+        assert!(resources.has(b"Font"));
+        assert_eq!(resources.get(b"XObject").unwrap().as_dict().unwrap().len(), 2);
+        let content = String::from_utf8(doc.page_content(page_id).unwrap()).unwrap();
+        assert!(content.contains("q 1 0 0 1 50 700 cm /Flat1 Do Q"));
+        assert!(!doc.objects().any(|(_, o)| o.as_dict().is_ok_and(|d| d.has(b"T"))));
+    }
+
+    #[test]
+    fn test_import_into_clean_template() {
+        let filled = form();
+        let data = export_form_data(&filled);
+
+        let mut template = form();
+        let mut blank = FormData::default();
+        blank.fields.push(FormField { name: "person.name".into(), kind: FieldKind::Text, values: vec![String::new()] });
+        import_form_data(&mut template, &blank).unwrap();
+
+        let mut data = data;
+        data.fields.retain(|f| f.name != "tracking");
+        data.fields.push(FormField { name: "missing".into(), kind: FieldKind::Text, values: vec!["x".into()] });
+        data.fields.iter_mut().find(|f| f.name == "agree").unwrap().values = vec!["Yes".into()];
+
+        let report = import_form_data(&mut template, &data).unwrap();
+        assert_eq!(report.filled, 2);
+        assert_eq!(report.unknown, vec!["missing"]);
+
+        let imported = export_form_data(&template);
+        assert_eq!(imported.get("person.name").unwrap().values, vec!["Ada"]);
+        assert_eq!(imported.get("agree").unwrap().values, vec!["Yes"]);
+        let agree = fields(&template.structure).terminals.into_iter().find(|f| f.name == "agree").unwrap();
+        let widget = template.dictionary(agree.widgets[0]).unwrap();
+        assert_eq!(widget.get(b"AS").unwrap().as_name().unwrap(), b"Yes");
+    }
+}
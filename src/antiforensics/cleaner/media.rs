@@ -0,0 +1,215 @@
+//! 3D and Multimedia Removal
+//! Author: kartik4091
+//! Created: 2025-06-04 19:20:38 UTC
+//! Removes media annotations found by the media scanner, or replaces them
+//! with a static stamp showing their poster appearance, so the page keeps
+//! its look while the model, movie or sound and any attached scripts go.
+
+use std::collections::HashSet;
+use lopdf::{Object, ObjectId};
+use serde::{Serialize, Deserialize};
+
+use super::{
+    scope::{object_pages, CleanTarget, CleaningScope},
+    CleanerError,
+    Result,
+};
+use crate::antiforensics::{
+    scanner::media::{find_media, MediaItem, MediaKind},
+    types::Document,
+    ArtifactType,
+};
+
+/// What happens to a media annotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MediaAction {
+    /// Delete the annotation and its payloads
+    Remove,
+    /// Keep the poster as a static stamp; remove when there is no poster
+    #[default]
+    ReplaceWithPoster,
+}
+
+/// Keys that make an annotation interactive media rather than a picture
+const MEDIA_KEYS: [&[u8]; 10] = [
+    b"3DD", b"3DV", b"3DA", b"3DI", b"RichMediaContent", b"RichMediaSettings",
+    b"Movie", b"Sound", b"A", b"AA",
+];
+
+/// Media cleaning configuration
+#[derive(Debug, Clone)]
+pub struct MediaCleaner {
+    action: MediaAction,
+    /// Kinds handled; others are left in place
+    kinds: HashSet<MediaKind>,
+    /// Annotations outside the scope are left in place
+    scope: CleaningScope,
+}
+
+/// What media cleaning changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaCleanReport {
+    /// Annotations deleted
+    pub removed: Vec<ObjectId>,
+    /// Annotations turned into poster stamps
+    pub replaced: Vec<ObjectId>,
+    /// Payload and script streams deleted
+    pub payloads_removed: usize,
+}
+
+impl MediaCleaner {
+    pub fn new(action: MediaAction) -> Self {
+        Self {
+            action,
+            kinds: [MediaKind::ThreeD, MediaKind::RichMedia, MediaKind::Movie, MediaKind::Sound, MediaKind::Screen]
+                .into_iter()
+                .collect(),
+            scope: CleaningScope::default(),
+        }
+    }
+
+    /// Restricts cleaning to annotations inside `scope`
+    pub fn with_scope(mut self, scope: CleaningScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Restricts cleaning to the given kinds
+    pub fn with_kinds(mut self, kinds: impl IntoIterator<Item = MediaKind>) -> Self {
+        self.kinds = kinds.into_iter().collect();
+        self
+    }
+
+    pub fn clean(&self, doc: &mut Document) -> Result<MediaCleanReport> {
+        let mut report = MediaCleanReport::default();
+        let pages = if self.scope.is_unrestricted() { Default::default() } else { object_pages(&doc.structure) };
+        let items: Vec<MediaItem> = find_media(&doc.structure).into_iter()
+            .filter(|i| self.kinds.contains(&i.kind))
+            .filter(|i| self.scope.allows(&CleanTarget {
+                location: &format!("/Annots/{} {} R", i.annotation.0, i.annotation.1),
+                artifact_type: if i.scripts.is_empty() { ArtifactType::Content } else { ArtifactType::JavaScript },
+                object: Some(i.annotation),
+                page: pages.get(&i.annotation).copied(),
+            }))
+            .collect();
+
+        for item in &items {
+            for id in item.payloads.iter().chain(&item.scripts) {
+                if doc.structure.objects.remove(id).is_some() {
+                    report.payloads_removed += 1;
+                }
+            }
+
+            if self.action == MediaAction::ReplaceWithPoster && item.poster.is_some() {
+                to_stamp(doc, item.annotation)?;
+                report.replaced.push(item.annotation);
+            } else {
+                doc.structure.objects.remove(&item.annotation);
+                report.removed.push(item.annotation);
+            }
+        }
+
+        if !report.removed.is_empty() {
+            let removed: HashSet<ObjectId> = report.removed.iter().copied().collect();
+            unlink_annotations(doc, &removed);
+        }
+        Ok(report)
+    }
+}
+
+impl Default for MediaCleaner {
+    fn default() -> Self {
+        Self::new(MediaAction::default())
+    }
+}
+
+/// Turns a media annotation into a stamp that only shows its appearance
+fn to_stamp(doc: &mut Document, id: ObjectId) -> Result<()> {
+    let annotation = doc.structure.get_object_mut(id)
+        .and_then(Object::as_dict_mut)
+        .map_err(|e| CleanerError::Internal(format!("media annotation {:?}: {}", id, e)))?;
+    for key in MEDIA_KEYS {
+        annotation.remove(key);
+    }
+    annotation.set("Subtype", Object::Name(b"Stamp".to_vec()));
+    // Read-only and locked, so viewers neither activate nor move it
+    annotation.set("F", 4_i64 | 64 | 128);
+    Ok(())
+}
+
+/// Drops references to deleted annotations from page /Annots arrays
+fn unlink_annotations(doc: &mut Document, removed: &HashSet<ObjectId>) {
+    let keep = |object: &Object| !matches!(object, Object::Reference(id) if removed.contains(id));
+
+    let pages: Vec<ObjectId> = doc.pages().map(|(_, id)| id).collect();
+    for page in pages {
+        let annots = match doc.dictionary(page).map(|p| p.get(b"Annots")) {
+            Some(Ok(Object::Reference(array))) => Some(*array),
+            _ => None,
+        };
+        let array = match annots {
+            Some(array) => doc.object_mut(array),
+            None => doc.dictionary_mut(page).and_then(|p| p.get_mut(b"Annots").ok()),
+        };
+        if let Some(Object::Array(items)) = array {
+            items.retain(keep);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antiforensics::scanner::media::tests::media_document;
+
+    fn document() -> Document {
+        Document::from_pdf("media.pdf".into(), media_document())
+    }
+
+    fn annots(doc: &Document) -> Vec<Object> {
+        let (_, page) = doc.pages().next().unwrap();
+        doc.dictionary(page).unwrap().get(b"Annots").unwrap().as_array().unwrap().clone()
+    }
+
+    #[test]
+    fn test_poster_replaces_media() {
+        let mut doc = document();
+        let report = MediaCleaner::default().clean(&mut doc).unwrap();
+
+        // The 3D annotation has a poster, the sound annotation does not
+        assert_eq!(report.replaced.len(), 1);
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!(report.payloads_removed, 3);
+
+        let stamp = doc.dictionary(report.replaced[0]).unwrap();
+        assert_eq!(stamp.get(b"Subtype").unwrap().as_name().unwrap(), b"Stamp");
+        assert!(!stamp.has(b"3DD"));
+        assert!(stamp.has(b"AP"));
+        assert_eq!(annots(&doc).len(), 1);
+        assert!(find_media(&doc.structure).is_empty());
+    }
+
+    #[test]
+    fn test_remove_only_selected_kinds() {
+        let mut doc = document();
+        let report = MediaCleaner::new(MediaAction::Remove)
+            .with_kinds([MediaKind::Sound])
+            .clean(&mut doc)
+            .unwrap();
+
+        assert_eq!(report.removed.len(), 1);
+        let remaining = find_media(&doc.structure);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].kind, MediaKind::ThreeD);
+    }
+
+    #[test]
+    fn test_scope_outside_media_pages_changes_nothing() {
+        let mut doc = document();
+        let scope = CleaningScope::from_filters(["pages=2-9".parse().unwrap()]);
+        let report = MediaCleaner::default().with_scope(scope).clean(&mut doc).unwrap();
+
+        assert_eq!(report, MediaCleanReport::default());
+        assert_eq!(find_media(&doc.structure).len(), 2);
+    }
+}
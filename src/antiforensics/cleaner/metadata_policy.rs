@@ -0,0 +1,95 @@
+//! Field-level metadata preservation
+//! Author: kartik4091
+//! Created: 2025-06-05 00:55:02 UTC
+//! Metadata cleaning is all or nothing by default: the XMP packet and the
+//! catalog's /Lang go, and the identifying Info keys with them. Some of it
+//! is worth keeping, though: screen readers announce the document title
+//! and pick their voice by its language. A [`MetadataPolicy`] names the
+//! fields to keep, stripping every other one, and fields to drop even so.
+//!
+//! Fields are named by Info key (`Title`), XMP property (`dc:title`) or
+//! catalog key (`Lang`, `MarkInfo`), ignoring case. A field present in
+//! more than one place is named by any of its names, so `Title` keeps
+//! `dc:title` too and `Lang` keeps `dc:language`.
+
+use serde::{Serialize, Deserialize};
+
+use crate::antiforensics::analyzer::info_xmp::MetadataField;
+
+/// Which metadata fields cleaning keeps
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataPolicy {
+    /// Fields kept; when any are given, all other metadata is stripped
+    pub keep: Vec<String>,
+    /// Fields stripped even when kept or otherwise left alone
+    pub drop: Vec<String>,
+}
+
+impl MetadataPolicy {
+    /// Keeps `fields` and strips the rest
+    pub fn keep_only<S: Into<String>>(fields: impl IntoIterator<Item = S>) -> Self {
+        Self { keep: fields.into_iter().map(Into::into).collect(), drop: Vec::new() }
+    }
+
+    pub fn with_drop<S: Into<String>>(mut self, fields: impl IntoIterator<Item = S>) -> Self {
+        self.drop.extend(fields.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether only the kept fields survive, rather than all metadata going
+    pub fn is_selective(&self) -> bool {
+        !self.keep.is_empty()
+    }
+
+    /// Whether the policy changes nothing about the default all-or-nothing cleaning
+    pub fn is_default(&self) -> bool {
+        self.keep.is_empty() && self.drop.is_empty()
+    }
+
+    /// Whether the field `name` is kept
+    pub fn keeps(&self, name: &str) -> bool {
+        names(&self.keep, name) && !self.drops(name)
+    }
+
+    /// Whether the field `name` is dropped explicitly
+    pub fn drops(&self, name: &str) -> bool {
+        names(&self.drop, name)
+    }
+}
+
+/// Whether any of `fields` names the field `name`
+fn names(fields: &[String], name: &str) -> bool {
+    fields.iter().any(|field| aliases(field).iter().any(|alias| alias.eq_ignore_ascii_case(name)))
+}
+
+/// Every name of the field `field` names
+fn aliases(field: &str) -> Vec<&str> {
+    let field = field.trim();
+    let groups = MetadataField::ALL.iter()
+        .map(|f| std::iter::once(f.info_key()).chain(f.xmp_names().iter().copied()).collect::<Vec<_>>())
+        .chain([vec!["Lang", "dc:language"]]);
+    for group in groups {
+        if group.iter().any(|name| name.eq_ignore_ascii_case(field)) {
+            return group;
+        }
+    }
+    vec![field]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_match_by_any_name() {
+        let policy = MetadataPolicy::keep_only(["title", "Lang", "dc:creator"]).with_drop(["Author"]);
+        assert!(policy.is_selective());
+        assert!(policy.keeps("Title") && policy.keeps("dc:title"));
+        assert!(policy.keeps("Lang") && policy.keeps("dc:language"));
+        // Dropping wins over keeping
+        assert!(!policy.keeps("dc:creator") && policy.drops("Author"));
+        assert!(!policy.keeps("Producer") && !policy.drops("Producer"));
+        assert!(!policy.keeps("xmp:CreatorTool"));
+        assert!(MetadataPolicy::default().is_default());
+    }
+}
@@ -19,14 +19,46 @@ use serde::{Serialize, Deserialize};
 use tracing::{info, warn, error, debug, instrument};
 use rand::{Rng, rngs::OsRng};
 
+pub mod attachment_normalizer;
+pub mod attachments;
+pub mod dates;
+pub mod document_id;
 pub mod file_cleaner;
+pub mod form_data;
+pub mod forms;
+pub mod media;
 pub mod metadata_cleaner;
+pub mod metadata_policy;
+pub mod platform_metadata;
+pub mod portfolio;
+pub mod redact;
+pub mod renumber;
+pub mod scope;
 pub mod secure_delete;
+pub mod syntax_normalizer;
+pub mod timestamps;
+pub mod tracking;
 
 pub use self::{
+    attachment_normalizer::{AttachmentDates, AttachmentNormalizeReport, AttachmentNormalizer},
+    attachments::{AttachmentCleanReport, AttachmentPolicy},
+    dates::{DateNormalizeReport, DateNormalizer},
+    document_id::{DocumentIdRegenerator, IdRegeneration},
     file_cleaner::FileCleaner,
+    form_data::{FieldKind, FormData, FormDataError, FormDataFormat, FormField},
+    forms::{FlattenReport, ImportReport},
+    media::{MediaAction, MediaCleaner, MediaCleanReport},
     metadata_cleaner::MetadataCleaner,
-    secure_delete::SecureDelete,
+    metadata_policy::MetadataPolicy,
+    platform_metadata::{PlatformMetadataConfig, PlatformScrubReport},
+    portfolio::{PortfolioCleaner, PortfolioCleanReport},
+    redact::{Redaction, RedactionReport, TextRedactor},
+    renumber::{ObjectRenumberer, RenumberReport},
+    scope::{CleanTarget, CleaningScope, PageRange, ScopeFilter},
+    secure_delete::{SecureDelete, SecureDeleteConfig, WipeMethod},
+    syntax_normalizer::{SyntaxNormalizeReport, SyntaxNormalizer},
+    timestamps::{FileTimestamps, TimestampPolicy},
+    tracking::{TrackingAction, TrackingCleaner, TrackingCleanReport},
 };
 
 /// Cleaner configuration
@@ -280,7 +312,7 @@ impl BaseCleaner {
 impl Default for CleanerConfig {
     fn default() -> Self {
         Self {
-            max_concurrent_ops: 4,
+            max_concurrent_ops: crate::concurrency::ConcurrencyConfig::default().limits().clean,
             timeout: Duration::from_secs(300), // 5 minutes
             overwrite_patterns: vec![
                 vec![0x00], // Zeros
@@ -0,0 +1,182 @@
+//! Platform Metadata Scrubbing
+//! Author: kartik4091
+//! Created: 2025-06-04 17:48:31 UTC
+//! Removes metadata the filesystem keeps beside a file's content:
+//! NTFS alternate data streams (including the Zone.Identifier
+//! mark-of-the-web) on Windows, and extended attributes on Linux and
+//! macOS, where resource forks, quarantine flags and Finder info are
+//! stored as `com.apple.*` attributes.
+
+use std::{collections::HashSet, io, path::Path};
+use serde::{Serialize, Deserialize};
+
+/// Platform metadata scrubbing configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlatformMetadataConfig {
+    /// Scrub at all
+    pub enabled: bool,
+    /// Attributes / streams kept even when scrubbing
+    ///
+    /// Security labels and ACLs live in extended attributes on Linux;
+    /// removing them changes access control rather than hiding history.
+    pub keep: HashSet<String>,
+}
+
+impl Default for PlatformMetadataConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            keep: [
+                "security.selinux",
+                "security.capability",
+                "system.posix_acl_access",
+                "system.posix_acl_default",
+            ].iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+/// What scrubbing removed from one file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlatformScrubReport {
+    /// Names of removed attributes or streams
+    pub removed: Vec<String>,
+    /// Attributes or streams that could not be removed, with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+/// Removes alternate streams / extended attributes from `path`
+pub fn scrub(path: &Path, config: &PlatformMetadataConfig) -> io::Result<PlatformScrubReport> {
+    let mut report = PlatformScrubReport::default();
+    if !config.enabled {
+        return Ok(report);
+    }
+
+    for name in list(path)? {
+        if config.keep.contains(&name) {
+            continue;
+        }
+        match remove(path, &name) {
+            Ok(()) => report.removed.push(name),
+            Err(e) => report.failed.push((name, e.to_string())),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(unix)]
+fn list(path: &Path) -> io::Result<Vec<String>> {
+    match xattr::list(path) {
+        Ok(names) => Ok(names.map(|n| n.to_string_lossy().into_owned()).collect()),
+        // Filesystems without xattr support have nothing to scrub
+        Err(e) if e.raw_os_error() == Some(UNSUPPORTED) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(target_os = "linux")]
+const UNSUPPORTED: i32 = 95; // EOPNOTSUPP
+#[cfg(all(unix, not(target_os = "linux")))]
+const UNSUPPORTED: i32 = 45; // ENOTSUP on macOS and the BSDs
+
+#[cfg(unix)]
+fn remove(path: &Path, name: &str) -> io::Result<()> {
+    xattr::remove(path, name)
+}
+
+/// Named streams of a file, without the unnamed `::$DATA` stream
+#[cfg(windows)]
+fn list(path: &Path) -> io::Result<Vec<String>> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::{
+        Foundation::{ERROR_HANDLE_EOF, INVALID_HANDLE_VALUE},
+        Storage::FileSystem::{FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard, WIN32_FIND_STREAM_DATA},
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut data: WIN32_FIND_STREAM_DATA = unsafe { std::mem::zeroed() };
+    // SAFETY: `wide` is NUL-terminated and `data` matches FindStreamInfoStandard
+    let handle = unsafe {
+        FindFirstStreamW(wide.as_ptr(), FindStreamInfoStandard, &mut data as *mut _ as *mut _, 0)
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        let error = io::Error::last_os_error();
+        return if error.raw_os_error() == Some(ERROR_HANDLE_EOF as i32) { Ok(Vec::new()) } else { Err(error) };
+    }
+
+    let mut names = Vec::new();
+    loop {
+        let len = data.cStreamName.iter().position(|&c| c == 0).unwrap_or(data.cStreamName.len());
+        let raw = String::from_utf16_lossy(&data.cStreamName[..len]);
+        // Entries look like ":Zone.Identifier:$DATA"; "::$DATA" is the file content
+        if let Some(name) = raw.strip_prefix(':').and_then(|s| s.strip_suffix(":$DATA")) {
+            if !name.is_empty() {
+                names.push(name.to_string());
+            }
+        }
+        // SAFETY: `handle` is a live stream search handle
+        if unsafe { FindNextStreamW(handle, &mut data as *mut _ as *mut _) } == 0 {
+            break;
+        }
+    }
+    // SAFETY: `handle` came from FindFirstStreamW and is closed once
+    unsafe { FindClose(handle) };
+    Ok(names)
+}
+
+#[cfg(windows)]
+fn remove(path: &Path, name: &str) -> io::Result<()> {
+    let mut stream = path.as_os_str().to_owned();
+    stream.push(":");
+    stream.push(name);
+    std::fs::remove_file(stream)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn list(_path: &Path) -> io::Result<Vec<String>> {
+    Ok(Vec::new())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn remove(_path: &Path, _name: &str) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_disabled_scrub_is_noop() {
+        let file = NamedTempFile::new().unwrap();
+        let config = PlatformMetadataConfig { enabled: false, ..Default::default() };
+        assert_eq!(scrub(file.path(), &config).unwrap(), PlatformScrubReport::default());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_user_xattrs_removed() {
+        let file = NamedTempFile::new().unwrap();
+        // tmpfs and some container filesystems reject user attributes
+        if xattr::set(file.path(), "user.origin", b"https://example.com").is_err() {
+            return;
+        }
+
+        let report = scrub(file.path(), &PlatformMetadataConfig::default()).unwrap();
+        assert_eq!(report.removed, vec!["user.origin"]);
+        assert!(xattr::get(file.path(), "user.origin").unwrap().is_none());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_zone_identifier_removed() {
+        let file = NamedTempFile::new().unwrap();
+        let mut zone = file.path().as_os_str().to_owned();
+        zone.push(":Zone.Identifier");
+        std::fs::write(&zone, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+
+        let report = scrub(file.path(), &PlatformMetadataConfig::default()).unwrap();
+        assert_eq!(report.removed, vec!["Zone.Identifier"]);
+    }
+}
@@ -0,0 +1,215 @@
+//! PDF Portfolio Cleaning
+//! Author: kartik4091
+//! Created: 2025-06-04 18:52:03 UTC
+//! Cleans every PDF embedded in a portfolio with the same policy used for
+//! the cover document, and optionally splits a portfolio into separate
+//! sanitized files. Non-PDF entries are left as they are.
+
+use std::path::{Path, PathBuf};
+use lopdf::{Object, ObjectId};
+
+use super::{CleanerError, Result};
+use crate::antiforensics::{
+    scanner::portfolio::{entries, is_portfolio, PortfolioEntry},
+    types::Document,
+};
+use crate::builder::CleanPolicy;
+use crate::paths;
+
+/// What cleaning a portfolio changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PortfolioCleanReport {
+    /// Embedded PDFs rewritten
+    pub cleaned: usize,
+    /// Entries left untouched, by name: non-PDFs and PDFs that did not parse
+    pub skipped: Vec<String>,
+    /// Removed entries, e.g. `portfolio:invoice.pdf/Root/OpenAction`
+    pub actions: Vec<String>,
+}
+
+/// Applies a clean policy inside portfolios
+#[derive(Debug, Clone)]
+pub struct PortfolioCleaner {
+    policy: CleanPolicy,
+    /// Portfolios nested inside portfolios are cleaned this deep
+    max_nesting: usize,
+}
+
+impl PortfolioCleaner {
+    pub fn new(policy: CleanPolicy) -> Self {
+        Self { policy, max_nesting: 4 }
+    }
+
+    /// Cleans every embedded PDF in place; the cover document itself is not touched
+    pub fn clean(&self, doc: &mut Document) -> Result<PortfolioCleanReport> {
+        let mut report = PortfolioCleanReport::default();
+        self.clean_level(doc, "", 0, &mut report)?;
+        Ok(report)
+    }
+
+    /// Writes every embedded PDF, cleaned, to `out_dir` and returns the written paths
+    ///
+    /// Output names come from the entry names reduced to their final
+    /// component, so names like `../../x.pdf` cannot escape `out_dir`.
+    pub fn split(&self, doc: &Document, out_dir: &Path) -> Result<Vec<PathBuf>> {
+        if !is_portfolio(&doc.structure) {
+            return Err(CleanerError::InvalidInput("document is not a portfolio".into()));
+        }
+        std::fs::create_dir_all(paths::extended(out_dir))?;
+
+        let mut written = Vec::new();
+        for (index, entry) in entries(&doc.structure).iter().enumerate().filter(|(_, e)| e.is_pdf) {
+            let mut inner = load_entry(doc, entry)?;
+            self.policy.apply_recorded(&mut inner).map_err(internal)?;
+            self.clean_level(&mut inner, "", 1, &mut PortfolioCleanReport::default())?;
+
+            let path = unique(out_dir.join(output_name(&entry.name, index)));
+            inner.structure.save(paths::extended(&path))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    fn clean_level(
+        &self,
+        doc: &mut Document,
+        prefix: &str,
+        nesting: usize,
+        report: &mut PortfolioCleanReport,
+    ) -> Result<()> {
+        if !is_portfolio(&doc.structure) || nesting > self.max_nesting {
+            return Ok(());
+        }
+
+        for entry in entries(&doc.structure) {
+            if !entry.is_pdf {
+                report.skipped.push(entry.name);
+                continue;
+            }
+            let Ok(mut inner) = load_entry(doc, &entry) else {
+                report.skipped.push(entry.name);
+                continue;
+            };
+
+            let entry_prefix = format!("{}portfolio:{}", prefix, entry.name);
+            let removed = self.policy.apply_recorded(&mut inner).map_err(internal)?;
+            report.actions.extend(removed.into_iter().map(|path| format!("{}{}", entry_prefix, path)));
+            self.clean_level(&mut inner, &format!("{}/", entry_prefix), nesting + 1, report)?;
+
+            let mut bytes = Vec::new();
+            inner.structure.save_to(&mut bytes)?;
+            replace_content(doc, entry.stream_id, bytes)?;
+            report.cleaned += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Default for PortfolioCleaner {
+    fn default() -> Self {
+        Self::new(CleanPolicy::default())
+    }
+}
+
+fn load_entry(doc: &Document, entry: &PortfolioEntry) -> Result<Document> {
+    let stream = doc.object(entry.stream_id)
+        .ok_or(lopdf::Error::ObjectNotFound)
+        .and_then(Object::as_stream)
+        .map_err(internal)?;
+    let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+    Document::parse(PathBuf::from(&entry.name), &content).map_err(internal)
+}
+
+/// Swaps in cleaned bytes and drops /Params entries that described the old ones
+fn replace_content(doc: &mut Document, stream_id: ObjectId, bytes: Vec<u8>) -> Result<()> {
+    let size = bytes.len() as i64;
+    let stream = doc.object_mut(stream_id)
+        .ok_or(lopdf::Error::ObjectNotFound)
+        .and_then(Object::as_stream_mut)
+        .map_err(internal)?;
+    stream.set_plain_content(bytes);
+    // Compression only fails for unsupported filters; plain content is still valid
+    let _ = stream.compress();
+
+    if let Ok(params) = stream.dict.get_mut(b"Params").and_then(Object::as_dict_mut) {
+        for key in [&b"CheckSum"[..], b"ModDate", b"CreationDate"] {
+            params.remove(key);
+        }
+        params.set("Size", size);
+    }
+    Ok(())
+}
+
+fn internal(error: lopdf::Error) -> CleanerError {
+    CleanerError::Internal(format!("portfolio entry: {}", error))
+}
+
+/// Final path component of an entry name, with a `.pdf` extension
+fn output_name(name: &str, index: usize) -> String {
+    // Entry names are often full paths from the authoring machine, with either separator
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let base: String = base.chars().filter(|c| !c.is_control() && *c != ':').collect();
+    let base = match base.trim() {
+        "" | "." | ".." => format!("entry-{}", index + 1),
+        trimmed => trimmed.to_string(),
+    };
+
+    if Path::new(&base).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")) {
+        base
+    } else {
+        format!("{}.pdf", base)
+    }
+}
+
+/// `path`, or `stem-2.pdf`, `stem-3.pdf`, ... when it already exists
+fn unique(path: PathBuf) -> PathBuf {
+    if !paths::extended(&path).exists() {
+        return path;
+    }
+    (2..)
+        .map(|n| paths::with_stem_suffix(&path, format!("-{}", n)))
+        .find(|candidate| !paths::extended(candidate).exists())
+        .expect("unbounded range yields a free name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antiforensics::scanner::portfolio::tests::portfolio;
+
+    fn document() -> Document {
+        Document::from_pdf("portfolio.pdf".into(), portfolio())
+    }
+
+    #[test]
+    fn test_embedded_pdfs_cleaned() {
+        let mut doc = document();
+        let report = PortfolioCleaner::default().clean(&mut doc).unwrap();
+
+        assert_eq!(report.cleaned, 1);
+        assert_eq!(report.skipped, vec!["notes.txt"]);
+        assert_eq!(report.actions, vec!["portfolio:invoice.pdf/Root/OpenAction"]);
+
+        let entry = &entries(&doc.structure)[0];
+        let inner = load_entry(&doc, entry).unwrap();
+        assert!(!inner.catalog().unwrap().has(b"OpenAction"));
+    }
+
+    #[test]
+    fn test_split_writes_sanitized_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = PortfolioCleaner::default().split(&document(), dir.path()).unwrap();
+
+        assert_eq!(written, vec![dir.path().join("invoice.pdf")]);
+        let inner = lopdf::Document::load(&written[0]).unwrap();
+        let root = inner.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        assert!(!inner.get_dictionary(root).unwrap().has(b"OpenAction"));
+    }
+
+    #[test]
+    fn test_output_names_cannot_escape() {
+        assert_eq!(output_name("../../etc/passwd", 0), "passwd.pdf");
+        assert_eq!(output_name("C:\\Users\\alice\\report.PDF", 0), "report.PDF");
+        assert_eq!(output_name("..", 2), "entry-3.pdf");
+    }
+}
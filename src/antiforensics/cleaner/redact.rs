@@ -0,0 +1,1034 @@
+//! Text redaction by query
+//! Author: kartik4091
+//! Created: 2025-06-05 00:48:19 UTC
+//! Redacts text by what it says rather than where it sits: every match of
+//! a literal or regex query (SSNs, account numbers, names) in the text a
+//! page draws is traced back to the glyphs that drew it. Those glyphs are
+//! cut from the content stream, not covered, and replaced by the same
+//! amount of positioning so the rest of the line stays where it was; a
+//! black box can be painted over the gap. The old content streams are
+//! deleted, so the text is gone from the file and not just from the page.
+//!
+//! Text is read from page content streams, the form XObjects they draw
+//! and annotation appearance streams, through each font's ToUnicode map
+//! where it has one and a predefined CJK CMap's charset where that leaves
+//! codes out; metadata is left to the other cleaners. Text in fonts whose
+//! codes do not decode to text cannot be searched, so a match there may
+//! remain; the report lists where, and callers treat that as a failure.
+//! Glyph boxes come from the font widths, with half an em assumed for
+//! fonts that declare none.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use lopdf::{Dictionary, Object, ObjectId, Stream};
+use regex::Regex;
+
+use crate::antiforensics::scanner::unicode_spoof::decode_pdf_string;
+use crate::antiforensics::text_encoding::PredefinedCMap;
+use crate::antiforensics::types::Document;
+use crate::core::content::{self, Operand, Operation};
+
+/// Affine matrix `[a b c d e f]` in PDF row-vector convention
+type Matrix = [f64; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Axis-aligned rectangle `[llx lly urx ury]` in default user space
+pub type Rect = [f64; 4];
+
+/// Glyph width assumed for fonts without widths, in thousandths of an em
+const DEFAULT_WIDTH: f64 = 500.0;
+
+/// Glyph box below and above the baseline, as fractions of the font size
+const DESCENT: f64 = 0.2;
+const ASCENT: f64 = 0.8;
+
+/// Codes a single /W or ToUnicode range may cover
+const MAX_RANGE: u32 = 0xFFFF;
+
+/// Forms drawn from forms followed before giving up
+const MAX_FORM_DEPTH: usize = 16;
+
+/// One redacted match
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redaction {
+    /// 1-based page number
+    pub page: u32,
+    /// Index of the query that matched, in the order queries were added
+    pub query: usize,
+    /// Area the removed glyphs covered, one rectangle per text operation
+    pub rects: Vec<Rect>,
+    pub glyphs: usize,
+}
+
+/// What redaction removed; matched text is deliberately not kept
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RedactionReport {
+    pub redactions: Vec<Redaction>,
+    /// Rewritten content streams deleted from the file
+    pub streams_removed: usize,
+    /// Form XObjects and appearance streams rewritten in place
+    pub forms_rewritten: usize,
+    /// Pages and streams with text that could not be searched, e.g. `page 2`
+    /// or `12 0 R`; a match may remain there
+    pub unsearchable: Vec<String>,
+}
+
+impl RedactionReport {
+    pub fn pages(&self) -> usize {
+        self.redactions.iter().map(|r| r.page).collect::<HashSet<_>>().len()
+    }
+
+    /// Whether every piece of text in the document was searched
+    pub fn is_complete(&self) -> bool {
+        self.unsearchable.is_empty()
+    }
+}
+
+/// Removes page text matching literal or regex queries
+#[derive(Debug, Clone, Default)]
+pub struct TextRedactor {
+    queries: Vec<Regex>,
+    draw_boxes: bool,
+}
+
+impl TextRedactor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a query matching `text` literally
+    pub fn text(mut self, text: &str) -> Self {
+        self.queries.push(Regex::new(&regex::escape(text)).expect("escaped text is a valid regex"));
+        self
+    }
+
+    pub fn regex(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.queries.push(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Paints a black box where each removed match was
+    pub fn draw_boxes(mut self, draw: bool) -> Self {
+        self.draw_boxes = draw;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    pub fn redact(&self, doc: &mut Document) -> lopdf::Result<RedactionReport> {
+        let mut report = RedactionReport::default();
+        if self.queries.is_empty() {
+            return Ok(report);
+        }
+
+        let mut replaced = Vec::new();
+        let mut walk = FormWalk { visited: HashSet::new(), page: 0 };
+        let pages: Vec<(u32, ObjectId)> = doc.pages().collect();
+        for (number, page_id) in pages {
+            let resources = Resources::of_page(&doc.structure, page_id);
+            let data = doc.page_content(page_id)?;
+            let redacted = self.redact_content(&data, &resources, number, &format!("page {}", number))?;
+            if redacted.unsearchable {
+                report.unsearchable.push(format!("page {}", number));
+            }
+            let mut forms = resources.forms_drawn(&redacted.operations);
+            forms.extend(appearance_streams(&doc.structure, page_id));
+            if let Some(rewritten) = redacted.rewritten {
+                replaced.extend(doc.structure.get_page_contents(page_id));
+                let mut stream = Stream::new(Dictionary::new(), rewritten);
+                let _ = stream.compress();
+                let contents = doc.structure.add_object(stream);
+                doc.structure.get_dictionary_mut(page_id)?.set("Contents", contents);
+                report.redactions.extend(redacted.redactions);
+            }
+            walk.page = number;
+            for form in forms {
+                self.redact_form(&mut doc.structure, form, &resources, 0, &mut walk, &mut report)?;
+            }
+        }
+
+        // Another page may still draw a replaced stream
+        let mut referenced = HashSet::new();
+        for (_, object) in doc.objects() {
+            references(object, &mut referenced);
+        }
+        for id in replaced {
+            if !referenced.contains(&id) && doc.structure.objects.remove(&id).is_some() {
+                report.streams_removed += 1;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Redacts a form XObject or appearance stream in place, then the forms it draws
+    fn redact_form(
+        &self,
+        doc: &mut lopdf::Document,
+        id: ObjectId,
+        inherited: &Resources,
+        depth: usize,
+        walk: &mut FormWalk,
+        report: &mut RedactionReport,
+    ) -> lopdf::Result<()> {
+        let location = format!("{} {} R", id.0, id.1);
+        if depth > MAX_FORM_DEPTH {
+            report.unsearchable.push(location);
+            return Ok(());
+        }
+        // Forms drawn on several pages, or from themselves, are redacted once
+        if !walk.visited.insert(id) {
+            return Ok(());
+        }
+        let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) else { return Ok(()) };
+        let data = match stream.decompressed_content() {
+            Ok(data) => data,
+            Err(_) if !stream.dict.has(b"Filter") => stream.content.clone(),
+            Err(_) => {
+                report.unsearchable.push(location);
+                return Ok(());
+            }
+        };
+        // Forms without their own resources use those of what draws them
+        let resources = Resources::of_stream(doc, &stream.dict).unwrap_or_else(|| inherited.clone());
+        let redacted = self.redact_content(&data, &resources, walk.page, &location)?;
+        if redacted.unsearchable {
+            report.unsearchable.push(location);
+        }
+        if let Some(rewritten) = redacted.rewritten {
+            let stream = doc.get_object_mut(id)?.as_stream_mut()?;
+            stream.dict.remove(b"Filter");
+            stream.dict.remove(b"DecodeParms");
+            stream.set_content(rewritten);
+            let _ = stream.compress();
+            report.forms_rewritten += 1;
+            report.redactions.extend(redacted.redactions);
+        }
+        for form in resources.forms_drawn(&redacted.operations) {
+            self.redact_form(doc, form, &resources, depth + 1, walk, report)?;
+        }
+        Ok(())
+    }
+
+    /// Finds the matches in one content stream and cuts them out
+    fn redact_content(&self, data: &[u8], resources: &Resources, page: u32, location: &str) -> lopdf::Result<RedactedContent> {
+        let operations = content::parse(data)
+            .map_err(|e| lopdf::Error::Syntax(format!("content of {}: {}", location, e)))?;
+        let run = TextRun::collect(&operations, &resources.fonts);
+
+        let mut removed = HashSet::new();
+        let mut redactions = Vec::new();
+        for (query, pattern) in self.queries.iter().enumerate() {
+            for found in pattern.find_iter(&run.text) {
+                let glyphs = run.glyphs_in(found.range());
+                if glyphs.is_empty() {
+                    continue;
+                }
+                let mut rects: Vec<(usize, Rect)> = Vec::new();
+                for &index in &glyphs {
+                    let glyph = &run.glyphs[index];
+                    match rects.last_mut() {
+                        Some((op, rect)) if *op == glyph.op => *rect = union(rect, &glyph.rect),
+                        _ => rects.push((glyph.op, glyph.rect)),
+                    }
+                }
+                removed.extend(glyphs.iter().copied());
+                redactions.push(Redaction {
+                    page,
+                    query,
+                    rects: rects.into_iter().map(|(_, rect)| rect).collect(),
+                    glyphs: glyphs.len(),
+                });
+            }
+        }
+
+        let mut redacted = RedactedContent { rewritten: None, redactions, unsearchable: run.unsearchable > 0, operations: Vec::new() };
+        if !redacted.redactions.is_empty() {
+            // Wrapped so the boxes are drawn in the stream's default space
+            let mut rewritten = b"q\n".to_vec();
+            rewritten.extend(content::serialize(&cut(&operations, &run.glyphs, &removed)));
+            rewritten.extend_from_slice(b"Q\n");
+            if self.draw_boxes {
+                for rect in redacted.redactions.iter().flat_map(|r| &r.rects) {
+                    rewritten.extend(format!(
+                        "q 0 g {:.2} {:.2} {:.2} {:.2} re f Q\n",
+                        rect[0], rect[1], rect[2] - rect[0], rect[3] - rect[1],
+                    ).into_bytes());
+                }
+            }
+            redacted.rewritten = Some(rewritten);
+        }
+        redacted.operations = operations;
+        Ok(redacted)
+    }
+}
+
+/// Forms already redacted, and the page whose forms are being walked
+struct FormWalk {
+    visited: HashSet<ObjectId>,
+    page: u32,
+}
+
+/// Outcome of redacting one content stream
+struct RedactedContent {
+    /// Content without the matches; `None` when nothing matched
+    rewritten: Option<Vec<u8>>,
+    redactions: Vec<Redaction>,
+    /// Whether it shows text in fonts whose codes do not decode to text
+    unsearchable: bool,
+    /// The original operations, for the forms they draw
+    operations: Vec<Operation>,
+}
+
+/// Fonts and form XObjects a content stream can use, by resource name
+#[derive(Debug, Clone, Default)]
+struct Resources {
+    fonts: HashMap<Vec<u8>, FontMetrics>,
+    forms: HashMap<Vec<u8>, ObjectId>,
+}
+
+impl Resources {
+    fn of_page(doc: &lopdf::Document, page_id: ObjectId) -> Self {
+        let fonts = doc.get_page_fonts(page_id).into_iter()
+            .map(|(name, font)| (name, FontMetrics::load(doc, font)))
+            .collect();
+        let mut forms = HashMap::new();
+        let (direct, inherited) = doc.get_page_resources(page_id);
+        // Nearer resource dictionaries win, so they are read last
+        let dicts = inherited.iter().rev().filter_map(|&id| doc.get_dictionary(id).ok()).chain(direct);
+        for dict in dicts {
+            forms.extend(form_xobjects(doc, dict));
+        }
+        Self { fonts, forms }
+    }
+
+    /// Resources of a form XObject or appearance stream; `None` when it has none
+    fn of_stream(doc: &lopdf::Document, dict: &Dictionary) -> Option<Self> {
+        let resources = resolve(doc, dict.get(b"Resources").ok()?)?.as_dict().ok()?;
+        let fonts = resources.get(b"Font").ok()
+            .and_then(|fonts| resolve(doc, fonts)?.as_dict().ok())
+            .map(|fonts| {
+                fonts.iter()
+                    .filter_map(|(name, font)| Some((name.clone(), FontMetrics::load(doc, resolve(doc, font)?.as_dict().ok()?))))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Some(Self { fonts, forms: form_xobjects(doc, resources) })
+    }
+
+    /// Forms the `Do` operations draw
+    fn forms_drawn(&self, operations: &[Operation]) -> Vec<ObjectId> {
+        operations.iter()
+            .filter(|op| op.operator == "Do")
+            .filter_map(|op| self.forms.get(op.operands.first()?.as_name()?).copied())
+            .collect()
+    }
+}
+
+/// Form XObjects of a resource dictionary
+fn form_xobjects(doc: &lopdf::Document, resources: &Dictionary) -> HashMap<Vec<u8>, ObjectId> {
+    let Some(xobjects) = resources.get(b"XObject").ok().and_then(|x| resolve(doc, x)?.as_dict().ok()) else {
+        return HashMap::new();
+    };
+    xobjects.iter()
+        .filter_map(|(name, object)| {
+            let id = object.as_reference().ok()?;
+            let stream = doc.get_object(id).and_then(Object::as_stream).ok()?;
+            let form = stream.dict.get(b"Subtype").and_then(Object::as_name).is_ok_and(|subtype| subtype == b"Form");
+            form.then(|| (name.clone(), id))
+        })
+        .collect()
+}
+
+/// Normal, rollover and down appearance streams of a page's annotations
+fn appearance_streams(doc: &lopdf::Document, page_id: ObjectId) -> Vec<ObjectId> {
+    let Some(annots) = doc.get_dictionary(page_id).ok()
+        .and_then(|page| resolve(doc, page.get(b"Annots").ok()?)?.as_array().ok()) else {
+        return Vec::new();
+    };
+    let mut streams = Vec::new();
+    for annot in annots.iter().filter_map(|annot| resolve(doc, annot)?.as_dict().ok()) {
+        let Some(ap) = annot.get(b"AP").ok().and_then(|ap| resolve(doc, ap)?.as_dict().ok()) else { continue };
+        for key in [&b"N"[..], b"R", b"D"] {
+            match ap.get(key) {
+                Ok(Object::Reference(id)) => match doc.get_object(*id) {
+                    Ok(Object::Stream(_)) => streams.push(*id),
+                    // A dictionary of streams, one per appearance state
+                    Ok(Object::Dictionary(states)) => streams.extend(states.iter().filter_map(|(_, s)| s.as_reference().ok())),
+                    _ => {}
+                },
+                Ok(Object::Dictionary(states)) => streams.extend(states.iter().filter_map(|(_, s)| s.as_reference().ok())),
+                _ => {}
+            }
+        }
+    }
+    streams
+}
+
+/// Widths and text of one font's codes
+#[derive(Debug, Clone, Default)]
+struct FontMetrics {
+    /// Composite font, read two bytes per code
+    two_byte: bool,
+    first_char: u32,
+    widths: Vec<f64>,
+    cid_widths: HashMap<u32, f64>,
+    /// Width of codes without an entry
+    missing_width: f64,
+    to_unicode: HashMap<u32, String>,
+    /// Predefined CMap of a composite font, splitting and decoding codes
+    /// the /ToUnicode map leaves out
+    cmap: Option<PredefinedCMap>,
+    /// Whether its codes decode to the text they show
+    searchable: bool,
+}
+
+impl FontMetrics {
+    fn load(doc: &lopdf::Document, font: &Dictionary) -> Self {
+        let mut metrics = Self { missing_width: DEFAULT_WIDTH, ..Default::default() };
+        if let Some(Object::Stream(stream)) = font.get(b"ToUnicode").ok().and_then(|o| resolve(doc, o)) {
+            let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+            metrics.to_unicode = parse_to_unicode(&data);
+        }
+        let subtype = font.get(b"Subtype").and_then(Object::as_name).unwrap_or_default();
+        // Type 3 glyphs are named by the font, composite codes are glyph
+        // IDs unless a predefined CMap gives their charset
+        metrics.searchable = !metrics.to_unicode.is_empty() || subtype != b"Type3";
+
+        if font.get(b"Subtype").and_then(Object::as_name).is_ok_and(|subtype| subtype == b"Type0") {
+            metrics.two_byte = true;
+            metrics.missing_width = 1000.0;
+            metrics.cmap = font.get(b"Encoding").and_then(Object::as_name).ok().and_then(PredefinedCMap::from_name);
+            metrics.searchable = !metrics.to_unicode.is_empty() || metrics.cmap.is_some();
+            let descendant = font.get(b"DescendantFonts").ok()
+                .and_then(|o| resolve(doc, o)?.as_array().ok()?.first())
+                .and_then(|o| resolve(doc, o)?.as_dict().ok());
+            let Some(cid_font) = descendant else { return metrics };
+            if let Some(width) = cid_font.get(b"DW").ok().and_then(|o| number(doc, o)) {
+                metrics.missing_width = width;
+            }
+            if let Some(Object::Array(w)) = cid_font.get(b"W").ok().and_then(|o| resolve(doc, o)) {
+                metrics.cid_widths = parse_cid_widths(doc, w);
+            }
+            return metrics;
+        }
+
+        metrics.first_char = font.get(b"FirstChar").ok().and_then(|o| number(doc, o)).unwrap_or(0.0) as u32;
+        if let Some(Object::Array(widths)) = font.get(b"Widths").ok().and_then(|o| resolve(doc, o)) {
+            metrics.widths = widths.iter().map(|w| number(doc, w).unwrap_or(0.0)).collect();
+        }
+        let missing = font.get(b"FontDescriptor").ok()
+            .and_then(|o| resolve(doc, o)?.as_dict().ok()?.get(b"MissingWidth").ok())
+            .and_then(|o| number(doc, o));
+        if let Some(width) = missing.filter(|&w| w > 0.0) {
+            metrics.missing_width = width;
+        }
+        metrics
+    }
+
+    /// Byte ranges of the codes in a shown string
+    fn codes(&self, bytes: &[u8]) -> Vec<Range<usize>> {
+        if let Some(cmap) = self.cmap {
+            return cmap.codes(bytes);
+        }
+        let step = if self.two_byte { 2 } else { 1 };
+        (0..bytes.len()).step_by(step).map(|start| start..(start + step).min(bytes.len())).collect()
+    }
+
+    fn width(&self, code: u32) -> f64 {
+        let width = if self.two_byte {
+            self.cid_widths.get(&code).copied()
+        } else {
+            code.checked_sub(self.first_char).and_then(|i| self.widths.get(i as usize)).copied()
+        };
+        width.unwrap_or(self.missing_width)
+    }
+
+    fn text(&self, code: u32, bytes: &[u8]) -> String {
+        if let Some(text) = self.to_unicode.get(&code) {
+            return text.clone();
+        }
+        if let Some(cmap) = self.cmap {
+            cmap.decode(bytes)
+        } else if self.two_byte {
+            char::from_u32(code).map(String::from).unwrap_or_default()
+        } else {
+            decode_pdf_string(bytes)
+        }
+    }
+}
+
+fn code_of(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |code, &b| code << 8 | u32::from(b))
+}
+
+/// A glyph drawn by a text-showing operation
+#[derive(Debug, Clone)]
+struct Glyph {
+    op: usize,
+    /// Operand index of its string, or array index within a TJ array
+    element: usize,
+    /// Bytes of its code within that string
+    code: Range<usize>,
+    /// Its displacement as a TJ adjustment, in thousandths of text space
+    adjustment: f64,
+    rect: Rect,
+}
+
+/// Text state kept across `q` and `Q`
+#[derive(Debug, Clone)]
+struct TextState {
+    ctm: Matrix,
+    font: Option<Vec<u8>>,
+    size: f64,
+    char_spacing: f64,
+    word_spacing: f64,
+    /// Horizontal scaling as a fraction
+    scale: f64,
+    leading: f64,
+    rise: f64,
+}
+
+/// Page text in drawing order, with the glyph behind each character
+struct TextRun {
+    text: String,
+    /// Byte range of `text` each glyph produced
+    spans: Vec<Range<usize>>,
+    glyphs: Vec<Glyph>,
+    /// Glyphs shown in fonts that are missing or do not decode
+    unsearchable: usize,
+}
+
+impl TextRun {
+    fn collect(operations: &[Operation], fonts: &HashMap<Vec<u8>, FontMetrics>) -> Self {
+        let fallback = FontMetrics { missing_width: DEFAULT_WIDTH, ..Default::default() };
+        let mut run = Self { text: String::new(), spans: Vec::new(), glyphs: Vec::new(), unsearchable: 0 };
+        let mut state = TextState {
+            ctm: IDENTITY,
+            font: None,
+            size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        };
+        let mut stack: Vec<TextState> = Vec::new();
+        let mut text_matrix = IDENTITY;
+        let mut line_matrix = IDENTITY;
+
+        for (index, op) in operations.iter().enumerate() {
+            let nums = op.numbers();
+            let font = state.font.as_ref().and_then(|name| fonts.get(name)).unwrap_or(&fallback);
+            match op.operator.as_str() {
+                "q" => stack.push(state.clone()),
+                "Q" => {
+                    if let Some(saved) = stack.pop() {
+                        state = saved;
+                    }
+                }
+                "cm" if nums.len() == 6 => state.ctm = multiply(&to_matrix(&nums), &state.ctm),
+                "BT" | "ET" => {
+                    text_matrix = IDENTITY;
+                    line_matrix = IDENTITY;
+                    run.break_line();
+                }
+                "Tf" => {
+                    state.font = op.operands.first().and_then(Operand::as_name).map(<[u8]>::to_vec);
+                    if let Some(size) = op.operands.get(1).and_then(Operand::as_number) {
+                        state.size = size;
+                    }
+                }
+                "Tc" if nums.len() == 1 => state.char_spacing = nums[0],
+                "Tw" if nums.len() == 1 => state.word_spacing = nums[0],
+                "Tz" if nums.len() == 1 => state.scale = nums[0] / 100.0,
+                "TL" if nums.len() == 1 => state.leading = nums[0],
+                "Ts" if nums.len() == 1 => state.rise = nums[0],
+                "Tm" if nums.len() == 6 => {
+                    line_matrix = to_matrix(&nums);
+                    text_matrix = line_matrix;
+                    run.break_line();
+                }
+                "Td" | "TD" if nums.len() == 2 => {
+                    if op.operator == "TD" {
+                        state.leading = -nums[1];
+                    }
+                    line_matrix = multiply(&translate(nums[0], nums[1]), &line_matrix);
+                    text_matrix = line_matrix;
+                    if nums[1] != 0.0 {
+                        run.break_line();
+                    }
+                }
+                "T*" => {
+                    line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                    text_matrix = line_matrix;
+                    run.break_line();
+                }
+                "Tj" | "'" | "\"" => {
+                    if op.operator == "\"" && nums.len() >= 2 {
+                        state.word_spacing = nums[0];
+                        state.char_spacing = nums[1];
+                    }
+                    if op.operator != "Tj" {
+                        line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                        text_matrix = line_matrix;
+                        run.break_line();
+                    }
+                    let element = op.operands.len().saturating_sub(1);
+                    if let Some(bytes) = op.operands.get(element).and_then(Operand::as_string) {
+                        run.show(bytes, index, element, &state, font, &mut text_matrix);
+                    }
+                }
+                "TJ" => {
+                    let Some(Operand::Array(items)) = op.operands.first() else { continue };
+                    for (element, item) in items.iter().enumerate() {
+                        if let Some(bytes) = item.as_string() {
+                            run.show(bytes, index, element, &state, font, &mut text_matrix);
+                        } else if let Some(adjustment) = item.as_number() {
+                            let tx = -adjustment / 1000.0 * state.size * state.scale;
+                            text_matrix = multiply(&translate(tx, 0.0), &text_matrix);
+                            // Large kerning stands in for a space
+                            if adjustment < -200.0 {
+                                run.text.push(' ');
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        run
+    }
+
+    fn break_line(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+
+    fn show(
+        &mut self,
+        bytes: &[u8],
+        op: usize,
+        element: usize,
+        state: &TextState,
+        font: &FontMetrics,
+        text_matrix: &mut Matrix,
+    ) {
+        for code_range in font.codes(bytes) {
+            let code_bytes = &bytes[code_range.clone()];
+            let code = code_of(code_bytes);
+            let width = font.width(code) / 1000.0 * state.size;
+            let word_spacing = if code_bytes == b" " { state.word_spacing } else { 0.0 };
+            let tx = (width + state.char_spacing + word_spacing) * state.scale;
+
+            let rendering = multiply(text_matrix, &state.ctm);
+            let (bottom, top) = (state.rise - DESCENT * state.size, state.rise + ASCENT * state.size);
+            let corners = [(0.0, bottom), (width * state.scale, bottom), (0.0, top), (width * state.scale, top)]
+                .map(|(x, y)| apply(&rendering, x, y));
+            let rect = corners.iter().fold(
+                [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY],
+                |r, &(x, y)| [r[0].min(x), r[1].min(y), r[2].max(x), r[3].max(y)],
+            );
+            let unit = state.size * state.scale;
+            let adjustment = if unit != 0.0 { -tx * 1000.0 / unit } else { 0.0 };
+
+            let start = self.text.len();
+            self.text.push_str(&font.text(code, code_bytes));
+            self.spans.push(start..self.text.len());
+            self.glyphs.push(Glyph { op, element, code: code_range, adjustment, rect });
+            *text_matrix = multiply(&translate(tx, 0.0), text_matrix);
+            if !font.searchable {
+                self.unsearchable += 1;
+            }
+        }
+    }
+
+    /// Glyphs that produced any of `range` of the text
+    fn glyphs_in(&self, range: Range<usize>) -> Vec<usize> {
+        self.spans.iter().enumerate()
+            .filter(|(_, span)| span.start < range.end && range.start < span.end.max(span.start + 1))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// Operations with the `removed` glyphs cut out and their displacement kept
+fn cut(operations: &[Operation], glyphs: &[Glyph], removed: &HashSet<usize>) -> Vec<Operation> {
+    let mut cuts: HashMap<usize, HashMap<usize, Vec<(Range<usize>, f64)>>> = HashMap::new();
+    for &index in removed {
+        let glyph = &glyphs[index];
+        cuts.entry(glyph.op).or_default().entry(glyph.element).or_default()
+            .push((glyph.code.clone(), glyph.adjustment));
+    }
+    for element_cuts in cuts.values_mut().flat_map(|elements| elements.values_mut()) {
+        element_cuts.sort_by_key(|(code, _)| code.start);
+    }
+
+    let mut out = Vec::with_capacity(operations.len());
+    for (index, op) in operations.iter().enumerate() {
+        let Some(op_cuts) = cuts.get(&index) else {
+            out.push(op.clone());
+            continue;
+        };
+        match op.operator.as_str() {
+            "TJ" => {
+                let items = match op.operands.first() {
+                    Some(Operand::Array(items)) => items.as_slice(),
+                    _ => &[],
+                };
+                out.push(Operation::new("TJ", vec![Operand::Array(cut_strings(items.iter().enumerate(), op_cuts))]));
+            }
+            "Tj" | "'" | "\"" => {
+                // Showing an array instead keeps the line moves and spacing these set
+                if op.operator == "\"" && op.operands.len() == 3 {
+                    out.push(Operation::new("Tw", vec![op.operands[0].clone()]));
+                    out.push(Operation::new("Tc", vec![op.operands[1].clone()]));
+                }
+                if op.operator != "Tj" {
+                    out.push(Operation::new("T*", Vec::new()));
+                }
+                let element = op.operands.len().saturating_sub(1);
+                let shown = op.operands.get(element).map(|operand| (element, operand));
+                out.push(Operation::new("TJ", vec![Operand::Array(cut_strings(shown.into_iter(), op_cuts))]));
+            }
+            _ => out.push(op.clone()),
+        }
+    }
+    out
+}
+
+/// TJ array items with the cut codes replaced by adjustments
+fn cut_strings<'a>(
+    items: impl Iterator<Item = (usize, &'a Operand)>,
+    cuts: &HashMap<usize, Vec<(Range<usize>, f64)>>,
+) -> Vec<Operand> {
+    fn flush(out: &mut Vec<Operand>, adjustment: &mut f64) {
+        if *adjustment != 0.0 {
+            out.push(Operand::Real((*adjustment * 1000.0).round() / 1000.0));
+            *adjustment = 0.0;
+        }
+    }
+    fn push_string(out: &mut Vec<Operand>, adjustment: &mut f64, hex: bool, bytes: Vec<u8>) {
+        if bytes.is_empty() {
+            return;
+        }
+        flush(out, adjustment);
+        out.push(if hex { Operand::HexString(bytes) } else { Operand::String(bytes) });
+    }
+
+    let mut out = Vec::new();
+    let mut adjustment = 0.0;
+    for (element, item) in items {
+        if let Some(number) = item.as_number() {
+            adjustment += number;
+            continue;
+        }
+        let Some(bytes) = item.as_string() else { continue };
+        let hex = matches!(item, Operand::HexString(_));
+        let mut position = 0;
+        for (code, removed) in cuts.get(&element).map(Vec::as_slice).unwrap_or_default() {
+            push_string(&mut out, &mut adjustment, hex, bytes[position..code.start].to_vec());
+            adjustment += removed;
+            position = code.end;
+        }
+        push_string(&mut out, &mut adjustment, hex, bytes[position..].to_vec());
+    }
+    flush(&mut out, &mut adjustment);
+    out
+}
+
+/// Code-to-text entries of a ToUnicode CMap's bfchar and bfrange sections
+fn parse_to_unicode(data: &[u8]) -> HashMap<u32, String> {
+    enum Token {
+        Hex(Vec<u8>),
+        Open,
+        Close,
+        Word(Vec<u8>),
+    }
+
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        match data[pos] {
+            b'<' if data.get(pos + 1) == Some(&b'<') => pos += 2,
+            b'>' if data.get(pos + 1) == Some(&b'>') => pos += 2,
+            b'<' => {
+                let end = data[pos..].iter().position(|&b| b == b'>').map_or(data.len(), |at| pos + at);
+                let digits: Vec<u8> = data[pos + 1..end].iter().copied().filter(u8::is_ascii_hexdigit).collect();
+                let bytes = digits.chunks(2)
+                    .map(|pair| {
+                        let hex = std::str::from_utf8(pair).unwrap_or("0");
+                        // An odd final digit is followed by an implied 0
+                        u8::from_str_radix(&format!("{:0<2}", hex), 16).unwrap_or(0)
+                    })
+                    .collect();
+                tokens.push(Token::Hex(bytes));
+                pos = end + 1;
+            }
+            b'[' => {
+                tokens.push(Token::Open);
+                pos += 1;
+            }
+            b']' => {
+                tokens.push(Token::Close);
+                pos += 1;
+            }
+            b if b.is_ascii_alphanumeric() => {
+                let end = data[pos..].iter().position(|b| !b.is_ascii_alphanumeric()).map_or(data.len(), |at| pos + at);
+                tokens.push(Token::Word(data[pos..end].to_vec()));
+                pos = end;
+            }
+            _ => pos += 1,
+        }
+    }
+
+    let utf16 = |bytes: &[u8]| -> Vec<u16> {
+        bytes.chunks(2).map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])).collect()
+    };
+    let is_hex = |token: &Token| matches!(token, Token::Hex(_));
+    let mut map = HashMap::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let Token::Word(word) = token else { continue };
+        match word.as_slice() {
+            b"beginbfchar" => {
+                while let Some(Token::Hex(source)) = tokens.next_if(is_hex) {
+                    let Some(Token::Hex(target)) = tokens.next_if(is_hex) else { break };
+                    map.insert(code_of(&source), String::from_utf16_lossy(&utf16(&target)));
+                }
+            }
+            b"beginbfrange" => {
+                while let Some(Token::Hex(low)) = tokens.next_if(is_hex) {
+                    let Some(Token::Hex(high)) = tokens.next_if(is_hex) else { break };
+                    let (low, high) = (code_of(&low), code_of(&high));
+                    let high = high.min(low.saturating_add(MAX_RANGE));
+                    match tokens.next() {
+                        Some(Token::Hex(target)) => {
+                            let units = utf16(&target);
+                            for (offset, code) in (low..=high).enumerate() {
+                                let mut units = units.clone();
+                                if let Some(last) = units.last_mut() {
+                                    *last = last.wrapping_add(offset as u16);
+                                }
+                                map.insert(code, String::from_utf16_lossy(&units));
+                            }
+                        }
+                        Some(Token::Open) => {
+                            let mut code = low;
+                            while let Some(Token::Hex(target)) = tokens.next_if(is_hex) {
+                                if code <= high {
+                                    map.insert(code, String::from_utf16_lossy(&utf16(&target)));
+                                }
+                                code += 1;
+                            }
+                            tokens.next_if(|t| matches!(t, Token::Close));
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    map
+}
+
+/// CID widths from a CIDFont /W array: `c [w1 w2 ...]` and `c_first c_last w` entries
+fn parse_cid_widths(doc: &lopdf::Document, w: &[Object]) -> HashMap<u32, f64> {
+    let mut widths = HashMap::new();
+    let mut i = 0;
+    while let Some(first) = w.get(i).and_then(|o| number(doc, o)) {
+        let first = first as u32;
+        match w.get(i + 1).and_then(|o| resolve(doc, o)) {
+            Some(Object::Array(list)) => {
+                for (offset, width) in list.iter().enumerate() {
+                    if let Some(width) = number(doc, width) {
+                        widths.insert(first + offset as u32, width);
+                    }
+                }
+                i += 2;
+            }
+            Some(last) => {
+                let (Some(last), Some(width)) = (number(doc, last), w.get(i + 2).and_then(|o| number(doc, o))) else { break };
+                for cid in first..=(last as u32).min(first.saturating_add(MAX_RANGE)) {
+                    widths.insert(cid, width);
+                }
+                i += 3;
+            }
+            None => break,
+        }
+    }
+    widths
+}
+
+fn resolve<'a>(doc: &'a lopdf::Document, object: &'a Object) -> Option<&'a Object> {
+    doc.dereference(object).ok().map(|(_, object)| object)
+}
+
+fn number(doc: &lopdf::Document, object: &Object) -> Option<f64> {
+    match resolve(doc, object)? {
+        Object::Integer(i) => Some(*i as f64),
+        Object::Real(r) => Some(*r as f64),
+        _ => None,
+    }
+}
+
+fn references(object: &Object, out: &mut HashSet<ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            out.insert(*id);
+        }
+        Object::Array(items) => items.iter().for_each(|item| references(item, out)),
+        Object::Dictionary(dict) => dict.iter().for_each(|(_, value)| references(value, out)),
+        Object::Stream(stream) => stream.dict.iter().for_each(|(_, value)| references(value, out)),
+        _ => {}
+    }
+}
+
+fn to_matrix(nums: &[f64]) -> Matrix {
+    [nums[0], nums[1], nums[2], nums[3], nums[4], nums[5]]
+}
+
+fn translate(x: f64, y: f64) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, x, y]
+}
+
+fn multiply(m1: &Matrix, m2: &Matrix) -> Matrix {
+    [
+        m1[0] * m2[0] + m1[1] * m2[2],
+        m1[0] * m2[1] + m1[1] * m2[3],
+        m1[2] * m2[0] + m1[3] * m2[2],
+        m1[2] * m2[1] + m1[3] * m2[3],
+        m1[4] * m2[0] + m1[5] * m2[2] + m2[4],
+        m1[4] * m2[1] + m1[5] * m2[3] + m2[5],
+    ]
+}
+
+fn apply(m: &Matrix, x: f64, y: f64) -> (f64, f64) {
+    (x * m[0] + y * m[2] + m[4], x * m[1] + y * m[3] + m[5])
+}
+
+fn union(a: &Rect, b: &Rect) -> Rect {
+    [a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_matches_removed_from_content() {
+        let mut pdf = lopdf::Document::with_version("1.7");
+        let pages_id = pdf.new_object_id();
+        let font = pdf.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+            "FirstChar" => 32,
+            "Widths" => vec![Object::Integer(600); 95],
+        });
+        let content = pdf.add_object(Stream::new(
+            Dictionary::new(),
+            b"BT /F1 10 Tf 72 700 Td (SSN 123-45-6789 on file) Tj 0 -12 Td [(Call Ann) -250 (today)] TJ ET".to_vec(),
+        ));
+        let page = pdf.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content,
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+        });
+        pdf.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page.into()],
+            "Count" => 1,
+        }));
+        let catalog = pdf.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        pdf.trailer.set("Root", catalog);
+        let mut doc = Document::from_pdf("redact.pdf".into(), pdf);
+
+        let report = TextRedactor::new()
+            .regex(r"\d{3}-\d{2}-\d{4}").unwrap()
+            .text("Ann")
+            .draw_boxes(true)
+            .redact(&mut doc)
+            .unwrap();
+
+        assert_eq!(report.redactions.len(), 2);
+        assert_eq!(report.pages(), 1);
+        assert_eq!(report.streams_removed, 1);
+        assert!(doc.object(content).is_none());
+        // "SSN " is 4 glyphs of 6pt before the 11 removed ones
+        let ssn = &report.redactions[0];
+        assert_eq!((ssn.query, ssn.glyphs), (0, 11));
+        let rect = ssn.rects[0];
+        assert!((rect[0] - 96.0).abs() < 1e-9 && (rect[2] - 162.0).abs() < 1e-9, "{:?}", rect);
+        assert!((rect[1] - 698.0).abs() < 1e-9 && (rect[3] - 708.0).abs() < 1e-9, "{:?}", rect);
+
+        let data = doc.page_content(page).unwrap();
+        let text = String::from_utf8_lossy(&data);
+        assert!(!text.contains("123") && !text.contains("Ann"), "{}", text);
+        assert!(text.contains("[(SSN ) -6600 ( on file)] TJ"), "{}", text);
+        assert!(text.contains("[(Call ) -2050 (today)] TJ"), "{}", text);
+        assert!(text.contains("q 0 g 96.00 698.00 66.00 10.00 re f Q"), "{}", text);
+    }
+
+    #[test]
+    fn test_forms_and_appearances_searched_and_undecodable_text_reported() {
+        let mut pdf = lopdf::Document::with_version("1.7");
+        let pages_id = pdf.new_object_id();
+        let font = pdf.add_object(dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Courier" });
+        let glyph_ids = pdf.add_object(dictionary! { "Type" => "Font", "Subtype" => "Type0", "Encoding" => "Identity-H" });
+        let fonts = dictionary! { "F1" => font, "F2" => glyph_ids };
+        let inner = pdf.add_object(Stream::new(
+            dictionary! { "Subtype" => "Form", "Resources" => dictionary! { "Font" => fonts.clone() } },
+            b"BT /F1 10 Tf (SSN 123-45-6789) Tj ET".to_vec(),
+        ));
+        let outer = pdf.add_object(Stream::new(
+            dictionary! {
+                "Subtype" => "Form",
+                "Resources" => dictionary! { "XObject" => dictionary! { "Fx" => inner } },
+            },
+            b"/Fx Do".to_vec(),
+        ));
+        let appearance = pdf.add_object(Stream::new(
+            dictionary! { "Subtype" => "Form", "Resources" => dictionary! { "Font" => fonts.clone() } },
+            b"BT /F1 9 Tf (123-45-6789) Tj /F2 9 Tf <0012> Tj ET".to_vec(),
+        ));
+        let content = pdf.add_object(Stream::new(Dictionary::new(), b"/Fo Do".to_vec()));
+        let page = pdf.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content,
+            "Resources" => dictionary! { "Font" => fonts, "XObject" => dictionary! { "Fo" => outer } },
+            "Annots" => vec![Object::Dictionary(dictionary! {
+                "Subtype" => "FreeText",
+                "AP" => dictionary! { "N" => appearance },
+            })],
+        });
+        pdf.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page.into()],
+            "Count" => 1,
+        }));
+        let catalog = pdf.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        pdf.trailer.set("Root", catalog);
+        let mut doc = Document::from_pdf("redact.pdf".into(), pdf);
+
+        let report = TextRedactor::new().regex(r"\d{3}-\d{2}-\d{4}").unwrap().redact(&mut doc).unwrap();
+
+        assert_eq!(report.redactions.len(), 2);
+        assert_eq!(report.forms_rewritten, 2);
+        for id in [inner, appearance] {
+            let stream = doc.object(id).unwrap().as_stream().unwrap();
+            let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+            assert!(!String::from_utf8_lossy(&data).contains("123"), "{}", String::from_utf8_lossy(&data));
+        }
+        // Glyph IDs without a ToUnicode map could spell anything
+        assert!(!report.is_complete());
+        assert_eq!(report.unsearchable, vec![format!("{} 0 R", appearance.0)]);
+    }
+}
@@ -0,0 +1,160 @@
+//! Canonical object renumbering
+//! Author: kartik4091
+//! Created: 2025-06-04 20:44:27 UTC
+//! Rewrites every object to a dense number in reachability order from the
+//! trailer, with generation 0, so neither gaps, generation numbers nor
+//! the original numbering order survive into the output. Objects nothing
+//! refers to keep their relative order after the reachable ones.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use lopdf::{Dictionary, Object, ObjectId};
+
+use crate::antiforensics::types::Document;
+
+/// What renumbering changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenumberReport {
+    /// Objects whose number or generation changed
+    pub renumbered: usize,
+    /// Objects that had a non-zero generation
+    pub generations_reset: usize,
+    /// References to missing objects, replaced by null
+    pub dangling_references: usize,
+    /// Old to new ID of every object whose ID changed
+    pub mapping: BTreeMap<ObjectId, ObjectId>,
+}
+
+/// Renumbers documents to `1..=n` with generation 0
+#[derive(Debug, Clone, Default)]
+pub struct ObjectRenumberer;
+
+impl ObjectRenumberer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn renumber(&self, doc: &mut Document) -> RenumberReport {
+        let order = canonical_order(doc);
+        let mapping: HashMap<ObjectId, ObjectId> = order.iter()
+            .enumerate()
+            .map(|(index, &old)| (old, (index as u32 + 1, 0)))
+            .collect();
+
+        let changed: BTreeMap<ObjectId, ObjectId> = mapping.iter()
+            .filter(|(old, new)| old != new)
+            .map(|(&old, &new)| (old, new))
+            .collect();
+        let mut report = RenumberReport {
+            renumbered: changed.len(),
+            generations_reset: order.iter().filter(|id| id.1 != 0).count(),
+            dangling_references: 0,
+            mapping: changed,
+        };
+
+        let pdf = &mut doc.structure;
+        let mut objects = BTreeMap::new();
+        for (old, mut object) in std::mem::take(&mut pdf.objects) {
+            remap(&mut object, &mapping, &mut report.dangling_references);
+            objects.insert(mapping[&old], object);
+        }
+        pdf.objects = objects;
+        remap_dict(&mut pdf.trailer, &mapping, &mut report.dangling_references);
+        pdf.max_id = order.len() as u32;
+        report
+    }
+}
+
+/// Objects in depth-first order from the trailer, then unreferenced ones by number
+pub fn canonical_order(doc: &Document) -> Vec<ObjectId> {
+    let mut order = Vec::with_capacity(doc.structure.objects.len());
+    let mut seen = HashSet::new();
+
+    let mut stack = Vec::new();
+    collect_dict_refs(&doc.structure.trailer, &mut stack);
+    stack.reverse();
+    while let Some(id) = stack.pop() {
+        let Some(object) = doc.object(id) else { continue };
+        if !seen.insert(id) {
+            continue;
+        }
+        order.push(id);
+        let start = stack.len();
+        collect_refs(object, &mut stack);
+        stack[start..].reverse();
+    }
+
+    order.extend(doc.objects().map(|(id, _)| id).filter(|id| !seen.contains(id)));
+    order
+}
+
+fn collect_refs(object: &Object, refs: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => refs.push(*id),
+        Object::Array(items) => items.iter().for_each(|item| collect_refs(item, refs)),
+        Object::Dictionary(dict) => collect_dict_refs(dict, refs),
+        Object::Stream(stream) => collect_dict_refs(&stream.dict, refs),
+        _ => {}
+    }
+}
+
+fn collect_dict_refs(dict: &Dictionary, refs: &mut Vec<ObjectId>) {
+    dict.iter().for_each(|(_, value)| collect_refs(value, refs));
+}
+
+fn remap(object: &mut Object, mapping: &HashMap<ObjectId, ObjectId>, dangling: &mut usize) {
+    match object {
+        Object::Reference(id) => match mapping.get(id) {
+            Some(new) => *id = *new,
+            None => {
+                // An undefined object is null; keeping the number could point it at a reused one
+                *object = Object::Null;
+                *dangling += 1;
+            }
+        },
+        Object::Array(items) => items.iter_mut().for_each(|item| remap(item, mapping, dangling)),
+        Object::Dictionary(dict) => remap_dict(dict, mapping, dangling),
+        Object::Stream(stream) => remap_dict(&mut stream.dict, mapping, dangling),
+        _ => {}
+    }
+}
+
+fn remap_dict(dict: &mut Dictionary, mapping: &HashMap<ObjectId, ObjectId>, dangling: &mut usize) {
+    dict.iter_mut().for_each(|(_, value)| remap(value, mapping, dangling));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antiforensics::analyzer::object_numbering::{analyze, tests::covert_numbering, NumberingConfig};
+
+    #[test]
+    fn test_renumbered_densely_in_reachability_order() {
+        let mut doc = Document::from_pdf("numbering.pdf".into(), covert_numbering());
+        let report = ObjectRenumberer::new().renumber(&mut doc);
+        assert_eq!(report.renumbered, 4);
+        assert_eq!(report.generations_reset, 4);
+        assert_eq!(report.dangling_references, 0);
+        assert_eq!(report.mapping[&(3, u16::from(b'L'))], (2, 0));
+
+        let analysis = analyze(&doc.structure, &NumberingConfig::default());
+        assert!(analysis.is_canonical());
+        assert_eq!(analysis.encoded_text, None);
+
+        // Root first, then the page tree it leads to, then Info
+        assert_eq!(doc.root_id(), Some((1, 0)));
+        assert_eq!(doc.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap(), (2, 0));
+        assert_eq!(doc.info_id(), Some((5, 0)));
+        assert_eq!(doc.pages().count(), 1);
+    }
+
+    #[test]
+    fn test_dangling_reference_becomes_null() {
+        let mut doc = Document::from_pdf("dangling.pdf".into(), lopdf::Document::with_version("1.7"));
+        let catalog = doc.structure.add_object(lopdf::dictionary! { "Type" => "Catalog", "Outlines" => (99, 0) });
+        doc.set_root_id(catalog);
+
+        let report = ObjectRenumberer::new().renumber(&mut doc);
+        assert_eq!(report.dangling_references, 1);
+        assert!(matches!(doc.catalog().unwrap().get(b"Outlines").unwrap(), Object::Null));
+    }
+}
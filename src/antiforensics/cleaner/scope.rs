@@ -0,0 +1,283 @@
+//! Scoped Cleaning
+//! Author: kartik4091
+//! Created: 2025-06-04 23:06:19 UTC
+//! Restricts cleaning to part of a document: a page range, some artifact
+//! types, a set of objects or locations matching a glob. Each removal a
+//! cleaner is about to make is described as a [`CleanTarget`] and only
+//! goes ahead when the scope allows it.
+//!
+//! Filters of different kinds must all match; values of one kind are
+//! alternatives. Document-level entries such as `/Root/OpenAction` or
+//! `/Info/Producer` are on no page, so a page filter excludes them.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    str::FromStr,
+};
+use lopdf::{Object, ObjectId};
+use serde::{Serialize, Deserialize};
+
+use crate::antiforensics::{types::Document, ArtifactType, ForensicArtifact};
+
+/// Pages `first..=last`, numbered from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageRange {
+    pub first: u32,
+    pub last: u32,
+}
+
+impl PageRange {
+    pub fn contains(&self, page: u32) -> bool {
+        (self.first..=self.last).contains(&page)
+    }
+}
+
+/// Something a cleaner is about to remove or rewrite
+#[derive(Debug, Clone)]
+pub struct CleanTarget<'a> {
+    /// PDF path as recorded in cleaning actions, e.g. `/Info/Producer`
+    pub location: &'a str,
+    pub artifact_type: ArtifactType,
+    pub object: Option<ObjectId>,
+    /// Page the target is on; `None` for document-level entries
+    pub page: Option<u32>,
+}
+
+/// One `--scope` filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeFilter {
+    Pages(PageRange),
+    Type(ArtifactType),
+    Object(ObjectId),
+    Location(String),
+}
+
+impl FromStr for ScopeFilter {
+    type Err = String;
+
+    /// Parses `pages=3-5`, `type=metadata`, `object=12` or `object=12.1`,
+    /// and `location=/Info/*`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s.split_once('=')
+            .ok_or_else(|| format!("invalid scope `{s}`: expected pages=, type=, object= or location="))?;
+        let value = value.trim();
+        match kind.trim() {
+            "pages" | "page" => {
+                let (first, last) = value.split_once('-').unwrap_or((value, value));
+                let page = |n: &str| n.trim().parse::<u32>().ok().filter(|&n| n > 0);
+                match (page(first), page(last)) {
+                    (Some(first), Some(last)) if first <= last => Ok(Self::Pages(PageRange { first, last })),
+                    _ => Err(format!("invalid page range `{value}`: expected N or N-M, from 1")),
+                }
+            }
+            "type" => parse_type(value).map(Self::Type),
+            "object" => {
+                let (number, generation) = value.split_once(['.', ' ']).unwrap_or((value, "0"));
+                match (number.trim().parse(), generation.trim().parse()) {
+                    (Ok(number), Ok(generation)) => Ok(Self::Object((number, generation))),
+                    _ => Err(format!("invalid object `{value}`: expected N or N.G")),
+                }
+            }
+            "location" | "path" if !value.is_empty() => Ok(Self::Location(value.to_string())),
+            other => Err(format!("invalid scope `{other}`: expected pages, type, object or location")),
+        }
+    }
+}
+
+fn parse_type(value: &str) -> Result<ArtifactType, String> {
+    match value.to_ascii_lowercase().replace('_', "-").as_str() {
+        "metadata" => Ok(ArtifactType::Metadata),
+        "content" => Ok(ArtifactType::Content),
+        "structure" | "actions" => Ok(ArtifactType::Structure),
+        "javascript" | "scripts" => Ok(ArtifactType::JavaScript),
+        "binary" => Ok(ArtifactType::Binary),
+        "signature" | "signatures" => Ok(ArtifactType::Signature),
+        "attachments" | "embedded-file" | "embedded-files" => Ok(ArtifactType::EmbeddedFile),
+        other => Err(format!(
+            "unknown artifact type `{other}`: expected metadata, content, structure, javascript, binary, signatures or attachments"
+        )),
+    }
+}
+
+/// Part of a document cleaning may touch; the default allows everything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CleaningScope {
+    pub pages: Vec<PageRange>,
+    pub artifact_types: Vec<ArtifactType>,
+    pub objects: BTreeSet<ObjectId>,
+    /// Globs over locations; `*` matches any run of characters, `?` one
+    pub locations: Vec<String>,
+}
+
+impl CleaningScope {
+    /// Scope combining `filters`
+    pub fn from_filters(filters: impl IntoIterator<Item = ScopeFilter>) -> Self {
+        let mut scope = Self::default();
+        for filter in filters {
+            match filter {
+                ScopeFilter::Pages(range) => scope.pages.push(range),
+                ScopeFilter::Type(artifact_type) => scope.artifact_types.push(artifact_type),
+                ScopeFilter::Object(id) => {
+                    scope.objects.insert(id);
+                }
+                ScopeFilter::Location(glob) => scope.locations.push(glob),
+            }
+        }
+        scope
+    }
+
+    /// Whether the scope allows everything
+    pub fn is_unrestricted(&self) -> bool {
+        self.pages.is_empty() && self.artifact_types.is_empty() && self.objects.is_empty() && self.locations.is_empty()
+    }
+
+    pub fn allows(&self, target: &CleanTarget<'_>) -> bool {
+        (self.pages.is_empty() || target.page.is_some_and(|page| self.pages.iter().any(|r| r.contains(page))))
+            && (self.artifact_types.is_empty() || self.artifact_types.contains(&target.artifact_type))
+            && (self.objects.is_empty() || target.object.is_some_and(|id| self.objects.contains(&id)))
+            && (self.locations.is_empty()
+                || self.locations.iter().any(|glob| glob_match(glob.as_bytes(), target.location.as_bytes())))
+    }
+
+    /// Artifacts inside the scope, placed by their `object_id` metadata
+    ///
+    /// Location globs are matched against the artifact's location, e.g.
+    /// `3 0 R:/OpenAction`, which differs from cleaning action paths.
+    pub fn select<'a>(&self, doc: &Document, artifacts: &'a [ForensicArtifact]) -> Vec<&'a ForensicArtifact> {
+        if self.is_unrestricted() {
+            return artifacts.iter().collect();
+        }
+        let pages = object_pages(&doc.structure);
+        artifacts.iter()
+            .filter(|artifact| {
+                let object = artifact.metadata.get("object_id").and_then(|id| {
+                    let (number, generation) = id.split_once(' ')?;
+                    Some((number.parse().ok()?, generation.parse().ok()?))
+                });
+                self.allows(&CleanTarget {
+                    location: &artifact.location,
+                    artifact_type: artifact.artifact_type.clone(),
+                    object,
+                    page: object.and_then(|id| pages.get(&id).copied()),
+                })
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for CleaningScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_unrestricted() {
+            return f.write_str("whole document");
+        }
+        let mut parts = Vec::new();
+        parts.extend(self.pages.iter().map(|r| format!("pages {}-{}", r.first, r.last)));
+        parts.extend(self.artifact_types.iter().map(|t| format!("{:?}", t)));
+        parts.extend(self.objects.iter().map(|(n, g)| format!("{} {} R", n, g)));
+        parts.extend(self.locations.iter().cloned());
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// Page numbers of page objects, their content streams and annotations
+pub fn object_pages(doc: &lopdf::Document) -> HashMap<ObjectId, u32> {
+    let mut pages = HashMap::new();
+    for (number, page_id) in doc.get_pages() {
+        pages.insert(page_id, number);
+        let Ok(page) = doc.get_dictionary(page_id) else { continue };
+        for key in [&b"Annots"[..], b"Contents"] {
+            let items = match page.get(key) {
+                Ok(Object::Reference(id)) => match doc.get_object(*id) {
+                    Ok(Object::Array(items)) => items.clone(),
+                    _ => vec![Object::Reference(*id)],
+                },
+                Ok(Object::Array(items)) => items.clone(),
+                _ => continue,
+            };
+            for item in items {
+                if let Object::Reference(id) = item {
+                    pages.entry(id).or_insert(number);
+                }
+            }
+        }
+    }
+    pages
+}
+
+/// Matches `text` against a glob of `*` and `?` wildcards
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(location: &str, artifact_type: ArtifactType, page: Option<u32>) -> CleanTarget<'_> {
+        CleanTarget { location, artifact_type, object: None, page }
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        assert_eq!("pages=3-5".parse::<ScopeFilter>(), Ok(ScopeFilter::Pages(PageRange { first: 3, last: 5 })));
+        assert_eq!("pages=2".parse::<ScopeFilter>(), Ok(ScopeFilter::Pages(PageRange { first: 2, last: 2 })));
+        assert_eq!("type=attachments".parse::<ScopeFilter>(), Ok(ScopeFilter::Type(ArtifactType::EmbeddedFile)));
+        assert_eq!("object=12.1".parse::<ScopeFilter>(), Ok(ScopeFilter::Object((12, 1))));
+        assert_eq!("location=/Info/*".parse::<ScopeFilter>(), Ok(ScopeFilter::Location("/Info/*".into())));
+        assert!("pages=5-3".parse::<ScopeFilter>().is_err());
+        assert!("pages=0".parse::<ScopeFilter>().is_err());
+        assert!("colour=red".parse::<ScopeFilter>().is_err());
+    }
+
+    #[test]
+    fn test_filters_combine() {
+        let scope = CleaningScope::from_filters([
+            ScopeFilter::Type(ArtifactType::Metadata),
+            ScopeFilter::Location("/Info/*Date".into()),
+        ]);
+        assert!(scope.allows(&target("/Info/ModDate", ArtifactType::Metadata, None)));
+        assert!(!scope.allows(&target("/Info/Producer", ArtifactType::Metadata, None)));
+        assert!(!scope.allows(&target("/Info/ModDate", ArtifactType::Structure, None)));
+        assert!(CleaningScope::default().allows(&target("/Root/AA", ArtifactType::Structure, None)));
+    }
+
+    #[test]
+    fn test_page_filter_excludes_document_level_targets() {
+        let scope = CleaningScope::from_filters(["pages=2-3".parse().unwrap()]);
+        assert!(scope.allows(&target("/Annots/7 0 R", ArtifactType::Content, Some(3))));
+        assert!(!scope.allows(&target("/Annots/7 0 R", ArtifactType::Content, Some(4))));
+        assert!(!scope.allows(&target("/Root/OpenAction", ArtifactType::Structure, None)));
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_match(b"/Root/*", b"/Root/OpenAction"));
+        assert!(glob_match(b"*Embedded*", b"/Root/Names/EmbeddedFiles/a.exe"));
+        assert!(glob_match(b"/Info/?uthor", b"/Info/Author"));
+        assert!(!glob_match(b"/Info/*", b"/Root/Metadata"));
+        assert!(glob_match(b"*", b""));
+    }
+}
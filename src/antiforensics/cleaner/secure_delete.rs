@@ -146,14 +146,15 @@ impl SecureDelete {
     /// Renames file multiple times before deletion
     #[instrument(skip(self, file))]
     async fn secure_rename(&self, file: &PathBuf) -> Result<()> {
+        let file = crate::paths::extended(file);
         let parent = file.parent()
             .ok_or_else(|| CleanerError::InvalidInput("Invalid file path".into()))?;
 
         for _ in 0..self.config.rename_count {
             let new_name = parent.join(self.generate_random_name());
-            fs::rename(file, &new_name).await?;
+            fs::rename(&file, &new_name).await?;
             tokio::time::sleep(Duration::from_millis(10)).await;
-            fs::rename(&new_name, file).await?;
+            fs::rename(&new_name, &file).await?;
         }
         Ok(())
     }
@@ -198,17 +199,20 @@ impl Cleaner for SecureDelete {
         let _permit = self.base.semaphore.acquire().await
             .map_err(|e| CleanerError::Internal(e.to_string()))?;
 
+        // Long paths need the extended form on Windows
+        let target = crate::paths::extended(path).into_owned();
+
         // Validate input
-        self.validate(path).await?;
+        self.validate(&target).await?;
 
         // Get file size
-        let metadata = fs::metadata(path).await?;
+        let metadata = fs::metadata(&target).await?;
         let file_size = metadata.len();
 
         // Open file for overwriting
         let mut file = OpenOptions::new()
             .write(true)
-            .open(path)
+            .open(&target)
             .await?;
 
         // Get wipe patterns
@@ -221,17 +225,17 @@ impl Cleaner for SecureDelete {
 
         // Rename file if configured
         if self.config.rename_before_delete {
-            self.secure_rename(path).await?;
+            self.secure_rename(&target).await?;
         }
 
         // Delete file
         drop(file);
-        fs::remove_file(path).await?;
+        fs::remove_file(&target).await?;
 
         // Cleanup empty directories if configured
         let mut dirs_cleaned = 0;
         if self.config.delete_empty_dirs {
-            if let Some(parent) = path.parent() {
+            if let Some(parent) = target.parent() {
                 dirs_cleaned = self.cleanup_empty_dirs(parent).await?;
             }
         }
@@ -342,6 +346,28 @@ mod tests {
         assert!(!path.exists());
     }
 
+    #[tokio::test]
+    async fn test_exotic_file_names() {
+        let deleter = SecureDelete::new(SecureDeleteConfig {
+            delete_empty_dirs: false,
+            ..create_test_config()
+        });
+        let temp_dir = TempDir::new().unwrap();
+        let mut dir = temp_dir.path().to_path_buf();
+        while dir.as_os_str().len() < 300 {
+            dir.push("nested-directory-level");
+        }
+        std::fs::create_dir_all(crate::paths::extended(&dir)).unwrap();
+
+        for name in crate::paths::tests::exotic_names() {
+            let path = dir.join(name);
+            std::fs::write(crate::paths::extended(&path), b"test data").unwrap();
+            let result = deleter.clean_file(&path).await.unwrap();
+            assert_eq!(result.path, path);
+            assert!(!crate::paths::extended(&path).exists());
+        }
+    }
+
     #[tokio::test]
     async fn test_wipe_patterns() {
         let deleter = SecureDelete::new(SecureDeleteConfig {
@@ -155,13 +155,16 @@ impl StructureCleaner {
         let mut referenced = HashSet::new();
         
         // Add root and essential objects
-        referenced.insert(document.structure.trailer.root);
-        if let Some(info) = document.structure.trailer.info {
+        let root = document.root_id().ok_or_else(|| {
+            Error::InvalidInput("Trailer has no /Root".into())
+        })?;
+        referenced.insert(root);
+        if let Some(info) = document.info_id() {
             referenced.insert(info);
         }
         
         // Add objects referenced from root
-        let mut to_process = vec![document.structure.trailer.root];
+        let mut to_process = vec![root];
         while let Some(id) = to_process.pop() {
             if let Some(refs) = self.references.get(&id) {
                 for &ref_id in refs {
@@ -421,12 +424,12 @@ impl StructureCleaner {
         document.structure.objects = new_objects;
         
         // Update trailer
-        if let Some(&new_root) = number_map.get(&document.structure.trailer.root) {
-            document.structure.trailer.root = new_root;
+        if let Some(&new_root) = document.root_id().and_then(|root| number_map.get(&root)) {
+            document.set_root_id(new_root);
         }
-        if let Some(info) = document.structure.trailer.info {
+        if let Some(info) = document.info_id() {
             if let Some(&new_info) = number_map.get(&info) {
-                document.structure.trailer.info = Some(new_info);
+                document.set_info_id(new_info);
             }
         }
         
@@ -495,7 +498,7 @@ mod tests {
         let referenced = ObjectId { number: 2, generation: 0 };
         let unreferenced = ObjectId { number: 3, generation: 0 };
         
-        document.structure.trailer.root = root;
+        document.set_root_id(root);
         document.structure.objects.insert(root, Object::Reference(referenced));
         document.structure.objects.insert(referenced, Object::Null);
         document.structure.objects.insert(unreferenced, Object::Null);
@@ -0,0 +1,100 @@
+//! Syntax-layer normalization
+//! Author: kartik4091
+//! Created: 2025-06-04 20:17:36 UTC
+//! Rewrites a file from its parsed objects so comments, padding between
+//! objects, whitespace runs and data after `%%EOF` are dropped. Comments
+//! inside content streams are part of the stream data and are left alone.
+//! Names and strings are written back in canonical form, which undoes
+//! escapes used to hide keys like /J#61vaScript.
+
+use super::{CleanerError, Result};
+use crate::antiforensics::{analyzer::xref_integrity, scanner::syntax_stego::layout};
+
+/// What normalizing a file removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyntaxNormalizeReport {
+    /// Comments other than the header, binary marker and `%%EOF`
+    pub comments_removed: usize,
+    /// Non-whitespace bytes that followed the last `%%EOF`
+    pub trailing_bytes_removed: usize,
+    /// Names and strings whose needless escapes were undone
+    pub escapes_decoded: usize,
+    /// Bytes the file shrank by
+    pub bytes_removed: usize,
+}
+
+/// Drops everything outside the object graph
+#[derive(Debug, Clone, Default)]
+pub struct SyntaxNormalizer;
+
+impl SyntaxNormalizer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Returns the rewritten file and what was removed from `data`
+    pub fn normalize(&self, data: &[u8]) -> Result<(Vec<u8>, SyntaxNormalizeReport)> {
+        let before = layout(data);
+        let mut doc = xref_integrity::load_checked(data)
+            .map_err(|e| CleanerError::Internal(format!("syntax normalization: {}", e)))?;
+
+        let mut output = Vec::new();
+        doc.save_to(&mut output)?;
+
+        let after = layout(&output);
+        let report = SyntaxNormalizeReport {
+            comments_removed: before.content_comments().count().saturating_sub(after.content_comments().count()),
+            trailing_bytes_removed: before.trailing_bytes,
+            escapes_decoded: (before.obfuscated_names.len() + before.obfuscated_strings.len())
+                .saturating_sub(after.obfuscated_names.len() + after.obfuscated_strings.len()),
+            bytes_removed: data.len().saturating_sub(output.len()),
+        };
+        Ok((output, report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antiforensics::scanner::SyntaxScanner;
+    use lopdf::dictionary;
+
+    fn padded_document() -> Vec<u8> {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog" });
+        doc.trailer.set("Root", catalog);
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+
+        // Hide a comment and padding after the last object; only startxref moves
+        let mut hidden = b"%U2VjcmV0IHBheWxvYWQgaGlkZGVuIGluIGEgY29tbWVudA==\n".to_vec();
+        hidden.extend_from_slice(&[b' '; 128]);
+        let xref = data.windows(6).position(|w| w == b"\nxref\n").unwrap() + 1;
+        let startxref = data.windows(9).rposition(|w| w == b"startxref").unwrap();
+        let offset: usize = String::from_utf8_lossy(&data[startxref + 9..])
+            .split_whitespace().next().unwrap().parse().unwrap();
+        let mut table = data.split_off(xref);
+        table.truncate(startxref - xref);
+        data.extend_from_slice(&hidden);
+        data.extend_from_slice(&table);
+        data.extend_from_slice(format!("startxref\n{}\n%%EOF\n", offset + hidden.len()).as_bytes());
+        data.extend_from_slice(b"appended secret");
+        data
+    }
+
+    #[test]
+    fn test_comments_and_trailing_data_removed() {
+        let data = padded_document();
+        assert!(!SyntaxScanner::new().scan_bytes(&data).is_empty());
+
+        let (output, report) = SyntaxNormalizer::new().normalize(&data).unwrap();
+        assert_eq!(report.comments_removed, 1);
+        assert_eq!(report.trailing_bytes_removed, "appendedsecret".len());
+        assert!(report.bytes_removed > 128);
+
+        let after = layout(&output);
+        assert_eq!(after.content_comments().count(), 0);
+        assert_eq!(after.trailing_bytes, 0);
+        assert!(lopdf::Document::load_mem(&output).is_ok());
+    }
+}
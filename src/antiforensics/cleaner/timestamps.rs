@@ -0,0 +1,127 @@
+//! Filesystem Timestamp Control
+//! Author: kartik4091
+//! Created: 2025-06-04 18:05:12 UTC
+//! Sets the access, modification and (on Windows) creation times of
+//! written or cleaned files, so the filesystem does not record when a
+//! document was processed. Unix change time (ctime) is maintained by the
+//! kernel and cannot be set; it always reflects the last write.
+
+use std::{
+    fs::{File, FileTimes, Metadata, OpenOptions},
+    io,
+    path::Path,
+    time::SystemTime,
+};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+/// How output file times are set
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimestampPolicy {
+    /// Leave whatever the filesystem records
+    #[default]
+    Untouched,
+    /// Set every settable time to a fixed instant
+    Fixed(DateTime<Utc>),
+    /// Copy times from the input (or, for in-place cleaning, the file before it was touched)
+    CopyFromInput,
+}
+
+/// Times captured from a file before processing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileTimestamps {
+    pub accessed: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+}
+
+impl FileTimestamps {
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            accessed: metadata.accessed().ok(),
+            modified: metadata.modified().ok(),
+            created: metadata.created().ok(),
+        }
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Ok(Self::from_metadata(&std::fs::metadata(path)?))
+    }
+
+    fn fixed(time: SystemTime) -> Self {
+        Self {
+            accessed: Some(time),
+            modified: Some(time),
+            created: Some(time),
+        }
+    }
+}
+
+/// Applies `policy` to `path`
+///
+/// `input` holds the times to copy under `CopyFromInput`; without it that
+/// policy leaves the file untouched.
+pub fn apply(path: &Path, policy: &TimestampPolicy, input: Option<FileTimestamps>) -> io::Result<()> {
+    let times = match (policy, input) {
+        (TimestampPolicy::Untouched, _) | (TimestampPolicy::CopyFromInput, None) => return Ok(()),
+        (TimestampPolicy::Fixed(time), _) => FileTimestamps::fixed((*time).into()),
+        (TimestampPolicy::CopyFromInput, Some(input)) => input,
+    };
+
+    let file = OpenOptions::new().write(true).open(path)?;
+    set(&file, &times)
+}
+
+fn set(file: &File, times: &FileTimestamps) -> io::Result<()> {
+    let mut file_times = FileTimes::new();
+    if let Some(accessed) = times.accessed {
+        file_times = file_times.set_accessed(accessed);
+    }
+    if let Some(modified) = times.modified {
+        file_times = file_times.set_modified(modified);
+    }
+    #[cfg(windows)]
+    if let Some(created) = times.created {
+        use std::os::windows::fs::FileTimesExt;
+        file_times = file_times.set_created(created);
+    }
+    file.set_times(file_times)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::time::Duration;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_fixed_time_applied() {
+        let file = NamedTempFile::new().unwrap();
+        let fixed = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        apply(file.path(), &TimestampPolicy::Fixed(fixed), None).unwrap();
+
+        let modified = std::fs::metadata(file.path()).unwrap().modified().unwrap();
+        assert_eq!(modified, SystemTime::from(fixed));
+    }
+
+    #[test]
+    fn test_copy_from_input() {
+        let input = NamedTempFile::new().unwrap();
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        input.as_file().set_times(FileTimes::new().set_modified(old).set_accessed(old)).unwrap();
+        let times = FileTimestamps::read(input.path()).unwrap();
+
+        let output = NamedTempFile::new().unwrap();
+        apply(output.path(), &TimestampPolicy::CopyFromInput, Some(times)).unwrap();
+        assert_eq!(std::fs::metadata(output.path()).unwrap().modified().unwrap(), old);
+    }
+
+    #[test]
+    fn test_untouched_leaves_file_alone() {
+        let file = NamedTempFile::new().unwrap();
+        let before = std::fs::metadata(file.path()).unwrap().modified().unwrap();
+        apply(file.path(), &TimestampPolicy::Untouched, None).unwrap();
+        assert_eq!(std::fs::metadata(file.path()).unwrap().modified().unwrap(), before);
+    }
+}
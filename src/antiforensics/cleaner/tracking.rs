@@ -0,0 +1,334 @@
+//! Tracking element removal
+//! Author: kartik4091
+//! Created: 2025-06-04 23:27:40 UTC
+//! Removes the references found by the tracking scanner, or localizes
+//! external streams by embedding the file they name. Localizing is opt-in
+//! and only reads files inside a base directory given for it: the names
+//! come from an untrusted document, so absolute names, `..` and links
+//! leading out of the directory are refused. Network targets are never
+//! fetched, since fetching them is exactly the phone-home this prevents,
+//! and are removed instead. Tracking actions are always removed, together with
+//! every `/A`, `/OpenAction`, `/AA` and `/Next` entry pointing at them.
+
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+};
+use lopdf::{Dictionary, Object, ObjectId};
+use serde::{Serialize, Deserialize};
+
+use super::{
+    scope::{object_pages, CleanTarget, CleaningScope},
+    Result,
+};
+use crate::antiforensics::{
+    scanner::tracking::{find_tracking, tracking_action, TrackingElement, TrackingKind},
+    types::Document,
+    ArtifactType,
+};
+
+/// What happens to an external stream reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TrackingAction {
+    /// Drop the reference, keeping whatever data the stream holds itself
+    #[default]
+    Remove,
+    /// Embed the referenced local file; remove references to network targets
+    Localize,
+}
+
+/// Stream dictionary keys describing external data
+const EXTERNAL_KEYS: [&[u8]; 3] = [b"F", b"FFilter", b"FDecodeParms"];
+
+/// Tracking cleaning configuration
+#[derive(Debug, Clone)]
+pub struct TrackingCleaner {
+    action: TrackingAction,
+    /// Directory file names are resolved in when localizing; without one,
+    /// external streams are removed
+    base_dir: Option<PathBuf>,
+    /// Elements outside the scope are left in place
+    scope: CleaningScope,
+}
+
+/// What tracking cleaning changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackingCleanReport {
+    /// External streams that now embed their data
+    pub localized: Vec<ObjectId>,
+    /// Elements whose reference or action was removed
+    pub removed: Vec<(ObjectId, TrackingKind)>,
+}
+
+impl TrackingCleanReport {
+    /// Cleaning actions, e.g. `/Tracking/SubmitForm/12 0 R`
+    pub fn actions(&self) -> Vec<String> {
+        let localized = self.localized.iter().map(|id| (id, "Localized".to_string()));
+        let removed = self.removed.iter().map(|(id, kind)| (id, format!("{:?}", kind)));
+        localized.chain(removed)
+            .map(|((number, generation), what)| format!("/Tracking/{}/{} {} R", what, number, generation))
+            .collect()
+    }
+}
+
+impl TrackingCleaner {
+    pub fn new(action: TrackingAction) -> Self {
+        Self { action, base_dir: None, scope: CleaningScope::default() }
+    }
+
+    /// Localizes files from inside `dir` only
+    pub fn with_base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(dir.into());
+        self
+    }
+
+    /// Restricts cleaning to elements inside `scope`
+    pub fn with_scope(mut self, scope: CleaningScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    pub fn clean(&self, doc: &mut Document) -> Result<TrackingCleanReport> {
+        let mut report = TrackingCleanReport::default();
+        let pages = if self.scope.is_unrestricted() { Default::default() } else { object_pages(&doc.structure) };
+        let elements: Vec<TrackingElement> = find_tracking(&doc.structure).into_iter()
+            .filter(|e| self.scope.allows(&CleanTarget {
+                location: &format!("{} {} R", e.object.0, e.object.1),
+                artifact_type: ArtifactType::Structure,
+                object: Some(e.object),
+                page: pages.get(&e.object).copied(),
+            }))
+            .collect();
+
+        let mut doomed = HashSet::new();
+        for element in &elements {
+            if element.kind == TrackingKind::ExternalStream {
+                if self.action == TrackingAction::Localize && !element.remote && self.localize(doc, element) {
+                    report.localized.push(element.object);
+                } else {
+                    if let Some(Object::Stream(stream)) = doc.object_mut(element.object) {
+                        for key in EXTERNAL_KEYS {
+                            stream.dict.remove(key);
+                        }
+                    }
+                    report.removed.push((element.object, element.kind));
+                }
+                continue;
+            }
+
+            let is_action = doc.dictionary(element.object)
+                .is_some_and(|dict| tracking_action(&doc.structure, dict).is_some());
+            if is_action {
+                doomed.insert(element.object);
+            } else {
+                remove_inline_actions(&mut doc.structure, element.object);
+            }
+            report.removed.push((element.object, element.kind));
+        }
+
+        if !doomed.is_empty() {
+            unlink_actions(&mut doc.structure, &doomed);
+            for id in &doomed {
+                doc.structure.objects.remove(id);
+            }
+        }
+        report.removed.dedup();
+        Ok(report)
+    }
+
+    /// Embeds the local file an external stream names; false when it cannot be read
+    fn localize(&self, doc: &mut Document, element: &TrackingElement) -> bool {
+        let Some(path) = self.resolve(&element.target) else { return false };
+        let data = match std::fs::read(crate::paths::extended(&path)) {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::debug!("Cannot localize {}: {}", path.display(), e);
+                return false;
+            }
+        };
+        let Some(Object::Stream(stream)) = doc.object_mut(element.object) else { return false };
+        // The file is encoded with the external filters, which now apply to the embedded data
+        let filter = stream.dict.remove(b"FFilter");
+        let parms = stream.dict.remove(b"FDecodeParms");
+        stream.dict.remove(b"F");
+        stream.dict.remove(b"Filter");
+        stream.dict.remove(b"DecodeParms");
+        if let Some(filter) = filter {
+            stream.dict.set("Filter", filter);
+        }
+        if let Some(parms) = parms {
+            stream.dict.set("DecodeParms", parms);
+        }
+        stream.set_content(data);
+        true
+    }
+
+    /// Canonical path of `target` inside the base directory
+    ///
+    /// `None` for URLs, absolute names, names with `..` and names that lead
+    /// out of the directory through a link.
+    fn resolve(&self, target: &str) -> Option<PathBuf> {
+        let base_dir = self.base_dir.as_ref()?;
+        let target = target.strip_prefix("file://").unwrap_or(target);
+        if target.contains("://") {
+            return None;
+        }
+        let path = Path::new(target);
+        if !path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+            tracing::warn!("Not localizing {:?}: outside the base directory", target);
+            return None;
+        }
+        let base_dir = base_dir.canonicalize().ok()?;
+        let resolved = base_dir.join(path).canonicalize().ok()?;
+        if !resolved.starts_with(&base_dir) {
+            tracing::warn!("Not localizing {:?}: links outside the base directory", target);
+            return None;
+        }
+        Some(resolved)
+    }
+}
+
+impl Default for TrackingCleaner {
+    fn default() -> Self {
+        Self::new(TrackingAction::default())
+    }
+}
+
+/// Whether `object` is an inline tracking action
+fn is_inline_tracking(doc: &lopdf::Document, object: &Object) -> bool {
+    object.as_dict().is_ok_and(|action| tracking_action(doc, action).is_some())
+}
+
+/// Drops inline tracking actions from the `/A`, `/OpenAction` and `/AA` entries of `holder`
+fn remove_inline_actions(doc: &mut lopdf::Document, holder: ObjectId) {
+    let Ok(dict) = doc.get_dictionary(holder) else { return };
+    let keys: Vec<Vec<u8>> = [&b"A"[..], b"OpenAction"].into_iter()
+        .filter(|key| dict.get(key).is_ok_and(|action| is_inline_tracking(doc, action)))
+        .map(<[u8]>::to_vec)
+        .collect();
+    let triggers: Vec<Vec<u8>> = match dict.get(b"AA") {
+        Ok(Object::Dictionary(aa)) => aa.iter()
+            .filter(|(_, action)| is_inline_tracking(doc, action))
+            .map(|(key, _)| key.clone())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let Ok(dict) = doc.get_object_mut(holder).and_then(Object::as_dict_mut) else { return };
+    for key in keys {
+        dict.remove(&key);
+    }
+    remove_triggers(dict, |key, _| triggers.contains(key));
+}
+
+/// Drops every reference to the actions in `doomed`
+fn unlink_actions(doc: &mut lopdf::Document, doomed: &HashSet<ObjectId>) {
+    let points_at = |object: &Object| matches!(object, Object::Reference(id) if doomed.contains(id));
+    for object in doc.objects.values_mut() {
+        let dict = match object {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &mut stream.dict,
+            _ => continue,
+        };
+        for key in [&b"A"[..], b"OpenAction", b"Next"] {
+            if dict.get(key).is_ok_and(points_at) {
+                dict.remove(key);
+            }
+        }
+        if let Ok(Object::Array(next)) = dict.get_mut(b"Next") {
+            next.retain(|action| !points_at(action));
+        }
+        remove_triggers(dict, |_, action| points_at(action));
+    }
+}
+
+/// Removes `/AA` entries matching `drop`, and `/AA` itself once empty
+fn remove_triggers(dict: &mut Dictionary, drop: impl Fn(&Vec<u8>, &Object) -> bool) {
+    let Ok(Object::Dictionary(aa)) = dict.get_mut(b"AA") else { return };
+    let keys: Vec<Vec<u8>> = aa.iter().filter(|(key, action)| drop(key, action)).map(|(key, _)| key.clone()).collect();
+    for key in keys {
+        aa.remove(&key);
+    }
+    if aa.is_empty() {
+        dict.remove(b"AA");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antiforensics::scanner::tracking::tests::tracking_document;
+    use lopdf::StringFormat;
+
+    fn document() -> Document {
+        Document::from_pdf("tracking.pdf".into(), tracking_document())
+    }
+
+    #[test]
+    fn test_remove_all_tracking() {
+        let mut doc = document();
+        let report = TrackingCleaner::default().clean(&mut doc).unwrap();
+
+        assert_eq!(report.removed.len(), 3);
+        assert!(find_tracking(&doc.structure).is_empty());
+        // The widget no longer points at the deleted submit action
+        let widget = doc.objects()
+            .filter_map(|(_, o)| o.as_dict().ok())
+            .find(|d| d.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Widget"[..]))
+            .unwrap();
+        assert!(!widget.has(b"A"));
+        assert_eq!(report.actions()[0], "/Tracking/ExternalStream/1 0 R");
+    }
+
+    #[test]
+    fn test_localize_embeds_local_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("logo.jpg"), b"\xFF\xD8jpeg").unwrap();
+        let mut doc = document();
+        let local = doc.structure.add_object(lopdf::Stream::new(
+            lopdf::dictionary! {
+                "F" => Object::String(b"logo.jpg".to_vec(), StringFormat::Literal),
+                "FFilter" => "DCTDecode",
+            },
+            Vec::new(),
+        ));
+
+        let report = TrackingCleaner::new(TrackingAction::Localize)
+            .with_base_dir(dir.path())
+            .clean(&mut doc)
+            .unwrap();
+
+        assert_eq!(report.localized, vec![local]);
+        let stream = doc.object(local).unwrap().as_stream().unwrap();
+        assert_eq!(stream.content, b"\xFF\xD8jpeg");
+        assert_eq!(stream.dict.get(b"Filter").unwrap().as_name().unwrap(), b"DCTDecode");
+        assert!(!stream.dict.has(b"F"));
+        // The remote image is not fetched, only unlinked
+        assert!(report.removed.iter().any(|(_, kind)| *kind == TrackingKind::ExternalStream));
+    }
+
+    #[test]
+    fn test_localize_rejects_paths_outside_base_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let base = root.path().join("base");
+        std::fs::create_dir(&base).unwrap();
+        let secret = root.path().join("secret");
+        std::fs::write(&secret, b"key").unwrap();
+        let cleaner = TrackingCleaner::new(TrackingAction::Localize).with_base_dir(&base);
+
+        let absolute = secret.to_string_lossy().into_owned();
+        for target in [absolute.as_str(), "../secret", "sub/../../secret", "file:///etc/shadow"] {
+            assert_eq!(cleaner.resolve(target), None, "{}", target);
+        }
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&secret, base.join("link")).unwrap();
+            assert_eq!(cleaner.resolve("link"), None);
+        }
+        // Without a base directory nothing is localized
+        assert_eq!(TrackingCleaner::new(TrackingAction::Localize).resolve("logo.jpg"), None);
+
+        std::fs::write(base.join("logo.jpg"), b"jpeg").unwrap();
+        assert_eq!(cleaner.resolve("./logo.jpg"), Some(base.canonicalize().unwrap().join("logo.jpg")));
+    }
+}
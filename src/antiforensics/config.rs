@@ -7,14 +7,17 @@ use std::{
     fs,
     path::{Path, PathBuf},
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
+use tokio::{
+    sync::{broadcast, RwLock},
+    task::JoinHandle,
+};
 use tracing::{debug, error, info, warn, Level};
 
-use crate::error::{Error, Result};
+use crate::antiforensics::error::{Error, Result};
 
 /// Core configuration structure for the antiforensics system
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +45,10 @@ pub struct Config {
     
     /// Resource limits
     pub resources: ResourceConfig,
+
+    /// Retries of transient IO failures
+    #[serde(default)]
+    pub retry: RetryConfig,
     
     /// Custom settings
     #[serde(default)]
@@ -128,12 +135,45 @@ pub struct ResourceConfig {
     pub nice_value: i8,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Attempts including the first; 1 disables retries
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
 /// Configuration manager for dynamic config updates
-#[derive(Debug)]
+///
+/// Watchers run on every update before it is stored, and may reject it;
+/// `PdfEngine::follow_config` registers one that hot-applies the worker
+/// budget, retry policy and pattern database. Subscribers are told about
+/// updates after they are stored.
 pub struct ConfigManager {
     config: Arc<RwLock<Config>>,
     config_path: PathBuf,
     watchers: Vec<ConfigWatcher>,
+    changes: broadcast::Sender<Arc<Config>>,
+}
+
+impl std::fmt::Debug for ConfigManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfigManager")
+            .field("config_path", &self.config_path)
+            .field("watchers", &self.watchers.len())
+            .finish()
+    }
 }
 
 type ConfigWatcher = Box<dyn Fn(&Config) -> Result<()> + Send + Sync>;
@@ -205,6 +245,7 @@ impl Config {
                 io_priority: 4,
                 nice_value: 0,
             },
+            retry: RetryConfig::default(),
             custom: HashMap::new(),
         }
     }
@@ -265,6 +306,7 @@ impl ConfigManager {
             config: Arc::new(RwLock::new(config)),
             config_path: path.as_ref().to_path_buf(),
             watchers: Vec::new(),
+            changes: broadcast::channel(16).0,
         })
     }
 
@@ -272,19 +314,62 @@ impl ConfigManager {
         self.watchers.push(watcher);
     }
 
+    /// Receives every configuration stored from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Config>> {
+        self.changes.subscribe()
+    }
+
     pub async fn update(&self, new_config: Config) -> Result<()> {
         new_config.validate()?;
 
+        // Held through the watchers, so concurrent updates apply in the order they are stored
+        let mut config = self.config.write().await;
         for watcher in &self.watchers {
             watcher(&new_config)?;
         }
-
-        let mut config = self.config.write().await;
         *config = new_config;
 
+        // No subscribers is not an error
+        let _ = self.changes.send(Arc::new(config.clone()));
         Ok(())
     }
 
+    /// Reads the config file again and applies it
+    pub async fn reload(&self) -> Result<()> {
+        let contents = tokio::fs::read_to_string(&self.config_path).await?;
+        self.update(Config::from_str(&contents)?).await
+    }
+
+    /// Reloads the config file whenever its modification time changes
+    ///
+    /// Files that fail to parse, validate or pass a watcher are logged and
+    /// skipped; the previous configuration stays live.
+    pub fn watch(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut last_modified: Option<SystemTime> = None;
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let modified = match tokio::fs::metadata(&self.config_path).await.and_then(|m| m.modified()) {
+                    Ok(modified) => modified,
+                    Err(e) => {
+                        debug!("Config file {} unavailable: {}", self.config_path.display(), e);
+                        continue;
+                    }
+                };
+                // The file was loaded when the manager was created
+                let previous = last_modified.replace(modified);
+                if previous.is_none() || previous == Some(modified) {
+                    continue;
+                }
+                match self.reload().await {
+                    Ok(()) => info!("Reloaded configuration from {}", self.config_path.display()),
+                    Err(e) => warn!("Rejected configuration reload from {}: {}", self.config_path.display(), e),
+                }
+            }
+        })
+    }
+
     pub async fn get_config(&self) -> Arc<Config> {
         Arc::new(self.config.read().await.clone())
     }
@@ -339,4 +424,30 @@ mod tests {
         manager.update(new_config).await.unwrap();
         assert!(watcher_called.load(std::sync::atomic::Ordering::SeqCst));
     }
+
+    #[tokio::test]
+    async fn test_rejected_update_keeps_config() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.yml");
+        fs::write(&config_path, serde_yaml::to_string(&Config::default()).unwrap()).unwrap();
+
+        let mut manager = ConfigManager::new(&config_path).await.unwrap();
+        manager.add_watcher(Box::new(|config| match config.performance.max_concurrent_tasks {
+            0 => Err(Error::Configuration("no workers".into())),
+            _ => Ok(()),
+        }));
+        let mut changes = manager.subscribe();
+
+        let mut rejected = Config::default();
+        rejected.performance.max_concurrent_tasks = 0;
+        assert!(manager.update(rejected).await.is_err());
+        assert_eq!(manager.get_config().await.performance.max_concurrent_tasks, 32);
+        assert!(changes.try_recv().is_err());
+
+        let mut reloaded = Config::default();
+        reloaded.retry.max_attempts = 1;
+        fs::write(&config_path, serde_yaml::to_string(&reloaded).unwrap()).unwrap();
+        manager.reload().await.unwrap();
+        assert_eq!(changes.try_recv().unwrap().retry.max_attempts, 1);
+    }
 }
\ No newline at end of file
@@ -6,7 +6,12 @@ use std::{
     sync::Arc,
     collections::HashMap,
     time::{Instant, Duration},
-    io::{self, Read, BufReader},
+    io::{self, Read, BufReader, Write},
+    path::Path,
+    fmt,
+    hash::Hasher as _,
+    str::FromStr,
+    sync::OnceLock,
 };
 use tokio::{
     sync::{RwLock, Semaphore},
@@ -15,9 +20,13 @@ use tokio::{
 };
 use sha2::{Sha256, Sha512, Digest};
 use md5::{Md5, Md5State};
+use sha1::Sha1;
+use sha3::Sha3_256;
 use blake3::Hasher as Blake3;
+use twox_hash::XxHash64;
 use tracing::{info, warn, error, debug, instrument};
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
 use crate::{
     error::{Result, ForensicError},
@@ -41,19 +50,74 @@ struct HashState {
 }
 
 /// Document hashes
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Also used as the on-disk manifest format for verification; algorithms
+/// missing from a manifest deserialize as empty strings and are skipped.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DocumentHashes {
     /// Document ID
+    #[serde(default)]
     pub document_id: String,
     /// MD5 hash
+    #[serde(default)]
     pub md5: String,
+    /// SHA1 hash
+    #[serde(default)]
+    pub sha1: String,
     /// SHA256 hash
+    #[serde(default)]
     pub sha256: String,
     /// SHA512 hash
+    #[serde(default)]
     pub sha512: String,
     /// BLAKE3 hash
+    #[serde(default)]
     pub blake3: String,
+    /// SHA3-256 hash
+    #[serde(default)]
+    pub sha3_256: String,
+    /// xxHash64 checksum (seed 0), not collision resistant
+    #[serde(default)]
+    pub xxh64: String,
     /// Computation timestamp
+    #[serde(default)]
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Result of comparing a single algorithm against the expected value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashMatchStatus {
+    /// Computed hash equals the expected hash
+    Match,
+    /// Computed hash differs from the expected hash
+    Mismatch,
+    /// No expected value was provided
+    Skipped,
+}
+
+/// Per-algorithm comparison entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashComparison {
+    /// Algorithm compared
+    pub algorithm: HashAlgorithm,
+    /// Expected hash from the manifest
+    pub expected: String,
+    /// Recomputed hash
+    pub actual: String,
+    /// Comparison outcome
+    pub status: HashMatchStatus,
+}
+
+/// Outcome of verifying a document against known-good hashes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashVerification {
+    /// Document ID
+    pub document_id: String,
+    /// Per-algorithm comparisons
+    pub comparisons: Vec<HashComparison>,
+    /// True when a cryptographic hash matched and no algorithm mismatched
+    pub verified: bool,
+    /// Verification timestamp
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
@@ -82,20 +146,197 @@ enum HashOperation {
     /// Verify existing hashes
     Verify,
     /// Update specific hash
-    Update(HashType),
+    Update(HashAlgorithm),
 }
 
-/// Hash type
-#[derive(Debug, Clone, Copy)]
-pub enum HashType {
+/// Hash algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HashAlgorithm {
     /// MD5 hash
     Md5,
+    /// SHA1 hash
+    Sha1,
     /// SHA256 hash
     Sha256,
     /// SHA512 hash
     Sha512,
     /// BLAKE3 hash
     Blake3,
+    /// SHA3-256 hash
+    Sha3_256,
+    /// xxHash64 checksum, for fast integrity checks where collisions are
+    /// not a concern
+    XxHash64,
+}
+
+/// Former name of [`HashAlgorithm`]
+pub type HashType = HashAlgorithm;
+
+impl HashAlgorithm {
+    /// Every supported algorithm
+    pub const ALL: &'static [HashAlgorithm] = &[
+        Self::Md5,
+        Self::Sha1,
+        Self::Sha256,
+        Self::Sha512,
+        Self::Blake3,
+        Self::Sha3_256,
+        Self::XxHash64,
+    ];
+
+    /// Algorithms computed when none are selected
+    pub const DEFAULT: &'static [HashAlgorithm] = &[
+        Self::Md5,
+        Self::Sha1,
+        Self::Sha256,
+        Self::Sha512,
+        Self::Blake3,
+    ];
+
+    /// Name accepted by `--hash` and printed in reports
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Md5 => "md5",
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+            Self::Blake3 => "blake3",
+            Self::Sha3_256 => "sha3-256",
+            Self::XxHash64 => "xxh64",
+        }
+    }
+
+    /// False for checksums that only guard against accidental corruption
+    pub fn is_cryptographic(self) -> bool {
+        self != Self::XxHash64
+    }
+
+    /// Parses a comma-separated list such as `md5,sha256,blake3`
+    pub fn parse_list(list: &str) -> std::result::Result<Vec<Self>, String> {
+        let mut algorithms = Vec::new();
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let algorithm = name.parse()?;
+            if !algorithms.contains(&algorithm) {
+                algorithms.push(algorithm);
+            }
+        }
+        if algorithms.is_empty() {
+            return Err("no hash algorithms given".to_string());
+        }
+        Ok(algorithms)
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "md5" => Ok(Self::Md5),
+            "sha1" | "sha-1" => Ok(Self::Sha1),
+            "sha256" | "sha-256" => Ok(Self::Sha256),
+            "sha512" | "sha-512" => Ok(Self::Sha512),
+            "blake3" => Ok(Self::Blake3),
+            "sha3" | "sha3-256" => Ok(Self::Sha3_256),
+            "xxh64" | "xxhash64" | "xxhash" => Ok(Self::XxHash64),
+            other => Err(format!(
+                "unknown hash algorithm `{}` (expected one of: {})",
+                other,
+                Self::ALL.iter().map(|a| a.name()).collect::<Vec<_>>().join(", "),
+            )),
+        }
+    }
+}
+
+/// Inputs at least this large are hashed in parallel under the default
+/// [`Blake3Mode::Auto`]
+///
+/// [`calibrate_parallel_threshold`] put the crossover between 1 and 4 MiB
+/// on 4 to 32 core machines; the default errs high so that small files on
+/// busy hosts do not pay for thread dispatch.
+pub const PARALLEL_BLAKE3_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// Read size when BLAKE3 runs in parallel, large enough to keep every
+/// thread busy
+pub const PARALLEL_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Below this, `update_rayon` is slower than a plain update
+const PARALLEL_MIN_UPDATE: usize = 128 * 1024;
+
+/// How BLAKE3 spreads its work across threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blake3Mode {
+    /// Hash on the calling thread
+    Sequential,
+    /// Hash every chunk on the rayon pool
+    Parallel,
+    /// Hash in parallel once the input reaches `threshold` bytes
+    Auto { threshold: u64 },
+}
+
+impl Default for Blake3Mode {
+    fn default() -> Self {
+        Self::Auto { threshold: PARALLEL_BLAKE3_THRESHOLD }
+    }
+}
+
+impl Blake3Mode {
+    /// `Auto` with a threshold measured on this machine
+    ///
+    /// The measurement runs once per process and takes well under a second.
+    pub fn calibrated() -> Self {
+        static THRESHOLD: OnceLock<u64> = OnceLock::new();
+        Self::Auto { threshold: *THRESHOLD.get_or_init(calibrate_parallel_threshold) }
+    }
+
+    /// Whether an input of `len` bytes is hashed in parallel
+    pub fn is_parallel(self, len: u64) -> bool {
+        match self {
+            Self::Sequential => false,
+            Self::Parallel => true,
+            Self::Auto { threshold } => len >= threshold && rayon::current_num_threads() > 1,
+        }
+    }
+}
+
+/// Smallest input size at which parallel BLAKE3 beats sequential here
+///
+/// Times both modes on doubling sizes from 64 KiB to 32 MiB, best of three
+/// runs each, and returns the first size where parallel was at least 20%
+/// faster. Returns `u64::MAX` when it never was, e.g. on a single core.
+pub fn calibrate_parallel_threshold() -> u64 {
+    if rayon::current_num_threads() < 2 {
+        return u64::MAX;
+    }
+
+    let data = vec![0xA5u8; 32 * 1024 * 1024];
+    let best_of_three = |f: &dyn Fn()| {
+        (0..3).map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        }).min().unwrap_or_default()
+    };
+
+    let mut size = 64 * 1024;
+    while size <= data.len() {
+        let sample = &data[..size];
+        let sequential = best_of_three(&|| { std::hint::black_box(blake3::hash(sample)); });
+        let parallel = best_of_three(&|| {
+            std::hint::black_box(Blake3::new().update_rayon(sample).finalize());
+        });
+        if parallel.as_secs_f64() * 1.2 <= sequential.as_secs_f64() {
+            return size as u64;
+        }
+        size *= 2;
+    }
+    u64::MAX
 }
 
 /// Hash computation configuration
@@ -109,6 +350,8 @@ pub struct HashConfig {
     pub timeout: Duration,
     /// Cache computed hashes
     pub enable_cache: bool,
+    /// Whether BLAKE3 hashes documents across threads
+    pub blake3_mode: Blake3Mode,
 }
 
 impl Default for HashConfig {
@@ -118,6 +361,7 @@ impl Default for HashConfig {
             max_concurrent: num_cpus::get(),
             timeout: Duration::from_secs(300),
             enable_cache: true,
+            blake3_mode: Blake3Mode::default(),
         }
     }
 }
@@ -207,6 +451,47 @@ impl HashHandler {
         result
     }
 
+    /// Recomputes all hashes for a document and compares them against
+    /// known-good values, bypassing the hash cache
+    #[instrument(skip(self, document, expected), err(Debug))]
+    pub async fn verify_hashes(&self, document: &Document, expected: DocumentHashes) -> Result<HashVerification> {
+        debug!("Verifying hashes for document {}", document.id());
+
+        let _permit = self.acquire_permit().await?;
+        let start = Instant::now();
+
+        {
+            let mut state = self.state.write().await;
+            state.active_computations += 1;
+        }
+
+        let result = self.compute_all_hashes(document).await;
+
+        {
+            let mut state = self.state.write().await;
+            state.active_computations -= 1;
+            state.computation_history.push(ComputationRecord {
+                document_id: document.id().to_string(),
+                operation: HashOperation::Verify,
+                start_time: start,
+                duration: start.elapsed(),
+                bytes_processed: document.size(),
+                success: result.is_ok(),
+            });
+        }
+
+        let verification = compare_hashes(&result?, &expected);
+        self.metrics.increment_counter(
+            if verification.verified { "hash_verifications_passed" } else { "hash_verifications_failed" }
+        ).await;
+
+        if !verification.verified {
+            warn!("Hash verification failed for document {}", document.id());
+        }
+
+        Ok(verification)
+    }
+
     /// Gets cached hashes if available
     async fn get_cached_hashes(&self, document: &Document) -> Option<DocumentHashes> {
         let state = self.state.read().await;
@@ -224,15 +509,11 @@ impl HashHandler {
 
     /// Computes all hashes for a document
     async fn compute_all_hashes(&self, document: &Document) -> Result<DocumentHashes> {
-        let mut buffer = vec![0u8; self.config.buffer_size];
+        let parallel = self.config.blake3_mode.is_parallel(document.size());
+        let buffer_size = if parallel { self.config.buffer_size.max(PARALLEL_CHUNK_SIZE) } else { self.config.buffer_size };
+        let mut buffer = vec![0u8; buffer_size];
         let mut file = document.open_async().await?;
-
-        let mut md5 = Md5::new();
-        let mut sha256 = Sha256::new();
-        let mut sha512 = Sha512::new();
-        let mut blake3 = Blake3::new();
-
-        let mut bytes_read = 0u64;
+        let mut hasher = StreamingHasher::new().parallel_blake3(parallel);
 
         loop {
             let n = file.read(&mut buffer).await?;
@@ -240,25 +521,328 @@ impl HashHandler {
                 break;
             }
 
-            md5.update(&buffer[..n]);
-            sha256.update(&buffer[..n]);
-            sha512.update(&buffer[..n]);
-            blake3.update(&buffer[..n]);
-
-            bytes_read += n as u64;
+            hasher.update(&buffer[..n]);
         }
 
         // Update metrics
-        self.metrics.increment_counter_by("bytes_processed", bytes_read).await;
-
-        Ok(DocumentHashes {
-            document_id: document.id().to_string(),
-            md5: format!("{:x}", md5.finalize()),
-            sha256: format!("{:x}", sha256.finalize()),
-            sha512: format!("{:x}", sha512.finalize()),
-            blake3: format!("{:x}", blake3.finalize()),
+        self.metrics.increment_counter_by("bytes_processed", hasher.bytes_hashed()).await;
+
+        Ok(hasher.finalize(document.id().to_string()))
+    }
+}
+
+impl HashVerification {
+    /// Returns comparisons whose hashes did not match
+    pub fn mismatches(&self) -> impl Iterator<Item = &HashComparison> {
+        self.comparisons.iter().filter(|c| c.status == HashMatchStatus::Mismatch)
+    }
+}
+
+impl DocumentHashes {
+    /// Loads a JSON hash manifest from disk
+    pub fn load_manifest(path: &Path) -> io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid hash manifest: {}", e)))
+    }
+
+    /// Digest for `algorithm`, empty when it was not computed
+    pub fn get(&self, algorithm: HashAlgorithm) -> &str {
+        match algorithm {
+            HashAlgorithm::Md5 => &self.md5,
+            HashAlgorithm::Sha1 => &self.sha1,
+            HashAlgorithm::Sha256 => &self.sha256,
+            HashAlgorithm::Sha512 => &self.sha512,
+            HashAlgorithm::Blake3 => &self.blake3,
+            HashAlgorithm::Sha3_256 => &self.sha3_256,
+            HashAlgorithm::XxHash64 => &self.xxh64,
+        }
+    }
+
+    /// Computed digests in `HashAlgorithm::ALL` order
+    pub fn computed(&self) -> impl Iterator<Item = (HashAlgorithm, &str)> {
+        HashAlgorithm::ALL.iter()
+            .map(move |&algorithm| (algorithm, self.get(algorithm)))
+            .filter(|(_, digest)| !digest.is_empty())
+    }
+}
+
+/// Compares computed hashes against expected values, algorithm by algorithm
+pub fn compare_hashes(actual: &DocumentHashes, expected: &DocumentHashes) -> HashVerification {
+    let comparisons: Vec<HashComparison> = HashAlgorithm::ALL.iter().map(|&algorithm| {
+        let expected = expected.get(algorithm).trim().to_lowercase();
+        let actual = actual.get(algorithm);
+        // An algorithm that was not computed cannot confirm the manifest
+        let status = if expected.is_empty() || actual.is_empty() {
+            HashMatchStatus::Skipped
+        } else if expected == actual.to_lowercase() {
+            HashMatchStatus::Match
+        } else {
+            HashMatchStatus::Mismatch
+        };
+
+        HashComparison {
+            algorithm,
+            expected,
+            actual: actual.to_string(),
+            status,
+        }
+    }).collect();
+
+    // A checksum match alone is easy to forge, so a cryptographic hash has
+    // to confirm the manifest
+    let compared = comparisons.iter()
+        .any(|c| c.status == HashMatchStatus::Match && c.algorithm.is_cryptographic());
+    let mismatched = comparisons.iter().any(|c| c.status == HashMatchStatus::Mismatch);
+
+    HashVerification {
+        document_id: actual.document_id.clone(),
+        comparisons,
+        verified: compared && !mismatched,
+        timestamp: chrono::Utc::now(),
+    }
+}
+
+/// Incremental hasher computing the selected algorithms in a single pass
+pub struct StreamingHasher {
+    md5: Option<Md5>,
+    sha1: Option<Sha1>,
+    sha256: Option<Sha256>,
+    sha512: Option<Sha512>,
+    blake3: Option<Blake3>,
+    sha3_256: Option<Sha3_256>,
+    xxh64: Option<XxHash64>,
+    parallel_blake3: bool,
+    bytes: u64,
+}
+
+impl StreamingHasher {
+    /// Creates a hasher for `HashAlgorithm::DEFAULT`
+    pub fn new() -> Self {
+        Self::with_algorithms(HashAlgorithm::DEFAULT)
+    }
+
+    /// Creates a hasher for `algorithms` only; the others finalize as
+    /// empty strings
+    pub fn with_algorithms(algorithms: &[HashAlgorithm]) -> Self {
+        let enabled = |algorithm| algorithms.contains(&algorithm);
+        Self {
+            md5: enabled(HashAlgorithm::Md5).then(Md5::new),
+            sha1: enabled(HashAlgorithm::Sha1).then(Sha1::new),
+            sha256: enabled(HashAlgorithm::Sha256).then(Sha256::new),
+            sha512: enabled(HashAlgorithm::Sha512).then(Sha512::new),
+            blake3: enabled(HashAlgorithm::Blake3).then(Blake3::new),
+            sha3_256: enabled(HashAlgorithm::Sha3_256).then(Sha3_256::new),
+            xxh64: enabled(HashAlgorithm::XxHash64).then(|| XxHash64::with_seed(0)),
+            parallel_blake3: false,
+            bytes: 0,
+        }
+    }
+
+    /// Hashes large chunks with BLAKE3 on the rayon pool
+    ///
+    /// The other selected algorithms run on the calling thread meanwhile.
+    /// Digests are identical either way.
+    pub fn parallel_blake3(mut self, enabled: bool) -> Self {
+        self.parallel_blake3 = enabled;
+        self
+    }
+
+    /// Feeds a chunk of data into every selected hasher
+    pub fn update(&mut self, data: &[u8]) {
+        let mut blake3 = self.blake3.take();
+        match &mut blake3 {
+            Some(h) if self.parallel_blake3 && data.len() >= PARALLEL_MIN_UPDATE => {
+                rayon::join(|| { h.update_rayon(data); }, || self.update_sequential(data));
+            }
+            Some(h) => {
+                h.update(data);
+                self.update_sequential(data);
+            }
+            None => self.update_sequential(data),
+        }
+        self.blake3 = blake3;
+        self.bytes += data.len() as u64;
+    }
+
+    /// Feeds every selected hasher except BLAKE3
+    fn update_sequential(&mut self, data: &[u8]) {
+        if let Some(h) = &mut self.md5 { h.update(data); }
+        if let Some(h) = &mut self.sha1 { h.update(data); }
+        if let Some(h) = &mut self.sha256 { h.update(data); }
+        if let Some(h) = &mut self.sha512 { h.update(data); }
+        if let Some(h) = &mut self.sha3_256 { h.update(data); }
+        if let Some(h) = &mut self.xxh64 { h.write(data); }
+    }
+
+    /// Number of bytes hashed so far
+    pub fn bytes_hashed(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Finishes hashing and returns the digests
+    pub fn finalize(self, document_id: impl Into<String>) -> DocumentHashes {
+        DocumentHashes {
+            document_id: document_id.into(),
+            md5: self.md5.map(|h| format!("{:x}", h.finalize())).unwrap_or_default(),
+            sha1: self.sha1.map(|h| format!("{:x}", h.finalize())).unwrap_or_default(),
+            sha256: self.sha256.map(|h| format!("{:x}", h.finalize())).unwrap_or_default(),
+            sha512: self.sha512.map(|h| format!("{:x}", h.finalize())).unwrap_or_default(),
+            blake3: self.blake3.map(|h| h.finalize().to_hex().to_string()).unwrap_or_default(),
+            sha3_256: self.sha3_256.map(|h| format!("{:x}", h.finalize())).unwrap_or_default(),
+            xxh64: self.xxh64.map(|h| format!("{:016x}", h.finish())).unwrap_or_default(),
             timestamp: chrono::Utc::now(),
-        })
+        }
+    }
+}
+
+impl Default for StreamingHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writer adapter that hashes every byte written through it
+///
+/// Lets output paths produce `DocumentHashes` as a by-product of writing
+/// instead of re-reading the finished file.
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: StreamingHasher,
+}
+
+impl<W: Write> HashingWriter<W> {
+    /// Wraps a writer, hashing with `HashAlgorithm::DEFAULT`
+    pub fn new(inner: W) -> Self {
+        Self::with_algorithms(inner, HashAlgorithm::DEFAULT)
+    }
+
+    /// Wraps a writer, hashing with `algorithms` only
+    pub fn with_algorithms(inner: W, algorithms: &[HashAlgorithm]) -> Self {
+        Self {
+            inner,
+            hasher: StreamingHasher::with_algorithms(algorithms),
+        }
+    }
+
+    /// Number of bytes written so far
+    pub fn bytes_written(&self) -> u64 {
+        self.hasher.bytes_hashed()
+    }
+
+    /// Flushes the inner writer and returns it with the computed hashes
+    pub fn finalize(mut self, document_id: impl Into<String>) -> io::Result<(W, DocumentHashes)> {
+        self.inner.flush()?;
+        Ok((self.inner, self.hasher.finalize(document_id)))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Computes the default hashes for a file on disk using blocking IO
+///
+/// Used by the CLI where no async runtime or metrics collector is available.
+pub fn hash_file(path: &Path, buffer_size: usize) -> io::Result<DocumentHashes> {
+    hash_file_with(path, buffer_size, HashAlgorithm::DEFAULT)
+}
+
+/// Like [`hash_file`], computing only `algorithms`
+pub fn hash_file_with(path: &Path, buffer_size: usize, algorithms: &[HashAlgorithm]) -> io::Result<DocumentHashes> {
+    hash_file_mode(path, buffer_size, algorithms, Blake3Mode::default())
+}
+
+/// Like [`hash_file_with`], choosing how BLAKE3 uses threads
+///
+/// Parallel hashing reads in [`PARALLEL_CHUNK_SIZE`] chunks regardless of
+/// `buffer_size`.
+pub fn hash_file_mode(
+    path: &Path,
+    buffer_size: usize,
+    algorithms: &[HashAlgorithm],
+    blake3_mode: Blake3Mode,
+) -> io::Result<DocumentHashes> {
+    let file = std::fs::File::open(path)?;
+    let parallel = algorithms.contains(&HashAlgorithm::Blake3) && blake3_mode.is_parallel(file.metadata()?.len());
+    let buffer_size = if parallel { buffer_size.max(PARALLEL_CHUNK_SIZE) } else { buffer_size.max(1) };
+
+    let mut reader = BufReader::with_capacity(buffer_size, file);
+    let mut buffer = vec![0u8; buffer_size];
+    let mut hasher = StreamingHasher::with_algorithms(algorithms).parallel_blake3(parallel);
+
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hasher.finalize(path.display().to_string()))
+}
+
+/// SHA-256 of a parsed object, independent of how it was serialized
+///
+/// Dictionary keys are hashed in sorted order and every value carries a
+/// type tag and length, so equal digests mean equal objects regardless of
+/// whitespace, key order or string notation in the file.
+pub fn object_digest(object: &lopdf::Object) -> String {
+    let mut hasher = Sha256::new();
+    hash_object(object, &mut hasher);
+    format!("{:x}", hasher.finalize())
+}
+
+fn hash_object(object: &lopdf::Object, hasher: &mut Sha256) {
+    use lopdf::Object;
+
+    let tagged = |hasher: &mut Sha256, tag: u8, data: &[u8]| {
+        hasher.update([tag]);
+        hasher.update((data.len() as u64).to_be_bytes());
+        hasher.update(data);
+    };
+    match object {
+        Object::Null => hasher.update(b"n"),
+        Object::Boolean(value) => hasher.update(if *value { b"t" } else { b"f" }),
+        Object::Integer(value) => tagged(hasher, b'i', &value.to_be_bytes()),
+        Object::Real(value) => tagged(hasher, b'r', &value.to_be_bytes()),
+        Object::Name(name) => tagged(hasher, b'/', name),
+        Object::String(data, _) => tagged(hasher, b's', data),
+        Object::Reference((number, generation)) => {
+            hasher.update(b"R");
+            hasher.update(number.to_be_bytes());
+            hasher.update(generation.to_be_bytes());
+        }
+        Object::Array(items) => {
+            tagged(hasher, b'[', &(items.len() as u64).to_be_bytes());
+            items.iter().for_each(|item| hash_object(item, hasher));
+        }
+        Object::Dictionary(dict) => hash_dictionary(dict, hasher),
+        Object::Stream(stream) => {
+            hash_dictionary(&stream.dict, hasher);
+            tagged(hasher, b'S', &stream.content);
+        }
+    }
+}
+
+fn hash_dictionary(dict: &lopdf::Dictionary, hasher: &mut Sha256) {
+    let mut entries: Vec<_> = dict.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    hasher.update(b"<<");
+    hasher.update((entries.len() as u64).to_be_bytes());
+    for (key, value) in entries {
+        hasher.update((key.len() as u64).to_be_bytes());
+        hasher.update(key);
+        hash_object(value, hasher);
     }
 }
 
@@ -371,4 +955,150 @@ mod tests {
         assert!(counters.get("hash_computations_completed").is_some());
         assert!(metrics.get_histogram("hash_computation_duration").await.count > 0);
     }
-                                      }
+
+    #[tokio::test]
+    async fn test_hash_verification() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let handler = HashHandler::new(HashConfig::default(), metrics.clone());
+
+        let test_file = create_test_file(b"Hello, World!").await;
+        let document = Document::new(test_file.path());
+        let expected = handler.compute_hashes(&document).await.unwrap();
+
+        let verification = handler.verify_hashes(&document, expected).await.unwrap();
+        assert!(verification.verified);
+        assert_eq!(verification.mismatches().count(), 0);
+    }
+
+    #[test]
+    fn test_compare_hashes_reports_mismatch() {
+        let actual = hash_file(create_sync_test_file(b"actual").path(), 1024).unwrap();
+        let expected = DocumentHashes {
+            md5: actual.md5.to_uppercase(),
+            sha256: "00".repeat(32),
+            sha512: String::new(),
+            ..actual.clone()
+        };
+
+        let verification = compare_hashes(&actual, &expected);
+        assert!(!verification.verified);
+
+        let statuses: Vec<_> = verification.comparisons.iter()
+            .map(|c| (c.algorithm, c.status))
+            .collect();
+        assert!(statuses.contains(&(HashAlgorithm::Md5, HashMatchStatus::Match)));
+        assert!(statuses.contains(&(HashAlgorithm::Sha256, HashMatchStatus::Mismatch)));
+        assert!(statuses.contains(&(HashAlgorithm::Sha512, HashMatchStatus::Skipped)));
+    }
+
+    #[test]
+    fn test_empty_manifest_is_not_verified() {
+        let actual = hash_file(create_sync_test_file(b"data").path(), 1024).unwrap();
+        let manifest: DocumentHashes = serde_json::from_str("{}").unwrap();
+
+        let verification = compare_hashes(&actual, &manifest);
+        assert!(!verification.verified);
+        assert!(verification.comparisons.iter().all(|c| c.status == HashMatchStatus::Skipped));
+    }
+
+    fn create_sync_test_file(content: &[u8]) -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), content).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_hashing_writer_matches_file_hash() {
+        let data = b"%PDF-1.7 streaming hash test".repeat(1000);
+        let file = NamedTempFile::new().unwrap();
+
+        let mut writer = HashingWriter::new(std::fs::File::create(file.path()).unwrap());
+        for chunk in data.chunks(333) {
+            writer.write_all(chunk).unwrap();
+        }
+        let (_, streamed) = writer.finalize("doc").unwrap();
+
+        let reread = hash_file(file.path(), 4096).unwrap();
+        assert_eq!(streamed.md5, reread.md5);
+        assert_eq!(streamed.sha1, reread.sha1);
+        assert_eq!(streamed.sha256, reread.sha256);
+        assert_eq!(streamed.sha512, reread.sha512);
+        assert_eq!(streamed.blake3, reread.blake3);
+    }
+
+    #[test]
+    fn test_selected_algorithms_only() {
+        let file = create_sync_test_file(b"");
+        let hashes = hash_file_with(
+            file.path(),
+            1024,
+            &[HashAlgorithm::Sha3_256, HashAlgorithm::XxHash64],
+        ).unwrap();
+
+        assert_eq!(hashes.sha3_256, "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a");
+        assert_eq!(hashes.xxh64, "ef46db3751d8e999");
+        assert!(hashes.md5.is_empty() && hashes.sha256.is_empty());
+        assert_eq!(
+            hashes.computed().map(|(a, _)| a).collect::<Vec<_>>(),
+            vec![HashAlgorithm::Sha3_256, HashAlgorithm::XxHash64],
+        );
+    }
+
+    #[test]
+    fn test_uncomputed_algorithm_is_skipped() {
+        let file = create_sync_test_file(b"data");
+        let actual = hash_file_with(file.path(), 1024, &[HashAlgorithm::Sha256, HashAlgorithm::XxHash64]).unwrap();
+        let expected = hash_file_with(file.path(), 1024, HashAlgorithm::ALL).unwrap();
+
+        let verification = compare_hashes(&actual, &expected);
+        assert!(verification.verified);
+        assert_eq!(
+            verification.comparisons.iter().filter(|c| c.status == HashMatchStatus::Match).count(),
+            2,
+        );
+    }
+
+    #[test]
+    fn test_checksum_match_alone_is_not_verified() {
+        let file = create_sync_test_file(b"data");
+        let actual = hash_file_with(file.path(), 1024, &[HashAlgorithm::XxHash64]).unwrap();
+        let expected = hash_file_with(file.path(), 1024, HashAlgorithm::ALL).unwrap();
+
+        let verification = compare_hashes(&actual, &expected);
+        assert!(!verification.verified);
+        assert_eq!(verification.mismatches().count(), 0);
+    }
+
+    #[test]
+    fn test_parse_algorithm_list() {
+        assert_eq!(
+            HashAlgorithm::parse_list("md5, SHA3-256,xxhash64,md5").unwrap(),
+            vec![HashAlgorithm::Md5, HashAlgorithm::Sha3_256, HashAlgorithm::XxHash64],
+        );
+        assert!(HashAlgorithm::parse_list("sha256,crc32").is_err());
+        assert!(HashAlgorithm::parse_list(" , ").is_err());
+        assert_eq!(HashAlgorithm::Sha3_256.to_string(), "sha3-256");
+    }
+
+    #[test]
+    fn test_parallel_blake3_matches_sequential() {
+        let data: Vec<u8> = (0..3 * 1024 * 1024u32).map(|i| (i * 31 % 251) as u8).collect();
+        let file = create_sync_test_file(&data);
+
+        let sequential = hash_file_mode(file.path(), 1024 * 1024, HashAlgorithm::DEFAULT, Blake3Mode::Sequential).unwrap();
+        let parallel = hash_file_mode(file.path(), 1024 * 1024, HashAlgorithm::DEFAULT, Blake3Mode::Parallel).unwrap();
+
+        assert_eq!(parallel.blake3, blake3::hash(&data).to_hex().to_string());
+        assert_eq!(parallel.blake3, sequential.blake3);
+        assert_eq!(parallel.sha256, sequential.sha256);
+    }
+
+    #[test]
+    fn test_auto_mode_threshold() {
+        let mode = Blake3Mode::Auto { threshold: 1024 };
+        assert!(!mode.is_parallel(1023));
+        assert_eq!(mode.is_parallel(1024), rayon::current_num_threads() > 1);
+        assert!(!Blake3Mode::Sequential.is_parallel(u64::MAX));
+        assert!(Blake3Mode::Parallel.is_parallel(0));
+    }
+}
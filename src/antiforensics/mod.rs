@@ -14,6 +14,7 @@ pub mod analyzer;
 pub mod cleaner;
 pub mod encryption;
 pub mod hash;
+pub mod patterns;
 pub mod report;
 pub mod scanner;
 pub mod stego;
@@ -23,6 +24,8 @@ pub mod verifier;
 
 // Core types and utilities
 pub mod types;
+pub mod references;
+pub mod text_encoding;
 pub mod error;
 pub mod config;
 
@@ -239,5 +242,5 @@ mod tests {
 // Re-exports for convenience
 pub use analyzer::AnalyzerResult;
 pub use cleaner::CleanerResult;
-pub use types::{ProcessingMetrics, RiskLevel};
+pub use types::{sort_artifacts, ArtifactType, ForensicArtifact, ProcessingMetrics, RiskLevel};
 pub use error::{Error, Result};
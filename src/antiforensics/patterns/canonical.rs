@@ -0,0 +1,152 @@
+//! Canonical decoding of names and strings in raw PDF bytes
+//! Author: kartik4091
+//! Created: 2025-06-05 00:09:27 UTC
+//! A name may spell any byte as `#xx` and a literal string any byte as an
+//! octal escape, so `/J#61vaScript` is `/JavaScript` to every reader and
+//! `(\145val)` is `(eval)`. The object parser decodes both, but code that
+//! matches patterns against raw bytes has to decode them itself, or the
+//! escapes walk straight past it. Escaping a letter or digit is never
+//! needed, so an escape like that is itself a sign of obfuscation.
+
+use std::borrow::Cow;
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0') || b"()<>[]{}/%".contains(&byte)
+}
+
+fn hex_pair(raw: &[u8], at: usize) -> Option<u8> {
+    let pair = raw.get(at..at + 2)?;
+    u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()
+}
+
+/// Decodes `#xx` escapes in a name; malformed escapes are kept as is
+pub fn decode_name(raw: &[u8]) -> Cow<'_, [u8]> {
+    if !raw.contains(&b'#') {
+        return Cow::Borrowed(raw);
+    }
+    let mut decoded = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        match (raw[i], hex_pair(raw, i + 1)) {
+            (b'#', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Cow::Owned(decoded)
+}
+
+/// Whether a name escapes a letter or digit, which no writer needs to do
+pub fn is_obfuscated_name(raw: &[u8]) -> bool {
+    raw.iter().enumerate()
+        .any(|(i, &b)| b == b'#' && hex_pair(raw, i + 1).is_some_and(|byte| byte.is_ascii_alphanumeric()))
+}
+
+/// Names in `data` as `(offset of the slash, raw name without it)`
+///
+/// Works on bytes alone, so names inside strings and streams are
+/// included; callers that need precision lex the file first.
+pub fn raw_names(data: &[u8]) -> impl Iterator<Item = (usize, &[u8])> + '_ {
+    data.iter().enumerate()
+        .filter(|&(_, &b)| b == b'/')
+        .map(move |(offset, _)| {
+            let start = offset + 1;
+            let end = data[start..].iter().position(|&b| is_delimiter(b)).map_or(data.len(), |p| p + start);
+            (offset, &data[start..end])
+        })
+        .filter(|(_, name)| !name.is_empty())
+}
+
+/// Decodes the escapes of a literal string, given without its outer parentheses
+pub fn decode_literal_string(raw: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] != b'\\' {
+            decoded.push(raw[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        let Some(&escaped) = raw.get(i) else { break };
+        i += 1;
+        match escaped {
+            b'n' => decoded.push(b'\n'),
+            b'r' => decoded.push(b'\r'),
+            b't' => decoded.push(b'\t'),
+            b'b' => decoded.push(0x08),
+            b'f' => decoded.push(0x0c),
+            b'0'..=b'7' => {
+                let mut value = u32::from(escaped - b'0');
+                for _ in 0..2 {
+                    match raw.get(i) {
+                        Some(&digit @ b'0'..=b'7') => {
+                            value = value * 8 + u32::from(digit - b'0');
+                            i += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                // High-order overflow is ignored, as the specification says
+                decoded.push(value as u8);
+            }
+            // A backslash at the end of a line continues the string
+            b'\r' => {
+                if raw.get(i) == Some(&b'\n') {
+                    i += 1;
+                }
+            }
+            b'\n' => {}
+            // `\(`, `\)`, `\\` and unknown escapes stand for the character itself
+            other => decoded.push(other),
+        }
+    }
+    decoded
+}
+
+/// Whether a literal string writes a letter or digit as an octal escape
+pub fn is_obfuscated_string(raw: &[u8]) -> bool {
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] == b'\\' {
+            let digits = raw[i + 1..].iter().take(3).take_while(|b| (b'0'..=b'7').contains(b)).count();
+            if digits > 0 {
+                let value = raw[i + 1..i + 1 + digits].iter().fold(0u32, |v, &d| v * 8 + u32::from(d - b'0'));
+                if (value as u8).is_ascii_alphanumeric() {
+                    return true;
+                }
+            }
+            i += 1 + digits.max(1);
+        } else {
+            i += 1;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_and_strings_decoded() {
+        assert_eq!(&*decode_name(b"J#61vaScript"), b"JavaScript");
+        assert_eq!(&*decode_name(b"A#2"), b"A#2");
+        assert!(is_obfuscated_name(b"J#61vaScript"));
+        // Escaping a delimiter is what escapes are for
+        assert!(!is_obfuscated_name(b"Name#20With#2FSlash"));
+
+        let names: Vec<(usize, &[u8])> = raw_names(b"<</S/J#61vaScript /JS(x)>>").collect();
+        assert_eq!(names, vec![(2, &b"S"[..]), (4, &b"J#61vaScript"[..]), (18, &b"JS"[..])]);
+
+        assert_eq!(decode_literal_string(br"\145val\(1\)\\\n"), b"eval(1)\\\n");
+        assert_eq!(decode_literal_string(b"a\\\r\nb\\0"), b"ab\0");
+        assert!(is_obfuscated_string(br"\145val"));
+        assert!(!is_obfuscated_string(br"line\nbreak \(\) \000"));
+    }
+}
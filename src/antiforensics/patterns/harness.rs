@@ -0,0 +1,255 @@
+//! Rule testing against labeled fixtures
+//! Author: kartik4091
+//! Created: 2025-06-04 22:58:06 UTC
+//! Runs a pattern database over a directory of fixture PDFs and compares
+//! what each rule fired on with what it should have fired on, so rule
+//! authors see precision and recall per rule before shipping an update.
+//!
+//! Fixtures are the `.pdf` files of one directory. A `labels.yaml` next
+//! to them maps fixture file names to the rule ids expected to fire:
+//!
+//! ```yaml
+//! launch.pdf: [key.launch, key.open-action]
+//! clean.pdf: []
+//! ```
+//!
+//! Fixtures missing from the labels are expected to fire no rule. Key
+//! and info rules are evaluated on the parsed document, raw names and
+//! idioms on the raw bytes, as the scanners use them.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use crate::antiforensics::{
+    patterns::{PatternDatabase, PatternError, PatternKind},
+    scanner::{DocumentScanner, QuickScanner},
+};
+
+/// Labels file expected in a fixture directory
+pub const LABELS_FILE: &str = "labels.yaml";
+
+/// How one rule did over the fixtures
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RuleMetrics {
+    pub id: String,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    /// Fixtures the rule should have fired on but did not
+    pub missed: Vec<String>,
+    /// Fixtures the rule fired on without a label
+    pub false_alarms: Vec<String>,
+}
+
+impl RuleMetrics {
+    /// Share of firings that were expected; `None` when the rule never fired
+    pub fn precision(&self) -> Option<f64> {
+        ratio(self.true_positives, self.true_positives + self.false_positives)
+    }
+
+    /// Share of expected firings that happened; `None` when no fixture is labeled with the rule
+    pub fn recall(&self) -> Option<f64> {
+        ratio(self.true_positives, self.true_positives + self.false_negatives)
+    }
+}
+
+fn ratio(part: usize, whole: usize) -> Option<f64> {
+    (whole > 0).then(|| part as f64 / whole as f64)
+}
+
+/// Result of testing one database against one fixture directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RuleTestReport {
+    pub database_version: u32,
+    pub fixtures: usize,
+    /// One entry per rule, in database order
+    pub rules: Vec<RuleMetrics>,
+    /// Fixtures lopdf could not parse; only raw rules were evaluated on them
+    pub unparsed: Vec<String>,
+    /// Labels naming rules the database does not have, as `fixture: id`
+    pub unknown_labels: Vec<String>,
+}
+
+impl RuleTestReport {
+    /// Rules falling short of either threshold; rules without data pass
+    pub fn failures(&self, min_precision: f64, min_recall: f64) -> Vec<&RuleMetrics> {
+        self.rules.iter()
+            .filter(|rule| {
+                rule.precision().is_some_and(|p| p < min_precision)
+                    || rule.recall().is_some_and(|r| r < min_recall)
+            })
+            .collect()
+    }
+}
+
+/// Runs a database over labeled fixtures
+pub struct RuleTester {
+    patterns: Arc<PatternDatabase>,
+}
+
+impl RuleTester {
+    pub fn new(patterns: Arc<PatternDatabase>) -> Self {
+        Self { patterns }
+    }
+
+    /// Tests every fixture of `dir` against its labels
+    pub fn run(&self, dir: &Path) -> Result<RuleTestReport, PatternError> {
+        let labels_path = dir.join(LABELS_FILE);
+        let labels: BTreeMap<String, BTreeSet<String>> = if labels_path.exists() {
+            serde_yaml::from_str(&fs::read_to_string(&labels_path)?)?
+        } else {
+            BTreeMap::new()
+        };
+
+        let mut fixtures: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")))
+            .collect();
+        fixtures.sort();
+
+        let mut metrics: Vec<RuleMetrics> = self.patterns.rules().iter()
+            .map(|rule| RuleMetrics { id: rule.id.clone(), ..Default::default() })
+            .collect();
+        let mut report = RuleTestReport {
+            database_version: self.patterns.version(),
+            fixtures: fixtures.len(),
+            rules: Vec::new(),
+            unparsed: Vec::new(),
+            unknown_labels: Vec::new(),
+        };
+
+        for (name, ids) in &labels {
+            for id in ids.iter().filter(|id| self.patterns.rule(id).is_none()) {
+                report.unknown_labels.push(format!("{}: {}", name, id));
+            }
+        }
+
+        let empty = BTreeSet::new();
+        for path in &fixtures {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let (fired, parsed) = self.fired(&fs::read(path)?)?;
+            if !parsed {
+                report.unparsed.push(name.clone());
+            }
+            let expected = labels.get(&name).unwrap_or(&empty);
+
+            for rule in &mut metrics {
+                match (fired.contains(&rule.id), expected.contains(&rule.id)) {
+                    (true, true) => rule.true_positives += 1,
+                    (true, false) => {
+                        rule.false_positives += 1;
+                        rule.false_alarms.push(name.clone());
+                    }
+                    (false, true) => {
+                        rule.false_negatives += 1;
+                        rule.missed.push(name.clone());
+                    }
+                    (false, false) => {}
+                }
+            }
+        }
+
+        report.rules = metrics;
+        Ok(report)
+    }
+
+    /// Ids of the rules firing on one fixture, and whether it parsed
+    fn fired(&self, data: &[u8]) -> Result<(BTreeSet<String>, bool), PatternError> {
+        let mut fired = BTreeSet::new();
+
+        let parsed = match DocumentScanner::new().with_patterns(self.patterns.clone()).scan_bytes(data) {
+            Ok(artifacts) => {
+                fired.extend(artifacts.into_iter().filter_map(|a| a.metadata.get("pattern_id").cloned()));
+                true
+            }
+            Err(_) => false,
+        };
+
+        let quick = QuickScanner { patterns: Some(self.patterns.clone()), ..Default::default() }.scan_reader(data)?;
+        for rule in self.patterns.rules() {
+            if let PatternKind::RawName(name) = &rule.kind {
+                if quick.hits.contains_key(name) {
+                    fired.insert(rule.id.clone());
+                }
+            }
+        }
+        let _span = tracing::trace_span!("regex_match", bytes = data.len()).entered();
+        for (rule, idiom) in self.patterns.idioms() {
+            if idiom.is_match(data) {
+                fired.insert(rule.id.clone());
+            }
+        }
+
+        Ok((fired, parsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Document, Object};
+
+    fn write_fixture(dir: &Path, name: &str, catalog: lopdf::Dictionary) {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.add_object(dictionary! { "Type" => "Pages", "Kids" => Vec::<Object>::new(), "Count" => 0 });
+        let mut catalog = catalog;
+        catalog.set("Type", "Catalog");
+        catalog.set("Pages", pages_id);
+        let catalog_id = doc.add_object(catalog);
+        doc.trailer.set("Root", catalog_id);
+        doc.save(dir.join(name)).unwrap();
+    }
+
+    #[test]
+    fn test_precision_and_recall_per_rule() {
+        let dir = tempfile::tempdir().unwrap();
+        write_fixture(dir.path(), "launch.pdf", dictionary! {
+            "OpenAction" => dictionary! { "S" => "Launch", "F" => Object::string_literal("calc.exe") },
+        });
+        write_fixture(dir.path(), "uri.pdf", dictionary! {
+            "OpenAction" => dictionary! { "S" => "URI", "URI" => Object::string_literal("https://example.com") },
+        });
+        write_fixture(dir.path(), "clean.pdf", dictionary! {});
+        fs::write(
+            dir.path().join(LABELS_FILE),
+            "launch.pdf: [key.launch, key.open-action]\nuri.pdf: [key.uri, key.launch]\nclean.pdf: [key.bogus]\n",
+        ).unwrap();
+
+        let report = RuleTester::new(Arc::new(PatternDatabase::builtin())).run(dir.path()).unwrap();
+        let rule = |id: &str| report.rules.iter().find(|r| r.id == id).unwrap();
+
+        assert_eq!(report.fixtures, 3);
+        assert!(report.unparsed.is_empty());
+        assert_eq!(report.unknown_labels, vec!["clean.pdf: key.bogus"]);
+
+        assert_eq!(rule("key.launch").precision(), Some(1.0));
+        assert_eq!(rule("key.launch").recall(), Some(0.5));
+        assert_eq!(rule("key.launch").missed, vec!["uri.pdf"]);
+        // The URI action also fires the open-action rule, which uri.pdf does not expect
+        assert_eq!(rule("key.open-action").precision(), Some(0.5));
+        assert_eq!(rule("key.open-action").false_alarms, vec!["uri.pdf"]);
+        assert_eq!(rule("key.javascript").precision(), None);
+
+        let failing: Vec<&str> = report.failures(0.9, 0.9).iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(failing, vec!["key.launch", "key.open-action"]);
+    }
+
+    #[test]
+    fn test_unlabeled_directory_counts_every_firing_as_false() {
+        let dir = tempfile::tempdir().unwrap();
+        write_fixture(dir.path(), "xfa.pdf", dictionary! {
+            "AcroForm" => dictionary! { "XFA" => Object::string_literal("<xdp/>") },
+        });
+
+        let report = RuleTester::new(Arc::new(PatternDatabase::builtin())).run(dir.path()).unwrap();
+        let xfa = report.rules.iter().find(|r| r.id == "raw.xfa").unwrap();
+        assert_eq!(xfa.false_positives, 1);
+        assert_eq!(xfa.recall(), None);
+    }
+}
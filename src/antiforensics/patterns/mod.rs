@@ -0,0 +1,400 @@
+//! Versioned database of detection patterns
+//! Author: kartik4091
+//! Created: 2025-06-04 22:51:30 UTC
+//! The keys, names and idioms the scanners look for live here rather than
+//! in each scanner, under one database version that scan results report.
+//! The built-in database is compiled in; updated databases are shipped as
+//! `.patdb` files and installed at runtime with [`install`], without a new
+//! release of the engine.
+//!
+//! A `.patdb` file is an 8-byte magic, the little-endian format version,
+//! the SHA-256 of the payload and the bincode-encoded payload. Names are
+//! stored decoded and regular expressions are compiled once, when the
+//! database is loaded, so scanners only do lookups.
+//!
+//! Rule authors write databases as YAML and compile them to `.patdb`;
+//! [`harness`] measures each rule against labeled fixtures first:
+//!
+//! ```yaml
+//! version: 2
+//! released: 2025-06-05
+//! rules:
+//!   - id: key.launch
+//!     kind: !Key Launch
+//!     description: Launch action
+//!     risk_level: Critical
+//!     artifact_type: Structure
+//!     weight: 0.6
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::{Arc, OnceLock, RwLock},
+};
+use regex::bytes::Regex;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::antiforensics::{ArtifactType, RiskLevel};
+
+pub mod canonical;
+pub mod harness;
+
+/// Layout version of `.patdb` files, bumped on incompatible changes
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Metadata key under which artifacts record the database version
+pub const DATABASE_VERSION_KEY: &str = "pattern_db_version";
+
+const MAGIC: &[u8; 8] = b"PEPATDB\0";
+
+/// Version of the built-in database; bump whenever `builtin_rules` changes
+const BUILTIN_VERSION: u32 = 1;
+const BUILTIN_RELEASED: &str = "2025-06-04";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PatternError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a pattern database: {0}")]
+    Format(String),
+    #[error("Corrupt pattern database: {0}")]
+    Decode(#[from] bincode::Error),
+    #[error("Invalid YAML pattern database: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid rule {id}: {reason}")]
+    InvalidRule { id: String, reason: String },
+    #[error("Pattern database {offered} is older than the installed {installed}")]
+    Downgrade { installed: u32, offered: u32 },
+}
+
+/// What a rule matches
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PatternKind {
+    /// A dictionary key or action subtype (`/S`); also a name in raw bytes
+    Key(String),
+    /// A name only worth noting in raw bytes, where it hides content from
+    /// a byte-level scan (e.g. `/ObjStm`)
+    RawName(String),
+    /// A document information key that identifies authors or tooling
+    InfoKey(String),
+    /// A regular expression over raw, uncompressed bytes
+    Idiom(String),
+}
+
+/// One detection pattern
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternRule {
+    /// Stable identifier, e.g. `key.javascript`
+    pub id: String,
+    pub kind: PatternKind,
+    pub description: String,
+    pub risk_level: RiskLevel,
+    pub artifact_type: ArtifactType,
+    /// Contribution to the quick scan's risk score
+    pub weight: f64,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Serialized form of a database
+#[derive(Serialize, Deserialize)]
+struct DatabaseFile {
+    version: u32,
+    released: String,
+    rules: Vec<PatternRule>,
+}
+
+/// Compiled, versioned set of pattern rules
+#[derive(Debug)]
+pub struct PatternDatabase {
+    version: u32,
+    released: String,
+    rules: Vec<PatternRule>,
+    /// Decoded key and raw names to rule indices
+    names: HashMap<Vec<u8>, usize>,
+    info_keys: Vec<usize>,
+    idioms: Vec<(usize, Regex)>,
+}
+
+impl PatternDatabase {
+    /// Validates and compiles `rules`
+    pub fn new(version: u32, released: impl Into<String>, rules: Vec<PatternRule>) -> Result<Self, PatternError> {
+        let mut ids = HashSet::new();
+        let mut names = HashMap::new();
+        let mut info_keys = Vec::new();
+        let mut idioms = Vec::new();
+
+        for (index, rule) in rules.iter().enumerate() {
+            let invalid = |reason: String| PatternError::InvalidRule { id: rule.id.clone(), reason };
+            if !ids.insert(rule.id.as_str()) {
+                return Err(invalid("duplicate id".into()));
+            }
+            if !(0.0..=1.0).contains(&rule.weight) {
+                return Err(invalid(format!("weight {} outside 0..=1", rule.weight)));
+            }
+            match &rule.kind {
+                PatternKind::Key(name) | PatternKind::RawName(name) => {
+                    if name.is_empty() || name.starts_with('/') {
+                        return Err(invalid("names are given without the leading slash".into()));
+                    }
+                    if names.insert(name.as_bytes().to_vec(), index).is_some() {
+                        return Err(invalid(format!("/{} is matched by another rule", name)));
+                    }
+                }
+                PatternKind::InfoKey(_) => info_keys.push(index),
+                PatternKind::Idiom(pattern) => {
+                    let regex = Regex::new(pattern).map_err(|e| invalid(e.to_string()))?;
+                    idioms.push((index, regex));
+                }
+            }
+        }
+
+        Ok(Self { version, released: released.into(), rules, names, info_keys, idioms })
+    }
+
+    /// The database compiled into this build
+    pub fn builtin() -> Self {
+        Self::new(BUILTIN_VERSION, BUILTIN_RELEASED, builtin_rules()).expect("built-in patterns are valid")
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Release date of this version, `YYYY-MM-DD`
+    pub fn released(&self) -> &str {
+        &self.released
+    }
+
+    pub fn rules(&self) -> &[PatternRule] {
+        &self.rules
+    }
+
+    pub fn rule(&self, id: &str) -> Option<&PatternRule> {
+        self.rules.iter().find(|rule| rule.id == id)
+    }
+
+    /// Rule for a dictionary key or action subtype
+    pub fn key(&self, name: &[u8]) -> Option<&PatternRule> {
+        self.name(name).filter(|rule| matches!(rule.kind, PatternKind::Key(_)))
+    }
+
+    /// Rule for a name seen in raw bytes, key or raw-only
+    pub fn name(&self, name: &[u8]) -> Option<&PatternRule> {
+        self.names.get(name).map(|&index| &self.rules[index])
+    }
+
+    /// Key rules, in database order
+    pub fn keys(&self) -> impl Iterator<Item = &PatternRule> {
+        self.rules.iter().filter(|rule| matches!(rule.kind, PatternKind::Key(_)))
+    }
+
+    pub fn info_keys(&self) -> impl Iterator<Item = &PatternRule> {
+        self.info_keys.iter().map(|&index| &self.rules[index])
+    }
+
+    /// Idiom rules with their compiled expressions
+    pub fn idioms(&self) -> impl Iterator<Item = (&PatternRule, &Regex)> {
+        self.idioms.iter().map(|(index, regex)| (&self.rules[*index], regex))
+    }
+
+    fn file(&self) -> DatabaseFile {
+        DatabaseFile { version: self.version, released: self.released.clone(), rules: self.rules.clone() }
+    }
+
+    /// Encodes the database in the `.patdb` format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let payload = bincode::serialize(&self.file()).expect("pattern rules always serialize");
+
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 4 + 32 + payload.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&Sha256::digest(&payload));
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    /// Decodes and compiles a `.patdb` database
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PatternError> {
+        let header = MAGIC.len() + 4 + 32;
+        if bytes.len() < header || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(PatternError::Format("missing pattern database header".into()));
+        }
+        let format = u32::from_le_bytes(bytes[8..12].try_into().expect("four bytes"));
+        if format != FORMAT_VERSION {
+            return Err(PatternError::Format(format!("format {} is not supported (expected {})", format, FORMAT_VERSION)));
+        }
+        let (checksum, payload) = bytes[12..].split_at(32);
+        if Sha256::digest(payload).as_slice() != checksum {
+            return Err(PatternError::Format("checksum mismatch".into()));
+        }
+
+        let file: DatabaseFile = bincode::deserialize(payload)?;
+        Self::new(file.version, file.released, file.rules)
+    }
+
+    /// Parses and compiles a YAML database
+    pub fn from_yaml(text: &str) -> Result<Self, PatternError> {
+        let file: DatabaseFile = serde_yaml::from_str(text)?;
+        Self::new(file.version, file.released, file.rules)
+    }
+
+    pub fn to_yaml(&self) -> String {
+        serde_yaml::to_string(&self.file()).expect("pattern rules always serialize")
+    }
+
+    /// Loads a `.yaml`/`.yml` source or a compiled `.patdb` database
+    pub fn load(path: &Path) -> Result<Self, PatternError> {
+        let yaml = path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"));
+        if yaml {
+            Self::from_yaml(&fs::read_to_string(path)?)
+        } else {
+            Self::from_bytes(&fs::read(path)?)
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), PatternError> {
+        Ok(fs::write(path, self.to_bytes())?)
+    }
+}
+
+fn active_slot() -> &'static RwLock<Arc<PatternDatabase>> {
+    static ACTIVE: OnceLock<RwLock<Arc<PatternDatabase>>> = OnceLock::new();
+    ACTIVE.get_or_init(|| RwLock::new(Arc::new(PatternDatabase::builtin())))
+}
+
+/// Database the scanners currently use
+pub fn active() -> Arc<PatternDatabase> {
+    active_slot().read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Makes `database` the active one for scans started from now on
+///
+/// Refuses databases older than the installed one, so a stale update
+/// cannot silently roll detections back. Installing the same version
+/// again is allowed.
+pub fn install(database: PatternDatabase) -> Result<(), PatternError> {
+    let mut slot = active_slot().write().unwrap_or_else(|e| e.into_inner());
+    if database.version < slot.version {
+        return Err(PatternError::Downgrade { installed: slot.version, offered: database.version });
+    }
+    tracing::info!("Installed pattern database {} ({})", database.version, database.released);
+    *slot = Arc::new(database);
+    Ok(())
+}
+
+fn builtin_rules() -> Vec<PatternRule> {
+    use ArtifactType::*;
+    use PatternKind::*;
+    use RiskLevel::{Critical, High, Low, Medium};
+
+    let rule = |id: &str, kind, description: &str, risk_level, artifact_type, weight| PatternRule {
+        id: id.into(),
+        kind,
+        description: description.into(),
+        risk_level,
+        artifact_type,
+        weight,
+        tags: Vec::new(),
+    };
+    vec![
+        rule("key.javascript", Key("JavaScript".into()), "JavaScript action", Critical, JavaScript, 0.4),
+        rule("key.js", Key("JS".into()), "Script body", Critical, JavaScript, 0.4),
+        rule("key.launch", Key("Launch".into()), "Launch action", Critical, Structure, 0.6),
+        rule("key.embedded-file", Key("EmbeddedFile".into()), "Embedded file", High, EmbeddedFile, 0.2),
+        rule("key.submit-form", Key("SubmitForm".into()), "Form submission action", High, Structure, 0.3),
+        rule("key.import-data", Key("ImportData".into()), "Data import action", High, Structure, 0.3),
+        rule("key.rich-media", Key("RichMedia".into()), "Rich media (Flash/3D) content", High, Content, 0.3),
+        rule("key.open-action", Key("OpenAction".into()), "Document open action", High, Structure, 0.15),
+        rule("key.additional-actions", Key("AA".into()), "Additional actions", High, Structure, 0.15),
+        rule("key.uri", Key("URI".into()), "Link to external URI", Medium, Content, 0.05),
+        rule("key.goto-remote", Key("GoToR".into()), "Remote go-to action", Medium, Structure, 0.1),
+        rule("raw.goto-embedded", RawName("GoToE".into()), "Embedded go-to action", Medium, Structure, 0.1),
+        rule("raw.xfa", RawName("XFA".into()), "XFA form", Medium, Structure, 0.15),
+        rule("raw.encrypt", RawName("Encrypt".into()), "Encryption hides content from byte-level scans", Medium, Structure, 0.1),
+        rule("raw.object-stream", RawName("ObjStm".into()), "Object streams hide objects from byte-level scans", Low, Structure, 0.05),
+        rule("info.author", InfoKey("Author".into()), "Author name", Low, Metadata, 0.0),
+        rule("info.creator", InfoKey("Creator".into()), "Creating application", Low, Metadata, 0.0),
+        rule("info.producer", InfoKey("Producer".into()), "Producing library", Low, Metadata, 0.0),
+        rule("info.creation-date", InfoKey("CreationDate".into()), "Creation time", Low, Metadata, 0.0),
+        rule("info.mod-date", InfoKey("ModDate".into()), "Modification time", Low, Metadata, 0.0),
+        rule("info.company", InfoKey("Company".into()), "Company name", Low, Metadata, 0.0),
+        rule(
+            "idiom.script-call",
+            Idiom(r"(?-u:\b)(?:eval|unescape|String\.fromCharCode|app\.launchURL|this\.exportDataObject)\s*\(".into()),
+            "Script idiom in uncompressed content",
+            High,
+            JavaScript,
+            0.2,
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_lookups() {
+        let db = PatternDatabase::builtin();
+        assert_eq!(db.key(b"JS").unwrap().risk_level, RiskLevel::Critical);
+        assert!(db.key(b"ObjStm").is_none());
+        assert!(db.name(b"ObjStm").is_some());
+        assert_eq!(db.info_keys().count(), 6);
+        assert_eq!(db.idioms().count(), 1);
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let db = PatternDatabase::builtin();
+        let loaded = PatternDatabase::from_bytes(&db.to_bytes()).unwrap();
+        assert_eq!(loaded.version(), db.version());
+        assert_eq!(loaded.rules(), db.rules());
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let db = PatternDatabase::builtin();
+        let loaded = PatternDatabase::from_yaml(&db.to_yaml()).unwrap();
+        assert_eq!(loaded.rules(), db.rules());
+
+        let authored = "version: 9\nreleased: 2025-06-05\nrules:\n  - id: raw.xfa\n    kind: !RawName XFA\n    \
+            description: XFA form\n    risk_level: Medium\n    artifact_type: Structure\n    weight: 0.15\n";
+        let db = PatternDatabase::from_yaml(authored).unwrap();
+        assert_eq!(db.version(), 9);
+        assert!(db.name(b"XFA").is_some());
+    }
+
+    #[test]
+    fn test_rejects_tampered_and_foreign_files() {
+        let mut bytes = PatternDatabase::builtin().to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert!(matches!(PatternDatabase::from_bytes(&bytes), Err(PatternError::Format(_))));
+        assert!(matches!(PatternDatabase::from_bytes(b"%PDF-1.7"), Err(PatternError::Format(_))));
+    }
+
+    #[test]
+    fn test_rejects_invalid_rules() {
+        let mut rules = builtin_rules();
+        rules.push(rules[0].clone());
+        assert!(matches!(PatternDatabase::new(2, "2025-06-05", rules), Err(PatternError::InvalidRule { .. })));
+
+        let mut rules = builtin_rules();
+        rules[0].kind = PatternKind::Idiom("(".into());
+        assert!(PatternDatabase::new(2, "2025-06-05", rules).is_err());
+    }
+
+    #[test]
+    fn test_install_refuses_downgrade() {
+        let newer = PatternDatabase::new(BUILTIN_VERSION + 1, "2025-06-05", builtin_rules()).unwrap();
+        install(newer).unwrap();
+        assert_eq!(active().version(), BUILTIN_VERSION + 1);
+        assert!(matches!(install(PatternDatabase::builtin()), Err(PatternError::Downgrade { .. })));
+    }
+}
@@ -0,0 +1,186 @@
+//! Reference traversal with cycle detection
+//! Author: kartik4091
+//! Created: 2025-06-05 00:21:06 UTC
+//! Walks that follow references between objects cannot rely on a depth
+//! limit alone: a /Kids array that lists its own node twice is only 32
+//! levels deep yet takes 2^32 steps to walk, and a loop through a chain
+//! of references repeats until the limit. `Visited` keys every entered
+//! object by number and generation so each is walked once, and records a
+//! cycle whenever an object is met again while still on the current path.
+//! Documents loop by design through back-references like /Parent and
+//! /P, so `find_cycles` only reports loops that go through none of them.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use lopdf::{Dictionary, Object, ObjectId};
+
+/// Cycles reported by `find_cycles`; one is enough to flag a document
+const MAX_CYCLES: usize = 16;
+
+/// Keys whose references point back up a tree or across to a page the
+/// walk usually came from, closing loops every document has
+const BACK_REFERENCE_KEYS: &[&[u8]] = &[b"Parent", b"P", b"Prev", b"Last", b"Pg", b"Dest", b"D", b"V"];
+
+/// A loop of references, from the object met again to the one referring back to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceCycle(pub Vec<ObjectId>);
+
+impl ReferenceCycle {
+    /// Object the loop returns to
+    pub fn start(&self) -> ObjectId {
+        self.0[0]
+    }
+}
+
+impl fmt::Display for ReferenceCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let objects: Vec<String> = self.0.iter()
+            .chain(self.0.first())
+            .map(|(num, generation)| format!("{} {} R", num, generation))
+            .collect();
+        write!(f, "{}", objects.join(" -> "))
+    }
+}
+
+/// Objects entered by a recursive walk, and the cycles met on the way
+///
+/// Callers `enter` an object before walking into it, skip it when that
+/// returns false, and `leave` it once its children are done.
+#[derive(Debug, Default)]
+pub struct Visited {
+    entered: HashSet<ObjectId>,
+    path: Vec<ObjectId>,
+    pub cycles: Vec<ReferenceCycle>,
+}
+
+impl Visited {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enters `id`, or returns false when it was entered before
+    pub fn enter(&mut self, id: ObjectId) -> bool {
+        if self.entered.insert(id) {
+            self.path.push(id);
+            return true;
+        }
+        if let Some(at) = self.path.iter().position(|&on_path| on_path == id) {
+            self.cycles.push(ReferenceCycle(self.path[at..].to_vec()));
+        }
+        false
+    }
+
+    /// Leaves the object entered last
+    pub fn leave(&mut self) {
+        self.path.pop();
+    }
+
+    /// Whether `id` was entered at some point
+    pub fn contains(&self, id: ObjectId) -> bool {
+        self.entered.contains(&id)
+    }
+}
+
+/// References directly inside `object`, each with whether it is a back-reference
+fn references(object: &Object, key: &[u8], ring: bool, out: &mut Vec<(ObjectId, bool)>) {
+    match object {
+        Object::Reference(id) => out.push((*id, ring || BACK_REFERENCE_KEYS.contains(&key))),
+        Object::Array(items) => items.iter().for_each(|item| references(item, key, ring, out)),
+        Object::Dictionary(dict) => dict_references(dict, out),
+        Object::Stream(stream) => dict_references(&stream.dict, out),
+        _ => {}
+    }
+}
+
+fn dict_references(dict: &Dictionary, out: &mut Vec<(ObjectId, bool)>) {
+    // Article beads are linked into a ring by /N and /V
+    let ring = dict.get(b"Type").and_then(Object::as_name).is_ok_and(|kind| kind == b"Bead");
+    for (key, value) in dict.iter() {
+        references(value, key, ring, out);
+    }
+}
+
+/// One object on the path of `find_cycles`
+struct Frame {
+    id: ObjectId,
+    /// Whether the reference leading here was a back-reference
+    back: bool,
+    children: std::vec::IntoIter<(ObjectId, bool)>,
+}
+
+/// Reference loops of a document that do not go through a back-reference
+///
+/// Walks from the trailer first, then from every object it did not
+/// reach, without recursion, so the deepest graph costs heap and not stack.
+pub fn find_cycles(doc: &lopdf::Document) -> Vec<ReferenceCycle> {
+    let mut roots = Vec::new();
+    dict_references(&doc.trailer, &mut roots);
+    roots.extend(doc.objects.keys().map(|&id| (id, false)));
+
+    let mut cycles = Vec::new();
+    let mut done: HashSet<ObjectId> = HashSet::new();
+    let mut on_path: HashMap<ObjectId, usize> = HashMap::new();
+    let mut path: Vec<Frame> = Vec::new();
+    for (root, back) in roots {
+        let mut next = Some((root, back));
+        loop {
+            if let Some((id, back)) = next.take() {
+                if let Some(&at) = on_path.get(&id) {
+                    let benign = back || path[at + 1..].iter().any(|frame| frame.back);
+                    if !benign && cycles.len() < MAX_CYCLES {
+                        cycles.push(ReferenceCycle(path[at..].iter().map(|frame| frame.id).collect()));
+                    }
+                } else if let Some(object) = doc.objects.get(&id).filter(|_| done.insert(id)) {
+                    let mut children = Vec::new();
+                    references(object, b"", false, &mut children);
+                    on_path.insert(id, path.len());
+                    path.push(Frame { id, back, children: children.into_iter() });
+                }
+            }
+            let Some(frame) = path.last_mut() else { break };
+            match frame.children.next() {
+                Some(child) => next = Some(child),
+                None => {
+                    on_path.remove(&frame.id);
+                    path.pop();
+                }
+            }
+        }
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_cycles_found_without_back_references() {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id });
+        let link = doc.add_object(dictionary! { "Subtype" => "Link", "P" => page, "Dest" => vec![page.into()] });
+        doc.get_dictionary_mut(page).unwrap().set("Annots", vec![link.into()]);
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page.into()] }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+        // A page tree and its links loop only through back-references
+        assert!(find_cycles(&doc).is_empty());
+
+        // A node listed among its own kids' kids
+        let inner = doc.add_object(dictionary! { "Type" => "Pages", "Kids" => vec![pages_id.into()] });
+        doc.get_dictionary_mut(pages_id).unwrap().set("Kids", vec![page.into(), inner.into()]);
+        let cycles = find_cycles(&doc);
+        assert_eq!(cycles, vec![ReferenceCycle(vec![pages_id, inner])]);
+        assert_eq!(cycles[0].to_string(), format!("{} 0 R -> {} 0 R -> {} 0 R", pages_id.0, inner.0, pages_id.0));
+
+        let mut visited = Visited::new();
+        assert!(visited.enter(pages_id) && visited.enter(inner));
+        assert!(!visited.enter(pages_id));
+        visited.leave();
+        visited.leave();
+        assert!(!visited.enter(inner));
+        assert_eq!(visited.cycles, vec![ReferenceCycle(vec![pages_id, inner])]);
+    }
+}
@@ -0,0 +1,279 @@
+//! Accessibility impact of cleaning
+//! Author: kartik4091
+//! Created: 2025-06-05 01:03:48 UTC
+//! Cleaning works on the same structures assistive technology reads: the
+//! catalog's /MarkInfo and /Lang are metadata to a cleaner, structure
+//! elements hang off objects that get removed, and renumbering can leave
+//! the tree pointing nowhere. [`AccessibilityProfile`] measures what a
+//! screen reader depends on, and [`AccessibilityImpact`] compares the
+//! input with the output, warning about everything cleaning took away.
+
+use std::collections::HashSet;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use crate::antiforensics::{analyzer::info_xmp, scanner::unicode_spoof::decode_pdf_string};
+
+/// Structure elements visited before the walk gives up, against hostile trees
+const MAX_ELEMENTS: usize = 100_000;
+
+/// Structure tree levels followed; a deep /K chain would otherwise
+/// overflow the stack
+const MAX_DEPTH: usize = 256;
+
+/// Accessibility-relevant structures of one document
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AccessibilityProfile {
+    /// `/MarkInfo /Marked true`: the document declares itself tagged
+    pub tagged: bool,
+    /// The catalog has a /StructTreeRoot
+    pub struct_tree: bool,
+    /// Structure elements reachable from the tree root
+    pub elements: usize,
+    /// Structure tree kids that reference missing objects
+    pub broken_references: usize,
+    /// Figure elements, after role mapping
+    pub figures: usize,
+    /// Figures with /Alt or /ActualText
+    pub figures_with_alt: usize,
+    /// Catalog /Lang
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// A title in the Info dictionary or XMP
+    pub title: bool,
+    /// `/ViewerPreferences /DisplayDocTitle true`
+    pub display_doc_title: bool,
+}
+
+impl AccessibilityProfile {
+    pub fn of(doc: &Document) -> Self {
+        let mut profile = Self::default();
+        let Ok(catalog) = doc.catalog() else { return profile };
+
+        profile.tagged = catalog.get(b"MarkInfo").ok()
+            .and_then(|mark_info| resolve(doc, mark_info).as_dict().ok())
+            .and_then(|mark_info| mark_info.get(b"Marked").ok()?.as_bool().ok())
+            .unwrap_or(false);
+        profile.language = catalog.get(b"Lang").ok()
+            .and_then(|lang| resolve(doc, lang).as_str().ok())
+            .map(decode_pdf_string)
+            .filter(|lang| !lang.trim().is_empty());
+        profile.title = info_xmp::info_values(doc).contains_key(&info_xmp::MetadataField::Title)
+            || info_xmp::xmp_values(doc).is_some_and(|xmp| xmp.contains_key(&info_xmp::MetadataField::Title));
+        profile.display_doc_title = catalog.get(b"ViewerPreferences").ok()
+            .and_then(|preferences| resolve(doc, preferences).as_dict().ok())
+            .and_then(|preferences| preferences.get(b"DisplayDocTitle").ok()?.as_bool().ok())
+            .unwrap_or(false);
+
+        if let Some(root) = catalog.get(b"StructTreeRoot").ok().and_then(|root| resolve(doc, root).as_dict().ok()) {
+            profile.struct_tree = true;
+            let role_map = root.get(b"RoleMap").ok().and_then(|map| resolve(doc, map).as_dict().ok());
+            let mut walk = TreeWalk { doc, role_map, visited: HashSet::new(), depth: 0, profile: &mut profile };
+            if let Ok(kids) = root.get(b"K") {
+                walk.kids(kids);
+            }
+        }
+        profile
+    }
+}
+
+/// Follows a reference, leaving anything unresolvable as is
+fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+    doc.dereference(object).map_or(object, |(_, object)| object)
+}
+
+/// Counts structure elements depth-first
+struct TreeWalk<'a, 'p> {
+    doc: &'a Document,
+    role_map: Option<&'a Dictionary>,
+    visited: HashSet<ObjectId>,
+    /// Levels below the tree root of the kids being walked
+    depth: usize,
+    profile: &'p mut AccessibilityProfile,
+}
+
+impl<'a> TreeWalk<'a, '_> {
+    /// Walks a /K value: one kid or an array of them
+    fn kids(&mut self, kids: &'a Object) {
+        match kids {
+            Object::Array(items) => items.iter().for_each(|kid| self.kid(kid)),
+            kid => self.kid(kid),
+        }
+    }
+
+    fn kid(&mut self, kid: &'a Object) {
+        if self.profile.elements >= MAX_ELEMENTS || self.depth > MAX_DEPTH {
+            return;
+        }
+        let element = match kid {
+            Object::Reference(id) => {
+                if !self.visited.insert(*id) {
+                    return;
+                }
+                match self.doc.get_object(*id) {
+                    Ok(Object::Dictionary(dict)) => dict,
+                    Ok(_) => return,
+                    Err(_) => {
+                        self.profile.broken_references += 1;
+                        return;
+                    }
+                }
+            }
+            Object::Dictionary(dict) => dict,
+            // Marked content identifiers
+            _ => return,
+        };
+        // Marked content and object references point into pages, not the tree
+        let kind = element.get(b"Type").and_then(Object::as_name).ok();
+        if matches!(kind, Some(b"MCR" | b"OBJR")) || !element.has(b"S") {
+            return;
+        }
+
+        self.profile.elements += 1;
+        if self.standard_type(element) == Some(&b"Figure"[..]) {
+            self.profile.figures += 1;
+            if element.has(b"Alt") || element.has(b"ActualText") {
+                self.profile.figures_with_alt += 1;
+            }
+        }
+        if let Ok(kids) = element.get(b"K") {
+            self.depth += 1;
+            self.kids(kids);
+            self.depth -= 1;
+        }
+    }
+
+    /// Structure type, through one level of /RoleMap
+    fn standard_type(&self, element: &'a Dictionary) -> Option<&'a [u8]> {
+        let kind = element.get(b"S").and_then(Object::as_name).ok()?;
+        let mapped = self.role_map.and_then(|map| map.get(kind).and_then(Object::as_name).ok());
+        Some(mapped.unwrap_or(kind))
+    }
+}
+
+/// Accessibility of a document before and after processing
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AccessibilityImpact {
+    pub before: AccessibilityProfile,
+    pub after: AccessibilityProfile,
+    /// What processing took away; empty when accessibility was not reduced
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl AccessibilityImpact {
+    pub fn compare(before: AccessibilityProfile, after: AccessibilityProfile) -> Self {
+        let mut warnings = Vec::new();
+        if before.tagged && !after.tagged {
+            warnings.push("tagged PDF marker (/MarkInfo /Marked) removed".to_string());
+        }
+        if before.struct_tree && !after.struct_tree {
+            warnings.push("structure tree removed".to_string());
+        } else if after.elements < before.elements {
+            warnings.push(format!("{} of {} structure element(s) lost", before.elements - after.elements, before.elements));
+        }
+        if after.broken_references > before.broken_references {
+            warnings.push(format!(
+                "structure tree has {} broken reference(s), up from {}",
+                after.broken_references, before.broken_references,
+            ));
+        }
+        if after.figures_with_alt < before.figures_with_alt {
+            warnings.push(format!("alt text lost from {} figure(s)", before.figures_with_alt - after.figures_with_alt));
+        }
+        if before.language.is_some() && after.language.is_none() {
+            warnings.push("document language (/Lang) removed".to_string());
+        }
+        if before.title && !after.title {
+            warnings.push("document title removed".to_string());
+        }
+        if before.display_doc_title && !after.display_doc_title {
+            warnings.push("/DisplayDocTitle preference removed".to_string());
+        }
+        Self { before, after, warnings }
+    }
+
+    /// Whether processing reduced accessibility
+    pub fn reduced(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_lost_alt_text_and_language_warned() {
+        let mut doc = Document::with_version("1.7");
+        let info = doc.add_object(dictionary! { "Title" => Object::string_literal("Annual report") });
+        let root_id = doc.new_object_id();
+        let figure = doc.add_object(dictionary! {
+            "Type" => "StructElem",
+            "S" => "Image",
+            "P" => root_id,
+            "Alt" => Object::string_literal("Revenue by quarter"),
+            "K" => 0,
+        });
+        let paragraph = doc.add_object(dictionary! {
+            "Type" => "StructElem",
+            "S" => "P",
+            "P" => root_id,
+            "K" => vec![dictionary! { "Type" => "MCR", "MCID" => 1 }.into()],
+        });
+        doc.objects.insert(root_id, Object::Dictionary(dictionary! {
+            "Type" => "StructTreeRoot",
+            "RoleMap" => dictionary! { "Image" => "Figure" },
+            "K" => vec![figure.into(), paragraph.into()],
+        }));
+        let catalog = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "MarkInfo" => dictionary! { "Marked" => true },
+            "Lang" => Object::string_literal("en-GB"),
+            "StructTreeRoot" => root_id,
+        });
+        doc.trailer.set("Root", catalog);
+        doc.trailer.set("Info", info);
+
+        let before = AccessibilityProfile::of(&doc);
+        assert_eq!(before, AccessibilityProfile {
+            tagged: true,
+            struct_tree: true,
+            elements: 2,
+            broken_references: 0,
+            figures: 1,
+            figures_with_alt: 1,
+            language: Some("en-GB".into()),
+            title: true,
+            display_doc_title: false,
+        });
+        assert!(!AccessibilityImpact::compare(before.clone(), before.clone()).reduced());
+
+        doc.get_object_mut(figure).unwrap().as_dict_mut().unwrap().remove(b"Alt");
+        doc.get_object_mut(catalog).unwrap().as_dict_mut().unwrap().remove(b"Lang");
+        doc.objects.remove(&paragraph);
+        let impact = AccessibilityImpact::compare(before, AccessibilityProfile::of(&doc));
+        assert_eq!(impact.warnings, vec![
+            "1 of 2 structure element(s) lost",
+            "structure tree has 1 broken reference(s), up from 0",
+            "alt text lost from 1 figure(s)",
+            "document language (/Lang) removed",
+        ]);
+    }
+
+    #[test]
+    fn test_deep_structure_chain_cut_off() {
+        let mut doc = Document::with_version("1.7");
+        let mut kid = Object::Integer(0);
+        for _ in 0..10_000 {
+            kid = doc.add_object(dictionary! { "Type" => "StructElem", "S" => "Div", "K" => kid }).into();
+        }
+        let root_id = doc.add_object(dictionary! { "Type" => "StructTreeRoot", "K" => kid });
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "StructTreeRoot" => root_id });
+        doc.trailer.set("Root", catalog);
+
+        assert_eq!(AccessibilityProfile::of(&doc).elements, MAX_DEPTH + 1);
+    }
+}
@@ -0,0 +1,170 @@
+//! Input byte ranges of objects
+//! Author: kartik4091
+//! Created: 2025-06-04 23:46:52 UTC
+//! Locates every indirect object in the bytes of the input file, from
+//! `N G obj` through `endobj`, so an evidence log can name exactly which
+//! bytes a cleaning step removed or altered. Only offsets are kept, never
+//! content; with the input's SHA-256 in the log, anyone holding the
+//! original can check the ranges against it. Objects compressed into an
+//! object stream are located by their container and index.
+
+use std::collections::BTreeMap;
+use lopdf::{xref::XrefEntry, Object, ObjectId};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+/// Half-open range of input bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Where an object was in the input file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InputLocation {
+    /// Stored directly in the file
+    Bytes(ByteRange),
+    /// Compressed into object stream `container` at position `index`
+    ObjectStream {
+        container: String,
+        index: u16,
+        /// Bytes of the object stream itself, when it could be located
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        container_range: Option<ByteRange>,
+    },
+}
+
+/// Input location of every object, keyed by its current ID
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ByteMap {
+    objects: BTreeMap<ObjectId, InputLocation>,
+}
+
+impl ByteMap {
+    /// Locates the objects of `doc` in `data`, the bytes it was loaded from
+    ///
+    /// Call before transforming `doc`: stream lengths are taken from the
+    /// loaded objects. Objects whose cross-reference offset does not point
+    /// at their `obj` header are left out rather than guessed.
+    pub fn build(data: &[u8], doc: &lopdf::Document) -> Self {
+        let mut direct = BTreeMap::new();
+        let mut compressed = Vec::new();
+        for (&number, entry) in &doc.reference_table.entries {
+            match *entry {
+                XrefEntry::Normal { offset, generation } => {
+                    let id = (number, generation);
+                    if let Some(range) = object_range(data, offset as usize, id, doc.objects.get(&id)) {
+                        direct.insert(id, range);
+                    }
+                }
+                XrefEntry::Compressed { container, index } => compressed.push((number, container, index)),
+                _ => {}
+            }
+        }
+
+        let mut objects: BTreeMap<ObjectId, InputLocation> =
+            direct.iter().map(|(&id, &range)| (id, InputLocation::Bytes(range))).collect();
+        for (number, container, index) in compressed {
+            let container_range = direct.get(&(container, 0)).copied();
+            objects.insert((number, 0), InputLocation::ObjectStream {
+                container: format!("{} 0 R", container),
+                index,
+                container_range,
+            });
+        }
+        Self { objects }
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<&InputLocation> {
+        self.objects.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Follows a renumbering, so locations stay attached to the same objects
+    pub fn renumber(&mut self, mapping: &BTreeMap<ObjectId, ObjectId>) {
+        self.objects = std::mem::take(&mut self.objects).into_iter()
+            .map(|(id, location)| (mapping.get(&id).copied().unwrap_or(id), location))
+            .collect();
+    }
+}
+
+/// Range of the object `id` whose header should be at `offset`
+fn object_range(data: &[u8], offset: usize, id: ObjectId, object: Option<&Object>) -> Option<ByteRange> {
+    let header = format!("{} {} obj", id.0, id.1);
+    let start = offset + data.get(offset..)?.iter().take_while(|b| b.is_ascii_whitespace()).count();
+    if !data.get(start..)?.starts_with(header.as_bytes()) {
+        return None;
+    }
+    // Stream data may contain "endobj"; skip it using the loaded length
+    let mut search = start + header.len();
+    if let Some(Object::Stream(stream)) = object {
+        if let Some(keyword) = find(data, b"stream", search) {
+            let mut data_start = keyword + b"stream".len();
+            if data.get(data_start) == Some(&b'\r') {
+                data_start += 1;
+            }
+            if data.get(data_start) == Some(&b'\n') {
+                data_start += 1;
+            }
+            search = data_start + stream.content.len();
+        }
+    }
+    let end = find(data, b"endobj", search)? + b"endobj".len();
+    Some(ByteRange { start: start as u64, end: end as u64 })
+}
+
+fn find(data: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|i| from + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn test_objects_located_in_input() {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let info = doc.add_object(dictionary! { "Author" => Object::string_literal("alice") });
+        let stream = doc.add_object(Stream::new(dictionary! {}, b"BT (endobj) Tj ET".to_vec()));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog" });
+        doc.trailer.set("Root", catalog);
+        doc.trailer.set("Info", info);
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+
+        let loaded = lopdf::Document::load_mem(&data).unwrap();
+        let map = ByteMap::build(&data, &loaded);
+        assert_eq!(map.len(), 3);
+        let Some(InputLocation::Bytes(range)) = map.get(stream) else { panic!("stream not located") };
+        let bytes = &data[range.start as usize..range.end as usize];
+        assert!(bytes.starts_with(format!("{} 0 obj", stream.0).as_bytes()));
+        // The "endobj" inside the stream data does not end the range early
+        assert!(bytes.ends_with(b"endobj"));
+        assert!(bytes.windows(17).any(|w| w == b"BT (endobj) Tj ET"));
+
+        let mut renumbered = map.clone();
+        renumbered.renumber(&BTreeMap::from([(info, (9, 0))]));
+        assert_eq!(renumbered.get((9, 0)), map.get(info));
+        assert_eq!(renumbered.get(info), None);
+    }
+}
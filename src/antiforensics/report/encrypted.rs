@@ -0,0 +1,183 @@
+//! Encrypted report output
+//! Author: kartik4091
+//! Created: 2025-06-05 00:41:37 UTC
+//! Sidecar reports and evidence logs quote the documents they describe:
+//! excerpts of flagged objects, decoded script and URI text, file names.
+//! When those are sensitive, reports can be written encrypted in the age
+//! format, either with a passphrase or to one or more X25519 public keys
+//! (`age1…`), so only the holders can read them. Encrypted reports get an
+//! `.age` suffix and open with `decrypt-report` or any age implementation.
+
+use std::{
+    ffi::OsString,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use age::{
+    scrypt,
+    secrecy::{ExposeSecret, SecretString},
+    x25519,
+};
+
+/// Header every age file starts with
+const AGE_HEADER: &[u8] = b"age-encryption.org/v1\n";
+
+/// Suffix appended to the names of encrypted reports
+pub const ENCRYPTED_SUFFIX: &str = ".age";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReportCryptoError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid key: {0}")]
+    Key(String),
+    #[error("cannot decrypt report: {0}")]
+    Decrypt(#[from] age::DecryptError),
+}
+
+/// Who can read encrypted reports
+pub enum ReportEncryption {
+    Passphrase(SecretString),
+    /// X25519 public keys; any one of their secret keys decrypts
+    Recipients(Vec<x25519::Recipient>),
+}
+
+impl ReportEncryption {
+    pub fn passphrase(passphrase: impl Into<String>) -> Self {
+        Self::Passphrase(SecretString::from(passphrase.into()))
+    }
+
+    /// Parses `age1…` public keys
+    pub fn recipients<S: AsRef<str>>(keys: impl IntoIterator<Item = S>) -> Result<Self, ReportCryptoError> {
+        let recipients = keys.into_iter()
+            .map(|key| {
+                let key = key.as_ref().trim();
+                x25519::Recipient::from_str(key)
+                    .map_err(|e| ReportCryptoError::Key(format!("`{}` is not an age public key: {}", key, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if recipients.is_empty() {
+            return Err(ReportCryptoError::Key("no recipients given".into()));
+        }
+        Ok(Self::Recipients(recipients))
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let encryptor = match self {
+            Self::Passphrase(passphrase) => {
+                age::Encryptor::with_user_passphrase(SecretString::from(passphrase.expose_secret().to_string()))
+            }
+            Self::Recipients(recipients) => {
+                age::Encryptor::with_recipients(recipients.iter().map(|recipient| recipient as &dyn age::Recipient))
+                    .expect("constructed with at least one recipient")
+            }
+        };
+        let mut encrypted = Vec::with_capacity(data.len() + 256);
+        let mut writer = encryptor.wrap_output(&mut encrypted)?;
+        writer.write_all(data)?;
+        writer.finish()?;
+        Ok(encrypted)
+    }
+}
+
+/// What opens an encrypted report
+pub enum ReportKey {
+    Passphrase(SecretString),
+    Identities(Vec<x25519::Identity>),
+}
+
+impl ReportKey {
+    pub fn passphrase(passphrase: impl Into<String>) -> Self {
+        Self::Passphrase(SecretString::from(passphrase.into()))
+    }
+
+    /// Reads the `AGE-SECRET-KEY-1…` lines of an age identity file
+    ///
+    /// Blank lines and `#` comments, as written by `age-keygen`, are skipped.
+    pub fn identity_file(path: &Path) -> Result<Self, ReportCryptoError> {
+        let identities = std::fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                x25519::Identity::from_str(line)
+                    .map_err(|_| ReportCryptoError::Key(format!("{} holds a line that is not an age secret key", path.display())))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if identities.is_empty() {
+            return Err(ReportCryptoError::Key(format!("{} holds no age secret key", path.display())));
+        }
+        Ok(Self::Identities(identities))
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(AGE_HEADER)
+}
+
+/// Path an encrypted report is written to: `a.report.json` becomes `a.report.json.age`
+pub fn encrypted_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(ENCRYPTED_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Writes `data` to `path`, or with `encryption` to its encrypted path; returns the path written
+pub fn write(path: &Path, data: &[u8], encryption: Option<&ReportEncryption>) -> io::Result<PathBuf> {
+    let Some(encryption) = encryption else {
+        std::fs::write(path, data)?;
+        return Ok(path.to_path_buf());
+    };
+    let path = encrypted_path(path);
+    std::fs::write(&path, encryption.encrypt(data)?)?;
+    Ok(path)
+}
+
+pub fn decrypt(data: &[u8], key: &ReportKey) -> Result<Vec<u8>, ReportCryptoError> {
+    let decryptor = age::Decryptor::new(data)?;
+    let mut reader = match key {
+        ReportKey::Passphrase(passphrase) => {
+            if !decryptor.is_scrypt() {
+                return Err(ReportCryptoError::Key("report is encrypted to public keys; give an identity file".into()));
+            }
+            let identity = scrypt::Identity::new(SecretString::from(passphrase.expose_secret().to_string()));
+            decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))?
+        }
+        ReportKey::Identities(identities) => {
+            if decryptor.is_scrypt() {
+                return Err(ReportCryptoError::Key("report is encrypted with a passphrase, not to a key".into()));
+            }
+            decryptor.decrypt(identities.iter().map(|identity| identity as &dyn age::Identity))?
+        }
+    };
+    let mut decrypted = Vec::new();
+    reader.read_to_end(&mut decrypted)?;
+    Ok(decrypted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_decrypt_only_with_their_key() {
+        let identity = x25519::Identity::generate();
+        let public = identity.to_public().to_string();
+        assert!(ReportEncryption::recipients(["age1notakey"]).is_err());
+        let encryption = ReportEncryption::recipients([public.as_str()]).unwrap();
+
+        let report = br#"{"findings":[{"snippet":"app.launchURL('https://example.com')"}]}"#;
+        let encrypted = encryption.encrypt(report).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.windows(9).any(|w| w == b"launchURL"));
+
+        let key = ReportKey::Identities(vec![identity]);
+        assert_eq!(decrypt(&encrypted, &key).unwrap(), report);
+        let other = ReportKey::Identities(vec![x25519::Identity::generate()]);
+        assert!(matches!(decrypt(&encrypted, &other), Err(ReportCryptoError::Decrypt(_))));
+        assert!(matches!(decrypt(&encrypted, &ReportKey::passphrase("secret")), Err(ReportCryptoError::Key(_))));
+
+        assert_eq!(encrypted_path(Path::new("/out/a.report.json")), PathBuf::from("/out/a.report.json.age"));
+    }
+}
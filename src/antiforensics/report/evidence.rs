@@ -0,0 +1,354 @@
+//! Evidence log for chain-of-custody processing
+//! Author: kartik4091
+//! Created: 2025-06-04 21:16:05 UTC
+//! Records every object a cleaning step added, changed or removed, with
+//! digests before and after, the rule that did it, when and on whose
+//! behalf. The log is serialized as canonical JSON (sorted keys, no
+//! insignificant whitespace) and signed with Ed25519, so anyone holding
+//! the public key can show it has not been edited since processing,
+//! without being able to sign an edited log themselves.
+//! With byte ranges on, each entry for an input object also names where
+//! that object was in the input file.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use lopdf::ObjectId;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use super::byte_ranges::{ByteMap, InputLocation};
+use super::encrypted::{self, ReportEncryption};
+use crate::antiforensics::hash::object_digest;
+
+/// Log format version, bumped on incompatible changes
+pub const EVIDENCE_FORMAT_VERSION: u32 = 1;
+
+/// Signature algorithm written into signed logs
+pub const SIGNATURE_ALGORITHM: &str = "Ed25519";
+
+/// One object-level change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EvidenceEntry {
+    /// Position in the log, from 1
+    pub sequence: u64,
+    /// Object as `N G R`, or `trailer`
+    pub object: String,
+    /// Digest before the step; `None` when the step created the object
+    pub before_hash: Option<String>,
+    /// Digest after the step; `None` when the step removed the object
+    pub after_hash: Option<String>,
+    /// New ID when the step renumbered the object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renumbered_to: Option<String>,
+    /// Where the object was in the input file, when byte ranges are recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_location: Option<InputLocation>,
+    /// Cleaning step that made the change
+    pub rule: String,
+    pub timestamp: DateTime<Utc>,
+    pub operator: String,
+}
+
+/// Signature over the canonical form of a log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EvidenceSignature {
+    pub algorithm: String,
+    /// Caller-chosen name of the key, so verifiers know which one to use
+    pub key_id: String,
+    /// Hex-encoded signature
+    pub value: String,
+}
+
+/// Object digests of a document at one point in processing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    objects: BTreeMap<ObjectId, String>,
+    trailer: String,
+}
+
+impl Snapshot {
+    pub fn of(doc: &lopdf::Document) -> Self {
+        Self {
+            objects: doc.objects.iter().map(|(&id, object)| (id, object_digest(object))).collect(),
+            trailer: object_digest(&lopdf::Object::Dictionary(doc.trailer.clone())),
+        }
+    }
+}
+
+/// Complete log for one processed file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EvidenceLog {
+    pub version: u32,
+    pub input: PathBuf,
+    pub input_sha256: String,
+    pub output: Option<PathBuf>,
+    pub output_sha256: Option<String>,
+    pub operator: String,
+    pub started_at: DateTime<Utc>,
+    pub entries: Vec<EvidenceEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<EvidenceSignature>,
+    /// Input object locations; entries only carry what is looked up here
+    #[serde(skip)]
+    byte_map: Option<ByteMap>,
+}
+
+impl EvidenceLog {
+    pub fn new(input: impl Into<PathBuf>, input_sha256: impl Into<String>, operator: impl Into<String>) -> Self {
+        Self {
+            version: EVIDENCE_FORMAT_VERSION,
+            input: input.into(),
+            input_sha256: input_sha256.into(),
+            output: None,
+            output_sha256: None,
+            operator: operator.into(),
+            started_at: Utc::now(),
+            entries: Vec::new(),
+            signature: None,
+            byte_map: None,
+        }
+    }
+
+    /// Records the input location of every changed object from now on
+    ///
+    /// `map` must be built from the input before any step runs.
+    pub fn with_byte_ranges(mut self, map: ByteMap) -> Self {
+        self.byte_map = Some(map);
+        self
+    }
+
+    /// Records the differences between `before` and the document as it is now
+    pub fn record_step(&mut self, rule: &str, before: &Snapshot, after: &lopdf::Document) {
+        let after = Snapshot::of(after);
+        let ids: BTreeSet<ObjectId> = before.objects.keys().chain(after.objects.keys()).copied().collect();
+        for id in ids {
+            let (old, new) = (before.objects.get(&id), after.objects.get(&id));
+            if old != new {
+                // Objects a step created have no input bytes
+                let location = old.and_then(|_| self.input_location(id));
+                self.push(rule, format_id(id), old.cloned(), new.cloned(), None, location);
+            }
+        }
+        if before.trailer != after.trailer {
+            self.push(rule, "trailer".into(), Some(before.trailer.clone()), Some(after.trailer), None, None);
+        }
+    }
+
+    /// Records a renumbering step, pairing each object with its new ID
+    pub fn record_renumbering(
+        &mut self,
+        rule: &str,
+        before: &Snapshot,
+        after: &lopdf::Document,
+        mapping: &BTreeMap<ObjectId, ObjectId>,
+    ) {
+        let after = Snapshot::of(after);
+        for (&old_id, old_hash) in &before.objects {
+            let new_id = mapping.get(&old_id).copied().unwrap_or(old_id);
+            let new_hash = after.objects.get(&new_id);
+            if new_id != old_id || new_hash != Some(old_hash) {
+                let renumbered_to = (new_id != old_id).then(|| format_id(new_id));
+                let location = self.input_location(old_id);
+                self.push(rule, format_id(old_id), Some(old_hash.clone()), new_hash.cloned(), renumbered_to, location);
+            }
+        }
+        if before.trailer != after.trailer {
+            self.push(rule, "trailer".into(), Some(before.trailer.clone()), Some(after.trailer), None, None);
+        }
+        if let Some(map) = self.byte_map.as_mut() {
+            map.renumber(mapping);
+        }
+    }
+
+    fn input_location(&self, id: ObjectId) -> Option<InputLocation> {
+        self.byte_map.as_ref()?.get(id).cloned()
+    }
+
+    fn push(
+        &mut self,
+        rule: &str,
+        object: String,
+        before_hash: Option<String>,
+        after_hash: Option<String>,
+        renumbered_to: Option<String>,
+        input_location: Option<InputLocation>,
+    ) {
+        self.entries.push(EvidenceEntry {
+            sequence: self.entries.len() as u64 + 1,
+            object,
+            before_hash,
+            after_hash,
+            renumbered_to,
+            input_location,
+            rule: rule.to_string(),
+            timestamp: Utc::now(),
+            operator: self.operator.clone(),
+        });
+    }
+
+    /// Canonical JSON of the log without its signature: what the signature covers
+    pub fn canonical_bytes(&self) -> serde_json::Result<Vec<u8>> {
+        let unsigned = Self { signature: None, byte_map: None, ..self.clone() };
+        // Value maps are sorted by key, and compact output has no insignificant whitespace
+        serde_json::to_vec(&serde_json::to_value(&unsigned)?)
+    }
+
+    pub fn sign(&mut self, key_id: &str, key: &SigningKey) -> serde_json::Result<()> {
+        let signature = key.sign(&self.canonical_bytes()?);
+        self.signature = Some(EvidenceSignature {
+            algorithm: SIGNATURE_ALGORITHM.into(),
+            key_id: key_id.into(),
+            value: encode_hex(&signature.to_bytes()),
+        });
+        Ok(())
+    }
+
+    /// Whether the log carries a valid signature by the holder of `key`'s secret half
+    pub fn verify(&self, key: &VerifyingKey) -> bool {
+        let Some(signature) = &self.signature else { return false };
+        if signature.algorithm != SIGNATURE_ALGORITHM {
+            return false;
+        }
+        let Ok(bytes) = self.canonical_bytes() else { return false };
+        let Some(signature) = decode_hex(&signature.value).and_then(|value| Signature::from_slice(&value).ok()) else {
+            return false;
+        };
+        key.verify_strict(&bytes, &signature).is_ok()
+    }
+
+    /// Log path for `output`: `out/a.pdf` becomes `out/a.evidence.json`
+    pub fn path_for(output: &Path) -> PathBuf {
+        output.with_extension("evidence.json")
+    }
+
+    /// Writes the canonical form, signature included, next to `output`
+    ///
+    /// With `encryption`, the log is written encrypted under an `.age` suffix;
+    /// the signature covers the plaintext.
+    pub fn write(&self, output: &Path, encryption: Option<&ReportEncryption>) -> io::Result<PathBuf> {
+        encrypted::write(&Self::path_for(output), &serde_json::to_vec(&serde_json::to_value(self)?)?, encryption)
+    }
+}
+
+fn format_id((number, generation): ObjectId) -> String {
+    format!("{} {} R", number, generation)
+}
+
+/// Signing key from a key file: the 32-byte secret, raw or hex-encoded
+pub fn signing_key(bytes: &[u8]) -> io::Result<SigningKey> {
+    let text = std::str::from_utf8(bytes).ok().map(str::trim);
+    let secret = match text.and_then(decode_hex) {
+        Some(secret) if secret.len() == 32 => secret,
+        _ => bytes.to_vec(),
+    };
+    let secret: [u8; 32] = secret.try_into().map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, "evidence key must be a 32-byte Ed25519 secret, raw or hex")
+    })?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+/// Hex form of the public key verifiers need for logs signed with `key`
+pub fn public_key_hex(key: &SigningKey) -> String {
+    encode_hex(key.verifying_key().as_bytes())
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn document() -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let info = doc.add_object(dictionary! { "Author" => lopdf::Object::string_literal("alice") });
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog" });
+        doc.trailer.set("Root", catalog);
+        doc.trailer.set("Info", info);
+        doc
+    }
+
+    #[test]
+    fn test_changes_recorded_per_object() {
+        let mut doc = document();
+        let mut log = EvidenceLog::new("in.pdf", "00", "analyst");
+
+        let before = Snapshot::of(&doc);
+        doc.objects.remove(&(1, 0));
+        doc.trailer.remove(b"Info");
+        doc.get_dictionary_mut((2, 0)).unwrap().set("Lang", lopdf::Object::string_literal("en"));
+        log.record_step("remove_metadata", &before, &doc);
+
+        let objects: Vec<&str> = log.entries.iter().map(|e| e.object.as_str()).collect();
+        assert_eq!(objects, vec!["1 0 R", "2 0 R", "trailer"]);
+        assert_eq!(log.entries[0].after_hash, None);
+        assert!(log.entries.iter().all(|e| e.rule == "remove_metadata" && e.operator == "analyst"));
+        assert_eq!(log.entries[2].sequence, 3);
+    }
+
+    #[test]
+    fn test_signature_detects_tampering() {
+        let mut doc = document();
+        let mut log = EvidenceLog::new("in.pdf", "00", "analyst");
+        let before = Snapshot::of(&doc);
+        doc.objects.remove(&(1, 0));
+        log.record_step("remove_metadata", &before, &doc);
+        let key = signing_key(&[7; 32]).unwrap();
+        log.sign("evidence-key", &key).unwrap();
+
+        // Verifying needs only the public half
+        let public = key.verifying_key();
+        assert!(log.verify(&public));
+        assert!(!log.verify(&SigningKey::from_bytes(&[8; 32]).verifying_key()));
+
+        let mut tampered = log.clone();
+        tampered.entries[0].operator = "someone else".into();
+        assert!(!tampered.verify(&public));
+
+        // Key files hold the secret raw or as hex
+        let hex = format!("{}\n", encode_hex(&[7; 32]));
+        assert_eq!(signing_key(hex.as_bytes()).unwrap().to_bytes(), key.to_bytes());
+        assert!(signing_key(b"secret").is_err());
+    }
+
+    #[test]
+    fn test_byte_ranges_follow_objects() {
+        let mut data = Vec::new();
+        document().save_to(&mut data).unwrap();
+        let mut doc = lopdf::Document::load_mem(&data).unwrap();
+        let mut log = EvidenceLog::new("in.pdf", "00", "analyst").with_byte_ranges(ByteMap::build(&data, &doc));
+
+        let before = Snapshot::of(&doc);
+        doc.objects.remove(&(1, 0));
+        doc.add_object(dictionary! { "Type" => "Metadata" });
+        log.record_step("remove_metadata", &before, &doc);
+
+        let Some(InputLocation::Bytes(range)) = &log.entries[0].input_location else { panic!("no range") };
+        assert!(data[range.start as usize..].starts_with(b"1 0 obj"));
+        // The added object was not in the input
+        assert_eq!(log.entries[1].input_location, None);
+        let json = String::from_utf8(log.canonical_bytes().unwrap()).unwrap();
+        assert!(json.contains("\"kind\":\"bytes\"") && !json.contains("alice"));
+    }
+
+    #[test]
+    fn test_canonical_form_is_stable() {
+        let log = EvidenceLog::new("in.pdf", "00", "analyst");
+        let bytes = log.canonical_bytes().unwrap();
+        let reparsed: EvidenceLog = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(reparsed.canonical_bytes().unwrap(), bytes);
+        assert!(!bytes.contains(&b'\n'));
+    }
+}
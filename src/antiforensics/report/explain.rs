@@ -0,0 +1,454 @@
+//! Per-artifact evidence bundles
+//! Author: kartik4091
+//! Created: 2025-06-04 22:38:15 UTC
+//! A finding is easier to trust when the report shows what it was found
+//! in. [`Explainer`] resolves an artifact's location back to the object it
+//! points at and captures an excerpt of that object's dictionary, a
+//! decoded snippet of its content and the chain of references leading to
+//! it from the trailer, so reviewers can check the finding without opening
+//! the PDF in another tool.
+//!
+//! Bundles are built from the document as scanned, before cleaning.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use crate::antiforensics::{scanner::unicode_spoof::decode_pdf_string, ForensicArtifact};
+
+/// Limits on how much of each object a bundle copies
+#[derive(Debug, Clone)]
+pub struct ExplainConfig {
+    /// Characters of the rendered dictionary to keep
+    pub dictionary_chars: usize,
+    /// Bytes of decoded content to keep
+    pub snippet_bytes: usize,
+}
+
+impl Default for ExplainConfig {
+    fn default() -> Self {
+        Self { dictionary_chars: 1024, snippet_bytes: 512 }
+    }
+}
+
+/// What a finding points at, captured for review
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EvidenceBundle {
+    /// Object the finding was resolved to, as `N G R`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object: Option<String>,
+    /// The flagged value in PDF syntax, truncated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<String>,
+    /// Decoded stream content or script/URI text; binary data is given as
+    /// `hex:` followed by the leading bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    /// References from the trailer down to the object, like
+    /// `trailer/Root`, `1 0 R/Pages`, `2 0 R/Kids[0]`; empty when the
+    /// object is unreachable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parent_chain: Vec<String>,
+}
+
+impl EvidenceBundle {
+    pub fn is_empty(&self) -> bool {
+        self.object.is_none() && self.dictionary.is_none() && self.snippet.is_none()
+    }
+}
+
+/// A finding with the evidence behind it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExplainedFinding {
+    pub artifact_id: String,
+    pub artifact_type: String,
+    pub risk_level: String,
+    pub location: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern_id: Option<String>,
+    /// Findings this one stands for after deduplication
+    #[serde(default = "one")]
+    pub occurrences: usize,
+    pub evidence: EvidenceBundle,
+}
+
+fn one() -> usize {
+    1
+}
+
+/// Builds evidence bundles for findings in one document
+pub struct Explainer<'a> {
+    doc: &'a Document,
+    config: ExplainConfig,
+    /// For each object, the objects referencing it and through which key
+    referrers: HashMap<ObjectId, Vec<(ObjectId, String)>>,
+}
+
+impl<'a> Explainer<'a> {
+    pub fn new(doc: &'a Document) -> Self {
+        Self::with_config(doc, ExplainConfig::default())
+    }
+
+    pub fn with_config(doc: &'a Document, config: ExplainConfig) -> Self {
+        let mut referrers: HashMap<ObjectId, Vec<(ObjectId, String)>> = HashMap::new();
+        for (&id, object) in &doc.objects {
+            collect_references(object, &mut String::new(), &mut |target, path| {
+                referrers.entry(target).or_default().push((id, path.to_string()));
+            });
+        }
+        for list in referrers.values_mut() {
+            list.sort();
+        }
+        Self { doc, config, referrers }
+    }
+
+    /// Bundles every artifact whose location resolves to something
+    pub fn explain_all(&self, artifacts: &[ForensicArtifact]) -> Vec<ExplainedFinding> {
+        artifacts.iter()
+            .filter_map(|artifact| {
+                let evidence = self.explain(artifact);
+                (!evidence.is_empty()).then(|| ExplainedFinding {
+                    artifact_id: artifact.id.clone(),
+                    artifact_type: format!("{:?}", artifact.artifact_type),
+                    risk_level: format!("{:?}", artifact.risk_level),
+                    location: artifact.location.clone(),
+                    description: artifact.description.clone(),
+                    pattern_id: artifact.metadata.get("pattern_id").cloned(),
+                    occurrences: 1,
+                    evidence,
+                })
+            })
+            .collect()
+    }
+
+    /// Evidence for one artifact; empty when its location names no object
+    pub fn explain(&self, artifact: &ForensicArtifact) -> EvidenceBundle {
+        let Some((id, value)) = self.resolve(artifact) else { return EvidenceBundle::default() };
+
+        let object = self.doc.objects.get(&id);
+        let value = value.or(object);
+        let dictionary = value.map(|value| truncate(render(value, 0), self.config.dictionary_chars));
+        let snippet = value.and_then(|value| self.snippet(value));
+
+        EvidenceBundle {
+            object: Some(format!("{} {} R", id.0, id.1)),
+            dictionary,
+            snippet,
+            parent_chain: self.parent_chain(id),
+        }
+    }
+
+    /// Object an artifact was resolved to, if its location names one
+    pub fn object_of(&self, artifact: &ForensicArtifact) -> Option<ObjectId> {
+        self.resolve(artifact).map(|(id, _)| id)
+    }
+
+    /// Object an artifact points at, and the flagged value inside it when
+    /// the location names one
+    fn resolve(&self, artifact: &ForensicArtifact) -> Option<(ObjectId, Option<&'a Object>)> {
+        // `N G R:/Path` from object scanners, `object_id` = `N G` in metadata
+        let (head, rest) = artifact.location.split_once(':').unwrap_or((artifact.location.as_str(), ""));
+        let path = rest.starts_with('/').then_some(rest);
+        let id = parse_reference(head)
+            .or_else(|| artifact.metadata.get("object_id").and_then(|id| parse_reference(&format!("{} R", id))));
+        if let Some(id) = id {
+            let object = self.doc.objects.get(&id)?;
+            let value = path.and_then(|path| self.walk(object, path).map(|(_, value)| value));
+            return Some((id, value));
+        }
+
+        // `/Root/...` paths from the catalog-level scanners
+        let path = artifact.location.strip_prefix("/Root")?;
+        let root = self.doc.trailer.get(b"Root").ok()?.as_reference().ok()?;
+        let catalog = self.doc.objects.get(&root)?;
+        match self.walk(catalog, path) {
+            Some((Some(id), value)) => Some((id, Some(value))),
+            Some((None, value)) => Some((root, Some(value))),
+            None => Some((root, None)),
+        }
+    }
+
+    /// Follows `/A/B/C` through dictionaries, dereferencing on the way
+    ///
+    /// Returns the last object reached through a reference, if any, and
+    /// the value at the end of the path.
+    fn walk(&self, start: &'a Object, path: &str) -> Option<(Option<ObjectId>, &'a Object)> {
+        let mut current = start;
+        let mut last_id = None;
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            let dict = match current {
+                Object::Dictionary(dict) => dict,
+                Object::Stream(stream) => &stream.dict,
+                _ => return None,
+            };
+            current = dict.get(segment.as_bytes()).ok()?;
+            if let Object::Reference(id) = current {
+                last_id = Some(*id);
+                current = self.doc.objects.get(id)?;
+            }
+        }
+        Some((last_id, current))
+    }
+
+    /// Decoded content of a stream, or the script or URI a dictionary holds
+    fn snippet(&self, value: &Object) -> Option<String> {
+        let dict = match value {
+            Object::Stream(stream) => {
+                let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                return Some(self.describe_bytes(&content));
+            }
+            Object::String(bytes, _) => return Some(self.describe_text(&decode_pdf_string(bytes))),
+            Object::Dictionary(dict) => dict,
+            _ => return None,
+        };
+        [b"JS".as_slice(), b"URI", b"F", b"Contents"].iter()
+            .find_map(|key| match dict.get(key).ok()? {
+                Object::String(bytes, _) => Some(self.describe_text(&decode_pdf_string(bytes))),
+                Object::Reference(id) => match self.doc.objects.get(id)? {
+                    Object::Stream(stream) => {
+                        let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                        Some(self.describe_bytes(&content))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+    }
+
+    fn describe_bytes(&self, bytes: &[u8]) -> String {
+        let head = &bytes[..bytes.len().min(self.config.snippet_bytes)];
+        let printable = head.iter()
+            .filter(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+            .count();
+        if head.is_empty() || printable * 10 >= head.len() * 9 {
+            self.describe_text(&String::from_utf8_lossy(bytes))
+        } else {
+            let hex: String = head.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("hex:{}{}", hex, if bytes.len() > head.len() { "…" } else { "" })
+        }
+    }
+
+    fn describe_text(&self, text: &str) -> String {
+        truncate(text.to_string(), self.config.snippet_bytes)
+    }
+
+    /// Shortest chain of references from the trailer to `target`
+    fn parent_chain(&self, target: ObjectId) -> Vec<String> {
+        let Some(root) = self.doc.trailer.get(b"Root").ok().and_then(|r| r.as_reference().ok()) else {
+            return Vec::new();
+        };
+
+        // Breadth-first upwards from the target until the catalog is reached
+        let mut came_from: HashMap<ObjectId, (ObjectId, String)> = HashMap::new();
+        let mut seen: HashSet<ObjectId> = HashSet::from([target]);
+        let mut queue = VecDeque::from([target]);
+        let mut found = target == root;
+        while let Some(id) = queue.pop_front() {
+            if found {
+                break;
+            }
+            for (parent, path) in self.referrers.get(&id).into_iter().flatten() {
+                if seen.insert(*parent) {
+                    came_from.insert(*parent, (id, path.clone()));
+                    if *parent == root {
+                        found = true;
+                        break;
+                    }
+                    queue.push_back(*parent);
+                }
+            }
+        }
+        if !found {
+            return Vec::new();
+        }
+
+        let mut chain = vec!["trailer/Root".to_string()];
+        let mut current = root;
+        while current != target {
+            let (child, path) = &came_from[&current];
+            chain.push(format!("{} {} R{}", current.0, current.1, path));
+            current = *child;
+        }
+        chain
+    }
+}
+
+/// Parses a leading `N G R`
+fn parse_reference(text: &str) -> Option<ObjectId> {
+    let mut parts = text.split_whitespace();
+    let number = parts.next()?.parse().ok()?;
+    let generation = parts.next()?.parse().ok()?;
+    (parts.next()? == "R").then_some((number, generation))
+}
+
+/// Calls `found` for every reference inside `object` with its key path
+///
+/// `/Parent` and `/P` point back up the tree and are skipped, so chains
+/// always lead down from the catalog.
+pub(super) fn collect_references(object: &Object, path: &mut String, found: &mut impl FnMut(ObjectId, &str)) {
+    match object {
+        Object::Reference(id) => found(*id, path),
+        Object::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                let len = path.len();
+                path.push_str(&format!("[{}]", index));
+                collect_references(item, path, found);
+                path.truncate(len);
+            }
+        }
+        Object::Dictionary(dict) => collect_dictionary(dict, path, found),
+        Object::Stream(stream) => collect_dictionary(&stream.dict, path, found),
+        _ => {}
+    }
+}
+
+fn collect_dictionary(dict: &Dictionary, path: &mut String, found: &mut impl FnMut(ObjectId, &str)) {
+    for (key, value) in dict.iter() {
+        if key == b"Parent" || key == b"P" {
+            continue;
+        }
+        let len = path.len();
+        path.push('/');
+        path.push_str(&String::from_utf8_lossy(key));
+        collect_references(value, path, found);
+        path.truncate(len);
+    }
+}
+
+/// Renders an object in PDF syntax, eliding nesting past two levels
+fn render(object: &Object, depth: usize) -> String {
+    match object {
+        Object::Null => "null".into(),
+        Object::Boolean(value) => value.to_string(),
+        Object::Integer(value) => value.to_string(),
+        Object::Real(value) => value.to_string(),
+        Object::Name(name) => format!("/{}", String::from_utf8_lossy(name)),
+        Object::String(bytes, _) => {
+            let text = decode_pdf_string(bytes);
+            if text.chars().all(|c| !c.is_control() || c.is_whitespace()) {
+                format!("({})", text.replace('\\', "\\\\").replace('(', "\\(").replace(')', "\\)"))
+            } else {
+                format!("<{}>", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+            }
+        }
+        Object::Reference((number, generation)) => format!("{} {} R", number, generation),
+        Object::Array(_) | Object::Dictionary(_) if depth >= 2 => match object {
+            Object::Array(_) => "[…]".into(),
+            _ => "<< … >>".into(),
+        },
+        Object::Array(items) => {
+            let items: Vec<String> = items.iter().map(|item| render(item, depth + 1)).collect();
+            format!("[{}]", items.join(" "))
+        }
+        Object::Dictionary(dict) => render_dictionary(dict, depth),
+        Object::Stream(stream) => format!(
+            "{} stream ({} bytes)",
+            render_dictionary(&stream.dict, depth),
+            stream.content.len(),
+        ),
+    }
+}
+
+fn render_dictionary(dict: &Dictionary, depth: usize) -> String {
+    let entries: Vec<String> = dict.iter()
+        .map(|(key, value)| format!("/{} {}", String::from_utf8_lossy(key), render(value, depth + 1)))
+        .collect();
+    format!("<< {} >>", entries.join(" "))
+}
+
+fn truncate(mut text: String, max_chars: usize) -> String {
+    if let Some((cut, _)) = text.char_indices().nth(max_chars) {
+        text.truncate(cut);
+        text.push('…');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+    use std::collections::HashMap;
+
+    fn document() -> (Document, ObjectId, ObjectId) {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let script_id = doc.add_object(Stream::new(dictionary! {}, b"app.alert('hi');".to_vec()));
+        let action_id = doc.add_object(dictionary! { "S" => "JavaScript", "JS" => Object::Reference(script_id) });
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "AA" => dictionary! { "O" => action_id },
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }));
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "OpenAction" => dictionary! { "S" => "URI", "URI" => Object::string_literal("https://example.com/t") },
+        });
+        doc.trailer.set("Root", catalog_id);
+        (doc, action_id, catalog_id)
+    }
+
+    #[test]
+    fn test_bundle_for_object_reference() {
+        let (doc, action_id, catalog_id) = document();
+        let artifact = ForensicArtifact {
+            location: format!("{} {} R", action_id.0, action_id.1),
+            ..Default::default()
+        };
+
+        let bundle = Explainer::new(&doc).explain(&artifact);
+        assert_eq!(bundle.object, Some(format!("{} {} R", action_id.0, action_id.1)));
+        assert!(bundle.dictionary.unwrap().contains("/S /JavaScript"));
+        assert_eq!(bundle.snippet.as_deref(), Some("app.alert('hi');"));
+        assert_eq!(bundle.parent_chain.first().map(String::as_str), Some("trailer/Root"));
+        assert_eq!(bundle.parent_chain[1], format!("{} {} R/Pages", catalog_id.0, catalog_id.1));
+        assert!(bundle.parent_chain.last().unwrap().ends_with("/AA/O"));
+    }
+
+    #[test]
+    fn test_bundle_for_catalog_path() {
+        let (doc, _, catalog_id) = document();
+        let artifact = ForensicArtifact { location: "/Root/OpenAction".into(), ..Default::default() };
+
+        let bundle = Explainer::new(&doc).explain(&artifact);
+        assert_eq!(bundle.object, Some(format!("{} {} R", catalog_id.0, catalog_id.1)));
+        assert_eq!(bundle.dictionary.as_deref(), Some("<< /S /URI /URI (https://example.com/t) >>"));
+        assert_eq!(bundle.snippet.as_deref(), Some("https://example.com/t"));
+        assert_eq!(bundle.parent_chain, vec!["trailer/Root"]);
+    }
+
+    #[test]
+    fn test_object_id_metadata_and_unresolvable_locations() {
+        let (doc, action_id, _) = document();
+        let explainer = Explainer::new(&doc);
+
+        let mut metadata = HashMap::new();
+        metadata.insert("object_id".to_string(), format!("{} {}", action_id.0, action_id.1));
+        let by_metadata = ForensicArtifact { location: "Field: JS".into(), metadata, ..Default::default() };
+        assert!(explainer.explain(&by_metadata).object.is_some());
+
+        let unresolvable = ForensicArtifact { location: "File header".into(), ..Default::default() };
+        assert!(explainer.explain(&unresolvable).is_empty());
+        assert_eq!(explainer.explain_all(&[by_metadata, unresolvable]).len(), 1);
+    }
+
+    #[test]
+    fn test_truncates_binary_snippets() {
+        let mut doc = Document::with_version("1.7");
+        let id = doc.add_object(Stream::new(dictionary! {}, vec![0u8, 1, 2, 3, 0xff]));
+        let config = ExplainConfig { snippet_bytes: 2, ..Default::default() };
+        let artifact = ForensicArtifact { location: format!("{} 0 R", id.0), ..Default::default() };
+
+        let bundle = Explainer::with_config(&doc, config).explain(&artifact);
+        assert_eq!(bundle.snippet.as_deref(), Some("hex:0001…"));
+        assert!(bundle.parent_chain.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+//! Object reference graph export
+//! Author: kartik4091
+//! Created: 2025-06-04 23:52:37 UTC
+//! Turns the references between a document's objects into a graph for
+//! Graphviz (DOT) or Gephi and yEd (GraphML). Nodes carry each object's
+//! type and the highest risk of the findings resolved to it; the risky
+//! view keeps only flagged objects and the paths leading to them, which
+//! is usually what an analyst wants to look at in a large file.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt::Write as _;
+use lopdf::{Document, Object, ObjectId};
+use serde::{Serialize, Deserialize};
+
+use super::explain::{collect_references, Explainer};
+use crate::antiforensics::{ForensicArtifact, RiskLevel};
+
+/// Output format of a graph export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// GraphML XML
+    GraphMl,
+}
+
+/// One indirect object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+    /// `/Type` and `/Subtype` when present, otherwise the kind of object
+    pub kind: String,
+    /// Highest risk among the findings in this object
+    pub risk: Option<RiskLevel>,
+    /// Findings resolved to this object
+    pub findings: usize,
+}
+
+/// A reference from one object to another
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GraphEdge {
+    /// Referring object; `None` for the trailer
+    pub from: Option<ObjectId>,
+    pub to: ObjectId,
+    /// Key path of the reference inside the referring object, like `/Kids[0]`
+    pub key: String,
+}
+
+/// References between the objects of a document
+///
+/// `/Parent` and `/P` back-references are left out, as in evidence
+/// bundles, so the graph reads down from the trailer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectGraph {
+    pub nodes: BTreeMap<ObjectId, GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl ObjectGraph {
+    /// Builds the graph of `doc`, marking the objects `artifacts` resolve to
+    pub fn build(doc: &Document, artifacts: &[ForensicArtifact]) -> Self {
+        let mut nodes: BTreeMap<ObjectId, GraphNode> = doc.objects.iter()
+            .map(|(&id, object)| (id, GraphNode { kind: kind(object), risk: None, findings: 0 }))
+            .collect();
+
+        let explainer = Explainer::new(doc);
+        for artifact in artifacts {
+            let Some(node) = explainer.object_of(artifact).and_then(|id| nodes.get_mut(&id)) else { continue };
+            node.findings += 1;
+            if node.risk.is_none_or(|risk| artifact.risk_level.severity() > risk.severity()) {
+                node.risk = Some(artifact.risk_level);
+            }
+        }
+
+        let mut edges = Vec::new();
+        collect_references(&Object::Dictionary(doc.trailer.clone()), &mut String::new(), &mut |to, key| {
+            edges.push(GraphEdge { from: None, to, key: key.to_string() });
+        });
+        for (&id, object) in &doc.objects {
+            collect_references(object, &mut String::new(), &mut |to, key| {
+                edges.push(GraphEdge { from: Some(id), to, key: key.to_string() });
+            });
+        }
+        // Dangling references are no part of the structure
+        edges.retain(|edge| nodes.contains_key(&edge.to));
+        edges.sort();
+        Self { nodes, edges }
+    }
+
+    /// Only flagged objects and the objects through which they are reached
+    pub fn risky_only(mut self) -> Self {
+        let mut referrers: HashMap<ObjectId, Vec<ObjectId>> = HashMap::new();
+        for edge in &self.edges {
+            if let Some(from) = edge.from {
+                referrers.entry(edge.to).or_default().push(from);
+            }
+        }
+        let mut keep: BTreeSet<ObjectId> = self.nodes.iter()
+            .filter(|(_, node)| node.findings > 0)
+            .map(|(&id, _)| id)
+            .collect();
+        let mut queue: VecDeque<ObjectId> = keep.iter().copied().collect();
+        while let Some(id) = queue.pop_front() {
+            for &parent in referrers.get(&id).into_iter().flatten() {
+                if keep.insert(parent) {
+                    queue.push_back(parent);
+                }
+            }
+        }
+        self.nodes.retain(|id, _| keep.contains(id));
+        self.edges.retain(|edge| keep.contains(&edge.to) && edge.from.is_none_or(|from| keep.contains(&from)));
+        self
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::GraphMl => self.to_graphml(),
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph pdf {\n    node [shape=box, fontname=\"monospace\"];\n");
+        out.push_str("    \"trailer\" [shape=ellipse];\n");
+        for (id, node) in &self.nodes {
+            let mut label = format!("{} {} R\\n{}", id.0, id.1, dot_escape(&node.kind));
+            let mut style = String::new();
+            if let Some(risk) = node.risk {
+                let _ = write!(label, "\\n{:?} ×{}", risk, node.findings);
+                let _ = write!(style, ", style=filled, fillcolor=\"{}\"", risk_color(risk));
+            }
+            let _ = writeln!(out, "    \"{}\" [label=\"{}\"{}];", node_key(*id), label, style);
+        }
+        for edge in &self.edges {
+            let from = edge.from.map_or_else(|| "trailer".to_string(), node_key);
+            let _ = writeln!(out, "    \"{}\" -> \"{}\" [label=\"{}\"];", from, node_key(edge.to), dot_escape(&edge.key));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <key id=\"risk\" for=\"node\" attr.name=\"risk\" attr.type=\"string\"/>\n",
+            "  <key id=\"findings\" for=\"node\" attr.name=\"findings\" attr.type=\"int\"><default>0</default></key>\n",
+            "  <key id=\"key\" for=\"edge\" attr.name=\"key\" attr.type=\"string\"/>\n",
+            "  <graph id=\"pdf\" edgedefault=\"directed\">\n",
+            "    <node id=\"trailer\"><data key=\"kind\">trailer</data></node>\n",
+        ));
+        for (id, node) in &self.nodes {
+            let _ = write!(out, "    <node id=\"{}\"><data key=\"kind\">{}</data>", node_key(*id), xml_escape(&node.kind));
+            if let Some(risk) = node.risk {
+                let _ = write!(out, "<data key=\"risk\">{:?}</data><data key=\"findings\">{}</data>", risk, node.findings);
+            }
+            out.push_str("</node>\n");
+        }
+        for (index, edge) in self.edges.iter().enumerate() {
+            let from = edge.from.map_or_else(|| "trailer".to_string(), node_key);
+            let _ = writeln!(
+                out,
+                "    <edge id=\"e{}\" source=\"{}\" target=\"{}\"><data key=\"key\">{}</data></edge>",
+                index, from, node_key(edge.to), xml_escape(&edge.key),
+            );
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+fn node_key(id: ObjectId) -> String {
+    format!("{} {}", id.0, id.1)
+}
+
+/// `/Type` and `/Subtype` of dictionaries and streams, or the object kind
+fn kind(object: &Object) -> String {
+    let dict = match object {
+        Object::Dictionary(dict) => dict,
+        Object::Stream(stream) => &stream.dict,
+        Object::Array(_) => return "Array".to_string(),
+        Object::Reference(_) => return "Reference".to_string(),
+        Object::String(..) => return "String".to_string(),
+        Object::Name(_) => return "Name".to_string(),
+        _ => return "Value".to_string(),
+    };
+    let names: Vec<String> = [b"Type".as_slice(), b"Subtype", b"S"].iter()
+        .filter_map(|key| dict.get(key).and_then(Object::as_name_str).ok())
+        .map(str::to_string)
+        .collect();
+    match (names.is_empty(), object) {
+        (false, _) => names.join("/"),
+        (true, Object::Stream(_)) => "Stream".to_string(),
+        (true, _) => "Dictionary".to_string(),
+    }
+}
+
+fn risk_color(risk: RiskLevel) -> &'static str {
+    match risk {
+        RiskLevel::Critical => "#e06666",
+        RiskLevel::High => "#f4cccc",
+        RiskLevel::Medium => "#fce5cd",
+        RiskLevel::Low => "#fff2cc",
+        RiskLevel::None => "#eeeeee",
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// `text` escaped for XML, with characters XML 1.0 does not allow replaced
+/// by U+FFFD
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\u{0}'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}' => escaped.push(char::REPLACEMENT_CHARACTER),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_graph_marks_and_filters_risky_objects() {
+        let mut doc = Document::with_version("1.7");
+        let pages = doc.new_object_id();
+        let action = doc.add_object(dictionary! { "S" => "JavaScript", "JS" => Object::string_literal("app.alert(1)") });
+        let page = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages });
+        doc.objects.insert(pages, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page.into()], "Count" => 1 }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages, "OpenAction" => action });
+        doc.trailer.set("Root", catalog);
+
+        let artifact = ForensicArtifact {
+            location: format!("{} 0 R:/JS", action.0),
+            risk_level: RiskLevel::High,
+            ..Default::default()
+        };
+        let graph = ObjectGraph::build(&doc, &[artifact]);
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.nodes[&action].risk, Some(RiskLevel::High));
+        assert_eq!(graph.nodes[&action].kind, "JavaScript");
+        // The page's /Parent back-reference is not an edge
+        assert!(!graph.edges.iter().any(|edge| edge.from == Some(page)));
+
+        let risky = graph.risky_only();
+        assert_eq!(risky.nodes.keys().copied().collect::<Vec<_>>(), vec![action, catalog]);
+        let dot = risky.to_dot();
+        assert!(dot.contains(&format!("\"{} 0\" -> \"{} 0\" [label=\"/OpenAction\"]", catalog.0, action.0)));
+        assert!(dot.contains("\"trailer\" -> "));
+        let graphml = risky.to_graphml();
+        assert!(graphml.contains("<data key=\"risk\">High</data>"));
+        assert_eq!(graphml.matches("<edge ").count(), 2);
+    }
+
+    #[test]
+    fn test_xml_escape_replaces_illegal_characters() {
+        assert_eq!(xml_escape("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+        assert_eq!(xml_escape("tab\tline\n"), "tab\tline\n");
+        assert_eq!(xml_escape("\u{1B}[2J\u{0}\u{FFFF}"), "\u{FFFD}[2J\u{FFFD}\u{FFFD}");
+    }
+}
@@ -0,0 +1,133 @@
+//! Per-page risk heatmap
+//! Author: kartik4091
+//! Created: 2025-06-05 01:42:53 UTC
+//! Findings in a long document are listed by type and object, which does
+//! not tell a reviewer where to look. [`page_risks`] places each finding on
+//! a page, by its `page` metadata or by the page its `object_id` belongs
+//! to, and summarizes every page that has findings: counts by risk, a
+//! severity-weighted score and the most severe findings, riskiest page
+//! first. Document-level findings such as `/Info/Producer` are on no page
+//! and are left out.
+
+use std::collections::{BTreeMap, HashMap};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use crate::antiforensics::{
+    cleaner::scope::object_pages,
+    report::rollup::{self, RolledUpFinding},
+    ForensicArtifact,
+};
+
+/// Findings listed per page
+pub const MAX_TOP_FINDINGS: usize = 3;
+
+/// Risk summary of one page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PageRisk {
+    /// 1-based page number
+    pub page: u32,
+    pub total: usize,
+    /// Finding counts by risk level
+    pub by_risk: BTreeMap<String, usize>,
+    /// Sum of the findings' severities, 1 for Low to 4 for Critical
+    pub score: u32,
+    /// Most severe findings on the page, rolled up
+    pub top: Vec<RolledUpFinding>,
+    /// Rendering of the page as a PNG data URI, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+/// Page of a finding, if it is on one
+pub fn artifact_page(artifact: &ForensicArtifact, pages: &HashMap<lopdf::ObjectId, u32>) -> Option<u32> {
+    if let Some(page) = artifact.metadata.get("page").and_then(|page| page.parse().ok()) {
+        return Some(page);
+    }
+    let (number, generation) = artifact.metadata.get("object_id")?.split_once(' ')?;
+    pages.get(&(number.parse().ok()?, generation.parse().ok()?)).copied()
+}
+
+/// Pages with findings, highest score first
+pub fn page_risks(doc: &lopdf::Document, artifacts: &[ForensicArtifact]) -> Vec<PageRisk> {
+    let pages = object_pages(doc);
+    let mut by_page: BTreeMap<u32, Vec<ForensicArtifact>> = BTreeMap::new();
+    for artifact in artifacts {
+        if let Some(page) = artifact_page(artifact, &pages) {
+            by_page.entry(page).or_default().push(artifact.clone());
+        }
+    }
+
+    let mut risks: Vec<PageRisk> = by_page.into_iter()
+        .map(|(page, artifacts)| {
+            let mut by_risk = BTreeMap::new();
+            for artifact in &artifacts {
+                *by_risk.entry(format!("{:?}", artifact.risk_level)).or_default() += 1;
+            }
+            let mut top = rollup::roll_up(&artifacts);
+            top.truncate(MAX_TOP_FINDINGS);
+            PageRisk {
+                page,
+                total: artifacts.len(),
+                by_risk,
+                score: artifacts.iter().map(|artifact| u32::from(artifact.risk_level.severity())).sum(),
+                top,
+                thumbnail: None,
+            }
+        })
+        .collect();
+    risks.sort_by(|a, b| b.score.cmp(&a.score).then(a.page.cmp(&b.page)));
+    risks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Document, Object, Stream};
+    use crate::antiforensics::{ArtifactType, RiskLevel};
+
+    #[test]
+    fn test_findings_placed_and_pages_ranked() {
+        let mut doc = Document::with_version("1.7");
+        let pages = doc.new_object_id();
+        let mut kids = Vec::new();
+        let mut annotations = Vec::new();
+        for _ in 0..3 {
+            let content = doc.add_object(Stream::new(dictionary! {}, Vec::new()));
+            let annotation = doc.add_object(dictionary! { "Type" => "Annot", "Subtype" => "Link" });
+            let page = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages,
+                "Contents" => content,
+                "Annots" => vec![annotation.into()],
+            });
+            kids.push(Object::from(page));
+            annotations.push(annotation);
+        }
+        doc.objects.insert(pages, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => 3 }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+        doc.trailer.set("Root", catalog);
+
+        let artifact = |risk_level, metadata: &[(&str, String)]| ForensicArtifact {
+            artifact_type: ArtifactType::JavaScript,
+            risk_level,
+            description: format!("{:?} finding", risk_level),
+            metadata: metadata.iter().map(|(k, v)| (k.to_string(), v.clone())).collect(),
+            ..Default::default()
+        };
+        let object = |id: lopdf::ObjectId| format!("{} {}", id.0, id.1);
+        let artifacts = [
+            artifact(RiskLevel::Low, &[("object_id", object(annotations[0]))]),
+            artifact(RiskLevel::Critical, &[("object_id", object(annotations[2]))]),
+            artifact(RiskLevel::Medium, &[("page", "3".into())]),
+            artifact(RiskLevel::High, &[("object_id", object(catalog))]),
+            artifact(RiskLevel::High, &[]),
+        ];
+
+        let risks = page_risks(&doc, &artifacts);
+        let ranked: Vec<(u32, usize, u32)> = risks.iter().map(|risk| (risk.page, risk.total, risk.score)).collect();
+        assert_eq!(ranked, vec![(3, 2, 6), (1, 1, 1)]);
+        assert_eq!(risks[0].by_risk.get("Critical"), Some(&1));
+        assert_eq!(risks[0].top[0].risk_level, "Critical");
+    }
+}
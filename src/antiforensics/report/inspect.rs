@@ -0,0 +1,326 @@
+//! Human-readable views of single objects
+//! Author: kartik4091
+//! Created: 2025-06-05 00:27:33 UTC
+//! Analysts often need to look at one object, the trailer or what a page
+//! actually draws without reaching for a hex editor. [`Inspector`] prints
+//! any of them in indented PDF syntax, with strings decoded and stream
+//! data run through its filters; text is printed as is and binary data
+//! as a hex dump.
+
+use std::fmt::Write as _;
+use std::str::FromStr;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::antiforensics::scanner::unicode_spoof::decode_pdf_string;
+
+/// Binary stream bytes shown in a hex dump before the rest are counted
+const HEX_DUMP_BYTES: usize = 512;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InspectError {
+    #[error("unknown target `{0}`; expected trailer, catalog, an object number like `12` or `12 0 R`, or page:N")]
+    InvalidTarget(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("{0}: {1}")]
+    Pdf(String, lopdf::Error),
+}
+
+/// Something `Inspector::show` can print
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InspectTarget {
+    Trailer,
+    Catalog,
+    Object(ObjectId),
+    /// Decoded content of a page, numbered from 1
+    Page(u32),
+}
+
+impl FromStr for InspectTarget {
+    type Err = InspectError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InspectError::InvalidTarget(s.to_string());
+        let target = s.trim();
+        match target.to_ascii_lowercase().as_str() {
+            "trailer" => return Ok(Self::Trailer),
+            "catalog" | "root" => return Ok(Self::Catalog),
+            _ => {}
+        }
+        if let Some(number) = target.strip_prefix("page:").or_else(|| target.strip_prefix("page ")) {
+            return number.trim().parse().ok().filter(|&n| n > 0).map(Self::Page).ok_or_else(invalid);
+        }
+
+        let parts: Vec<&str> = target.split_whitespace().collect();
+        let parts = match parts.as_slice() {
+            [rest @ .., "R"] => rest,
+            all => all,
+        };
+        match parts {
+            [number] => Ok(Self::Object((number.parse().map_err(|_| invalid())?, 0))),
+            [number, generation] => Ok(Self::Object((
+                number.parse().map_err(|_| invalid())?,
+                generation.parse().map_err(|_| invalid())?,
+            ))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Prints objects of one document
+pub struct Inspector<'a> {
+    doc: &'a Document,
+    /// Print stream data as stored instead of decoded
+    raw: bool,
+}
+
+impl<'a> Inspector<'a> {
+    pub fn new(doc: &'a Document) -> Self {
+        Self { doc, raw: false }
+    }
+
+    pub fn raw(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    pub fn show(&self, target: InspectTarget) -> Result<String, InspectError> {
+        match target {
+            InspectTarget::Trailer => {
+                let mut out = String::from("trailer\n");
+                write_dictionary(&mut out, &self.doc.trailer, 0);
+                out.push('\n');
+                Ok(out)
+            }
+            InspectTarget::Catalog => {
+                let root = self.doc.trailer.get(b"Root").and_then(Object::as_reference)
+                    .map_err(|e| InspectError::Pdf("trailer /Root".into(), e))?;
+                self.show(InspectTarget::Object(root))
+            }
+            InspectTarget::Object(id) => {
+                let object = self.doc.objects.get(&id)
+                    .ok_or_else(|| InspectError::NotFound(format!("object {} {} R", id.0, id.1)))?;
+                let mut out = format!("{} {} obj\n", id.0, id.1);
+                write_object(&mut out, object, 0);
+                out.push('\n');
+                if let Object::Stream(stream) = object {
+                    self.write_stream(&mut out, stream);
+                }
+                out.push_str("endobj\n");
+                Ok(out)
+            }
+            InspectTarget::Page(number) => {
+                let page_id = *self.doc.get_pages().get(&number)
+                    .ok_or_else(|| InspectError::NotFound(format!("page {}", number)))?;
+                let content = self.doc.get_page_content(page_id)
+                    .map_err(|e| InspectError::Pdf(format!("page {} content", number), e))?;
+                let streams: Vec<String> = self.doc.get_page_contents(page_id).iter()
+                    .map(|(num, generation)| format!("{} {} R", num, generation))
+                    .collect();
+                let mut out = format!(
+                    "page {} ({} {} R), content from {}, {} bytes decoded\n",
+                    number, page_id.0, page_id.1, streams.join(", "), content.len(),
+                );
+                write_data(&mut out, &content);
+                Ok(out)
+            }
+        }
+    }
+
+    fn write_stream(&self, out: &mut String, stream: &lopdf::Stream) {
+        let filters = stream.filters().unwrap_or_default().iter()
+            .map(|filter| escape_name(filter.as_bytes()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let decoded = match (self.raw, stream.dict.has(b"Filter")) {
+            (false, true) => stream.decompressed_content().ok(),
+            _ => None,
+        };
+        match &decoded {
+            Some(data) => {
+                let _ = writeln!(out, "stream ({} bytes, {}; {} decoded)", stream.content.len(), filters, data.len());
+            }
+            None if !filters.is_empty() && !self.raw => {
+                let _ = writeln!(out, "stream ({} bytes, {}; not decodable, shown as stored)", stream.content.len(), filters);
+            }
+            None => {
+                let _ = writeln!(out, "stream ({} bytes as stored)", stream.content.len());
+            }
+        }
+        write_data(out, decoded.as_deref().unwrap_or(&stream.content));
+        out.push_str("endstream\n");
+    }
+}
+
+fn is_text(data: &[u8]) -> bool {
+    std::str::from_utf8(data).is_ok_and(|text| text.chars().all(|c| !c.is_control() || c.is_whitespace()))
+}
+
+/// Text as is, anything else as a hex dump of its leading bytes
+fn write_data(out: &mut String, data: &[u8]) {
+    if is_text(data) {
+        out.push_str(&String::from_utf8_lossy(data));
+        if !data.ends_with(b"\n") {
+            out.push('\n');
+        }
+        return;
+    }
+    for (row, chunk) in data[..data.len().min(HEX_DUMP_BYTES)].chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk.iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        let _ = writeln!(out, "{:08x}  {:<47}  |{}|", row * 16, hex.join(" "), ascii);
+    }
+    if data.len() > HEX_DUMP_BYTES {
+        let _ = writeln!(out, "… {} more bytes", data.len() - HEX_DUMP_BYTES);
+    }
+}
+
+fn indent(out: &mut String, level: usize) {
+    out.push_str(&"  ".repeat(level));
+}
+
+fn is_scalar(object: &Object) -> bool {
+    !matches!(object, Object::Array(_) | Object::Dictionary(_) | Object::Stream(_))
+}
+
+/// Writes an object in PDF syntax, breaking dictionaries and nested arrays over lines
+fn write_object(out: &mut String, object: &Object, level: usize) {
+    match object {
+        Object::Null => out.push_str("null"),
+        Object::Boolean(value) => out.push_str(&value.to_string()),
+        Object::Integer(value) => out.push_str(&value.to_string()),
+        Object::Real(value) => out.push_str(&value.to_string()),
+        Object::Name(name) => write_name(out, name),
+        Object::String(bytes, _) => {
+            let text = decode_pdf_string(bytes);
+            if text.chars().all(|c| !c.is_control() || c == ' ') {
+                let _ = write!(out, "({})", text.replace('\\', "\\\\").replace('(', "\\(").replace(')', "\\)"));
+            } else {
+                let _ = write!(out, "<{}>", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>());
+            }
+        }
+        Object::Reference((number, generation)) => {
+            let _ = write!(out, "{} {} R", number, generation);
+        }
+        Object::Array(items) if items.iter().all(is_scalar) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_object(out, item, level);
+            }
+            out.push(']');
+        }
+        Object::Array(items) => {
+            out.push_str("[\n");
+            for item in items {
+                indent(out, level + 1);
+                write_object(out, item, level + 1);
+                out.push('\n');
+            }
+            indent(out, level);
+            out.push(']');
+        }
+        Object::Dictionary(dict) => write_dictionary(out, dict, level),
+        Object::Stream(stream) => write_dictionary(out, &stream.dict, level),
+    }
+}
+
+fn write_name(out: &mut String, name: &[u8]) {
+    out.push('/');
+    out.push_str(&escape_name(name));
+}
+
+/// A name in PDF syntax, `#xx`-escaping everything but printable ASCII,
+/// so names never carry control characters to the terminal
+fn escape_name(name: &[u8]) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for &b in name {
+        if (0x21..=0x7E).contains(&b) && !b"#()<>[]{}/%".contains(&b) {
+            escaped.push(b as char);
+        } else {
+            let _ = write!(escaped, "#{:02X}", b);
+        }
+    }
+    escaped
+}
+
+fn write_dictionary(out: &mut String, dict: &Dictionary, level: usize) {
+    if dict.is_empty() {
+        out.push_str("<< >>");
+        return;
+    }
+    out.push_str("<<\n");
+    for (key, value) in dict.iter() {
+        indent(out, level + 1);
+        write_name(out, key);
+        out.push(' ');
+        write_object(out, value, level + 1);
+        out.push('\n');
+    }
+    indent(out, level);
+    out.push_str(">>");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn test_targets_shown_decoded() {
+        assert_eq!("12".parse::<InspectTarget>().unwrap(), InspectTarget::Object((12, 0)));
+        assert_eq!("12 3 R".parse::<InspectTarget>().unwrap(), InspectTarget::Object((12, 3)));
+        assert_eq!("page:2".parse::<InspectTarget>().unwrap(), InspectTarget::Page(2));
+        assert_eq!(" Trailer ".parse::<InspectTarget>().unwrap(), InspectTarget::Trailer);
+        assert!("page:0".parse::<InspectTarget>().is_err());
+        assert!("12 x".parse::<InspectTarget>().is_err());
+
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, b"BT /F1 12 Tf (Hello) Tj ET\n").unwrap();
+        let content_id = doc.add_object(Stream::new(dictionary! { "Filter" => "FlateDecode" }, encoder.finish().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+
+        let inspector = Inspector::new(&doc);
+        let page = inspector.show(InspectTarget::Object(page_id)).unwrap();
+        assert!(page.contains("  /MediaBox [0 0 612 792]\n"), "{}", page);
+        assert!(page.starts_with(&format!("{} 0 obj\n<<\n", page_id.0)));
+
+        let stream = inspector.show(InspectTarget::Object(content_id)).unwrap();
+        assert!(stream.contains("FlateDecode; 27 decoded)\nBT /F1 12 Tf (Hello) Tj ET\nendstream\n"), "{}", stream);
+        let raw = Inspector::new(&doc).raw(true).show(InspectTarget::Object(content_id)).unwrap();
+        assert!(raw.contains("as stored)\n00000000  78"), "{}", raw);
+
+        assert!(inspector.show(InspectTarget::Page(1)).unwrap().ends_with("(Hello) Tj ET\n"));
+        assert!(inspector.show(InspectTarget::Catalog).unwrap().contains("/Type /Catalog"));
+        assert!(matches!(inspector.show(InspectTarget::Page(2)), Err(InspectError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_names_escaped() {
+        let mut doc = Document::with_version("1.7");
+        let id = doc.add_object(dictionary! {
+            "\x1b[2J" => Object::Name(b"A B#\x1b\xff".to_vec()),
+        });
+        let shown = Inspector::new(&doc).show(InspectTarget::Object(id)).unwrap();
+        assert!(shown.contains("  /#1B#5B2J /A#20B#23#1B#FF\n"), "{}", shown);
+        assert!(!shown.contains('\x1b'));
+    }
+}
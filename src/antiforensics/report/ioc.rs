@@ -0,0 +1,442 @@
+//! Threat-intel indicator export for detected artifacts
+//! Author: kartik4091
+//! Created: 2025-06-04 09:12:40 UTC
+//! This module extracts indicators of compromise (URLs, domains, IPs and
+//! file hashes) from scan artifacts and emits them as STIX 2.1 bundles or
+//! MISP-compatible event JSON for ingestion into TI platforms.
+
+use std::collections::{BTreeMap, HashSet};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use tracing::{debug, instrument};
+
+use crate::antiforensics::{ArtifactType, ForensicArtifact, RiskLevel};
+
+/// Metadata keys that may carry a hash of an embedded file
+const EMBEDDED_HASH_KEYS: [(&str, IndicatorKind); 3] = [
+    ("sha256", IndicatorKind::Sha256),
+    ("sha1", IndicatorKind::Sha1),
+    ("md5", IndicatorKind::Md5),
+];
+
+/// Kind of extracted indicator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum IndicatorKind {
+    Url,
+    Domain,
+    Ipv4,
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+/// Indicator extracted from one or more artifacts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Indicator {
+    /// Indicator kind
+    pub kind: IndicatorKind,
+    /// Normalized indicator value
+    pub value: String,
+    /// Highest risk level among source artifacts
+    pub risk_level: RiskLevel,
+    /// IDs of the artifacts the indicator was extracted from
+    pub source_artifacts: Vec<String>,
+    /// Embedded file name, when the indicator is a file hash
+    pub file_name: Option<String>,
+}
+
+/// Output format for indicator export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IocFormat {
+    /// STIX 2.1 bundle
+    Stix21,
+    /// MISP event JSON
+    Misp,
+}
+
+/// Indicator export configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IocExportConfig {
+    /// Output format
+    pub format: IocFormat,
+    /// Minimum artifact risk level to export
+    pub min_risk_level: RiskLevel,
+    /// Name of the producing identity in STIX output
+    pub identity_name: String,
+    /// Description used for the MISP event / STIX report
+    pub event_info: String,
+    /// Domains never exported as indicators
+    pub domain_allowlist: HashSet<String>,
+}
+
+/// Custom error type for indicator export
+#[derive(Debug, thiserror::Error)]
+pub enum IocError {
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Invalid pattern: {0}")]
+    Pattern(#[from] regex::Error),
+}
+
+/// Result type alias for indicator export
+pub type Result<T> = std::result::Result<T, IocError>;
+
+/// Extracts indicators from artifacts and serializes them
+pub struct IocExporter {
+    /// Export configuration
+    config: IocExportConfig,
+    /// URL matcher
+    url_regex: Regex,
+    /// IPv4 matcher
+    ipv4_regex: Regex,
+}
+
+impl IocExporter {
+    /// Creates a new exporter
+    pub fn new(config: IocExportConfig) -> Result<Self> {
+        Ok(Self {
+            config,
+            url_regex: Regex::new(r#"(?i)\b(?:https?|ftp)://[^\s<>"'()\[\]{}]+"#)?,
+            ipv4_regex: Regex::new(r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b")?,
+        })
+    }
+
+    /// Extracts deduplicated indicators from the given artifacts
+    #[instrument(skip(self, artifacts))]
+    pub fn extract(&self, artifacts: &[ForensicArtifact]) -> Vec<Indicator> {
+        let mut indicators: BTreeMap<(IndicatorKind, String), Indicator> = BTreeMap::new();
+
+        for artifact in artifacts {
+            if artifact.risk_level.severity() < self.config.min_risk_level.severity() {
+                continue;
+            }
+
+            let mut found = Vec::new();
+            for text in Self::searchable_text(artifact) {
+                for m in self.url_regex.find_iter(text) {
+                    let url = m.as_str().trim_end_matches(|c| c == '.' || c == ',' || c == ';');
+                    found.push((IndicatorKind::Url, url.to_string(), None));
+                    if let Some(host) = Self::url_host(url) {
+                        if self.ipv4_regex.is_match(&host) {
+                            found.push((IndicatorKind::Ipv4, host, None));
+                        } else if !self.config.domain_allowlist.contains(&host) {
+                            found.push((IndicatorKind::Domain, host, None));
+                        }
+                    }
+                }
+                for m in self.ipv4_regex.find_iter(text) {
+                    found.push((IndicatorKind::Ipv4, m.as_str().to_string(), None));
+                }
+            }
+
+            if artifact.artifact_type == ArtifactType::EmbeddedFile {
+                let file_name = artifact.metadata.get("filename").cloned();
+                for (key, kind) in EMBEDDED_HASH_KEYS.iter() {
+                    if let Some(hash) = artifact.metadata.get(*key) {
+                        found.push((*kind, hash.to_lowercase(), file_name.clone()));
+                    }
+                }
+                if !artifact.hash.is_empty() && !artifact.metadata.contains_key("sha256") {
+                    found.push((IndicatorKind::Sha256, artifact.hash.to_lowercase(), file_name));
+                }
+            }
+
+            for (kind, value, file_name) in found {
+                let entry = indicators.entry((kind, value.clone())).or_insert_with(|| Indicator {
+                    kind,
+                    value,
+                    risk_level: artifact.risk_level,
+                    source_artifacts: Vec::new(),
+                    file_name: file_name.clone(),
+                });
+                if artifact.risk_level.severity() > entry.risk_level.severity() {
+                    entry.risk_level = artifact.risk_level;
+                }
+                if !entry.source_artifacts.contains(&artifact.id) {
+                    entry.source_artifacts.push(artifact.id.clone());
+                }
+                if entry.file_name.is_none() {
+                    entry.file_name = file_name;
+                }
+            }
+        }
+
+        debug!("Extracted {} indicators from {} artifacts", indicators.len(), artifacts.len());
+        indicators.into_values().collect()
+    }
+
+    /// Extracts indicators and serializes them in the configured format
+    pub fn export(&self, artifacts: &[ForensicArtifact]) -> Result<String> {
+        let indicators = self.extract(artifacts);
+        let value = match self.config.format {
+            IocFormat::Stix21 => self.to_stix_bundle(&indicators),
+            IocFormat::Misp => self.to_misp_event(&indicators),
+        };
+        Ok(serde_json::to_string_pretty(&value)?)
+    }
+
+    /// Builds a STIX 2.1 bundle from indicators
+    pub fn to_stix_bundle(&self, indicators: &[Indicator]) -> Value {
+        let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        let identity_id = format!("identity--{}", uuid::Uuid::new_v4());
+
+        let mut objects = vec![json!({
+            "type": "identity",
+            "spec_version": "2.1",
+            "id": identity_id,
+            "created": now,
+            "modified": now,
+            "name": self.config.identity_name,
+            "identity_class": "system",
+        })];
+
+        for indicator in indicators {
+            objects.push(json!({
+                "type": "indicator",
+                "spec_version": "2.1",
+                "id": format!("indicator--{}", uuid::Uuid::new_v4()),
+                "created_by_ref": identity_id,
+                "created": now,
+                "modified": now,
+                "name": format!("{:?}: {}", indicator.kind, indicator.value),
+                "description": self.config.event_info,
+                "indicator_types": ["malicious-activity"],
+                "pattern": Self::stix_pattern(indicator),
+                "pattern_type": "stix",
+                "valid_from": now,
+                "confidence": stix_confidence(indicator.risk_level),
+                "x_source_artifacts": indicator.source_artifacts,
+            }));
+        }
+
+        json!({
+            "type": "bundle",
+            "id": format!("bundle--{}", uuid::Uuid::new_v4()),
+            "objects": objects,
+        })
+    }
+
+    /// Builds a MISP event from indicators
+    pub fn to_misp_event(&self, indicators: &[Indicator]) -> Value {
+        let highest = indicators.iter()
+            .map(|i| i.risk_level)
+            .max_by_key(|level| level.severity())
+            .unwrap_or(RiskLevel::None);
+
+        let attributes: Vec<Value> = indicators.iter().map(|indicator| {
+            let (attr_type, category, value) = match (indicator.kind, &indicator.file_name) {
+                (IndicatorKind::Url, _) => ("url", "Network activity", indicator.value.clone()),
+                (IndicatorKind::Domain, _) => ("domain", "Network activity", indicator.value.clone()),
+                (IndicatorKind::Ipv4, _) => ("ip-dst", "Network activity", indicator.value.clone()),
+                (IndicatorKind::Md5, Some(name)) => ("filename|md5", "Payload delivery", format!("{}|{}", name, indicator.value)),
+                (IndicatorKind::Sha1, Some(name)) => ("filename|sha1", "Payload delivery", format!("{}|{}", name, indicator.value)),
+                (IndicatorKind::Sha256, Some(name)) => ("filename|sha256", "Payload delivery", format!("{}|{}", name, indicator.value)),
+                (IndicatorKind::Md5, None) => ("md5", "Payload delivery", indicator.value.clone()),
+                (IndicatorKind::Sha1, None) => ("sha1", "Payload delivery", indicator.value.clone()),
+                (IndicatorKind::Sha256, None) => ("sha256", "Payload delivery", indicator.value.clone()),
+            };
+
+            json!({
+                "uuid": uuid::Uuid::new_v4().to_string(),
+                "type": attr_type,
+                "category": category,
+                "value": value,
+                "to_ids": indicator.risk_level.severity() >= RiskLevel::High.severity(),
+                "comment": format!("Source artifacts: {}", indicator.source_artifacts.join(", ")),
+            })
+        }).collect();
+
+        json!({
+            "Event": {
+                "uuid": uuid::Uuid::new_v4().to_string(),
+                "info": self.config.event_info,
+                "date": chrono::Utc::now().format("%Y-%m-%d").to_string(),
+                "threat_level_id": misp_threat_level(highest),
+                "analysis": "0",
+                "distribution": "0",
+                "Attribute": attributes,
+            }
+        })
+    }
+
+    /// Returns the artifact fields that may contain indicators
+    fn searchable_text(artifact: &ForensicArtifact) -> Vec<&str> {
+        let mut texts = vec![artifact.location.as_str(), artifact.description.as_str()];
+        if let Some(value) = artifact.metadata.get("value") {
+            texts.push(value.as_str());
+        }
+        texts
+    }
+
+    /// Extracts the lowercase host portion of a URL
+    fn url_host(url: &str) -> Option<String> {
+        let rest = url.split_once("://")?.1;
+        let authority = rest.split(|c| c == '/' || c == '?' || c == '#').next()?;
+        let host = authority.rsplit('@').next()?.split(':').next()?;
+        if host.is_empty() {
+            None
+        } else {
+            Some(host.to_lowercase())
+        }
+    }
+
+    /// Builds a STIX pattern expression for an indicator
+    fn stix_pattern(indicator: &Indicator) -> String {
+        let value = indicator.value.replace('\\', "\\\\").replace('\'', "\\'");
+        match indicator.kind {
+            IndicatorKind::Url => format!("[url:value = '{}']", value),
+            IndicatorKind::Domain => format!("[domain-name:value = '{}']", value),
+            IndicatorKind::Ipv4 => format!("[ipv4-addr:value = '{}']", value),
+            IndicatorKind::Md5 => format!("[file:hashes.MD5 = '{}']", value),
+            IndicatorKind::Sha1 => format!("[file:hashes.'SHA-1' = '{}']", value),
+            IndicatorKind::Sha256 => format!("[file:hashes.'SHA-256' = '{}']", value),
+        }
+    }
+}
+
+/// Maps a risk level to a STIX confidence value
+fn stix_confidence(level: RiskLevel) -> u8 {
+    match level {
+        RiskLevel::Critical => 95,
+        RiskLevel::High => 85,
+        RiskLevel::Medium => 60,
+        RiskLevel::Low => 30,
+        RiskLevel::None => 10,
+    }
+}
+
+/// Maps a risk level to a MISP threat level id (1 = high, 4 = undefined)
+fn misp_threat_level(level: RiskLevel) -> &'static str {
+    match level {
+        RiskLevel::Critical | RiskLevel::High => "1",
+        RiskLevel::Medium => "2",
+        RiskLevel::Low => "3",
+        RiskLevel::None => "4",
+    }
+}
+
+impl Default for IocExportConfig {
+    fn default() -> Self {
+        Self {
+            format: IocFormat::Stix21,
+            min_risk_level: RiskLevel::Low,
+            identity_name: "pdf_engine".into(),
+            event_info: "Indicators extracted from PDF forensic scan".into(),
+            domain_allowlist: ["www.w3.org", "ns.adobe.com", "purl.org"].iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn url_artifact() -> ForensicArtifact {
+        ForensicArtifact {
+            id: "A1".into(),
+            artifact_type: ArtifactType::Content,
+            location: "3 0 R:/URI".into(),
+            description: "URL found in string".into(),
+            risk_level: RiskLevel::Medium,
+            metadata: {
+                let mut m = HashMap::new();
+                m.insert("value".into(), "https://evil.example.com/payload?id=1".into());
+                m
+            },
+            ..Default::default()
+        }
+    }
+
+    fn embedded_artifact() -> ForensicArtifact {
+        ForensicArtifact {
+            id: "A2".into(),
+            artifact_type: ArtifactType::EmbeddedFile,
+            risk_level: RiskLevel::Critical,
+            hash: "ABCDEF".into(),
+            metadata: {
+                let mut m = HashMap::new();
+                m.insert("filename".into(), "invoice.exe".into());
+                m
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_extracts_url_and_domain() {
+        let exporter = IocExporter::new(IocExportConfig::default()).unwrap();
+        let indicators = exporter.extract(&[url_artifact()]);
+
+        assert!(indicators.iter().any(|i| i.kind == IndicatorKind::Url
+            && i.value == "https://evil.example.com/payload?id=1"));
+        assert!(indicators.iter().any(|i| i.kind == IndicatorKind::Domain
+            && i.value == "evil.example.com"));
+    }
+
+    #[test]
+    fn test_deduplicates_across_artifacts() {
+        let exporter = IocExporter::new(IocExportConfig::default()).unwrap();
+        let mut second = url_artifact();
+        second.id = "A3".into();
+        second.risk_level = RiskLevel::High;
+
+        let indicators = exporter.extract(&[url_artifact(), second]);
+        let url = indicators.iter().find(|i| i.kind == IndicatorKind::Url).unwrap();
+        assert_eq!(url.source_artifacts, vec!["A1".to_string(), "A3".to_string()]);
+        assert_eq!(url.risk_level, RiskLevel::High);
+    }
+
+    #[test]
+    fn test_embedded_file_hash() {
+        let exporter = IocExporter::new(IocExportConfig::default()).unwrap();
+        let indicators = exporter.extract(&[embedded_artifact()]);
+
+        assert_eq!(indicators.len(), 1);
+        assert_eq!(indicators[0].kind, IndicatorKind::Sha256);
+        assert_eq!(indicators[0].value, "abcdef");
+        assert_eq!(indicators[0].file_name.as_deref(), Some("invoice.exe"));
+    }
+
+    #[test]
+    fn test_stix_bundle_shape() {
+        let exporter = IocExporter::new(IocExportConfig::default()).unwrap();
+        let indicators = exporter.extract(&[embedded_artifact()]);
+        let bundle = exporter.to_stix_bundle(&indicators);
+
+        assert_eq!(bundle["type"], "bundle");
+        let objects = bundle["objects"].as_array().unwrap();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[1]["pattern"], "[file:hashes.'SHA-256' = 'abcdef']");
+    }
+
+    #[test]
+    fn test_misp_event_shape() {
+        let config = IocExportConfig {
+            format: IocFormat::Misp,
+            ..Default::default()
+        };
+        let exporter = IocExporter::new(config).unwrap();
+        let json = exporter.export(&[embedded_artifact()]).unwrap();
+        let event: Value = serde_json::from_str(&json).unwrap();
+
+        let attribute = &event["Event"]["Attribute"][0];
+        assert_eq!(attribute["type"], "filename|sha256");
+        assert_eq!(attribute["value"], "invoice.exe|abcdef");
+        assert_eq!(attribute["to_ids"], true);
+    }
+
+    #[test]
+    fn test_min_risk_filter() {
+        let config = IocExportConfig {
+            min_risk_level: RiskLevel::High,
+            ..Default::default()
+        };
+        let exporter = IocExporter::new(config).unwrap();
+        assert!(exporter.extract(&[url_artifact()]).is_empty());
+    }
+}
@@ -2,3 +2,34 @@
 // Author: kartik4091
 // Created: 2025-06-03 08:00:41 UTC
 
+
+pub mod accessibility;
+pub mod byte_ranges;
+pub mod encrypted;
+pub mod evidence;
+pub mod explain;
+pub mod graph;
+pub mod heatmap;
+pub mod inspect;
+pub mod ioc;
+pub mod object_map;
+pub mod provenance;
+pub mod replay;
+pub mod rollup;
+pub mod schema;
+pub mod sidecar;
+
+pub use self::accessibility::{AccessibilityImpact, AccessibilityProfile};
+pub use self::byte_ranges::{ByteMap, ByteRange, InputLocation};
+pub use self::encrypted::{ReportCryptoError, ReportEncryption, ReportKey};
+pub use self::evidence::{EvidenceEntry, EvidenceLog, EvidenceSignature, Snapshot};
+pub use self::explain::{EvidenceBundle, ExplainConfig, ExplainedFinding, Explainer};
+pub use self::graph::{GraphEdge, GraphFormat, GraphNode, ObjectGraph};
+pub use self::heatmap::PageRisk;
+pub use self::inspect::{InspectError, InspectTarget, Inspector};
+pub use self::provenance::{ProvenanceRecord, ProvenanceStatus, ProvenanceTemplate};
+pub use self::replay::{ReplayDiff, ReplayRecord, StageDiff, StageRecord};
+pub use self::object_map::{ObjectMap, ObjectMapping};
+pub use self::ioc::{Indicator, IndicatorKind, IocExporter, IocExportConfig, IocFormat};
+pub use self::rollup::RolledUpFinding;
+pub use self::sidecar::{PageThumbnail, ScanSummary, SidecarReport};
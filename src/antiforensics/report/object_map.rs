@@ -0,0 +1,170 @@
+//! Object provenance
+//! Author: kartik4091
+//! Created: 2025-06-05 01:53:16 UTC
+//! Findings name objects by their number in the input (`12 0 R:/JS`), but
+//! cleaning removes objects, renumbering gives the rest new numbers and
+//! deduplication folds identical ones together, so the same number in the
+//! output is usually a different object. An [`ObjectMap`] follows every
+//! input object through those steps, and translates object IDs and
+//! artifact locations between the input and output documents.
+
+use std::collections::BTreeMap;
+use lopdf::{Document, ObjectId};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+/// Where one input object ended up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ObjectMapping {
+    /// Input object, as `12 0 R`
+    pub input: String,
+    /// Output object it became, absent when it was removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// Input object IDs and the output objects they became
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectMap {
+    /// Current ID of every input object; `None` once removed
+    objects: BTreeMap<ObjectId, Option<ObjectId>>,
+}
+
+impl ObjectMap {
+    /// Map of a freshly loaded document, where every object is itself
+    pub fn identity(doc: &Document) -> Self {
+        Self { objects: doc.objects.keys().map(|&id| (id, Some(id))).collect() }
+    }
+
+    /// Marks input objects no longer in `doc` as removed
+    ///
+    /// Call before renumbering, while removed numbers cannot yet have been
+    /// given to other objects.
+    pub fn drop_missing(&mut self, doc: &Document) {
+        for current in self.objects.values_mut() {
+            if current.is_some_and(|id| !doc.objects.contains_key(&id)) {
+                *current = None;
+            }
+        }
+    }
+
+    /// Follows a renumbering, given as old to new ID of every object that moved
+    pub fn renumber(&mut self, mapping: &BTreeMap<ObjectId, ObjectId>) {
+        for current in self.objects.values_mut().flatten() {
+            if let Some(&new) = mapping.get(current) {
+                *current = new;
+            }
+        }
+    }
+
+    /// Follows a deduplication, given as merged object to the one kept in its place
+    pub fn merge(&mut self, merged: &BTreeMap<ObjectId, ObjectId>) {
+        self.renumber(merged);
+    }
+
+    /// Follows a later step that took this map's output as its input
+    pub fn then(&mut self, later: &ObjectMap) {
+        for current in self.objects.values_mut() {
+            *current = current.and_then(|id| later.output_of(id));
+        }
+    }
+
+    /// Output object an input object became
+    pub fn output_of(&self, input: ObjectId) -> Option<ObjectId> {
+        self.objects.get(&input).copied().flatten()
+    }
+
+    /// Input objects an output object derives from: one, several after a
+    /// merge, or none for objects cleaning added
+    pub fn inputs_of(&self, output: ObjectId) -> Vec<ObjectId> {
+        self.objects.iter()
+            .filter(|&(_, &current)| current == Some(output))
+            .map(|(&input, _)| input)
+            .collect()
+    }
+
+    /// An input artifact location such as `12 0 R:/OpenAction` in output
+    /// numbering; `None` when the object was removed
+    ///
+    /// Locations that do not start with an object reference, like
+    /// `/Info/Producer`, are the same in both documents.
+    pub fn translate_location(&self, location: &str) -> Option<String> {
+        let Some((id, rest)) = parse_reference(location) else { return Some(location.to_string()) };
+        let (number, generation) = self.output_of(id)?;
+        Some(format!("{} {} R{}", number, generation, rest))
+    }
+
+    /// Every input object in number order, for reports
+    pub fn mappings(&self) -> Vec<ObjectMapping> {
+        self.objects.iter()
+            .map(|(&input, &output)| ObjectMapping { input: format_id(input), output: output.map(format_id) })
+            .collect()
+    }
+}
+
+fn format_id((number, generation): ObjectId) -> String {
+    format!("{} {} R", number, generation)
+}
+
+/// `N G R` at the start of `location`, and what follows it
+fn parse_reference(location: &str) -> Option<(ObjectId, &str)> {
+    let mut parts = location.splitn(3, ' ');
+    let number = parts.next()?.parse().ok()?;
+    let generation = parts.next()?.parse().ok()?;
+    let rest = parts.next()?.strip_prefix('R')?;
+    Some(((number, generation), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Object};
+
+    #[test]
+    fn test_objects_followed_through_removal_renumbering_and_merges() {
+        let mut doc = Document::with_version("1.7");
+        for _ in 0..4 {
+            doc.add_object(dictionary! { "Type" => "Font" });
+        }
+        doc.objects.insert((9, 2), Object::Null);
+        let mut map = ObjectMap::identity(&doc);
+
+        // 2 is cleaned away, then 9 2 R becomes 2 0 R and 3, 4 shift down
+        doc.objects.remove(&(2, 0));
+        map.drop_missing(&doc);
+        map.renumber(&BTreeMap::from([((9, 2), (2, 0)), ((3, 0), (4, 0)), ((4, 0), (3, 0))]));
+        // Deduplication folds 4 0 R into 1 0 R
+        map.merge(&BTreeMap::from([((4, 0), (1, 0))]));
+
+        assert_eq!(map.output_of((2, 0)), None);
+        assert_eq!(map.output_of((9, 2)), Some((2, 0)));
+        assert_eq!(map.output_of((4, 0)), Some((3, 0)));
+        assert_eq!(map.inputs_of((1, 0)), vec![(1, 0), (3, 0)]);
+        assert!(map.inputs_of((7, 0)).is_empty());
+
+        assert_eq!(map.translate_location("9 2 R:/OpenAction").as_deref(), Some("2 0 R:/OpenAction"));
+        assert_eq!(map.translate_location("2 0 R:/JS"), None);
+        assert_eq!(map.translate_location("/Info/Producer").as_deref(), Some("/Info/Producer"));
+        assert_eq!(map.mappings()[1], ObjectMapping { input: "2 0 R".into(), output: None });
+    }
+
+    #[test]
+    fn test_maps_chain_across_steps() {
+        let mut doc = Document::with_version("1.7");
+        for _ in 0..3 {
+            doc.add_object(dictionary! { "Type" => "Font" });
+        }
+        let mut first = ObjectMap::identity(&doc);
+        first.merge(&BTreeMap::from([((3, 0), (2, 0))]));
+        doc.objects.remove(&(3, 0));
+
+        let mut second = ObjectMap::identity(&doc);
+        doc.objects.remove(&(1, 0));
+        second.drop_missing(&doc);
+        first.then(&second);
+
+        assert_eq!(first.output_of((1, 0)), None);
+        assert_eq!(first.output_of((3, 0)), Some((2, 0)));
+        assert_eq!(first.inputs_of((2, 0)), vec![(2, 0), (3, 0)]);
+    }
+}
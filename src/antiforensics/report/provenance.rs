@@ -0,0 +1,268 @@
+//! Embedded provenance records
+//! Author: kartik4091
+//! Created: 2025-06-04 21:38:52 UTC
+//! A processed document can carry a small JSON record of how it was
+//! produced: tool and version, cleaning policy, when, and a digest of the
+//! content as it left the cleaner. The record lives in a stream referenced
+//! from the catalog's page-piece dictionary (`/PieceInfo /PdfEngine
+//! /Private`), the place the specification reserves for private
+//! application data, so readers that do not know it ignore it.
+//!
+//! The digest covers every object except the record itself and the
+//! catalog entry pointing at it, so [`verify`] can tell whether a stamped
+//! document was edited afterwards. The trailer, and with it the document
+//! ID, is not covered; stamp after renumbering and before encryption.
+
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use lopdf::{dictionary, Dictionary, Object, ObjectId, Stream};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use sha2::{Digest, Sha256};
+
+use crate::antiforensics::hash::object_digest;
+use crate::pdf_date::PdfDate;
+
+/// Record format version, bumped on incompatible changes
+pub const PROVENANCE_FORMAT_VERSION: u32 = 1;
+
+/// Key of this tool's data in the catalog's `/PieceInfo`
+pub const PIECE_INFO_KEY: &str = "PdfEngine";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProvenanceError {
+    #[error("PDF error: {0}")]
+    Pdf(#[from] lopdf::Error),
+    #[error("Malformed provenance record: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// What was done to a document, as embedded in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ProvenanceRecord {
+    pub format: u32,
+    pub tool: String,
+    pub tool_version: String,
+    /// Cleaning policy the document was processed under
+    pub policy_id: String,
+    /// Omitted when the template leaves timestamps out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processed_at: Option<DateTime<Utc>>,
+    /// SHA-256 over the document's objects at stamping time
+    pub content_sha256: String,
+    /// Caller-defined fields, after placeholder substitution
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Shape of the records to embed
+///
+/// Values in `fields` may use the placeholders `{tool}`, `{version}`,
+/// `{policy}`, `{timestamp}` and `{hash}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvenanceTemplate {
+    pub tool: String,
+    pub tool_version: String,
+    pub policy_id: String,
+    /// Record the processing time; off for reproducible output
+    pub include_timestamp: bool,
+    pub fields: BTreeMap<String, String>,
+}
+
+impl ProvenanceTemplate {
+    /// Template naming this crate as the tool
+    pub fn new(policy_id: impl Into<String>) -> Self {
+        Self {
+            tool: env!("CARGO_PKG_NAME").into(),
+            tool_version: env!("CARGO_PKG_VERSION").into(),
+            policy_id: policy_id.into(),
+            include_timestamp: true,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_field(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.insert(key.into(), value.into());
+        self
+    }
+
+    /// Fills the template for a document whose content digest is `content_sha256`
+    pub fn render(&self, content_sha256: &str, now: DateTime<Utc>) -> ProvenanceRecord {
+        let processed_at = self.include_timestamp.then_some(now);
+        let timestamp = processed_at.map(|t| t.to_rfc3339()).unwrap_or_default();
+        let substitute = |value: &str| {
+            value.replace("{tool}", &self.tool)
+                .replace("{version}", &self.tool_version)
+                .replace("{policy}", &self.policy_id)
+                .replace("{timestamp}", &timestamp)
+                .replace("{hash}", content_sha256)
+        };
+        ProvenanceRecord {
+            format: PROVENANCE_FORMAT_VERSION,
+            tool: self.tool.clone(),
+            tool_version: self.tool_version.clone(),
+            policy_id: self.policy_id.clone(),
+            processed_at,
+            content_sha256: content_sha256.into(),
+            fields: self.fields.iter().map(|(k, v)| (k.clone(), substitute(v))).collect(),
+        }
+    }
+}
+
+/// SHA-256 over the digests of all objects, in object order
+pub fn content_digest(doc: &lopdf::Document) -> String {
+    let mut hasher = Sha256::new();
+    for ((number, generation), object) in &doc.objects {
+        hasher.update(format!("{} {} {}\n", number, generation, object_digest(object)));
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Embeds a record rendered from `template`, replacing any earlier one
+pub fn stamp(doc: &mut lopdf::Document, template: &ProvenanceTemplate) -> Result<ProvenanceRecord, ProvenanceError> {
+    remove(doc)?;
+    let record = template.render(&content_digest(doc), Utc::now());
+
+    let stream = Stream::new(dictionary! { "Type" => "PdfEngineProvenance" }, serde_json::to_vec(&record)?);
+    let id = doc.add_object(stream);
+    // Required by the specification; a fixed date keeps untimed output reproducible
+    let last_modified = PdfDate::from_datetime(&record.processed_at.unwrap_or(DateTime::UNIX_EPOCH));
+    let data = dictionary! {
+        "LastModified" => last_modified.to_object(),
+        "Private" => id,
+    };
+
+    let catalog = catalog_mut(doc)?;
+    if !matches!(catalog.get(b"PieceInfo"), Ok(Object::Dictionary(_))) {
+        catalog.set("PieceInfo", Dictionary::new());
+    }
+    if let Ok(Object::Dictionary(piece_info)) = catalog.get_mut(b"PieceInfo") {
+        piece_info.set(PIECE_INFO_KEY, data);
+    }
+    Ok(record)
+}
+
+/// Reads the embedded record, if the document has one
+pub fn read(doc: &lopdf::Document) -> Result<Option<ProvenanceRecord>, ProvenanceError> {
+    let Some(id) = record_id(doc) else { return Ok(None) };
+    let Ok(Object::Stream(stream)) = doc.get_object(id) else { return Ok(None) };
+    let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+    Ok(Some(serde_json::from_slice(&content)?))
+}
+
+/// Removes the embedded record, returning whether there was one
+pub fn remove(doc: &mut lopdf::Document) -> Result<bool, ProvenanceError> {
+    let Some(id) = record_id(doc) else { return Ok(false) };
+    doc.objects.remove(&id);
+    let catalog = catalog_mut(doc)?;
+    let empty = match catalog.get_mut(b"PieceInfo") {
+        Ok(Object::Dictionary(piece_info)) => {
+            piece_info.remove(PIECE_INFO_KEY.as_bytes());
+            piece_info.is_empty()
+        }
+        _ => false,
+    };
+    if empty {
+        catalog.remove(b"PieceInfo");
+    }
+    Ok(true)
+}
+
+/// Result of checking an embedded record against the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProvenanceStatus {
+    /// No record embedded
+    Missing,
+    /// The content is unchanged since stamping
+    Intact(ProvenanceRecord),
+    /// The content changed after stamping
+    Modified { record: ProvenanceRecord, actual_sha256: String },
+}
+
+/// Reads the record and checks its digest against the current content
+pub fn verify(doc: &lopdf::Document) -> Result<ProvenanceStatus, ProvenanceError> {
+    let Some(record) = read(doc)? else { return Ok(ProvenanceStatus::Missing) };
+    let mut unstamped = doc.clone();
+    remove(&mut unstamped)?;
+    let actual_sha256 = content_digest(&unstamped);
+    Ok(if actual_sha256 == record.content_sha256 {
+        ProvenanceStatus::Intact(record)
+    } else {
+        ProvenanceStatus::Modified { record, actual_sha256 }
+    })
+}
+
+fn catalog_mut(doc: &mut lopdf::Document) -> lopdf::Result<&mut Dictionary> {
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    doc.get_dictionary_mut(root)
+}
+
+fn record_id(doc: &lopdf::Document) -> Option<ObjectId> {
+    let root = doc.trailer.get(b"Root").ok()?.as_reference().ok()?;
+    let piece_info = doc.get_dictionary(root).ok()?.get(b"PieceInfo").ok()?;
+    let piece_info = match piece_info {
+        Object::Reference(id) => doc.get_dictionary(*id).ok()?,
+        Object::Dictionary(dict) => dict,
+        _ => return None,
+    };
+    let data = match piece_info.get(PIECE_INFO_KEY.as_bytes()).ok()? {
+        Object::Reference(id) => doc.get_dictionary(*id).ok()?,
+        Object::Dictionary(dict) => dict,
+        _ => return None,
+    };
+    data.get(b"Private").ok()?.as_reference().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document() -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let pages = doc.add_object(dictionary! { "Type" => "Pages", "Kids" => vec![], "Count" => 0 });
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+        doc.trailer.set("Root", catalog);
+        doc
+    }
+
+    #[test]
+    fn test_stamp_read_back_after_save() {
+        let mut doc = document();
+        let template = ProvenanceTemplate::new("strict-v2").with_field("note", "{tool} {version} under {policy}");
+        let stamped = stamp(&mut doc, &template).unwrap();
+        assert_eq!(stamped.fields["note"], format!("pdf_engine {} under strict-v2", env!("CARGO_PKG_VERSION")));
+
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        let reloaded = lopdf::Document::load_mem(&bytes).unwrap();
+        assert_eq!(read(&reloaded).unwrap(), Some(stamped.clone()));
+        assert_eq!(verify(&reloaded).unwrap(), ProvenanceStatus::Intact(stamped));
+    }
+
+    #[test]
+    fn test_later_edit_detected() {
+        let mut doc = document();
+        stamp(&mut doc, &ProvenanceTemplate::new("default")).unwrap();
+        doc.add_object(dictionary! { "Injected" => true });
+        assert!(matches!(verify(&doc).unwrap(), ProvenanceStatus::Modified { .. }));
+    }
+
+    #[test]
+    fn test_restamp_and_remove() {
+        let mut doc = document();
+        let before = content_digest(&doc);
+        stamp(&mut doc, &ProvenanceTemplate::new("a")).unwrap();
+        stamp(&mut doc, &ProvenanceTemplate::new("b")).unwrap();
+        assert_eq!(read(&doc).unwrap().unwrap().policy_id, "b");
+
+        assert!(remove(&mut doc).unwrap());
+        assert_eq!(read(&doc).unwrap(), None);
+        assert!(!catalog_mut(&mut doc).unwrap().has(b"PieceInfo"));
+        assert_eq!(content_digest(&doc), before);
+    }
+
+    #[test]
+    fn test_unstamped_document_is_missing() {
+        assert_eq!(verify(&document()).unwrap(), ProvenanceStatus::Missing);
+    }
+}
@@ -0,0 +1,181 @@
+//! Record and replay of pipeline runs
+//! Author: kartik4091
+//! Created: 2025-06-05 01:21:37 UTC
+//! A behavior change in a large fixture shows up as a different output
+//! hash, which says nothing about which step changed. A [`ReplayRecord`]
+//! captures one run: the input hash, the arguments it was run with and a
+//! digest of the document after every pipeline stage. Replaying the record
+//! with another build re-executes the run and [`ReplayRecord::diff`] names
+//! the first stage whose output differs, so `git bisect run` can drive the
+//! replay across releases.
+//!
+//! Stages that embed the time or fresh random values (timestamped
+//! provenance, encryption) differ on every run; record with
+//! `--provenance-no-timestamp` and without encryption for stable replays.
+
+use std::{fs, io, path::{Path, PathBuf}};
+use chrono::{DateTime, Utc};
+use lopdf::Document;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use sha2::{Digest, Sha256};
+
+use crate::antiforensics::hash::object_digest;
+
+/// Record format version, bumped on incompatible changes
+pub const REPLAY_FORMAT_VERSION: u32 = 1;
+
+/// Document state after one pipeline stage
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StageRecord {
+    /// Pipeline step, as named in evidence logs
+    pub stage: String,
+    /// SHA-256 over the digests of every object and of the trailer
+    pub digest: String,
+    pub objects: usize,
+}
+
+impl StageRecord {
+    pub fn of(stage: &str, doc: &Document) -> Self {
+        let mut hasher = Sha256::new();
+        for (&(number, generation), object) in &doc.objects {
+            hasher.update(number.to_be_bytes());
+            hasher.update(generation.to_be_bytes());
+            hasher.update(object_digest(object));
+        }
+        hasher.update(b"trailer");
+        hasher.update(object_digest(&lopdf::Object::Dictionary(doc.trailer.clone())));
+        Self { stage: stage.to_string(), digest: format!("{:x}", hasher.finalize()), objects: doc.objects.len() }
+    }
+}
+
+/// One recorded run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ReplayRecord {
+    pub version: u32,
+    /// Version of the tool that made the record
+    pub tool_version: String,
+    pub recorded_at: DateTime<Utc>,
+    pub input: PathBuf,
+    pub input_sha256: String,
+    /// Command-line arguments of the run, without the program name
+    pub args: Vec<String>,
+    /// Stages in the order they ran
+    pub stages: Vec<StageRecord>,
+    pub output_sha256: Option<String>,
+}
+
+impl ReplayRecord {
+    pub fn new(input: &Path, input_sha256: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            version: REPLAY_FORMAT_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            recorded_at: Utc::now(),
+            input: input.to_path_buf(),
+            input_sha256: input_sha256.into(),
+            args,
+            stages: Vec::new(),
+            output_sha256: None,
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let record: Self = serde_json::from_slice(&fs::read(path)?)?;
+        if record.version > REPLAY_FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("replay format version {} is newer than {}", record.version, REPLAY_FORMAT_VERSION),
+            ));
+        }
+        Ok(record)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Compares a replay of this record with it, stage by stage
+    pub fn diff(&self, replayed: &ReplayRecord) -> ReplayDiff {
+        let stages = (0..self.stages.len().max(replayed.stages.len()))
+            .filter_map(|index| {
+                let expected = self.stages.get(index);
+                let actual = replayed.stages.get(index);
+                (expected != actual).then(|| StageDiff { index, expected: expected.cloned(), actual: actual.cloned() })
+            })
+            .collect();
+        ReplayDiff {
+            input_changed: self.input_sha256 != replayed.input_sha256,
+            stages,
+            output_changed: self.output_sha256 != replayed.output_sha256,
+        }
+    }
+}
+
+/// A stage whose recorded and replayed output differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageDiff {
+    /// Position in the run, from 0
+    pub index: usize,
+    /// Recorded stage; `None` when the replay ran more stages
+    pub expected: Option<StageRecord>,
+    /// Replayed stage; `None` when the replay ran fewer stages
+    pub actual: Option<StageRecord>,
+}
+
+/// Differences between a record and its replay
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayDiff {
+    /// The replay read a different input, so every difference is expected
+    pub input_changed: bool,
+    /// Every differing stage, in run order; later ones usually follow from the first
+    pub stages: Vec<StageDiff>,
+    pub output_changed: bool,
+}
+
+impl ReplayDiff {
+    /// Whether the replay reproduced the record
+    pub fn is_empty(&self) -> bool {
+        !self.input_changed && self.stages.is_empty() && !self.output_changed
+    }
+
+    /// The stage to look at: where the runs first went apart
+    pub fn first_divergence(&self) -> Option<&StageDiff> {
+        self.stages.first()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_first_divergent_stage_found() {
+        let mut doc = Document::with_version("1.7");
+        let info = doc.add_object(dictionary! { "Producer" => lopdf::Object::string_literal("Writer") });
+        doc.trailer.set("Info", info);
+
+        let mut recorded = ReplayRecord::new(Path::new("in.pdf"), "abc", vec!["in.pdf".into(), "out.pdf".into()]);
+        recorded.stages.push(StageRecord::of("metadata", &doc));
+        doc.trailer.remove(b"Info");
+        recorded.stages.push(StageRecord::of("set_metadata", &doc));
+        recorded.output_sha256 = Some("def".into());
+
+        let mut replayed = recorded.clone();
+        assert!(recorded.diff(&replayed).is_empty());
+        assert_eq!(StageRecord::of("set_metadata", &doc), recorded.stages[1]);
+
+        // A changed stage, and one more at the end
+        doc.objects.clear();
+        replayed.stages[1] = StageRecord::of("set_metadata", &doc);
+        replayed.stages.push(StageRecord::of("renumber", &doc));
+        let diff = recorded.diff(&replayed);
+        assert!(!diff.is_empty() && !diff.input_changed && !diff.output_changed);
+        assert_eq!(diff.stages.len(), 2);
+        let first = diff.first_divergence().unwrap();
+        assert_eq!(first.index, 1);
+        assert_eq!(first.expected.as_ref().unwrap().objects, 1);
+        assert_eq!(first.actual.as_ref().unwrap().objects, 0);
+        assert!(diff.stages[1].expected.is_none());
+    }
+}
@@ -0,0 +1,201 @@
+//! Report roll-ups
+//! Author: kartik4091
+//! Created: 2025-06-04 23:14:47 UTC
+//! Dirty documents produce thousands of findings that differ only in the
+//! object they sit in: one annotation per page, one font per subset. A
+//! dedup key of artifact type, pattern id and normalized location (object
+//! and array numbers replaced by `#`) groups those repeats, and reports
+//! list each group once with its count and highest risk, most severe
+//! first. `--full` keeps every finding instead.
+
+use std::{collections::HashMap, sync::OnceLock};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use crate::antiforensics::{report::explain::ExplainedFinding, ForensicArtifact, RiskLevel};
+
+/// Locations kept as examples of a group
+const MAX_EXAMPLES: usize = 3;
+
+/// Findings sharing a dedup key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RolledUpFinding {
+    pub key: String,
+    pub artifact_type: String,
+    /// Highest risk among the group
+    pub risk_level: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern_id: Option<String>,
+    /// Location with object and index numbers replaced by `#`
+    pub location: String,
+    /// Description of the first finding of the group
+    pub description: String,
+    pub count: usize,
+    /// First few locations, verbatim
+    pub examples: Vec<String>,
+}
+
+fn numbers() -> &'static Regex {
+    static NUMBERS: OnceLock<Regex> = OnceLock::new();
+    NUMBERS.get_or_init(|| Regex::new(r"\d+").expect("valid regex"))
+}
+
+/// `location` with every number replaced by `#`, e.g. `12 0 R:/A` to `# # R:/A`
+pub fn normalize_location(location: &str) -> String {
+    numbers().replace_all(location, "#").into_owned()
+}
+
+/// Dedup key of a finding: type, pattern id and normalized location
+///
+/// Descriptions are free text and change with wording, so findings
+/// without a pattern id are grouped by type and location alone.
+pub fn dedup_key(artifact_type: &str, pattern_id: Option<&str>, location: &str) -> String {
+    format!("{}|{}|{}", artifact_type, pattern_id.unwrap_or_default(), normalize_location(location))
+}
+
+/// Groups `artifacts` by dedup key, most severe and most frequent first
+pub fn roll_up(artifacts: &[ForensicArtifact]) -> Vec<RolledUpFinding> {
+    let mut groups: Vec<(RiskLevel, RolledUpFinding)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for artifact in artifacts {
+        let artifact_type = format!("{:?}", artifact.artifact_type);
+        let pattern_id = artifact.metadata.get("pattern_id");
+        let key = dedup_key(&artifact_type, pattern_id.map(String::as_str), &artifact.location);
+        match index.get(&key) {
+            Some(&i) => {
+                let (highest, group) = &mut groups[i];
+                group.count += 1;
+                if group.examples.len() < MAX_EXAMPLES {
+                    group.examples.push(artifact.location.clone());
+                }
+                if artifact.risk_level.severity() > highest.severity() {
+                    *highest = artifact.risk_level;
+                    group.risk_level = format!("{:?}", artifact.risk_level);
+                }
+            }
+            None => {
+                index.insert(key.clone(), groups.len());
+                groups.push((artifact.risk_level, RolledUpFinding {
+                    key,
+                    artifact_type,
+                    risk_level: format!("{:?}", artifact.risk_level),
+                    pattern_id: pattern_id.cloned(),
+                    location: normalize_location(&artifact.location),
+                    description: artifact.description.clone(),
+                    count: 1,
+                    examples: vec![artifact.location.clone()],
+                }));
+            }
+        }
+    }
+
+    groups.sort_by(|(a_risk, a), (b_risk, b)| {
+        b_risk.severity().cmp(&a_risk.severity())
+            .then(b.count.cmp(&a.count))
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+/// Keeps the first explained finding of each dedup key, counting the rest
+pub fn dedup_findings(findings: Vec<ExplainedFinding>) -> Vec<ExplainedFinding> {
+    let mut kept: Vec<ExplainedFinding> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for finding in findings {
+        let key = dedup_key(&finding.artifact_type, finding.pattern_id.as_deref(), &finding.location);
+        match index.get(&key) {
+            Some(&i) => kept[i].occurrences += finding.occurrences,
+            None => {
+                index.insert(key, kept.len());
+                kept.push(finding);
+            }
+        }
+    }
+    kept
+}
+
+/// Cleaning actions with repeats folded into one entry, e.g. `/Annots/# # R (x40)`
+pub fn dedup_actions(actions: &[String]) -> Vec<String> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for action in actions {
+        let normalized = normalize_location(action);
+        match index.get(&normalized) {
+            Some(&i) => counts[i].1 += 1,
+            None => {
+                index.insert(normalized, counts.len());
+                counts.push((action.clone(), 1));
+            }
+        }
+    }
+    counts.into_iter()
+        .map(|(action, count)| match count {
+            1 => action,
+            n => format!("{} (x{})", normalize_location(&action), n),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antiforensics::ArtifactType;
+
+    fn artifact(location: &str, risk_level: RiskLevel, pattern_id: &str) -> ForensicArtifact {
+        let mut artifact = ForensicArtifact {
+            artifact_type: ArtifactType::Structure,
+            location: location.into(),
+            risk_level,
+            description: "Link annotation".into(),
+            ..Default::default()
+        };
+        artifact.metadata.insert("pattern_id".into(), pattern_id.into());
+        artifact
+    }
+
+    #[test]
+    fn test_roll_up_groups_by_normalized_location() {
+        let artifacts = vec![
+            artifact("12 0 R:/A", RiskLevel::Low, "key.uri"),
+            artifact("14 0 R:/A", RiskLevel::Medium, "key.uri"),
+            artifact("15 0 R:/A", RiskLevel::Low, "key.uri"),
+            artifact("3 0 R:/OpenAction", RiskLevel::High, "key.launch"),
+        ];
+        let rolled = roll_up(&artifacts);
+
+        assert_eq!(rolled.len(), 2);
+        assert_eq!(rolled[0].pattern_id.as_deref(), Some("key.launch"));
+        assert_eq!(rolled[1].count, 3);
+        assert_eq!(rolled[1].risk_level, "Medium");
+        assert_eq!(rolled[1].location, "# # R:/A");
+        assert_eq!(rolled[1].examples, vec!["12 0 R:/A", "14 0 R:/A", "15 0 R:/A"]);
+    }
+
+    #[test]
+    fn test_roll_up_ignores_description_wording() {
+        let first = ForensicArtifact {
+            artifact_type: ArtifactType::JavaScript,
+            location: "12 0 R:/JS".into(),
+            description: "Script in annotation 12".into(),
+            ..Default::default()
+        };
+        let second = ForensicArtifact {
+            location: "13 0 R:/JS".into(),
+            description: "JavaScript in annotation 13".into(),
+            ..first.clone()
+        };
+        let rolled = roll_up(&[first, second]);
+
+        assert_eq!(rolled.len(), 1);
+        assert_eq!(rolled[0].count, 2);
+        assert_eq!(rolled[0].description, "Script in annotation 12");
+    }
+
+    #[test]
+    fn test_dedup_actions() {
+        let actions: Vec<String> = ["/Annots/7 0 R", "/Annots/9 0 R", "/Info/Producer"].map(String::from).into();
+        assert_eq!(dedup_actions(&actions), vec!["/Annots/# # R (x2)", "/Info/Producer"]);
+    }
+}
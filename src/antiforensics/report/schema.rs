@@ -0,0 +1,102 @@
+//! JSON Schema for emitted JSON
+//! Author: kartik4091
+//! Created: 2025-06-05 00:34:12 UTC
+//! Integrators consume sidecar reports, scan findings, evidence logs and
+//! the other JSON this tool writes. The schemas here are derived from the
+//! same serde types that write them, so they cannot drift from the output,
+//! and are printed by `--schema` for validation and client generation.
+
+use std::collections::BTreeMap;
+use schemars::{gen::SchemaSettings, schema::RootSchema, JsonSchema};
+
+use crate::antiforensics::{
+    analyzer::BaselineScoreLine,
+    patterns::harness::RuleTestReport,
+    report::{evidence::EvidenceLog, provenance::ProvenanceRecord, replay::ReplayRecord, sidecar::SidecarReport},
+    scanner::{triage::TriageRecord, QuickScanLine},
+    ForensicArtifact,
+};
+
+/// Names accepted by [`schema`], with what each describes
+pub const SCHEMAS: &[(&str, &str)] = &[
+    ("artifact", "one scan finding, as written to JSON lines sinks"),
+    ("sidecar", "<name>.report.json written next to each output"),
+    ("evidence", "<name>.evidence.json chain-of-custody log"),
+    ("provenance", "record embedded by --provenance"),
+    ("quick-scan", "one line of `quick-scan --json`"),
+    ("triage", "one line of `quick-scan --profile bulk-triage --json`"),
+    ("baseline-score", "one line of `baseline score --json`"),
+    ("rule-test", "report of `rules test --json`"),
+    ("replay", "run record written by --record-replay"),
+];
+
+fn generate<T: JsonSchema>() -> RootSchema {
+    SchemaSettings::draft07().into_generator().into_root_schema_for::<T>()
+}
+
+/// Schema of one kind of emitted JSON, by its name in [`SCHEMAS`]
+pub fn schema(name: &str) -> Option<RootSchema> {
+    match name {
+        "artifact" => Some(generate::<ForensicArtifact>()),
+        "sidecar" => Some(generate::<SidecarReport>()),
+        "evidence" => Some(generate::<EvidenceLog>()),
+        "provenance" => Some(generate::<ProvenanceRecord>()),
+        "quick-scan" => Some(generate::<QuickScanLine>()),
+        "triage" => Some(generate::<TriageRecord>()),
+        "baseline-score" => Some(generate::<BaselineScoreLine>()),
+        "rule-test" => Some(generate::<RuleTestReport>()),
+        "replay" => Some(generate::<ReplayRecord>()),
+        _ => None,
+    }
+}
+
+/// Every schema, keyed by name
+pub fn all() -> BTreeMap<&'static str, RootSchema> {
+    SCHEMAS.iter()
+        .filter_map(|&(name, _)| Some((name, schema(name)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antiforensics::{ArtifactType, RiskLevel};
+
+    #[test]
+    fn test_schemas_match_serialized_output() {
+        assert_eq!(all().len(), SCHEMAS.len());
+        assert!(schema("unknown").is_none());
+
+        let artifact = ForensicArtifact {
+            id: "a".into(),
+            artifact_type: ArtifactType::JavaScript,
+            location: "12 0 R".into(),
+            description: "script".into(),
+            risk_level: RiskLevel::High,
+            remediation: "remove".into(),
+            metadata: Default::default(),
+            detection_timestamp: chrono::Utc::now(),
+            hash: String::new(),
+            confidence: 1.0,
+        };
+        let value = serde_json::to_value(&artifact).unwrap();
+        let root = serde_json::to_value(schema("artifact").unwrap()).unwrap();
+        let properties = root["properties"].as_object().unwrap();
+        // Every serialized field is described, and every required one is serialized
+        for key in value.as_object().unwrap().keys() {
+            assert!(properties.contains_key(key), "{} missing from schema", key);
+        }
+        for key in root["required"].as_array().unwrap() {
+            assert!(value.get(key.as_str().unwrap()).is_some(), "{} not serialized", key);
+        }
+        assert!(root["definitions"].get("RiskLevel").is_some());
+
+        // Lines of `quick-scan --json` wrap the report with its path
+        let root = serde_json::to_value(schema("quick-scan").unwrap()).unwrap();
+        let required: Vec<&str> = root["required"].as_array().unwrap().iter().filter_map(|key| key.as_str()).collect();
+        assert_eq!(required, ["path", "report"]);
+        assert!(root["definitions"].get("QuickScanReport").is_some());
+        let root = serde_json::to_value(schema("baseline-score").unwrap()).unwrap();
+        assert!(root["properties"].get("anomaly").is_some());
+    }
+}
@@ -0,0 +1,208 @@
+//! Per-output sidecar reports
+//! Author: kartik4091
+//! Created: 2025-06-04 18:21:40 UTC
+//! A sidecar is a `<name>.report.json` file written next to each output
+//! of a batch run. It records what the input contained, what cleaning
+//! removed, the output hashes and whether the output verified, so
+//! downstream systems can consume per-file provenance without parsing
+//! the run log.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use crate::antiforensics::{
+    hash::DocumentHashes,
+    patterns::DATABASE_VERSION_KEY,
+    report::{
+        accessibility::AccessibilityImpact,
+        encrypted::{self, ReportEncryption},
+        explain::ExplainedFinding,
+        heatmap::PageRisk,
+        object_map::ObjectMapping,
+        rollup::{self, RolledUpFinding},
+    },
+    scanner::language::LANGUAGES_KEY,
+    ForensicArtifact,
+};
+use crate::visual_diff::PageDiff;
+
+/// Scan findings, summarized
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ScanSummary {
+    pub total: usize,
+    /// Artifact counts by risk level
+    pub by_risk: BTreeMap<String, usize>,
+    /// Artifact counts by artifact type
+    pub by_type: BTreeMap<String, usize>,
+    /// Languages of the page text, primary first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+    /// Version of the pattern database the scan matched against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern_db_version: Option<u32>,
+}
+
+impl ScanSummary {
+    pub fn from_artifacts(artifacts: &[ForensicArtifact]) -> Self {
+        let mut summary = Self { total: artifacts.len(), ..Default::default() };
+        for artifact in artifacts {
+            *summary.by_risk.entry(format!("{:?}", artifact.risk_level)).or_default() += 1;
+            *summary.by_type.entry(format!("{:?}", artifact.artifact_type)).or_default() += 1;
+            if let Some(languages) = artifact.metadata.get(LANGUAGES_KEY) {
+                summary.languages = languages.split(',').map(str::to_string).collect();
+            }
+            if let Some(version) = artifact.metadata.get(DATABASE_VERSION_KEY) {
+                summary.pattern_db_version = version.parse().ok().max(summary.pattern_db_version);
+            }
+        }
+        summary
+    }
+}
+
+/// Provenance record for one processed file
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SidecarReport {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub processed_at: DateTime<Utc>,
+    pub scan: ScanSummary,
+    /// Entries removed by cleaning, as PDF paths like `/Root/OpenAction`
+    pub cleaning_actions: Vec<String>,
+    /// Clean-and-rescan rounds run
+    #[serde(default)]
+    pub clean_rounds: usize,
+    /// Findings a re-scan of the cleaned output still reported
+    #[serde(default)]
+    pub remaining: ScanSummary,
+    pub hashes: DocumentHashes,
+    pub verified: bool,
+    /// Findings with the evidence behind them, when requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<ExplainedFinding>,
+    /// Scanned findings grouped by dedup key, most severe first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rollup: Vec<RolledUpFinding>,
+    /// Pages of the input with findings, riskiest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub heatmap: Vec<PageRisk>,
+    /// Page renderings of the input and output, when requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub thumbnails: Vec<PageThumbnail>,
+    /// Input/output page comparison, when requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub visual_diff: Vec<PageDiff>,
+    /// Accessibility of the input and output, with what cleaning took away
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accessibility: Option<AccessibilityImpact>,
+    /// Output object each input object became, when requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub object_map: Vec<ObjectMapping>,
+}
+
+/// Before/after rendering of one page, as PNG data URIs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PageThumbnail {
+    /// 0-based page index
+    pub page: usize,
+    pub before: String,
+    pub after: String,
+}
+
+impl SidecarReport {
+    /// Sidecar path for `output`: `out/a.pdf` becomes `out/a.report.json`
+    pub fn path_for(output: &Path) -> PathBuf {
+        output.with_extension("report.json")
+    }
+
+    /// Whether an output verified: the re-scan of it came back clean
+    /// (`rescan_clean`) and the visual diff flagged no page
+    ///
+    /// A flagged page changed without the requested operations calling
+    /// for it, so the output no longer shows what the input showed.
+    pub fn verdict(rescan_clean: bool, visual_diff: &[PageDiff]) -> bool {
+        rescan_clean && !visual_diff.iter().any(|diff| diff.flagged)
+    }
+
+    /// Folds repeated findings and cleaning actions into one entry each
+    ///
+    /// Counts in `scan`, `remaining` and `rollup` are unaffected; reports
+    /// written without compacting list every finding and action.
+    pub fn compact(mut self) -> Self {
+        self.findings = rollup::dedup_findings(self.findings);
+        self.cleaning_actions = rollup::dedup_actions(&self.cleaning_actions);
+        self
+    }
+
+    /// Writes the report next to its output and returns the sidecar path
+    ///
+    /// With `encryption`, the sidecar is written encrypted under an `.age` suffix.
+    pub fn write(&self, encryption: Option<&ReportEncryption>) -> io::Result<PathBuf> {
+        encrypted::write(&Self::path_for(&self.output), &serde_json::to_vec_pretty(self)?, encryption)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antiforensics::{ArtifactType, RiskLevel};
+
+    #[test]
+    fn test_sidecar_path() {
+        assert_eq!(
+            SidecarReport::path_for(Path::new("/out/a.pdf")),
+            PathBuf::from("/out/a.report.json"),
+        );
+    }
+
+    #[test]
+    fn test_verdict_follows_visual_diff() {
+        let diff = |flagged| PageDiff {
+            page: 0,
+            changed_pixels: if flagged { 120 } else { 0 },
+            changed_fraction: if flagged { 0.01 } else { 0.0 },
+            ssim: if flagged { 0.9 } else { 1.0 },
+            expected: false,
+            flagged,
+        };
+        assert!(SidecarReport::verdict(true, &[]));
+        assert!(SidecarReport::verdict(true, &[diff(false)]));
+        assert!(!SidecarReport::verdict(true, &[diff(false), diff(true)]));
+        assert!(!SidecarReport::verdict(false, &[diff(false)]));
+    }
+
+    #[test]
+    fn test_scan_summary_counts() {
+        let artifact = |artifact_type, risk_level| ForensicArtifact { artifact_type, risk_level, ..Default::default() };
+        let summary = ScanSummary::from_artifacts(&[
+            artifact(ArtifactType::JavaScript, RiskLevel::High),
+            artifact(ArtifactType::Metadata, RiskLevel::Low),
+            artifact(ArtifactType::Metadata, RiskLevel::Low),
+        ]);
+
+        assert_eq!(summary.total, 3);
+        assert_eq!(summary.by_risk["Low"], 2);
+        assert_eq!(summary.by_type["JavaScript"], 1);
+        assert!(summary.languages.is_empty());
+        assert_eq!(summary.pattern_db_version, None);
+    }
+
+    #[test]
+    fn test_scan_summary_languages() {
+        let mut artifact = ForensicArtifact { risk_level: RiskLevel::None, ..Default::default() };
+        artifact.metadata.insert(LANGUAGES_KEY.into(), "de,en".into());
+        assert_eq!(ScanSummary::from_artifacts(&[artifact]).languages, vec!["de", "en"]);
+    }
+
+    #[test]
+    fn test_scan_summary_pattern_version() {
+        let mut artifact = ForensicArtifact { risk_level: RiskLevel::High, ..Default::default() };
+        artifact.metadata.insert(DATABASE_VERSION_KEY.into(), "3".into());
+        assert_eq!(ScanSummary::from_artifacts(&[artifact]).pattern_db_version, Some(3));
+    }
+}
@@ -0,0 +1,264 @@
+//! Bounded aggregation of scan findings
+//! Author: kartik4091
+//! Created: 2025-06-04 16:52:08 UTC
+//! Groups identical findings and keeps only a capped number of locations
+//! per group, so a hostile document that triggers the same pattern
+//! hundreds of thousands of times still produces a small ScanResult.
+//! Groups are retrieved page by page.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use super::{Category, ScanFinding, Severity};
+
+/// Limits applied while aggregating findings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregationLimits {
+    /// Locations kept per group; further instances are only counted
+    pub max_instances: usize,
+
+    /// Distinct groups kept; further groups are only counted
+    pub max_groups: usize,
+}
+
+impl Default for AggregationLimits {
+    fn default() -> Self {
+        Self {
+            max_instances: 10,
+            max_groups: 1000,
+        }
+    }
+}
+
+/// Findings sharing severity, category and description
+#[derive(Debug, Clone)]
+pub struct FindingGroup {
+    /// Finding severity
+    pub severity: Severity,
+    /// Finding category
+    pub category: Category,
+    /// Shared description
+    pub description: String,
+    /// Recommendation of the first instance
+    pub recommendation: String,
+    /// Number of instances seen, including those without a stored location
+    pub count: usize,
+    /// Locations of the first `max_instances` instances
+    pub locations: Vec<String>,
+    /// Timestamp of the first instance
+    pub first_seen: chrono::DateTime<chrono::Utc>,
+}
+
+impl FindingGroup {
+    /// Instances counted but not individually kept
+    pub fn omitted(&self) -> usize {
+        self.count - self.locations.len()
+    }
+}
+
+type GroupKey = (Severity, Category, String);
+
+/// Aggregated findings of a scan
+#[derive(Debug, Clone, Default)]
+pub struct AggregatedFindings {
+    limits: AggregationLimits,
+    groups: Vec<FindingGroup>,
+    index: HashMap<GroupKey, usize>,
+    total: usize,
+    overflow_instances: usize,
+    overflow_severity: Option<Severity>,
+}
+
+/// One page of finding groups
+#[derive(Debug, Clone)]
+pub struct FindingPage<'a> {
+    /// Zero-based page number
+    pub page: usize,
+    /// Groups on this page
+    pub groups: &'a [FindingGroup],
+    /// Total number of stored groups
+    pub total_groups: usize,
+    /// Whether a later page exists
+    pub has_more: bool,
+}
+
+impl AggregatedFindings {
+    /// Creates an empty aggregation
+    pub fn new(limits: AggregationLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// Aggregates a batch of findings
+    pub fn from_findings(findings: impl IntoIterator<Item = ScanFinding>, limits: &AggregationLimits) -> Self {
+        let mut aggregated = Self::new(limits.clone());
+        aggregated.extend(findings);
+        aggregated
+    }
+
+    /// Adds one finding
+    pub fn push(&mut self, finding: ScanFinding) {
+        self.total += 1;
+        let key = (finding.severity, finding.category, finding.description);
+
+        if let Some(&i) = self.index.get(&key) {
+            let group = &mut self.groups[i];
+            group.count += 1;
+            if group.locations.len() < self.limits.max_instances {
+                group.locations.push(finding.location);
+            }
+            return;
+        }
+
+        if self.groups.len() >= self.limits.max_groups {
+            // Too many distinct findings; keep only the count and the worst severity
+            self.overflow_instances += 1;
+            self.overflow_severity = self.overflow_severity.max(Some(key.0));
+            return;
+        }
+
+        let (severity, category, description) = key.clone();
+        let mut locations = Vec::new();
+        if self.limits.max_instances > 0 {
+            locations.push(finding.location);
+        }
+        self.index.insert(key, self.groups.len());
+        self.groups.push(FindingGroup {
+            severity,
+            category,
+            description,
+            recommendation: finding.recommendation,
+            count: 1,
+            locations,
+            first_seen: finding.timestamp,
+        });
+    }
+
+    /// Number of findings seen
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Number of stored groups
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Whether no findings were seen
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Stored groups in first-seen order
+    pub fn groups(&self) -> &[FindingGroup] {
+        &self.groups
+    }
+
+    /// Findings dropped after `max_groups` was reached, and the worst severity among them
+    ///
+    /// Overflowed findings are counted per instance, since without storing
+    /// their keys repeated instances cannot be told apart from new groups.
+    pub fn overflow(&self) -> (usize, Option<Severity>) {
+        (self.overflow_instances, self.overflow_severity)
+    }
+
+    /// Highest severity seen, including overflowed findings
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.groups.iter().map(|g| g.severity).max().max(self.overflow_severity)
+    }
+
+    /// Returns page `page` of `page_size` groups, in first-seen order
+    pub fn page(&self, page: usize, page_size: usize) -> FindingPage<'_> {
+        let page_size = page_size.max(1);
+        let start = page.saturating_mul(page_size).min(self.groups.len());
+        let end = start.saturating_add(page_size).min(self.groups.len());
+
+        FindingPage {
+            page,
+            groups: &self.groups[start..end],
+            total_groups: self.groups.len(),
+            has_more: end < self.groups.len(),
+        }
+    }
+
+    /// Iterates over all pages of `page_size` groups
+    pub fn pages(&self, page_size: usize) -> impl Iterator<Item = FindingPage<'_>> + '_ {
+        let page_size = page_size.max(1);
+        let count = self.groups.len().div_ceil(page_size);
+        (0..count).map(move |page| self.page(page, page_size))
+    }
+}
+
+impl Extend<ScanFinding> for AggregatedFindings {
+    fn extend<I: IntoIterator<Item = ScanFinding>>(&mut self, iter: I) {
+        for finding in iter {
+            self.push(finding);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(description: &str, location: usize) -> ScanFinding {
+        ScanFinding {
+            severity: Severity::High,
+            category: Category::Content,
+            description: description.into(),
+            location: format!("offset {}", location),
+            recommendation: "Review".into(),
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_identical_findings_grouped_and_capped() {
+        let limits = AggregationLimits { max_instances: 3, max_groups: 10 };
+        let aggregated = AggregatedFindings::from_findings(
+            (0..100_000).map(|i| finding("JavaScript detected", i)),
+            &limits,
+        );
+
+        assert_eq!(aggregated.total(), 100_000);
+        assert_eq!(aggregated.len(), 1);
+        let group = &aggregated.groups()[0];
+        assert_eq!(group.count, 100_000);
+        assert_eq!(group.locations, vec!["offset 0", "offset 1", "offset 2"]);
+        assert_eq!(group.omitted(), 99_997);
+    }
+
+    #[test]
+    fn test_group_overflow_keeps_worst_severity() {
+        let limits = AggregationLimits { max_instances: 1, max_groups: 2 };
+        let mut aggregated = AggregatedFindings::new(limits);
+        aggregated.push(finding("a", 0));
+        aggregated.push(finding("b", 0));
+        aggregated.push(ScanFinding { severity: Severity::Critical, ..finding("c", 0) });
+
+        assert_eq!(aggregated.len(), 2);
+        assert_eq!(aggregated.overflow(), (1, Some(Severity::Critical)));
+        assert_eq!(aggregated.max_severity(), Some(Severity::Critical));
+    }
+
+    #[test]
+    fn test_pagination() {
+        let aggregated = AggregatedFindings::from_findings(
+            (0..25).map(|i| finding(&format!("pattern {}", i), i)),
+            &AggregationLimits::default(),
+        );
+
+        let second = aggregated.page(1, 10);
+        assert_eq!(second.groups.len(), 10);
+        assert_eq!(second.groups[0].description, "pattern 10");
+        assert!(second.has_more);
+
+        let last = aggregated.page(2, 10);
+        assert_eq!(last.groups.len(), 5);
+        assert!(!last.has_more);
+        assert!(aggregated.page(9, 10).groups.is_empty());
+        assert_eq!(aggregated.pages(10).count(), 3);
+    }
+}
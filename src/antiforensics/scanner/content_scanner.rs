@@ -314,7 +314,7 @@ impl Scanner for ContentScanner {
             path: path.clone(),
             size: metadata.len(),
             file_type: "Content".into(),
-            findings: all_findings,
+            findings: AggregatedFindings::from_findings(all_findings, &self.config.base.aggregation),
             metadata: HashMap::new(),
             metrics: ScanMetrics {
                 duration,
@@ -19,6 +19,7 @@ use super::{
     ScannerMetrics,
     BaseScanner,
     ScanContext,
+    sink::{self, ArtifactSink},
     signature_scanner::SignatureScanner,
     stream_scanner::StreamScanner,
     object_scanner::ObjectScanner,
@@ -42,6 +43,8 @@ pub struct DeepScanner {
     stream_scanner: Arc<StreamScanner>,
     /// Object scanner for structure analysis
     object_scanner: Arc<ObjectScanner>,
+    /// Sinks receiving artifacts as they are detected
+    sinks: Vec<Arc<dyn ArtifactSink>>,
 }
 
 impl DeepScanner {
@@ -55,9 +58,15 @@ impl DeepScanner {
             signature_scanner: Arc::new(SignatureScanner::new(config.clone())),
             stream_scanner: Arc::new(StreamScanner::new(config.clone())),
             object_scanner: Arc::new(ObjectScanner::new(config.clone())),
+            sinks: Vec::new(),
         })
     }
 
+    /// Registers a sink that receives artifacts as soon as they are detected
+    pub fn add_sink(&mut self, sink: Arc<dyn ArtifactSink>) {
+        self.sinks.push(sink);
+    }
+
     /// Performs initial document validation
     #[instrument(skip(self, doc), err(Display))]
     async fn validate_document(&self, doc: &Document) -> Result<(), PdfError> {
@@ -87,15 +96,19 @@ impl DeepScanner {
         // Scan document catalog
         if let Some(catalog) = doc.get_catalog() {
             context.depth += 1;
-            artifacts.extend(self.object_scanner.scan_object(catalog, context).await?);
+            let found = self.object_scanner.scan_object(catalog, context).await?;
             context.depth -= 1;
+            sink::publish(&self.sinks, &found).await;
+            artifacts.extend(found);
         }
 
         // Scan document info dictionary
         if let Some(info) = doc.get_info() {
             context.depth += 1;
-            artifacts.extend(self.object_scanner.scan_object(info, context).await?);
+            let found = self.object_scanner.scan_object(info, context).await?;
             context.depth -= 1;
+            sink::publish(&self.sinks, &found).await;
+            artifacts.extend(found);
         }
 
         Ok(artifacts)
@@ -118,7 +131,9 @@ impl DeepScanner {
                 continue;
             }
 
-            artifacts.extend(self.stream_scanner.scan_stream(&stream, context).await?);
+            let found = self.stream_scanner.scan_stream(&stream, context).await?;
+            sink::publish(&self.sinks, &found).await;
+            artifacts.extend(found);
         }
 
         Ok(artifacts)
@@ -130,7 +145,9 @@ impl DeepScanner {
         &self,
         doc: &Document,
     ) -> Result<Vec<ForensicArtifact>, PdfError> {
-        self.signature_scanner.scan_signatures(doc).await
+        let found = self.signature_scanner.scan_signatures(doc).await?;
+        sink::publish(&self.sinks, &found).await;
+        Ok(found)
     }
 
     /// Calculates overall risk level
@@ -209,6 +226,8 @@ impl Scanner for DeepScanner {
             artifacts.extend(self.scan_structure(doc, &mut context).await?);
         }
 
+        sink::flush_all(&self.sinks).await;
+
         let duration = start_time.elapsed();
         let risk_level = self.calculate_risk_level(&artifacts);
         let recommendations = self.generate_recommendations(&artifacts);
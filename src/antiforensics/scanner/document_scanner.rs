@@ -0,0 +1,309 @@
+//! Object-graph scanner over loaded lopdf documents
+//! Author: kartik4091
+//! Created: 2025-06-04 12:05:52 UTC
+//! Walks every indirect object and the trailer of a parsed document and
+//! reports risky keys, actions, identifying metadata and reference loops
+//! as artifacts.
+//! Used by the engine to measure what remains after cleaning.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use lopdf::{Dictionary, Object, ObjectId};
+use sha2::{Sha256, Digest};
+use tracing::{debug, instrument};
+
+use crate::antiforensics::{
+    patterns::{self, PatternDatabase, PatternKind, DATABASE_VERSION_KEY},
+    references,
+    types::Document,
+    ArtifactType,
+    ForensicArtifact,
+    RiskLevel,
+};
+
+/// Scanner over a parsed lopdf document
+#[derive(Debug, Clone)]
+pub struct DocumentScanner {
+    /// Maximum nesting depth followed inside a single object
+    max_depth: usize,
+    /// Risky keys and identifying metadata to report
+    patterns: Arc<PatternDatabase>,
+}
+
+impl DocumentScanner {
+    /// Creates a scanner with the default depth limit and the active patterns
+    pub fn new() -> Self {
+        Self { max_depth: 64, patterns: patterns::active() }
+    }
+
+    /// Scans with a specific pattern database instead of the active one
+    pub fn with_patterns(mut self, patterns: Arc<PatternDatabase>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    /// Overrides the maximum nesting depth
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Parses raw bytes and scans the resulting document
+    pub fn scan_bytes(&self, data: &[u8]) -> Result<Vec<ForensicArtifact>, lopdf::Error> {
+        let doc = Document::from_bytes(PathBuf::from("<memory>"), data.to_vec())?;
+        Ok(self.scan(&doc))
+    }
+
+    /// Scans every object and the trailer of a document
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        let mut artifacts = Vec::new();
+
+        for (id, object) in doc.objects() {
+            self.scan_object(id, object, "", 0, &mut artifacts);
+        }
+
+        self.scan_trailer(doc, &mut artifacts);
+
+        for cycle in references::find_cycles(&doc.structure) {
+            let mut artifact = Self::artifact(
+                cycle.start(),
+                "",
+                format!("Reference cycle {}", cycle),
+                RiskLevel::Medium,
+                ArtifactType::Structure,
+                &Object::Reference(cycle.start()),
+            );
+            artifact.remediation = "Break the reference loop; walkers that follow it never finish".into();
+            artifact.metadata.insert("cycle".into(), cycle.to_string());
+            artifacts.push(artifact);
+        }
+
+        let version = self.patterns.version().to_string();
+        for artifact in &mut artifacts {
+            artifact.metadata.insert(DATABASE_VERSION_KEY.into(), version.clone());
+        }
+
+        debug!("Document scan produced {} artifacts", artifacts.len());
+        artifacts
+    }
+
+    /// Recursively scans one object
+    fn scan_object(
+        &self,
+        id: ObjectId,
+        object: &Object,
+        path: &str,
+        depth: usize,
+        artifacts: &mut Vec<ForensicArtifact>,
+    ) {
+        if depth > self.max_depth {
+            return;
+        }
+
+        match object {
+            Object::Dictionary(dict) => self.scan_dictionary(id, dict, path, depth, artifacts),
+            Object::Stream(stream) => self.scan_dictionary(id, &stream.dict, path, depth, artifacts),
+            Object::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    let child = format!("{}[{}]", path, index);
+                    self.scan_object(id, item, &child, depth + 1, artifacts);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Scans dictionary keys and recurses into values
+    fn scan_dictionary(
+        &self,
+        id: ObjectId,
+        dict: &Dictionary,
+        path: &str,
+        depth: usize,
+        artifacts: &mut Vec<ForensicArtifact>,
+    ) {
+        for (key, value) in dict.iter() {
+            let child = format!("{}/{}", path, String::from_utf8_lossy(key));
+
+            let action_type = match value {
+                Object::Name(name) if key.as_slice() == b"S" => Some(name.as_slice()),
+                _ => None,
+            };
+
+            let by_key = self.patterns.key(key);
+            let by_action = action_type.and_then(|name| self.patterns.key(name));
+            let by_action = by_action.filter(|rule| by_key.map_or(true, |k| k.id != rule.id));
+            for rule in by_key.into_iter().chain(by_action) {
+                let name = match &rule.kind {
+                    PatternKind::Key(name) => name.as_str(),
+                    _ => continue,
+                };
+                let mut artifact = Self::artifact(
+                    id,
+                    &child,
+                    format!("Risky entry /{} present", name),
+                    rule.risk_level,
+                    rule.artifact_type.clone(),
+                    value,
+                );
+                artifact.metadata.insert("pattern_id".into(), rule.id.clone());
+                artifacts.push(artifact);
+            }
+
+            self.scan_object(id, value, &child, depth + 1, artifacts);
+        }
+    }
+
+    /// Scans the trailer's document information dictionary
+    fn scan_trailer(&self, doc: &Document, artifacts: &mut Vec<ForensicArtifact>) {
+        if let (Some(id), Some(dict)) = (doc.info_id(), doc.info()) {
+            for rule in self.patterns.info_keys() {
+                let key = match &rule.kind {
+                    PatternKind::InfoKey(key) => key,
+                    _ => continue,
+                };
+                if let Ok(value) = dict.get(key.as_bytes()) {
+                    let mut artifact = Self::artifact(
+                        id,
+                        &format!("/Info/{}", key),
+                        format!("Identifying metadata /{} present", key),
+                        rule.risk_level,
+                        rule.artifact_type.clone(),
+                        value,
+                    );
+                    artifact.metadata.insert("pattern_id".into(), rule.id.clone());
+                    artifacts.push(artifact);
+                }
+            }
+        }
+    }
+
+    /// Builds an artifact for an object location
+    fn artifact(
+        id: ObjectId,
+        path: &str,
+        description: String,
+        risk_level: RiskLevel,
+        artifact_type: ArtifactType,
+        value: &Object,
+    ) -> ForensicArtifact {
+        let rendered = match value {
+            Object::String(bytes, _) | Object::Name(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+            Object::Reference((num, gen)) => format!("{} {} R", num, gen),
+            other => format!("{:?}", other).chars().take(256).collect(),
+        };
+
+        // Exact key matches are specific; an empty value is likely a leftover stub
+        let empty = match value {
+            Object::Null => true,
+            Object::String(bytes, _) | Object::Name(bytes) => bytes.is_empty(),
+            Object::Array(items) => items.is_empty(),
+            Object::Dictionary(dict) => dict.is_empty(),
+            _ => false,
+        };
+
+        let mut metadata = HashMap::new();
+        metadata.insert("object_id".into(), format!("{} {}", id.0, id.1));
+        metadata.insert("path".into(), path.to_string());
+        metadata.insert("value".into(), rendered.clone());
+
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type,
+            location: format!("{} {} R:{}", id.0, id.1, path),
+            description,
+            risk_level,
+            remediation: match risk_level {
+                RiskLevel::Critical | RiskLevel::High => format!("Remove {} from the document", path),
+                _ => format!("Review {} for sensitive content", path),
+            },
+            metadata,
+            detection_timestamp: chrono::Utc::now(),
+            hash: format!("{:x}", Sha256::digest(rendered.as_bytes())),
+            confidence: if empty { 0.5 } else { 1.0 },
+        }
+    }
+}
+
+impl Default for DocumentScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::Fixture;
+    use lopdf::dictionary;
+
+    fn document_with_catalog(catalog: Dictionary) -> Document {
+        let mut doc = Document::default();
+        let catalog_id = doc.structure.add_object(catalog);
+        doc.set_root_id(catalog_id);
+        doc
+    }
+
+    #[test]
+    fn test_detects_javascript_action() {
+        let doc = Document::from_pdf(PathBuf::from("javascript.pdf"), Fixture::JavaScript.document().unwrap());
+
+        let artifacts = DocumentScanner::new().scan(&doc);
+        assert!(artifacts.iter().any(|a| a.risk_level == RiskLevel::Critical
+            && a.artifact_type == ArtifactType::JavaScript));
+        assert!(artifacts.iter().any(|a| a.location.ends_with("/OpenAction")));
+    }
+
+    #[test]
+    fn test_clean_document_has_no_artifacts() {
+        let doc = document_with_catalog(dictionary! { "Type" => "Catalog" });
+        assert!(DocumentScanner::new().scan(&doc).is_empty());
+    }
+
+    #[test]
+    fn test_detects_info_metadata() {
+        let mut doc = document_with_catalog(dictionary! { "Type" => "Catalog" });
+        let info_id = doc.structure.add_object(dictionary! {
+            "Author" => Object::string_literal("Jane Doe"),
+        });
+        doc.set_info_id(info_id);
+
+        let artifacts = DocumentScanner::new().scan(&doc);
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].artifact_type, ArtifactType::Metadata);
+        assert_eq!(artifacts[0].metadata["value"], "Jane Doe");
+    }
+
+    #[test]
+    fn test_reports_reference_cycles() {
+        let mut doc = document_with_catalog(dictionary! { "Type" => "Catalog" });
+        let first = doc.structure.new_object_id();
+        let second = doc.structure.add_object(dictionary! { "Kids" => vec![first.into()] });
+        doc.structure.objects.insert(first, Object::Dictionary(dictionary! { "Kids" => vec![second.into()] }));
+
+        let artifacts = DocumentScanner::new().scan(&doc);
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].artifact_type, ArtifactType::Structure);
+        assert_eq!(artifacts[0].metadata["cycle"], format!("{} 0 R -> {} 0 R -> {} 0 R", first.0, second.0, first.0));
+    }
+
+    #[test]
+    fn test_reports_pattern_database_version() {
+        let rules = PatternDatabase::builtin().rules().iter()
+            .filter(|rule| rule.id != "key.open-action")
+            .cloned()
+            .collect();
+        let patterns = Arc::new(PatternDatabase::new(7, "2025-06-05", rules).unwrap());
+        let doc = document_with_catalog(dictionary! {
+            "Type" => "Catalog",
+            "OpenAction" => dictionary! { "S" => "URI" },
+        });
+
+        let artifacts = DocumentScanner::new().with_patterns(patterns).scan(&doc);
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].metadata["pattern_id"], "key.uri");
+        assert_eq!(artifacts[0].metadata[DATABASE_VERSION_KEY], "7");
+    }
+}
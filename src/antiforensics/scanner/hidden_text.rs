@@ -0,0 +1,499 @@
+//! Invisible text detection
+//! Author: kartik4091
+//! Created: 2025-06-04 14:08:31 UTC
+//! Replays page content streams, tracking the graphics and text state, and
+//! flags text that is rendered but cannot be seen: invisible render modes,
+//! fill colors matching the page background, near-zero font sizes, and
+//! positions outside the crop box or active clip.
+
+use std::collections::HashMap;
+use lopdf::{Object, ObjectId};
+use sha2::{Sha256, Digest};
+use tracing::{debug, instrument, warn};
+
+use crate::antiforensics::{types::Document, ArtifactType, ForensicArtifact, RiskLevel};
+use crate::core::content::{self, Operand, Operation};
+use crate::spill::SpillConfig;
+use super::unicode_spoof::decode_pdf_string;
+
+/// Affine matrix `[a b c d e f]` in PDF row-vector convention
+type Matrix = [f64; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Axis-aligned rectangle `[llx lly urx ury]`
+type Rect = [f64; 4];
+
+/// Reason a text run is considered hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HiddenTextReason {
+    /// Text render mode 3 (neither fill nor stroke) or 7 (clip only)
+    InvisibleRenderMode,
+    /// Fill color indistinguishable from the page background
+    BackgroundColor,
+    /// Effective font size below the configured minimum
+    TinyFont,
+    /// Text origin lies outside the crop/media box
+    OffPage,
+    /// Text origin lies outside the active rectangular clip
+    Clipped,
+}
+
+impl HiddenTextReason {
+    /// How reliably this reason alone indicates deliberately hidden text
+    ///
+    /// Render modes are explicit; colors depend on the assumed background
+    /// and small or clipped text is common in legitimate layouts.
+    pub fn confidence(self) -> f64 {
+        match self {
+            HiddenTextReason::InvisibleRenderMode => 0.95,
+            HiddenTextReason::OffPage => 0.85,
+            HiddenTextReason::BackgroundColor => 0.6,
+            HiddenTextReason::Clipped => 0.6,
+            HiddenTextReason::TinyFont => 0.5,
+        }
+    }
+}
+
+/// Detection configuration
+#[derive(Debug, Clone)]
+pub struct HiddenTextConfig {
+    /// Assumed page background as RGB in `0.0..=1.0`
+    pub background: [f64; 3],
+
+    /// Per-channel tolerance when comparing fill and background colors
+    pub color_tolerance: f64,
+
+    /// Effective font sizes (in points) below this are considered unreadable
+    pub min_font_size: f64,
+
+    /// Characters of text captured in artifact descriptions
+    pub excerpt_len: usize,
+
+    /// Large decoded page content is staged on disk
+    pub spill: SpillConfig,
+}
+
+/// Graphics state subset relevant to visibility
+#[derive(Debug, Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    fill: [f64; 3],
+    clip: Option<Rect>,
+    font_size: f64,
+    render_mode: i64,
+    leading: f64,
+}
+
+/// Detects text rendered invisibly
+#[derive(Debug)]
+pub struct HiddenTextDetector {
+    config: HiddenTextConfig,
+}
+
+impl HiddenTextDetector {
+    /// Creates a new detector
+    pub fn new(config: HiddenTextConfig) -> Self {
+        Self { config }
+    }
+
+    /// Scans every page of a document
+    #[instrument(skip(self, doc))]
+    pub fn scan_document(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        let mut artifacts = Vec::new();
+
+        for (page_number, page_id) in doc.pages() {
+            let mut buffer = match doc.page_content_buffer(page_id, &self.config.spill) {
+                Ok(buffer) => buffer,
+                Err(e) => {
+                    warn!("Failed to read content of page {}: {}", page_number, e);
+                    continue;
+                }
+            };
+            let data = match buffer.contents() {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Failed to read back content of page {}: {}", page_number, e);
+                    continue;
+                }
+            };
+
+            match content::parse(&data) {
+                Ok(operations) => {
+                    let bounds = page_bounds(doc, page_id);
+                    artifacts.extend(self.scan_content(&operations, page_id, page_number, bounds));
+                }
+                Err(e) => warn!("Failed to decode content of page {}: {}", page_number, e),
+            }
+        }
+
+        debug!("Hidden text scan produced {} artifacts", artifacts.len());
+        artifacts
+    }
+
+    /// Replays one parsed content stream
+    fn scan_content(
+        &self,
+        operations: &[Operation],
+        page_id: ObjectId,
+        page_number: u32,
+        bounds: Rect,
+    ) -> Vec<ForensicArtifact> {
+        let mut artifacts = Vec::new();
+        let mut state = GraphicsState {
+            ctm: IDENTITY,
+            fill: [0.0; 3],
+            clip: None,
+            font_size: 0.0,
+            render_mode: 0,
+            leading: 0.0,
+        };
+        let mut stack: Vec<GraphicsState> = Vec::new();
+        let mut text_matrix = IDENTITY;
+        let mut line_matrix = IDENTITY;
+        let mut pending_rect: Option<Rect> = None;
+        let mut pending_clip = false;
+
+        for (op_index, op) in operations.iter().enumerate() {
+            let nums = op.numbers();
+
+            match op.operator.as_str() {
+                "q" => stack.push(state.clone()),
+                "Q" => {
+                    if let Some(saved) = stack.pop() {
+                        state = saved;
+                    }
+                }
+                "cm" if nums.len() == 6 => {
+                    state.ctm = multiply(&[nums[0], nums[1], nums[2], nums[3], nums[4], nums[5]], &state.ctm);
+                }
+                "re" if nums.len() == 4 => {
+                    let (x0, y0) = apply(&state.ctm, nums[0], nums[1]);
+                    let (x1, y1) = apply(&state.ctm, nums[0] + nums[2], nums[1] + nums[3]);
+                    pending_rect = Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]);
+                }
+                "W" | "W*" => pending_clip = true,
+                "n" | "f" | "F" | "f*" | "S" | "s" | "B" | "B*" | "b" | "b*" => {
+                    if pending_clip {
+                        if let Some(rect) = pending_rect {
+                            state.clip = Some(match state.clip {
+                                Some(clip) => intersect(&clip, &rect),
+                                None => rect,
+                            });
+                        }
+                    }
+                    pending_clip = false;
+                    pending_rect = None;
+                }
+                "g" if nums.len() == 1 => state.fill = [nums[0]; 3],
+                "rg" if nums.len() == 3 => state.fill = [nums[0], nums[1], nums[2]],
+                "k" if nums.len() == 4 => state.fill = cmyk_to_rgb(&nums),
+                "sc" | "scn" => match nums.len() {
+                    1 => state.fill = [nums[0]; 3],
+                    3 => state.fill = [nums[0], nums[1], nums[2]],
+                    4 => state.fill = cmyk_to_rgb(&nums),
+                    _ => {}
+                },
+                "cs" => state.fill = [0.0; 3],
+                "BT" => {
+                    text_matrix = IDENTITY;
+                    line_matrix = IDENTITY;
+                }
+                "Tf" if nums.len() == 1 => state.font_size = nums[0],
+                "Tr" if nums.len() == 1 => state.render_mode = nums[0] as i64,
+                "TL" if nums.len() == 1 => state.leading = nums[0],
+                "Tm" if nums.len() == 6 => {
+                    line_matrix = [nums[0], nums[1], nums[2], nums[3], nums[4], nums[5]];
+                    text_matrix = line_matrix;
+                }
+                "Td" | "TD" if nums.len() == 2 => {
+                    if op.operator == "TD" {
+                        state.leading = -nums[1];
+                    }
+                    line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, nums[0], nums[1]], &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "T*" => {
+                    line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    if op.operator == "'" || op.operator == "\"" {
+                        line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                        text_matrix = line_matrix;
+                    }
+
+                    let text = operand_text(&op.operands);
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+
+                    let rendering = multiply(&text_matrix, &state.ctm);
+                    let reasons = self.classify(&state, &rendering, bounds);
+                    if !reasons.is_empty() {
+                        artifacts.push(self.artifact(page_id, page_number, op_index, op, &text, &reasons));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        artifacts
+    }
+
+    /// Determines why text drawn with the given state would be invisible
+    fn classify(&self, state: &GraphicsState, rendering: &Matrix, bounds: Rect) -> Vec<HiddenTextReason> {
+        let mut reasons = Vec::new();
+
+        if state.render_mode == 3 || state.render_mode == 7 {
+            reasons.push(HiddenTextReason::InvisibleRenderMode);
+        }
+
+        let fills = matches!(state.render_mode, 0 | 2 | 4 | 6);
+        if fills && state.fill.iter().zip(self.config.background.iter())
+            .all(|(f, b)| (f - b).abs() <= self.config.color_tolerance)
+        {
+            reasons.push(HiddenTextReason::BackgroundColor);
+        }
+
+        let scale = (rendering[0] * rendering[3] - rendering[1] * rendering[2]).abs().sqrt();
+        if (state.font_size * scale).abs() < self.config.min_font_size {
+            reasons.push(HiddenTextReason::TinyFont);
+        }
+
+        let (x, y) = (rendering[4], rendering[5]);
+        if !contains(&bounds, x, y) {
+            reasons.push(HiddenTextReason::OffPage);
+        } else if let Some(clip) = state.clip {
+            if !contains(&clip, x, y) {
+                reasons.push(HiddenTextReason::Clipped);
+            }
+        }
+
+        reasons
+    }
+
+    /// Builds an artifact for a hidden text run
+    fn artifact(
+        &self,
+        page_id: ObjectId,
+        page_number: u32,
+        op_index: usize,
+        op: &Operation,
+        text: &str,
+        reasons: &[HiddenTextReason],
+    ) -> ForensicArtifact {
+        let excerpt: String = text.chars().take(self.config.excerpt_len).collect();
+        let reason_list = reasons.iter().map(|r| format!("{:?}", r)).collect::<Vec<_>>().join(",");
+
+        let risk_level = if reasons.iter().any(|r| *r != HiddenTextReason::TinyFont) {
+            RiskLevel::High
+        } else {
+            RiskLevel::Medium
+        };
+
+        let mut metadata = HashMap::new();
+        metadata.insert("page".into(), page_number.to_string());
+        metadata.insert("operation_index".into(), op_index.to_string());
+        metadata.insert("byte_range".into(), format!("{}-{}", op.span.start, op.span.end));
+        metadata.insert("reasons".into(), reason_list.clone());
+        metadata.insert("text".into(), excerpt.clone());
+
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type: ArtifactType::Content,
+            location: format!("{} {} R:page {} op {}", page_id.0, page_id.1, page_number, op_index),
+            description: format!("Text rendered invisibly ({}): {:?}", reason_list, excerpt),
+            risk_level,
+            remediation: "Remove or reveal the hidden text run before distribution".into(),
+            metadata,
+            detection_timestamp: chrono::Utc::now(),
+            hash: format!("{:x}", Sha256::digest(text.as_bytes())),
+            confidence: ForensicArtifact::combine_confidence(reasons.iter().map(|r| r.confidence())),
+        }
+    }
+}
+
+/// Resolves the crop box (falling back to the media box) through page inheritance
+fn page_bounds(doc: &Document, page_id: ObjectId) -> Rect {
+    let mut media = None;
+    let mut crop = None;
+    let mut current = Some(page_id);
+
+    // Bounded walk: malformed trees can contain Parent cycles
+    for _ in 0..32 {
+        let Some(id) = current else { break };
+        let Some(dict) = doc.dictionary(id) else { break };
+        if crop.is_none() {
+            crop = dict.get(b"CropBox").ok().and_then(|o| rect(doc, o));
+        }
+        if media.is_none() {
+            media = dict.get(b"MediaBox").ok().and_then(|o| rect(doc, o));
+        }
+        current = dict.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+
+    crop.or(media).unwrap_or([0.0, 0.0, 612.0, 792.0])
+}
+
+fn rect(doc: &Document, object: &Object) -> Option<Rect> {
+    let object = doc.resolve(object)?;
+    let values: Vec<f64> = object.as_array().ok()?.iter().filter_map(number).collect();
+    if values.len() != 4 {
+        return None;
+    }
+    Some([
+        values[0].min(values[2]),
+        values[1].min(values[3]),
+        values[0].max(values[2]),
+        values[1].max(values[3]),
+    ])
+}
+
+fn number(object: &Object) -> Option<f64> {
+    match object {
+        Object::Integer(i) => Some(*i as f64),
+        Object::Real(r) => Some(*r as f64),
+        _ => None,
+    }
+}
+
+fn operand_text(operands: &[Operand]) -> String {
+    let mut text = String::new();
+    for operand in operands {
+        match operand {
+            Operand::String(bytes) | Operand::HexString(bytes) => text.push_str(&decode_pdf_string(bytes)),
+            Operand::Array(items) => text.push_str(&operand_text(items)),
+            _ => {}
+        }
+    }
+    text
+}
+
+fn multiply(m1: &Matrix, m2: &Matrix) -> Matrix {
+    [
+        m1[0] * m2[0] + m1[1] * m2[2],
+        m1[0] * m2[1] + m1[1] * m2[3],
+        m1[2] * m2[0] + m1[3] * m2[2],
+        m1[2] * m2[1] + m1[3] * m2[3],
+        m1[4] * m2[0] + m1[5] * m2[2] + m2[4],
+        m1[4] * m2[1] + m1[5] * m2[3] + m2[5],
+    ]
+}
+
+fn apply(m: &Matrix, x: f64, y: f64) -> (f64, f64) {
+    (x * m[0] + y * m[2] + m[4], x * m[1] + y * m[3] + m[5])
+}
+
+fn intersect(a: &Rect, b: &Rect) -> Rect {
+    [a[0].max(b[0]), a[1].max(b[1]), a[2].min(b[2]), a[3].min(b[3])]
+}
+
+fn contains(r: &Rect, x: f64, y: f64) -> bool {
+    x >= r[0] && x <= r[2] && y >= r[1] && y <= r[3]
+}
+
+fn cmyk_to_rgb(cmyk: &[f64]) -> [f64; 3] {
+    let k = 1.0 - cmyk[3];
+    [(1.0 - cmyk[0]) * k, (1.0 - cmyk[1]) * k, (1.0 - cmyk[2]) * k]
+}
+
+impl Default for HiddenTextConfig {
+    fn default() -> Self {
+        Self {
+            background: [1.0, 1.0, 1.0],
+            color_tolerance: 0.02,
+            min_font_size: 1.0,
+            excerpt_len: 80,
+            spill: SpillConfig::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    fn document_with_content(content: &str) -> Document {
+        let mut doc = Document::default();
+        let pages_id = doc.structure.new_object_id();
+        let content_id = doc.structure.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+        let page_id = doc.structure.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        doc.structure.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        }));
+        let catalog_id = doc.structure.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.set_root_id(catalog_id);
+        doc
+    }
+
+    fn reasons(content: &str) -> Vec<String> {
+        HiddenTextDetector::new(HiddenTextConfig::default())
+            .scan_document(&document_with_content(content))
+            .into_iter()
+            .map(|a| a.metadata["reasons"].clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_visible_text_not_flagged() {
+        assert!(reasons("BT /F1 12 Tf 72 720 Td (Hello) Tj ET").is_empty());
+    }
+
+    #[test]
+    fn test_white_on_white() {
+        assert_eq!(reasons("1 1 1 rg BT /F1 12 Tf 72 720 Td (secret) Tj ET"), vec!["BackgroundColor"]);
+    }
+
+    #[test]
+    fn test_artifact_records_byte_range() {
+        let artifacts = HiddenTextDetector::new(HiddenTextConfig::default())
+            .scan_document(&document_with_content("1 1 1 rg BT /F1 12 Tf 72 720 Td (secret) Tj ET"));
+        assert_eq!(artifacts[0].metadata["byte_range"], "32-43");
+    }
+
+    #[test]
+    fn test_invisible_render_mode() {
+        assert_eq!(reasons("BT 3 Tr /F1 12 Tf 72 720 Td (ocr layer) Tj ET"), vec!["InvisibleRenderMode"]);
+    }
+
+    #[test]
+    fn test_confidence_reflects_reasons() {
+        let detector = HiddenTextDetector::new(HiddenTextConfig::default());
+        let tiny = detector.scan_document(&document_with_content("BT /F1 0.2 Tf 72 720 Td (tiny) Tj ET"));
+        let hidden = detector.scan_document(&document_with_content("BT 3 Tr /F1 0.2 Tf 72 720 Td (both) Tj ET"));
+
+        assert!((tiny[0].confidence - 0.5).abs() < 1e-9);
+        assert!(hidden[0].confidence > HiddenTextReason::InvisibleRenderMode.confidence());
+    }
+
+    #[test]
+    fn test_tiny_font_is_medium() {
+        let artifacts = HiddenTextDetector::new(HiddenTextConfig::default())
+            .scan_document(&document_with_content("BT /F1 0.2 Tf 72 720 Td (tiny) Tj ET"));
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].metadata["reasons"], "TinyFont");
+        assert_eq!(artifacts[0].risk_level, RiskLevel::Medium);
+    }
+
+    #[test]
+    fn test_off_page_via_ctm() {
+        assert_eq!(reasons("q 1 0 0 1 2000 0 cm BT /F1 12 Tf 10 10 Td (far) Tj ET Q"), vec!["OffPage"]);
+    }
+
+    #[test]
+    fn test_clipped_text() {
+        assert_eq!(
+            reasons("q 0 0 100 100 re W n BT /F1 12 Tf 300 300 Td (clipped) Tj ET Q BT /F1 12 Tf 300 300 Td (shown) Tj ET"),
+            vec!["Clipped"]
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! ZUGFeRD / Factur-X invoice attachment recognition
+//! Author: kartik4091
+//! Created: 2025-06-04 21:52:16 UTC
+//! Hybrid e-invoices are PDF/A-3 documents carrying the machine-readable
+//! invoice as an embedded XML file with a reserved name, associated with
+//! the document through the catalog's /AF array. Stripping attachments
+//! wholesale destroys the legally relevant part of such invoices, so
+//! these attachments are recognized by name, their XML is checked for
+//! the expected structure and profile, and cleaners can keep the ones
+//! that pass.
+//!
+//! The XML check is structural, not schema validation: root element,
+//! guideline (profile) identifier and document number must be present,
+//! and DTDs are refused outright since nothing legitimate needs one.
+
+use std::sync::OnceLock;
+use lopdf::{Object, ObjectId};
+use regex::bytes::Regex;
+use tracing::instrument;
+
+use crate::antiforensics::{types::Document, ArtifactType, ForensicArtifact, RiskLevel};
+use super::portfolio::entries;
+
+/// Embedded invoices larger than this are not parsed
+const MAX_INVOICE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Invoice standard, as told by the attachment name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceStandard {
+    /// Factur-X 1.0 / ZUGFeRD 2.1 and later (`factur-x.xml`)
+    FacturX,
+    /// ZUGFeRD 2.0 (`zugferd-invoice.xml`)
+    Zugferd2,
+    /// ZUGFeRD 1.0 (`ZUGFeRD-invoice.xml`)
+    Zugferd1,
+    /// XRechnung profile of ZUGFeRD 2.1.1 and later (`xrechnung.xml`)
+    XRechnung,
+}
+
+impl InvoiceStandard {
+    /// Standard whose reserved attachment name is `name`
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        // ZUGFeRD 1 and 2.0 share a name; the root element tells them apart
+        match name.to_ascii_lowercase().as_str() {
+            "factur-x.xml" => Some(InvoiceStandard::FacturX),
+            "zugferd-invoice.xml" => Some(InvoiceStandard::Zugferd2),
+            "xrechnung.xml" => Some(InvoiceStandard::XRechnung),
+            _ => None,
+        }
+    }
+
+    fn root_element(&self) -> &'static str {
+        match self {
+            InvoiceStandard::Zugferd1 => "CrossIndustryDocument",
+            _ => "CrossIndustryInvoice",
+        }
+    }
+}
+
+/// Conformance profile from the guideline identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceProfile {
+    Minimum,
+    BasicWl,
+    Basic,
+    /// EN 16931, called Comfort in ZUGFeRD
+    En16931,
+    Extended,
+    XRechnung,
+}
+
+impl InvoiceProfile {
+    /// Profile named by a guideline URN such as `urn:factur-x.eu:1p0:basicwl`
+    pub fn from_guideline(id: &str) -> Option<Self> {
+        let id = id.trim().to_ascii_lowercase();
+        if id.contains("xrechnung") {
+            return Some(InvoiceProfile::XRechnung);
+        }
+        if id == "urn:cen.eu:en16931:2017" {
+            return Some(InvoiceProfile::En16931);
+        }
+        match id.rsplit(':').next()? {
+            "minimum" => Some(InvoiceProfile::Minimum),
+            "basicwl" => Some(InvoiceProfile::BasicWl),
+            "basic" => Some(InvoiceProfile::Basic),
+            "comfort" | "en16931" => Some(InvoiceProfile::En16931),
+            "extended" => Some(InvoiceProfile::Extended),
+            _ => None,
+        }
+    }
+}
+
+/// Reason an invoice attachment is not compliant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvoiceIssue {
+    /// Too large to check
+    TooLarge(usize),
+    NotUtf8,
+    /// Declares a DTD, the vehicle for entity expansion and external entities
+    Doctype,
+    /// Root element is not the one the standard prescribes
+    UnexpectedRoot(String),
+    /// Root element is never closed
+    Truncated,
+    MissingGuideline,
+    UnknownProfile(String),
+    MissingDocumentId,
+    /// Not listed in the catalog's /AF array, as PDF/A-3 requires
+    NotAssociated,
+    /// /AFRelationship missing or not Data, Alternative or Source
+    Relationship(Option<String>),
+}
+
+/// A recognized invoice attachment and the outcome of checking it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvoiceAttachment {
+    pub name: String,
+    pub filespec_id: Option<ObjectId>,
+    pub stream_id: ObjectId,
+    pub standard: InvoiceStandard,
+    pub profile: Option<InvoiceProfile>,
+    /// Invoice number from the exchanged document header
+    pub document_id: Option<String>,
+    pub issues: Vec<InvoiceIssue>,
+}
+
+impl InvoiceAttachment {
+    pub fn is_compliant(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Embedded files with a reserved invoice name, checked
+pub fn invoice_attachments(doc: &lopdf::Document) -> Vec<InvoiceAttachment> {
+    let associated = associated_files(doc);
+    entries(doc).into_iter()
+        .filter_map(|entry| {
+            let mut standard = InvoiceStandard::from_file_name(&entry.name)?;
+            let stream = doc.get_object(entry.stream_id).and_then(Object::as_stream).ok()?;
+            let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+            if standard == InvoiceStandard::Zugferd2 && root_local_name(&content).as_deref() == Some("CrossIndustryDocument") {
+                standard = InvoiceStandard::Zugferd1;
+            }
+
+            let mut attachment = InvoiceAttachment {
+                name: entry.name,
+                filespec_id: entry.filespec_id,
+                stream_id: entry.stream_id,
+                standard,
+                profile: None,
+                document_id: None,
+                issues: Vec::new(),
+            };
+            check_xml(&content, &mut attachment);
+            check_association(doc, &associated, &mut attachment);
+            Some(attachment)
+        })
+        .collect()
+}
+
+fn check_xml(content: &[u8], attachment: &mut InvoiceAttachment) {
+    if content.len() > MAX_INVOICE_SIZE {
+        attachment.issues.push(InvoiceIssue::TooLarge(content.len()));
+        return;
+    }
+    if std::str::from_utf8(content).is_err() {
+        attachment.issues.push(InvoiceIssue::NotUtf8);
+        return;
+    }
+    if content.windows(9).any(|w| w.eq_ignore_ascii_case(b"<!DOCTYPE")) {
+        attachment.issues.push(InvoiceIssue::Doctype);
+        return;
+    }
+
+    let expected = attachment.standard.root_element();
+    match root_tag(content) {
+        Some(tag) if local_name(&tag) == expected => {
+            let closing = format!("</{}>", tag);
+            if !content.trim_ascii_end().ends_with(closing.as_bytes()) {
+                attachment.issues.push(InvoiceIssue::Truncated);
+            }
+        }
+        other => attachment.issues.push(InvoiceIssue::UnexpectedRoot(other.unwrap_or_default())),
+    }
+
+    match first_capture(guideline_pattern(), content) {
+        None => attachment.issues.push(InvoiceIssue::MissingGuideline),
+        Some(guideline) => match InvoiceProfile::from_guideline(&guideline) {
+            Some(profile) => attachment.profile = Some(profile),
+            None => attachment.issues.push(InvoiceIssue::UnknownProfile(guideline)),
+        },
+    }
+
+    attachment.document_id = first_capture(document_id_pattern(), content).filter(|id| !id.is_empty());
+    if attachment.document_id.is_none() {
+        attachment.issues.push(InvoiceIssue::MissingDocumentId);
+    }
+}
+
+fn check_association(doc: &lopdf::Document, associated: &[ObjectId], attachment: &mut InvoiceAttachment) {
+    let Some(filespec_id) = attachment.filespec_id else {
+        attachment.issues.push(InvoiceIssue::NotAssociated);
+        return;
+    };
+    if !associated.contains(&filespec_id) {
+        attachment.issues.push(InvoiceIssue::NotAssociated);
+    }
+    let relationship = doc.get_dictionary(filespec_id).ok()
+        .and_then(|spec| spec.get(b"AFRelationship").ok())
+        .and_then(|value| value.as_name().ok())
+        .map(|name| String::from_utf8_lossy(name).into_owned());
+    if !matches!(relationship.as_deref(), Some("Data" | "Alternative" | "Source")) {
+        attachment.issues.push(InvoiceIssue::Relationship(relationship));
+    }
+}
+
+/// File specifications listed in the catalog's /AF array
+pub fn associated_files(doc: &lopdf::Document) -> Vec<ObjectId> {
+    let catalog = doc.trailer.get(b"Root").and_then(Object::as_reference)
+        .and_then(|root| doc.get_dictionary(root));
+    let af = catalog.ok().and_then(|catalog| catalog.get(b"AF").ok());
+    let items = match af {
+        Some(Object::Array(items)) => items,
+        Some(Object::Reference(id)) => match doc.get_object(*id) {
+            Ok(Object::Array(items)) => items,
+            _ => return Vec::new(),
+        },
+        _ => return Vec::new(),
+    };
+    items.iter().filter_map(|item| item.as_reference().ok()).collect()
+}
+
+/// Name of the first element, skipping the declaration, comments and processing instructions
+fn root_tag(content: &[u8]) -> Option<String> {
+    let mut rest = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    loop {
+        rest = rest.trim_ascii_start();
+        if rest.starts_with(b"<?") {
+            rest = &rest[find(rest, b"?>")? + 2..];
+        } else if rest.starts_with(b"<!--") {
+            rest = &rest[find(rest, b"-->")? + 3..];
+        } else if rest.starts_with(b"<") {
+            let end = rest[1..].iter().position(|b| b.is_ascii_whitespace() || *b == b'>' || *b == b'/')? + 1;
+            return Some(String::from_utf8_lossy(&rest[1..end]).into_owned());
+        } else {
+            return None;
+        }
+    }
+}
+
+fn root_local_name(content: &[u8]) -> Option<String> {
+    root_tag(content).map(|tag| local_name(&tag).to_string())
+}
+
+fn local_name(tag: &str) -> &str {
+    tag.rsplit(':').next().unwrap_or(tag)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn first_capture(pattern: &Regex, content: &[u8]) -> Option<String> {
+    let captures = pattern.captures(content)?;
+    Some(String::from_utf8_lossy(captures.get(1)?.as_bytes()).trim().to_string())
+}
+
+fn guideline_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?s)GuidelineSpecifiedDocumentContextParameter>\s*<(?:[\w.-]+:)?ID(?:\s[^>]*)?>([^<]+)<")
+            .expect("valid pattern")
+    })
+}
+
+fn document_id_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?s)<(?:[\w.-]+:)?(?:Header)?ExchangedDocument>\s*<(?:[\w.-]+:)?ID(?:\s[^>]*)?>([^<]*)<")
+            .expect("valid pattern")
+    })
+}
+
+/// Reports invoice attachments and whether they are compliant
+#[derive(Debug, Clone, Default)]
+pub struct InvoiceScanner;
+
+impl InvoiceScanner {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        invoice_attachments(&doc.structure).into_iter()
+            .map(|invoice| {
+                let mut metadata = std::collections::HashMap::new();
+                metadata.insert("standard".to_string(), format!("{:?}", invoice.standard));
+                if let Some(profile) = invoice.profile {
+                    metadata.insert("profile".to_string(), format!("{:?}", profile));
+                }
+                if let Some(id) = &invoice.document_id {
+                    metadata.insert("document_id".to_string(), id.clone());
+                }
+                let (description, risk_level, remediation) = if invoice.is_compliant() {
+                    (
+                        format!("{:?} e-invoice attachment {}", invoice.standard, invoice.name),
+                        RiskLevel::None,
+                        "Keep with the invoice attachment policy; removing it invalidates the e-invoice",
+                    )
+                } else {
+                    metadata.insert("issues".to_string(), format!("{:?}", invoice.issues));
+                    (
+                        format!("Non-compliant {:?} e-invoice attachment {}", invoice.standard, invoice.name),
+                        RiskLevel::Low,
+                        "Inspect the attachment; it will be stripped with other attachments",
+                    )
+                };
+                ForensicArtifact {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    artifact_type: ArtifactType::EmbeddedFile,
+                    location: format!("/Root/Names/EmbeddedFiles/{}", invoice.name),
+                    description,
+                    risk_level,
+                    remediation: remediation.into(),
+                    metadata,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    pub(crate) const FACTUR_X: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rsm:CrossIndustryInvoice xmlns:rsm="urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100"
+    xmlns:ram="urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100">
+  <rsm:ExchangedDocumentContext>
+    <ram:GuidelineSpecifiedDocumentContextParameter>
+      <ram:ID>urn:cen.eu:en16931:2017#compliant#urn:factur-x.eu:1p0:basic</ram:ID>
+    </ram:GuidelineSpecifiedDocumentContextParameter>
+  </rsm:ExchangedDocumentContext>
+  <rsm:ExchangedDocument>
+    <ram:ID>INV-2025-0042</ram:ID>
+  </rsm:ExchangedDocument>
+</rsm:CrossIndustryInvoice>
+"#;
+
+    /// Invoice with a compliant `factur-x.xml` and an unrelated attachment
+    pub(crate) fn hybrid_invoice(xml: &str) -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let xml = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile", "Subtype" => "text/xml" }, xml.as_bytes().to_vec()));
+        let other = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, b"MZ\x90\x00".to_vec()));
+        let xml_spec = doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal("factur-x.xml"),
+            "UF" => Object::string_literal("factur-x.xml"),
+            "AFRelationship" => "Data",
+            "EF" => dictionary! { "F" => xml },
+        });
+        let other_spec = doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal("tool.exe"),
+            "EF" => dictionary! { "F" => other },
+        });
+        let catalog = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "AF" => vec![Object::Reference(xml_spec)],
+            "Names" => dictionary! {
+                "EmbeddedFiles" => dictionary! {
+                    "Names" => vec![
+                        Object::string_literal("factur-x.xml"), Object::Reference(xml_spec),
+                        Object::string_literal("tool.exe"), Object::Reference(other_spec),
+                    ],
+                },
+            },
+        });
+        doc.trailer.set("Root", catalog);
+        doc
+    }
+
+    #[test]
+    fn test_compliant_invoice_recognized() {
+        let invoices = invoice_attachments(&hybrid_invoice(FACTUR_X));
+        assert_eq!(invoices.len(), 1);
+        let invoice = &invoices[0];
+        assert_eq!(invoice.standard, InvoiceStandard::FacturX);
+        assert_eq!(invoice.profile, Some(InvoiceProfile::Basic));
+        assert_eq!(invoice.document_id.as_deref(), Some("INV-2025-0042"));
+        assert!(invoice.is_compliant(), "{:?}", invoice.issues);
+    }
+
+    #[test]
+    fn test_doctype_and_wrong_root_rejected() {
+        let with_dtd = FACTUR_X.replace("<rsm:Cross", "<!DOCTYPE x [<!ENTITY e SYSTEM \"file:///etc/passwd\">]>\n<rsm:Cross");
+        assert_eq!(invoice_attachments(&hybrid_invoice(&with_dtd))[0].issues, vec![InvoiceIssue::Doctype]);
+
+        let invoices = invoice_attachments(&hybrid_invoice("<html><body>invoice</body></html>"));
+        assert!(invoices[0].issues.contains(&InvoiceIssue::UnexpectedRoot("html".into())));
+        assert!(invoices[0].issues.contains(&InvoiceIssue::MissingGuideline));
+    }
+
+    #[test]
+    fn test_profiles_from_guidelines() {
+        assert_eq!(InvoiceProfile::from_guideline("urn:factur-x.eu:1p0:minimum"), Some(InvoiceProfile::Minimum));
+        assert_eq!(InvoiceProfile::from_guideline("urn:factur-x.eu:1p0:basicwl"), Some(InvoiceProfile::BasicWl));
+        assert_eq!(InvoiceProfile::from_guideline("urn:cen.eu:en16931:2017"), Some(InvoiceProfile::En16931));
+        assert_eq!(
+            InvoiceProfile::from_guideline("urn:ferd:CrossIndustryDocument:invoice:1p0:comfort"),
+            Some(InvoiceProfile::En16931),
+        );
+        assert_eq!(
+            InvoiceProfile::from_guideline("urn:cen.eu:en16931:2017#compliant#urn:xoev-de:kosit:standard:xrechnung_2.3"),
+            Some(InvoiceProfile::XRechnung),
+        );
+        assert_eq!(InvoiceProfile::from_guideline("urn:example:custom"), None);
+    }
+
+    #[test]
+    fn test_unassociated_invoice_flagged() {
+        let mut doc = hybrid_invoice(FACTUR_X);
+        let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.get_dictionary_mut(root).unwrap().remove(b"AF");
+        assert_eq!(invoice_attachments(&doc)[0].issues, vec![InvoiceIssue::NotAssociated]);
+    }
+}
@@ -0,0 +1,357 @@
+//! Document language and script detection
+//! Author: kartik4091
+//! Created: 2025-06-04 22:31:47 UTC
+//! Classifies the text shown on a document's pages by Unicode script and
+//! guesses the language written in each script: scripts used by a single
+//! major language map to it directly, Latin and Cyrillic text is scored
+//! against short function-word lists. Coarse by design; it is meant for
+//! routing documents to language-specific processing, not for linguistics.
+//!
+//! Text is taken from text-showing operators as stored, so documents
+//! whose fonts use custom encodings may come out undetermined (`und`).
+
+use std::collections::{BTreeMap, HashMap};
+use lopdf::{content::Content, Object};
+use serde::{Serialize, Deserialize};
+use tracing::instrument;
+
+use crate::antiforensics::{types::Document, ArtifactType, ForensicArtifact, RiskLevel};
+use super::unicode_spoof::decode_pdf_string;
+
+/// Text-showing operators whose operands carry rendered text
+const TEXT_OPERATORS: [&str; 4] = ["Tj", "TJ", "'", "\""];
+
+/// Characters of page text examined at most
+const MAX_SAMPLE_CHARS: usize = 100_000;
+
+/// Scripts making up less of the text than this are not reported
+const MIN_SCRIPT_SHARE: f32 = 0.1;
+
+/// Function-word hits needed before a Latin or Cyrillic guess is made
+const MIN_WORD_HITS: usize = 3;
+
+/// Code reported when the language cannot be determined
+pub const UNDETERMINED: &str = "und";
+
+/// Metadata key listing detected language codes on the scan artifact
+pub const LANGUAGES_KEY: &str = "languages";
+
+/// Writing system of a letter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Hangul,
+    /// Hiragana and Katakana
+    Kana,
+    Han,
+}
+
+impl Script {
+    pub fn of(c: char) -> Option<Self> {
+        match c as u32 {
+            0x41..=0x5A | 0x61..=0x7A | 0xC0..=0x24F | 0x1E00..=0x1EFF if c.is_alphabetic() => Some(Script::Latin),
+            0x370..=0x3FF | 0x1F00..=0x1FFF => Some(Script::Greek),
+            0x400..=0x52F => Some(Script::Cyrillic),
+            0x591..=0x5F4 => Some(Script::Hebrew),
+            0x600..=0x6FF | 0x750..=0x77F | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Some(Script::Arabic),
+            0x900..=0x97F => Some(Script::Devanagari),
+            0xE00..=0xE7F => Some(Script::Thai),
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Some(Script::Hangul),
+            0x3040..=0x30FF => Some(Script::Kana),
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF => Some(Script::Han),
+            _ => None,
+        }
+    }
+}
+
+/// Function words by language, for scripts shared by many languages
+const LATIN_WORDS: [(&str, &[&str]); 7] = [
+    ("en", &["the", "and", "of", "to", "is", "in", "that", "for", "with", "this", "are", "be", "by", "on", "not"]),
+    ("de", &["der", "die", "und", "das", "ist", "nicht", "mit", "den", "ein", "eine", "zu", "von", "auf", "sich", "für"]),
+    ("fr", &["le", "la", "les", "et", "des", "est", "une", "dans", "pour", "que", "qui", "pas", "sur", "avec", "du"]),
+    ("es", &["el", "los", "las", "y", "es", "una", "para", "por", "con", "que", "del", "como", "pero", "está", "se"]),
+    ("it", &["il", "gli", "e", "di", "che", "è", "per", "una", "non", "sono", "con", "della", "nel", "alla", "anche"]),
+    ("pt", &["o", "os", "e", "não", "uma", "para", "com", "que", "do", "da", "em", "são", "pelo", "mais", "como"]),
+    ("nl", &["de", "het", "een", "en", "van", "is", "niet", "dat", "op", "zijn", "met", "voor", "ook", "aan", "wordt"]),
+];
+
+const CYRILLIC_WORDS: [(&str, &[&str]); 3] = [
+    ("ru", &["и", "в", "не", "что", "на", "это", "как", "он", "с", "по", "но", "из", "для", "был", "его"]),
+    ("uk", &["і", "та", "не", "що", "на", "це", "як", "він", "з", "до", "але", "для", "був", "його", "є"]),
+    ("bg", &["и", "в", "не", "че", "на", "това", "как", "той", "с", "за", "но", "от", "се", "са", "е"]),
+];
+
+/// One language found in the text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectedLanguage {
+    /// ISO 639-1 code, or `und`
+    pub code: String,
+    pub script: Script,
+    /// Fraction of the text's letters in this language's script
+    pub share: f32,
+    /// How clearly the text pointed at this language, 0 to 1
+    pub confidence: f32,
+}
+
+/// Languages and scripts of a document's text
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LanguageProfile {
+    /// By decreasing share
+    pub languages: Vec<DetectedLanguage>,
+    /// Letter counts by script
+    pub scripts: BTreeMap<Script, usize>,
+}
+
+impl LanguageProfile {
+    /// Language with the largest share of the text
+    pub fn primary(&self) -> Option<&DetectedLanguage> {
+        self.languages.first()
+    }
+
+    /// Language codes, primary first
+    pub fn codes(&self) -> Vec<&str> {
+        self.languages.iter().map(|language| language.code.as_str()).collect()
+    }
+}
+
+/// Detects the languages of a text
+pub fn detect_text(text: &str) -> LanguageProfile {
+    let mut profile = LanguageProfile::default();
+    let mut words: HashMap<Script, Vec<String>> = HashMap::new();
+    for word in text.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()) {
+        let Some(script) = word.chars().find_map(Script::of) else { continue };
+        for c in word.chars() {
+            if let Some(letter_script) = Script::of(c) {
+                *profile.scripts.entry(letter_script).or_default() += 1;
+            }
+        }
+        words.entry(script).or_default().push(word.to_lowercase());
+    }
+
+    let letters: usize = profile.scripts.values().sum();
+    if letters == 0 {
+        return profile;
+    }
+    // Japanese mixes kanji with kana; Han alone reads as Chinese
+    let kana = profile.scripts.get(&Script::Kana).copied().unwrap_or(0);
+    let mut shares: Vec<(Script, usize)> = profile.scripts.iter()
+        .map(|(script, count)| (*script, *count))
+        .filter(|(script, _)| !(kana > 0 && *script == Script::Han))
+        .map(|(script, count)| match script {
+            Script::Kana => (script, count + profile.scripts.get(&Script::Han).copied().unwrap_or(0)),
+            _ => (script, count),
+        })
+        .collect();
+    shares.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    for (script, count) in shares {
+        let share = count as f32 / letters as f32;
+        if share < MIN_SCRIPT_SHARE {
+            continue;
+        }
+        let script_words = words.get(&script).map(Vec::as_slice).unwrap_or_default();
+        let (code, confidence) = match script {
+            Script::Latin => by_function_words(script_words, &LATIN_WORDS),
+            Script::Cyrillic => by_function_words(script_words, &CYRILLIC_WORDS),
+            Script::Arabic => {
+                // Letters Persian adds to the Arabic alphabet
+                let persian = script_words.iter().flat_map(|w| w.chars()).any(|c| matches!(c, 'پ' | 'چ' | 'ژ' | 'گ'));
+                (if persian { "fa" } else { "ar" }, 0.8)
+            }
+            Script::Greek => ("el", 0.95),
+            Script::Hebrew => ("he", 0.9),
+            Script::Devanagari => ("hi", 0.8),
+            Script::Thai => ("th", 0.95),
+            Script::Hangul => ("ko", 0.95),
+            Script::Kana => ("ja", 0.95),
+            Script::Han => ("zh", 0.85),
+        };
+        profile.languages.push(DetectedLanguage { code: code.into(), script, share, confidence });
+    }
+    profile
+}
+
+/// Best-scoring language by function-word hits, with the winner's share of all hits
+fn by_function_words(words: &[String], lists: &[(&'static str, &[&str])]) -> (&'static str, f32) {
+    let scores: Vec<(&'static str, usize)> = lists.iter()
+        .map(|(code, list)| (*code, words.iter().filter(|w| list.contains(&w.as_str())).count()))
+        .collect();
+    let total: usize = scores.iter().map(|(_, hits)| hits).sum();
+    match scores.iter().max_by_key(|(_, hits)| *hits) {
+        Some((code, hits)) if *hits >= MIN_WORD_HITS => (*code, *hits as f32 / total as f32),
+        _ => (UNDETERMINED, 0.0),
+    }
+}
+
+/// Text shown on the document's pages, up to the sample limit
+pub fn page_text(doc: &Document) -> String {
+    let mut text = String::new();
+    for (_, page_id) in doc.pages() {
+        let Ok(content) = doc.page_content(page_id) else { continue };
+        let Ok(content) = Content::decode(&content) else { continue };
+        for operation in content.operations {
+            if TEXT_OPERATORS.contains(&operation.operator.as_str()) {
+                push_operand_text(&operation.operands, &mut text);
+                text.push(' ');
+            }
+            if text.len() >= MAX_SAMPLE_CHARS {
+                return text;
+            }
+        }
+    }
+    text
+}
+
+fn push_operand_text(operands: &[Object], text: &mut String) {
+    for operand in operands {
+        match operand {
+            Object::String(bytes, _) => text.push_str(&decode_pdf_string(bytes)),
+            Object::Array(items) => push_operand_text(items, text),
+            // Large negative kerning in TJ arrays stands in for a space
+            Object::Integer(n) if *n < -200 => text.push(' '),
+            Object::Real(n) if *n < -200.0 => text.push(' '),
+            _ => {}
+        }
+    }
+}
+
+/// Detects the languages of a parsed document's page text
+pub fn detect_document(doc: &Document) -> LanguageProfile {
+    detect_text(&page_text(doc))
+}
+
+/// Picks a value, such as a policy, by a document's primary language
+#[derive(Debug, Clone)]
+pub struct LanguageRouter<T> {
+    routes: HashMap<String, T>,
+    fallback: T,
+    /// Guesses less confident than this use the fallback
+    min_confidence: f32,
+}
+
+impl<T> LanguageRouter<T> {
+    pub fn new(fallback: T) -> Self {
+        Self { routes: HashMap::new(), fallback, min_confidence: 0.5 }
+    }
+
+    pub fn route(mut self, code: impl Into<String>, value: T) -> Self {
+        self.routes.insert(code.into(), value);
+        self
+    }
+
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    pub fn select(&self, profile: &LanguageProfile) -> &T {
+        profile.primary()
+            .filter(|language| language.confidence >= self.min_confidence)
+            .and_then(|language| self.routes.get(&language.code))
+            .unwrap_or(&self.fallback)
+    }
+}
+
+/// Reports the detected languages as an informational finding
+#[derive(Debug, Clone, Default)]
+pub struct LanguageScanner;
+
+impl LanguageScanner {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        let profile = detect_document(doc);
+        let Some(primary) = profile.primary() else { return Vec::new() };
+
+        let mut metadata = HashMap::new();
+        metadata.insert(LANGUAGES_KEY.to_string(), profile.codes().join(","));
+        metadata.insert("primary_language".to_string(), primary.code.clone());
+        metadata.insert("confidence".to_string(), format!("{:.2}", primary.confidence));
+        metadata.insert(
+            "scripts".to_string(),
+            profile.scripts.iter().map(|(script, count)| format!("{:?}:{}", script, count)).collect::<Vec<_>>().join(","),
+        );
+
+        vec![ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type: ArtifactType::Content,
+            location: "/Root/Pages".into(),
+            description: format!("Page text is in {}", profile.codes().join(", ")),
+            risk_level: RiskLevel::None,
+            remediation: "None; informational, for routing to language-specific processing".into(),
+            metadata,
+            confidence: primary.confidence as f64,
+            ..Default::default()
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn test_latin_languages_told_apart() {
+        let english = detect_text("The report is ready and the results of the review are in the appendix.");
+        assert_eq!(english.codes(), vec!["en"]);
+
+        let german = detect_text("Der Bericht ist fertig und die Ergebnisse der Prüfung sind nicht im Anhang.");
+        assert_eq!(german.codes(), vec!["de"]);
+        assert!(german.primary().unwrap().confidence > 0.5);
+
+        let french = detect_text("Le rapport est prêt et les résultats de la revue sont dans une annexe pour vous.");
+        assert_eq!(french.codes(), vec!["fr"]);
+    }
+
+    #[test]
+    fn test_scripts_and_mixed_documents() {
+        let profile = detect_text("Договор подписан и это не последний документ, что он получил. Summary follows.");
+        assert_eq!(profile.primary().unwrap().code, "ru");
+        assert_eq!(profile.primary().unwrap().script, Script::Cyrillic);
+
+        assert_eq!(detect_text("これは日本語の文書です").codes(), vec!["ja"]);
+        assert_eq!(detect_text("这是一个中文文件").codes(), vec!["zh"]);
+        assert_eq!(detect_text("Αυτό είναι ένα έγγραφο").codes(), vec!["el"]);
+        assert_eq!(detect_text("Invoice 4711").codes(), vec![UNDETERMINED]);
+        assert!(detect_text("12345 !!").languages.is_empty());
+    }
+
+    #[test]
+    fn test_router_falls_back_on_weak_guesses() {
+        let router = LanguageRouter::new("generic").route("de", "pii-de").route("en", "pii-en");
+        assert_eq!(*router.select(&detect_text("Der Vertrag ist nicht mit der Firma und die Bank.")), "pii-de");
+        assert_eq!(*router.select(&detect_text("Invoice 4711")), "generic");
+        assert_eq!(*router.select(&LanguageProfile::default()), "generic");
+    }
+
+    #[test]
+    fn test_document_text_detected() {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let content = doc.add_object(Stream::new(
+            dictionary! {},
+            b"BT /F1 12 Tf [(Die)-250(Rechnung)] TJ (ist nicht mit der Post und den Unterlagen gekommen) Tj ET".to_vec(),
+        ));
+        let page = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "Contents" => content });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page.into()], "Count" => 1 }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+
+        let doc = Document::from_pdf("test.pdf".into(), doc);
+        assert!(page_text(&doc).starts_with("Die Rechnung"));
+        let artifacts = LanguageScanner::new().scan(&doc);
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].metadata[LANGUAGES_KEY], "de");
+        assert_eq!(artifacts[0].risk_level, RiskLevel::None);
+    }
+}
@@ -0,0 +1,397 @@
+//! 3D and multimedia content detection
+//! Author: kartik4091
+//! Created: 2025-06-04 19:06:44 UTC
+//! Finds 3D (U3D/PRC), RichMedia, movie, sound and screen annotations,
+//! follows them to their embedded payloads, identifies payload formats by
+//! signature and looks inside for scripts, executables and network
+//! indicators. Payloads can be extracted for offline analysis.
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+use lopdf::{Dictionary, Object, ObjectId};
+use tracing::{debug, instrument};
+
+use crate::antiforensics::{types::Document, utils::resolve_dict_or_stream, ArtifactType, ForensicArtifact, RiskLevel};
+
+/// Annotation families carrying media
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaKind {
+    ThreeD,
+    RichMedia,
+    Movie,
+    Sound,
+    Screen,
+}
+
+impl MediaKind {
+    fn from_subtype(subtype: &[u8]) -> Option<Self> {
+        match subtype {
+            b"3D" => Some(Self::ThreeD),
+            b"RichMedia" => Some(Self::RichMedia),
+            b"Movie" => Some(Self::Movie),
+            b"Sound" => Some(Self::Sound),
+            b"Screen" => Some(Self::Screen),
+            _ => None,
+        }
+    }
+}
+
+/// Payload format, identified by leading bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaFormat {
+    U3d,
+    Prc,
+    Flash,
+    Mp4,
+    Mp3,
+    Wav,
+    Avi,
+    Unknown,
+}
+
+impl MediaFormat {
+    pub fn sniff(data: &[u8]) -> Self {
+        match data {
+            [b'U', b'3', b'D', 0, ..] => Self::U3d,
+            [b'P', b'R', b'C', ..] => Self::Prc,
+            [b'F' | b'C' | b'Z', b'W', b'S', ..] => Self::Flash,
+            [_, _, _, _, b'f', b't', b'y', b'p', ..] => Self::Mp4,
+            [b'I', b'D', b'3', ..] | [0xFF, 0xFB | 0xF3 | 0xF2, ..] => Self::Mp3,
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Self::Wav,
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'A', b'V', b'I', b' ', ..] => Self::Avi,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// File extension used when extracting
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::U3d => "u3d",
+            Self::Prc => "prc",
+            Self::Flash => "swf",
+            Self::Mp4 => "mp4",
+            Self::Mp3 => "mp3",
+            Self::Wav => "wav",
+            Self::Avi => "avi",
+            Self::Unknown => "bin",
+        }
+    }
+}
+
+/// Byte patterns worth reporting when found inside a media payload
+const PAYLOAD_SIGNATURES: [(&str, &[u8]); 6] = [
+    ("embedded executable", b"MZ\x90\x00"),
+    ("embedded PDF", b"%PDF-"),
+    ("embedded ZIP archive", b"PK\x03\x04"),
+    ("JavaScript URI", b"javascript:"),
+    ("HTTP URL", b"http://"),
+    ("HTTPS URL", b"https://"),
+];
+
+/// One media annotation and what it references
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaItem {
+    pub annotation: ObjectId,
+    pub kind: MediaKind,
+    /// Embedded payload streams
+    pub payloads: Vec<ObjectId>,
+    /// External file names or URLs the media loads at view time
+    pub external: Vec<String>,
+    /// Script streams run with the media, e.g. 3D `/OnInstantiate`
+    pub scripts: Vec<ObjectId>,
+    /// Normal appearance stream shown before activation
+    pub poster: Option<ObjectId>,
+}
+
+/// Finds every media annotation in a document
+pub fn find_media(doc: &lopdf::Document) -> Vec<MediaItem> {
+    let mut items = Vec::new();
+    for (&id, object) in &doc.objects {
+        let Ok(dict) = object.as_dict() else { continue };
+        let Some(kind) = dict.get(b"Subtype").and_then(Object::as_name).ok().and_then(MediaKind::from_subtype) else {
+            continue;
+        };
+
+        let mut item = MediaItem {
+            annotation: id,
+            kind,
+            payloads: Vec::new(),
+            external: Vec::new(),
+            scripts: Vec::new(),
+            poster: poster(doc, dict),
+        };
+        match kind {
+            MediaKind::ThreeD => three_d(doc, dict, &mut item),
+            MediaKind::RichMedia => rich_media(doc, dict, &mut item),
+            MediaKind::Movie => {
+                if let Some(file) = dict.get(b"Movie").ok().and_then(|m| resolve_dict_or_stream(doc, m)).and_then(|m| m.get(b"F").ok()) {
+                    file_spec(doc, file, &mut item);
+                }
+            }
+            MediaKind::Sound => {
+                if let Ok(Object::Reference(sound)) = dict.get(b"Sound") {
+                    item.payloads.push(*sound);
+                }
+            }
+            MediaKind::Screen => screen(doc, dict, &mut item),
+        }
+        items.push(item);
+    }
+    items.sort_by_key(|item| item.annotation);
+    items
+}
+
+/// Decoded payloads of an item, by stream id
+pub fn extract(doc: &lopdf::Document, item: &MediaItem) -> Vec<(ObjectId, Vec<u8>)> {
+    item.payloads.iter()
+        .filter_map(|&id| {
+            let stream = doc.get_object(id).and_then(Object::as_stream).ok()?;
+            let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+            Some((id, data))
+        })
+        .collect()
+}
+
+/// Writes every media payload to `dir` as `media-<object>-<generation>.<ext>`
+pub fn extract_all(doc: &lopdf::Document, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for item in find_media(doc) {
+        for ((number, generation), data) in extract(doc, &item) {
+            let name = format!("media-{}-{}.{}", number, generation, MediaFormat::sniff(&data).extension());
+            let path = dir.join(name);
+            std::fs::write(&path, &data)?;
+            written.push(path);
+        }
+    }
+    Ok(written)
+}
+
+/// Signature names found anywhere in `data`
+pub fn payload_signatures(data: &[u8]) -> Vec<&'static str> {
+    PAYLOAD_SIGNATURES.iter()
+        // Skip the payload's own header so an embedded PDF is not the PDF itself
+        .filter(|(_, pattern)| data.len() > 1 && data[1..].windows(pattern.len()).any(|w| w.eq_ignore_ascii_case(pattern)))
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+fn poster(doc: &lopdf::Document, annotation: &Dictionary) -> Option<ObjectId> {
+    let ap = resolve_dict_or_stream(doc, annotation.get(b"AP").ok()?)?;
+    ap.get(b"N").ok()?.as_reference().ok()
+}
+
+fn three_d(doc: &lopdf::Document, annotation: &Dictionary, item: &mut MediaItem) {
+    let Ok(Object::Reference(id)) = annotation.get(b"3DD") else { return };
+    let mut id = *id;
+    // /3DD is either the 3D stream or a 3D reference dictionary pointing at it
+    if let Ok(Object::Dictionary(reference)) = doc.get_object(id) {
+        let Ok(Object::Reference(target)) = reference.get(b"3DD") else { return };
+        id = *target;
+    }
+    let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) else { return };
+    item.payloads.push(id);
+    if let Ok(Object::Reference(script)) = stream.dict.get(b"OnInstantiate") {
+        item.scripts.push(*script);
+    }
+}
+
+fn rich_media(doc: &lopdf::Document, annotation: &Dictionary, item: &mut MediaItem) {
+    let assets = annotation.get(b"RichMediaContent").ok()
+        .and_then(|content| resolve_dict_or_stream(doc, content))
+        .and_then(|content| resolve_dict_or_stream(doc, content.get(b"Assets").ok()?));
+    if let Some(Ok(Object::Array(names))) = assets.map(|a| a.get(b"Names")) {
+        for pair in names.chunks(2) {
+            if let [_, spec] = pair {
+                file_spec(doc, spec, item);
+            }
+        }
+    }
+}
+
+fn screen(doc: &lopdf::Document, annotation: &Dictionary, item: &mut MediaItem) {
+    let clip_data = annotation.get(b"A").ok()
+        .and_then(|a| resolve_dict_or_stream(doc, a))
+        .filter(|action| action.get(b"S").and_then(Object::as_name).ok() == Some(&b"Rendition"[..]))
+        .and_then(|action| resolve_dict_or_stream(doc, action.get(b"R").ok()?))
+        .and_then(|rendition| resolve_dict_or_stream(doc, rendition.get(b"C").ok()?))
+        .and_then(|clip| clip.get(b"D").ok());
+    match clip_data {
+        Some(Object::Reference(id)) if doc.get_object(*id).and_then(Object::as_stream).is_ok() => item.payloads.push(*id),
+        Some(data) => file_spec(doc, data, item),
+        None => {}
+    }
+}
+
+/// Records an embedded stream or an external reference from a file specification
+fn file_spec(doc: &lopdf::Document, spec: &Object, item: &mut MediaItem) {
+    if let Object::String(name, _) = spec {
+        item.external.push(String::from_utf8_lossy(name).into_owned());
+        return;
+    }
+    let Some(spec) = resolve_dict_or_stream(doc, spec) else { return };
+    let embedded = spec.get(b"EF").ok()
+        .and_then(|ef| resolve_dict_or_stream(doc, ef))
+        .and_then(|ef| ef.get(b"F").or_else(|_| ef.get(b"UF")).ok()?.as_reference().ok());
+    match embedded {
+        Some(id) => item.payloads.push(id),
+        None => {
+            // A file specification without /EF names a file or URL outside the document
+            if let Ok(Object::String(name, _)) = spec.get(b"F").or_else(|_| spec.get(b"UF")) {
+                item.external.push(String::from_utf8_lossy(name).into_owned());
+            }
+        }
+    }
+}
+
+/// Reports media annotations and what their payloads contain
+#[derive(Debug, Clone, Default)]
+pub struct MediaScanner;
+
+impl MediaScanner {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        let items = find_media(&doc.structure);
+        debug!("Found {} media annotation(s)", items.len());
+        items.iter().map(|item| self.artifact(&doc.structure, item)).collect()
+    }
+
+    fn artifact(&self, doc: &lopdf::Document, item: &MediaItem) -> ForensicArtifact {
+        let mut formats = Vec::new();
+        let mut signatures = Vec::new();
+        let mut size = 0;
+        for (_, data) in extract(doc, item) {
+            size += data.len();
+            formats.push(format!("{:?}", MediaFormat::sniff(&data)));
+            signatures.extend(payload_signatures(&data));
+        }
+        signatures.sort_unstable();
+        signatures.dedup();
+
+        let flash = formats.iter().any(|f| f == "Flash");
+        let risk_level = if !item.scripts.is_empty() || signatures.contains(&"embedded executable") {
+            RiskLevel::Critical
+        } else if flash || !signatures.is_empty() || !item.external.is_empty() {
+            RiskLevel::High
+        } else {
+            RiskLevel::Medium
+        };
+
+        let mut metadata = HashMap::new();
+        metadata.insert("kind".into(), format!("{:?}", item.kind));
+        metadata.insert("formats".into(), formats.join(","));
+        metadata.insert("payload_bytes".into(), size.to_string());
+        metadata.insert("has_poster".into(), item.poster.is_some().to_string());
+        if !signatures.is_empty() {
+            metadata.insert("signatures".into(), signatures.join(","));
+        }
+        if !item.external.is_empty() {
+            metadata.insert("external".into(), item.external.join(","));
+        }
+        if !item.scripts.is_empty() {
+            metadata.insert("scripts".into(), item.scripts.len().to_string());
+        }
+
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type: if item.scripts.is_empty() { ArtifactType::Content } else { ArtifactType::JavaScript },
+            location: format!("{} {} R", item.annotation.0, item.annotation.1),
+            description: format!(
+                "{:?} annotation with {} embedded payload(s) and {} external reference(s)",
+                item.kind, item.payloads.len(), item.external.len(),
+            ),
+            risk_level,
+            remediation: if item.poster.is_some() {
+                "Replace the media with its poster image".into()
+            } else {
+                "Remove the media annotation and its payloads".into()
+            },
+            metadata,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    /// Single page with a scripted 3D annotation (with poster) and a sound annotation
+    pub(crate) fn media_document() -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let script = doc.add_object(Stream::new(dictionary! {}, b"host.alert(1)".to_vec()));
+        let model = doc.add_object(Stream::new(
+            dictionary! { "Type" => "3D", "Subtype" => "U3D", "OnInstantiate" => script },
+            b"U3D\0 model https://tracker.example/ping".to_vec(),
+        ));
+        let poster = doc.add_object(Stream::new(dictionary! { "Type" => "XObject", "Subtype" => "Form" }, b"q Q".to_vec()));
+        let three_d = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "3D",
+            "Rect" => vec![0.into(), 0.into(), 100.into(), 100.into()],
+            "3DD" => model,
+            "AP" => dictionary! { "N" => poster },
+        });
+        let sound_data = doc.add_object(Stream::new(dictionary! { "Type" => "Sound" }, b"RIFF\0\0\0\0WAVEfmt ".to_vec()));
+        let sound = doc.add_object(dictionary! { "Type" => "Annot", "Subtype" => "Sound", "Sound" => sound_data });
+
+        let pages = doc.new_object_id();
+        let page = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages,
+            "Annots" => vec![three_d.into(), sound.into()],
+        });
+        doc.objects.insert(pages, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page.into()],
+            "Count" => 1,
+        }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+        doc.trailer.set("Root", catalog);
+        doc
+    }
+
+    #[test]
+    fn test_sniff_formats() {
+        assert_eq!(MediaFormat::sniff(b"U3D\0rest"), MediaFormat::U3d);
+        assert_eq!(MediaFormat::sniff(b"CWS\x0a"), MediaFormat::Flash);
+        assert_eq!(MediaFormat::sniff(b"\0\0\0\x20ftypisom"), MediaFormat::Mp4);
+        assert_eq!(MediaFormat::sniff(b"RIFF\0\0\0\0WAVE"), MediaFormat::Wav);
+        assert_eq!(MediaFormat::sniff(b"hello"), MediaFormat::Unknown);
+    }
+
+    #[test]
+    fn test_media_found_and_classified() {
+        let items = find_media(&media_document());
+        assert_eq!(items.len(), 2);
+
+        let three_d = items.iter().find(|i| i.kind == MediaKind::ThreeD).unwrap();
+        assert_eq!(three_d.payloads.len(), 1);
+        assert_eq!(three_d.scripts.len(), 1);
+        assert!(three_d.poster.is_some());
+
+        let doc = Document::from_pdf(PathBuf::from("media.pdf"), media_document());
+        let artifacts = MediaScanner::new().scan(&doc);
+        let scripted = artifacts.iter().find(|a| a.metadata["kind"] == "ThreeD").unwrap();
+        assert_eq!(scripted.risk_level, RiskLevel::Critical);
+        assert_eq!(scripted.metadata["formats"], "U3d");
+        assert_eq!(scripted.metadata["signatures"], "HTTPS URL");
+    }
+
+    #[test]
+    fn test_extract_all_writes_payloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = extract_all(&media_document(), dir.path()).unwrap();
+        let extensions: Vec<_> = written.iter().filter_map(|p| p.extension()?.to_str()).collect();
+        assert!(extensions.contains(&"u3d"));
+        assert!(extensions.contains(&"wav"));
+    }
+}
@@ -320,7 +320,7 @@ impl Scanner for MetadataScanner {
             path: path.clone(),
             size: data.len() as u64,
             file_type: "Metadata".into(),
-            findings,
+            findings: AggregatedFindings::from_findings(findings, &self.config.base.aggregation),
             metadata,
             metrics: ScanMetrics {
                 duration,
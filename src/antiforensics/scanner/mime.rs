@@ -0,0 +1,615 @@
+//! Content-based MIME sniffing of embedded files
+//! Author: kartik4091
+//! Created: 2025-06-05 00:03:41 UTC
+//! An embedded file's /Subtype and the extension of its name are chosen
+//! by whoever made the document and say nothing reliable about what the
+//! file is. Each embedded file is identified from its content instead,
+//! by magic bytes backed by a structure check where the magic alone is
+//! weak, and a declared type that disagrees is reported. The sniffed
+//! type, not the declared one, decides which scanner looks inside.
+
+use std::collections::HashMap;
+use lopdf::{Dictionary, Object, ObjectId};
+use tracing::{debug, instrument, warn};
+
+use crate::antiforensics::{
+    types::Document,
+    utils::streams::decoded_up_to,
+    ArtifactType,
+    ForensicArtifact,
+    RiskLevel,
+};
+use super::{
+    document_scanner::DocumentScanner,
+    media::{payload_signatures, MediaFormat},
+    portfolio::is_portfolio,
+};
+
+/// Bytes at the start of a file searched for a PDF header, as readers do
+pub(crate) const PDF_HEADER_WINDOW: usize = 1024;
+
+/// Bytes at the start of a file checked when deciding it is text
+const TEXT_WINDOW: usize = 4096;
+
+/// Embedded files are decoded this far; larger ones are sniffed from
+/// their start and not looked inside
+pub(crate) const MAX_FILE_BYTES: usize = 256 * 1024 * 1024;
+
+/// File type told by content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    Pdf,
+    Xml,
+    Html,
+    Rtf,
+    /// Plain text with no more specific structure
+    Text,
+    /// Text starting with a `#!` interpreter line
+    Script,
+    Zip,
+    /// Word, Excel or PowerPoint 2007+ (a ZIP with `[Content_Types].xml`)
+    Ooxml,
+    /// OpenDocument (a ZIP whose first entry is `mimetype`)
+    OpenDocument,
+    /// OLE compound file: legacy Office documents and MSI installers
+    OleCompound,
+    Gzip,
+    SevenZip,
+    Rar,
+    Png,
+    Jpeg,
+    Gif,
+    Tiff,
+    Bmp,
+    /// Windows PE executable or DLL
+    Executable,
+    Elf,
+    MachO,
+    Media(MediaFormat),
+    Unknown,
+}
+
+impl FileType {
+    /// Identifies `data` by its content
+    pub fn sniff(data: &[u8]) -> Self {
+        match data {
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n', _, _, _, _, b'I', b'H', b'D', b'R', ..] => return Self::Png,
+            [0xFF, 0xD8, 0xFF, ..] => return Self::Jpeg,
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => return Self::Gif,
+            [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => return Self::Tiff,
+            [b'B', b'M', _, _, _, _, 0, 0, 0, 0, ..] => return Self::Bmp,
+            [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1, ..] => return Self::OleCompound,
+            [0x1F, 0x8B, 0x08, ..] => return Self::Gzip,
+            [b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C, ..] => return Self::SevenZip,
+            [b'R', b'a', b'r', b'!', 0x1A, 0x07, ..] => return Self::Rar,
+            [0x7F, b'E', b'L', b'F', 1 | 2, ..] => return Self::Elf,
+            [0xFE, 0xED, 0xFA, 0xCE | 0xCF, ..] | [0xCE | 0xCF, 0xFA, 0xED, 0xFE, ..] => return Self::MachO,
+            [b'P', b'K', 0x03, 0x04, ..] => return zip_type(data),
+            [b'M', b'Z', ..] if is_pe(data) => return Self::Executable,
+            [b'{', b'\\', b'r', b't', b'f', ..] => return Self::Rtf,
+            _ => {}
+        }
+        if data[..data.len().min(PDF_HEADER_WINDOW)].windows(5).any(|w| w == b"%PDF-") {
+            return Self::Pdf;
+        }
+        match MediaFormat::sniff(data) {
+            MediaFormat::Unknown => {}
+            format => return Self::Media(format),
+        }
+        if !is_text(data) {
+            return Self::Unknown;
+        }
+
+        let text = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data).trim_ascii_start();
+        let head = &text[..text.len().min(256)];
+        if text.starts_with(b"#!") {
+            Self::Script
+        } else if starts_with_ignore_case(head, b"<!doctype html") || starts_with_ignore_case(head, b"<html") {
+            Self::Html
+        } else if head.starts_with(b"<?xml") || (head.starts_with(b"<") && head.get(1).is_some_and(u8::is_ascii_alphabetic)) {
+            Self::Xml
+        } else {
+            Self::Text
+        }
+    }
+
+    /// Type named by a MIME type such as an embedded file's /Subtype
+    ///
+    /// Returns `None` for `application/octet-stream` and types this
+    /// sniffer does not distinguish, which claim nothing checkable.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let mime = mime.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        let file_type = match mime.as_str() {
+            "application/pdf" | "application/x-pdf" => Self::Pdf,
+            "text/html" | "application/xhtml+xml" => Self::Html,
+            "text/xml" | "application/xml" => Self::Xml,
+            m if m.ends_with("+xml") => Self::Xml,
+            "application/rtf" | "text/rtf" => Self::Rtf,
+            "text/plain" | "text/csv" | "application/json" => Self::Text,
+            "application/zip" | "application/x-zip-compressed" => Self::Zip,
+            m if m.starts_with("application/vnd.openxmlformats-officedocument.") => Self::Ooxml,
+            m if m.starts_with("application/vnd.oasis.opendocument.") => Self::OpenDocument,
+            "application/msword" | "application/vnd.ms-excel" | "application/vnd.ms-powerpoint"
+            | "application/x-msi" | "application/vnd.ms-outlook" => Self::OleCompound,
+            "application/gzip" | "application/x-gzip" => Self::Gzip,
+            "application/x-7z-compressed" => Self::SevenZip,
+            "application/vnd.rar" | "application/x-rar-compressed" => Self::Rar,
+            "image/png" => Self::Png,
+            "image/jpeg" => Self::Jpeg,
+            "image/gif" => Self::Gif,
+            "image/tiff" => Self::Tiff,
+            "image/bmp" => Self::Bmp,
+            "application/x-msdownload" | "application/x-dosexec" | "application/vnd.microsoft.portable-executable" => Self::Executable,
+            "application/x-executable" | "application/x-elf" => Self::Elf,
+            "application/x-mach-binary" => Self::MachO,
+            "application/x-sh" | "text/x-shellscript" | "text/x-python" => Self::Script,
+            "audio/mpeg" => Self::Media(MediaFormat::Mp3),
+            "audio/wav" | "audio/x-wav" => Self::Media(MediaFormat::Wav),
+            "video/mp4" => Self::Media(MediaFormat::Mp4),
+            "video/x-msvideo" => Self::Media(MediaFormat::Avi),
+            "application/x-shockwave-flash" => Self::Media(MediaFormat::Flash),
+            "model/u3d" => Self::Media(MediaFormat::U3d),
+            "model/prc" => Self::Media(MediaFormat::Prc),
+            _ => return None,
+        };
+        Some(file_type)
+    }
+
+    /// Type suggested by a file name extension
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let (_, extension) = name.rsplit_once('.')?;
+        let file_type = match extension.to_ascii_lowercase().as_str() {
+            "pdf" => Self::Pdf,
+            "xml" => Self::Xml,
+            "html" | "htm" | "xhtml" => Self::Html,
+            "rtf" => Self::Rtf,
+            "txt" | "csv" | "json" | "md" => Self::Text,
+            "sh" | "py" | "pl" => Self::Script,
+            "zip" => Self::Zip,
+            "docx" | "xlsx" | "pptx" | "docm" | "xlsm" | "pptm" => Self::Ooxml,
+            "odt" | "ods" | "odp" => Self::OpenDocument,
+            "doc" | "xls" | "ppt" | "msi" | "msg" => Self::OleCompound,
+            "gz" | "tgz" => Self::Gzip,
+            "7z" => Self::SevenZip,
+            "rar" => Self::Rar,
+            "png" => Self::Png,
+            "jpg" | "jpeg" => Self::Jpeg,
+            "gif" => Self::Gif,
+            "tif" | "tiff" => Self::Tiff,
+            "bmp" => Self::Bmp,
+            "exe" | "dll" | "scr" | "sys" => Self::Executable,
+            "mp3" => Self::Media(MediaFormat::Mp3),
+            "wav" => Self::Media(MediaFormat::Wav),
+            "mp4" | "m4v" => Self::Media(MediaFormat::Mp4),
+            "avi" => Self::Media(MediaFormat::Avi),
+            "swf" => Self::Media(MediaFormat::Flash),
+            "u3d" => Self::Media(MediaFormat::U3d),
+            "prc" => Self::Media(MediaFormat::Prc),
+            _ => return None,
+        };
+        Some(file_type)
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            Self::Pdf => "application/pdf",
+            Self::Xml => "application/xml",
+            Self::Html => "text/html",
+            Self::Rtf => "application/rtf",
+            Self::Text => "text/plain",
+            Self::Script => "text/x-script",
+            Self::Zip => "application/zip",
+            Self::Ooxml => "application/vnd.openxmlformats-officedocument",
+            Self::OpenDocument => "application/vnd.oasis.opendocument",
+            Self::OleCompound => "application/x-ole-storage",
+            Self::Gzip => "application/gzip",
+            Self::SevenZip => "application/x-7z-compressed",
+            Self::Rar => "application/vnd.rar",
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::Tiff => "image/tiff",
+            Self::Bmp => "image/bmp",
+            Self::Executable => "application/vnd.microsoft.portable-executable",
+            Self::Elf => "application/x-executable",
+            Self::MachO => "application/x-mach-binary",
+            Self::Media(MediaFormat::Mp3) => "audio/mpeg",
+            Self::Media(MediaFormat::Wav) => "audio/wav",
+            Self::Media(MediaFormat::Mp4) => "video/mp4",
+            Self::Media(MediaFormat::Avi) => "video/x-msvideo",
+            Self::Media(MediaFormat::Flash) => "application/x-shockwave-flash",
+            Self::Media(MediaFormat::U3d) => "model/u3d",
+            Self::Media(MediaFormat::Prc) => "model/prc",
+            Self::Media(MediaFormat::Unknown) | Self::Unknown => "application/octet-stream",
+        }
+    }
+
+    /// Native code or a script that runs when opened
+    pub fn is_executable(self) -> bool {
+        matches!(self, Self::Executable | Self::Elf | Self::MachO | Self::Script)
+    }
+
+    /// Whether content sniffed as `self` fits a file declared as `declared`
+    ///
+    /// Text-based formats are not always told apart by content, and a ZIP
+    /// whose marker entry sits past the first header sniffs as plain ZIP,
+    /// so those pairs are accepted rather than reported.
+    pub fn fits(self, declared: FileType) -> bool {
+        use FileType::*;
+        match (declared, self) {
+            _ if self == declared => true,
+            // Nothing recognizable, so nothing to contradict
+            (_, Unknown) => true,
+            (Xml | Html | Text | Script, Text) => true,
+            (Text, Xml | Html) | (Html, Xml) => true,
+            (Ooxml | OpenDocument, Zip) | (Zip, Ooxml | OpenDocument) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Whether a `MZ` file has a PE header where its DOS header points
+fn is_pe(data: &[u8]) -> bool {
+    let Some(offset) = data.get(0x3C..0x40) else { return false };
+    let offset = u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize;
+    data.get(offset..offset.saturating_add(4)) == Some(b"PE\0\0".as_slice())
+}
+
+/// Tells OOXML and OpenDocument packages from other ZIP archives
+fn zip_type(data: &[u8]) -> FileType {
+    // First local header: name length at 26, extra length at 28, name at 30
+    let Some(header) = data.get(26..30) else { return FileType::Unknown };
+    let name_length = u16::from_le_bytes([header[0], header[1]]) as usize;
+    let extra_length = u16::from_le_bytes([header[2], header[3]]) as usize;
+    let Some(name) = data.get(30..30 + name_length) else { return FileType::Unknown };
+
+    let stored = data.get(30 + name_length + extra_length..).unwrap_or_default();
+    if name == b"mimetype" && stored.starts_with(b"application/vnd.oasis.opendocument.") {
+        FileType::OpenDocument
+    } else if data.windows(19).any(|w| w == b"[Content_Types].xml") {
+        FileType::Ooxml
+    } else {
+        FileType::Zip
+    }
+}
+
+/// UTF-8 (or ASCII) without NULs and with few other control characters
+fn is_text(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(TEXT_WINDOW)];
+    if head.is_empty() || head.contains(&0) {
+        return false;
+    }
+    // The window may cut a multi-byte character in two
+    let valid = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    let controls = head.iter().filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C)).count();
+    valid && controls * 100 <= head.len()
+}
+
+fn starts_with_ignore_case(data: &[u8], prefix: &[u8]) -> bool {
+    data.len() >= prefix.len() && data[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// An embedded file stream with its declared and sniffed types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedFile {
+    /// Name from the file specification, or the stream's object reference
+    pub name: String,
+    pub filespec_id: Option<ObjectId>,
+    pub stream_id: ObjectId,
+    /// The stream's /Subtype
+    pub declared: Option<String>,
+    /// Decoded size in bytes, or the size limit for an oversized file
+    pub size: usize,
+    pub sniffed: FileType,
+    /// Decodes to more than the size limit
+    pub oversized: bool,
+}
+
+impl EmbeddedFile {
+    /// Declared types the content does not fit, as `(source, declared)`
+    pub fn mismatches(&self) -> Vec<(&'static str, String)> {
+        let mut mismatches = Vec::new();
+        if let Some(declared) = &self.declared {
+            if FileType::from_mime(declared).is_some_and(|t| !self.sniffed.fits(t)) {
+                mismatches.push(("subtype", declared.clone()));
+            }
+        }
+        if let Some(declared) = FileType::from_file_name(&self.name) {
+            if !self.sniffed.fits(declared) {
+                mismatches.push(("extension", declared.mime().to_string()));
+            }
+        }
+        mismatches
+    }
+}
+
+/// Every /EmbeddedFile stream, sniffed, in object order
+pub fn embedded_files(doc: &Document) -> Vec<EmbeddedFile> {
+    decoded_files(doc, MAX_FILE_BYTES).map(|(file, _)| file).collect()
+}
+
+/// Every /EmbeddedFile stream with its content, decoded once and at most
+/// `limit` bytes of it
+fn decoded_files(doc: &Document, limit: usize) -> impl Iterator<Item = (EmbeddedFile, Vec<u8>)> + '_ {
+    let mut names: HashMap<ObjectId, (String, Option<ObjectId>)> = HashMap::new();
+    for (id, object) in doc.objects() {
+        let Ok(dict) = object.as_dict() else { continue };
+        file_spec(&doc.structure, Some(id), dict, &mut names);
+        // File attachment annotations often carry the specification inline
+        if let Ok(Object::Dictionary(spec)) = dict.get(b"FS") {
+            file_spec(&doc.structure, None, spec, &mut names);
+        }
+    }
+
+    doc.streams()
+        .filter_map(move |(id, stream)| {
+            if stream.dict.get(b"Type").and_then(Object::as_name).ok() != Some(b"EmbeddedFile".as_slice()) {
+                return None;
+            }
+            let (content, complete) = decoded_up_to(stream, limit);
+            let (name, filespec_id) = names.get(&id).cloned()
+                .unwrap_or_else(|| (format!("{} {} R", id.0, id.1), None));
+            let file = EmbeddedFile {
+                name,
+                filespec_id,
+                stream_id: id,
+                declared: stream.dict.get(b"Subtype").and_then(Object::as_name_str).ok().map(str::to_string),
+                size: content.len(),
+                sniffed: FileType::sniff(&content),
+                oversized: !complete,
+            };
+            Some((file, content))
+        })
+}
+
+fn file_spec(
+    doc: &lopdf::Document,
+    id: Option<ObjectId>,
+    spec: &Dictionary,
+    names: &mut HashMap<ObjectId, (String, Option<ObjectId>)>,
+) {
+    let ef = match spec.get(b"EF") {
+        Ok(Object::Dictionary(ef)) => ef,
+        Ok(Object::Reference(ef)) => match doc.get_dictionary(*ef) {
+            Ok(ef) => ef,
+            Err(_) => return,
+        },
+        _ => return,
+    };
+    let Some(stream) = ef.get(b"UF").or_else(|_| ef.get(b"F")).ok().and_then(|s| s.as_reference().ok()) else { return };
+    let name = [&b"UF"[..], b"F"].iter().find_map(|k| match spec.get(k) {
+        Ok(Object::String(bytes, _)) if !bytes.is_empty() => Some(String::from_utf8_lossy(bytes).into_owned()),
+        _ => None,
+    });
+    if let Some(name) = name {
+        names.entry(stream).or_insert((name, id));
+    }
+}
+
+/// Reports embedded files whose content contradicts their declared type
+/// and passes each file to the scanner for what it actually is
+#[derive(Debug, Clone)]
+pub struct MimeSniffer {
+    scanner: DocumentScanner,
+    /// Embedded files decoding to more bytes than this are sniffed from
+    /// their start only
+    max_file_bytes: usize,
+}
+
+impl MimeSniffer {
+    pub fn new() -> Self {
+        Self {
+            scanner: DocumentScanner::new(),
+            max_file_bytes: MAX_FILE_BYTES,
+        }
+    }
+
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        // Portfolio entries are scanned by the portfolio scanner
+        let portfolio = is_portfolio(&doc.structure);
+
+        let mut artifacts = Vec::new();
+        let mut sniffed = 0;
+        for (file, content) in decoded_files(doc, self.max_file_bytes) {
+            sniffed += 1;
+            let mismatches = file.mismatches();
+            if !mismatches.is_empty() || file.sniffed.is_executable() {
+                artifacts.push(Self::artifact(&file, &mismatches));
+            }
+            if file.oversized {
+                warn!("embedded file {} decodes to more than {} bytes; not scanned", file.name, self.max_file_bytes);
+                continue;
+            }
+
+            match file.sniffed {
+                FileType::Pdf if !portfolio => artifacts.extend(self.scan_pdf(&file, content)),
+                FileType::Media(_) | FileType::Zip | FileType::Ooxml | FileType::OpenDocument
+                | FileType::OleCompound | FileType::Unknown => {
+                    artifacts.extend(Self::scan_payload(&file, &content));
+                }
+                _ => {}
+            }
+        }
+        debug!("Sniffed {} embedded file(s)", sniffed);
+        artifacts
+    }
+
+    /// Scans an embedded PDF as a document of its own
+    fn scan_pdf(&self, file: &EmbeddedFile, content: Vec<u8>) -> Vec<ForensicArtifact> {
+        let Ok(inner) = Document::from_bytes(std::path::PathBuf::from(&file.name), content) else {
+            debug!("embedded PDF {} did not parse", file.name);
+            return Vec::new();
+        };
+        let prefix = format!("attachment:{}/", file.name);
+        self.scanner.scan(&inner).into_iter()
+            .map(|mut artifact| {
+                artifact.location = format!("{}{}", prefix, artifact.location);
+                artifact.metadata.insert("attachment".into(), file.name.clone());
+                artifact
+            })
+            .collect()
+    }
+
+    /// Looks inside binary containers for executables, PDFs and URLs
+    fn scan_payload(file: &EmbeddedFile, content: &[u8]) -> Option<ForensicArtifact> {
+        let signatures = payload_signatures(content);
+        if signatures.is_empty() {
+            return None;
+        }
+
+        let mut metadata = Self::metadata(file);
+        metadata.insert("signatures".into(), signatures.join(","));
+        Some(ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type: ArtifactType::EmbeddedFile,
+            location: format!("{} {} R", file.stream_id.0, file.stream_id.1),
+            description: format!("Embedded file {} ({}) contains {}", file.name, file.sniffed.mime(), signatures.join(", ")),
+            risk_level: if signatures.contains(&"embedded executable") { RiskLevel::Critical } else { RiskLevel::High },
+            remediation: "Remove the embedded file or extract and analyze it in isolation".into(),
+            metadata,
+            ..Default::default()
+        })
+    }
+
+    fn metadata(file: &EmbeddedFile) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        metadata.insert("object_id".into(), format!("{} {}", file.stream_id.0, file.stream_id.1));
+        metadata.insert("file_name".into(), file.name.clone());
+        metadata.insert("sniffed_type".into(), file.sniffed.mime().to_string());
+        metadata.insert("size".into(), file.size.to_string());
+        if file.oversized {
+            metadata.insert("oversized".into(), "true".into());
+        }
+        if let Some(declared) = &file.declared {
+            metadata.insert("declared_type".into(), declared.clone());
+        }
+        metadata
+    }
+
+    fn artifact(file: &EmbeddedFile, mismatches: &[(&'static str, String)]) -> ForensicArtifact {
+        let mut metadata = Self::metadata(file);
+        for (source, declared) in mismatches {
+            metadata.insert(format!("{}_mismatch", source), declared.clone());
+        }
+
+        let claims: Vec<String> = mismatches.iter()
+            .map(|(source, declared)| format!("its {} says {}", source, declared))
+            .collect();
+        let description = if claims.is_empty() {
+            format!("Embedded file {} is {}", file.name, file.sniffed.mime())
+        } else {
+            format!("Embedded file {} is {}, but {}", file.name, file.sniffed.mime(), claims.join(" and "))
+        };
+
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type: ArtifactType::EmbeddedFile,
+            location: format!("{} {} R", file.stream_id.0, file.stream_id.1),
+            description,
+            risk_level: if file.sniffed.is_executable() { RiskLevel::High } else { RiskLevel::Medium },
+            remediation: "Remove the embedded file, or correct its declared type after reviewing it".into(),
+            metadata,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for MimeSniffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::Fixture;
+    use lopdf::{dictionary, Stream};
+
+    fn pe() -> Vec<u8> {
+        let mut data = vec![0u8; 0x80];
+        data[..2].copy_from_slice(b"MZ");
+        data[0x3C] = 0x40;
+        data[0x40..0x44].copy_from_slice(b"PE\0\0");
+        data
+    }
+
+    #[test]
+    fn test_sniff_checks_structure() {
+        assert_eq!(FileType::sniff(&pe()), FileType::Executable);
+        // MZ without a PE header is not an executable
+        assert_eq!(FileType::sniff(b"MZ is a postcode"), FileType::Text);
+        assert_eq!(FileType::sniff(b"junk\n%PDF-1.7\n"), FileType::Pdf);
+        assert_eq!(FileType::sniff(b"<?xml version=\"1.0\"?><a/>"), FileType::Xml);
+        assert_eq!(FileType::sniff(b"#!/bin/sh\nrm -rf /"), FileType::Script);
+
+        let mut zip = b"PK\x03\x04".to_vec();
+        zip.extend([0u8; 22]);
+        zip.extend(8u16.to_le_bytes());
+        zip.extend(0u16.to_le_bytes());
+        zip.extend(b"mimetypeapplication/vnd.oasis.opendocument.text");
+        assert_eq!(FileType::sniff(&zip), FileType::OpenDocument);
+
+        assert_eq!(FileType::from_mime("application/vnd.openxmlformats-officedocument.wordprocessingml.document"), Some(FileType::Ooxml));
+        assert_eq!(FileType::from_mime("application/octet-stream"), None);
+        assert!(FileType::Zip.fits(FileType::Ooxml));
+        assert!(!FileType::Executable.fits(FileType::Pdf));
+    }
+
+    #[test]
+    fn test_mismatch_reported_and_pdf_routed() {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let exe = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile", "Subtype" => "application/pdf" }, pe()));
+        doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "UF" => Object::string_literal("invoice.pdf"),
+            "EF" => dictionary! { "F" => exe },
+        });
+
+        // Declared as text; sniffed as a PDF and scanned as one
+        let pdf = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile", "Subtype" => "text/plain" }, Fixture::JavaScript.bytes()));
+        doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "FileAttachment",
+            "FS" => dictionary! { "Type" => "Filespec", "F" => Object::string_literal("notes.txt"), "EF" => dictionary! { "F" => pdf } },
+        });
+
+        let doc = Document::from_pdf("test.pdf".into(), doc);
+        let files = embedded_files(&doc);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].sniffed, FileType::Executable);
+        assert_eq!(files[0].mismatches().len(), 2);
+        assert_eq!(files[1].name, "notes.txt");
+
+        let artifacts = MimeSniffer::new().scan(&doc);
+        let exe_artifact = artifacts.iter().find(|a| a.location == format!("{} 0 R", exe.0)).unwrap();
+        assert_eq!(exe_artifact.risk_level, RiskLevel::High);
+        assert!(exe_artifact.description.contains("its subtype says application/pdf"));
+        assert!(artifacts.iter().any(|a| a.location == format!("{} 0 R", pdf.0) && a.risk_level == RiskLevel::Medium));
+        assert!(artifacts.iter().any(|a| a.location.starts_with("attachment:notes.txt/")
+            && a.artifact_type == ArtifactType::JavaScript));
+    }
+
+    #[test]
+    fn test_oversized_files_sniffed_but_not_scanned() {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let pdf = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, Fixture::JavaScript.bytes()));
+        let exe = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, pe()));
+
+        let sniffer = MimeSniffer { max_file_bytes: 96, ..MimeSniffer::new() };
+        let doc = Document::from_pdf("test.pdf".into(), doc);
+        let files: Vec<_> = decoded_files(&doc, sniffer.max_file_bytes).map(|(file, _)| file).collect();
+        assert!(files.iter().all(|file| file.oversized && file.size == 96));
+        assert_eq!(files[0].sniffed, FileType::Pdf);
+
+        let artifacts = sniffer.scan(&doc);
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].location, format!("{} 0 R", exe.0));
+        assert_eq!(artifacts[0].metadata["oversized"], "true");
+        assert!(!artifacts.iter().any(|a| a.location == format!("{} 0 R", pdf.0)));
+    }
+}
@@ -17,15 +17,48 @@ use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
 use tracing::{info, warn, error, debug, instrument};
 
+pub mod aggregate;
 pub mod pdf_scanner;
 pub mod metadata_scanner;
 pub mod content_scanner;
+pub mod document_scanner;
+pub mod hidden_text;
+pub mod invoice;
+pub mod language;
+pub mod media;
+pub mod mime;
+pub mod palette_stego;
+pub mod portfolio;
+pub mod quick;
+pub mod sink;
+pub mod syntax_stego;
+pub mod tracking;
+pub mod triage;
+pub mod unicode_spoof;
+pub mod usage_rights;
 
 pub use self::{
+    aggregate::{AggregatedFindings, AggregationLimits, FindingGroup, FindingPage},
     pdf_scanner::PdfScanner,
     metadata_scanner::MetadataScanner,
     content_scanner::ContentScanner,
+    document_scanner::DocumentScanner,
+    hidden_text::{HiddenTextConfig, HiddenTextDetector, HiddenTextReason},
+    invoice::{InvoiceAttachment, InvoiceIssue, InvoiceProfile, InvoiceScanner, InvoiceStandard},
+    language::{DetectedLanguage, LanguageProfile, LanguageRouter, LanguageScanner, Script},
+    media::{MediaFormat, MediaItem, MediaKind, MediaScanner},
+    mime::{EmbeddedFile, FileType, MimeSniffer},
+    palette_stego::{PaletteAnalysis, PaletteScanner},
+    portfolio::{PortfolioEntry, PortfolioScanner},
+    quick::{QuickScanLine, QuickScanReport, QuickScanner},
+    sink::{ArtifactSink, ChannelSink, JsonlSink},
+    syntax_stego::{SyntaxLayout, SyntaxScanner, SyntaxStegoConfig},
+    tracking::{TrackingElement, TrackingKind, TrackingScanner},
+    unicode_spoof::{SpoofFinding, SpoofKind, UnicodeSpoofDetector},
+    usage_rights::{UsageRights, UsageRightsScanner},
 };
+#[cfg(feature = "webhook")]
+pub use self::sink::{WebhookSink, WebhookSinkConfig};
 
 /// Scanner configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +75,8 @@ pub struct ScannerConfig {
     pub worker_threads: usize,
     /// Memory limit per scan
     pub memory_limit: usize,
+    /// Bounds on findings kept in a ScanResult
+    pub aggregation: AggregationLimits,
 }
 
 /// Custom error type for scanner operations
@@ -75,8 +110,8 @@ pub struct ScanResult {
     pub size: u64,
     /// File type
     pub file_type: String,
-    /// Scan findings
-    pub findings: Vec<ScanFinding>,
+    /// Scan findings, grouped and capped
+    pub findings: AggregatedFindings,
     /// Metadata
     pub metadata: HashMap<String, String>,
     /// Performance metrics
@@ -112,7 +147,7 @@ pub struct ScanMetrics {
 }
 
 /// Finding severity levels
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Severity {
     Info,
     Low,
@@ -122,7 +157,7 @@ pub enum Severity {
 }
 
 /// Finding categories
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Category {
     Metadata,
     Content,
@@ -287,7 +322,7 @@ impl BaseScanner {
 impl Default for ScannerConfig {
     fn default() -> Self {
         Self {
-            max_concurrent_scans: 4,
+            max_concurrent_scans: crate::concurrency::ConcurrencyConfig::default().limits().scan,
             max_file_size: 10 * 1024 * 1024, // 10MB
             scan_timeout: Duration::from_secs(300), // 5 minutes
             extensions: ["pdf", "doc", "docx", "txt"].iter()
@@ -295,6 +330,7 @@ impl Default for ScannerConfig {
                 .collect(),
             worker_threads: num_cpus::get(),
             memory_limit: 1024 * 1024 * 1024, // 1GB
+            aggregation: AggregationLimits::default(),
         }
     }
 }
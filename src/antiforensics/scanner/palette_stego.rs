@@ -0,0 +1,325 @@
+//! Palette steganography detection for indexed-color images
+//! Author: kartik4091
+//! Created: 2025-06-04 19:48:26 UTC
+//! Palette tools hide data without touching pixel values the way LSB
+//! embedding does: they add near-identical palette entries and flip
+//! indices between them (EzStego and friends), or permute the palette
+//! itself (GifShuffle). Neither shows up in sample-level LSB statistics,
+//! so the palette is examined directly.
+
+use std::collections::HashMap;
+use lopdf::{Dictionary, Object, ObjectId};
+use tracing::debug;
+
+use crate::antiforensics::{types::Document, ArtifactType, ForensicArtifact, RiskLevel};
+
+/// Largest per-channel difference at which two entries count as near-duplicates
+const NEAR_DUPLICATE_DISTANCE: u8 = 2;
+
+/// Palette evidence for one indexed image
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteAnalysis {
+    /// Palette entries
+    pub entries: usize,
+    /// Distinct entry pairs that differ by at most a couple of levels per channel
+    pub near_duplicate_pairs: usize,
+    /// Share of adjacent entry pairs (2k, 2k+1) that are near-duplicates
+    pub adjacent_pair_ratio: f64,
+    /// Entropy of the luminance rank steps between consecutive entries,
+    /// normalized to `0.0..=1.0`; 0 is a sorted palette, 1 a random permutation
+    pub ordering_entropy: f64,
+    /// How evenly index usage is split within near-duplicate pairs, in
+    /// `0.0..=1.0`; embedding equalizes the two halves. `None` without pixel data
+    pub pair_usage_balance: Option<f64>,
+    /// Combined likelihood of palette steganography, in `0.0..=1.0`
+    pub score: f64,
+}
+
+impl PaletteAnalysis {
+    /// Human-readable reasons the palette looks manipulated
+    pub fn indicators(&self) -> Vec<String> {
+        let mut indicators = Vec::new();
+        if self.near_duplicate_pairs > 0 {
+            indicators.push(format!("{} near-duplicate palette entry pair(s)", self.near_duplicate_pairs));
+        }
+        if self.adjacent_pair_ratio >= 0.5 {
+            indicators.push(format!("{:.0}% of adjacent entries form near-duplicate pairs", self.adjacent_pair_ratio * 100.0));
+        }
+        if self.entries >= 16 && self.ordering_entropy >= 0.9 {
+            indicators.push("palette order is indistinguishable from a random permutation".into());
+        }
+        if let Some(balance) = self.pair_usage_balance.filter(|b| *b >= 0.9) {
+            indicators.push(format!("index usage within near-duplicate pairs is {:.0}% balanced", balance * 100.0));
+        }
+        indicators
+    }
+}
+
+/// Analyzes a palette and, when available, the image's index samples
+pub fn analyze_palette(palette: &[[u8; 3]], indices: Option<&[u8]>) -> PaletteAnalysis {
+    let pairs = near_duplicate_pairs(palette);
+    let adjacent = palette.chunks_exact(2).filter(|pair| is_near_duplicate(pair[0], pair[1])).count();
+    let adjacent_pair_ratio = if palette.len() >= 2 { adjacent as f64 / (palette.len() / 2) as f64 } else { 0.0 };
+    let ordering_entropy = ordering_entropy(palette);
+    let pair_usage_balance = indices.filter(|_| !pairs.is_empty()).map(|indices| usage_balance(&pairs, indices));
+
+    let duplicate_share = (pairs.len() * 2) as f64 / palette.len().max(1) as f64;
+    let mut score = 0.5 * duplicate_share.min(1.0) + 0.3 * adjacent_pair_ratio;
+    if palette.len() >= 16 && ordering_entropy >= 0.9 {
+        score += 0.2;
+    }
+    if let Some(balance) = pair_usage_balance {
+        // Balanced usage only means something when near-duplicates exist
+        score = 0.6 * score + 0.4 * balance;
+    }
+
+    PaletteAnalysis {
+        entries: palette.len(),
+        near_duplicate_pairs: pairs.len(),
+        adjacent_pair_ratio,
+        ordering_entropy,
+        pair_usage_balance,
+        score: score.clamp(0.0, 1.0),
+    }
+}
+
+fn is_near_duplicate(a: [u8; 3], b: [u8; 3]) -> bool {
+    a != b && a.iter().zip(&b).all(|(x, y)| x.abs_diff(*y) <= NEAR_DUPLICATE_DISTANCE)
+}
+
+fn near_duplicate_pairs(palette: &[[u8; 3]]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (i, &a) in palette.iter().enumerate() {
+        for (j, &b) in palette.iter().enumerate().skip(i + 1) {
+            if is_near_duplicate(a, b) {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+fn luminance([r, g, b]: [u8; 3]) -> u32 {
+    299 * r as u32 + 587 * g as u32 + 114 * b as u32
+}
+
+fn ordering_entropy(palette: &[[u8; 3]]) -> f64 {
+    if palette.len() < 3 {
+        return 0.0;
+    }
+    let mut order: Vec<usize> = (0..palette.len()).collect();
+    order.sort_by_key(|&i| (luminance(palette[i]), i));
+    let mut rank = vec![0i64; palette.len()];
+    for (position, &index) in order.iter().enumerate() {
+        rank[index] = position as i64;
+    }
+
+    let mut steps: HashMap<i64, usize> = HashMap::new();
+    for window in rank.windows(2) {
+        *steps.entry(window[1] - window[0]).or_default() += 1;
+    }
+    let total = (palette.len() - 1) as f64;
+    let entropy: f64 = steps.values()
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+    // The most steps can take is one distinct value per transition
+    entropy / total.log2()
+}
+
+fn usage_balance(pairs: &[(usize, usize)], indices: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &index in indices {
+        counts[index as usize] += 1;
+    }
+
+    let (mut balanced, mut total) = (0.0, 0.0);
+    for &(a, b) in pairs {
+        let (na, nb) = (counts.get(a).copied().unwrap_or(0), counts.get(b).copied().unwrap_or(0));
+        if na + nb == 0 {
+            continue;
+        }
+        let weight = (na + nb) as f64;
+        balanced += weight * (1.0 - na.abs_diff(nb) as f64 / weight);
+        total += weight;
+    }
+    if total == 0.0 { 0.0 } else { balanced / total }
+}
+
+/// RGB palette of an `/Indexed /DeviceRGB` image, or `None` for other images
+pub fn indexed_palette(doc: &lopdf::Document, image: &Dictionary) -> Option<Vec<[u8; 3]>> {
+    let color_space = match image.get(b"ColorSpace").ok()? {
+        Object::Reference(id) => doc.get_object(*id).ok()?,
+        other => other,
+    };
+    let Object::Array(parts) = color_space else { return None };
+    let [Object::Name(kind), base, hival, lookup] = parts.as_slice() else { return None };
+    if kind != b"Indexed" || !matches!(base, Object::Name(name) if name == b"DeviceRGB") {
+        return None;
+    }
+
+    let entries = hival.as_i64().ok()?.clamp(0, 255) as usize + 1;
+    let table = match lookup {
+        Object::String(bytes, _) => bytes.clone(),
+        Object::Reference(id) => {
+            let stream = doc.get_object(*id).and_then(Object::as_stream).ok()?;
+            stream.decompressed_content().unwrap_or_else(|_| stream.content.clone())
+        }
+        _ => return None,
+    };
+    Some(table.chunks_exact(3).take(entries).map(|c| [c[0], c[1], c[2]]).collect())
+}
+
+/// Analyzes every 8-bit indexed RGB image of a document
+pub fn analyze_document(doc: &Document) -> Vec<(ObjectId, PaletteAnalysis)> {
+    doc.streams()
+        .filter_map(|(id, stream)| {
+            let palette = indexed_palette(&doc.structure, &stream.dict)?;
+            // Index statistics only make sense when each byte is one sample
+            let indices = match stream.dict.get(b"BitsPerComponent").and_then(Object::as_i64) {
+                Ok(8) if stream.dict.has(b"Filter") => stream.decompressed_content().ok(),
+                Ok(8) => Some(stream.content.clone()),
+                _ => None,
+            };
+            Some((id, analyze_palette(&palette, indices.as_deref())))
+        })
+        .collect()
+}
+
+/// Reports indexed images whose palettes look manipulated
+#[derive(Debug, Clone)]
+pub struct PaletteScanner {
+    /// Scores at or above this are reported
+    pub threshold: f64,
+}
+
+impl Default for PaletteScanner {
+    fn default() -> Self {
+        Self { threshold: 0.75 }
+    }
+}
+
+impl PaletteScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        analyze_document(doc).into_iter()
+            .filter(|(_, analysis)| analysis.score >= self.threshold)
+            .map(|(id, analysis)| {
+                debug!("Palette anomalies in {} {} R: {:?}", id.0, id.1, analysis.indicators());
+                let mut metadata = HashMap::new();
+                metadata.insert("entries".into(), analysis.entries.to_string());
+                metadata.insert("near_duplicate_pairs".into(), analysis.near_duplicate_pairs.to_string());
+                metadata.insert("score".into(), format!("{:.2}", analysis.score));
+                ForensicArtifact {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    artifact_type: ArtifactType::Binary,
+                    location: format!("{} {} R", id.0, id.1),
+                    description: format!("Indexed image palette looks manipulated: {}", analysis.indicators().join("; ")),
+                    risk_level: RiskLevel::Medium,
+                    remediation: "Re-encode the image or remove it".into(),
+                    metadata,
+                    confidence: analysis.score,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    /// Coarse 6x6x6 color cube, a typical quantizer palette
+    fn natural_palette() -> Vec<[u8; 3]> {
+        let mut palette = Vec::new();
+        for r in 0..6u8 {
+            for g in 0..6u8 {
+                for b in 0..6u8 {
+                    palette.push([r * 51, g * 51, b * 51]);
+                }
+            }
+        }
+        palette
+    }
+
+    /// EzStego-style palette: every color followed by a one-level twin
+    fn stego_palette() -> Vec<[u8; 3]> {
+        (0..64u8).flat_map(|i| {
+            let color = [i * 4, 255 - i * 4, i * 2];
+            [color, [color[0] + 1, color[1], color[2]]]
+        }).collect()
+    }
+
+    #[test]
+    fn test_natural_palette_scores_low() {
+        let analysis = analyze_palette(&natural_palette(), None);
+        assert_eq!(analysis.near_duplicate_pairs, 0);
+        assert!(analysis.score < 0.3, "score {}", analysis.score);
+    }
+
+    #[test]
+    fn test_paired_palette_with_balanced_usage_scores_high() {
+        let palette = stego_palette();
+        // Embedding flips each pixel between the twins about half the time
+        let indices: Vec<u8> = (0..4096u32).map(|i| ((i / 2 % 64) * 2 + i % 2) as u8).collect();
+        let analysis = analyze_palette(&palette, Some(&indices));
+
+        assert_eq!(analysis.near_duplicate_pairs, 64);
+        assert_eq!(analysis.adjacent_pair_ratio, 1.0);
+        assert!(analysis.pair_usage_balance.unwrap() > 0.99);
+        assert!(analysis.score > 0.8, "score {}", analysis.score);
+    }
+
+    #[test]
+    fn test_ordering_entropy_extremes() {
+        let sorted: Vec<[u8; 3]> = (0..64u8).map(|i| [i * 4, i * 4, i * 4]).collect();
+        assert_eq!(ordering_entropy(&sorted), 0.0);
+
+        // Fisher-Yates with a fixed LCG, so the test is deterministic
+        let mut levels: Vec<u8> = (0..64).collect();
+        let mut state = 12345u32;
+        for k in (1..levels.len()).rev() {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            levels.swap(k, (state >> 16) as usize % (k + 1));
+        }
+        let shuffled: Vec<[u8; 3]> = levels.iter().map(|&v| [v * 4, v * 4, v * 4]).collect();
+        assert!(ordering_entropy(&shuffled) > 0.5);
+    }
+
+    #[test]
+    fn test_indexed_image_palette_extracted() {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let lookup: Vec<u8> = stego_palette().concat();
+        let image = doc.add_object(Stream::new(dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => 2,
+            "Height" => 1,
+            "BitsPerComponent" => 8,
+            "ColorSpace" => vec![
+                Object::Name(b"Indexed".to_vec()),
+                Object::Name(b"DeviceRGB".to_vec()),
+                Object::Integer(127),
+                Object::String(lookup, lopdf::StringFormat::Hexadecimal),
+            ],
+        }, vec![0, 1]));
+
+        let doc = Document::from_pdf(std::path::PathBuf::new(), doc);
+        let results = analyze_document(&doc);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, image);
+        assert_eq!(results[0].1.entries, 128);
+
+        // Two samples are too few to judge usage, but the palette alone is paired
+        let artifacts = PaletteScanner::new().scan(&doc);
+        assert_eq!(artifacts.len(), usize::from(results[0].1.score >= 0.75));
+        assert!(artifacts.iter().all(|a| a.location == format!("{} 0 R", image.0)));
+    }
+}
@@ -258,7 +258,7 @@ impl Scanner for PdfScanner {
             path: path.clone(),
             size: data.len() as u64,
             file_type: "PDF".into(),
-            findings,
+            findings: AggregatedFindings::from_findings(findings, &self.config.base.aggregation),
             metadata,
             metrics: ScanMetrics {
                 duration,
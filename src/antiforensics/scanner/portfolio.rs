@@ -0,0 +1,286 @@
+//! PDF portfolio (collection) detection and scanning
+//! Author: kartik4091
+//! Created: 2025-06-04 18:40:17 UTC
+//! A portfolio is a cover document whose catalog carries /Collection and
+//! whose real content is a set of embedded files. Scanning only the
+//! cover misses everything that matters, so embedded PDFs are parsed and
+//! scanned in turn, with their findings prefixed by the entry name.
+
+use std::{collections::HashMap, path::PathBuf};
+use lopdf::{Dictionary, Object, ObjectId};
+use tracing::{debug, instrument, warn};
+
+use crate::antiforensics::{
+    references::Visited,
+    types::Document,
+    utils::{resolve_dict, streams::{decoded_prefix, decoded_within}},
+    ArtifactType,
+    ForensicArtifact,
+    RiskLevel,
+};
+use super::{document_scanner::DocumentScanner, mime::{FileType, PDF_HEADER_WINDOW}};
+
+/// Maximum name tree depth followed
+const MAX_TREE_DEPTH: usize = 32;
+
+/// One embedded file of a portfolio
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortfolioEntry {
+    /// Display name, from /UF, /F or the name tree key
+    pub name: String,
+    /// File specification dictionary, when indirect
+    pub filespec_id: Option<ObjectId>,
+    /// Embedded file stream
+    pub stream_id: ObjectId,
+    /// Whether the decoded content sniffs as a PDF
+    pub is_pdf: bool,
+}
+
+/// Whether the catalog declares a collection
+pub fn is_portfolio(doc: &lopdf::Document) -> bool {
+    catalog(doc).is_some_and(|catalog| catalog.has(b"Collection"))
+}
+
+/// Embedded files listed in the /EmbeddedFiles name tree, in tree order
+pub fn entries(doc: &lopdf::Document) -> Vec<PortfolioEntry> {
+    let mut entries = Vec::new();
+    let tree = catalog(doc)
+        .and_then(|catalog| resolve_dict(doc, catalog.get(b"Names").ok()?))
+        .and_then(|names| resolve_dict(doc, names.get(b"EmbeddedFiles").ok()?));
+    if let Some(tree) = tree {
+        let mut visited = Visited::new();
+        collect(doc, tree, 0, &mut visited, &mut entries);
+        for cycle in &visited.cycles {
+            warn!("EmbeddedFiles name tree loops through {}; entries listed once", cycle);
+        }
+    }
+    entries
+}
+
+fn catalog(doc: &lopdf::Document) -> Option<&Dictionary> {
+    let root = doc.trailer.get(b"Root").ok()?;
+    resolve_dict(doc, root)
+}
+
+fn collect(
+    doc: &lopdf::Document,
+    node: &Dictionary,
+    depth: usize,
+    visited: &mut Visited,
+    entries: &mut Vec<PortfolioEntry>,
+) {
+    if depth > MAX_TREE_DEPTH {
+        warn!("EmbeddedFiles name tree deeper than {}; remaining entries skipped", MAX_TREE_DEPTH);
+        return;
+    }
+
+    if let Ok(Object::Array(names)) = node.get(b"Names") {
+        for pair in names.chunks(2) {
+            let [Object::String(key, _), value] = pair else { continue };
+            if let Some(entry) = entry(doc, &String::from_utf8_lossy(key), value) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    if let Ok(Object::Array(kids)) = node.get(b"Kids") {
+        for kid in kids {
+            let Some(dict) = resolve_dict(doc, kid) else { continue };
+            match kid.as_reference() {
+                Ok(id) if visited.enter(id) => {
+                    collect(doc, dict, depth + 1, visited, entries);
+                    visited.leave();
+                }
+                Ok(_) => {}
+                Err(_) => collect(doc, dict, depth + 1, visited, entries),
+            }
+        }
+    }
+}
+
+fn entry(doc: &lopdf::Document, key: &str, filespec: &Object) -> Option<PortfolioEntry> {
+    let filespec_id = filespec.as_reference().ok();
+    let spec = resolve_dict(doc, filespec)?;
+    let ef = resolve_dict(doc, spec.get(b"EF").ok()?)?;
+    let stream_id = ef.get(b"UF").or_else(|_| ef.get(b"F")).ok()?.as_reference().ok()?;
+    let stream = doc.get_object(stream_id).ok()?.as_stream().ok()?;
+
+    let name = [&b"UF"[..], b"F"].iter()
+        .find_map(|k| match spec.get(k) {
+            Ok(Object::String(bytes, _)) if !bytes.is_empty() => Some(String::from_utf8_lossy(bytes).into_owned()),
+            _ => None,
+        })
+        .unwrap_or_else(|| key.to_string());
+    // The header is all sniffing needs; the rest may inflate without bound
+    let head = decoded_prefix(stream, PDF_HEADER_WINDOW);
+
+    Some(PortfolioEntry {
+        name,
+        filespec_id,
+        stream_id,
+        is_pdf: FileType::sniff(&head) == FileType::Pdf,
+    })
+}
+
+/// Scans a portfolio's cover and every embedded PDF
+#[derive(Debug, Clone)]
+pub struct PortfolioScanner {
+    scanner: DocumentScanner,
+    /// Portfolios nested inside portfolios are followed this deep
+    max_nesting: usize,
+    /// Embedded PDFs decoding to more bytes than this are not scanned
+    max_entry_bytes: usize,
+}
+
+impl PortfolioScanner {
+    pub fn new() -> Self {
+        Self {
+            scanner: DocumentScanner::new(),
+            max_nesting: 4,
+            max_entry_bytes: 256 * 1024 * 1024,
+        }
+    }
+
+    /// Scans embedded PDFs; returns nothing for documents that are not portfolios
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        self.scan_level(&doc.structure, "", 0)
+    }
+
+    fn scan_level(&self, doc: &lopdf::Document, prefix: &str, nesting: usize) -> Vec<ForensicArtifact> {
+        if !is_portfolio(doc) {
+            return Vec::new();
+        }
+
+        let entries = entries(doc);
+        let mut artifacts = vec![portfolio_artifact(prefix, &entries)];
+        if nesting >= self.max_nesting {
+            warn!("portfolio nesting deeper than {}; inner documents not scanned", self.max_nesting);
+            return artifacts;
+        }
+
+        for entry in entries.iter().filter(|e| e.is_pdf) {
+            let Ok(stream) = doc.get_object(entry.stream_id).and_then(Object::as_stream) else { continue };
+            let Some(content) = decoded_within(stream, self.max_entry_bytes) else {
+                warn!("embedded PDF {} decodes to more than {} bytes; not scanned", entry.name, self.max_entry_bytes);
+                continue;
+            };
+            let Ok(inner) = Document::from_bytes(PathBuf::from(&entry.name), content) else {
+                debug!("embedded PDF {} did not parse", entry.name);
+                continue;
+            };
+
+            let entry_prefix = format!("{}portfolio:{}/", prefix, entry.name);
+            for mut artifact in self.scanner.scan(&inner) {
+                artifact.location = format!("{}{}", entry_prefix, artifact.location);
+                artifact.metadata.insert("portfolio_entry".into(), entry.name.clone());
+                artifacts.push(artifact);
+            }
+            artifacts.extend(self.scan_level(&inner.structure, &entry_prefix, nesting + 1));
+        }
+
+        artifacts
+    }
+}
+
+impl Default for PortfolioScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn portfolio_artifact(prefix: &str, entries: &[PortfolioEntry]) -> ForensicArtifact {
+    let pdfs = entries.iter().filter(|e| e.is_pdf).count();
+    let mut metadata = HashMap::new();
+    metadata.insert("entries".into(), entries.len().to_string());
+    metadata.insert("embedded_pdfs".into(), pdfs.to_string());
+
+    ForensicArtifact {
+        id: uuid::Uuid::new_v4().to_string(),
+        artifact_type: ArtifactType::EmbeddedFile,
+        location: format!("{}/Root/Collection", prefix),
+        description: format!("PDF portfolio with {} embedded file(s), {} of them PDFs", entries.len(), pdfs),
+        risk_level: RiskLevel::Medium,
+        remediation: "Clean every portfolio entry or split the portfolio into separate documents".into(),
+        metadata,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::fixtures::Fixture;
+    use lopdf::{dictionary, Stream};
+
+    /// Portfolio with one embedded PDF and one text file
+    pub(crate) fn portfolio() -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let pdf = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, Fixture::JavaScript.bytes()));
+        let text = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, b"notes".to_vec()));
+        let pdf_spec = doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "UF" => Object::string_literal("invoice.pdf"),
+            "EF" => dictionary! { "F" => pdf },
+        });
+        let text_spec = doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal("notes.txt"),
+            "EF" => dictionary! { "F" => text },
+        });
+        let catalog = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Collection" => dictionary! { "View" => "D" },
+            "Names" => dictionary! {
+                "EmbeddedFiles" => dictionary! {
+                    "Names" => vec![
+                        Object::string_literal("1"), Object::Reference(pdf_spec),
+                        Object::string_literal("2"), Object::Reference(text_spec),
+                    ],
+                },
+            },
+        });
+        doc.trailer.set("Root", catalog);
+        doc
+    }
+
+    #[test]
+    fn test_entries_enumerated() {
+        let doc = portfolio();
+        assert!(is_portfolio(&doc));
+
+        let entries = entries(&doc);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "invoice.pdf");
+        assert!(entries[0].is_pdf);
+        assert_eq!(entries[1].name, "notes.txt");
+        assert!(!entries[1].is_pdf);
+    }
+
+    #[test]
+    fn test_embedded_pdf_findings_prefixed() {
+        let doc = Document::from_pdf(PathBuf::from("portfolio.pdf"), portfolio());
+        let artifacts = PortfolioScanner::new().scan(&doc);
+
+        assert!(artifacts[0].description.contains("2 embedded file(s)"));
+        assert!(artifacts.iter().any(|a| a.location.starts_with("portfolio:invoice.pdf/")
+            && a.artifact_type == ArtifactType::JavaScript));
+    }
+
+    #[test]
+    fn test_oversized_entries_not_scanned() {
+        let doc = Document::from_pdf(PathBuf::from("portfolio.pdf"), portfolio());
+        let scanner = PortfolioScanner { max_entry_bytes: 64, ..PortfolioScanner::new() };
+        let artifacts = scanner.scan(&doc);
+
+        assert_eq!(artifacts.len(), 1);
+        assert!(artifacts[0].description.contains("1 of them PDFs"));
+    }
+
+    #[test]
+    fn test_plain_document_is_not_portfolio() {
+        let doc = Fixture::Clean.document().unwrap();
+        assert!(!is_portfolio(&doc));
+        assert!(entries(&doc).is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+//! Approximate scan of raw file bytes
+//! Author: kartik4091
+//! Created: 2025-06-04 22:44:52 UTC
+//! Triage over large corpora cannot afford to parse every document.
+//! [`QuickScanner`] streams the raw file through the names and idioms of
+//! the active pattern database without building the object graph or
+//! decompressing streams, and turns the hits into a coarse risk estimate.
+//!
+//! The estimate is approximate by construction: anything inside
+//! compressed streams or object streams is invisible to it, and names in
+//! comments or string literals count as hits. Reports and artifacts are
+//! labeled accordingly; run the full scan before acting on a file.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+use regex::bytes::Regex;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use crate::antiforensics::{
+    patterns::{self, canonical, PatternDatabase, PatternKind, PatternRule, DATABASE_VERSION_KEY},
+    ArtifactType,
+    ForensicArtifact,
+    RiskLevel,
+};
+
+/// Metadata key marking artifacts of the quick scan
+pub const APPROXIMATE_KEY: &str = "approximate";
+
+/// Bytes carried between reads so patterns spanning a boundary are found
+const OVERLAP: usize = 256;
+
+/// PDF names; `#xx` escapes are decoded before matching
+fn name_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"/([A-Za-z0-9#_.+\-]{1,127})").expect("valid name pattern"))
+}
+
+/// Coarse findings of one quick scan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct QuickScanReport {
+    /// Always true; quick scans never see compressed content
+    pub approximate: bool,
+    pub bytes_scanned: u64,
+    pub duration: Duration,
+    /// Whether the file starts with a `%PDF-` header
+    pub pdf_header: bool,
+    /// Signature hits by name
+    pub hits: BTreeMap<String, usize>,
+    /// Names written with `#xx` escapes that decode to a signature,
+    /// a common way to slip past naive scanners
+    pub obfuscated_names: usize,
+    /// Script idioms such as `eval(` in uncompressed content
+    pub script_idioms: usize,
+    /// `%%EOF` markers after the first, one per incremental update
+    pub incremental_updates: usize,
+    /// Risk in `0.0..=1.0`
+    pub score: f64,
+    pub risk_level: RiskLevel,
+    /// Version of the pattern database the scan matched against
+    #[serde(default)]
+    pub pattern_db_version: u32,
+}
+
+/// One line of `quick-scan --json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct QuickScanLine {
+    pub path: PathBuf,
+    pub report: QuickScanReport,
+}
+
+impl QuickScanReport {
+    /// One artifact per signature hit, labeled approximate
+    ///
+    /// Descriptions and risk levels come from the active pattern database.
+    pub fn artifacts(&self) -> Vec<ForensicArtifact> {
+        let patterns = patterns::active();
+        let mut artifacts: Vec<ForensicArtifact> = patterns.rules().iter()
+            .filter_map(|rule| {
+                let name = raw_name(rule)?;
+                let count = *self.hits.get(name)?;
+                Some(self.artifact(
+                    rule.artifact_type.clone(),
+                    rule.risk_level,
+                    format!("/{}", name),
+                    format!("{} (/{} seen {} time(s), approximate)", rule.description, name, count),
+                ))
+            })
+            .collect();
+        if self.obfuscated_names > 0 {
+            artifacts.push(self.artifact(
+                ArtifactType::Structure,
+                RiskLevel::High,
+                "raw bytes".into(),
+                format!("{} hex-escaped name(s) hiding active content (approximate)", self.obfuscated_names),
+            ));
+        }
+        if self.script_idioms > 0 {
+            let (artifact_type, risk_level) = patterns.idioms().next()
+                .map_or((ArtifactType::JavaScript, RiskLevel::High), |(rule, _)| (rule.artifact_type.clone(), rule.risk_level));
+            artifacts.push(self.artifact(
+                artifact_type,
+                risk_level,
+                "raw bytes".into(),
+                format!("{} script idiom(s) in uncompressed content (approximate)", self.script_idioms),
+            ));
+        }
+        artifacts
+    }
+
+    fn artifact(&self, artifact_type: ArtifactType, risk_level: RiskLevel, location: String, description: String) -> ForensicArtifact {
+        let mut metadata = HashMap::new();
+        metadata.insert(APPROXIMATE_KEY.to_string(), "true".to_string());
+        metadata.insert(DATABASE_VERSION_KEY.to_string(), self.pattern_db_version.to_string());
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type,
+            location,
+            description,
+            risk_level,
+            remediation: "Run the full scan to confirm".into(),
+            metadata,
+            detection_timestamp: chrono::Utc::now(),
+            // Raw-byte hits are weaker evidence than parsed objects
+            confidence: 0.6,
+            ..Default::default()
+        }
+    }
+}
+
+/// Name a rule matches in raw bytes, if any
+fn raw_name(rule: &PatternRule) -> Option<&str> {
+    match &rule.kind {
+        PatternKind::Key(name) | PatternKind::RawName(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// Streams raw bytes through signature patterns
+#[derive(Debug, Clone)]
+pub struct QuickScanner {
+    /// Read size
+    pub chunk_size: usize,
+    /// Stop after this many bytes; the report covers the prefix only
+    pub max_bytes: Option<u64>,
+    /// Patterns to match instead of the active database
+    pub patterns: Option<Arc<PatternDatabase>>,
+}
+
+impl Default for QuickScanner {
+    fn default() -> Self {
+        Self { chunk_size: 1024 * 1024, max_bytes: None, patterns: None }
+    }
+}
+
+impl QuickScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scan_file(&self, path: &Path) -> io::Result<QuickScanReport> {
+        self.scan_reader(std::fs::File::open(path)?)
+    }
+
+    pub fn scan_reader(&self, mut reader: impl Read) -> io::Result<QuickScanReport> {
+        let started = Instant::now();
+        let patterns = self.patterns.clone().unwrap_or_else(patterns::active);
+        let mut report = QuickScanReport {
+            approximate: true,
+            bytes_scanned: 0,
+            duration: Duration::ZERO,
+            pdf_header: false,
+            hits: BTreeMap::new(),
+            obfuscated_names: 0,
+            script_idioms: 0,
+            incremental_updates: 0,
+            score: 0.0,
+            risk_level: RiskLevel::None,
+            pattern_db_version: patterns.version(),
+        };
+        let mut buffer: Vec<u8> = Vec::with_capacity(self.chunk_size + OVERLAP);
+        let mut chunk = vec![0u8; self.chunk_size.max(OVERLAP)];
+        let mut eof_markers = 0;
+        let mut first = true;
+
+        loop {
+            let limit = self.max_bytes.map_or(chunk.len(), |max| {
+                chunk.len().min(max.saturating_sub(report.bytes_scanned) as usize)
+            });
+            let n = if limit == 0 { 0 } else { reader.read(&mut chunk[..limit])? };
+            let last = n == 0;
+            buffer.extend_from_slice(&chunk[..n]);
+            report.bytes_scanned += n as u64;
+
+            // The header may follow up to 1 KiB of junk; the first read covers
+            // as much of that as the chunk size allows
+            if first {
+                report.pdf_header = buffer[..buffer.len().min(1024)].windows(5).any(|w| w == b"%PDF-");
+                first = false;
+            }
+
+            // Matches starting in the overlap are counted on the next pass
+            let boundary = if last { buffer.len() } else { buffer.len().saturating_sub(OVERLAP) };
+            if boundary > 0 || last {
+                self.scan_window(&patterns, &buffer, boundary, &mut report, &mut eof_markers);
+                buffer.drain(..boundary);
+            }
+            if last {
+                break;
+            }
+        }
+
+        report.incremental_updates = eof_markers.saturating_sub(1);
+        Self::score(&patterns, &mut report);
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
+    fn scan_window(
+        &self,
+        patterns: &PatternDatabase,
+        window: &[u8],
+        boundary: usize,
+        report: &mut QuickScanReport,
+        eof_markers: &mut usize,
+    ) {
+        for capture in name_pattern().captures_iter(window) {
+            let whole = capture.get(0).expect("group 0 always matches");
+            if whole.start() >= boundary {
+                break;
+            }
+            let raw = &capture[1];
+            if let Some(name) = patterns.name(&canonical::decode_name(raw)).and_then(raw_name) {
+                *report.hits.entry(name.to_string()).or_default() += 1;
+                if canonical::is_obfuscated_name(raw) {
+                    report.obfuscated_names += 1;
+                }
+            }
+        }
+        let regex_match = tracing::trace_span!("regex_match", bytes = window.len()).entered();
+        for (_, idiom) in patterns.idioms() {
+            report.script_idioms += idiom.find_iter(window).filter(|m| m.start() < boundary).count();
+        }
+        drop(regex_match);
+        *eof_markers += window.windows(5)
+            .enumerate()
+            .filter(|(start, w)| *start < boundary && *w == b"%%EOF")
+            .count();
+    }
+
+    fn score(patterns: &PatternDatabase, report: &mut QuickScanReport) {
+        let mut score: f64 = patterns.rules().iter()
+            .filter(|rule| raw_name(rule).map_or(false, |name| report.hits.contains_key(name)))
+            .map(|rule| rule.weight)
+            .sum();
+        if report.obfuscated_names > 0 {
+            score += 0.4;
+        }
+        if report.script_idioms > 0 {
+            score += patterns.idioms().map(|(rule, _)| rule.weight).fold(0.0, f64::max);
+        }
+        if !report.pdf_header {
+            score += 0.1;
+        }
+        report.score = score.min(1.0);
+        report.risk_level = match report.score {
+            s if s >= 0.8 => RiskLevel::Critical,
+            s if s >= 0.5 => RiskLevel::High,
+            s if s >= 0.2 => RiskLevel::Medium,
+            s if s > 0.0 => RiskLevel::Low,
+            _ => RiskLevel::None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(data: &[u8], chunk_size: usize) -> QuickScanReport {
+        QuickScanner { chunk_size, ..Default::default() }.scan_reader(data).unwrap()
+    }
+
+    #[test]
+    fn test_clean_file_scores_none() {
+        let report = scan(b"%PDF-1.7\n1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj\n%%EOF\n", 4096);
+        assert!(report.approximate && report.pdf_header);
+        assert!(report.hits.is_empty());
+        assert_eq!(report.risk_level, RiskLevel::None);
+        assert!(report.artifacts().is_empty());
+    }
+
+    #[test]
+    fn test_obfuscated_script_is_high_risk() {
+        let data = b"%PDF-1.4\n1 0 obj << /OpenAction << /S /J#61vaScript /JS (eval(x)) >> >> endobj\n%%EOF\n%%EOF\n";
+        let report = scan(data, 4096);
+
+        assert_eq!(report.hits["JavaScript"], 1);
+        assert_eq!(report.obfuscated_names, 1);
+        assert_eq!(report.script_idioms, 1);
+        assert_eq!(report.incremental_updates, 1);
+        assert!(matches!(report.risk_level, RiskLevel::High | RiskLevel::Critical));
+        assert!(report.artifacts().iter().all(|a| a.metadata[APPROXIMATE_KEY] == "true"));
+        assert!(report.artifacts().iter().all(|a| a.metadata[DATABASE_VERSION_KEY] == report.pattern_db_version.to_string()));
+    }
+
+    #[test]
+    fn test_chunk_boundaries_do_not_change_counts() {
+        let mut data = b"%PDF-1.7\n".to_vec();
+        for i in 0..200 {
+            data.extend_from_slice(format!("{} 0 obj << /S /URI /URI (x) /AA << >> >> endobj\n", i).as_bytes());
+        }
+        let whole = scan(&data, 1 << 20);
+        for chunk_size in [300, 512, 1000] {
+            let chunked = scan(&data, chunk_size);
+            assert_eq!(chunked.hits, whole.hits, "chunk size {}", chunk_size);
+        }
+        assert_eq!(whole.hits["URI"], 400);
+        assert_eq!(whole.hits["AA"], 200);
+    }
+
+    #[test]
+    fn test_max_bytes_limits_the_scan() {
+        let data = [b"%PDF-1.7\n".as_slice(), &[b' '; 2000], b"/Launch"].concat();
+        let report = QuickScanner { chunk_size: 512, max_bytes: Some(1024), ..Default::default() }.scan_reader(data.as_slice()).unwrap();
+        assert_eq!(report.bytes_scanned, 1024);
+        assert!(report.hits.is_empty());
+    }
+}
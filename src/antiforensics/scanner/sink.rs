@@ -0,0 +1,334 @@
+//! Artifact sinks for streaming scan findings
+//! Author: kartik4091
+//! Created: 2025-06-04 10:41:17 UTC
+//! Sinks receive artifacts as soon as a scanner detects them, so
+//! high-volume pipelines can forward findings to queues or collectors
+//! without waiting for the final ScanResult. `WebhookSink` needs the
+//! `webhook` feature.
+
+use std::path::Path;
+#[cfg(feature = "webhook")]
+use std::time::Duration;
+use async_trait::async_trait;
+use tokio::{
+    fs::File,
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
+    sync::{mpsc, Mutex},
+};
+use tracing::warn;
+#[cfg(feature = "webhook")]
+use tracing::{debug, instrument};
+
+use crate::antiforensics::ForensicArtifact;
+
+/// Custom error type for artifact sinks
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Delivery failed: {0}")]
+    Delivery(String),
+
+    #[error("Sink closed: {0}")]
+    Closed(String),
+}
+
+/// Result type alias for sink operations
+pub type Result<T> = std::result::Result<T, SinkError>;
+
+/// Receiver of artifacts as they are detected
+#[async_trait]
+pub trait ArtifactSink: Send + Sync {
+    /// Short name used in logs
+    fn name(&self) -> &str;
+
+    /// Delivers a single artifact
+    async fn emit(&self, artifact: &ForensicArtifact) -> Result<()>;
+
+    /// Delivers a batch of artifacts
+    async fn emit_batch(&self, artifacts: &[ForensicArtifact]) -> Result<()> {
+        for artifact in artifacts {
+            self.emit(artifact).await?;
+        }
+        Ok(())
+    }
+
+    /// Flushes any buffered artifacts
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes one JSON object per line to any async writer
+pub struct JsonlSink<W: AsyncWrite + Unpin + Send> {
+    name: String,
+    writer: Mutex<BufWriter<W>>,
+}
+
+impl JsonlSink<tokio::io::Stdout> {
+    /// Creates a sink writing to standard output
+    pub fn stdout() -> Self {
+        Self::new("stdout", tokio::io::stdout())
+    }
+}
+
+impl JsonlSink<File> {
+    /// Creates a sink appending to a JSONL file
+    pub async fn file(path: &Path) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(Self::new(&path.display().to_string(), file))
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send> JsonlSink<W> {
+    /// Creates a sink over an arbitrary writer
+    pub fn new(name: &str, writer: W) -> Self {
+        Self {
+            name: format!("jsonl:{}", name),
+            writer: Mutex::new(BufWriter::new(writer)),
+        }
+    }
+
+    /// Consumes the sink and returns the inner writer
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().into_inner()
+    }
+}
+
+#[async_trait]
+impl<W: AsyncWrite + Unpin + Send> ArtifactSink for JsonlSink<W> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn emit(&self, artifact: &ForensicArtifact) -> Result<()> {
+        let mut line = serde_json::to_vec(artifact)?;
+        line.push(b'\n');
+        self.writer.lock().await.write_all(&line).await?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.writer.lock().await.flush().await?;
+        Ok(())
+    }
+}
+
+/// Webhook sink configuration
+#[cfg(feature = "webhook")]
+#[derive(Debug, Clone)]
+pub struct WebhookSinkConfig {
+    /// Endpoint receiving POSTed JSON arrays of artifacts
+    pub url: String,
+    /// Number of artifacts buffered before a POST
+    pub batch_size: usize,
+    /// Request timeout
+    pub timeout: Duration,
+    /// Optional bearer token
+    pub auth_token: Option<String>,
+}
+
+#[cfg(feature = "webhook")]
+/// POSTs batches of artifacts to an HTTP endpoint
+#[cfg(feature = "webhook")]
+pub struct WebhookSink {
+    config: WebhookSinkConfig,
+    client: reqwest::Client,
+    buffer: Mutex<Vec<ForensicArtifact>>,
+}
+
+#[cfg(feature = "webhook")]
+impl WebhookSink {
+    /// Creates a new webhook sink
+    pub fn new(config: WebhookSinkConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| SinkError::Delivery(format!("Failed to build HTTP client: {}", e)))?;
+
+        Ok(Self {
+            config,
+            client,
+            buffer: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Sends a batch to the endpoint
+    #[instrument(skip(self, batch), fields(count = batch.len()))]
+    async fn post(&self, batch: &[ForensicArtifact]) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let mut request = self.client.post(&self.config.url).json(batch);
+        if let Some(token) = &self.config.auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await
+            .map_err(|e| SinkError::Delivery(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(SinkError::Delivery(format!(
+                "Webhook {} returned {}", self.config.url, response.status()
+            )));
+        }
+
+        debug!("Delivered {} artifacts to webhook", batch.len());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "webhook")]
+#[async_trait]
+impl ArtifactSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn emit(&self, artifact: &ForensicArtifact) -> Result<()> {
+        let ready = {
+            let mut buffer = self.buffer.lock().await;
+            buffer.push(artifact.clone());
+            if buffer.len() >= self.config.batch_size.max(1) {
+                std::mem::take(&mut *buffer)
+            } else {
+                Vec::new()
+            }
+        };
+        self.post(&ready).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let pending = std::mem::take(&mut *self.buffer.lock().await);
+        self.post(&pending).await
+    }
+}
+
+/// Forwards artifacts into a tokio channel
+pub struct ChannelSink {
+    sender: mpsc::Sender<ForensicArtifact>,
+}
+
+impl ChannelSink {
+    /// Creates a sink and the receiving end of its channel
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<ForensicArtifact>) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        (Self { sender }, receiver)
+    }
+
+    /// Creates a sink from an existing sender
+    pub fn from_sender(sender: mpsc::Sender<ForensicArtifact>) -> Self {
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl ArtifactSink for ChannelSink {
+    fn name(&self) -> &str {
+        "channel"
+    }
+
+    async fn emit(&self, artifact: &ForensicArtifact) -> Result<()> {
+        self.sender.send(artifact.clone()).await
+            .map_err(|_| SinkError::Closed("Artifact receiver dropped".into()))
+    }
+}
+
+/// Delivers artifacts to every sink, logging rather than propagating failures
+///
+/// A misbehaving sink must never fail the scan itself.
+pub async fn publish(sinks: &[std::sync::Arc<dyn ArtifactSink>], artifacts: &[ForensicArtifact]) {
+    if artifacts.is_empty() {
+        return;
+    }
+
+    for sink in sinks {
+        if let Err(e) = sink.emit_batch(artifacts).await {
+            warn!("Artifact sink {} failed: {}", sink.name(), e);
+        }
+    }
+}
+
+/// Flushes every sink, logging failures
+pub async fn flush_all(sinks: &[std::sync::Arc<dyn ArtifactSink>]) {
+    for sink in sinks {
+        if let Err(e) = sink.flush().await {
+            warn!("Failed to flush artifact sink {}: {}", sink.name(), e);
+        }
+    }
+}
+
+impl Default for WebhookSinkConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            batch_size: 100,
+            timeout: Duration::from_secs(10),
+            auth_token: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn artifact(id: &str) -> ForensicArtifact {
+        ForensicArtifact {
+            id: id.into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_sink_writes_lines() {
+        let sink = JsonlSink::new("memory", Vec::new());
+        sink.emit(&artifact("a")).await.unwrap();
+        sink.emit(&artifact("b")).await.unwrap();
+        sink.flush().await.unwrap();
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        let ids: Vec<String> = output.lines()
+            .map(|l| serde_json::from_str::<ForensicArtifact>(l).unwrap().id)
+            .collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_channel_sink_forwards() {
+        let (sink, mut rx) = ChannelSink::new(4);
+        sink.emit_batch(&[artifact("a"), artifact("b")]).await.unwrap();
+
+        assert_eq!(rx.recv().await.unwrap().id, "a");
+        assert_eq!(rx.recv().await.unwrap().id, "b");
+    }
+
+    #[tokio::test]
+    async fn test_channel_sink_closed() {
+        let (sink, rx) = ChannelSink::new(1);
+        drop(rx);
+        assert!(matches!(sink.emit(&artifact("a")).await, Err(SinkError::Closed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_publish_tolerates_failing_sink() {
+        let (closed, rx) = ChannelSink::new(1);
+        drop(rx);
+        let (open, mut open_rx) = ChannelSink::new(4);
+
+        let sinks: Vec<Arc<dyn ArtifactSink>> = vec![Arc::new(closed), Arc::new(open)];
+        publish(&sinks, &[artifact("a")]).await;
+
+        assert_eq!(open_rx.recv().await.unwrap().id, "a");
+    }
+}
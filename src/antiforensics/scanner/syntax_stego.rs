@@ -0,0 +1,440 @@
+//! Comment and whitespace steganography detection
+//! Author: kartik4091
+//! Created: 2025-06-04 20:03:51 UTC
+//! Works on the raw file bytes rather than the parsed object graph, which
+//! discards comments, padding and whatever follows the last `%%EOF`. The
+//! lexer skips strings and stream data and measures comment density and
+//! content, the gaps between objects, space/tab runs that could encode
+//! bits, and trailing data. Names and literal strings that escape letters
+//! or digits are recorded too: the parser decodes them, so only the raw
+//! bytes show that a key like /JavaScript was spelled to evade matching.
+
+use tracing::instrument;
+
+use crate::antiforensics::{
+    patterns::{self, canonical},
+    utils::{entropy, FindingTemplate},
+    ArtifactType,
+    ForensicArtifact,
+    RiskLevel,
+};
+
+const FINDING: FindingTemplate = FindingTemplate {
+    artifact_type: ArtifactType::Structure,
+    location_prefix: "syntax:",
+    remediation: "Rewrite the file to drop comments, padding and trailing data",
+};
+
+/// Thresholds for syntax-layer anomalies
+#[derive(Debug, Clone)]
+pub struct SyntaxStegoConfig {
+    /// Comment bytes per file byte above which density is reported
+    pub max_comment_density: f64,
+    /// Comments at least this long are checked for encoded content
+    pub min_suspicious_comment: usize,
+    /// Shannon entropy (bits per byte) above which a comment looks encoded
+    pub comment_entropy_threshold: f64,
+    /// Whitespace between two objects above which padding is reported
+    pub max_object_gap: usize,
+    /// Mixed space/tab runs at least this long are reported
+    pub min_mixed_whitespace_run: usize,
+    /// Individual findings reported before the rest are only counted
+    pub max_findings: usize,
+}
+
+impl Default for SyntaxStegoConfig {
+    fn default() -> Self {
+        Self {
+            max_comment_density: 0.01,
+            min_suspicious_comment: 32,
+            comment_entropy_threshold: 4.5,
+            max_object_gap: 64,
+            min_mixed_whitespace_run: 8,
+            max_findings: 50,
+        }
+    }
+}
+
+/// One `%` comment outside strings and streams
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    pub offset: usize,
+    /// Comment text without the leading `%`
+    pub text: Vec<u8>,
+}
+
+/// Lexical layout of a file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyntaxLayout {
+    pub comments: Vec<Comment>,
+    /// `(offset, length)` of the whitespace following each `endobj`
+    pub object_gaps: Vec<(usize, usize)>,
+    /// `(offset, length)` of whitespace runs mixing spaces and tabs
+    pub mixed_whitespace: Vec<(usize, usize)>,
+    /// Offsets where something other than an object, xref or trailer follows `endobj`
+    pub stray_data: Vec<usize>,
+    /// Bytes after the last `%%EOF` that are not whitespace
+    pub trailing_bytes: usize,
+    /// `(offset, raw name)` of names escaping a letter or digit as `#xx`
+    pub obfuscated_names: Vec<(usize, Vec<u8>)>,
+    /// `(offset, raw body)` of literal strings escaping a letter or digit in octal
+    pub obfuscated_strings: Vec<(usize, Vec<u8>)>,
+}
+
+impl SyntaxLayout {
+    /// Comments other than the header, binary marker and `%%EOF` lines
+    pub fn content_comments(&self) -> impl Iterator<Item = &Comment> {
+        self.comments.iter().filter(|c| !is_structural_comment(c))
+    }
+
+    /// Comment bytes, excluding the header, binary marker and `%%EOF` lines
+    pub fn comment_bytes(&self) -> usize {
+        self.content_comments().map(|c| c.text.len() + 1).sum()
+    }
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    is_whitespace(byte) || b"()<>[]{}/%".contains(&byte)
+}
+
+/// Header, binary marker and end-of-file comments every writer emits
+fn is_structural_comment(comment: &Comment) -> bool {
+    comment.text.starts_with(b"PDF-")
+        || comment.text.starts_with(b"%EOF")
+        || (comment.offset < 1024 && comment.text.len() <= 8 && comment.text.iter().all(|&b| b >= 0x80))
+}
+
+/// Whether a keyword starts at `at` and stands alone
+fn keyword_at(data: &[u8], at: usize, keyword: &[u8]) -> bool {
+    data[at..].starts_with(keyword)
+        && (at == 0 || is_delimiter(data[at - 1]))
+        && data.get(at + keyword.len()).map_or(true, |&b| is_delimiter(b))
+}
+
+/// Whether an object header `N G obj` starts at `at`
+fn object_header_at(data: &[u8], at: usize) -> bool {
+    let mut i = at;
+    for _ in 0..2 {
+        let start = i;
+        while data.get(i).is_some_and(u8::is_ascii_digit) {
+            i += 1;
+        }
+        if i == start {
+            return false;
+        }
+        while data.get(i).is_some_and(|&b| is_whitespace(b)) {
+            i += 1;
+        }
+    }
+    keyword_at(data, i, b"obj")
+}
+
+fn find(data: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    data.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|p| p + from)
+}
+
+/// Records the comment starting at `start` and returns the offset of its end of line
+fn read_comment(data: &[u8], start: usize, comments: &mut Vec<Comment>) -> usize {
+    let end = data[start..].iter().position(|&b| matches!(b, b'\r' | b'\n')).map_or(data.len(), |p| p + start);
+    comments.push(Comment { offset: start, text: data[start + 1..end].to_vec() });
+    end
+}
+
+/// Lexes the file, skipping strings and stream data
+pub fn layout(data: &[u8]) -> SyntaxLayout {
+    let mut layout = SyntaxLayout::default();
+    let mut i = 0;
+
+    while i < data.len() {
+        match data[i] {
+            b'%' => i = read_comment(data, i, &mut layout.comments),
+            b'/' => {
+                let start = i + 1;
+                i = start;
+                while i < data.len() && !is_delimiter(data[i]) {
+                    i += 1;
+                }
+                if canonical::is_obfuscated_name(&data[start..i]) {
+                    layout.obfuscated_names.push((start - 1, data[start..i].to_vec()));
+                }
+            }
+            b'(' => {
+                let start = i;
+                let mut depth = 0usize;
+                while i < data.len() {
+                    match data[i] {
+                        b'\\' => i += 1,
+                        b'(' => depth += 1,
+                        b')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                let body = &data[start + 1..i.min(data.len())];
+                if canonical::is_obfuscated_string(body) {
+                    layout.obfuscated_strings.push((start, body.to_vec()));
+                }
+                i += 1;
+            }
+            b'<' if data.get(i + 1) == Some(&b'<') => i += 2,
+            b'<' => i = find(data, i, b">").map_or(data.len(), |end| end + 1),
+            b' ' | b'\t' => {
+                let start = i;
+                while i < data.len() && matches!(data[i], b' ' | b'\t') {
+                    i += 1;
+                }
+                let run = &data[start..i];
+                if run.contains(&b' ') && run.contains(&b'\t') {
+                    layout.mixed_whitespace.push((start, run.len()));
+                }
+            }
+            b's' if keyword_at(data, i, b"stream") && (i == 0 || data[i - 1] != b'/') => {
+                i = find(data, i + 6, b"endstream").map_or(data.len(), |end| end + 9);
+            }
+            b'e' if keyword_at(data, i, b"endobj") => {
+                i += 6;
+                // Comments between objects are recorded but do not interrupt the gap
+                let (start, mut padding) = (i, 0);
+                loop {
+                    while i < data.len() && is_whitespace(data[i]) {
+                        i += 1;
+                        padding += 1;
+                    }
+                    if data.get(i) != Some(&b'%') {
+                        break;
+                    }
+                    i = read_comment(data, i, &mut layout.comments);
+                }
+                layout.object_gaps.push((start, padding));
+                let follows_cleanly = i == data.len()
+                    || object_header_at(data, i)
+                    || [&b"xref"[..], b"trailer", b"startxref"].iter().any(|k| keyword_at(data, i, k));
+                if !follows_cleanly {
+                    layout.stray_data.push(i);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    if let Some(eof) = data.windows(5).rposition(|w| w == b"%%EOF") {
+        layout.trailing_bytes = data[eof + 5..].iter().filter(|&&b| !is_whitespace(b)).count();
+    }
+    layout
+}
+
+/// Whether comment text looks like base64 or hex rather than prose
+fn looks_encoded(text: &[u8]) -> bool {
+    let trimmed: Vec<u8> = text.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    !trimmed.is_empty()
+        && !text.contains(&b' ')
+        && (trimmed.iter().all(u8::is_ascii_hexdigit)
+            || trimmed.iter().all(|b| b.is_ascii_alphanumeric() || b"+/=-_".contains(b)))
+}
+
+/// Reports syntax-layer hiding places in raw PDF bytes
+#[derive(Debug, Clone, Default)]
+pub struct SyntaxScanner {
+    config: SyntaxStegoConfig,
+}
+
+impl SyntaxScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: SyntaxStegoConfig) -> Self {
+        Self { config }
+    }
+
+    #[instrument(skip(self, data), fields(len = data.len()))]
+    pub fn scan_bytes(&self, data: &[u8]) -> Vec<ForensicArtifact> {
+        let layout = layout(data);
+        let mut artifacts = Vec::new();
+
+        let comment_bytes = layout.comment_bytes();
+        let density = comment_bytes as f64 / data.len().max(1) as f64;
+        if density > self.config.max_comment_density {
+            artifacts.push(FINDING.artifact(
+                "comments",
+                format!("Comments make up {:.1}% of the file ({} bytes)", density * 100.0, comment_bytes),
+                RiskLevel::Medium,
+                [("comment_bytes", comment_bytes.to_string())],
+            ));
+        }
+
+        let suspicious: Vec<&Comment> = layout.content_comments()
+            .filter(|c| c.text.len() >= self.config.min_suspicious_comment)
+            .filter(|c| entropy(&c.text) >= self.config.comment_entropy_threshold || looks_encoded(&c.text))
+            .collect();
+        for comment in suspicious.iter().take(self.config.max_findings) {
+            artifacts.push(FINDING.artifact(
+                &format!("offset:{}", comment.offset),
+                format!("Comment of {} bytes looks like encoded data", comment.text.len()),
+                RiskLevel::High,
+                [("entropy", format!("{:.2}", entropy(&comment.text)))],
+            ));
+        }
+
+        let padded: Vec<&(usize, usize)> = layout.object_gaps.iter()
+            .filter(|(_, len)| *len > self.config.max_object_gap)
+            .collect();
+        if !padded.is_empty() {
+            let bytes: usize = padded.iter().map(|(_, len)| len).sum();
+            artifacts.push(FINDING.artifact(
+                &format!("offset:{}", padded[0].0),
+                format!("{} inter-object gap(s) padded with {} whitespace bytes", padded.len(), bytes),
+                RiskLevel::Medium,
+                [("padding_bytes", bytes.to_string())],
+            ));
+        }
+
+        let mixed: Vec<&(usize, usize)> = layout.mixed_whitespace.iter()
+            .filter(|(_, len)| *len >= self.config.min_mixed_whitespace_run)
+            .collect();
+        if !mixed.is_empty() {
+            artifacts.push(FINDING.artifact(
+                &format!("offset:{}", mixed[0].0),
+                format!("{} run(s) of mixed spaces and tabs, a common whitespace encoding", mixed.len()),
+                RiskLevel::High,
+                [("runs", mixed.len().to_string())],
+            ));
+        }
+
+        if let Some(&offset) = layout.stray_data.first() {
+            artifacts.push(FINDING.artifact(
+                &format!("offset:{}", offset),
+                format!("{} place(s) with unparsed data between objects", layout.stray_data.len()),
+                RiskLevel::High,
+                [("occurrences", layout.stray_data.len().to_string())],
+            ));
+        }
+
+        self.scan_obfuscation(&layout, &mut artifacts);
+
+        if layout.trailing_bytes > 0 {
+            artifacts.push(FINDING.artifact(
+                "eof",
+                format!("{} bytes of data after the final %%EOF", layout.trailing_bytes),
+                RiskLevel::High,
+                [("trailing_bytes", layout.trailing_bytes.to_string())],
+            ));
+        }
+
+        artifacts
+    }
+
+    /// Reports escaped names and strings, singling out those that decode
+    /// to something the pattern database looks for
+    fn scan_obfuscation(&self, layout: &SyntaxLayout, artifacts: &mut Vec<ForensicArtifact>) {
+        let patterns = patterns::active();
+        let mut hiding = 0;
+        for (offset, raw) in &layout.obfuscated_names {
+            let decoded = canonical::decode_name(raw);
+            let Some(rule) = patterns.name(&decoded) else { continue };
+            hiding += 1;
+            if hiding <= self.config.max_findings {
+                artifacts.push(FINDING.artifact(
+                    &format!("offset:{}", offset),
+                    format!("Name /{} is /{} written with #xx escapes", String::from_utf8_lossy(raw), String::from_utf8_lossy(&decoded)),
+                    RiskLevel::High,
+                    [("pattern_id", rule.id.clone())],
+                ));
+            }
+        }
+        for (offset, raw) in &layout.obfuscated_strings {
+            let decoded = canonical::decode_literal_string(raw);
+            let regex_match = tracing::trace_span!("regex_match", bytes = decoded.len()).entered();
+            let matched = patterns.idioms().find(|(_, idiom)| idiom.is_match(&decoded) && !idiom.is_match(raw));
+            drop(regex_match);
+            let Some((rule, _)) = matched else {
+                continue;
+            };
+            hiding += 1;
+            if hiding <= self.config.max_findings {
+                artifacts.push(FINDING.artifact(
+                    &format!("offset:{}", offset),
+                    format!("String written with octal escapes matches \"{}\"", rule.description),
+                    RiskLevel::High,
+                    [("pattern_id", rule.id.clone())],
+                ));
+            }
+        }
+
+        let escaped = layout.obfuscated_names.len() + layout.obfuscated_strings.len();
+        if escaped > hiding {
+            let first = layout.obfuscated_names.iter().chain(&layout.obfuscated_strings).map(|(offset, _)| *offset).min();
+            artifacts.push(FINDING.artifact(
+                &format!("offset:{}", first.unwrap_or_default()),
+                format!("{} name(s) or string(s) needlessly escape letters or digits", escaped - hiding),
+                RiskLevel::Medium,
+                [("occurrences", (escaped - hiding).to_string())],
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const CLEAN: &[u8] = b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n1 0 obj\n<< /Type /Catalog /Title (100% (nested) done) >>\nendobj\n\
+2 0 obj\n<< /Length 8 >>\nstream\n% not a comment\nendstream\nendobj\nxref\n0 1\ntrailer\n<< /Root 1 0 R >>\nstartxref\n0\n%%EOF\n";
+
+    /// A small file with data hidden in every syntax-layer hiding place
+    pub(crate) fn stego_file() -> Vec<u8> {
+        let mut data = b"%PDF-1.7\n1 0 obj\n<< /Type /Catalog >>\nendobj\n".to_vec();
+        data.extend_from_slice(b"%U2VjcmV0IHBheWxvYWQgaGlkZGVuIGluIGEgY29tbWVudA==\n");
+        data.extend_from_slice(&[b' '; 200]);
+        data.extend_from_slice(b"\n2 0 obj\n<< /A 1 >> \t \t\t \t  \nendobj\nhidden\n3 0 obj\n1\nendobj\n");
+        data.extend_from_slice(b"trailer\n<< /Root 1 0 R >>\n%%EOF\nappended secret");
+        data
+    }
+
+    #[test]
+    fn test_clean_file_has_no_findings() {
+        let layout = layout(CLEAN);
+        // The comment-like line inside the stream and the % in the string are skipped
+        assert_eq!(layout.comments.len(), 3);
+        assert!(layout.comments.iter().all(is_structural_comment));
+        assert!(SyntaxScanner::new().scan_bytes(CLEAN).is_empty());
+    }
+
+    #[test]
+    fn test_hiding_places_reported() {
+        let data = stego_file();
+        let layout = layout(&data);
+        assert_eq!(layout.trailing_bytes, "appendedsecret".len());
+        assert_eq!(layout.stray_data.len(), 1);
+        assert_eq!(layout.mixed_whitespace.len(), 1);
+
+        let descriptions: Vec<String> = SyntaxScanner::new().scan_bytes(&data).into_iter().map(|a| a.description).collect();
+        assert!(descriptions.iter().any(|d| d.contains("looks like encoded data")));
+        assert!(descriptions.iter().any(|d| d.contains("inter-object gap")));
+        assert!(descriptions.iter().any(|d| d.contains("mixed spaces and tabs")));
+        assert!(descriptions.iter().any(|d| d.contains("after the final %%EOF")));
+    }
+
+    #[test]
+    fn test_escaped_keys_reported() {
+        let data = b"%PDF-1.7\n1 0 obj\n<< /S /J#61vaScript /JS (\\145val\\(1\\)) /T (\\101) /N#20 1 >>\nendobj\n";
+        let layout = layout(data);
+        assert_eq!(layout.obfuscated_names, vec![(23, b"J#61vaScript".to_vec())]);
+        assert_eq!(layout.obfuscated_strings.len(), 2);
+
+        let artifacts = SyntaxScanner::new().scan_bytes(data);
+        let descriptions: Vec<&str> = artifacts.iter().map(|a| a.description.as_str()).collect();
+        assert!(descriptions.contains(&"Name /J#61vaScript is /JavaScript written with #xx escapes"));
+        assert!(descriptions.iter().any(|d| d.starts_with("String written with octal escapes")));
+        assert!(descriptions.contains(&"1 name(s) or string(s) needlessly escape letters or digits"));
+    }
+}
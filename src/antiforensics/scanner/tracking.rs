@@ -0,0 +1,253 @@
+//! Tracking element detection
+//! Author: kartik4091
+//! Created: 2025-06-04 23:21:12 UTC
+//! Finds references that make a viewer reach outside the document:
+//! external streams (`/F` on a stream dictionary, whose data the viewer
+//! loads from a file or URL), form submit and import-data targets, and
+//! remote go-to actions. A document referencing a unique URL this way
+//! reports when and where it is opened, like a web bug in an e-mail.
+
+use std::collections::HashMap;
+use lopdf::{Dictionary, Object, ObjectId};
+use tracing::{debug, instrument};
+
+use crate::antiforensics::{types::Document, ArtifactType, ForensicArtifact, RiskLevel};
+
+/// How a document reaches outside itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrackingKind {
+    /// Stream whose data lives in an external file or URL
+    ExternalStream,
+    /// `SubmitForm` action target
+    SubmitForm,
+    /// `ImportData` action, loading FDF data
+    ImportData,
+    /// `GoToR` action into another document
+    RemoteGoTo,
+}
+
+impl TrackingKind {
+    fn from_action(action: &[u8]) -> Option<Self> {
+        match action {
+            b"SubmitForm" => Some(Self::SubmitForm),
+            b"ImportData" => Some(Self::ImportData),
+            b"GoToR" => Some(Self::RemoteGoTo),
+            _ => None,
+        }
+    }
+}
+
+/// One reference to something outside the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackingElement {
+    /// Stream or action object; for inline actions, the object holding them
+    pub object: ObjectId,
+    pub kind: TrackingKind,
+    /// File name or URL referenced
+    pub target: String,
+    /// Whether the target is a network URL rather than a file name
+    pub remote: bool,
+}
+
+/// URL schemes that reach the network
+const REMOTE_SCHEMES: [&str; 4] = ["http://", "https://", "ftp://", "//"];
+
+/// Whether `target` names a network location
+pub fn is_remote(target: &str) -> bool {
+    let target = target.trim_start();
+    REMOTE_SCHEMES.iter()
+        .any(|scheme| target.get(..scheme.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme)))
+        || target.starts_with(r"\\")
+}
+
+/// Finds every tracking element in a document
+pub fn find_tracking(doc: &lopdf::Document) -> Vec<TrackingElement> {
+    let mut elements = Vec::new();
+    for (&id, object) in &doc.objects {
+        match object {
+            Object::Stream(stream) => {
+                if let Some(target) = stream.dict.get(b"F").ok().and_then(|spec| file_spec_target(doc, spec)) {
+                    elements.push(element(id, TrackingKind::ExternalStream, target));
+                }
+            }
+            Object::Dictionary(dict) => {
+                for (kind, target) in action_targets(doc, dict) {
+                    elements.push(element(id, kind, target));
+                }
+            }
+            _ => {}
+        }
+    }
+    elements.sort_by_key(|element| element.object);
+    elements
+}
+
+fn element(object: ObjectId, kind: TrackingKind, target: String) -> TrackingElement {
+    TrackingElement { object, kind, remote: is_remote(&target), target }
+}
+
+/// Tracking actions of `dict` itself, or inline in its `/A`, `/OpenAction` and `/AA` entries
+fn action_targets(doc: &lopdf::Document, dict: &Dictionary) -> Vec<(TrackingKind, String)> {
+    let mut found = Vec::new();
+    if let Some(action) = tracking_action(doc, dict) {
+        found.push(action);
+    }
+    let mut inline: Vec<&Dictionary> = [&b"A"[..], b"OpenAction"].iter()
+        .filter_map(|key| dict.get(key).ok()?.as_dict().ok())
+        .collect();
+    if let Ok(Object::Dictionary(triggers)) = dict.get(b"AA") {
+        inline.extend(triggers.iter().filter_map(|(_, action)| action.as_dict().ok()));
+    }
+    found.extend(inline.into_iter().filter_map(|action| tracking_action(doc, action)));
+    found
+}
+
+/// Kind and target of `dict` when it is a tracking action
+pub(crate) fn tracking_action(doc: &lopdf::Document, dict: &Dictionary) -> Option<(TrackingKind, String)> {
+    let kind = TrackingKind::from_action(dict.get(b"S").and_then(Object::as_name).ok()?)?;
+    let target = file_spec_target(doc, dict.get(b"F").ok()?)?;
+    Some((kind, target))
+}
+
+/// File name or URL a file specification names, if it is not embedded
+fn file_spec_target(doc: &lopdf::Document, spec: &Object) -> Option<String> {
+    let spec = match spec {
+        Object::Reference(id) => doc.get_object(*id).ok()?,
+        other => other,
+    };
+    match spec {
+        Object::String(name, _) => Some(String::from_utf8_lossy(name).into_owned()),
+        Object::Dictionary(spec) => {
+            // Embedded files travel with the document
+            if spec.has(b"EF") {
+                return None;
+            }
+            let name = spec.get(b"UF").or_else(|_| spec.get(b"F")).ok()?;
+            match name {
+                Object::String(name, _) => Some(String::from_utf8_lossy(name).into_owned()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Reports references that make viewers load or send data elsewhere
+#[derive(Debug, Clone, Default)]
+pub struct TrackingScanner;
+
+impl TrackingScanner {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        let elements = find_tracking(&doc.structure);
+        debug!("Found {} tracking element(s)", elements.len());
+        elements.iter().map(|element| self.artifact(element)).collect()
+    }
+
+    fn artifact(&self, element: &TrackingElement) -> ForensicArtifact {
+        let mut metadata = HashMap::new();
+        metadata.insert("kind".into(), format!("{:?}", element.kind));
+        metadata.insert("target".into(), element.target.clone());
+        metadata.insert("remote".into(), element.remote.to_string());
+
+        let what = match element.kind {
+            TrackingKind::ExternalStream => "Stream loaded from",
+            TrackingKind::SubmitForm => "Form submitted to",
+            TrackingKind::ImportData => "Form data imported from",
+            TrackingKind::RemoteGoTo => "Link into remote document",
+        };
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type: ArtifactType::Structure,
+            location: format!("{} {} R", element.object.0, element.object.1),
+            description: format!("{} {}", what, element.target),
+            // A network target tells its owner the document was opened
+            risk_level: if element.remote { RiskLevel::High } else { RiskLevel::Medium },
+            remediation: match element.kind {
+                TrackingKind::ExternalStream => "Embed the referenced data or remove the reference".into(),
+                _ => "Remove the action".into(),
+            },
+            metadata,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream, StringFormat};
+    use std::path::PathBuf;
+
+    fn string(s: &str) -> Object {
+        Object::String(s.as_bytes().to_vec(), StringFormat::Literal)
+    }
+
+    /// Page with an external image, a submit button and a remote link
+    pub(crate) fn tracking_document() -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let image = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "F" => dictionary! { "FS" => "URL", "F" => string("https://tracker.example/p.gif?id=42") },
+            },
+            Vec::new(),
+        ));
+        let submit = doc.add_object(dictionary! {
+            "S" => "SubmitForm",
+            "F" => dictionary! { "FS" => "URL", "F" => string("https://collect.example/form") },
+        });
+        let widget = doc.add_object(dictionary! { "Type" => "Annot", "Subtype" => "Widget", "A" => submit });
+        let link = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "A" => dictionary! { "S" => "GoToR", "F" => string("other.pdf"), "D" => vec![0.into()] },
+        });
+        let content = doc.add_object(Stream::new(dictionary! {}, b"q /Im0 Do Q".to_vec()));
+
+        let pages = doc.new_object_id();
+        let page = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages,
+            "Contents" => content,
+            "Resources" => dictionary! { "XObject" => dictionary! { "Im0" => image } },
+            "Annots" => vec![widget.into(), link.into()],
+        });
+        doc.objects.insert(pages, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page.into()],
+            "Count" => 1,
+        }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+        doc.trailer.set("Root", catalog);
+        doc
+    }
+
+    #[test]
+    fn test_tracking_elements_found() {
+        let elements = find_tracking(&tracking_document());
+        let kinds: Vec<_> = elements.iter().map(|e| (e.kind, e.remote)).collect();
+        assert_eq!(kinds, vec![
+            (TrackingKind::ExternalStream, true),
+            (TrackingKind::SubmitForm, true),
+            (TrackingKind::RemoteGoTo, false),
+        ]);
+        assert_eq!(elements[0].target, "https://tracker.example/p.gif?id=42");
+    }
+
+    #[test]
+    fn test_remote_targets_are_high_risk() {
+        let doc = Document::from_pdf(PathBuf::from("tracking.pdf"), tracking_document());
+        let artifacts = TrackingScanner::new().scan(&doc);
+        assert_eq!(artifacts.len(), 3);
+        assert_eq!(artifacts.iter().filter(|a| a.risk_level == RiskLevel::High).count(), 2);
+        assert!(is_remote("HTTPS://x") && is_remote(r"\\share\file") && !is_remote("file.pdf"));
+    }
+}
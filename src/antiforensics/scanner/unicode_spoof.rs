@@ -0,0 +1,415 @@
+//! Unicode and bidi spoofing detection
+//! Author: kartik4091
+//! Created: 2025-06-04 13:22:09 UTC
+//! Flags zero-width characters, bidirectional override codepoints and
+//! homoglyph-heavy strings in text streams and metadata values. These
+//! are used to hide content from human reviewers while keeping it
+//! machine-readable.
+
+use std::collections::HashMap;
+use lopdf::{content::Content, Object};
+use tracing::{debug, instrument, warn};
+
+use crate::antiforensics::{types::Document, ArtifactType, ForensicArtifact, RiskLevel};
+
+/// Text-showing operators whose operands carry rendered text
+const TEXT_OPERATORS: [&str; 4] = ["Tj", "TJ", "'", "\""];
+
+/// Kind of spoofing technique detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpoofKind {
+    /// Invisible zero-width or formatting character
+    ZeroWidth,
+    /// Bidi embedding/override/isolate control
+    BidiOverride,
+    /// Implicit directional mark (LRM/RLM/ALM)
+    BidiMark,
+    /// Word mixing Latin letters with look-alike Cyrillic/Greek letters
+    MixedScript,
+}
+
+/// Single spoofing finding with exact position
+#[derive(Debug, Clone)]
+pub struct SpoofFinding {
+    /// Technique detected
+    pub kind: SpoofKind,
+
+    /// Character offset within the decoded text
+    pub char_offset: usize,
+
+    /// Byte offset within the UTF-8 encoded decoded text
+    pub byte_offset: usize,
+
+    /// Offending codepoint(s), e.g. "U+202E"
+    pub codepoints: String,
+
+    /// Risk level assigned to the finding
+    pub risk_level: RiskLevel,
+
+    /// Surrounding text with controls made visible
+    pub excerpt: String,
+}
+
+/// Detection configuration
+#[derive(Debug, Clone)]
+pub struct DetectionConfig {
+    /// Report directional marks (LRM/RLM), which are legitimate in RTL text
+    pub report_bidi_marks: bool,
+
+    /// Minimum number of mixed-script words before the string is flagged High
+    pub homoglyph_word_threshold: usize,
+
+    /// Characters of context captured on each side of a finding
+    pub excerpt_radius: usize,
+}
+
+/// Detection statistics
+#[derive(Debug, Default, Clone)]
+pub struct DetectionStats {
+    /// Number of text values inspected
+    pub values_scanned: usize,
+
+    /// Number of findings by kind
+    pub findings: HashMap<String, usize>,
+
+    /// Content streams that failed to decode
+    pub undecodable_streams: usize,
+}
+
+/// Detects Unicode-based content hiding
+#[derive(Debug)]
+pub struct UnicodeSpoofDetector {
+    /// Detection configuration
+    config: DetectionConfig,
+
+    /// Detection statistics
+    stats: DetectionStats,
+}
+
+impl UnicodeSpoofDetector {
+    /// Creates a new detector
+    pub fn new(config: DetectionConfig) -> Self {
+        Self {
+            config,
+            stats: DetectionStats::default(),
+        }
+    }
+
+    /// Returns detection statistics
+    pub fn stats(&self) -> &DetectionStats {
+        &self.stats
+    }
+
+    /// Scans Info dictionary values and page content streams of a document
+    #[instrument(skip(self, doc))]
+    pub fn scan_document(&mut self, doc: &Document) -> Vec<ForensicArtifact> {
+        let mut artifacts = Vec::new();
+
+        if let (Some(info_id), Some(info)) = (doc.info_id(), doc.info()) {
+            for (key, value) in info.iter() {
+                if let Object::String(bytes, _) = value {
+                    let text = decode_pdf_string(bytes);
+                    let location = format!("{} {} R:/Info/{}", info_id.0, info_id.1, String::from_utf8_lossy(key));
+                    for finding in self.scan_text(&text) {
+                        artifacts.push(Self::to_artifact(&finding, &location, ArtifactType::Metadata));
+                    }
+                }
+            }
+        }
+
+        for (page_number, page_id) in doc.pages() {
+            let data = match doc.page_content(page_id) {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Failed to read content of page {}: {}", page_number, e);
+                    self.stats.undecodable_streams += 1;
+                    continue;
+                }
+            };
+
+            let content = match Content::decode(&data) {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Failed to decode content of page {}: {}", page_number, e);
+                    self.stats.undecodable_streams += 1;
+                    continue;
+                }
+            };
+
+            for (op_index, operation) in content.operations.iter().enumerate() {
+                if !TEXT_OPERATORS.contains(&operation.operator.as_str()) {
+                    continue;
+                }
+
+                let text = Self::operand_text(&operation.operands);
+                let location = format!(
+                    "{} {} R:page {} op {} {}",
+                    page_id.0, page_id.1, page_number, op_index, operation.operator
+                );
+                for finding in self.scan_text(&text) {
+                    artifacts.push(Self::to_artifact(&finding, &location, ArtifactType::Content));
+                }
+            }
+        }
+
+        debug!("Unicode spoof scan produced {} artifacts", artifacts.len());
+        artifacts
+    }
+
+    /// Scans a decoded text value
+    pub fn scan_text(&mut self, text: &str) -> Vec<SpoofFinding> {
+        self.stats.values_scanned += 1;
+        let mut findings = Vec::new();
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+
+        for (char_offset, (byte_offset, c)) in chars.iter().enumerate() {
+            let classified = match classify_control(*c) {
+                Some(SpoofKind::BidiMark) if !self.config.report_bidi_marks => None,
+                // A leading BOM is an encoding marker, not hidden content
+                Some(SpoofKind::ZeroWidth) if *c == '\u{FEFF}' && char_offset == 0 => None,
+                other => other,
+            };
+
+            if let Some(kind) = classified {
+                findings.push(SpoofFinding {
+                    kind,
+                    char_offset,
+                    byte_offset: *byte_offset,
+                    codepoints: format!("U+{:04X}", *c as u32),
+                    risk_level: match kind {
+                        SpoofKind::BidiOverride => RiskLevel::High,
+                        _ => RiskLevel::Medium,
+                    },
+                    excerpt: self.excerpt(&chars, char_offset),
+                });
+            }
+        }
+
+        findings.extend(self.scan_homoglyphs(text, &chars));
+
+        for finding in &findings {
+            *self.stats.findings.entry(format!("{:?}", finding.kind)).or_insert(0) += 1;
+        }
+
+        findings
+    }
+
+    /// Detects words mixing Latin with confusable Cyrillic/Greek letters
+    fn scan_homoglyphs(&self, text: &str, chars: &[(usize, char)]) -> Vec<SpoofFinding> {
+        let mut words = Vec::new();
+        let mut start: Option<usize> = None;
+
+        for (index, (_, c)) in chars.iter().enumerate() {
+            match (c.is_alphabetic(), start) {
+                (true, None) => start = Some(index),
+                (false, Some(s)) => {
+                    words.push((s, index));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(s) = start {
+            words.push((s, chars.len()));
+        }
+
+        let mixed: Vec<(usize, usize)> = words.into_iter()
+            .filter(|(s, e)| {
+                let word = &chars[*s..*e];
+                let latin = word.iter().any(|(_, c)| c.is_ascii_alphabetic());
+                let confusable = word.iter().any(|(_, c)| is_confusable(*c));
+                latin && confusable
+            })
+            .collect();
+
+        let risk_level = if mixed.len() >= self.config.homoglyph_word_threshold {
+            RiskLevel::High
+        } else {
+            RiskLevel::Medium
+        };
+
+        mixed.into_iter().map(|(s, e)| {
+            let codepoints = chars[s..e].iter()
+                .filter(|(_, c)| is_confusable(*c))
+                .map(|(_, c)| format!("U+{:04X}", *c as u32))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let byte_end = chars.get(e).map(|(b, _)| *b).unwrap_or(text.len());
+
+            SpoofFinding {
+                kind: SpoofKind::MixedScript,
+                char_offset: s,
+                byte_offset: chars[s].0,
+                codepoints,
+                risk_level,
+                excerpt: text[chars[s].0..byte_end].to_string(),
+            }
+        }).collect()
+    }
+
+    /// Builds a context excerpt with control characters made visible
+    fn excerpt(&self, chars: &[(usize, char)], center: usize) -> String {
+        let start = center.saturating_sub(self.config.excerpt_radius);
+        let end = (center + self.config.excerpt_radius + 1).min(chars.len());
+        chars[start..end].iter()
+            .map(|(_, c)| match classify_control(*c) {
+                Some(_) => format!("<U+{:04X}>", *c as u32),
+                None => c.to_string(),
+            })
+            .collect()
+    }
+
+    /// Concatenates the text carried by an operator's operands
+    fn operand_text(operands: &[Object]) -> String {
+        let mut text = String::new();
+        for operand in operands {
+            match operand {
+                Object::String(bytes, _) => text.push_str(&decode_pdf_string(bytes)),
+                Object::Array(items) => text.push_str(&Self::operand_text(items)),
+                _ => {}
+            }
+        }
+        text
+    }
+
+    /// Converts a finding into a forensic artifact
+    fn to_artifact(finding: &SpoofFinding, location: &str, artifact_type: ArtifactType) -> ForensicArtifact {
+        use sha2::{Sha256, Digest};
+
+        let mut metadata = HashMap::new();
+        metadata.insert("spoof_kind".into(), format!("{:?}", finding.kind));
+        metadata.insert("char_offset".into(), finding.char_offset.to_string());
+        metadata.insert("byte_offset".into(), finding.byte_offset.to_string());
+        metadata.insert("codepoints".into(), finding.codepoints.clone());
+        metadata.insert("excerpt".into(), finding.excerpt.clone());
+
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type,
+            location: format!("{}@{}", location, finding.char_offset),
+            description: match finding.kind {
+                SpoofKind::ZeroWidth => format!("Zero-width character {} hides text", finding.codepoints),
+                SpoofKind::BidiOverride => format!("Bidi override {} reorders displayed text", finding.codepoints),
+                SpoofKind::BidiMark => format!("Directional mark {} in text", finding.codepoints),
+                SpoofKind::MixedScript => format!("Homoglyph word mixes Latin with {}", finding.codepoints),
+            },
+            risk_level: finding.risk_level,
+            remediation: "Normalize the text and review what a reader actually sees against the extracted text".into(),
+            metadata,
+            detection_timestamp: chrono::Utc::now(),
+            hash: format!("{:x}", Sha256::digest(finding.excerpt.as_bytes())),
+            // Marks and mixed scripts also occur in legitimate multilingual text
+            confidence: match finding.kind {
+                SpoofKind::BidiOverride => 0.9,
+                SpoofKind::ZeroWidth => 0.8,
+                SpoofKind::MixedScript if finding.risk_level == RiskLevel::High => 0.85,
+                SpoofKind::MixedScript => 0.6,
+                SpoofKind::BidiMark => 0.4,
+            },
+        }
+    }
+}
+
+/// Classifies invisible and directional control characters
+fn classify_control(c: char) -> Option<SpoofKind> {
+    match c {
+        '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{2061}'..='\u{2064}'
+        | '\u{FEFF}' | '\u{180E}' | '\u{00AD}' | '\u{034F}' => Some(SpoofKind::ZeroWidth),
+        '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' => Some(SpoofKind::BidiOverride),
+        '\u{200E}' | '\u{200F}' | '\u{061C}' => Some(SpoofKind::BidiMark),
+        _ => None,
+    }
+}
+
+/// Cyrillic and Greek letters visually identical to Latin letters
+fn is_confusable(c: char) -> bool {
+    matches!(c,
+        // Cyrillic: а в е к м н о р с т у х і ј ѕ ԁ and capitals
+        'а' | 'е' | 'о' | 'р' | 'с' | 'у' | 'х' | 'і' | 'ј' | 'ѕ' | 'ԁ' | 'һ' |
+        'А' | 'В' | 'Е' | 'К' | 'М' | 'Н' | 'О' | 'Р' | 'С' | 'Т' | 'Х' | 'І' | 'Ј' | 'Ѕ' |
+        // Greek: ο α ν ι and capitals
+        'ο' | 'α' | 'ν' | 'ι' |
+        'Α' | 'Β' | 'Ε' | 'Ζ' | 'Η' | 'Ι' | 'Κ' | 'Μ' | 'Ν' | 'Ο' | 'Ρ' | 'Τ' | 'Χ' | 'Υ'
+    )
+}
+
+/// Decodes a PDF text string: UTF-16BE or UTF-8 after a BOM, PDFDocEncoding otherwise
+pub(crate) fn decode_pdf_string(bytes: &[u8]) -> String {
+    crate::antiforensics::text_encoding::decode_text_string(bytes)
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            report_bidi_marks: false,
+            homoglyph_word_threshold: 3,
+            excerpt_radius: 12,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> UnicodeSpoofDetector {
+        UnicodeSpoofDetector::new(DetectionConfig::default())
+    }
+
+    #[test]
+    fn test_zero_width_offsets() {
+        let findings = detector().scan_text("pay\u{200B}ment");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, SpoofKind::ZeroWidth);
+        assert_eq!(findings[0].char_offset, 3);
+        assert_eq!(findings[0].byte_offset, 3);
+        assert_eq!(findings[0].codepoints, "U+200B");
+    }
+
+    #[test]
+    fn test_bidi_override_is_high() {
+        let findings = detector().scan_text("invoice\u{202E}fdp.exe");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, SpoofKind::BidiOverride);
+        assert_eq!(findings[0].risk_level, RiskLevel::High);
+        assert!(findings[0].excerpt.contains("<U+202E>"));
+    }
+
+    #[test]
+    fn test_leading_bom_ignored() {
+        assert!(detector().scan_text("\u{FEFF}Title").is_empty());
+    }
+
+    #[test]
+    fn test_bidi_marks_configurable() {
+        assert!(detector().scan_text("abc\u{200F}").is_empty());
+
+        let mut detector = UnicodeSpoofDetector::new(DetectionConfig {
+            report_bidi_marks: true,
+            ..Default::default()
+        });
+        assert_eq!(detector.scan_text("abc\u{200F}")[0].kind, SpoofKind::BidiMark);
+    }
+
+    #[test]
+    fn test_mixed_script_homoglyphs() {
+        // "pаypal" with Cyrillic а
+        let findings = detector().scan_text("login to p\u{0430}ypal now");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, SpoofKind::MixedScript);
+        assert_eq!(findings[0].char_offset, 9);
+        assert_eq!(findings[0].codepoints, "U+0430");
+        assert_eq!(findings[0].risk_level, RiskLevel::Medium);
+    }
+
+    #[test]
+    fn test_pure_cyrillic_not_flagged() {
+        assert!(detector().scan_text("привет мир").is_empty());
+    }
+
+    #[test]
+    fn test_utf16_metadata_decoding() {
+        let bytes = [0xFE, 0xFF, 0x00, 0x41, 0x20, 0x2E, 0x00, 0x42];
+        assert_eq!(decode_pdf_string(&bytes), "A\u{202E}B");
+    }
+}
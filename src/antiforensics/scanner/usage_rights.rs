@@ -0,0 +1,153 @@
+//! Usage rights (Reader extensions) detection
+//! Author: kartik4091
+//! Created: 2025-06-04 19:34:09 UTC
+//! Reader-extended documents carry a `/Perms /UR3` (or legacy `/UR`)
+//! signature granting extra rights in free viewers. Any modification
+//! invalidates it, and viewers then warn about a broken signature or
+//! disable features, so it is reported explicitly before cleaning.
+
+use std::collections::HashMap;
+use lopdf::{Dictionary, Object, ObjectId};
+use tracing::instrument;
+
+use crate::antiforensics::{types::Document, utils::resolve_dict, ArtifactType, ForensicArtifact, RiskLevel};
+
+/// Usage rights keys in the catalog's /Perms dictionary
+pub const USAGE_RIGHTS_KEYS: [&[u8]; 2] = [b"UR3", b"UR"];
+
+/// A usage rights signature and the rights it grants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageRights {
+    /// `UR3` or `UR`
+    pub key: String,
+    /// Signature dictionary, when indirect
+    pub signature: Option<ObjectId>,
+    /// Signature handler, e.g. `Adobe.PPKLite`
+    pub filter: Option<String>,
+    /// Granted rights as `Category/Right`, e.g. `Form/FillIn`
+    pub rights: Vec<String>,
+}
+
+/// Usage rights signatures present in the catalog
+pub fn detect(doc: &lopdf::Document) -> Vec<UsageRights> {
+    let Some(perms) = perms(doc) else { return Vec::new() };
+
+    USAGE_RIGHTS_KEYS.iter()
+        .filter_map(|&key| {
+            let value = perms.get(key).ok()?;
+            let signature = value.as_reference().ok();
+            let dict = resolve_dict(doc, value);
+            Some(UsageRights {
+                key: String::from_utf8_lossy(key).into_owned(),
+                signature,
+                filter: dict
+                    .and_then(|d| d.get(b"Filter").and_then(Object::as_name).ok())
+                    .map(|name| String::from_utf8_lossy(name).into_owned()),
+                rights: dict.map(|d| granted_rights(doc, d)).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+fn perms(doc: &lopdf::Document) -> Option<&Dictionary> {
+    let catalog = resolve_dict(doc, doc.trailer.get(b"Root").ok()?)?;
+    resolve_dict(doc, catalog.get(b"Perms").ok()?)
+}
+
+/// Rights listed in the UR transform parameters of the signature references
+fn granted_rights(doc: &lopdf::Document, signature: &Dictionary) -> Vec<String> {
+    let Ok(Object::Array(references)) = signature.get(b"Reference") else { return Vec::new() };
+
+    let mut rights = Vec::new();
+    for reference in references.iter().filter_map(|r| resolve_dict(doc, r)) {
+        let Some(params) = reference.get(b"TransformParams").ok().and_then(|p| resolve_dict(doc, p)) else { continue };
+        for category in [&b"Document"[..], b"Form", b"Annots", b"Signature", b"EF"] {
+            if let Ok(Object::Array(names)) = params.get(category) {
+                for name in names.iter().filter_map(|n| n.as_name().ok()) {
+                    rights.push(format!("{}/{}", String::from_utf8_lossy(category), String::from_utf8_lossy(name)));
+                }
+            }
+        }
+    }
+    rights
+}
+
+/// Reports usage rights signatures
+#[derive(Debug, Clone, Default)]
+pub struct UsageRightsScanner;
+
+impl UsageRightsScanner {
+    pub fn new() -> Self {
+        Self
+    }
+
+    #[instrument(skip(self, doc))]
+    pub fn scan(&self, doc: &Document) -> Vec<ForensicArtifact> {
+        detect(&doc.structure).into_iter()
+            .map(|rights| {
+                let mut metadata = HashMap::new();
+                metadata.insert("key".into(), rights.key.clone());
+                metadata.insert("rights".into(), rights.rights.join(","));
+                if let Some(filter) = &rights.filter {
+                    metadata.insert("filter".into(), filter.clone());
+                }
+
+                ForensicArtifact {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    artifact_type: ArtifactType::Signature,
+                    location: format!("/Root/Perms/{}", rights.key),
+                    description: format!(
+                        "Usage rights signature granting {} right(s); any modification invalidates it",
+                        rights.rights.len(),
+                    ),
+                    risk_level: RiskLevel::Medium,
+                    remediation: "Remove the usage rights signature so cleaned output does not show a broken signature".into(),
+                    metadata,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// Reader-extended document with form fill-in and annotation rights
+    pub(crate) fn reader_extended() -> lopdf::Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let signature = doc.add_object(dictionary! {
+            "Type" => "Sig",
+            "Filter" => "Adobe.PPKLite",
+            "Reference" => vec![Object::Dictionary(dictionary! {
+                "TransformMethod" => "UR3",
+                "TransformParams" => dictionary! {
+                    "Form" => vec![Object::Name(b"FillIn".to_vec())],
+                    "Annots" => vec![Object::Name(b"Create".to_vec()), Object::Name(b"Modify".to_vec())],
+                },
+            })],
+        });
+        let catalog = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Perms" => dictionary! { "UR3" => signature, "DocMDP" => Object::Null },
+        });
+        doc.trailer.set("Root", catalog);
+        doc
+    }
+
+    #[test]
+    fn test_usage_rights_detected() {
+        let rights = detect(&reader_extended());
+        assert_eq!(rights.len(), 1);
+        assert_eq!(rights[0].key, "UR3");
+        assert_eq!(rights[0].filter.as_deref(), Some("Adobe.PPKLite"));
+        assert_eq!(rights[0].rights, vec!["Form/FillIn", "Annots/Create", "Annots/Modify"]);
+    }
+
+    #[test]
+    fn test_plain_document_has_no_usage_rights() {
+        assert!(detect(&lopdf::Document::with_version("1.7")).is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! Text string and predefined CMap decoding
+//! Author: kartik4091
+//! Created: 2025-06-05 01:31:09 UTC
+//! PDF text strings are PDFDocEncoding, UTF-16BE or (since PDF 2.0) UTF-8
+//! after a byte order mark, and UTF-16 ones may embed language escapes.
+//! Reading them as Latin-1 turns curly quotes, dashes and the euro sign
+//! into control characters, and reading them as UTF-8 drops whole titles,
+//! so metadata matching and cleaning miss what they look for.
+//! [`decode_text_string`] decodes all of them.
+//!
+//! Page text in composite fonts is encoded by the font's CMap. Without a
+//! /ToUnicode map, the predefined CJK CMaps still say how to read it: the
+//! `Uni*` ones are Unicode encodings, and the legacy ones (`90ms-RKSJ-H`,
+//! `GBK-EUC-H`, `ETen-B5-H`, `KSCms-UHC-H`, ...) are national charsets.
+//! [`PredefinedCMap`] splits shown strings into codes and decodes them, so
+//! text search and redaction work on CJK documents.
+
+use std::ops::Range;
+use encoding_rs::{Encoding, BIG5, EUC_JP, EUC_KR, GB18030, GBK, SHIFT_JIS};
+
+/// PDFDocEncoding bytes 0x18-0x1F, which differ from Latin-1
+const DOC_LOW: [char; 8] = ['\u{02D8}', '\u{02C7}', '\u{02C6}', '\u{02D9}', '\u{02DD}', '\u{02DB}', '\u{02DA}', '\u{02DC}'];
+
+/// PDFDocEncoding bytes 0x80-0xA0, which differ from Latin-1
+const DOC_HIGH: [char; 33] = [
+    '\u{2022}', '\u{2020}', '\u{2021}', '\u{2026}', '\u{2014}', '\u{2013}', '\u{0192}', '\u{2044}',
+    '\u{2039}', '\u{203A}', '\u{2212}', '\u{2030}', '\u{201E}', '\u{201C}', '\u{201D}', '\u{2018}',
+    '\u{2019}', '\u{201A}', '\u{2122}', '\u{FB01}', '\u{FB02}', '\u{0141}', '\u{0152}', '\u{0160}',
+    '\u{0178}', '\u{017D}', '\u{0131}', '\u{0142}', '\u{0153}', '\u{0161}', '\u{017E}', '\u{FFFD}',
+    '\u{20AC}',
+];
+
+/// Character of one PDFDocEncoding byte; undefined bytes become U+FFFD
+pub fn pdf_doc_char(byte: u8) -> char {
+    match byte {
+        0x18..=0x1F => DOC_LOW[usize::from(byte - 0x18)],
+        0x80..=0xA0 => DOC_HIGH[usize::from(byte - 0x80)],
+        0x7F | 0xAD => char::REPLACEMENT_CHARACTER,
+        _ => char::from(byte),
+    }
+}
+
+/// Decodes a PDF text string
+///
+/// Strings without a byte order mark are PDFDocEncoding, unless they are
+/// valid UTF-8 with non-ASCII characters: producers write that often, and
+/// PDFDocEncoding text almost never happens to be valid UTF-8.
+pub fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        strip_language_escapes(String::from_utf16_lossy(&utf16_units(rest, u16::from_be_bytes)))
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        // Little-endian is not allowed, but some producers write it
+        strip_language_escapes(String::from_utf16_lossy(&utf16_units(rest, u16::from_le_bytes)))
+    } else if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        strip_language_escapes(String::from_utf8_lossy(rest).into_owned())
+    } else if let Some(text) = std::str::from_utf8(bytes).ok().filter(|_| !bytes.is_ascii()) {
+        text.to_string()
+    } else {
+        bytes.iter().map(|&byte| pdf_doc_char(byte)).collect()
+    }
+}
+
+fn utf16_units(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Vec<u16> {
+    bytes.chunks(2).map(|pair| unit([pair[0], *pair.get(1).unwrap_or(&0)])).collect()
+}
+
+/// Drops `ESC language ESC` sequences marking the language of Unicode text
+fn strip_language_escapes(text: String) -> String {
+    if !text.contains('\u{1B}') {
+        return text;
+    }
+    text.split('\u{1B}').step_by(2).collect()
+}
+
+/// Text encoding of a predefined CMap named by a composite font's /Encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PredefinedCMap {
+    /// `Uni*-UCS2-*`: two-byte UCS-2 codes
+    Ucs2,
+    /// `Uni*-UTF16-*`
+    Utf16,
+    /// `Uni*-UTF8-*`
+    Utf8,
+    /// `Uni*-UTF32-*`
+    Utf32,
+    /// A national multi-byte charset: Shift-JIS, EUC-JP, GBK, GB18030, Big5 or EUC-KR (UHC)
+    Charset(&'static Encoding),
+}
+
+impl PredefinedCMap {
+    /// CMap by name, e.g. `UniJIS-UCS2-H` or `90ms-RKSJ-V`; `None` for
+    /// `Identity-H` and CMaps without a charset to decode by
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        let name = std::str::from_utf8(name).ok()?;
+        let base = name.strip_suffix("-H").or_else(|| name.strip_suffix("-V"))?;
+        if base.starts_with("Uni") {
+            return if base.contains("-UCS2") {
+                Some(Self::Ucs2)
+            } else if base.ends_with("-UTF16") {
+                Some(Self::Utf16)
+            } else if base.ends_with("-UTF8") {
+                Some(Self::Utf8)
+            } else if base.ends_with("-UTF32") {
+                Some(Self::Utf32)
+            } else {
+                None
+            };
+        }
+        let encoding = if base.contains("RKSJ") {
+            SHIFT_JIS
+        } else if base == "EUC" || base == "78-EUC" {
+            EUC_JP
+        } else if base.starts_with("GBK2K") {
+            GB18030
+        } else if base.starts_with("GBK") || (base.starts_with("GB") && base.ends_with("-EUC")) {
+            GBK
+        } else if base.contains("B5") {
+            BIG5
+        } else if base.starts_with("KSC") && !base.contains("Johab") {
+            EUC_KR
+        } else {
+            return None;
+        };
+        Some(Self::Charset(encoding))
+    }
+
+    /// Byte ranges of the codes in a shown string
+    pub fn codes(self, bytes: &[u8]) -> Vec<Range<usize>> {
+        let mut codes = Vec::new();
+        let mut start = 0;
+        while start < bytes.len() {
+            let end = (start + self.code_len(&bytes[start..])).min(bytes.len());
+            codes.push(start..end);
+            start = end;
+        }
+        codes
+    }
+
+    /// Length of the code starting `bytes`, by its first bytes
+    fn code_len(self, bytes: &[u8]) -> usize {
+        let lead = bytes[0];
+        let multi = (0x81..=0xFE).contains(&lead);
+        match self {
+            Self::Ucs2 => 2,
+            Self::Utf16 if (0xD8..=0xDB).contains(&lead) => 4,
+            Self::Utf16 => 2,
+            Self::Utf8 => match lead {
+                0xF0.. => 4,
+                0xE0.. => 3,
+                0xC0.. => 2,
+                _ => 1,
+            },
+            Self::Utf32 => 4,
+            Self::Charset(encoding) if encoding == SHIFT_JIS => {
+                if matches!(lead, 0x81..=0x9F | 0xE0..=0xFC) { 2 } else { 1 }
+            }
+            Self::Charset(encoding) if encoding == EUC_JP => match lead {
+                0x8F => 3,
+                0x8E | 0xA1..=0xFE => 2,
+                _ => 1,
+            },
+            Self::Charset(encoding) if encoding == GB18030 && multi => {
+                if bytes.get(1).is_some_and(u8::is_ascii_digit) { 4 } else { 2 }
+            }
+            Self::Charset(_) if multi => 2,
+            Self::Charset(_) => 1,
+        }
+    }
+
+    /// Text of one code
+    pub fn decode(self, code: &[u8]) -> String {
+        match self {
+            Self::Ucs2 | Self::Utf16 => String::from_utf16_lossy(&utf16_units(code, u16::from_be_bytes)),
+            Self::Utf8 => String::from_utf8_lossy(code).into_owned(),
+            Self::Utf32 => code.chunks(4)
+                .map(|bytes| bytes.iter().fold(0u32, |value, &b| value << 8 | u32::from(b)))
+                .map(|value| char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+            Self::Charset(encoding) => encoding.decode_without_bom_handling(code).0.into_owned(),
+        }
+    }
+
+    /// Text of a whole shown string
+    pub fn decode_string(self, bytes: &[u8]) -> String {
+        self.codes(bytes).into_iter().map(|code| self.decode(&bytes[code])).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_strings_and_cjk_cmaps_decoded() {
+        // PDFDocEncoding quotes, dash and euro; Latin-1 above 0xA0
+        assert_eq!(decode_text_string(b"\x8DQ3\x8E \x84 \xA0 5 caf\xE9"), "\u{201C}Q3\u{201D} \u{2014} \u{20AC} 5 café");
+        assert_eq!(decode_text_string("東京".as_bytes()), "東京");
+        assert_eq!(decode_text_string(b"\xEF\xBB\xBFZ\xC3\xBCrich"), "Zürich");
+        // UTF-16BE with a language escape
+        let mut utf16 = vec![0xFE, 0xFF, 0x00, 0x1B];
+        utf16.extend("ja".encode_utf16().chain(['\u{1B}' as u16]).chain("日本".encode_utf16()).flat_map(u16::to_be_bytes));
+        assert_eq!(decode_text_string(&utf16), "日本");
+
+        let sjis = PredefinedCMap::from_name(b"90ms-RKSJ-H").unwrap();
+        assert_eq!(sjis, PredefinedCMap::Charset(SHIFT_JIS));
+        let shown = b"ID \x93\xFA\x96\x7B";
+        assert_eq!(sjis.codes(shown), vec![0..1, 1..2, 2..3, 3..5, 5..7]);
+        assert_eq!(sjis.decode_string(shown), "ID 日本");
+        assert_eq!(PredefinedCMap::from_name(b"GBK-EUC-H").unwrap().decode_string(b"\xD6\xD0\xCE\xC4"), "中文");
+        assert_eq!(PredefinedCMap::from_name(b"ETen-B5-V").unwrap().decode_string(b"\xA4\xA4\xA4\xE5"), "中文");
+        assert_eq!(PredefinedCMap::from_name(b"KSCms-UHC-H").unwrap().decode_string(b"\xC7\xD1\xB1\xDB"), "한글");
+        assert_eq!(PredefinedCMap::from_name(b"UniJIS-UCS2-H").unwrap().decode_string(b"\x65\xE5\x67\x2C"), "日本");
+        let utf16 = PredefinedCMap::from_name(b"UniJIS-UTF16-H").unwrap();
+        assert_eq!(utf16.codes(b"\xD8\x40\xDC\x0B\x00A"), vec![0..4, 4..6]);
+        assert_eq!(PredefinedCMap::from_name(b"Identity-H"), None);
+    }
+}
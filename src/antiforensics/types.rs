@@ -10,8 +10,14 @@ use std::{
 };
 
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use tokio::sync::RwLock;
 
+use crate::spill::{self, SpillBuffer, SpillConfig};
+
+/// PDF object model shared by every subsystem
+pub use lopdf::{Dictionary, Object, ObjectId, Stream};
+
 /// Processing stages in the antiforensics pipeline
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ProcessingStage {
@@ -25,7 +31,7 @@ pub enum ProcessingStage {
 }
 
 /// Risk levels for identified issues
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub enum RiskLevel {
     Critical,
     High,
@@ -57,6 +63,10 @@ pub struct PerformanceMetrics {
 }
 
 /// Document representation
+///
+/// `structure` holds the parsed object graph and is the single source of
+/// truth for PDF structure; prefer the typed accessors over reaching into
+/// the trailer directly.
 #[derive(Debug, Clone)]
 pub struct Document {
     pub id: String,
@@ -65,6 +75,7 @@ pub struct Document {
     pub metadata: DocumentMetadata,
     pub content: Arc<RwLock<DocumentContent>>,
     pub state: DocumentState,
+    pub structure: lopdf::Document,
 }
 
 /// Document metadata
@@ -163,6 +174,79 @@ pub enum ModificationType {
     Custom(u32),
 }
 
+/// Forensic artifact categories
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum ArtifactType {
+    Metadata,
+    Content,
+    Structure,
+    JavaScript,
+    Binary,
+    Signature,
+    EmbeddedFile,
+    Custom(String),
+}
+
+/// Artifact detected by a scanner or analyzer
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ForensicArtifact {
+    pub id: String,
+    pub artifact_type: ArtifactType,
+    pub location: String,
+    pub description: String,
+    pub risk_level: RiskLevel,
+    pub remediation: String,
+    pub metadata: HashMap<String, String>,
+    pub detection_timestamp: chrono::DateTime<chrono::Utc>,
+    pub hash: String,
+    /// Detector's confidence that the finding is real, in `0.0..=1.0`
+    #[serde(default = "full_confidence")]
+    pub confidence: f64,
+}
+
+fn full_confidence() -> f64 {
+    1.0
+}
+
+impl ForensicArtifact {
+    /// Combines independent signals into one confidence
+    ///
+    /// The finding is only a false positive if every signal is, so each
+    /// corroborating signal raises the result: `1 - Π(1 - s)`.
+    pub fn combine_confidence(signals: impl IntoIterator<Item = f64>) -> f64 {
+        1.0 - signals.into_iter()
+            .map(|s| 1.0 - s.clamp(0.0, 1.0))
+            .product::<f64>()
+    }
+
+    /// Report order: by location, then object ID, then pattern ID
+    ///
+    /// Remaining ties are broken by type and description. The random `id`
+    /// and the detection time are never compared, so the same findings sort
+    /// the same way however the scanners that produced them were scheduled.
+    pub fn stable_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.location.cmp(&other.location)
+            .then_with(|| self.object_id().cmp(&other.object_id()))
+            .then_with(|| self.metadata.get("pattern_id").cmp(&other.metadata.get("pattern_id")))
+            .then_with(|| self.artifact_type.cmp(&other.artifact_type))
+            .then_with(|| self.description.cmp(&other.description))
+    }
+
+    /// Object the artifact was found in, from its `object_id` metadata
+    fn object_id(&self) -> Option<lopdf::ObjectId> {
+        let mut parts = self.metadata.get("object_id")?.split_whitespace();
+        Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+    }
+}
+
+/// Puts artifacts in report order; call before emitting them
+///
+/// Scans that run concurrently finish in no particular order, which would
+/// otherwise make reports of the same document differ from run to run.
+pub fn sort_artifacts(artifacts: &mut [ForensicArtifact]) {
+    artifacts.sort_by(ForensicArtifact::stable_cmp);
+}
+
 /// Compression types
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CompressionType {
@@ -176,7 +260,8 @@ pub enum CompressionType {
 impl Document {
     /// Creates a new document
     pub fn new(path: PathBuf, size: u64) -> Self {
-        let id = format!("{:x}", md5::compute(path.to_string_lossy().as_bytes()));
+        // Lossy conversion would give distinct non-UTF-8 paths the same id
+        let id = format!("{:x}", md5::compute(path.as_os_str().as_encoded_bytes()));
         
         Self {
             id,
@@ -185,9 +270,213 @@ impl Document {
             metadata: DocumentMetadata::new(&path),
             content: Arc::new(RwLock::new(DocumentContent::new())),
             state: DocumentState::new(),
+            structure: lopdf::Document::with_version("1.7"),
+        }
+    }
+
+    /// Parses a document from memory
+    pub fn from_bytes(path: PathBuf, data: Vec<u8>) -> std::result::Result<Self, lopdf::Error> {
+        let structure = crate::antiforensics::analyzer::xref_integrity::load_checked(&data)?;
+        let mut document = Self::from_pdf(path, structure);
+        document.size = data.len() as u64;
+        document.content = Arc::new(RwLock::new(DocumentContent {
+            data,
+            ..DocumentContent::new()
+        }));
+        Ok(document)
+    }
+
+    /// Parses a document from borrowed bytes without keeping a copy of them
+    ///
+    /// Used for memory-mapped input, where the parser reads object data
+    /// straight from the mapping.
+    pub fn parse(path: PathBuf, data: &[u8]) -> std::result::Result<Self, lopdf::Error> {
+        let structure = crate::antiforensics::analyzer::xref_integrity::load_checked(data)?;
+        let mut document = Self::from_pdf(path, structure);
+        document.size = data.len() as u64;
+        Ok(document)
+    }
+
+    /// Reads and parses a document from disk
+    pub async fn load(path: PathBuf) -> std::result::Result<Self, lopdf::Error> {
+        let data = tokio::fs::read(&path).await?;
+        Self::from_bytes(path, data)
+    }
+
+    /// Wraps an already parsed document
+    pub fn from_pdf(path: PathBuf, structure: lopdf::Document) -> Self {
+        let mut document = Self::new(path, 0);
+        document.structure = structure;
+        document
+    }
+
+    /// Gets document identifier
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Gets an object by id
+    pub fn object(&self, id: ObjectId) -> Option<&Object> {
+        self.structure.objects.get(&id)
+    }
+
+    /// Gets a mutable object by id
+    pub fn object_mut(&mut self, id: ObjectId) -> Option<&mut Object> {
+        self.structure.objects.get_mut(&id)
+    }
+
+    /// Follows references until a direct object is reached
+    pub fn resolve<'a>(&'a self, object: &'a Object) -> Option<&'a Object> {
+        let mut current = object;
+        // Bounded to survive reference cycles
+        for _ in 0..32 {
+            match current {
+                Object::Reference(id) => current = self.object(*id)?,
+                direct => return Some(direct),
+            }
+        }
+        None
+    }
+
+    /// Gets a dictionary, looking through stream objects
+    pub fn dictionary(&self, id: ObjectId) -> Option<&Dictionary> {
+        match self.object(id)? {
+            Object::Dictionary(dict) => Some(dict),
+            Object::Stream(stream) => Some(&stream.dict),
+            _ => None,
         }
     }
 
+    /// Gets a mutable dictionary, looking through stream objects
+    pub fn dictionary_mut(&mut self, id: ObjectId) -> Option<&mut Dictionary> {
+        match self.object_mut(id)? {
+            Object::Dictionary(dict) => Some(dict),
+            Object::Stream(stream) => Some(&mut stream.dict),
+            _ => None,
+        }
+    }
+
+    /// Gets the catalog object id from the trailer
+    pub fn root_id(&self) -> Option<ObjectId> {
+        self.structure.trailer.get(b"Root").and_then(Object::as_reference).ok()
+    }
+
+    /// Points the trailer at a new catalog
+    pub fn set_root_id(&mut self, id: ObjectId) {
+        self.structure.trailer.set("Root", Object::Reference(id));
+    }
+
+    /// Gets the document catalog
+    pub fn catalog(&self) -> Option<&Dictionary> {
+        self.dictionary(self.root_id()?)
+    }
+
+    /// Gets the mutable document catalog
+    pub fn catalog_mut(&mut self) -> Option<&mut Dictionary> {
+        let id = self.root_id()?;
+        self.dictionary_mut(id)
+    }
+
+    /// Gets the Info dictionary object id from the trailer
+    pub fn info_id(&self) -> Option<ObjectId> {
+        self.structure.trailer.get(b"Info").and_then(Object::as_reference).ok()
+    }
+
+    /// Points the trailer at a new Info dictionary
+    pub fn set_info_id(&mut self, id: ObjectId) {
+        self.structure.trailer.set("Info", Object::Reference(id));
+    }
+
+    /// Detaches the Info dictionary from the trailer, returning its id
+    pub fn remove_info(&mut self) -> Option<ObjectId> {
+        let id = self.info_id();
+        self.structure.trailer.remove(b"Info");
+        id
+    }
+
+    /// Gets the document Info dictionary
+    pub fn info(&self) -> Option<&Dictionary> {
+        self.dictionary(self.info_id()?)
+    }
+
+    /// Gets the mutable document Info dictionary
+    pub fn info_mut(&mut self) -> Option<&mut Dictionary> {
+        let id = self.info_id()?;
+        self.dictionary_mut(id)
+    }
+
+    /// Gets the trailer /ID array
+    pub fn trailer_ids(&self) -> Option<&Vec<Object>> {
+        self.structure.trailer.get(b"ID").and_then(Object::as_array).ok()
+    }
+
+    /// Gets the mutable trailer /ID array
+    pub fn trailer_ids_mut(&mut self) -> Option<&mut Vec<Object>> {
+        self.structure.trailer.get_mut(b"ID").and_then(Object::as_array_mut).ok()
+    }
+
+    /// Replaces the trailer /ID array
+    pub fn set_trailer_ids(&mut self, ids: Vec<Object>) {
+        self.structure.trailer.set("ID", Object::Array(ids));
+    }
+
+    /// Gets the trailer /Encrypt entry, if the document is encrypted
+    pub fn encrypt(&self) -> Option<&Object> {
+        self.structure.trailer.get(b"Encrypt").ok()
+    }
+
+    /// Iterates over page object ids with their 1-based page numbers
+    ///
+    /// The page tree is walked as the iterator advances, so breaking out
+    /// early does not visit the remaining pages.
+    pub fn pages(&self) -> impl Iterator<Item = (u32, ObjectId)> + '_ {
+        (1..).zip(self.structure.page_iter())
+    }
+
+    /// Iterates over all indirect objects in object number order
+    pub fn objects(&self) -> impl Iterator<Item = (ObjectId, &Object)> + '_ {
+        self.structure.objects.iter().map(|(&id, object)| (id, object))
+    }
+
+    /// Gets the decoded content of a page
+    pub fn page_content(&self, page_id: ObjectId) -> std::result::Result<Vec<u8>, lopdf::Error> {
+        self.structure.get_page_content(page_id)
+    }
+
+    /// Decodes a page's content streams into a buffer that spills to disk when large
+    pub fn page_content_buffer(
+        &self,
+        page_id: ObjectId,
+        config: &SpillConfig,
+    ) -> std::io::Result<SpillBuffer> {
+        use std::io::Write;
+
+        let mut buffer = SpillBuffer::new(config);
+        for id in self.structure.get_page_contents(page_id) {
+            if let Some(stream) = self.object(id).and_then(|o| o.as_stream().ok()) {
+                spill::decode_stream_into(stream, &mut buffer)?;
+                buffer.write_all(b"\n")?;
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// Iterates over all stream objects
+    pub fn streams(&self) -> impl Iterator<Item = (ObjectId, &Stream)> + '_ {
+        self.objects().filter_map(|(id, object)| match object {
+            Object::Stream(stream) => Some((id, stream)),
+            _ => None,
+        })
+    }
+
+    /// Iterates mutably over all stream objects
+    pub fn streams_mut(&mut self) -> impl Iterator<Item = (ObjectId, &mut Stream)> + '_ {
+        self.structure.objects.iter_mut().filter_map(|(id, object)| match object {
+            Object::Stream(stream) => Some((*id, stream)),
+            _ => None,
+        })
+    }
+
     /// Gets document size
     pub async fn size(&self) -> crate::error::Result<usize> {
         let content = self.content.read().await;
@@ -205,6 +494,42 @@ impl Document {
     }
 }
 
+impl RiskLevel {
+    /// Numeric severity rank, `None` = 0 through `Critical` = 4
+    pub fn severity(&self) -> u8 {
+        match self {
+            RiskLevel::None => 0,
+            RiskLevel::Low => 1,
+            RiskLevel::Medium => 2,
+            RiskLevel::High => 3,
+            RiskLevel::Critical => 4,
+        }
+    }
+}
+
+impl Default for ForensicArtifact {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            artifact_type: ArtifactType::Custom(String::new()),
+            location: String::new(),
+            description: String::new(),
+            risk_level: RiskLevel::None,
+            remediation: String::new(),
+            metadata: HashMap::new(),
+            detection_timestamp: chrono::Utc::now(),
+            hash: String::new(),
+            confidence: 1.0,
+        }
+    }
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::new(PathBuf::from("<memory>"), 0)
+    }
+}
+
 impl DocumentMetadata {
     /// Creates new metadata for a document
     pub fn new(path: &PathBuf) -> Self {
@@ -285,6 +610,57 @@ mod tests {
     use super::*;
     use tempfile::NamedTempFile;
 
+    fn artifact(location: &str, object: Option<&str>, pattern: Option<&str>) -> ForensicArtifact {
+        let mut metadata = HashMap::new();
+        if let Some(object) = object {
+            metadata.insert("object_id".to_string(), object.to_string());
+        }
+        if let Some(pattern) = pattern {
+            metadata.insert("pattern_id".to_string(), pattern.to_string());
+        }
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type: ArtifactType::Metadata,
+            location: location.to_string(),
+            description: format!("{} {:?} {:?}", location, object, pattern),
+            metadata,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_concurrent_scans_sort_identically() {
+        let findings = || vec![
+            artifact("/Info/Author", None, Some("meta.author")),
+            artifact("/Info/Author", None, Some("meta.any")),
+            artifact("10 0 R/JS", Some("10 0"), Some("key.js")),
+            artifact("10 0 R/JS", Some("9 0"), Some("key.js")),
+            artifact("2 0 R/URI", Some("2 0"), None),
+            artifact("2 0 R/URI", Some("2 0"), Some("key.uri")),
+        ];
+        let report = || {
+            // Each scanner pushes its findings from its own thread, racing the others
+            let collected = std::sync::Mutex::new(Vec::new());
+            std::thread::scope(|scope| {
+                for finding in findings() {
+                    let collected = &collected;
+                    scope.spawn(move || collected.lock().unwrap().push(finding));
+                }
+            });
+            let mut artifacts = collected.into_inner().unwrap();
+            sort_artifacts(&mut artifacts);
+            artifacts.iter().map(|a| a.description.clone()).collect::<Vec<_>>()
+        };
+
+        let first = report();
+        for _ in 0..20 {
+            assert_eq!(report(), first);
+        }
+        assert!(first[0].starts_with("/Info/Author") && first[0].ends_with("Some(\"meta.any\")"));
+        assert!(first[2].starts_with("10 0 R/JS Some(\"9 0\")"));
+        assert!(first[4].starts_with("2 0 R/URI") && first[4].ends_with("None"));
+    }
+
     #[test]
     fn test_document_creation() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -316,6 +692,56 @@ mod tests {
         assert_eq!(doc.size().await.unwrap(), 0);
     }
 
+    #[test]
+    fn test_typed_accessors() {
+        use lopdf::dictionary;
+
+        let mut pdf = lopdf::Document::with_version("1.7");
+        let pages_id = pdf.new_object_id();
+        let content_id = pdf.add_object(Stream::new(dictionary! {}, b"BT ET".to_vec()));
+        let page_id = pdf.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        pdf.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }));
+        let catalog_id = pdf.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        let info_id = pdf.add_object(dictionary! { "Author" => Object::string_literal("A") });
+        pdf.trailer.set("Root", catalog_id);
+        pdf.trailer.set("Info", info_id);
+
+        let mut doc = Document::from_pdf(PathBuf::from("test.pdf"), pdf);
+        assert_eq!(doc.root_id(), Some(catalog_id));
+        assert!(doc.catalog().unwrap().has(b"Pages"));
+        assert!(doc.info().unwrap().has(b"Author"));
+        assert_eq!(doc.pages().collect::<Vec<_>>(), vec![(1, page_id)]);
+        assert_eq!(doc.streams().count(), 1);
+        assert!(doc.encrypt().is_none());
+
+        doc.set_trailer_ids(vec![Object::string_literal("a"), Object::string_literal("b")]);
+        assert_eq!(doc.trailer_ids().map(Vec::len), Some(2));
+
+        assert_eq!(doc.remove_info(), Some(info_id));
+        assert!(doc.info().is_none());
+    }
+
+    #[test]
+    fn test_resolve_follows_references() {
+        let mut doc = Document::default();
+        let target = doc.structure.add_object(Object::Integer(7));
+        let indirect = doc.structure.add_object(Object::Reference(target));
+
+        assert_eq!(doc.resolve(&Object::Reference(indirect)), Some(&Object::Integer(7)));
+
+        // Self-referencing object must not loop forever
+        doc.structure.objects.insert(indirect, Object::Reference(indirect));
+        assert_eq!(doc.resolve(&Object::Reference(indirect)), None);
+    }
+
     #[test]
     fn test_risk_handling() {
         let temp_file = NamedTempFile::new().unwrap();
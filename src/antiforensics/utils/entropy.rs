@@ -0,0 +1,33 @@
+//! Byte entropy
+//! Author: kartik4091
+//! Created: 2025-06-04 21:06:44 UTC
+//! Random-looking bytes where text or structure is expected, such as a
+//! document ID or a comment, suggest encoded or encrypted data.
+
+/// Shannon entropy in bits per byte
+pub fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &b in bytes {
+        counts[b as usize] += 1;
+    }
+    let len = bytes.len() as f64;
+    counts.iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entropy_bounds() {
+        assert_eq!(entropy(&[7; 32]), 0.0);
+        assert_eq!(entropy(&(0..=255).collect::<Vec<u8>>()), 8.0);
+        assert_eq!(entropy(b"abab"), 1.0);
+    }
+}
@@ -0,0 +1,60 @@
+//! Finding construction shared by analyzers and scanners
+//! Author: kartik4091
+//! Created: 2025-06-04 21:06:44 UTC
+//! Detectors report several findings that differ only in location,
+//! description, risk and a few metadata entries. A [`FindingTemplate`]
+//! holds the rest, once per detector.
+
+use std::collections::HashMap;
+
+use crate::antiforensics::{ArtifactType, ForensicArtifact, RiskLevel};
+
+/// Fields every finding of one kind shares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FindingTemplate {
+    pub artifact_type: ArtifactType,
+    /// Put before each finding's location, like `xref:`
+    pub location_prefix: &'static str,
+    pub remediation: &'static str,
+}
+
+impl FindingTemplate {
+    /// A finding at `location` with the given metadata entries
+    pub fn artifact<const N: usize>(
+        &self,
+        location: &str,
+        description: String,
+        risk_level: RiskLevel,
+        metadata: [(&str, String); N],
+    ) -> ForensicArtifact {
+        let metadata: HashMap<String, String> = metadata.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        ForensicArtifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            artifact_type: self.artifact_type.clone(),
+            location: format!("{}{}", self.location_prefix, location),
+            description,
+            risk_level,
+            remediation: self.remediation.into(),
+            metadata,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_fills_shared_fields() {
+        const FINDING: FindingTemplate = FindingTemplate {
+            artifact_type: ArtifactType::Structure,
+            location_prefix: "xref:",
+            remediation: "Rewrite the file",
+        };
+        let artifact = FINDING.artifact("eof", "Missing startxref".into(), RiskLevel::Medium, [("chain_length", "0".into())]);
+        assert_eq!(artifact.location, "xref:eof");
+        assert_eq!(artifact.remediation, "Rewrite the file");
+        assert_eq!(artifact.metadata["chain_length"], "0");
+    }
+}
@@ -21,12 +21,19 @@ pub mod metrics;
 pub mod cache;
 pub mod validation;
 pub mod logging;
+pub mod entropy;
+pub mod findings;
+pub mod objects;
+pub mod streams;
 
 pub use self::{
     metrics::Metrics,
     cache::Cache,
     validation::Validation,
     logging::Logger,
+    entropy::entropy,
+    findings::FindingTemplate,
+    objects::{resolve_dict, resolve_dict_or_stream},
 };
 
 /// Error types for utility operations
@@ -0,0 +1,53 @@
+//! Object lookups shared by scanners and cleaners
+//! Author: kartik4091
+//! Created: 2025-06-04 21:06:44 UTC
+//! Dictionary entries may hold a dictionary inline or a reference to one,
+//! and every catalog walk has to accept both.
+
+use lopdf::{Dictionary, Document, Object};
+
+/// The dictionary `object` is or refers to
+pub fn resolve_dict<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    match object {
+        Object::Reference(id) => doc.get_dictionary(*id).ok(),
+        Object::Dictionary(dict) => Some(dict),
+        _ => None,
+    }
+}
+
+/// Like [`resolve_dict`], also accepting a reference to a stream, whose
+/// dictionary is returned
+pub fn resolve_dict_or_stream<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    match object {
+        Object::Reference(id) => match doc.get_object(*id).ok()? {
+            Object::Dictionary(dict) => Some(dict),
+            Object::Stream(stream) => Some(&stream.dict),
+            _ => None,
+        },
+        Object::Dictionary(dict) => Some(dict),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn test_inline_and_referenced_dictionaries_resolved() {
+        let mut doc = Document::with_version("1.7");
+        let dict = doc.add_object(dictionary! { "Type" => "Filespec" });
+        let stream = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, Vec::new()));
+        let inline = Object::Dictionary(dictionary! { "Type" => "Action" });
+
+        assert!(resolve_dict(&doc, &inline).is_some_and(|d| d.has(b"Type")));
+        assert!(resolve_dict(&doc, &Object::Reference(dict)).is_some());
+        assert!(resolve_dict(&doc, &Object::Reference(stream)).is_none());
+        assert!(resolve_dict(&doc, &Object::Integer(1)).is_none());
+        assert_eq!(
+            resolve_dict_or_stream(&doc, &Object::Reference(stream)).and_then(|d| d.type_name().ok()),
+            Some("EmbeddedFile"),
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Bounded stream decoding
+//! Author: kartik4091
+//! Created: 2025-06-04 18:52:06 UTC
+//! Embedded files and other attacker-supplied streams can inflate to many
+//! times their stored size. These helpers decode FlateDecode content
+//! incrementally and stop at a byte limit instead of materializing the
+//! whole stream first.
+
+use std::io::Read;
+use flate2::read::ZlibDecoder;
+use lopdf::{Object, Stream};
+
+/// Decoded content of `stream`, or `None` when it decodes to more than
+/// `limit` bytes
+///
+/// Content that does not decode is returned as stored, like the callers
+/// did with `decompressed_content` before.
+pub fn decoded_within(stream: &Stream, limit: usize) -> Option<Vec<u8>> {
+    let (content, complete) = decoded_up_to(stream, limit);
+    complete.then_some(content)
+}
+
+/// The first `len` decoded bytes of `stream`, for sniffing file types
+pub fn decoded_prefix(stream: &Stream, len: usize) -> Vec<u8> {
+    decoded_up_to(stream, len).0
+}
+
+/// Up to `limit` decoded bytes, and whether that was all of them
+pub fn decoded_up_to(stream: &Stream, limit: usize) -> (Vec<u8>, bool) {
+    let mut content = match stream.dict.get(b"Filter") {
+        Err(_) => stream.content.clone(),
+        Ok(filter) if is_plain_flate(stream, filter) => {
+            let mut decoded = Vec::new();
+            let mut decoder = ZlibDecoder::new(&stream.content[..]).take(limit as u64 + 1);
+            match decoder.read_to_end(&mut decoded) {
+                Ok(_) => decoded,
+                Err(_) => stream.content.clone(),
+            }
+        }
+        // Predictors and the remaining filters go through lopdf; none of
+        // them expands like Flate does
+        Ok(_) => stream.decompressed_content().unwrap_or_else(|_| stream.content.clone()),
+    };
+    let complete = content.len() <= limit;
+    content.truncate(limit);
+    (content, complete)
+}
+
+/// A single FlateDecode filter without predictor parameters
+fn is_plain_flate(stream: &Stream, filter: &Object) -> bool {
+    let flate = match filter {
+        Object::Name(name) => name == b"FlateDecode",
+        Object::Array(filters) => matches!(filters.as_slice(), [Object::Name(name)] if name == b"FlateDecode"),
+        _ => false,
+    };
+    flate && !stream.dict.has(b"DecodeParms")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use flate2::{write::ZlibEncoder, Compression};
+    use lopdf::{dictionary, Dictionary};
+
+    fn deflated(data: &[u8]) -> Stream {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        Stream::new(dictionary! { "Filter" => "FlateDecode" }, encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn test_decoding_stops_at_limit() {
+        let bomb = deflated(&vec![0u8; 1 << 20]);
+        assert!(bomb.content.len() < 4096);
+        assert_eq!(decoded_within(&bomb, 4096), None);
+        assert_eq!(decoded_prefix(&bomb, 8), vec![0u8; 8]);
+
+        let small = deflated(b"%PDF-1.7\n");
+        assert_eq!(decoded_within(&small, 4096).as_deref(), Some(&b"%PDF-1.7\n"[..]));
+        assert_eq!(decoded_within(&small, 9).as_deref(), Some(&b"%PDF-1.7\n"[..]));
+        assert_eq!(decoded_within(&small, 8), None);
+    }
+
+    #[test]
+    fn test_unfiltered_and_broken_content_returned_as_stored() {
+        let plain = Stream::new(Dictionary::new(), b"notes".to_vec());
+        assert_eq!(decoded_within(&plain, 16).as_deref(), Some(&b"notes"[..]));
+
+        let broken = Stream::new(dictionary! { "Filter" => "FlateDecode" }, b"not zlib".to_vec());
+        assert_eq!(decoded_prefix(&broken, 3), b"not".to_vec());
+    }
+}
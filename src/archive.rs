@@ -0,0 +1,306 @@
+//! PDFs inside ZIP and tar.gz archives
+//!
+//! Ingest pipelines often receive archives of PDFs rather than the PDFs
+//! themselves. `ExtractedArchive` unpacks the PDF members of a `.zip`,
+//! `.tar.gz` or `.tgz` into a private temporary directory, readable by
+//! the current user only, so they can be cleaned like any other input,
+//! and `write_archive` packs the outputs back into an archive of the same
+//! kind.
+//!
+//! Member names are sanitized on extraction: absolute paths and `..`
+//! components never leave the extraction directory, and members whose
+//! sanitized names collide are numbered instead of overwriting each
+//! other. Extraction stops at `MAX_EXTRACTED_BYTES`, so a decompression
+//! bomb fails instead of filling the disk.
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+use serde::{Serialize, Deserialize};
+
+/// Total bytes extracted from one archive before giving up
+pub const MAX_EXTRACTED_BYTES: u64 = 8 * 1024 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("ZIP error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("{0} is not a .zip, .tar.gz or .tgz archive")]
+    UnknownFormat(PathBuf),
+    #[error("archive expands to more than {} bytes", MAX_EXTRACTED_BYTES)]
+    TooLarge,
+}
+
+/// Container formats read and written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Format by file name: `.zip`, `.tar.gz` or `.tgz`, in any case
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+
+    /// `name` without its archive suffix: `batch.tar.gz` becomes `batch`
+    pub fn stem(self, name: &str) -> &str {
+        let suffixes: &[&str] = match self {
+            Self::Zip => &[".zip"],
+            Self::TarGz => &[".tar.gz", ".tgz"],
+        };
+        suffixes.iter()
+            .find_map(|suffix| {
+                let split = name.len().checked_sub(suffix.len())?;
+                name.is_char_boundary(split).then_some(split)
+                    .filter(|&split| name[split..].eq_ignore_ascii_case(suffix))
+                    .map(|split| &name[..split])
+            })
+            .unwrap_or(name)
+    }
+}
+
+/// A PDF member unpacked to disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMember {
+    /// Sanitized name within the archive, `/`-separated
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// PDF members of an archive, unpacked into a temporary directory that
+/// is deleted on drop
+#[derive(Debug)]
+pub struct ExtractedArchive {
+    pub format: ArchiveFormat,
+    pub members: Vec<ArchiveMember>,
+    dir: tempfile::TempDir,
+    /// Lowercased member names, so names differing only in case do not
+    /// collide on case-insensitive filesystems
+    taken: HashSet<String>,
+}
+
+impl ExtractedArchive {
+    /// Unpacks the `.pdf` members of `archive`; other members are skipped
+    pub fn open(archive: &Path) -> Result<Self, ArchiveError> {
+        let format = ArchiveFormat::from_path(archive).ok_or_else(|| ArchiveError::UnknownFormat(archive.to_path_buf()))?;
+        // Created with mode 0700, so other users cannot read or swap the members
+        let dir = tempfile::Builder::new().prefix("archive-").tempdir()?;
+        let mut extracted = Self { format, members: Vec::new(), dir, taken: HashSet::new() };
+        let mut budget = MAX_EXTRACTED_BYTES;
+        let file = fs::File::open(archive)?;
+        match format {
+            ArchiveFormat::Zip => {
+                let mut zip = zip::ZipArchive::new(file)?;
+                for index in 0..zip.len() {
+                    let entry = zip.by_index(index)?;
+                    if entry.is_file() {
+                        let name = entry.name().to_string();
+                        extracted.add(&name, entry, &mut budget)?;
+                    }
+                }
+            }
+            ArchiveFormat::TarGz => {
+                let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(file));
+                for entry in tar.entries()? {
+                    let entry = entry?;
+                    if entry.header().entry_type().is_file() {
+                        let name = entry.path()?.to_string_lossy().into_owned();
+                        extracted.add(&name, entry, &mut budget)?;
+                    }
+                }
+            }
+        }
+        extracted.members.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(extracted)
+    }
+
+    /// Directory the members were unpacked to
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    fn add(&mut self, name: &str, reader: impl Read, budget: &mut u64) -> Result<(), ArchiveError> {
+        let Some(name) = sanitize(name) else { return Ok(()) };
+        if !name.to_ascii_lowercase().ends_with(".pdf") {
+            return Ok(());
+        }
+        let name = self.unique(name);
+        let path = self.dir.path().join(&name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let written = io::copy(&mut reader.take(*budget + 1), &mut fs::File::create(&path)?)?;
+        if written > *budget {
+            return Err(ArchiveError::TooLarge);
+        }
+        *budget -= written;
+        self.members.push(ArchiveMember { name, path });
+        Ok(())
+    }
+
+    /// `name`, or `name (2).pdf`, `name (3).pdf`... when an earlier member took it
+    fn unique(&mut self, name: String) -> String {
+        if self.taken.insert(name.to_lowercase()) {
+            return name;
+        }
+        let (stem, extension) = name.split_at(name.len() - ".pdf".len());
+        (2..)
+            .map(|n| format!("{} ({}){}", stem, n, extension))
+            .find(|candidate| self.taken.insert(candidate.to_lowercase()))
+            .expect("unbounded")
+    }
+}
+
+/// `name` as a relative `/`-separated path, without root, `.` or `..` components
+fn sanitize(name: &str) -> Option<String> {
+    let name = name.replace('\\', "/");
+    let parts: Vec<&str> = Path::new(&name).components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect();
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Packs `files`, given relative to `dir`, into a new archive at `path`
+///
+/// Only the listed files are packed; anything else written under `dir`
+/// stays out of the archive.
+pub fn write_archive(format: ArchiveFormat, dir: &Path, files: &[PathBuf], path: &Path) -> Result<usize, ArchiveError> {
+    let mut files = files.to_vec();
+    files.sort();
+    files.dedup();
+    let name = |file: &Path| {
+        file.components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+
+    let out = fs::File::create(path)?;
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipWriter::new(out);
+            let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+            for file in &files {
+                zip.start_file(name(file), options)?;
+                io::copy(&mut fs::File::open(dir.join(file))?, &mut zip)?;
+            }
+            zip.finish()?.flush()?;
+        }
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(out, flate2::Compression::default()));
+            for file in &files {
+                tar.append_path_with_name(dir.join(file), name(file))?;
+            }
+            tar.into_inner()?.finish()?.flush()?;
+        }
+    }
+    Ok(files.len())
+}
+
+/// What became of one archive member
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberMapping {
+    /// Sanitized member name
+    pub member: String,
+    /// Cleaned output, absent when cleaning failed; inside the archive
+    /// when repacked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    pub verified: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// `<archive>.mapping.json`: archive members and their outputs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveMapping {
+    pub archive: PathBuf,
+    /// Repacked archive, when the outputs were packed again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repacked: Option<PathBuf>,
+    pub members: Vec<MemberMapping>,
+}
+
+impl ArchiveMapping {
+    /// Mapping path for an archive in `dir`: `batch.zip` becomes `dir/batch.mapping.json`
+    pub fn path_for(dir: &Path, format: ArchiveFormat, archive: &Path) -> PathBuf {
+        let name = archive.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        dir.join(format!("{}.mapping.json", format.stem(&name)))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdf_members_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("a.pdf"), b"%PDF-1.7 a").unwrap();
+        fs::write(source.join("nested/B.PDF"), b"%PDF-1.7 b").unwrap();
+        fs::write(source.join("notes.txt"), b"skipped").unwrap();
+        fs::write(source.join("a.pdf.json"), b"not listed").unwrap();
+        let files: Vec<PathBuf> = ["a.pdf", "nested/B.PDF", "notes.txt"].iter().map(PathBuf::from).collect();
+
+        for name in ["batch.zip", "batch.tar.gz"] {
+            let archive = dir.path().join(name);
+            let format = ArchiveFormat::from_path(&archive).unwrap();
+            assert_eq!(write_archive(format, &source, &files, &archive).unwrap(), 3);
+            assert_eq!(format.stem(name), "batch");
+
+            let extracted = ExtractedArchive::open(&archive).unwrap();
+            let names: Vec<&str> = extracted.members.iter().map(|m| m.name.as_str()).collect();
+            assert_eq!(names, vec!["a.pdf", "nested/B.PDF"], "{}", name);
+            assert_eq!(fs::read(&extracted.members[1].path).unwrap(), b"%PDF-1.7 b");
+            let temp = extracted.path().to_path_buf();
+            drop(extracted);
+            assert!(!temp.exists());
+        }
+
+        // Members whose sanitized names collide are kept apart
+        let archive = dir.path().join("colliding.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        for member in ["a.pdf", "../a.pdf", "/A.pdf"] {
+            zip.start_file(member, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(member.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let extracted = ExtractedArchive::open(&archive).unwrap();
+        let names: Vec<&str> = extracted.members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["a (2).pdf", "a (3).pdf", "a.pdf"]);
+        assert_eq!(fs::read(&extracted.members[2].path).unwrap(), b"a.pdf");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(extracted.path()).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+
+        assert_eq!(sanitize("../../etc/evil.pdf").as_deref(), Some("etc/evil.pdf"));
+        assert_eq!(sanitize("/abs\\win.pdf").as_deref(), Some("abs/win.pdf"));
+        assert_eq!(sanitize(".."), None);
+        assert!(ArchiveFormat::from_path(Path::new("a.rar")).is_none());
+    }
+}
@@ -0,0 +1,368 @@
+//! Pre-clean backups and the cleaning transaction log
+//!
+//! Every processing run is a cleaning transaction identified by its
+//! `ProcessingResult::document_id`. Before cleaning starts, the
+//! `BackupManager` keeps the exact input bytes; the transaction log
+//! records when the run began, whether it committed or failed, and any
+//! later rollback. `rollback` returns the input as it was before the
+//! clean, checked against the SHA-256 recorded when it was backed up.
+//!
+//! In memory, backups last as long as the engine or until newer backups
+//! push them over the memory budget, oldest first. On disk, backups are
+//! read-only files under `<dir>/backups/` and the log is the append-only
+//! `<dir>/transactions.jsonl`, so a restarted engine opening the same
+//! directory can still roll back earlier runs.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Where pre-clean backups are kept
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BackupMode {
+    /// No backups; cleaning cannot be rolled back
+    #[default]
+    Off,
+    /// Backups in memory, lost when the engine stops; the oldest are
+    /// dropped once they hold more than `max_bytes`
+    Memory { max_bytes: usize },
+    /// Backups and the transaction log in this directory
+    Disk(PathBuf),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("No cleaning transaction {0}")]
+    NotFound(String),
+    #[error("Backup of {0} is missing or was pruned")]
+    Missing(String),
+    #[error("Backup of {0} does not match the recorded input hash")]
+    Corrupt(String),
+}
+
+/// State of a cleaning transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionState {
+    /// Input backed up, cleaning running (or the engine stopped mid-run)
+    Begun,
+    Committed,
+    Failed,
+    RolledBack,
+}
+
+/// Latest record of one cleaning transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub cleaning_id: String,
+    pub state: TransactionState,
+    /// When the state was recorded
+    pub at: DateTime<Utc>,
+    pub began_at: DateTime<Utc>,
+    /// Input file, when the run was started from a path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+    pub input_sha256: String,
+    pub input_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A reasonable `BackupMode::Memory` budget for batch runs
+pub const DEFAULT_MEMORY_BUDGET: usize = 512 * 1024 * 1024;
+
+/// Keeps inputs of cleaning runs and the log of what happened to them
+#[derive(Debug)]
+pub struct BackupManager {
+    dir: Option<PathBuf>,
+    transactions: Mutex<HashMap<String, Transaction>>,
+    /// Backups of an in-memory manager
+    memory: Mutex<MemoryBackups>,
+}
+
+/// In-memory backups, dropped oldest first to stay within a byte budget
+#[derive(Debug, Default)]
+struct MemoryBackups {
+    max_bytes: usize,
+    bytes: usize,
+    backups: HashMap<String, Vec<u8>>,
+    /// Cleaning ids, oldest backup first
+    order: VecDeque<String>,
+}
+
+impl MemoryBackups {
+    fn new(max_bytes: usize) -> Self {
+        Self { max_bytes, ..Default::default() }
+    }
+
+    /// Keeps `input`, dropping the oldest backups until it fits
+    ///
+    /// The newest backup is always kept, even when it alone is over budget.
+    fn insert(&mut self, cleaning_id: &str, input: &[u8]) {
+        self.remove(cleaning_id);
+        while self.bytes + input.len() > self.max_bytes {
+            let Some(oldest) = self.order.pop_front() else { break };
+            if let Some(backup) = self.backups.remove(&oldest) {
+                self.bytes -= backup.len();
+                log::debug!("dropped in-memory backup of {} to stay within {} bytes", oldest, self.max_bytes);
+            }
+        }
+        self.bytes += input.len();
+        self.backups.insert(cleaning_id.to_string(), input.to_vec());
+        self.order.push_back(cleaning_id.to_string());
+    }
+
+    fn remove(&mut self, cleaning_id: &str) {
+        if let Some(backup) = self.backups.remove(cleaning_id) {
+            self.bytes -= backup.len();
+            self.order.retain(|id| id != cleaning_id);
+        }
+    }
+
+    fn get(&self, cleaning_id: &str) -> Option<&Vec<u8>> {
+        self.backups.get(cleaning_id)
+    }
+}
+
+impl BackupManager {
+    /// Manager for `mode`; `None` when backups are off
+    pub fn from_mode(mode: &BackupMode) -> io::Result<Option<Self>> {
+        match mode {
+            BackupMode::Off => Ok(None),
+            BackupMode::Memory { max_bytes } => Ok(Some(Self::in_memory(*max_bytes))),
+            BackupMode::Disk(dir) => Self::open(dir).map(Some),
+        }
+    }
+
+    /// Manager keeping up to `max_bytes` of backups in memory
+    pub fn in_memory(max_bytes: usize) -> Self {
+        Self { dir: None, transactions: Mutex::default(), memory: Mutex::new(MemoryBackups::new(max_bytes)) }
+    }
+
+    /// Opens the backup directory at `dir`, replaying its transaction log
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = crate::paths::extended(&dir.into()).into_owned();
+        fs::create_dir_all(dir.join("backups"))?;
+
+        let mut transactions = HashMap::new();
+        match fs::read_to_string(dir.join("transactions.jsonl")) {
+            Ok(log) => {
+                for line in log.lines().filter(|line| !line.trim().is_empty()) {
+                    // A torn last line from a crash is skipped, not fatal
+                    match serde_json::from_str::<Transaction>(line) {
+                        Ok(record) => {
+                            transactions.insert(record.cleaning_id.clone(), record);
+                        }
+                        Err(e) => log::warn!("skipping unreadable transaction record in {}: {}", dir.display(), e),
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Self { dir: Some(dir), transactions: Mutex::new(transactions), memory: Mutex::default() })
+    }
+
+    /// Whether backups survive an engine restart
+    pub fn is_persistent(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Backs up `input` and opens a transaction for it
+    pub fn begin(&self, cleaning_id: &str, source: Option<&Path>, input: &[u8]) -> io::Result<()> {
+        if let Some(path) = self.backup_path(cleaning_id) {
+            let staging = path.with_extension("pdf.tmp");
+            fs::write(&staging, input)?;
+            let mut permissions = fs::metadata(&staging)?.permissions();
+            permissions.set_readonly(true);
+            fs::set_permissions(&staging, permissions)?;
+            fs::rename(&staging, &path)?;
+        } else {
+            self.memory.lock().unwrap_or_else(|e| e.into_inner()).insert(cleaning_id, input);
+        }
+
+        let now = Utc::now();
+        self.record(Transaction {
+            cleaning_id: cleaning_id.to_string(),
+            state: TransactionState::Begun,
+            at: now,
+            began_at: now,
+            source: source.map(Path::to_path_buf),
+            input_sha256: format!("{:x}", Sha256::digest(input)),
+            input_bytes: input.len() as u64,
+            output_sha256: None,
+            error: None,
+        })
+    }
+
+    /// Marks the transaction as committed with the output it produced
+    pub fn commit(&self, cleaning_id: &str, output: &[u8]) -> Result<(), BackupError> {
+        let output_sha256 = format!("{:x}", Sha256::digest(output));
+        self.transition(cleaning_id, TransactionState::Committed, |t| t.output_sha256 = Some(output_sha256))
+    }
+
+    /// Marks the transaction as failed; its backup stays available
+    pub fn fail(&self, cleaning_id: &str, error: &str) -> Result<(), BackupError> {
+        self.transition(cleaning_id, TransactionState::Failed, |t| t.error = Some(error.to_string()))
+    }
+
+    /// The exact input of a cleaning run, as it was before the clean
+    ///
+    /// Rolling back does not delete the backup, so it can be repeated.
+    pub fn rollback(&self, cleaning_id: &str) -> Result<Vec<u8>, BackupError> {
+        let transaction = self.transaction(cleaning_id)
+            .ok_or_else(|| BackupError::NotFound(cleaning_id.to_string()))?;
+
+        let input = match self.backup_path(cleaning_id) {
+            Some(path) => fs::read(&path).map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => BackupError::Missing(cleaning_id.to_string()),
+                _ => BackupError::Io(e),
+            })?,
+            None => self.memory.lock().unwrap_or_else(|e| e.into_inner())
+                .get(cleaning_id)
+                .cloned()
+                .ok_or_else(|| BackupError::Missing(cleaning_id.to_string()))?,
+        };
+        if format!("{:x}", Sha256::digest(&input)) != transaction.input_sha256 {
+            return Err(BackupError::Corrupt(cleaning_id.to_string()));
+        }
+
+        self.transition(cleaning_id, TransactionState::RolledBack, |_| {})?;
+        Ok(input)
+    }
+
+    pub fn transaction(&self, cleaning_id: &str) -> Option<Transaction> {
+        self.transactions.lock().unwrap_or_else(|e| e.into_inner()).get(cleaning_id).cloned()
+    }
+
+    /// Every transaction, oldest first
+    pub fn transactions(&self) -> Vec<Transaction> {
+        let mut transactions: Vec<Transaction> = self.transactions.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        transactions.sort_by(|a, b| a.began_at.cmp(&b.began_at).then_with(|| a.cleaning_id.cmp(&b.cleaning_id)));
+        transactions
+    }
+
+    fn transition(
+        &self,
+        cleaning_id: &str,
+        state: TransactionState,
+        update: impl FnOnce(&mut Transaction),
+    ) -> Result<(), BackupError> {
+        let mut transaction = self.transaction(cleaning_id)
+            .ok_or_else(|| BackupError::NotFound(cleaning_id.to_string()))?;
+        transaction.state = state;
+        transaction.at = Utc::now();
+        update(&mut transaction);
+        Ok(self.record(transaction)?)
+    }
+
+    /// Appends `transaction` to the log and makes it the current record
+    fn record(&self, transaction: Transaction) -> io::Result<()> {
+        let mut transactions = self.transactions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(dir) = &self.dir {
+            let mut line = serde_json::to_vec(&transaction)?;
+            line.push(b'\n');
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dir.join("transactions.jsonl"))?
+                .write_all(&line)?;
+        }
+        transactions.insert(transaction.cleaning_id.clone(), transaction);
+        Ok(())
+    }
+
+    fn backup_path(&self, cleaning_id: &str) -> Option<PathBuf> {
+        // Ids are engine-made UUIDs; keep anything else from escaping the directory
+        let name: String = cleaning_id.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        self.dir.as_ref().map(|dir| dir.join("backups").join(format!("{}.pdf", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollback_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let manager = BackupManager::open(dir.path()).unwrap();
+            manager.begin("run-1", Some(Path::new("in.pdf")), b"%PDF-1.7 original").unwrap();
+            manager.commit("run-1", b"%PDF-1.7 cleaned").unwrap();
+        }
+
+        let manager = BackupManager::open(dir.path()).unwrap();
+        assert_eq!(manager.transaction("run-1").unwrap().state, TransactionState::Committed);
+        assert_eq!(manager.rollback("run-1").unwrap(), b"%PDF-1.7 original");
+        assert_eq!(manager.transaction("run-1").unwrap().state, TransactionState::RolledBack);
+
+        let reopened = BackupManager::open(dir.path()).unwrap();
+        assert_eq!(reopened.transactions().len(), 1);
+        assert_eq!(reopened.transactions()[0].state, TransactionState::RolledBack);
+        assert_eq!(reopened.transactions()[0].source.as_deref(), Some(Path::new("in.pdf")));
+    }
+
+    #[test]
+    fn test_in_memory_rollback() {
+        let manager = BackupManager::in_memory(DEFAULT_MEMORY_BUDGET);
+        manager.begin("run-1", None, b"original").unwrap();
+        manager.fail("run-1", "parse error").unwrap();
+        assert_eq!(manager.rollback("run-1").unwrap(), b"original");
+        assert!(matches!(manager.rollback("run-2"), Err(BackupError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_in_memory_backups_stay_within_budget() {
+        let manager = BackupManager::in_memory(16);
+        manager.begin("run-1", None, b"first...").unwrap();
+        manager.begin("run-2", None, b"second..").unwrap();
+        manager.begin("run-3", None, b"third...").unwrap();
+
+        assert!(matches!(manager.rollback("run-1"), Err(BackupError::Missing(_))));
+        assert_eq!(manager.rollback("run-2").unwrap(), b"second..");
+        assert_eq!(manager.rollback("run-3").unwrap(), b"third...");
+        assert_eq!(manager.memory.lock().unwrap().bytes, 16);
+
+        // A backup over budget on its own replaces everything else
+        manager.begin("run-4", None, &[0u8; 32]).unwrap();
+        assert!(matches!(manager.rollback("run-3"), Err(BackupError::Missing(_))));
+        assert_eq!(manager.rollback("run-4").unwrap(), vec![0u8; 32]);
+    }
+
+    #[test]
+    fn test_tampered_backup_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = BackupManager::open(dir.path()).unwrap();
+        manager.begin("run-1", None, b"original").unwrap();
+
+        let path = manager.backup_path("run-1").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&path, permissions).unwrap();
+        fs::write(&path, b"tampered").unwrap();
+
+        assert!(matches!(manager.rollback("run-1"), Err(BackupError::Corrupt(_))));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(manager.rollback("run-1"), Err(BackupError::Missing(_))));
+    }
+}
@@ -7,64 +7,50 @@
 // User: kartik6717
 // Note: Placeholder code has been replaced with actual implementations
 
-#![allow(warnings)]
+use log::{error, info};
+use pdf_engine::{builder::CleanPolicy, sandbox, EngineConfig, PdfEngine};
+use std::path::Path;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Re-executed as the sandboxed decoder; confine before doing anything else
+    let mut argv = std::env::args().skip(1);
+    if argv.next().as_deref() == Some(sandbox::WORKER_ARG) {
+        sandbox::run_worker(argv);
+    }
 
-use pdf_engine::{PdfEngine, ProcessConfig, SecurityConfig};
-use std::{path::Path, time::Duration};
-use tokio;
-use log::{info, error};
+    tokio::runtime::Runtime::new()?.block_on(run())
+}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging
     env_logger::init();
-    
-    info!("Starting PDF Processing Workflow");
-    info!("Timestamp: 2025-05-31 20:01:05");
-    info!("User: kartik6717");
 
-    // Initialize PDF Engine
-    let engine = PdfEngine::new().await?;
+    info!("Starting PDF Processing Workflow");
 
-    // Process configuration
-    let config = ProcessConfig {
-        clean_metadata: true,
-        normalize_version: true,
-        apply_security: true,
-        forensic_clean: true,
-        security: SecurityConfig {
-            encrypt: true,
-            user_password: Some("user123".to_string()),
-            owner_password: Some("owner456".to_string()),
-            permissions: vec![
-                "print".to_string(),
-                "copy".to_string(),
-            ],
-        },
+    // Initialize PDF Engine, decoding untrusted streams in worker processes
+    let config = EngineConfig {
+        sandbox: Some(sandbox::SandboxConfig::default()),
+        ..Default::default()
     };
+    let engine = PdfEngine::new(Some(config)).await?;
 
     // Process all PDFs in the input directory
-    process_directory("input", "output", &engine, &config).await?;
-
-    Ok(())
+    process_directory("input", "output", &engine).await
 }
 
 async fn process_directory(
     input_dir: &str,
     output_dir: &str,
     engine: &PdfEngine,
-    config: &ProcessConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Create output directory if it doesn't exist
     std::fs::create_dir_all(output_dir)?;
 
     // Process all PDF files
     for entry in std::fs::read_dir(input_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        
+        let path = entry?.path();
         if path.extension().and_then(|s| s.to_str()) == Some("pdf") {
-            process_pdf(&path, output_dir, engine, config).await?;
+            process_pdf(&path, output_dir, engine).await?;
         }
     }
 
@@ -75,26 +61,18 @@ async fn process_pdf(
     input_path: &Path,
     output_dir: &str,
     engine: &PdfEngine,
-    config: &ProcessConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let filename = input_path.file_name().unwrap().to_str().unwrap();
-    let output_path = Path::new(output_dir).join(format!("processed_{}", filename));
+    let filename = pdf_engine::paths::display_name(input_path);
+    let mut output_name = std::ffi::OsString::from("processed_");
+    output_name.push(input_path.file_name().unwrap_or_default());
+    let output_path = Path::new(output_dir).join(output_name);
 
     info!("Processing: {}", filename);
 
-    // Read input file
-    let input = std::fs::read(input_path)?;
-
-    // Process document
-    match engine.process_document(&input, config.clone()).await {
-        Ok(output) => {
-            // Write output file
-            std::fs::write(&output_path, output)?;
-            info!("Successfully processed: {}", filename);
-        }
-        Err(e) => {
-            error!("Failed to process {}: {}", filename, e);
-        }
+    let pipeline = engine.pipeline_file(input_path)?.clean(CleanPolicy::default());
+    match pipeline.save(&output_path).await {
+        Ok(_) => info!("Successfully processed: {}", filename),
+        Err(e) => error!("Failed to process {}: {}", filename, e),
     }
 
     Ok(())
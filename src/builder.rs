@@ -0,0 +1,822 @@
+//! Builder for custom processing pipelines
+//!
+//! `PdfEngine::pipeline` returns a [`PipelineBuilder`] whose type parameter
+//! tracks the last step added. Each step is only available from the states
+//! where it makes sense, so an out-of-order pipeline fails to compile:
+//!
+//! ```no_run
+//! # async fn example(engine: &pdf_engine::PdfEngine, input: Vec<u8>) -> Result<(), pdf_engine::PdfError> {
+//! use pdf_engine::builder::CleanPolicy;
+//!
+//! let output = engine.pipeline(input)
+//!     .scan()
+//!     .clean(CleanPolicy::default())
+//!     .optimize()
+//!     .save("clean.pdf")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ```compile_fail
+//! # async fn example(engine: &pdf_engine::PdfEngine, key: pdf_engine::security::keys::Key) {
+//! // Signing requires a cleaned document
+//! engine.pipeline(Vec::new()).sign(key);
+//! # }
+//! ```
+
+use std::{collections::BTreeSet, marker::PhantomData, path::Path, time::Instant};
+
+use lopdf::{Object, ObjectId};
+
+use crate::{
+    antiforensics::{
+        analyzer::info_xmp,
+        cleaner::{
+            attachments::{strip_attachments_scoped, AttachmentPolicy},
+            metadata_policy::MetadataPolicy,
+            scope::{CleanTarget, CleaningScope},
+            timestamps::FileTimestamps,
+        },
+        report::ObjectMap,
+        scanner::{invoice::invoice_attachments, sink, usage_rights::USAGE_RIGHTS_KEYS, DocumentScanner, UsageRightsScanner},
+        types::Document,
+        ArtifactType,
+        ForensicArtifact,
+    },
+    concurrency::Subsystem,
+    plugins::PluginReport,
+    security::{encryption::EncryptionConfig, keys::Key, operator::Operation},
+    writer::{WriteOptions, WriteResult},
+    PdfEngine,
+    PdfError,
+    ProcessingStage,
+    ResultExt,
+    StageLog,
+    StageReport,
+};
+
+/// Entries removed from the catalog and Info dictionary by the clean step
+#[derive(Debug, Clone)]
+pub struct CleanPolicy {
+    /// Remove the catalog `/JavaScript` entry
+    pub remove_javascript: bool,
+    /// Remove `/OpenAction` and `/AA` from the catalog
+    pub remove_actions: bool,
+    /// Remove `/Metadata`, `/Lang`, `/MarkInfo` and `/PieceInfo` from the catalog
+    pub remove_metadata: bool,
+    /// Remove `/Perms /UR3` and `/UR` usage rights signatures, which any
+    /// change invalidates
+    pub remove_usage_rights: bool,
+    /// Embedded files to strip; with `KeepInvoices` the XMP metadata of a
+    /// document carrying a compliant e-invoice is kept, since the invoice
+    /// profile is declared there
+    pub attachments: AttachmentPolicy,
+    /// Info dictionary keys to strip
+    pub info_keys: Vec<String>,
+    /// Metadata fields kept or dropped regardless of the settings above
+    pub metadata: MetadataPolicy,
+    /// Part of the document the policy may change
+    pub scope: CleaningScope,
+}
+
+impl Default for CleanPolicy {
+    fn default() -> Self {
+        Self {
+            remove_javascript: true,
+            remove_actions: true,
+            remove_metadata: true,
+            remove_usage_rights: true,
+            attachments: AttachmentPolicy::Keep,
+            info_keys: ["ModDate", "CreationDate", "Producer", "Creator"]
+                .iter()
+                .map(|key| key.to_string())
+                .collect(),
+            metadata: MetadataPolicy::default(),
+            scope: CleaningScope::default(),
+        }
+    }
+}
+
+impl CleanPolicy {
+    /// Applies the policy to a parsed document
+    pub fn apply(&self, doc: &mut Document) -> Result<(), lopdf::Error> {
+        self.apply_recorded(doc).map(|_| ())
+    }
+
+    /// Applies the policy and returns the entries it removed, e.g. `/Root/OpenAction`
+    pub fn apply_recorded(&self, doc: &mut Document) -> Result<Vec<String>, lopdf::Error> {
+        let keep_xmp = self.attachments == AttachmentPolicy::KeepInvoices
+            && invoice_attachments(&doc.structure).iter().any(|invoice| invoice.is_compliant());
+        let root_id = doc.root_id().ok_or(lopdf::Error::DictKey)?;
+        let root = doc.catalog_mut().ok_or(lopdf::Error::DictKey)?;
+
+        let mut catalog_keys: Vec<(&[u8], ArtifactType)> = Vec::new();
+        if self.remove_javascript {
+            catalog_keys.push((&b"JavaScript"[..], ArtifactType::JavaScript));
+        }
+        if self.remove_actions {
+            catalog_keys.extend([&b"OpenAction"[..], b"AA"].map(|key| (key, ArtifactType::Structure)));
+        }
+        // A selective metadata policy trims the XMP packet instead of
+        // removing it, and trims it even when metadata is otherwise kept
+        let strip_xmp = self.remove_metadata && !keep_xmp;
+        if self.remove_metadata {
+            if strip_xmp && !self.metadata.is_selective() {
+                catalog_keys.push((&b"Metadata"[..], ArtifactType::Metadata));
+            }
+            catalog_keys.extend([&b"Lang"[..], b"MarkInfo", b"PieceInfo"].into_iter()
+                .filter(|key| !self.metadata.keeps(&String::from_utf8_lossy(key)))
+                .map(|key| (key, ArtifactType::Metadata)));
+        }
+
+        let mut removed = Vec::new();
+        for (key, artifact_type) in catalog_keys {
+            let location = format!("/Root/{}", String::from_utf8_lossy(key));
+            let target = CleanTarget { location: &location, artifact_type, object: Some(root_id), page: None };
+            if self.scope.allows(&target) && root.remove(key).is_some() {
+                removed.push(location);
+            }
+        }
+
+        if self.metadata.is_selective() && !keep_xmp {
+            removed.extend(retain_xmp(&mut doc.structure, root_id, &self.scope, |name| self.metadata.keeps(name))?);
+        } else if !strip_xmp && !self.metadata.drop.is_empty() {
+            removed.extend(retain_xmp(&mut doc.structure, root_id, &self.scope, |name| !self.metadata.drops(name))?);
+        }
+
+        if self.remove_usage_rights {
+            removed.extend(remove_usage_rights(&mut doc.structure, root_id, &self.scope)?);
+        }
+
+        let attachments = strip_attachments_scoped(doc, self.attachments, &self.scope)?;
+        removed.extend(attachments.removed.iter().map(|name| format!("/Root/Names/EmbeddedFiles/{}", name)));
+
+        if !self.info_keys.is_empty() || !self.metadata.is_default() {
+            // Info is normally indirect, but inline dictionaries occur too
+            let info_id = doc.info_id();
+            let info = match info_id {
+                Some(_) => doc.info_mut(),
+                None => doc.structure.trailer.get_mut(b"Info").and_then(Object::as_dict_mut).ok(),
+            };
+            if let Some(info) = info {
+                let mut keys = self.info_keys.clone();
+                for (key, _) in info.iter() {
+                    let key = String::from_utf8_lossy(key).into_owned();
+                    if (self.metadata.is_selective() || self.metadata.drops(&key)) && !keys.contains(&key) {
+                        keys.push(key);
+                    }
+                }
+                keys.retain(|key| !self.metadata.keeps(key));
+                for key in &keys {
+                    let location = format!("/Info/{}", key);
+                    let target = CleanTarget {
+                        location: &location,
+                        artifact_type: ArtifactType::Metadata,
+                        object: info_id,
+                        page: None,
+                    };
+                    if self.scope.allows(&target) && info.remove(key.as_bytes()).is_some() {
+                        removed.push(location);
+                    }
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Removes the XMP properties `keep` rejects, and the packet once none are left
+fn retain_xmp(
+    doc: &mut lopdf::Document,
+    root_id: ObjectId,
+    scope: &CleaningScope,
+    keep: impl FnMut(&str) -> bool,
+) -> Result<Vec<String>, lopdf::Error> {
+    let target = CleanTarget {
+        location: "/Root/Metadata",
+        artifact_type: ArtifactType::Metadata,
+        object: Some(root_id),
+        page: None,
+    };
+    if !scope.allows(&target) {
+        return Ok(Vec::new());
+    }
+    let Some(report) = info_xmp::retain_xmp(doc, keep)? else { return Ok(Vec::new()) };
+    if report.kept.is_empty() {
+        doc.get_object_mut(root_id)?.as_dict_mut()?.remove(b"Metadata");
+        return Ok(vec![target.location.to_string()]);
+    }
+    Ok(report.removed.iter().map(|name| format!("/Root/Metadata/{}", name)).collect())
+}
+
+/// Removes usage rights signatures, dropping `/Perms` once it is empty
+///
+/// `/DocMDP` certification is a deliberate author choice and is kept.
+fn remove_usage_rights(
+    doc: &mut lopdf::Document,
+    root_id: ObjectId,
+    scope: &CleaningScope,
+) -> Result<Vec<String>, lopdf::Error> {
+    let perms_id = match doc.get_dictionary(root_id)?.get(b"Perms") {
+        Ok(Object::Reference(id)) => Some(*id),
+        Ok(_) => None,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let perms = match perms_id {
+        Some(id) => doc.get_object_mut(id)?.as_dict_mut()?,
+        None => doc.get_object_mut(root_id)?.as_dict_mut()?.get_mut(b"Perms")?.as_dict_mut()?,
+    };
+
+    let mut removed = Vec::new();
+    let mut signatures = Vec::new();
+    for key in USAGE_RIGHTS_KEYS {
+        let location = format!("/Root/Perms/{}", String::from_utf8_lossy(key));
+        let target = CleanTarget {
+            location: &location,
+            artifact_type: ArtifactType::Signature,
+            object: Some(perms_id.unwrap_or(root_id)),
+            page: None,
+        };
+        if !scope.allows(&target) {
+            continue;
+        }
+        if let Some(value) = perms.remove(key) {
+            if let Object::Reference(id) = value {
+                signatures.push(id);
+            }
+            removed.push(location);
+        }
+    }
+    let now_empty = perms.is_empty();
+
+    for id in signatures {
+        doc.objects.remove(&id);
+    }
+    if now_empty {
+        doc.get_object_mut(root_id)?.as_dict_mut()?.remove(b"Perms");
+        if let Some(id) = perms_id {
+            doc.objects.remove(&id);
+        }
+    }
+    Ok(removed)
+}
+
+/// Input recorded, nothing run yet
+pub struct Loaded;
+/// Scan recorded
+pub struct Scanned;
+/// Clean recorded
+pub struct Cleaned;
+/// Optimization recorded
+pub struct Optimized;
+/// Encryption recorded
+pub struct Encrypted;
+/// Signature recorded
+pub struct Signed;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::Loaded {}
+    impl Sealed for super::Scanned {}
+    impl Sealed for super::Cleaned {}
+    impl Sealed for super::Optimized {}
+    impl Sealed for super::Encrypted {}
+    impl Sealed for super::Signed {}
+}
+
+/// States from which the document may be cleaned
+pub trait CanClean: sealed::Sealed {}
+impl CanClean for Loaded {}
+impl CanClean for Scanned {}
+
+/// States from which the document may be encrypted
+pub trait CanEncrypt: sealed::Sealed {}
+impl CanEncrypt for Cleaned {}
+impl CanEncrypt for Optimized {}
+
+/// States from which the document may be signed
+pub trait CanSign: sealed::Sealed {}
+impl CanSign for Cleaned {}
+impl CanSign for Optimized {}
+impl CanSign for Encrypted {}
+
+/// States in which the pipeline may run; output is only produced once cleaned
+pub trait CanSave: sealed::Sealed {}
+impl CanSave for Cleaned {}
+impl CanSave for Optimized {}
+impl CanSave for Encrypted {}
+impl CanSave for Signed {}
+
+enum Step {
+    Scan,
+    Clean(CleanPolicy),
+    Optimize,
+    Encrypt(EncryptionConfig),
+    Sign(Key),
+}
+
+impl Step {
+    /// What the engine's operator must be allowed to do for this step
+    fn operation(&self) -> Operation {
+        match self {
+            Step::Scan => Operation::Scan,
+            Step::Clean(_) | Step::Optimize => Operation::Clean,
+            Step::Encrypt(_) | Step::Sign(_) => Operation::Encrypt,
+        }
+    }
+}
+
+/// Result of running a custom pipeline
+#[derive(Debug)]
+pub struct PipelineOutput {
+    /// Final document bytes
+    pub data: Vec<u8>,
+    /// Artifacts reported by the scan step, if one was added
+    pub artifacts: Vec<ForensicArtifact>,
+    /// Outcome of every step, in execution order
+    pub stages: Vec<StageReport>,
+    /// Invocations of registered plugins during the scan and clean steps
+    pub plugins: Vec<PluginReport>,
+    /// Write details when the output was saved to disk
+    pub write: Option<WriteResult>,
+    /// Objects of the input, or of the document after the last clean step,
+    /// and what the optimize steps and the write made of them; absent when
+    /// neither ran
+    pub object_map: Option<ObjectMap>,
+}
+
+/// Records pipeline steps and runs them against the engine's subsystems
+pub struct PipelineBuilder<'e, S> {
+    engine: &'e PdfEngine,
+    input: Vec<u8>,
+    /// Times of the input file, copied to the output under `TimestampPolicy::CopyFromInput`
+    source_times: Option<FileTimestamps>,
+    steps: Vec<Step>,
+    _state: PhantomData<S>,
+}
+
+impl<'e, S> PipelineBuilder<'e, S> {
+    fn push<T>(mut self, step: Step) -> PipelineBuilder<'e, T> {
+        self.steps.push(step);
+        PipelineBuilder {
+            engine: self.engine,
+            input: self.input,
+            source_times: self.source_times,
+            steps: self.steps,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<'e> PipelineBuilder<'e, Loaded> {
+    pub(crate) fn new(engine: &'e PdfEngine, input: Vec<u8>, source_times: Option<FileTimestamps>) -> Self {
+        Self {
+            engine,
+            input,
+            source_times,
+            steps: Vec::new(),
+            _state: PhantomData,
+        }
+    }
+
+    /// Scans the original document for forensic artifacts, including
+    /// with any registered scanner plugins
+    pub fn scan(self) -> PipelineBuilder<'e, Scanned> {
+        self.push(Step::Scan)
+    }
+}
+
+impl<'e, S: CanClean> PipelineBuilder<'e, S> {
+    /// Removes the entries selected by `policy`, then runs core processing
+    /// and any registered cleaner plugins
+    pub fn clean(self, policy: CleanPolicy) -> PipelineBuilder<'e, Cleaned> {
+        self.push(Step::Clean(policy))
+    }
+}
+
+impl<'e> PipelineBuilder<'e, Cleaned> {
+    /// Optimizes the cleaned document
+    pub fn optimize(self) -> PipelineBuilder<'e, Optimized> {
+        self.push(Step::Optimize)
+    }
+}
+
+impl<'e, S: CanEncrypt> PipelineBuilder<'e, S> {
+    /// Encrypts the document with explicit options
+    pub fn encrypt(self, options: EncryptionConfig) -> PipelineBuilder<'e, Encrypted> {
+        self.push(Step::Encrypt(options))
+    }
+}
+
+impl<'e, S: CanSign> PipelineBuilder<'e, S> {
+    /// Signs the document with a managed signing key
+    pub fn sign(self, key: Key) -> PipelineBuilder<'e, Signed> {
+        self.push(Step::Sign(key))
+    }
+}
+
+impl<'e, S: CanSave> PipelineBuilder<'e, S> {
+    /// Runs the pipeline and writes the result to `path`
+    pub async fn save(self, path: impl AsRef<Path>) -> Result<PipelineOutput, PdfError> {
+        self.execute(Some(path.as_ref())).await
+    }
+
+    /// Runs the pipeline and keeps the result in memory
+    pub async fn run(self) -> Result<PipelineOutput, PdfError> {
+        self.execute(None).await
+    }
+
+    async fn execute(self, path: Option<&Path>) -> Result<PipelineOutput, PdfError> {
+        // Refuse before any step runs, so a denied pipeline writes nothing
+        let operations: BTreeSet<Operation> = self.steps.iter().map(Step::operation).collect();
+        let resource = path.map_or_else(|| "<pipeline>".to_string(), |p| p.display().to_string());
+        for operation in operations {
+            self.engine.authorize(operation, &resource).await?;
+        }
+        self.engine.admit(self.input.len() as u64)?;
+
+        let job = self.engine.jobs.begin()?;
+        job.run(self.execute_steps(path)).await
+    }
+
+    async fn execute_steps(self, path: Option<&Path>) -> Result<PipelineOutput, PdfError> {
+        let engine = self.engine;
+        let mut stages = StageLog::default();
+        let mut data = self.input;
+        let source_times = self.source_times;
+        let mut artifacts = Vec::new();
+        let mut plugins = Vec::new();
+        let mut object_map: Option<ObjectMap> = None;
+        // Encrypted or signed bytes are written as they are
+        let mut sealed = false;
+
+        for step in self.steps {
+            let started = Instant::now();
+            match step {
+                Step::Scan => {
+                    let _permit = engine.concurrency.acquire(Subsystem::Scan).await
+                        .stage(ProcessingStage::Scan)?;
+                    let scanned = scan_bytes(engine, &data).await;
+                    let (found, reports) = stages.record(ProcessingStage::Scan, started, scanned)?;
+                    artifacts = found;
+                    plugins.extend(reports);
+                }
+                Step::Clean(policy) => {
+                    let _permit = engine.concurrency.acquire(Subsystem::Clean).await
+                        .stage(ProcessingStage::Clean)?;
+                    let cleaned = clean_bytes(engine, &data, &policy, &mut plugins).await;
+                    data = stages.record(ProcessingStage::Clean, started, cleaned)?;
+                    // Cleaning removes objects without reporting which
+                    object_map = None;
+                }
+                Step::Optimize => {
+                    let _permit = engine.concurrency.acquire(Subsystem::Write).await
+                        .stage(ProcessingStage::Optimize)?;
+                    let optimized = engine.writer.optimize_document_mapped(&data).await;
+                    let (optimized, map) = stages.record(ProcessingStage::Optimize, started, optimized)?;
+                    data = optimized;
+                    follow(&mut object_map, &map);
+                }
+                Step::Encrypt(options) => {
+                    let encrypted = engine.security.encrypt_document_with(&data, &options).await;
+                    data = stages.record(ProcessingStage::Encrypt, started, encrypted)?;
+                    sealed = true;
+                }
+                Step::Sign(key) => {
+                    let signed = engine.security.sign_document_with(&data, &key).await;
+                    data = stages.record(ProcessingStage::Sign, started, signed)?;
+                    sealed = true;
+                }
+            }
+        }
+
+        let write = match path {
+            Some(path) => {
+                let started = Instant::now();
+                let _permit = engine.concurrency.acquire(Subsystem::Write).await
+                    .stage(ProcessingStage::Write)?;
+                let saved = if sealed {
+                    engine.retry_policy().run(|| engine.writer.save_bytes(&data, path, source_times)).await
+                } else {
+                    engine.retry_policy().run(|| {
+                        let options = WriteOptions { source_times, ..Default::default() };
+                        engine.writer.save_document(&data, path, Some(options))
+                    }).await
+                };
+                let write = stages.record_retried(ProcessingStage::Write, started, saved)?;
+                if !sealed {
+                    follow(&mut object_map, &write.object_map);
+                }
+                Some(write)
+            }
+            None => None,
+        };
+
+        Ok(PipelineOutput {
+            data,
+            artifacts,
+            stages: stages.reports,
+            plugins,
+            write,
+            object_map,
+        })
+    }
+}
+
+/// Extends `object_map` through a later step's map, or starts it there
+fn follow(object_map: &mut Option<ObjectMap>, later: &ObjectMap) {
+    match object_map {
+        Some(object_map) => object_map.then(later),
+        None => *object_map = Some(later.clone()),
+    }
+}
+
+async fn scan_bytes(
+    engine: &PdfEngine,
+    data: &[u8],
+) -> Result<(Vec<ForensicArtifact>, Vec<PluginReport>), PdfError> {
+    let doc = Document::from_bytes("<memory>".into(), data.to_vec())?;
+    let sinks = engine.artifact_sinks();
+    let mut artifacts = DocumentScanner::new().scan(&doc);
+    artifacts.extend(UsageRightsScanner::new().scan(&doc));
+    sink::publish(&sinks, &artifacts).await;
+
+    let reports = engine.plugins.run_scanners(&doc).await;
+    for report in &reports {
+        sink::publish(&sinks, &report.artifacts).await;
+        artifacts.extend(report.artifacts.iter().cloned());
+    }
+    sink::flush_all(&sinks).await;
+    Ok((artifacts, reports))
+}
+
+async fn clean_bytes(
+    engine: &PdfEngine,
+    data: &[u8],
+    policy: &CleanPolicy,
+    plugins: &mut Vec<PluginReport>,
+) -> Result<Vec<u8>, PdfError> {
+    let data = engine.decode_sandboxed(data).await?;
+    let mut doc = Document::parse("<memory>".into(), &data)?;
+    policy.apply(&mut doc)?;
+
+    let mut cleaned = Vec::with_capacity(data.len());
+    doc.structure.save_to(&mut cleaned)?;
+
+    let processed = engine.core.process_document(&cleaned).await?;
+    engine.plugins.run_cleaners(processed, plugins).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StageOutcome;
+    use lopdf::dictionary;
+
+    fn document_with_javascript() -> Document {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let info_id = doc.add_object(dictionary! {
+            "Producer" => Object::string_literal("Acme PDF"),
+            "Title" => Object::string_literal("Report"),
+        });
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "OpenAction" => dictionary! { "S" => "JavaScript", "JS" => Object::string_literal("app.alert(1)") },
+            "Lang" => Object::string_literal("en-US"),
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+        Document::from_pdf("javascript.pdf".into(), doc)
+    }
+
+    #[test]
+    fn test_clean_policy_removes_selected_entries() {
+        let mut doc = document_with_javascript();
+        CleanPolicy::default().apply(&mut doc).unwrap();
+
+        let root = doc.catalog().unwrap();
+        assert!(!root.has(b"OpenAction"));
+        assert!(!root.has(b"Lang"));
+
+        let info = doc.info().unwrap();
+        assert!(!info.has(b"Producer"));
+        assert!(info.has(b"Title"));
+    }
+
+    #[test]
+    fn test_clean_policy_records_removed_entries() {
+        let mut doc = document_with_javascript();
+        let removed = CleanPolicy::default().apply_recorded(&mut doc).unwrap();
+        assert_eq!(removed, vec!["/Root/OpenAction", "/Root/Lang", "/Info/Producer"]);
+    }
+
+    #[test]
+    fn test_clean_policy_removes_usage_rights() {
+        let extended = crate::antiforensics::scanner::usage_rights::tests::reader_extended();
+        let mut doc = Document::from_pdf("extended.pdf".into(), extended);
+        let removed = CleanPolicy::default().apply_recorded(&mut doc).unwrap();
+        assert_eq!(removed, vec!["/Root/Perms/UR3"]);
+
+        // DocMDP keeps /Perms alive
+        let perms = doc.catalog().unwrap().get(b"Perms").unwrap().as_dict().unwrap();
+        assert!(perms.has(b"DocMDP"));
+        assert!(crate::antiforensics::scanner::usage_rights::detect(&doc.structure).is_empty());
+    }
+
+    #[test]
+    fn test_clean_policy_keeps_disabled_entries() {
+        let mut doc = document_with_javascript();
+        let policy = CleanPolicy {
+            remove_metadata: false,
+            info_keys: Vec::new(),
+            ..Default::default()
+        };
+        policy.apply(&mut doc).unwrap();
+
+        assert!(doc.catalog().unwrap().has(b"Lang"));
+    }
+
+    #[test]
+    fn test_clean_policy_respects_scope() {
+        let mut doc = document_with_javascript();
+        let policy = CleanPolicy {
+            scope: CleaningScope::from_filters(["type=metadata".parse().unwrap()]),
+            ..Default::default()
+        };
+        let removed = policy.apply_recorded(&mut doc).unwrap();
+        assert_eq!(removed, vec!["/Root/Lang", "/Info/Producer"]);
+
+        assert!(doc.catalog().unwrap().has(b"OpenAction"));
+
+        // Document-level entries are on no page
+        let mut doc = document_with_javascript();
+        let policy = CleanPolicy { scope: CleaningScope::from_filters(["pages=1".parse().unwrap()]), ..Default::default() };
+        assert!(policy.apply_recorded(&mut doc).unwrap().is_empty());
+    }
+
+    /// Adds an XMP packet with a title, a creator and a producer
+    fn add_xmp(doc: &mut Document) -> ObjectId {
+        let xmp = doc.structure.add_object(lopdf::Stream::new(dictionary! { "Type" => "Metadata" }, br#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:pdf="http://ns.adobe.com/pdf/1.3/" pdf:Producer="Acme PDF">
+<dc:title><rdf:Alt><rdf:li xml:lang="x-default">Report</rdf:li></rdf:Alt></dc:title>
+<dc:creator><rdf:Seq><rdf:li>Alice</rdf:li></rdf:Seq></dc:creator>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#.to_vec()));
+        doc.catalog_mut().unwrap().set("Metadata", xmp);
+        doc.root_id().unwrap()
+    }
+
+    #[test]
+    fn test_clean_policy_keeps_listed_metadata_fields() {
+        let mut doc = document_with_javascript();
+        let root_id = add_xmp(&mut doc);
+
+        let policy = CleanPolicy { metadata: MetadataPolicy::keep_only(["Title", "Lang"]), ..Default::default() };
+        let removed = policy.apply_recorded(&mut doc).unwrap();
+        assert_eq!(removed, vec![
+            "/Root/OpenAction",
+            "/Root/Metadata/pdf:Producer",
+            "/Root/Metadata/dc:creator",
+            "/Info/Producer",
+        ]);
+        assert!(doc.dictionary(root_id).unwrap().has(b"Lang"));
+        assert!(doc.info().unwrap().has(b"Title"));
+        let xmp = info_xmp::xmp_values(&doc.structure).unwrap();
+        assert_eq!(xmp.len(), 1);
+        assert_eq!(xmp[&info_xmp::MetadataField::Title], "Report");
+
+        // Dropping the last kept field removes the packet
+        let policy = CleanPolicy {
+            metadata: MetadataPolicy::keep_only(["Title", "Lang"]).with_drop(["dc:title"]),
+            ..Default::default()
+        };
+        let removed = policy.apply_recorded(&mut doc).unwrap();
+        assert_eq!(removed, vec!["/Root/Metadata", "/Info/Title"]);
+    }
+
+    #[test]
+    fn test_keep_list_trims_xmp_when_metadata_kept() {
+        let mut doc = document_with_javascript();
+        let root_id = add_xmp(&mut doc);
+
+        let policy = CleanPolicy {
+            remove_metadata: false,
+            metadata: MetadataPolicy::keep_only(["Title"]),
+            ..Default::default()
+        };
+        let removed = policy.apply_recorded(&mut doc).unwrap();
+        assert!(removed.contains(&"/Root/Metadata/dc:creator".to_string()));
+        assert!(removed.contains(&"/Root/Metadata/pdf:Producer".to_string()));
+        assert!(doc.dictionary(root_id).unwrap().has(b"Lang"));
+        let xmp = info_xmp::xmp_values(&doc.structure).unwrap();
+        assert_eq!(xmp.len(), 1);
+        assert_eq!(xmp[&info_xmp::MetadataField::Title], "Report");
+    }
+
+    #[test]
+    fn test_clean_policy_keeps_invoice_and_its_metadata() {
+        use crate::antiforensics::scanner::invoice::tests::{hybrid_invoice, FACTUR_X};
+
+        let mut doc = Document::from_pdf("invoice.pdf".into(), hybrid_invoice(FACTUR_X));
+        let xmp = doc.structure.add_object(lopdf::Stream::new(dictionary! { "Type" => "Metadata" }, b"<x:xmpmeta/>".to_vec()));
+        doc.catalog_mut().unwrap().set("Metadata", xmp);
+
+        let policy = CleanPolicy { attachments: AttachmentPolicy::KeepInvoices, ..Default::default() };
+        let removed = policy.apply_recorded(&mut doc).unwrap();
+        assert_eq!(removed, vec!["/Root/Names/EmbeddedFiles/tool.exe"]);
+        assert!(doc.catalog().unwrap().has(b"Metadata"));
+        assert!(invoice_attachments(&doc.structure)[0].is_compliant());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_runs_steps_in_order() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let sample_pdf = include_bytes!("../tests/data/sample.pdf");
+
+        let output = engine.pipeline(&sample_pdf[..])
+            .scan()
+            .clean(CleanPolicy::default())
+            .optimize()
+            .run()
+            .await
+            .unwrap();
+
+        let order: Vec<ProcessingStage> = output.stages.iter().map(|r| r.stage).collect();
+        assert_eq!(order, vec![ProcessingStage::Scan, ProcessingStage::Clean, ProcessingStage::Optimize]);
+        assert!(output.stages.iter().all(|r| r.outcome == StageOutcome::Completed));
+        assert!(output.write.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_save_hashes_output() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let sample_pdf = include_bytes!("../tests/data/sample.pdf");
+        let dir = tempfile::tempdir().unwrap();
+
+        let output = engine.pipeline(&sample_pdf[..])
+            .clean(CleanPolicy::default())
+            .encrypt(EncryptionConfig::default())
+            .save(dir.path().join("out.pdf"))
+            .await
+            .unwrap();
+
+        assert_eq!(output.stages.last().unwrap().stage, ProcessingStage::Write);
+        assert!(output.write.unwrap().hashes.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_save_keeps_signature() {
+        use crate::security::keys::{KeyAlgorithm, KeyManagementSystem, KeyType};
+        use ed25519_dalek::Verifier;
+        use sha2::{Digest, Sha256, Sha512};
+
+        let engine = PdfEngine::new(None).await.unwrap();
+        let kms = KeyManagementSystem::new(&crate::SecurityConfig::default()).await.unwrap();
+        let key = kms.create_key(KeyType::Signing, KeyAlgorithm::Ed25519).await.unwrap();
+        let verifying_key = key.ed25519_signing_key().unwrap().verifying_key();
+        let sample_pdf = include_bytes!("../tests/data/sample.pdf");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("signed.pdf");
+
+        let output = engine.pipeline(&sample_pdf[..])
+            .clean(CleanPolicy::default())
+            .sign(key)
+            .save(&path)
+            .await
+            .unwrap();
+
+        let written = std::fs::read(&path).unwrap();
+        assert_eq!(written, output.data);
+        let (signed, signature) = written.split_at(written.len() - 64);
+        let signature = ed25519_dalek::Signature::from_slice(signature).unwrap();
+        assert!(verifying_key.verify(&Sha512::digest(signed), &signature).is_ok());
+        assert_eq!(output.write.unwrap().hashes.unwrap().sha256, format!("{:x}", Sha256::digest(&written)));
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_file_copies_input_times() {
+        use crate::antiforensics::cleaner::TimestampPolicy;
+        use std::{fs::FileTimes, time::{Duration, SystemTime}};
+
+        let config = crate::EngineConfig { output_timestamps: TimestampPolicy::CopyFromInput, ..Default::default() };
+        let engine = PdfEngine::new(Some(config)).await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.pdf");
+        std::fs::write(&input, include_bytes!("../tests/data/sample.pdf")).unwrap();
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        std::fs::File::options().write(true).open(&input).unwrap()
+            .set_times(FileTimes::new().set_modified(old).set_accessed(old))
+            .unwrap();
+
+        let output = dir.path().join("out.pdf");
+        engine.pipeline_file(&input).unwrap()
+            .clean(CleanPolicy::default())
+            .save(&output)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::metadata(&output).unwrap().modified().unwrap(), old);
+    }
+}
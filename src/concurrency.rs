@@ -0,0 +1,345 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Instant,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{watchdog::{HoldGuard, Watchdog}, PdfError};
+
+/// Engine subsystems that draw from the shared worker budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    Scan,
+    Clean,
+    Write,
+}
+
+/// Global worker budget and how it is split between subsystems
+///
+/// Each subsystem receives a share of `worker_budget` proportional to its
+/// weight, and always at least one worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcurrencyConfig {
+    pub worker_budget: usize,
+    pub scan_weight: u32,
+    pub clean_weight: u32,
+    pub write_weight: u32,
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            worker_budget: num_cpus::get(),
+            scan_weight: 1,
+            clean_weight: 2,
+            write_weight: 1,
+        }
+    }
+}
+
+/// Per-subsystem worker counts derived from a `ConcurrencyConfig`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubsystemLimits {
+    pub scan: usize,
+    pub clean: usize,
+    pub write: usize,
+}
+
+impl SubsystemLimits {
+    pub fn get(&self, subsystem: Subsystem) -> usize {
+        match subsystem {
+            Subsystem::Scan => self.scan,
+            Subsystem::Clean => self.clean,
+            Subsystem::Write => self.write,
+        }
+    }
+}
+
+impl ConcurrencyConfig {
+    pub fn validate(&self) -> Result<(), PdfError> {
+        if self.worker_budget == 0 {
+            return Err(PdfError::Configuration("worker_budget must be at least 1".into()));
+        }
+        if self.scan_weight + self.clean_weight + self.write_weight == 0 {
+            return Err(PdfError::Configuration("at least one subsystem weight must be non-zero".into()));
+        }
+        Ok(())
+    }
+
+    /// Splits the worker budget by weight, giving every subsystem at least one worker
+    pub fn limits(&self) -> SubsystemLimits {
+        let total_weight = (self.scan_weight + self.clean_weight + self.write_weight).max(1) as usize;
+        let share = |weight: u32| (self.worker_budget * weight as usize / total_weight).max(1);
+
+        SubsystemLimits {
+            scan: share(self.scan_weight),
+            clean: share(self.clean_weight),
+            write: share(self.write_weight),
+        }
+    }
+}
+
+/// Semaphore whose capacity can be changed while permits are held
+struct ResizableLimit {
+    semaphore: Arc<Semaphore>,
+    /// Locked for the whole of a resize, so concurrent resizes apply their
+    /// permit changes one after another
+    capacity: Mutex<usize>,
+    /// Permits a shrink could not retire because jobs held them; each
+    /// permit dropped while this is non-zero pays one off instead of
+    /// returning to the semaphore
+    debt: Arc<AtomicUsize>,
+}
+
+impl ResizableLimit {
+    fn new(capacity: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(capacity)),
+            capacity: Mutex::new(capacity),
+            debt: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        *self.capacity.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn resize(&self, capacity: usize) {
+        let mut current = self.capacity.lock().unwrap_or_else(PoisonError::into_inner);
+        let previous = std::mem::replace(&mut *current, capacity);
+
+        if capacity > previous {
+            // Slots still owed to an earlier shrink are simply no longer owed
+            let added = capacity - previous;
+            let owed = self.debt
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |debt| Some(debt.saturating_sub(added)))
+                .unwrap_or_default();
+            self.semaphore.add_permits(added - owed.min(added));
+        } else if capacity < previous {
+            // Retire free permits now and the rest as running jobs release
+            // theirs, so no job is interrupted by a shrink
+            for _ in capacity..previous {
+                match self.semaphore.try_acquire() {
+                    Ok(permit) => permit.forget(),
+                    Err(_) => {
+                        self.debt.fetch_add(1, Ordering::AcqRel);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A worker slot, tracked by the watchdog until dropped
+#[derive(Debug)]
+pub struct Permit {
+    permit: Option<OwnedSemaphorePermit>,
+    /// Retirements owed by the subsystem's limit
+    debt: Arc<AtomicUsize>,
+    _hold: HoldGuard,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let paid = self.debt
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |debt| debt.checked_sub(1))
+            .is_ok();
+        if let (true, Some(permit)) = (paid, self.permit.take()) {
+            permit.forget();
+        }
+    }
+}
+
+/// Runtime-tunable worker limits shared by the engine's subsystems
+pub struct ConcurrencyLimiter {
+    config: std::sync::RwLock<ConcurrencyConfig>,
+    scan: ResizableLimit,
+    clean: ResizableLimit,
+    write: ResizableLimit,
+    watchdog: Arc<Watchdog>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(config: &ConcurrencyConfig) -> Result<Self, PdfError> {
+        config.validate()?;
+        let limits = config.limits();
+
+        Ok(Self {
+            config: std::sync::RwLock::new(config.clone()),
+            scan: ResizableLimit::new(limits.scan),
+            clean: ResizableLimit::new(limits.clean),
+            write: ResizableLimit::new(limits.write),
+            watchdog: Arc::new(Watchdog::default()),
+        })
+    }
+
+    /// Reports held permits to `watchdog` instead of an unchecked default one
+    pub fn with_watchdog(mut self, watchdog: Arc<Watchdog>) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    pub fn watchdog(&self) -> &Arc<Watchdog> {
+        &self.watchdog
+    }
+
+    fn limit(&self, subsystem: Subsystem) -> &ResizableLimit {
+        match subsystem {
+            Subsystem::Scan => &self.scan,
+            Subsystem::Clean => &self.clean,
+            Subsystem::Write => &self.write,
+        }
+    }
+
+    /// Waits for a worker slot in the given subsystem
+    pub async fn acquire(&self, subsystem: Subsystem) -> Result<Permit, PdfError> {
+        let started = Instant::now();
+        let limit = self.limit(subsystem);
+        let permit = limit.semaphore.clone().acquire_owned().await
+            .map_err(|_| PdfError::Processing(format!("{:?} worker pool closed", subsystem)))?;
+        Ok(Permit {
+            permit: Some(permit),
+            debt: limit.debt.clone(),
+            _hold: self.watchdog.hold(subsystem, started.elapsed()),
+        })
+    }
+
+    /// Free worker slots in the given subsystem, or `None` if its pool is closed
+    pub fn available(&self, subsystem: Subsystem) -> Option<usize> {
+        let semaphore = &self.limit(subsystem).semaphore;
+        (!semaphore.is_closed()).then(|| semaphore.available_permits())
+    }
+
+    /// Current configuration
+    pub fn config(&self) -> ConcurrencyConfig {
+        self.config.read().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// Current per-subsystem limits
+    pub fn limits(&self) -> SubsystemLimits {
+        SubsystemLimits {
+            scan: self.scan.capacity(),
+            clean: self.clean.capacity(),
+            write: self.write.capacity(),
+        }
+    }
+
+    /// Applies a new budget
+    ///
+    /// Shrinking below the permits in use leaves the jobs holding them
+    /// running; their permits are retired as they are dropped.
+    pub fn reconfigure(&self, config: ConcurrencyConfig) -> Result<SubsystemLimits, PdfError> {
+        config.validate()?;
+        let limits = config.limits();
+
+        // Held across the resizes, so concurrent calls leave every subsystem
+        // at the limits of the same configuration
+        let mut current = self.config.write().map_err(|_|
+            PdfError::Processing("Failed to acquire concurrency config lock".to_string()))?;
+        self.scan.resize(limits.scan);
+        self.clean.resize(limits.clean);
+        self.write.resize(limits.write);
+        *current = config;
+
+        Ok(limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(worker_budget: usize) -> ConcurrencyConfig {
+        ConcurrencyConfig {
+            worker_budget,
+            scan_weight: 1,
+            clean_weight: 2,
+            write_weight: 1,
+        }
+    }
+
+    #[test]
+    fn test_budget_split_by_weight() {
+        assert_eq!(config(8).limits(), SubsystemLimits { scan: 2, clean: 4, write: 2 });
+    }
+
+    #[test]
+    fn test_every_subsystem_gets_a_worker() {
+        assert_eq!(config(1).limits(), SubsystemLimits { scan: 1, clean: 1, write: 1 });
+    }
+
+    #[test]
+    fn test_zero_budget_rejected() {
+        assert!(ConcurrencyLimiter::new(&config(0)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure_grows_and_shrinks() {
+        let limiter = ConcurrencyLimiter::new(&config(4)).unwrap();
+        assert_eq!(limiter.limits().clean, 2);
+
+        limiter.reconfigure(config(8)).unwrap();
+        assert_eq!(limiter.limits().clean, 4);
+        let held: Vec<_> = futures::future::join_all(
+            (0..4).map(|_| limiter.acquire(Subsystem::Clean))
+        ).await;
+        assert!(held.iter().all(|p| p.is_ok()));
+
+        limiter.reconfigure(config(4)).unwrap();
+        assert_eq!(limiter.limits().clean, 2);
+        drop(held);
+
+        let _a = limiter.acquire(Subsystem::Clean).await.unwrap();
+        let _b = limiter.acquire(Subsystem::Clean).await.unwrap();
+        let third = tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            limiter.acquire(Subsystem::Clean),
+        ).await;
+        assert!(third.is_err());
+    }
+
+    #[test]
+    fn test_resize_while_held_needs_no_runtime() {
+        let limiter = ConcurrencyLimiter::new(&config(8)).unwrap();
+        let mut held: Vec<_> = (0..4)
+            .map(|_| futures::executor::block_on(limiter.acquire(Subsystem::Clean)).unwrap())
+            .collect();
+
+        limiter.reconfigure(config(4)).unwrap();
+        assert_eq!(limiter.available(Subsystem::Clean), Some(0));
+
+        // Growing back cancels the owed retirements instead of adding slots
+        limiter.reconfigure(config(8)).unwrap();
+        assert_eq!(limiter.available(Subsystem::Clean), Some(0));
+
+        // Dropped permits pay off a shrink before any slot comes back
+        limiter.reconfigure(config(4)).unwrap();
+        held.truncate(2);
+        assert_eq!(limiter.available(Subsystem::Clean), Some(0));
+        held.clear();
+        assert_eq!(limiter.available(Subsystem::Clean), Some(2));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_reconfigures_settle_on_the_last() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(&config(4)).unwrap());
+        let tasks: Vec<_> = (0..64)
+            .map(|i| {
+                let limiter = limiter.clone();
+                tokio::task::spawn_blocking(move || limiter.reconfigure(config(if i % 2 == 0 { 16 } else { 2 })))
+            })
+            .collect();
+        for task in futures::future::join_all(tasks).await {
+            task.unwrap().unwrap();
+        }
+        limiter.reconfigure(config(8)).unwrap();
+
+        assert_eq!(limiter.limits(), config(8).limits());
+        for subsystem in [Subsystem::Scan, Subsystem::Clean, Subsystem::Write] {
+            assert_eq!(limiter.available(subsystem), Some(config(8).limits().get(subsystem)));
+        }
+    }
+}
@@ -0,0 +1,633 @@
+//! Content stream tokenizer and parser
+//!
+//! Splits a decoded page or form XObject content stream into operations,
+//! each carrying its operands and the byte span it occupies in the stream.
+//! Redaction, hidden-text detection, watermarking and normalization work on
+//! these operations instead of matching patterns in raw stream bytes, and
+//! `serialize` turns an edited operation list back into a content stream.
+
+use std::ops::Range;
+
+use super::error::PdfError;
+
+/// Operand preceding a content stream operator
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Real(f64),
+    /// Name without the leading slash, with `#xx` escapes decoded
+    Name(Vec<u8>),
+    /// Literal string, with escapes decoded
+    String(Vec<u8>),
+    /// Hexadecimal string, decoded to bytes
+    HexString(Vec<u8>),
+    Array(Vec<Operand>),
+    Dictionary(Vec<(Vec<u8>, Operand)>),
+}
+
+impl Operand {
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Operand::Integer(i) => Some(*i as f64),
+            Operand::Real(r) => Some(*r),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&[u8]> {
+        match self {
+            Operand::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Bytes of a literal or hexadecimal string
+    pub fn as_string(&self) -> Option<&[u8]> {
+        match self {
+            Operand::String(bytes) | Operand::HexString(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+/// One operator with its operands
+///
+/// Inline images are returned as a single `BI` operation whose operands are
+/// the image dictionary followed by the raw image data as a string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub operator: String,
+    pub operands: Vec<Operand>,
+    /// Bytes from the first operand through the end of the operator
+    pub span: Range<usize>,
+}
+
+impl Operation {
+    pub fn new(operator: &str, operands: Vec<Operand>) -> Self {
+        Self {
+            operator: operator.to_string(),
+            operands,
+            span: 0..0,
+        }
+    }
+
+    /// Numeric operands, skipping any that are not numbers
+    pub fn numbers(&self) -> Vec<f64> {
+        self.operands.iter().filter_map(Operand::as_number).collect()
+    }
+}
+
+/// Parses a whole content stream
+pub fn parse(data: &[u8]) -> Result<Vec<Operation>, PdfError> {
+    ContentParser::new(data).collect()
+}
+
+/// Writes operations back out as a content stream, one per line
+pub fn serialize(operations: &[Operation]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for op in operations {
+        if op.operator == "BI" {
+            write_inline_image(op, &mut out);
+            continue;
+        }
+        for operand in &op.operands {
+            write_operand(operand, &mut out);
+            out.push(b' ');
+        }
+        out.extend_from_slice(op.operator.as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Lazily yields operations from a content stream
+///
+/// Stops after the first error.
+pub struct ContentParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    failed: bool,
+}
+
+enum Token {
+    Operand(Operand),
+    Keyword(Vec<u8>),
+    ArrayStart,
+    ArrayEnd,
+    DictStart,
+    DictEnd,
+}
+
+impl<'a> ContentParser<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0, failed: false }
+    }
+
+    /// Current byte offset into the stream
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn error(&self, message: &str) -> PdfError {
+        PdfError::InvalidStructure(format!("content stream: {} at byte {}", message, self.pos))
+    }
+
+    fn next_operation(&mut self) -> Result<Option<Operation>, PdfError> {
+        let mut operands = Vec::new();
+        let mut start = None;
+
+        loop {
+            self.skip_whitespace();
+            if self.pos >= self.data.len() {
+                if operands.is_empty() {
+                    return Ok(None);
+                }
+                return Err(self.error("operands without an operator"));
+            }
+
+            let token_start = self.pos;
+            start.get_or_insert(token_start);
+
+            match self.next_token()? {
+                Token::Keyword(keyword) => {
+                    if keyword == b"BI" {
+                        return self.inline_image(start.unwrap_or(token_start), operands).map(Some);
+                    }
+                    let operator = String::from_utf8_lossy(&keyword).into_owned();
+                    return Ok(Some(Operation {
+                        operator,
+                        operands,
+                        span: start.unwrap_or(token_start)..self.pos,
+                    }));
+                }
+                token => operands.push(self.finish_operand(token)?),
+            }
+        }
+    }
+
+    /// Completes an operand, reading nested arrays and dictionaries
+    fn finish_operand(&mut self, token: Token) -> Result<Operand, PdfError> {
+        match token {
+            Token::Operand(operand) => Ok(operand),
+            Token::ArrayStart => {
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.pos >= self.data.len() {
+                        return Err(PdfError::UnexpectedEOF);
+                    }
+                    match self.next_token()? {
+                        Token::ArrayEnd => return Ok(Operand::Array(items)),
+                        Token::Keyword(_) => return Err(self.error("operator inside array")),
+                        token => items.push(self.finish_operand(token)?),
+                    }
+                }
+            }
+            Token::DictStart => {
+                let mut entries = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.pos >= self.data.len() {
+                        return Err(PdfError::UnexpectedEOF);
+                    }
+                    let key = match self.next_token()? {
+                        Token::DictEnd => return Ok(Operand::Dictionary(entries)),
+                        Token::Operand(Operand::Name(key)) => key,
+                        _ => return Err(self.error("dictionary key is not a name")),
+                    };
+                    self.skip_whitespace();
+                    let value = match self.next_token()? {
+                        Token::Keyword(_) | Token::DictEnd | Token::ArrayEnd => {
+                            return Err(self.error("dictionary value missing"))
+                        }
+                        token => self.finish_operand(token)?,
+                    };
+                    entries.push((key, value));
+                }
+            }
+            Token::ArrayEnd => Err(self.error("unbalanced ']'")),
+            Token::DictEnd => Err(self.error("unbalanced '>>'")),
+            Token::Keyword(_) => Err(self.error("unexpected operator")),
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, PdfError> {
+        let byte = match self.data.get(self.pos) {
+            Some(&byte) => byte,
+            None => return Err(PdfError::UnexpectedEOF),
+        };
+
+        match byte {
+            b'[' => {
+                self.pos += 1;
+                Ok(Token::ArrayStart)
+            }
+            b']' => {
+                self.pos += 1;
+                Ok(Token::ArrayEnd)
+            }
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                Ok(Token::DictStart)
+            }
+            b'>' if self.data.get(self.pos + 1) == Some(&b'>') => {
+                self.pos += 2;
+                Ok(Token::DictEnd)
+            }
+            b'<' => self.hex_string().map(Token::Operand),
+            b'(' => self.literal_string().map(Token::Operand),
+            b'/' => {
+                self.pos += 1;
+                Ok(Token::Operand(Operand::Name(self.name())))
+            }
+            b'+' | b'-' | b'.' | b'0'..=b'9' => self.number().map(Token::Operand),
+            b')' | b'>' | b'{' | b'}' => Err(self.error("unexpected delimiter")),
+            _ => {
+                let start = self.pos;
+                while self.pos < self.data.len() && is_regular(self.data[self.pos]) {
+                    self.pos += 1;
+                }
+                let word = &self.data[start..self.pos];
+                Ok(match word {
+                    b"true" => Token::Operand(Operand::Boolean(true)),
+                    b"false" => Token::Operand(Operand::Boolean(false)),
+                    b"null" => Token::Operand(Operand::Null),
+                    _ => Token::Keyword(word.to_vec()),
+                })
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&byte) = self.data.get(self.pos) {
+            if is_whitespace(byte) {
+                self.pos += 1;
+            } else if byte == b'%' {
+                while self.pos < self.data.len() && !matches!(self.data[self.pos], b'\r' | b'\n') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Operand, PdfError> {
+        let start = self.pos;
+        if matches!(self.data[self.pos], b'+' | b'-') {
+            self.pos += 1;
+        }
+        let mut real = false;
+        while let Some(&byte) = self.data.get(self.pos) {
+            match byte {
+                b'0'..=b'9' => {}
+                b'.' if !real => real = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+
+        let text = std::str::from_utf8(&self.data[start..self.pos]).unwrap_or_default();
+        // Producers emit "-" or "." for zero often enough to accept them
+        if matches!(text, "+" | "-" | "." | "-." | "+.") {
+            return Ok(Operand::Integer(0));
+        }
+        if real {
+            text.parse().map(Operand::Real).map_err(|_| self.error("malformed number"))
+        } else {
+            match text.parse() {
+                Ok(value) => Ok(Operand::Integer(value)),
+                // Out-of-range integers are treated as reals, as viewers do
+                Err(_) => text.parse().map(Operand::Real).map_err(|_| self.error("malformed number")),
+            }
+        }
+    }
+
+    fn name(&mut self) -> Vec<u8> {
+        let mut name = Vec::new();
+        while let Some(&byte) = self.data.get(self.pos) {
+            if !is_regular(byte) {
+                break;
+            }
+            if byte == b'#' {
+                let hex = self.data.get(self.pos + 1..self.pos + 3)
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok());
+                if let Some(value) = hex {
+                    name.push(value);
+                    self.pos += 3;
+                    continue;
+                }
+            }
+            name.push(byte);
+            self.pos += 1;
+        }
+        name
+    }
+
+    fn literal_string(&mut self) -> Result<Operand, PdfError> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        let mut depth = 1;
+
+        while let Some(&byte) = self.data.get(self.pos) {
+            self.pos += 1;
+            match byte {
+                b'(' => {
+                    depth += 1;
+                    bytes.push(byte);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(Operand::String(bytes));
+                    }
+                    bytes.push(byte);
+                }
+                b'\\' => {
+                    let escaped = match self.data.get(self.pos) {
+                        Some(&escaped) => escaped,
+                        None => break,
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0c),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.data.get(self.pos) {
+                                    Some(&digit @ b'0'..=b'7') => {
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            bytes.push(value as u8);
+                        }
+                        // Line continuation
+                        b'\r' => {
+                            if self.data.get(self.pos) == Some(&b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        other => bytes.push(other),
+                    }
+                }
+                _ => bytes.push(byte),
+            }
+        }
+
+        Err(PdfError::UnexpectedEOF)
+    }
+
+    fn hex_string(&mut self) -> Result<Operand, PdfError> {
+        self.pos += 1;
+        let mut digits = Vec::new();
+
+        while let Some(&byte) = self.data.get(self.pos) {
+            self.pos += 1;
+            match byte {
+                b'>' => {
+                    if digits.len() % 2 == 1 {
+                        digits.push(0);
+                    }
+                    let bytes = digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect();
+                    return Ok(Operand::HexString(bytes));
+                }
+                _ if is_whitespace(byte) => {}
+                _ => match (byte as char).to_digit(16) {
+                    Some(digit) => digits.push(digit as u8),
+                    None => return Err(self.error("invalid hex digit")),
+                },
+            }
+        }
+
+        Err(PdfError::UnexpectedEOF)
+    }
+
+    /// Reads `BI <dict entries> ID <data> EI`
+    fn inline_image(&mut self, start: usize, operands: Vec<Operand>) -> Result<Operation, PdfError> {
+        if !operands.is_empty() {
+            return Err(self.error("operands before BI"));
+        }
+
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.pos >= self.data.len() {
+                return Err(PdfError::UnexpectedEOF);
+            }
+            match self.next_token()? {
+                Token::Keyword(keyword) if keyword == b"ID" => break,
+                Token::Operand(Operand::Name(key)) => {
+                    self.skip_whitespace();
+                    let token = self.next_token()?;
+                    entries.push((key, self.finish_operand(token)?));
+                }
+                _ => return Err(self.error("malformed inline image dictionary")),
+            }
+        }
+
+        // Exactly one whitespace byte separates ID from the data
+        if self.data.get(self.pos).map_or(false, |b| is_whitespace(*b)) {
+            self.pos += 1;
+        }
+        let data_start = self.pos;
+
+        let mut cursor = data_start;
+        while cursor + 2 <= self.data.len() {
+            let preceded = cursor > data_start && is_whitespace(self.data[cursor - 1]);
+            let followed = self.data.get(cursor + 2).map_or(true, |b| is_whitespace(*b));
+            if preceded && followed && &self.data[cursor..cursor + 2] == b"EI" {
+                let data = self.data[data_start..cursor - 1].to_vec();
+                self.pos = cursor + 2;
+                return Ok(Operation {
+                    operator: "BI".to_string(),
+                    operands: vec![Operand::Dictionary(entries), Operand::String(data)],
+                    span: start..self.pos,
+                });
+            }
+            cursor += 1;
+        }
+
+        Err(PdfError::UnexpectedEOF)
+    }
+}
+
+impl<'a> Iterator for ContentParser<'a> {
+    type Item = Result<Operation, PdfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.next_operation() {
+            Ok(op) => op.map(Ok),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b'\0' | b'\t' | b'\n' | 0x0c | b'\r' | b' ')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(byte, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
+
+fn write_operand(operand: &Operand, out: &mut Vec<u8>) {
+    match operand {
+        Operand::Null => out.extend_from_slice(b"null"),
+        Operand::Boolean(value) => out.extend_from_slice(if *value { b"true" } else { b"false" }),
+        Operand::Integer(value) => out.extend_from_slice(value.to_string().as_bytes()),
+        Operand::Real(value) => out.extend_from_slice(value.to_string().as_bytes()),
+        Operand::Name(name) => {
+            out.push(b'/');
+            for &byte in name {
+                if is_regular(byte) && byte != b'#' && (b'!'..=b'~').contains(&byte) {
+                    out.push(byte);
+                } else {
+                    out.extend_from_slice(format!("#{:02X}", byte).as_bytes());
+                }
+            }
+        }
+        Operand::String(bytes) => {
+            out.push(b'(');
+            for &byte in bytes {
+                match byte {
+                    b'(' | b')' | b'\\' => out.extend_from_slice(&[b'\\', byte]),
+                    b'\r' => out.extend_from_slice(b"\\r"),
+                    _ => out.push(byte),
+                }
+            }
+            out.push(b')');
+        }
+        Operand::HexString(bytes) => {
+            out.push(b'<');
+            for byte in bytes {
+                out.extend_from_slice(format!("{:02X}", byte).as_bytes());
+            }
+            out.push(b'>');
+        }
+        Operand::Array(items) => {
+            out.push(b'[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(b' ');
+                }
+                write_operand(item, out);
+            }
+            out.push(b']');
+        }
+        Operand::Dictionary(entries) => {
+            out.extend_from_slice(b"<<");
+            write_entries(entries, out);
+            out.extend_from_slice(b">>");
+        }
+    }
+}
+
+fn write_entries(entries: &[(Vec<u8>, Operand)], out: &mut Vec<u8>) {
+    for (key, value) in entries {
+        write_operand(&Operand::Name(key.clone()), out);
+        out.push(b' ');
+        write_operand(value, out);
+        out.push(b' ');
+    }
+}
+
+fn write_inline_image(op: &Operation, out: &mut Vec<u8>) {
+    out.extend_from_slice(b"BI ");
+    if let Some(Operand::Dictionary(entries)) = op.operands.first() {
+        write_entries(entries, out);
+    }
+    out.extend_from_slice(b"ID ");
+    if let Some(Operand::String(data)) = op.operands.get(1) {
+        out.extend_from_slice(data);
+    }
+    out.extend_from_slice(b"\nEI\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operands_and_spans() {
+        let data = b"q 1 0 0 1 72.5 -10 cm\n/F1 12 Tf";
+        let ops = parse(data).unwrap();
+
+        assert_eq!(ops.len(), 3);
+        assert_eq!(ops[0].operator, "q");
+        assert_eq!(ops[1].operator, "cm");
+        assert_eq!(ops[1].numbers(), vec![1.0, 0.0, 0.0, 1.0, 72.5, -10.0]);
+        assert_eq!(&data[ops[1].span.clone()], b"1 0 0 1 72.5 -10 cm");
+        assert_eq!(ops[2].operands[0], Operand::Name(b"F1".to_vec()));
+        assert_eq!(&data[ops[2].span.clone()], b"/F1 12 Tf");
+    }
+
+    #[test]
+    fn test_strings_and_arrays() {
+        let ops = parse(b"[(a\\(b\\)) -120 <48 6 >] TJ (line\\\ncont\\101) Tj % trailing comment").unwrap();
+
+        assert_eq!(ops[0].operands[0], Operand::Array(vec![
+            Operand::String(b"a(b)".to_vec()),
+            Operand::Integer(-120),
+            Operand::HexString(vec![0x48, 0x60]),
+        ]));
+        assert_eq!(ops[1].operands[0].as_string(), Some(&b"linecontA"[..]));
+    }
+
+    #[test]
+    fn test_inline_image() {
+        let data = b"q BI /W 2 /H 1 /BPC 8 /CS /G ID \x00EI\xff\nEI Q";
+        let ops = parse(data).unwrap();
+
+        assert_eq!(ops.len(), 3);
+        assert_eq!(ops[1].operator, "BI");
+        assert_eq!(ops[1].operands[1], Operand::String(b"\x00EI\xff".to_vec()));
+        assert_eq!(ops[2].operator, "Q");
+    }
+
+    #[test]
+    fn test_marked_content_dictionary() {
+        let ops = parse(b"/Span <</ActualText (x)>> BDC EMC").unwrap();
+        assert_eq!(ops[0].operands[1], Operand::Dictionary(vec![
+            (b"ActualText".to_vec(), Operand::String(b"x".to_vec())),
+        ]));
+    }
+
+    #[test]
+    fn test_dangling_operands_rejected() {
+        assert!(parse(b"BT (unterminated").is_err());
+        assert!(parse(b"1 0 0").is_err());
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let data = b"BT /F#201 12 Tf [(a\\)) 5] TJ ET BI /W 1 ID x EI";
+        let ops = parse(data).unwrap();
+        let reparsed = parse(&serialize(&ops)).unwrap();
+
+        let strip = |ops: Vec<Operation>| ops.into_iter()
+            .map(|op| (op.operator, op.operands))
+            .collect::<Vec<_>>();
+        assert_eq!(strip(reparsed), strip(ops));
+    }
+}
@@ -14,6 +14,7 @@ pub mod error;
 pub mod types;
 pub mod constants;
 pub mod pdf_core;
+pub mod content;
 
 pub use error::PdfError;
 pub use types::*;
@@ -0,0 +1,296 @@
+//! Job lifecycle events for downstream automation
+//!
+//! The engine emits an event when a job is received and when it ends:
+//! scanned, cleaned, failed, or quarantined because the severity gate
+//! withheld its output. Events carry document hashes and summary counts
+//! and go to every registered `EventPublisher`. Publishing is best-effort:
+//! a publisher failure is logged and never fails the job.
+//!
+//! Kafka and NATS publishers are available with the `kafka` and `nats`
+//! features; `ChannelPublisher` delivers events in-process.
+
+use std::sync::{Arc, RwLock};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::broadcast;
+
+use crate::PdfError;
+
+/// Point in a job's lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Received,
+    Scanned,
+    Cleaned,
+    Failed,
+    /// Processing finished but the severity gate withheld the output
+    Quarantined,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Received => "received",
+            EventKind::Scanned => "scanned",
+            EventKind::Cleaned => "cleaned",
+            EventKind::Failed => "failed",
+            EventKind::Quarantined => "quarantined",
+        }
+    }
+}
+
+/// Summary counts of a job
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventStats {
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    /// Findings of a scan, or residual findings after cleaning
+    pub artifacts: usize,
+    pub duration_ms: u64,
+}
+
+/// One lifecycle event, serialized as JSON on the wire
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobEvent {
+    pub job_id: String,
+    pub kind: EventKind,
+    pub timestamp: DateTime<Utc>,
+    /// Input path, when the job read a file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_sha256: Option<String>,
+    pub stats: EventStats,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl JobEvent {
+    pub fn new(job_id: impl Into<String>, kind: EventKind) -> Self {
+        Self {
+            job_id: job_id.into(),
+            kind,
+            timestamp: Utc::now(),
+            source: None,
+            operator: None,
+            input_sha256: None,
+            output_sha256: None,
+            stats: EventStats::default(),
+            error: None,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>, PdfError> {
+        serde_json::to_vec(self).map_err(|e| PdfError::Processing(format!("event serialization failed: {}", e)))
+    }
+}
+
+/// Hex SHA-256 of `data`, as carried in events
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Destination for lifecycle events
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    /// Name used in log messages
+    fn name(&self) -> &str;
+
+    async fn publish(&self, event: &JobEvent) -> Result<(), PdfError>;
+}
+
+/// Registered publishers
+#[derive(Default)]
+pub struct EventBus {
+    publishers: RwLock<Vec<Arc<dyn EventPublisher>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, publisher: Arc<dyn EventPublisher>) {
+        if let Ok(mut publishers) = self.publishers.write() {
+            publishers.push(publisher);
+        }
+    }
+
+    /// Whether any publisher is registered; events are not built otherwise
+    pub fn is_active(&self) -> bool {
+        self.publishers.read().map(|p| !p.is_empty()).unwrap_or(false)
+    }
+
+    /// Sends `event` to every publisher, logging failures
+    pub async fn emit(&self, event: JobEvent) {
+        let publishers = match self.publishers.read() {
+            Ok(publishers) => publishers.clone(),
+            Err(_) => return,
+        };
+        for publisher in publishers {
+            if let Err(e) = publisher.publish(&event).await {
+                log::warn!(
+                    "publishing {} event of job {} to {} failed: {}",
+                    event.kind.as_str(), event.job_id, publisher.name(), e,
+                );
+            }
+        }
+    }
+}
+
+/// Delivers events to in-process subscribers
+///
+/// Events are dropped when nobody is subscribed; slow subscribers miss
+/// the oldest events once `capacity` is exceeded.
+pub struct ChannelPublisher {
+    sender: broadcast::Sender<JobEvent>,
+}
+
+impl ChannelPublisher {
+    pub fn new(capacity: usize) -> Self {
+        Self { sender: broadcast::channel(capacity.max(1)).0 }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[async_trait]
+impl EventPublisher for ChannelPublisher {
+    fn name(&self) -> &str {
+        "channel"
+    }
+
+    async fn publish(&self, event: &JobEvent) -> Result<(), PdfError> {
+        // No receivers is not an error: nobody is listening yet
+        let _ = self.sender.send(event.clone());
+        Ok(())
+    }
+}
+
+/// Publishes events to one Kafka topic, keyed by job ID so a job's
+/// events stay in order within a partition
+#[cfg(feature = "kafka")]
+pub struct KafkaPublisher {
+    producer: rdkafka::producer::FutureProducer,
+    topic: String,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaPublisher {
+    pub fn new(brokers: &str, topic: impl Into<String>) -> Result<Self, PdfError> {
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("message.timeout.ms", "5000")
+            .create()
+            .map_err(|e| PdfError::Configuration(format!("Kafka producer: {}", e)))?;
+        Ok(Self { producer, topic: topic.into() })
+    }
+}
+
+#[cfg(feature = "kafka")]
+#[async_trait]
+impl EventPublisher for KafkaPublisher {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    async fn publish(&self, event: &JobEvent) -> Result<(), PdfError> {
+        let payload = event.to_json()?;
+        let record = rdkafka::producer::FutureRecord::to(&self.topic)
+            .key(&event.job_id)
+            .payload(&payload);
+        self.producer
+            .send(record, std::time::Duration::from_secs(0))
+            .await
+            .map(|_| ())
+            .map_err(|(e, _)| PdfError::Processing(format!("Kafka delivery failed: {}", e)))
+    }
+}
+
+/// Publishes events to NATS on `<prefix>.<kind>`, so subscribers can
+/// pick lifecycle points by subject
+#[cfg(feature = "nats")]
+pub struct NatsPublisher {
+    client: async_nats::Client,
+    prefix: String,
+}
+
+#[cfg(feature = "nats")]
+impl NatsPublisher {
+    pub async fn connect(url: &str, prefix: impl Into<String>) -> Result<Self, PdfError> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| PdfError::Configuration(format!("NATS connection to {}: {}", url, e)))?;
+        Ok(Self { client, prefix: prefix.into() })
+    }
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl EventPublisher for NatsPublisher {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    async fn publish(&self, event: &JobEvent) -> Result<(), PdfError> {
+        let subject = format!("{}.{}", self.prefix, event.kind.as_str());
+        self.client
+            .publish(subject, event.to_json()?.into())
+            .await
+            .map_err(|e| PdfError::Processing(format!("NATS publish failed: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Failing;
+
+    #[async_trait]
+    impl EventPublisher for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        async fn publish(&self, _event: &JobEvent) -> Result<(), PdfError> {
+            Err(PdfError::Processing("broker down".into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bus_delivers_despite_failing_publisher() {
+        let bus = EventBus::new();
+        assert!(!bus.is_active());
+
+        let channel = Arc::new(ChannelPublisher::new(8));
+        let mut events = channel.subscribe();
+        bus.add(Arc::new(Failing));
+        bus.add(channel);
+
+        bus.emit(JobEvent::new("job-1", EventKind::Received)).await;
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.job_id, "job-1");
+        assert_eq!(event.kind, EventKind::Received);
+    }
+
+    #[test]
+    fn test_event_json_omits_unset_fields() {
+        let mut event = JobEvent::new("job-1", EventKind::Cleaned);
+        event.output_sha256 = Some(sha256_hex(b""));
+        let json: serde_json::Value = serde_json::from_slice(&event.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["kind"], "cleaned");
+        assert_eq!(json["output_sha256"], "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert!(json.get("error").is_none());
+    }
+}
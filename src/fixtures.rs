@@ -0,0 +1,281 @@
+//! Synthetic PDFs for testing detection
+//!
+//! Scanners and cleaners have to be tested against the constructs attacks
+//! use, but real malicious samples do not belong in a repository or on a
+//! validation machine. Each `Fixture` is a benign stand-in that carries the
+//! structure a scanner looks for: the script only calls `app.alert`, the
+//! attachment is a line of text, the hidden layer hides a sentence. The
+//! malformed ones are a valid file with one defect added after saving.
+//!
+//! Fixtures are built in memory and are the same byte for byte on every
+//! build, so tests can use them directly and the `fixtures` subcommand can
+//! write them out for checking a deployment.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+
+/// A kind of synthetic PDF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fixture {
+    /// One page of text and nothing else, as the control
+    Clean,
+    /// `/OpenAction` running JavaScript
+    JavaScript,
+    /// A text file in the /EmbeddedFiles name tree
+    EmbeddedFile,
+    /// Text in an optional content group that is off by default
+    HiddenLayer,
+    /// An incremental update that rewrites the Info dictionary
+    IncrementalUpdate,
+    /// A `startxref` that points next to the cross-reference table
+    BrokenXref,
+}
+
+impl Fixture {
+    pub const ALL: &'static [Fixture] = &[
+        Self::Clean,
+        Self::JavaScript,
+        Self::EmbeddedFile,
+        Self::HiddenLayer,
+        Self::IncrementalUpdate,
+        Self::BrokenXref,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Clean => "clean",
+            Self::JavaScript => "javascript",
+            Self::EmbeddedFile => "embedded-file",
+            Self::HiddenLayer => "hidden-layer",
+            Self::IncrementalUpdate => "incremental-update",
+            Self::BrokenXref => "broken-xref",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Clean => "one page of text, nothing to find",
+            Self::JavaScript => "open action running app.alert",
+            Self::EmbeddedFile => "embedded text file",
+            Self::HiddenLayer => "text in an optional content layer that is off",
+            Self::IncrementalUpdate => "incremental update rewriting the document title",
+            Self::BrokenXref => "startxref pointing into the cross-reference table",
+        }
+    }
+
+    pub fn file_name(self) -> String {
+        format!("{}.pdf", self.name())
+    }
+
+    /// The fixture as a file
+    pub fn bytes(self) -> Vec<u8> {
+        let mut builder = Builder::new(self);
+        match self {
+            Self::Clean | Self::IncrementalUpdate | Self::BrokenXref => {}
+            Self::JavaScript => {
+                let action = dictionary! {
+                    "S" => "JavaScript",
+                    "JS" => Object::string_literal("app.alert('pdf_engine fixture');"),
+                };
+                builder.catalog().set("OpenAction", action);
+            }
+            Self::EmbeddedFile => {
+                let mut file = Stream::new(dictionary! { "Type" => "EmbeddedFile" }, b"pdf_engine fixture attachment\n".to_vec());
+                file.dict.set("Params", dictionary! { "Size" => file.content.len() as i64 });
+                let file = builder.doc.add_object(file);
+                let spec = builder.doc.add_object(dictionary! {
+                    "Type" => "Filespec",
+                    "F" => Object::string_literal("note.txt"),
+                    "UF" => Object::string_literal("note.txt"),
+                    "EF" => dictionary! { "F" => file },
+                });
+                let names = dictionary! { "Names" => vec![Object::string_literal("note.txt"), spec.into()] };
+                builder.catalog().set("Names", dictionary! { "EmbeddedFiles" => names });
+            }
+            Self::HiddenLayer => {
+                let layer = builder.doc.add_object(dictionary! { "Type" => "OCG", "Name" => Object::string_literal("Hidden") });
+                builder.catalog().set("OCProperties", dictionary! {
+                    "OCGs" => vec![layer.into()],
+                    "D" => dictionary! { "Order" => vec![layer.into()], "OFF" => vec![layer.into()] },
+                });
+                builder.page_resources().set("Properties", dictionary! { "oc1" => layer });
+                builder.content.extend_from_slice(b"\n/OC /oc1 BDC BT /F1 12 Tf 72 700 Td (This line is on a hidden layer) Tj ET EMC");
+            }
+        }
+
+        let (mut doc, root) = builder.finish();
+        let mut data = Vec::new();
+        doc.save_to(&mut data).expect("writing to memory");
+        match self {
+            Self::IncrementalUpdate => append_update(&mut data, &doc, root),
+            Self::BrokenXref => {
+                let actual = startxref(&data);
+                set_startxref(&mut data, actual - 3);
+            }
+            _ => {}
+        }
+        data
+    }
+
+    /// The fixture as a parsed document
+    pub fn document(self) -> lopdf::Result<Document> {
+        Document::load_mem(&self.bytes())
+    }
+
+    /// Writes the fixture to `dir` as `<name>.pdf`
+    pub fn write(self, dir: &Path) -> io::Result<PathBuf> {
+        let path = dir.join(self.file_name());
+        fs::write(&path, self.bytes())?;
+        Ok(path)
+    }
+}
+
+impl fmt::Display for Fixture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Fixture {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase().replace('_', "-");
+        Self::ALL.iter().copied().find(|fixture| fixture.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|fixture| fixture.name()).collect();
+            format!("unknown fixture `{}`, expected one of: {}", s, names.join(", "))
+        })
+    }
+}
+
+/// Writes every fixture to `dir`, creating it if needed
+pub fn write_all(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    Fixture::ALL.iter().map(|fixture| fixture.write(dir)).collect()
+}
+
+/// A one-page document being put together
+struct Builder {
+    doc: Document,
+    catalog: ObjectId,
+    page: ObjectId,
+    content: Vec<u8>,
+}
+
+impl Builder {
+    fn new(fixture: Fixture) -> Self {
+        let mut doc = Document::with_version("1.7");
+        let pages = doc.new_object_id();
+        let font = doc.add_object(dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica" });
+        let page = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+        });
+        doc.objects.insert(pages, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page.into()], "Count" => 1 }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+        let info = doc.add_object(dictionary! {
+            "Title" => Object::string_literal(format!("Fixture: {}", fixture.name())),
+            "Producer" => Object::string_literal("pdf_engine fixtures"),
+        });
+        doc.trailer.set("Root", catalog);
+        doc.trailer.set("Info", info);
+        let content = format!("BT /F1 12 Tf 72 720 Td (pdf_engine fixture: {}) Tj ET", fixture.name()).into_bytes();
+        Self { doc, catalog, page, content }
+    }
+
+    fn catalog(&mut self) -> &mut lopdf::Dictionary {
+        self.dict(self.catalog)
+    }
+
+    fn page_resources(&mut self) -> &mut lopdf::Dictionary {
+        self.dict(self.page).get_mut(b"Resources").and_then(Object::as_dict_mut).expect("built with resources")
+    }
+
+    fn dict(&mut self, id: ObjectId) -> &mut lopdf::Dictionary {
+        self.doc.get_object_mut(id).and_then(Object::as_dict_mut).expect("built as a dictionary")
+    }
+
+    /// Adds the page content, returning the document and its catalog
+    fn finish(mut self) -> (Document, ObjectId) {
+        let content = self.doc.add_object(Stream::new(dictionary! {}, std::mem::take(&mut self.content)));
+        self.dict(self.page).set("Contents", content);
+        (self.doc, self.catalog)
+    }
+}
+
+/// Appends an update section with a new Info dictionary to a saved file
+fn append_update(data: &mut Vec<u8>, doc: &Document, root: ObjectId) {
+    let prev = startxref(data);
+    let info = doc.max_id + 1;
+    if !data.ends_with(b"\n") {
+        data.push(b'\n');
+    }
+    let object = data.len();
+    data.extend_from_slice(format!("{} 0 obj\n<</Title (Fixture: revised title)>>\nendobj\n", info).as_bytes());
+    let xref = data.len();
+    data.extend_from_slice(format!(
+        "xref\n0 1\n0000000000 65535 f \n{} 1\n{:010} 00000 n \ntrailer\n<</Size {} /Root {} {} R /Info {} 0 R /Prev {}>>\nstartxref\n{}\n%%EOF\n",
+        info, object, info + 1, root.0, root.1, info, prev, xref,
+    ).as_bytes());
+}
+
+/// Offset after the last `startxref` of a file this module saved
+fn startxref(data: &[u8]) -> usize {
+    let keyword = data.windows(9).rposition(|w| w == b"startxref").expect("saved with startxref");
+    String::from_utf8_lossy(&data[keyword + 9..])
+        .split_whitespace()
+        .next()
+        .and_then(|offset| offset.parse().ok())
+        .expect("saved with a startxref offset")
+}
+
+fn set_startxref(data: &mut Vec<u8>, offset: usize) {
+    let keyword = data.windows(9).rposition(|w| w == b"startxref").expect("saved with startxref");
+    data.truncate(keyword);
+    data.extend_from_slice(format!("startxref\n{}\n%%EOF\n", offset).as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antiforensics::{analyzer::xref_integrity, scanner::QuickScanner, ArtifactType};
+
+    #[test]
+    fn test_fixtures_trigger_their_detections() {
+        for &fixture in Fixture::ALL {
+            assert_eq!(fixture.bytes(), fixture.bytes(), "{} is not deterministic", fixture);
+            assert_eq!(fixture.name().parse::<Fixture>().unwrap(), fixture);
+        }
+        assert!("launch".parse::<Fixture>().is_err());
+
+        let found = |fixture: Fixture| -> Vec<ArtifactType> {
+            let report = QuickScanner::new().scan_reader(fixture.bytes().as_slice()).unwrap();
+            report.artifacts().into_iter().map(|artifact| artifact.artifact_type).collect()
+        };
+        assert!(!found(Fixture::Clean).contains(&ArtifactType::JavaScript));
+        assert!(found(Fixture::JavaScript).contains(&ArtifactType::JavaScript));
+        assert!(found(Fixture::EmbeddedFile).contains(&ArtifactType::EmbeddedFile));
+
+        let hidden = Fixture::HiddenLayer.document().unwrap();
+        let properties = hidden.catalog().unwrap().get(b"OCProperties").unwrap().as_dict().unwrap();
+        assert_eq!(properties.get(b"D").unwrap().as_dict().unwrap().get(b"OFF").unwrap().as_array().unwrap().len(), 1);
+
+        let update = Fixture::IncrementalUpdate.bytes();
+        assert_eq!(QuickScanner::new().scan_reader(update.as_slice()).unwrap().incremental_updates, 1);
+        let integrity = xref_integrity::check(&update);
+        assert_eq!(integrity.chain.len(), 2);
+        assert!(integrity.issues.is_empty(), "{:?}", integrity.issues);
+        let revised = Document::load_mem(&update).unwrap();
+        let info = revised.trailer.get(b"Info").and_then(Object::as_reference).unwrap();
+        assert_eq!(revised.get_dictionary(info).unwrap().get(b"Title").unwrap().as_str().unwrap(), b"Fixture: revised title");
+
+        let broken = xref_integrity::check(&Fixture::BrokenXref.bytes());
+        assert!(broken.issues.iter().any(|issue| matches!(issue, xref_integrity::XrefIssue::StartxrefWrong { .. })));
+    }
+}
@@ -0,0 +1,126 @@
+//! Liveness and readiness probes for long-running deployments
+//!
+//! `PdfEngine::liveness` only asks whether the engine can still make
+//! progress: every worker pool must be open and no permit held past the
+//! watchdog threshold. `PdfEngine::readiness`
+//! additionally checks the configuration and that the temp and spill
+//! directories are writable, since jobs fail late and confusingly when
+//! they are not. A server maps the report to `/healthz` and `/readyz`
+//! using `HealthReport::status_code` and the serialized report as body.
+
+use std::{fs, path::Path};
+use serde::Serialize;
+
+use crate::concurrency::{ConcurrencyLimiter, Subsystem};
+
+/// Outcome of a single check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthCheck {
+    pub name: String,
+    pub healthy: bool,
+    pub detail: String,
+}
+
+impl HealthCheck {
+    pub(crate) fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), healthy: true, detail: detail.into() }
+    }
+
+    pub(crate) fn fail(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), healthy: false, detail: detail.into() }
+    }
+}
+
+/// Result of a probe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    pub(crate) fn new(checks: Vec<HealthCheck>) -> Self {
+        Self {
+            healthy: checks.iter().all(|c| c.healthy),
+            checks,
+        }
+    }
+
+    /// HTTP status for a probe endpoint: 200 when healthy, 503 otherwise
+    pub fn status_code(&self) -> u16 {
+        if self.healthy { 200 } else { 503 }
+    }
+
+    /// Checks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &HealthCheck> {
+        self.checks.iter().filter(|c| !c.healthy)
+    }
+}
+
+/// One check per worker pool, and one for permits the watchdog finds stuck
+pub(crate) fn check_worker_pools(limiter: &ConcurrencyLimiter) -> Vec<HealthCheck> {
+    let limits = limiter.limits();
+    let mut checks: Vec<HealthCheck> = [Subsystem::Scan, Subsystem::Clean, Subsystem::Write].into_iter().map(|subsystem| {
+        let name = format!("{:?} workers", subsystem).to_lowercase();
+        match limiter.available(subsystem) {
+            Some(free) => HealthCheck::pass(name, format!("{} of {} free", free, limits.get(subsystem))),
+            None => HealthCheck::fail(name, "worker pool closed"),
+        }
+    }).collect();
+
+    let stuck = limiter.watchdog().stuck();
+    checks.push(if stuck.is_empty() {
+        HealthCheck::pass("stuck permits", "none")
+    } else {
+        let owners: Vec<String> = stuck.iter()
+            .map(|s| format!("{} ({}, {:?})", s.job_id.as_deref().unwrap_or("unscoped"), s.subsystem, s.held_for))
+            .collect();
+        HealthCheck::fail("stuck permits", owners.join(", "))
+    });
+    checks
+}
+
+/// Creates and removes a probe file in `dir`
+pub(crate) fn check_writable(name: &str, dir: &Path) -> HealthCheck {
+    let probe = dir.join(format!(".pdf-engine-health-{}", uuid::Uuid::new_v4()));
+    let result = fs::write(&probe, b"ok").and_then(|_| fs::remove_file(&probe));
+    match result {
+        Ok(()) => HealthCheck::pass(name, format!("{} is writable", dir.display())),
+        Err(e) => HealthCheck::fail(name, format!("{} is not writable: {}", dir.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concurrency::ConcurrencyConfig;
+
+    #[test]
+    fn test_unwritable_directory_fails() {
+        assert!(check_writable("temp dir", &std::env::temp_dir()).healthy);
+        assert!(!check_writable("temp dir", Path::new("/nonexistent/dir")).healthy);
+    }
+
+    #[tokio::test]
+    async fn test_worker_pools_report_free_slots() {
+        let limiter = ConcurrencyLimiter::new(&ConcurrencyConfig {
+            worker_budget: 4,
+            scan_weight: 1,
+            clean_weight: 2,
+            write_weight: 1,
+        }).unwrap();
+        let _held = limiter.acquire(Subsystem::Clean).await.unwrap();
+
+        let checks = check_worker_pools(&limiter);
+        assert!(checks.iter().all(|c| c.healthy));
+        assert_eq!(checks[1].detail, "1 of 2 free");
+    }
+
+    #[test]
+    fn test_status_code() {
+        let report = HealthReport::new(vec![HealthCheck::pass("a", ""), HealthCheck::fail("b", "down")]);
+        assert_eq!(report.status_code(), 503);
+        assert_eq!(report.failures().count(), 1);
+        assert_eq!(HealthReport::new(Vec::new()).status_code(), 200);
+    }
+}
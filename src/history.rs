@@ -0,0 +1,291 @@
+//! Persistent per-run metrics and trend reporting
+//!
+//! `MetricsRegistry` only describes the running process. Each batch run
+//! can additionally be appended as one JSON line to a history file, which
+//! `MetricsHistory::trend` rolls up per day, week or month for capacity
+//! planning. The file is append-only and safe to rotate or truncate.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::antiforensics::{ArtifactType, ForensicArtifact};
+
+/// Metrics of one completed run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    /// Wall-clock duration of the whole run
+    pub duration_ms: u64,
+    pub documents_processed: usize,
+    pub documents_failed: usize,
+    /// Summed per-document processing time
+    pub document_time_ms: u64,
+    /// Artifacts found, by artifact type
+    pub artifacts_by_type: BTreeMap<String, usize>,
+}
+
+impl RunRecord {
+    pub fn total_artifacts(&self) -> usize {
+        self.artifacts_by_type.values().sum()
+    }
+}
+
+/// Accumulates a `RunRecord` while a run is in progress
+#[derive(Debug)]
+pub struct RunRecorder {
+    start: Instant,
+    record: RunRecord,
+}
+
+impl RunRecorder {
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            record: RunRecord {
+                run_id: uuid::Uuid::new_v4().to_string(),
+                started_at: Utc::now(),
+                duration_ms: 0,
+                documents_processed: 0,
+                documents_failed: 0,
+                document_time_ms: 0,
+                artifacts_by_type: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Records a successfully processed document
+    pub fn document(&mut self, elapsed: Duration, artifacts: &[ForensicArtifact]) {
+        self.record.documents_processed += 1;
+        self.record.document_time_ms += elapsed.as_millis() as u64;
+        for artifact in artifacts {
+            *self.record.artifacts_by_type.entry(type_key(&artifact.artifact_type)).or_default() += 1;
+        }
+    }
+
+    /// Records a document that could not be processed
+    pub fn failure(&mut self, elapsed: Duration) {
+        self.record.documents_failed += 1;
+        self.record.document_time_ms += elapsed.as_millis() as u64;
+    }
+
+    pub fn finish(mut self) -> RunRecord {
+        self.record.duration_ms = self.start.elapsed().as_millis() as u64;
+        self.record
+    }
+}
+
+fn type_key(artifact_type: &ArtifactType) -> String {
+    match artifact_type {
+        ArtifactType::Custom(name) => name.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Length of a trend bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrendPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl TrendPeriod {
+    /// First day of the bucket containing `date`
+    fn bucket_start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            TrendPeriod::Day => date,
+            TrendPeriod::Week => date - ChronoDuration::days(date.weekday().num_days_from_monday() as i64),
+            TrendPeriod::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+}
+
+/// Runs rolled up over one period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrendBucket {
+    pub start: NaiveDate,
+    pub runs: usize,
+    pub documents_processed: usize,
+    pub documents_failed: usize,
+    pub artifacts_by_type: BTreeMap<String, usize>,
+    /// Mean processing time per document
+    pub mean_document_ms: f64,
+    /// Longest single run
+    pub max_run_ms: u64,
+}
+
+impl TrendBucket {
+    pub fn total_artifacts(&self) -> usize {
+        self.artifacts_by_type.values().sum()
+    }
+}
+
+/// Append-only JSONL file of run records
+#[derive(Debug, Clone)]
+pub struct MetricsHistory {
+    path: PathBuf,
+}
+
+impl MetricsHistory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends one record as a single line
+    pub fn append(&self, record: &RunRecord) -> io::Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(&line)?;
+        file.sync_data()
+    }
+
+    /// Loads records started at or after `since`, oldest first
+    ///
+    /// A missing file is an empty history. Malformed lines, such as one
+    /// cut short by a crash mid-append, are skipped.
+    pub fn load(&self, since: Option<DateTime<Utc>>) -> io::Result<Vec<RunRecord>> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut records = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<RunRecord>(&line) {
+                Ok(record) if since.map_or(true, |since| record.started_at >= since) => records.push(record),
+                Ok(_) => {}
+                Err(e) => log::warn!("skipping malformed line {} of {}: {}", number + 1, self.path.display(), e),
+            }
+        }
+        records.sort_by_key(|r| r.started_at);
+        Ok(records)
+    }
+
+    /// Rolls records up into consecutive buckets, oldest first
+    pub fn trend(records: &[RunRecord], period: TrendPeriod) -> Vec<TrendBucket> {
+        let mut buckets: BTreeMap<NaiveDate, (TrendBucket, u64)> = BTreeMap::new();
+
+        for record in records {
+            let start = period.bucket_start(record.started_at.date_naive());
+            let (bucket, document_time) = buckets.entry(start).or_insert_with(|| (TrendBucket {
+                start,
+                runs: 0,
+                documents_processed: 0,
+                documents_failed: 0,
+                artifacts_by_type: BTreeMap::new(),
+                mean_document_ms: 0.0,
+                max_run_ms: 0,
+            }, 0));
+
+            bucket.runs += 1;
+            bucket.documents_processed += record.documents_processed;
+            bucket.documents_failed += record.documents_failed;
+            bucket.max_run_ms = bucket.max_run_ms.max(record.duration_ms);
+            for (kind, count) in &record.artifacts_by_type {
+                *bucket.artifacts_by_type.entry(kind.clone()).or_default() += count;
+            }
+            *document_time += record.document_time_ms;
+        }
+
+        buckets.into_values().map(|(mut bucket, document_time)| {
+            let documents = bucket.documents_processed + bucket.documents_failed;
+            if documents > 0 {
+                bucket.mean_document_ms = document_time as f64 / documents as f64;
+            }
+            bucket
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(day: u32, documents: usize, artifacts: usize) -> RunRecord {
+        RunRecord {
+            run_id: uuid::Uuid::new_v4().to_string(),
+            started_at: Utc.with_ymd_and_hms(2025, 6, day, 12, 0, 0).unwrap(),
+            duration_ms: 1000 * day as u64,
+            documents_processed: documents,
+            documents_failed: 0,
+            document_time_ms: 100 * documents as u64,
+            artifacts_by_type: [("Metadata".to_string(), artifacts)].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_append_and_load_round_trip() {
+        let path = std::env::temp_dir().join(format!("history-{}.jsonl", uuid::Uuid::new_v4()));
+        let history = MetricsHistory::new(&path);
+        history.append(&record(2, 5, 3)).unwrap();
+        history.append(&record(1, 4, 1)).unwrap();
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"run_id\":").unwrap();
+
+        let records = history.load(None).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].documents_processed, 4);
+
+        let since = Utc.with_ymd_and_hms(2025, 6, 2, 0, 0, 0).unwrap();
+        assert_eq!(history.load(Some(since)).unwrap().len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_history_is_empty() {
+        let history = MetricsHistory::new("/nonexistent/history.jsonl");
+        assert!(history.load(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_weekly_trend() {
+        // 2025-06-02 is a Monday
+        let records = vec![record(2, 10, 4), record(4, 30, 6), record(10, 5, 1)];
+        let trend = MetricsHistory::trend(&records, TrendPeriod::Week);
+
+        assert_eq!(trend.len(), 2);
+        assert_eq!(trend[0].start, NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        assert_eq!(trend[0].runs, 2);
+        assert_eq!(trend[0].documents_processed, 40);
+        assert_eq!(trend[0].total_artifacts(), 10);
+        assert_eq!(trend[0].mean_document_ms, 100.0);
+        assert_eq!(trend[0].max_run_ms, 4000);
+        assert_eq!(trend[1].runs, 1);
+    }
+
+    #[test]
+    fn test_recorder_counts_artifacts_by_type() {
+        let mut recorder = RunRecorder::start();
+        let artifact = ForensicArtifact { artifact_type: ArtifactType::JavaScript, ..Default::default() };
+        recorder.document(Duration::from_millis(20), &[artifact.clone(), artifact]);
+        recorder.failure(Duration::from_millis(5));
+
+        let record = recorder.finish();
+        assert_eq!(record.documents_processed, 1);
+        assert_eq!(record.documents_failed, 1);
+        assert_eq!(record.document_time_ms, 25);
+        assert_eq!(record.artifacts_by_type["JavaScript"], 2);
+    }
+}
@@ -0,0 +1,176 @@
+//! Loading input documents from disk
+//!
+//! Small files are read with async IO into a single buffer. Files at or
+//! above the mapping threshold are memory-mapped instead, so the parser
+//! works on the page cache and the file is never copied into the heap.
+//!
+//! Mappings are read-only and sized from the file length seen at open.
+//! Truncating a mapped file from another process can still fault the
+//! reader, so callers that cannot rule that out (network filesystems,
+//! shared upload directories) should set `InputConfig::use_mmap` to false.
+
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use memmap2::{Mmap, MmapOptions};
+
+use crate::PdfError;
+
+/// How input files are brought into memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputConfig {
+    /// Memory-map large files; when false every file is read into a buffer
+    pub use_mmap: bool,
+    /// Files of at least this many bytes are memory-mapped
+    pub mmap_threshold: u64,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            use_mmap: true,
+            mmap_threshold: 64 * 1024 * 1024, // 64MB
+        }
+    }
+}
+
+/// Read-only mapping of an input file
+pub struct MappedFile {
+    map: Mmap,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl MappedFile {
+    /// Maps `path` read-only, refusing files whose size changes while mapping
+    fn open(path: PathBuf) -> Result<Self, PdfError> {
+        let file = std::fs::File::open(&path)?;
+        let metadata = file.metadata()?;
+        let len = usize::try_from(metadata.len()).map_err(|_| PdfError::Processing(
+            format!("{} is too large to map on this platform", path.display())
+        ))?;
+
+        // SAFETY: the mapping is read-only and bounded by the length checked
+        // above; the length is re-checked below and by `check_unchanged`.
+        let map = unsafe { MmapOptions::new().len(len).map(&file)? };
+        if file.metadata()?.len() != len as u64 {
+            return Err(PdfError::Processing(format!("{} changed size while being mapped", path.display())));
+        }
+
+        #[cfg(unix)]
+        let _ = map.advise(memmap2::Advice::Sequential);
+
+        Ok(Self {
+            map,
+            path,
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    /// Fails if the file was resized or rewritten since it was mapped
+    ///
+    /// Anything parsed from a mapping whose file changed may mix old and
+    /// new content and must be discarded.
+    pub fn check_unchanged(&self) -> Result<(), PdfError> {
+        let metadata = std::fs::metadata(&self.path)?;
+        if metadata.len() != self.map.len() as u64 || metadata.modified().ok() != self.modified {
+            return Err(PdfError::Processing(format!("{} was modified while mapped", self.path.display())));
+        }
+        Ok(())
+    }
+}
+
+/// Bytes of an input file
+pub enum InputData {
+    Buffered(Vec<u8>),
+    Mapped(MappedFile),
+}
+
+impl InputData {
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, InputData::Mapped(_))
+    }
+
+    /// Fails if a mapped file changed since it was opened; buffered input always passes
+    pub fn check_unchanged(&self) -> Result<(), PdfError> {
+        match self {
+            InputData::Buffered(_) => Ok(()),
+            InputData::Mapped(mapped) => mapped.check_unchanged(),
+        }
+    }
+}
+
+impl Deref for InputData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            InputData::Buffered(data) => data,
+            InputData::Mapped(mapped) => &mapped.map,
+        }
+    }
+}
+
+/// Opens `path`, mapping it when it is large enough to be worth it
+pub async fn open(path: &Path, config: &InputConfig) -> Result<InputData, PdfError> {
+    let path = crate::paths::extended(path);
+    let len = tokio::fs::metadata(path).await?.len();
+
+    if !config.use_mmap || len == 0 || len < config.mmap_threshold {
+        return Ok(InputData::Buffered(tokio::fs::read(path).await?));
+    }
+
+    let path = path.into_owned();
+    tokio::task::spawn_blocking(move || MappedFile::open(path).map(InputData::Mapped))
+        .await
+        .map_err(|e| PdfError::Processing(format!("input mapping task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_small_file_buffered() {
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+        let data = open(Path::new(sample), &InputConfig::default()).await.unwrap();
+
+        assert!(!data.is_mapped());
+        assert!(data.starts_with(b"%PDF"));
+    }
+
+    #[tokio::test]
+    async fn test_large_file_mapped() {
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+        let config = InputConfig { mmap_threshold: 1, ..Default::default() };
+        let data = open(Path::new(sample), &config).await.unwrap();
+
+        assert!(data.is_mapped());
+        assert_eq!(&data[..], &std::fs::read(sample).unwrap()[..]);
+        assert!(data.check_unchanged().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mmap_can_be_disabled() {
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+        let config = InputConfig { use_mmap: false, mmap_threshold: 1 };
+        let data = open(Path::new(sample), &config).await.unwrap();
+
+        assert!(!data.is_mapped());
+    }
+
+    #[tokio::test]
+    async fn test_modified_mapping_detected() {
+        let path = std::env::temp_dir().join(format!("input-{}.pdf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"%PDF-1.7\n%%EOF\n").unwrap();
+        let config = InputConfig { mmap_threshold: 1, ..Default::default() };
+        let data = open(&path, &config).await.unwrap();
+
+        std::fs::write(&path, b"%PDF-1.7\n1 0 obj null endobj\n%%EOF\n").unwrap();
+        assert!(data.check_unchanged().is_err());
+        drop(data);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
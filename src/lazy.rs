@@ -0,0 +1,232 @@
+//! Documents whose objects are parsed on demand
+//!
+//! `LazyDocument` keeps the input bytes, typically a memory mapping from
+//! `input`, together with the cross-reference table, and parses an object
+//! only when it is asked for. Walking a huge document through `objects()`
+//! or `pages()` holds one object at a time instead of the whole graph,
+//! which also suits language bindings that hand objects out one by one.
+//!
+//! Opening reads the file once from start to end, since lopdf loads the
+//! cross-reference table by reading every object, but none of the objects
+//! parsed on that pass are kept.
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+use lopdf::{xref::XrefEntry, Dictionary, Object, ObjectId, Reader};
+
+use crate::antiforensics::analyzer::xref_integrity;
+
+/// Filter lopdf applies to every object it reads
+type FilterFunc = fn(ObjectId, &mut Object) -> Option<(ObjectId, Object)>;
+
+fn keep_nothing(_: ObjectId, _: &mut Object) -> Option<(ObjectId, Object)> {
+    None
+}
+
+/// A document read from `data` one object at a time
+pub struct LazyDocument<'a> {
+    reader: Reader<'a>,
+    /// Most recently unpacked object stream; compressed objects are
+    /// usually read in order, so one is enough
+    object_stream: Mutex<Option<(u32, BTreeMap<ObjectId, Object>)>>,
+}
+
+impl<'a> LazyDocument<'a> {
+    /// Reads the cross-reference table and trailer of `data`, refusing a
+    /// looping /Prev chain as `xref_integrity::load_checked` does
+    pub fn open(data: &'a [u8]) -> Result<Self, lopdf::Error> {
+        xref_integrity::ensure_parsable(data)?;
+        let filter: FilterFunc = keep_nothing;
+        let skeleton = Reader { buffer: data, document: lopdf::Document::new() }.read(Some(filter))?;
+        Ok(Self {
+            reader: Reader { buffer: data, document: skeleton },
+            object_stream: Mutex::new(None),
+        })
+    }
+
+    pub fn trailer(&self) -> &Dictionary {
+        &self.reader.document.trailer
+    }
+
+    pub fn version(&self) -> &str {
+        &self.reader.document.version
+    }
+
+    /// Objects in the cross-reference table, free entries excluded
+    pub fn len(&self) -> usize {
+        self.ids().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids().next().is_none()
+    }
+
+    /// Parses one object
+    pub fn object(&self, id: ObjectId) -> Result<Object, lopdf::Error> {
+        match self.reader.document.reference_table.get(id.0) {
+            Some(XrefEntry::Normal { .. }) => self.reader.get_object(id),
+            Some(&XrefEntry::Compressed { container, .. }) if id.1 == 0 => self.compressed_object(container, id),
+            _ => Err(lopdf::Error::ObjectNotFound),
+        }
+    }
+
+    /// Byte offset of an object, or of the object stream holding it
+    pub fn offset(&self, id: ObjectId) -> Option<u64> {
+        let table = &self.reader.document.reference_table;
+        let entry = match table.get(id.0)? {
+            XrefEntry::Compressed { container, .. } => table.get(*container)?,
+            entry => entry,
+        };
+        match entry {
+            XrefEntry::Normal { offset, .. } => Some(u64::from(*offset)),
+            _ => None,
+        }
+    }
+
+    /// Follows references until a direct object is reached
+    pub fn resolve(&self, object: Object) -> Result<Object, lopdf::Error> {
+        let mut current = object;
+        // Bounded to survive reference cycles
+        for _ in 0..32 {
+            match current {
+                Object::Reference(id) => current = self.object(id)?,
+                direct => return Ok(direct),
+            }
+        }
+        Err(lopdf::Error::ReferenceLimit)
+    }
+
+    fn compressed_object(&self, container: u32, id: ObjectId) -> Result<Object, lopdf::Error> {
+        let mut cached = self.object_stream.lock().unwrap_or_else(|e| e.into_inner());
+        if cached.as_ref().map(|(number, _)| *number) != Some(container) {
+            let mut stream = self.reader.get_object((container, 0))?.as_stream()?.clone();
+            let objects = lopdf::ObjectStream::new(&mut stream)?.objects;
+            *cached = Some((container, objects));
+        }
+        cached.as_ref()
+            .and_then(|(_, objects)| objects.get(&id).cloned())
+            .ok_or(lopdf::Error::ObjectNotFound)
+    }
+
+    /// IDs of every object, in object number order
+    pub fn ids(&self) -> impl Iterator<Item = ObjectId> + '_ {
+        self.reader.document.reference_table.entries.iter().filter_map(|(&number, entry)| match *entry {
+            XrefEntry::Normal { generation, .. } => Some((number, generation)),
+            XrefEntry::Compressed { .. } => Some((number, 0)),
+            _ => None,
+        })
+    }
+
+    /// Every object, parsed as the iterator reaches it
+    ///
+    /// Objects that fail to parse are yielded with their error, so one
+    /// damaged object does not end the walk.
+    pub fn objects(&self) -> impl Iterator<Item = (ObjectId, Result<Object, lopdf::Error>)> + '_ {
+        self.ids().map(|id| (id, self.object(id)))
+    }
+
+    /// Every page in document order, walking the page tree as it goes
+    pub fn pages(&self) -> Pages<'_, 'a> {
+        let root = self.trailer().get(b"Root").and_then(Object::as_reference).ok();
+        let pages = root
+            .and_then(|root| self.object(root).ok())
+            .and_then(|catalog| catalog.as_dict().ok()?.get(b"Pages").ok().cloned());
+        Pages { doc: self, stack: vec![pages.into_iter().collect::<Vec<_>>().into_iter()], seen: HashSet::new(), number: 0 }
+    }
+}
+
+/// A page reached by `LazyDocument::pages`
+#[derive(Debug, Clone)]
+pub struct LazyPage {
+    /// 1-based page number
+    pub number: u32,
+    pub id: ObjectId,
+    pub dict: Dictionary,
+}
+
+/// Depth-first walk of a page tree, parsing one node at a time
+pub struct Pages<'d, 'a> {
+    doc: &'d LazyDocument<'a>,
+    /// Kids still to visit at each level of the tree
+    stack: Vec<std::vec::IntoIter<Object>>,
+    /// Nodes already visited; a node reached twice would repeat or loop
+    seen: HashSet<ObjectId>,
+    number: u32,
+}
+
+impl Iterator for Pages<'_, '_> {
+    type Item = LazyPage;
+
+    fn next(&mut self) -> Option<LazyPage> {
+        loop {
+            let next = match self.stack.last_mut()?.next() {
+                Some(next) => next,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            let Ok(id) = next.as_reference() else { continue };
+            if !self.seen.insert(id) {
+                continue;
+            }
+            let dict = match self.doc.object(id) {
+                Ok(Object::Dictionary(dict)) => dict,
+                Ok(Object::Stream(stream)) => stream.dict,
+                _ => continue,
+            };
+            let is_node = match dict.get(b"Type").and_then(Object::as_name) {
+                Ok(kind) => kind == b"Pages",
+                Err(_) => dict.has(b"Kids"),
+            };
+            if is_node {
+                let kids = dict.get(b"Kids").and_then(Object::as_array).cloned().unwrap_or_default();
+                self.stack.push(kids.into_iter());
+            } else {
+                self.number += 1;
+                return Some(LazyPage { number: self.number, id, dict });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn test_objects_and_pages_parsed_on_demand() {
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let mut kids = Vec::new();
+        for page in 0..3 {
+            let content = doc.add_object(Stream::new(dictionary! {}, format!("BT ({}) Tj ET", page).into_bytes()));
+            kids.push(doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "Contents" => content }).into());
+        }
+        // A kid listed twice is visited once
+        kids.push(kids[0].clone());
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => 3 }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+
+        let lazy = LazyDocument::open(&data).unwrap();
+        assert_eq!(lazy.len(), doc.objects.len());
+        let pages: Vec<LazyPage> = lazy.pages().collect();
+        assert_eq!(pages.iter().map(|p| p.number).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(pages.iter().map(|p| p.id).collect::<Vec<_>>(), doc.page_iter().collect::<Vec<_>>());
+
+        let content = pages[1].dict.get(b"Contents").unwrap().as_reference().unwrap();
+        assert_eq!(lazy.object(content).unwrap().as_stream().unwrap().content, b"BT (1) Tj ET");
+        let ids: Vec<ObjectId> = lazy.objects()
+            .map(|(id, object)| {
+                assert!(object.is_ok(), "object {:?} failed to parse", id);
+                id
+            })
+            .collect();
+        assert_eq!(ids, doc.objects.keys().copied().collect::<Vec<_>>());
+        assert!(lazy.object((99, 0)).is_err());
+    }
+}
@@ -1,13 +1,68 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 use uuid::Uuid;
 
+use antiforensics::{
+    analyzer::{
+        correlation::{CampaignReport, CorrelationAnalyzer, CorrelationConfig},
+        xref_integrity,
+        ObjectNumberingAnalyzer,
+        TrailerAnalyzer,
+        XrefIntegrityAnalyzer,
+    },
+    cleaner::SyntaxNormalizer,
+    report::ObjectMap,
+    scanner::{sink::{self, ArtifactSink}, DocumentScanner, SyntaxScanner, UsageRightsScanner},
+    ForensicArtifact,
+    RiskLevel,
+};
+use concurrency::{ConcurrencyLimiter, Subsystem, SubsystemLimits};
+use events::{EventKind, JobEvent};
+use security::operator::{Operation, Operator};
+
+pub mod aggregate;
+pub mod antiforensics;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod backup;
+pub mod builder;
+pub mod concurrency;
 pub mod core;
+pub mod events;
+pub mod fixtures;
+pub mod health;
+pub mod history;
+pub mod input;
+pub mod lazy;
 pub mod security;
 pub mod verification;
 pub mod writer;
 pub mod metrics;
+pub mod paths;
+pub mod pdf_date;
+pub mod plugins;
+pub mod profile;
+pub mod quota;
+#[cfg(feature = "render")]
+pub mod raster;
+pub mod retention;
+pub mod retry;
+pub mod sandbox;
+pub mod shutdown;
+pub mod spill;
+pub mod staging;
+pub mod store;
+pub mod usage;
 pub mod utils;
+pub mod visual_diff;
+pub mod watchdog;
 
 #[derive(Error, Debug)]
 pub enum PdfError {
@@ -31,6 +86,210 @@ pub enum PdfError {
     
     #[error("Compression error: {0}")]
     Compression(String),
+
+    #[error("PDF parse error: {0}")]
+    Parse(#[from] lopdf::Error),
+
+    /// Job refused or cancelled because the engine is shutting down
+    #[error("Engine shutting down: {0}")]
+    Shutdown(String),
+
+    /// The engine's operator lacks a role for the requested operation
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    /// The client is over its request rate or byte quota
+    #[error("Rate limited ({client}): {reason}")]
+    RateLimited {
+        client: String,
+        reason: String,
+        /// When the request could succeed; `None` if it never can
+        retry_after: Option<Duration>,
+    },
+
+    /// Another error annotated with where it occurred
+    #[error("{context}: {source}")]
+    Context {
+        context: ErrorContext,
+        #[source]
+        source: Box<PdfError>,
+    },
+
+    /// The watchdog failed a job holding a worker permit past its threshold
+    #[error("Job stalled: {0}")]
+    Stalled(String),
+
+    /// A cleaning transaction could not be rolled back
+    #[error("Backup error: {0}")]
+    Backup(#[from] backup::BackupError),
+}
+
+/// Pipeline stage in which an error or outcome occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProcessingStage {
+    Scan,
+    Validation,
+    Security,
+    Clean,
+    Gate,
+    Optimize,
+    Compress,
+    Encrypt,
+    Sign,
+    Write,
+}
+
+impl ProcessingStage {
+    /// Stages run by `PdfEngine::process_document`, in order
+    pub const PIPELINE: [ProcessingStage; 8] = [
+        ProcessingStage::Validation,
+        ProcessingStage::Security,
+        ProcessingStage::Clean,
+        ProcessingStage::Gate,
+        ProcessingStage::Optimize,
+        ProcessingStage::Compress,
+        ProcessingStage::Encrypt,
+        ProcessingStage::Sign,
+    ];
+}
+
+impl fmt::Display for ProcessingStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProcessingStage::Scan => "scan",
+            ProcessingStage::Validation => "validation",
+            ProcessingStage::Security => "security",
+            ProcessingStage::Clean => "clean",
+            ProcessingStage::Gate => "gate",
+            ProcessingStage::Optimize => "optimize",
+            ProcessingStage::Compress => "compress",
+            ProcessingStage::Encrypt => "encrypt",
+            ProcessingStage::Sign => "sign",
+            ProcessingStage::Write => "write",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Location details attached to an error
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub stage: Option<ProcessingStage>,
+    pub object_id: Option<lopdf::ObjectId>,
+    /// Byte offset into the input document
+    pub offset: Option<u64>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(stage) = self.stage {
+            parts.push(format!("{} stage", stage));
+        }
+        if let Some((num, gen)) = self.object_id {
+            parts.push(format!("object {} {} R", num, gen));
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("offset {}", offset));
+        }
+        if parts.is_empty() {
+            parts.push("unknown location".to_string());
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+impl PdfError {
+    /// Returns the attached context, if any
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            PdfError::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    pub fn stage(&self) -> Option<ProcessingStage> {
+        self.context().and_then(|c| c.stage)
+    }
+
+    pub fn object_id(&self) -> Option<lopdf::ObjectId> {
+        self.context().and_then(|c| c.object_id)
+    }
+
+    pub fn offset(&self) -> Option<u64> {
+        self.context().and_then(|c| c.offset)
+    }
+
+    /// HTTP status a server should answer with for this error
+    pub fn status_code(&self) -> u16 {
+        match self.root_cause() {
+            PdfError::RateLimited { .. } => 429,
+            PdfError::PermissionDenied(_) => 403,
+            PdfError::Shutdown(_) => 503,
+            PdfError::Parse(_) | PdfError::Validation(_) => 422,
+            PdfError::Configuration(_) => 400,
+            PdfError::Backup(backup::BackupError::NotFound(_)) => 404,
+            _ => 500,
+        }
+    }
+
+    /// Returns the underlying error without context
+    pub fn root_cause(&self) -> &PdfError {
+        match self {
+            PdfError::Context { source, .. } => source.root_cause(),
+            other => other,
+        }
+    }
+
+    /// Records the stage; an already recorded (innermost) stage is kept
+    pub fn with_stage(self, stage: ProcessingStage) -> Self {
+        self.annotate(|c| { c.stage.get_or_insert(stage); })
+    }
+
+    /// Records the object the error relates to
+    pub fn with_object(self, id: lopdf::ObjectId) -> Self {
+        self.annotate(|c| { c.object_id.get_or_insert(id); })
+    }
+
+    /// Records the byte offset the error relates to
+    pub fn with_offset(self, offset: u64) -> Self {
+        self.annotate(|c| { c.offset.get_or_insert(offset); })
+    }
+
+    fn annotate(self, apply: impl FnOnce(&mut ErrorContext)) -> Self {
+        match self {
+            PdfError::Context { mut context, source } => {
+                apply(&mut context);
+                PdfError::Context { context, source }
+            }
+            other => {
+                let mut context = ErrorContext::default();
+                apply(&mut context);
+                PdfError::Context { context, source: Box::new(other) }
+            }
+        }
+    }
+}
+
+/// Adds error context to `Result<T, PdfError>`
+pub trait ResultExt<T> {
+    fn stage(self, stage: ProcessingStage) -> Result<T, PdfError>;
+    fn object(self, id: lopdf::ObjectId) -> Result<T, PdfError>;
+    fn offset(self, offset: u64) -> Result<T, PdfError>;
+}
+
+impl<T> ResultExt<T> for Result<T, PdfError> {
+    fn stage(self, stage: ProcessingStage) -> Result<T, PdfError> {
+        self.map_err(|e| e.with_stage(stage))
+    }
+
+    fn object(self, id: lopdf::ObjectId) -> Result<T, PdfError> {
+        self.map_err(|e| e.with_object(id))
+    }
+
+    fn offset(self, offset: u64) -> Result<T, PdfError> {
+        self.map_err(|e| e.with_offset(offset))
+    }
 }
 
 pub struct ProcessingOptions {
@@ -39,6 +298,19 @@ pub struct ProcessingOptions {
     pub encrypt: bool,
     pub validate: bool,
     pub sign: bool,
+    /// Policy applied to artifacts that remain after cleaning
+    pub severity_gate: Option<SeverityGate>,
+    /// Leave every page rendering as it was: optimization (image
+    /// downsampling, font subsetting, content rewriting) is skipped even
+    /// when `optimize` is set, so only metadata and structure are cleaned.
+    /// Compression stays on, as it is lossless.
+    pub preserve_visual: bool,
+    /// Parse the final output back and check its structure before
+    /// returning it; a failure names the first broken object and offset
+    pub verify_output: bool,
+    /// Re-scan the cleaned document and clean it again while findings
+    /// remain; cleans once when `None`
+    pub verify_clean: Option<CleanVerification>,
 }
 
 impl Default for ProcessingOptions {
@@ -49,6 +321,106 @@ impl Default for ProcessingOptions {
             encrypt: false,
             validate: true,
             sign: false,
+            severity_gate: Some(SeverityGate::default()),
+            preserve_visual: false,
+            verify_output: false,
+            verify_clean: None,
+        }
+    }
+}
+
+/// When the clean stage counts its output as clean
+#[derive(Debug, Clone, Copy)]
+pub struct CleanVerification {
+    /// Findings at or above this level send the output through another round
+    pub threshold: RiskLevel,
+    /// Clean-and-rescan rounds before giving up
+    pub max_rounds: usize,
+}
+
+impl Default for CleanVerification {
+    fn default() -> Self {
+        Self {
+            threshold: RiskLevel::Medium,
+            max_rounds: 3,
+        }
+    }
+}
+
+/// What to do when residual artifacts reach the gate threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateMode {
+    /// Abort and emit no output
+    FailClosed,
+    /// Record the residual artifacts and continue
+    FailOpen,
+}
+
+/// Severity threshold checked against artifacts remaining after cleaning
+#[derive(Debug, Clone, Copy)]
+pub struct SeverityGate {
+    pub threshold: RiskLevel,
+    pub mode: GateMode,
+}
+
+impl Default for SeverityGate {
+    fn default() -> Self {
+        Self {
+            threshold: RiskLevel::Critical,
+            mode: GateMode::FailClosed,
+        }
+    }
+}
+
+/// Outcome of evaluating the severity gate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateOutcome {
+    /// No residual artifact reached the threshold
+    Passed,
+    /// Threshold reached in fail-open mode; output was still emitted
+    Warned,
+    /// Threshold reached in fail-closed mode; output was withheld
+    Blocked,
+}
+
+/// Gate decision recorded in the processing result
+#[derive(Debug, Clone)]
+pub struct GateDecision {
+    pub threshold: RiskLevel,
+    pub mode: GateMode,
+    pub outcome: GateOutcome,
+    pub residual_artifacts: usize,
+    pub highest_residual: RiskLevel,
+    /// Locations of residual artifacts at or above the threshold
+    pub blocking_locations: Vec<String>,
+}
+
+impl SeverityGate {
+    /// Evaluates the gate against artifacts found in the cleaned document
+    pub fn evaluate(&self, residual: &[ForensicArtifact]) -> GateDecision {
+        let highest_residual = residual.iter()
+            .map(|a| a.risk_level)
+            .max_by_key(|level| level.severity())
+            .unwrap_or(RiskLevel::None);
+
+        let blocking_locations: Vec<String> = residual.iter()
+            .filter(|a| a.risk_level.severity() >= self.threshold.severity())
+            .map(|a| a.location.clone())
+            .collect();
+
+        let outcome = match (blocking_locations.is_empty(), self.mode) {
+            (true, _) => GateOutcome::Passed,
+            (false, GateMode::FailOpen) => GateOutcome::Warned,
+            (false, GateMode::FailClosed) => GateOutcome::Blocked,
+        };
+
+        GateDecision {
+            threshold: self.threshold,
+            mode: self.mode,
+            outcome,
+            residual_artifacts: residual.len(),
+            highest_residual,
+            blocking_locations,
         }
     }
 }
@@ -60,6 +432,139 @@ pub struct ProcessingResult {
     pub compression_ratio: f64,
     pub processing_time: std::time::Duration,
     pub status: ProcessingStatus,
+    /// Severity gate decision, when a gate was configured
+    pub gate_decision: Option<GateDecision>,
+    /// Outcome of every pipeline stage, in execution order
+    pub stages: Vec<StageReport>,
+    /// Invocations of registered scanner and cleaner plugins
+    pub plugins: Vec<plugins::PluginReport>,
+    /// Retries of transient IO failures while reading the input
+    pub retries: u32,
+    /// CPU time, peak memory and IO of the job
+    pub resource_usage: usage::ResourceUsage,
+    /// Objects of the cleaned document and what optimization and
+    /// compression made of them; absent when neither ran
+    pub object_map: Option<ObjectMap>,
+    /// Clean-and-rescan rounds run by the clean stage; more than one only
+    /// with `ProcessingOptions::verify_clean`
+    pub clean_rounds: usize,
+}
+
+impl ProcessingResult {
+    /// Returns the report for a stage
+    pub fn stage(&self, stage: ProcessingStage) -> Option<&StageReport> {
+        self.stages.iter().find(|report| report.stage == stage)
+    }
+}
+
+/// Scan outcome of one file in a batch
+#[derive(Debug)]
+pub struct FileScanResult {
+    pub path: PathBuf,
+    pub artifacts: Vec<ForensicArtifact>,
+    /// Why the file could not be scanned
+    pub error: Option<String>,
+    /// Retries of transient IO failures while reading the file
+    pub retries: u32,
+}
+
+/// Per-file results of a batch scan and the findings shared across it
+#[derive(Debug)]
+pub struct BatchScanReport {
+    pub files: Vec<FileScanResult>,
+    pub campaign: CampaignReport,
+    /// Run metrics, also appended to `EngineConfig::history_path` when set
+    pub run: history::RunRecord,
+}
+
+/// Parsed file and what scanning it found
+struct ScannedFile {
+    doc: antiforensics::types::Document,
+    artifacts: Vec<ForensicArtifact>,
+    retries: u32,
+    input_bytes: u64,
+    /// Only computed when events are published
+    input_sha256: Option<String>,
+}
+
+/// How a single pipeline stage ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StageOutcome {
+    Completed,
+    /// Disabled by the processing options
+    Skipped,
+    /// Completed, but with a reduced guarantee
+    Degraded(String),
+    Failed(String),
+    /// An earlier stage stopped the pipeline
+    NotReached,
+}
+
+#[derive(Debug, Clone)]
+pub struct StageReport {
+    pub stage: ProcessingStage,
+    pub outcome: StageOutcome,
+    pub duration: Duration,
+    /// Retries of transient IO failures within the stage
+    pub retries: u32,
+}
+
+/// Collects stage reports while a document moves through the pipeline
+#[derive(Debug, Default)]
+struct StageLog {
+    reports: Vec<StageReport>,
+}
+
+impl StageLog {
+    /// Records the result of a stage and tags any error with it
+    fn record<T>(&mut self, stage: ProcessingStage, started: Instant, result: Result<T, PdfError>) -> Result<T, PdfError> {
+        let outcome = match &result {
+            Ok(_) => StageOutcome::Completed,
+            Err(e) => StageOutcome::Failed(e.root_cause().to_string()),
+        };
+        self.reports.push(StageReport { stage, outcome, duration: started.elapsed(), retries: 0 });
+        result.stage(stage)
+    }
+
+    /// Records a stage whose IO was retried under a `RetryPolicy`
+    fn record_retried<T>(&mut self, stage: ProcessingStage, started: Instant, retried: retry::Retried<T>) -> Result<T, PdfError> {
+        let result = self.record(stage, started, retried.result);
+        if let Some(report) = self.reports.last_mut() {
+            report.retries = retried.retries;
+        }
+        result
+    }
+
+    fn skip(&mut self, stage: ProcessingStage) {
+        self.reports.push(StageReport {
+            stage,
+            outcome: StageOutcome::Skipped,
+            duration: Duration::ZERO,
+            retries: 0,
+        });
+    }
+
+    /// Replaces the outcome of an already recorded stage
+    fn set_outcome(&mut self, stage: ProcessingStage, outcome: StageOutcome) {
+        if let Some(report) = self.reports.iter_mut().rev().find(|r| r.stage == stage) {
+            report.outcome = outcome;
+        }
+    }
+
+    /// Appends `NotReached` for pipeline stages that never ran
+    fn finish(mut self) -> Vec<StageReport> {
+        for stage in ProcessingStage::PIPELINE {
+            if !self.reports.iter().any(|r| r.stage == stage) {
+                self.reports.push(StageReport {
+                    stage,
+                    outcome: StageOutcome::NotReached,
+                    duration: Duration::ZERO,
+                    retries: 0,
+                });
+            }
+        }
+        self.reports
+    }
 }
 
 #[derive(Debug)]
@@ -75,6 +580,40 @@ pub struct EngineConfig {
     pub buffer_size: usize,
     pub temp_dir: std::path::PathBuf,
     pub metrics_enabled: bool,
+    /// Worker budget shared by scanning, cleaning and writing
+    pub concurrency: concurrency::ConcurrencyConfig,
+    /// When large decoded data moves from memory to temp files
+    pub spill: spill::SpillConfig,
+    /// How `process_file` and `scan_file` read their input
+    pub input: input::InputConfig,
+    /// JSONL file that batch runs append their metrics to
+    pub history_path: Option<PathBuf>,
+    /// Retries for transient failures while reading input and writing output
+    pub retry: retry::RetryPolicy,
+    /// Filesystem times given to written output files
+    pub output_timestamps: antiforensics::cleaner::TimestampPolicy,
+    /// Refuse every operation until an operator is set with [`PdfEngine::with_operator`]
+    pub require_operator: bool,
+    /// Request rate and byte limits by client, checked before jobs are admitted
+    pub quotas: quota::QuotaConfig,
+    /// Algorithms used to hash written output
+    pub hash_algorithms: Vec<antiforensics::hash::HashAlgorithm>,
+    /// `.patdb` file replacing the built-in detection patterns
+    pub pattern_database: Option<PathBuf>,
+    /// Where inputs are backed up before cleaning so runs can be rolled back
+    pub backups: backup::BackupMode,
+    /// When worker permits count as stuck and what happens to their jobs
+    pub watchdog: watchdog::WatchdogConfig,
+    /// Age and size limits of backup, quarantine and cache directories, and how often they are purged
+    ///
+    /// Background purges have no operator, so an interval is refused with `require_operator`.
+    pub retention: retention::RetentionConfig,
+    /// Minimum confidence of scan findings, and whether weaker ones are dropped or annotated
+    pub analyzer: antiforensics::analyzer::AnalyzerConfig,
+    /// Decode streams in a confined worker process before cleaning; off when `None`
+    ///
+    /// Programs enabling this must handle [`sandbox::WORKER_ARG`] at startup.
+    pub sandbox: Option<sandbox::SandboxConfig>,
 }
 
 impl Default for EngineConfig {
@@ -84,10 +623,49 @@ impl Default for EngineConfig {
             buffer_size: 8 * 1024 * 1024, // 8MB
             temp_dir: std::env::temp_dir(),
             metrics_enabled: true,
+            concurrency: concurrency::ConcurrencyConfig::default(),
+            spill: spill::SpillConfig::default(),
+            input: input::InputConfig::default(),
+            history_path: None,
+            retry: retry::RetryPolicy::default(),
+            output_timestamps: antiforensics::cleaner::TimestampPolicy::Untouched,
+            require_operator: false,
+            quotas: quota::QuotaConfig::default(),
+            hash_algorithms: antiforensics::hash::HashAlgorithm::DEFAULT.to_vec(),
+            pattern_database: None,
+            backups: backup::BackupMode::Off,
+            watchdog: watchdog::WatchdogConfig::default(),
+            retention: retention::RetentionConfig::default(),
+            analyzer: antiforensics::analyzer::AnalyzerConfig::default(),
+            sandbox: None,
         }
     }
 }
 
+impl EngineConfig {
+    /// Rejects settings the engine cannot run with
+    pub fn validate(&self) -> Result<(), PdfError> {
+        self.concurrency.validate()?;
+        self.quotas.validate()?;
+        if self.buffer_size == 0 {
+            return Err(PdfError::Configuration("buffer_size must be non-zero".into()));
+        }
+        if self.hash_algorithms.is_empty() {
+            return Err(PdfError::Configuration("hash_algorithms must name at least one algorithm".into()));
+        }
+        if !(0.0..=1.0).contains(&self.analyzer.min_confidence) {
+            return Err(PdfError::Configuration("analyzer.min_confidence must be within 0..=1".into()));
+        }
+        if self.spill.threshold > self.spill.memory_budget {
+            return Err(PdfError::Configuration(format!(
+                "spill threshold ({}) exceeds the memory budget ({})",
+                self.spill.threshold, self.spill.memory_budget,
+            )));
+        }
+        Ok(())
+    }
+}
+
 pub struct PdfEngine {
     config: EngineConfig,
     core: Arc<core::CoreSystem>,
@@ -95,11 +673,38 @@ pub struct PdfEngine {
     security: Arc<security::SecuritySystem>,
     verification: Arc<verification::VerificationSystem>,
     metrics: Arc<metrics::MetricsRegistry>,
+    concurrency: Arc<ConcurrencyLimiter>,
+    plugins: Arc<plugins::PluginRegistry>,
+    jobs: Arc<shutdown::JobTracker>,
+    quotas: Arc<quota::QuotaTracker>,
+    events: Arc<events::EventBus>,
+    /// `EngineConfig::retry`, replaced when a followed configuration reloads
+    retry: Arc<std::sync::RwLock<retry::RetryPolicy>>,
+    /// Receivers of scan findings as each scanner reports them
+    artifact_sinks: Arc<std::sync::RwLock<Vec<Arc<dyn ArtifactSink>>>>,
+    /// Pre-clean backups and the cleaning transaction log; `None` when off
+    backups: Option<Arc<backup::BackupManager>>,
+    /// Purges stores under `EngineConfig::retention`, periodically when an interval is set
+    retention: Arc<retention::RetentionManager>,
+    /// Whom operations run on behalf of; unattributed when unset
+    operator: Option<Arc<Operator>>,
 }
 
 impl PdfEngine {
     pub async fn new(config: Option<EngineConfig>) -> Result<Self, PdfError> {
         let config = config.unwrap_or_default();
+        let watchdog = Arc::new(watchdog::Watchdog::new(config.watchdog.clone()));
+        watchdog.start();
+        let concurrency = Arc::new(ConcurrencyLimiter::new(&config.concurrency)?.with_watchdog(watchdog));
+        let quotas = Arc::new(quota::QuotaTracker::new(config.quotas.clone())?);
+        spill::global_budget().lower_limit(config.spill.memory_budget);
+        if let Some(path) = &config.pattern_database {
+            antiforensics::patterns::PatternDatabase::load(path)
+                .and_then(antiforensics::patterns::install)
+                .map_err(|e| PdfError::Configuration(format!("pattern database {}: {}", path.display(), e)))?;
+        }
+        let backups = backup::BackupManager::from_mode(&config.backups)?.map(Arc::new);
+        let retention = Arc::new(retention::RetentionManager::new(config.retention.clone()));
         let metrics = if config.metrics_enabled {
             Arc::new(metrics::MetricsRegistry::new()?)
         } else {
@@ -111,50 +716,297 @@ impl PdfEngine {
         let security = Arc::new(security::SecuritySystem::new(&config, metrics.clone()).await?);
         let verification = Arc::new(verification::VerificationSystem::new(&config, metrics.clone()).await?);
 
-        Ok(Self {
+        let engine = Self {
+            retry: Arc::new(std::sync::RwLock::new(config.retry.clone())),
             config,
             core,
             writer,
             security,
             verification,
             metrics,
+            concurrency,
+            plugins: Arc::new(plugins::PluginRegistry::new()),
+            jobs: Arc::new(shutdown::JobTracker::new()),
+            quotas,
+            events: Arc::new(events::EventBus::new()),
+            artifact_sinks: Arc::new(std::sync::RwLock::new(Vec::new())),
+            backups,
+            retention,
+            operator: None,
+        };
+
+        // Scheduled purges run on nobody's behalf, so they are authorized and
+        // audited like an unattributed `purge_retained`
+        if engine.retention.is_scheduled() {
+            engine.authorize(Operation::Shred, "<retention schedule>").await.map_err(|e| match e {
+                PdfError::PermissionDenied(_) => PdfError::Configuration(
+                    "retention.interval_minutes cannot be used with require_operator; \
+                     purge with purge_retained on behalf of an operator with the shred role".into(),
+                ),
+                e => e,
+            })?;
+            engine.retention.start();
+        }
+        Ok(engine)
+    }
+
+    /// Handle on the same engine that runs operations on behalf of `operator`
+    ///
+    /// Subsystems, plugins and jobs are shared with `self`; only the
+    /// identity differs. Each operation is checked against the operator's
+    /// roles and the decision is written to the audit log.
+    pub fn with_operator(&self, operator: Operator) -> Self {
+        Self {
+            config: self.config.clone(),
+            core: self.core.clone(),
+            writer: self.writer.clone(),
+            security: self.security.clone(),
+            verification: self.verification.clone(),
+            metrics: self.metrics.clone(),
+            concurrency: self.concurrency.clone(),
+            plugins: self.plugins.clone(),
+            jobs: self.jobs.clone(),
+            quotas: self.quotas.clone(),
+            events: self.events.clone(),
+            retry: self.retry.clone(),
+            artifact_sinks: self.artifact_sinks.clone(),
+            backups: self.backups.clone(),
+            retention: self.retention.clone(),
+            operator: Some(Arc::new(operator)),
+        }
+    }
+
+    pub fn operator(&self) -> Option<&Operator> {
+        self.operator.as_deref()
+    }
+
+    /// Checks that the operator may run `operation` and audits the decision
+    pub(crate) async fn authorize(&self, operation: Operation, resource: &str) -> Result<(), PdfError> {
+        let decision = match self.operator.as_deref() {
+            Some(operator) => operator.authorize(operation),
+            None if self.config.require_operator => Err(PdfError::PermissionDenied(format!(
+                "{} requires an operator", operation,
+            ))),
+            None => Ok(()),
+        };
+        self.security.record_operation(self.operator.as_deref(), operation, resource, decision.is_ok()).await?;
+        if let Err(e) = &decision {
+            log::warn!("refused {} of {}: {}", operation, resource, e);
+        }
+        decision
+    }
+
+    /// Publishes a lifecycle event when any publisher is registered
+    ///
+    /// `fill` only runs when the event is sent, so hashing in it is free
+    /// for engines without publishers.
+    async fn emit(&self, job_id: &str, kind: EventKind, source: Option<&Path>, fill: impl FnOnce(&mut JobEvent)) {
+        if !self.events.is_active() {
+            return;
+        }
+        let mut event = JobEvent::new(job_id, kind);
+        event.source = source.map(|p| p.display().to_string());
+        event.operator = self.operator.as_ref().map(|o| o.id.clone());
+        fill(&mut event);
+        self.events.emit(event).await;
+    }
+
+    /// Counts a request of `bytes` input bytes against the operator's quota
+    pub(crate) fn admit(&self, bytes: u64) -> Result<(), PdfError> {
+        let client = self.operator.as_deref().map_or(quota::ANONYMOUS_CLIENT, |o| o.id.as_str());
+        self.quotas.admit(client, bytes).map_err(|e| {
+            log::warn!("{}", e);
+            e
         })
     }
 
+    /// Authorizes everything `options` will do to a document
+    async fn authorize_processing(&self, options: &ProcessingOptions, resource: &str) -> Result<(), PdfError> {
+        self.authorize(Operation::Clean, resource).await?;
+        // Signing uses the same key material as encryption
+        if options.encrypt || options.sign {
+            self.authorize(Operation::Encrypt, resource).await?;
+        }
+        Ok(())
+    }
+
     pub async fn process_document(
         &self,
         input: &[u8],
         options: Option<ProcessingOptions>
     ) -> Result<ProcessingResult, PdfError> {
+        let options = options.unwrap_or_default();
+        self.authorize_processing(&options, "<memory>").await?;
+        self.admit(input.len() as u64)?;
+        self.run_processing(input, options, None).await
+    }
+
+    /// The exact input of the cleaning run `cleaning_id`, as it was before the clean
+    ///
+    /// `cleaning_id` is the `document_id` of the run's [`ProcessingResult`].
+    /// Needs `EngineConfig::backups`; with on-disk backups, runs of
+    /// earlier engine instances can be rolled back too.
+    pub async fn rollback(&self, cleaning_id: &str) -> Result<Vec<u8>, PdfError> {
+        self.authorize(Operation::Clean, cleaning_id).await?;
+        let backups = self.backups.as_ref()
+            .ok_or_else(|| PdfError::Configuration("rollback needs backups enabled in EngineConfig::backups".into()))?;
+        Ok(backups.rollback(cleaning_id)?)
+    }
+
+    /// Cleaning transactions recorded by the backup manager, oldest first
+    pub fn cleaning_transactions(&self) -> Vec<backup::Transaction> {
+        self.backups.as_ref().map(|backups| backups.transactions()).unwrap_or_default()
+    }
+
+    /// Applies `EngineConfig::retention` now, outside the background schedule
+    ///
+    /// Needs the shred role, since purged files cannot be recovered.
+    pub async fn purge_retained(&self) -> Result<Vec<retention::PurgeReport>, PdfError> {
+        self.authorize(Operation::Shred, "<retention>").await?;
+        let retention = self.retention.clone();
+        tokio::task::spawn_blocking(move || retention.purge())
+            .await
+            .map_err(|e| PdfError::Processing(format!("retention purge failed: {}", e)))?
+            .map_err(PdfError::from)
+    }
+
+    async fn run_processing(
+        &self,
+        input: &[u8],
+        options: ProcessingOptions,
+        source: Option<&Path>,
+    ) -> Result<ProcessingResult, PdfError> {
+        let mut job = self.jobs.begin()?;
         let start_time = std::time::Instant::now();
         let document_id = Uuid::new_v4().to_string();
-        let options = options.unwrap_or_default();
+        job.label(&document_id);
+        let input_sha256 = self.events.is_active().then(|| events::sha256_hex(input));
+        self.emit(&document_id, EventKind::Received, source, |event| {
+            event.input_sha256 = input_sha256.clone();
+            event.stats.input_bytes = input.len() as u64;
+        }).await;
+        // Without a backup the clean could not be undone, so do not start it
+        if let Some(backups) = &self.backups {
+            backups.begin(&document_id, source, input)?;
+        }
+        let mut stages = StageLog::default();
+        let mut plugin_reports = Vec::new();
+        let mut object_map = None;
+        let mut clean_rounds = 0;
 
         // Track active jobs
         self.metrics.active_operations.inc();
         let _timer = self.metrics.processing_duration.start_timer();
 
-        let result = self.internal_process_document(input, &document_id, &options).await;
+        let meter = usage::UsageMeter::new();
+        meter.add_read(input.len() as u64);
+        let result = job.run(meter.measure(self.internal_process_document(
+            input,
+            &document_id,
+            &options,
+            &mut stages,
+            &mut plugin_reports,
+            &mut object_map,
+            &mut clean_rounds,
+        ))).await;
+        if let Ok((data, _)) = &result {
+            meter.add_written(data.len() as u64);
+        }
+        let resource_usage = meter.usage();
 
         // Update metrics
         self.metrics.active_operations.dec();
         self.metrics.documents_processed.inc();
         self.metrics.bytes_processed.inc_by(input.len() as f64);
 
+        let stages = stages.finish();
+
+        let (kind, output, residual, error) = match &result {
+            Ok((_, Some(decision))) if decision.outcome == GateOutcome::Blocked => {
+                (EventKind::Quarantined, None, decision.blocking_locations.len(), None)
+            }
+            Ok((data, decision)) => {
+                (EventKind::Cleaned, Some(data), decision.as_ref().map_or(0, |d| d.residual_artifacts), None)
+            }
+            Err(e) => (EventKind::Failed, None, 0, Some(e.to_string())),
+        };
+        if let Some(backups) = &self.backups {
+            let recorded = match (output, &error) {
+                (Some(data), _) => backups.commit(&document_id, data),
+                (None, Some(error)) => backups.fail(&document_id, error),
+                (None, None) => backups.fail(&document_id, "output withheld by the residual-risk gate"),
+            };
+            if let Err(e) = recorded {
+                log::warn!("could not record cleaning transaction {}: {}", document_id, e);
+            }
+        }
+        self.emit(&document_id, kind, source, |event| {
+            event.input_sha256 = input_sha256;
+            event.output_sha256 = output.map(|data| events::sha256_hex(data));
+            event.stats = events::EventStats {
+                input_bytes: input.len() as u64,
+                output_bytes: output.map_or(0, |data| data.len() as u64),
+                artifacts: residual,
+                duration_ms: start_time.elapsed().as_millis() as u64,
+            };
+            event.error = error;
+        }).await;
+
         match result {
-            Ok(processed_data) => {
+            Ok((_, Some(decision))) if decision.outcome == GateOutcome::Blocked => {
+                self.metrics.processing_errors.inc();
+                Ok(ProcessingResult {
+                    document_id,
+                    processed_bytes: 0,
+                    compression_ratio: 1.0,
+                    processing_time: start_time.elapsed(),
+                    status: ProcessingStatus::Failed(format!(
+                        "{} residual artifact(s) at or above {:?} after cleaning; output withheld",
+                        decision.blocking_locations.len(),
+                        decision.threshold,
+                    )),
+                    gate_decision: Some(decision),
+                    stages,
+                    plugins: plugin_reports,
+                    retries: 0,
+                    resource_usage,
+                    object_map: None,
+                    clean_rounds,
+                })
+            }
+            Ok((processed_data, gate_decision)) => {
                 let compression_ratio = if input.len() > 0 {
                     processed_data.len() as f64 / input.len() as f64
                 } else {
                     1.0
                 };
 
+                let degraded: Vec<String> = stages.iter()
+                    .filter_map(|report| match &report.outcome {
+                        StageOutcome::Degraded(reason) => Some(format!("{}: {}", report.stage, reason)),
+                        _ => None,
+                    })
+                    .collect();
+
+                let status = if degraded.is_empty() {
+                    ProcessingStatus::Success
+                } else {
+                    ProcessingStatus::PartialSuccess(degraded.join("; "))
+                };
+
                 Ok(ProcessingResult {
                     document_id,
                     processed_bytes: processed_data.len(),
                     compression_ratio,
                     processing_time: start_time.elapsed(),
-                    status: ProcessingStatus::Success,
+                    status,
+                    gate_decision,
+                    stages,
+                    plugins: plugin_reports,
+                    retries: 0,
+                    resource_usage,
+                    object_map,
+                    clean_rounds,
                 })
             }
             Err(e) => {
@@ -165,6 +1017,13 @@ impl PdfEngine {
                     compression_ratio: 1.0,
                     processing_time: start_time.elapsed(),
                     status: ProcessingStatus::Failed(e.to_string()),
+                    gate_decision: None,
+                    stages,
+                    plugins: plugin_reports,
+                    retries: 0,
+                    resource_usage,
+                    object_map: None,
+                    clean_rounds,
                 })
             }
         }
@@ -175,50 +1034,675 @@ impl PdfEngine {
         input: &[u8],
         document_id: &str,
         options: &ProcessingOptions,
-    ) -> Result<Vec<u8>, PdfError> {
+        stages: &mut StageLog,
+        plugin_reports: &mut Vec<plugins::PluginReport>,
+        object_map: &mut Option<ObjectMap>,
+        clean_rounds: &mut usize,
+    ) -> Result<(Vec<u8>, Option<GateDecision>), PdfError> {
         // Step 1: Validation
         if options.validate {
-            let verification_result = self.verification.verify_document(input).await?;
-            if !verification_result.is_valid {
-                return Err(PdfError::Validation(verification_result.message));
-            }
+            let started = Instant::now();
+            let verified = self.verification.verify_document(input).await.and_then(|result| {
+                if result.is_valid {
+                    Ok(())
+                } else {
+                    Err(PdfError::Validation(result.message))
+                }
+            });
+            stages.record(ProcessingStage::Validation, started, verified)?;
+        } else {
+            stages.skip(ProcessingStage::Validation);
         }
 
         // Step 2: Security checks
-        let security_result = self.security.check_document(input).await?;
-        if !security_result.is_secure {
-            return Err(PdfError::Security(security_result.message));
+        let started = Instant::now();
+        let checked = self.security.check_document(input).await.and_then(|result| {
+            if result.is_secure {
+                Ok(())
+            } else {
+                Err(PdfError::Security(result.message))
+            }
+        });
+        stages.record(ProcessingStage::Security, started, checked)?;
+
+        // Step 3: Core processing and syntax normalization, followed by registered cleaners
+        let started = Instant::now();
+        let cleaned = match self.concurrency.acquire(Subsystem::Clean).await {
+            Ok(_permit) => self.clean_verified(input, options.verify_clean.as_ref(), plugin_reports).await,
+            Err(e) => Err(e),
+        };
+        let (mut processed_data, rounds, remaining) = stages.record(ProcessingStage::Clean, started, cleaned)?;
+        *clean_rounds = rounds;
+        if !remaining.is_empty() {
+            stages.set_outcome(ProcessingStage::Clean, StageOutcome::Degraded(format!(
+                "{} finding(s) remain after {} cleaning round(s)",
+                remaining.len(),
+                rounds,
+            )));
         }
 
-        // Step 3: Core processing
-        let mut processed_data = self.core.process_document(input).await?;
+        // Step 3a: Registered scanners over the cleaned document
+        let mut plugin_artifacts = Vec::new();
+        if self.plugins.has_scanners() {
+            let started = Instant::now();
+            let scanned = match self.concurrency.acquire(Subsystem::Scan).await {
+                Ok(_permit) => match antiforensics::types::Document::from_bytes(
+                    PathBuf::from("<memory>"),
+                    processed_data.clone(),
+                ) {
+                    Ok(doc) => Ok(self.plugins.run_scanners(&doc).await),
+                    Err(e) => Err(PdfError::from(e)),
+                },
+                Err(e) => Err(e),
+            };
+            let reports = stages.record(ProcessingStage::Scan, started, scanned)?;
+
+            let failed: Vec<&str> = reports.iter()
+                .filter(|r| matches!(r.outcome, StageOutcome::Failed(_)))
+                .map(|r| r.name.as_str())
+                .collect();
+            if !failed.is_empty() {
+                stages.set_outcome(ProcessingStage::Scan, StageOutcome::Degraded(format!(
+                    "scanner plugin(s) failed: {}",
+                    failed.join(", "),
+                )));
+            }
+
+            plugin_artifacts = reports.iter().flat_map(|r| r.artifacts.iter().cloned()).collect();
+            plugin_reports.extend(reports);
+        }
+
+        // Step 3b: Severity gate on what the cleaning left behind. Runs
+        // before encryption so the residual content is still inspectable.
+        let gate_decision = match options.severity_gate {
+            Some(gate) => {
+                let started = Instant::now();
+                let evaluated = match self.concurrency.acquire(Subsystem::Scan).await {
+                    Ok(_permit) => DocumentScanner::new()
+                        .scan_bytes(&processed_data)
+                        .map(|mut residual| {
+                            residual.extend(plugin_artifacts.iter().cloned());
+                            antiforensics::sort_artifacts(&mut residual);
+                            gate.evaluate(&residual)
+                        })
+                        .map_err(PdfError::from),
+                    Err(e) => Err(e),
+                };
+                let decision = stages.record(ProcessingStage::Gate, started, evaluated)?;
+
+                match decision.outcome {
+                    GateOutcome::Passed => {}
+                    GateOutcome::Warned => stages.set_outcome(ProcessingStage::Gate, StageOutcome::Degraded(format!(
+                        "{} residual artifact(s) at or above {:?} remain",
+                        decision.blocking_locations.len(),
+                        decision.threshold,
+                    ))),
+                    GateOutcome::Blocked => {
+                        stages.set_outcome(ProcessingStage::Gate, StageOutcome::Failed(format!(
+                            "{} residual artifact(s) at or above {:?}; output withheld",
+                            decision.blocking_locations.len(),
+                            decision.threshold,
+                        )));
+                        return Ok((Vec::new(), Some(decision)));
+                    }
+                }
+                Some(decision)
+            }
+            None => {
+                stages.skip(ProcessingStage::Gate);
+                None
+            }
+        };
+
+        let write_permit = self.concurrency.acquire(Subsystem::Write).await
+            .stage(ProcessingStage::Optimize)?;
 
         // Step 4: Optimization
-        if options.optimize {
-            processed_data = self.writer.optimize_document(&processed_data).await?;
+        if options.optimize && !options.preserve_visual {
+            let started = Instant::now();
+            let optimized = self.writer.optimize_document_mapped(&processed_data).await;
+            let (optimized, map) = stages.record(ProcessingStage::Optimize, started, optimized)?;
+            processed_data = optimized;
+            *object_map = Some(map);
+        } else {
+            stages.skip(ProcessingStage::Optimize);
         }
 
         // Step 5: Compression
         if options.compress {
-            processed_data = self.writer.compress_document(&processed_data).await?;
+            let started = Instant::now();
+            let compressed = self.writer.compress_document_mapped(&processed_data).await;
+            let (compressed, map) = stages.record(ProcessingStage::Compress, started, compressed)?;
+            processed_data = compressed;
+            match object_map {
+                Some(object_map) => object_map.then(&map),
+                None => *object_map = Some(map),
+            }
+        } else {
+            stages.skip(ProcessingStage::Compress);
         }
+        drop(write_permit);
 
         // Step 6: Encryption
         if options.encrypt {
-            processed_data = self.security.encrypt_document(&processed_data).await?;
+            let started = Instant::now();
+            let encrypted = self.security.encrypt_document(&processed_data).await;
+            processed_data = stages.record(ProcessingStage::Encrypt, started, encrypted)?;
+        } else {
+            stages.skip(ProcessingStage::Encrypt);
         }
 
         // Step 7: Digital Signature
         if options.sign {
-            processed_data = self.security.sign_document(&processed_data).await?;
+            let started = Instant::now();
+            let signed = self.security.sign_document(&processed_data).await;
+            processed_data = stages.record(ProcessingStage::Sign, started, signed)?;
+        } else {
+            stages.skip(ProcessingStage::Sign);
+        }
+
+        // Never hand out bytes the engine cannot read back itself
+        if options.verify_output {
+            writer::roundtrip::verify(&processed_data).stage(ProcessingStage::Write)?;
+        }
+
+        Ok((processed_data, gate_decision))
+    }
+
+    /// Cleans `input`, then with `verify` re-scans the output and cleans
+    /// it again while findings at or above the threshold remain and the
+    /// last round still changed it
+    ///
+    /// Returns the output, the rounds run and the findings at or above
+    /// the threshold left after the last one.
+    async fn clean_verified(
+        &self,
+        input: &[u8],
+        verify: Option<&CleanVerification>,
+        plugin_reports: &mut Vec<plugins::PluginReport>,
+    ) -> Result<(Vec<u8>, usize, Vec<ForensicArtifact>), PdfError> {
+        let mut data = self.clean_input(input, plugin_reports).await?;
+        let mut rounds = 1;
+        let Some(verify) = verify else {
+            return Ok((data, rounds, Vec::new()));
+        };
+        loop {
+            let remaining: Vec<ForensicArtifact> = DocumentScanner::new().scan_bytes(&data)?
+                .into_iter()
+                .filter(|a| a.risk_level.severity() >= verify.threshold.severity())
+                .collect();
+            if remaining.is_empty() || rounds >= verify.max_rounds.max(1) {
+                return Ok((data, rounds, remaining));
+            }
+            rounds += 1;
+            let recleaned = self.clean_input(&data, plugin_reports).await?;
+            if recleaned == data {
+                return Ok((data, rounds, remaining));
+            }
+            data = recleaned;
+        }
+    }
+
+    /// Clean stage of `process_document`: sandboxed decoding when
+    /// configured, core processing, syntax normalization, then the
+    /// registered cleaners
+    async fn clean_input(
+        &self,
+        input: &[u8],
+        plugin_reports: &mut Vec<plugins::PluginReport>,
+    ) -> Result<Vec<u8>, PdfError> {
+        let input = self.decode_sandboxed(input).await?;
+        let data = self.core.process_document(&input).await?;
+        // Comments, padding and trailing data hide payloads outside the object graph
+        let (data, report) = SyntaxNormalizer::new().normalize(&data)
+            .map_err(|e| PdfError::Processing(e.to_string()))?;
+        log::debug!(
+            "syntax normalization removed {} comment(s) and {} byte(s)",
+            report.comments_removed, report.bytes_removed,
+        );
+        self.plugins.run_cleaners(data, plugin_reports).await
+    }
+
+    /// Decodes every filtered stream of `input` in a confined worker process
+    ///
+    /// Returns `input` unchanged unless [`EngineConfig::sandbox`] is set.
+    /// Streams the worker fails on are emptied; failing to start the
+    /// worker at all fails the job rather than decoding in-process.
+    pub(crate) async fn decode_sandboxed<'a>(&self, input: &'a [u8]) -> Result<Cow<'a, [u8]>, PdfError> {
+        let Some(config) = self.config.sandbox.clone() else {
+            return Ok(Cow::Borrowed(input));
+        };
+        let input = input.to_vec();
+        tokio::task::spawn_blocking(move || -> Result<Cow<'static, [u8]>, PdfError> {
+            let mut doc = lopdf::Document::load_mem(&input)?;
+            let mut decoder = sandbox::SandboxedDecoder::spawn(config)
+                .map_err(|e| PdfError::Security(format!("sandbox worker: {}", e)))?;
+            let report = sandbox::decode_document(&mut doc, &mut decoder);
+            log::debug!(
+                "sandbox ({}) decoded {} stream(s) and checked {} image(s); {} rejected",
+                report.confinement, report.decoded, report.images_checked, report.rejected.len(),
+            );
+            let mut decoded = Vec::with_capacity(input.len());
+            doc.save_to(&mut decoded)?;
+            Ok(Cow::Owned(decoded))
+        })
+        .await
+        .map_err(|e| PdfError::Processing(format!("sandbox task failed: {}", e)))?
+    }
+
+    /// Processes the PDF at `path`
+    ///
+    /// Large files are memory-mapped rather than read into a buffer; see
+    /// [`input::InputConfig`]. Fails if a mapped file is modified while it
+    /// is being processed.
+    pub async fn process_file(
+        &self,
+        path: impl AsRef<Path>,
+        options: Option<ProcessingOptions>,
+    ) -> Result<ProcessingResult, PdfError> {
+        let options = options.unwrap_or_default();
+        self.authorize_processing(&options, &path.as_ref().display().to_string()).await?;
+        let opened = self.open_input(path.as_ref()).await;
+        let input = opened.result?;
+        self.admit(input.len() as u64)?;
+        let mut result = self.run_processing(&input, options, Some(path.as_ref())).await?;
+        input.check_unchanged()?;
+        result.retries = opened.retries;
+        Ok(result)
+    }
+
+    /// Scans the PDF at `path` without modifying it
+    ///
+    /// Runs the built-in document scanner and every registered scanner
+    /// plugin, parsing the file once directly from its loaded bytes.
+    pub async fn scan_file(&self, path: impl AsRef<Path>) -> Result<Vec<ForensicArtifact>, PdfError> {
+        self.authorize(Operation::Scan, &path.as_ref().display().to_string()).await?;
+        // Missing files count as empty and fail when opened
+        self.admit(std::fs::metadata(path.as_ref()).map_or(0, |m| m.len()))?;
+        self.scan_path(path.as_ref()).await.map(|scanned| scanned.artifacts)
+    }
+
+    /// Estimates the risk of the file at `path` from its raw bytes
+    ///
+    /// Much faster than [`PdfEngine::scan_file`] because no object graph
+    /// is built and no stream is decompressed, but approximate: see
+    /// [`antiforensics::scanner::quick`]. Meant for triage, not for
+    /// deciding that a file is clean.
+    pub async fn quick_scan_file(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<antiforensics::scanner::QuickScanReport, PdfError> {
+        self.authorize(Operation::Scan, &path.as_ref().display().to_string()).await?;
+        self.admit(std::fs::metadata(path.as_ref()).map_or(0, |m| m.len()))?;
+        let path = path.as_ref().to_path_buf();
+        tokio::task::spawn_blocking(move || antiforensics::scanner::QuickScanner::new().scan_file(&path))
+            .await
+            .map_err(|e| PdfError::Processing(format!("quick scan task failed: {}", e)))?
+            .map_err(PdfError::from)
+    }
+
+    /// Scans every file of a batch and correlates the findings across it
+    ///
+    /// Files that fail to scan are reported individually and left out of
+    /// the correlation; they do not fail the batch.
+    pub async fn scan_files<P: AsRef<Path>>(
+        &self,
+        paths: impl IntoIterator<Item = P>,
+        config: CorrelationConfig,
+    ) -> Result<BatchScanReport, PdfError> {
+        self.authorize(Operation::Scan, "<batch>").await?;
+        let paths: Vec<P> = paths.into_iter().collect();
+        // A batch is one request for the bytes of all its files
+        self.admit(paths.iter().map(|p| std::fs::metadata(p.as_ref()).map_or(0, |m| m.len())).sum())?;
+        let mut correlation = CorrelationAnalyzer::new(config)
+            .map_err(|e| PdfError::Configuration(e.to_string()))?;
+        let mut files = Vec::new();
+        let mut run = history::RunRecorder::start();
+
+        for path in paths {
+            let path = path.as_ref();
+            let started = Instant::now();
+            match self.scan_path(path).await {
+                Ok(ScannedFile { doc, artifacts, retries, .. }) => {
+                    run.document(started.elapsed(), &artifacts);
+                    correlation.add_document(&path.display().to_string(), &doc, &artifacts);
+                    files.push(FileScanResult { path: path.to_path_buf(), artifacts, error: None, retries });
+                }
+                Err(e) => {
+                    run.failure(started.elapsed());
+                    log::warn!("scan of {} failed: {}", path.display(), e);
+                    files.push(FileScanResult {
+                        path: path.to_path_buf(),
+                        artifacts: Vec::new(),
+                        error: Some(e.to_string()),
+                        retries: 0,
+                    });
+                }
+            }
+        }
+
+        let run = run.finish();
+        if let Some(path) = &self.config.history_path {
+            if let Err(e) = history::MetricsHistory::new(path).append(&run) {
+                log::warn!("could not append run metrics to {}: {}", path.display(), e);
+            }
+        }
+
+        Ok(BatchScanReport {
+            files,
+            campaign: correlation.report(),
+            run,
+        })
+    }
+
+    /// Overwrites and deletes the file at `path`
+    ///
+    /// Needs the shred role. The file is gone whether or not the
+    /// returned result reports the overwrite as verified.
+    pub async fn shred_file(
+        &self,
+        path: impl AsRef<Path>,
+        config: antiforensics::cleaner::SecureDeleteConfig,
+    ) -> Result<antiforensics::cleaner::CleanResult, PdfError> {
+        use antiforensics::cleaner::Cleaner as _;
+
+        let path = path.as_ref().to_path_buf();
+        self.authorize(Operation::Shred, &path.display().to_string()).await?;
+        self.admit(0)?;
+        let job = self.jobs.begin()?;
+        job.run(async {
+            antiforensics::cleaner::SecureDelete::new(config)
+                .clean_file(&path)
+                .await
+                .map_err(|e| PdfError::Processing(format!("shredding {} failed: {}", path.display(), e)))
+        }).await
+    }
+
+    /// Opens an input file, retrying transient failures
+    async fn open_input(&self, path: &Path) -> retry::Retried<input::InputData> {
+        let opened = self.retry_policy().run(|| input::open(path, &self.config.input)).await;
+        if let Err(e) = &opened.result {
+            if opened.retries > 0 {
+                log::warn!("giving up on {} after {} retries: {}", path.display(), opened.retries, e);
+            }
         }
+        opened
+    }
+
+    async fn scan_path(&self, path: &Path) -> Result<ScannedFile, PdfError> {
+        let mut job = self.jobs.begin()?;
+        let job_id = Uuid::new_v4().to_string();
+        job.label(&job_id);
+        let started = Instant::now();
+        self.emit(&job_id, EventKind::Received, Some(path), |_| {}).await;
 
-        Ok(processed_data)
+        let scanned = job.run(self.scan_job(path)).await;
+        let kind = if scanned.is_ok() { EventKind::Scanned } else { EventKind::Failed };
+        self.emit(&job_id, kind, Some(path), |event| {
+            match &scanned {
+                Ok(file) => {
+                    event.input_sha256 = file.input_sha256.clone();
+                    event.stats.input_bytes = file.input_bytes;
+                    event.stats.artifacts = file.artifacts.len();
+                }
+                Err(e) => event.error = Some(e.to_string()),
+            }
+            event.stats.duration_ms = started.elapsed().as_millis() as u64;
+        }).await;
+        scanned
+    }
+
+    async fn scan_job(&self, path: &Path) -> Result<ScannedFile, PdfError> {
+        let opened = self.open_input(path).await;
+        let input = opened.result?;
+
+        let _permit = self.concurrency.acquire(Subsystem::Scan).await?;
+        // The parser follows /Prev without end when the chain loops
+        let xref = xref_integrity::check(&input);
+        if xref.has_prev_loop() {
+            return Err(PdfError::Validation("cross-reference /Prev chain loops; file was edited by hand".into()));
+        }
+        let doc = antiforensics::types::Document::parse(path.to_path_buf(), &input)?;
+        input.check_unchanged()?;
+        // Comments and padding only exist in the raw bytes
+        let mut syntax = SyntaxScanner::new().scan_bytes(&input);
+        syntax.extend(XrefIntegrityAnalyzer::artifacts(&xref));
+        let input_bytes = input.len() as u64;
+        let input_sha256 = self.events.is_active().then(|| events::sha256_hex(&input));
+        drop(input);
+
+        let sinks = self.artifact_sinks();
+        let mut artifacts = Vec::new();
+        self.collect_artifacts(&sinks, &mut artifacts, DocumentScanner::new().scan(&doc)).await;
+        self.collect_artifacts(&sinks, &mut artifacts, syntax).await;
+        self.collect_artifacts(&sinks, &mut artifacts, UsageRightsScanner::new().scan(&doc)).await;
+        self.collect_artifacts(&sinks, &mut artifacts, ObjectNumberingAnalyzer::default().scan(&doc)).await;
+        self.collect_artifacts(&sinks, &mut artifacts, TrailerAnalyzer::new().scan(&doc)).await;
+        for report in self.plugins.run_scanners(&doc).await {
+            if let StageOutcome::Failed(reason) = &report.outcome {
+                log::warn!("scanner plugin '{}' failed on {}: {}", report.name, path.display(), reason);
+            }
+            self.collect_artifacts(&sinks, &mut artifacts, report.artifacts).await;
+        }
+        sink::flush_all(&sinks).await;
+        antiforensics::sort_artifacts(&mut artifacts);
+
+        Ok(ScannedFile { doc, artifacts, retries: opened.retries, input_bytes, input_sha256 })
+    }
+
+    /// Keeps one scanner's findings above the confidence floor and hands them to `sinks`
+    async fn collect_artifacts(
+        &self,
+        sinks: &[Arc<dyn ArtifactSink>],
+        artifacts: &mut Vec<ForensicArtifact>,
+        found: Vec<ForensicArtifact>,
+    ) {
+        let found = self.config.analyzer.apply_min_confidence(found);
+        sink::publish(sinks, &found).await;
+        artifacts.extend(found);
+    }
+
+    /// Stops accepting jobs and drains the active ones
+    ///
+    /// New `process_*` and `scan_*` calls fail with `PdfError::Shutdown`
+    /// from here on. Jobs still running after `deadline` are cancelled and
+    /// fail with the same error. Logs are flushed before returning.
+    pub async fn shutdown(&self, deadline: Duration) -> shutdown::ShutdownReport {
+        log::info!("shutting down with {} active job(s)", self.jobs.active());
+        let report = self.jobs.shutdown(deadline).await;
+
+        if report.cancelled > 0 {
+            log::warn!("cancelled {} job(s) still running after {:?}", report.cancelled, deadline);
+        }
+        if report.abandoned > 0 {
+            log::error!("{} cancelled job(s) did not stop; their output may be incomplete", report.abandoned);
+        }
+        log::info!("shutdown complete in {:?}", report.elapsed);
+        log::logger().flush();
+
+        report
+    }
+
+    /// Starts a custom pipeline over `input`
+    ///
+    /// Steps are recorded by the returned builder and run when it is saved;
+    /// see [`builder::PipelineBuilder`] for the allowed orderings.
+    pub fn pipeline(&self, input: impl Into<Vec<u8>>) -> builder::PipelineBuilder<'_, builder::Loaded> {
+        builder::PipelineBuilder::new(self, input.into(), None)
+    }
+
+    /// Starts a custom pipeline over the file at `path`
+    ///
+    /// The file's times are captured before it is read, so a saved output
+    /// can take them over under `TimestampPolicy::CopyFromInput`.
+    pub fn pipeline_file(&self, path: impl AsRef<Path>) -> Result<builder::PipelineBuilder<'_, builder::Loaded>, PdfError> {
+        let mut file = std::fs::File::open(paths::extended(path.as_ref()))?;
+        let times = antiforensics::cleaner::FileTimestamps::from_metadata(&file.metadata()?);
+        let mut input = Vec::new();
+        std::io::Read::read_to_end(&mut file, &mut input)?;
+        Ok(builder::PipelineBuilder::new(self, input, Some(times)))
+    }
+
+    /// Adds a scanner that runs over every cleaned document before the gate
+    pub fn register_scanner(&self, scanner: Box<dyn plugins::Scanner>) -> Result<(), PdfError> {
+        self.plugins.register_scanner(scanner)
+    }
+
+    /// Adds a cleaner that runs after core processing in the clean stage
+    pub fn register_cleaner(&self, cleaner: Box<dyn plugins::Cleaner>) -> Result<(), PdfError> {
+        self.plugins.register_cleaner(cleaner)
+    }
+
+    /// Lifetime statistics of registered plugins, by name
+    pub fn plugin_stats(&self) -> HashMap<String, plugins::PluginStats> {
+        self.plugins.stats()
     }
 
     pub fn metrics(&self) -> Arc<metrics::MetricsRegistry> {
         self.metrics.clone()
     }
+
+    /// Time jobs spent waiting for worker permits, by subsystem
+    pub fn lock_waits(&self) -> Vec<watchdog::LockWaitStats> {
+        self.concurrency.watchdog().lock_waits()
+    }
+
+    /// Worker permits held longer than `EngineConfig::watchdog` allows
+    pub fn stuck_permits(&self) -> Vec<watchdog::StuckPermit> {
+        self.concurrency.watchdog().stuck()
+    }
+
+    /// Whether the engine can still make progress (`/healthz`)
+    pub fn liveness(&self) -> health::HealthReport {
+        health::HealthReport::new(health::check_worker_pools(&self.concurrency))
+    }
+
+    /// Whether the engine can take new jobs (`/readyz`)
+    ///
+    /// Touches the filesystem; do not poll it more often than needed.
+    pub fn readiness(&self) -> health::HealthReport {
+        let mut checks = health::check_worker_pools(&self.concurrency);
+        checks.push(if self.jobs.is_accepting() {
+            health::HealthCheck::pass("admission", "accepting jobs")
+        } else {
+            health::HealthCheck::fail("admission", "shutting down")
+        });
+        checks.push(match self.config.validate() {
+            Ok(()) => health::HealthCheck::pass("configuration", "valid"),
+            Err(e) => health::HealthCheck::fail("configuration", e.to_string()),
+        });
+        checks.push(health::check_writable("temp dir", &self.config.temp_dir));
+        if self.config.spill.dir != self.config.temp_dir {
+            checks.push(health::check_writable("spill dir", &self.config.spill.dir));
+        }
+        health::HealthReport::new(checks)
+    }
+
+    /// Sends job lifecycle events to `publisher` from now on
+    pub fn add_event_publisher(&self, publisher: Arc<dyn events::EventPublisher>) {
+        self.events.add(publisher);
+    }
+
+    /// Streams scan findings to `sink` from now on
+    ///
+    /// `scan_file`, `scan_files` and pipeline scan steps publish each
+    /// scanner's findings as soon as it finishes and flush the sinks when
+    /// the scan completes. Sink failures are logged, never fail the scan.
+    pub fn add_artifact_sink(&self, sink: Arc<dyn ArtifactSink>) {
+        if let Ok(mut sinks) = self.artifact_sinks.write() {
+            sinks.push(sink);
+        }
+    }
+
+    pub(crate) fn artifact_sinks(&self) -> Vec<Arc<dyn ArtifactSink>> {
+        self.artifact_sinks.read().map(|sinks| sinks.clone()).unwrap_or_default()
+    }
+
+    /// Requests, rejections and byte usage by client
+    pub fn quota_usage(&self) -> HashMap<String, quota::QuotaUsage> {
+        self.quotas.usage()
+    }
+
+    /// Replaces the per-client limits without restarting the engine
+    pub fn set_quotas(&self, config: quota::QuotaConfig) -> Result<(), PdfError> {
+        self.quotas.reconfigure(config)
+    }
+
+    /// Current per-subsystem worker limits
+    pub fn concurrency_limits(&self) -> SubsystemLimits {
+        self.concurrency.limits()
+    }
+
+    /// Redistributes the worker budget without restarting the engine
+    ///
+    /// Shrinking takes effect as in-flight work completes; running jobs
+    /// are not interrupted.
+    pub fn tune_concurrency(&self, config: concurrency::ConcurrencyConfig) -> Result<SubsystemLimits, PdfError> {
+        self.concurrency.reconfigure(config)
+    }
+
+    /// Retry policy for transient IO failures currently in effect
+    pub fn retry_policy(&self) -> retry::RetryPolicy {
+        self.retry.read().map(|policy| policy.clone()).unwrap_or_default()
+    }
+
+    /// Hot-applies every configuration `manager` stores from now on
+    ///
+    /// `performance.max_concurrent_tasks` becomes the worker budget,
+    /// `retry` the retry policy, and `analysis.patterns_file`, when it
+    /// exists, the pattern database. A configuration the engine cannot run
+    /// with is rejected before anything is applied, and the manager keeps
+    /// the previous one.
+    pub fn follow_config(&self, manager: &mut antiforensics::config::ConfigManager) {
+        let concurrency = self.concurrency.clone();
+        let retry = self.retry.clone();
+        manager.add_watcher(Box::new(move |config| {
+            apply_reloaded(&concurrency, &retry, config)
+                .map_err(|e| antiforensics::error::Error::Configuration(e.to_string()))
+        }));
+    }
+}
+
+/// Applies the reloadable settings of `config`, checking all of them first
+fn apply_reloaded(
+    concurrency: &ConcurrencyLimiter,
+    retry: &std::sync::RwLock<retry::RetryPolicy>,
+    config: &antiforensics::config::Config,
+) -> Result<(), PdfError> {
+    use antiforensics::patterns::{self, PatternDatabase};
+
+    let budget = concurrency::ConcurrencyConfig {
+        worker_budget: config.performance.max_concurrent_tasks,
+        ..concurrency.config()
+    };
+    budget.validate()?;
+    if config.retry.max_attempts == 0 {
+        return Err(PdfError::Configuration("retry.max_attempts must be at least 1".into()));
+    }
+    let path = &config.analysis.patterns_file;
+    let pattern_error = |e: patterns::PatternError| PdfError::Configuration(format!("pattern database {}: {}", path.display(), e));
+    let database = if path.exists() {
+        Some(PatternDatabase::load(path).map_err(pattern_error)?)
+    } else {
+        None
+    };
+    if let Some(database) = &database {
+        let installed = patterns::active().version();
+        if database.version() < installed {
+            return Err(pattern_error(patterns::PatternError::Downgrade { installed, offered: database.version() }));
+        }
+    }
+
+    concurrency.reconfigure(budget)?;
+    if let Ok(mut policy) = retry.write() {
+        policy.max_attempts = config.retry.max_attempts;
+        policy.initial_backoff = config.retry.initial_backoff;
+        policy.max_backoff = config.retry.max_backoff;
+    }
+    if let Some(database) = database {
+        patterns::install(database).map_err(pattern_error)?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -256,6 +1740,395 @@ mod tests {
         assert!(result.compression_ratio < 1.0);
     }
 
+    #[test]
+    fn test_severity_gate_fail_closed() {
+        let gate = SeverityGate::default();
+        let residual = vec![ForensicArtifact {
+            location: "4 0 R:/OpenAction/JS".into(),
+            risk_level: RiskLevel::Critical,
+            ..Default::default()
+        }];
+
+        let decision = gate.evaluate(&residual);
+        assert_eq!(decision.outcome, GateOutcome::Blocked);
+        assert_eq!(decision.blocking_locations, vec!["4 0 R:/OpenAction/JS".to_string()]);
+    }
+
+    #[test]
+    fn test_severity_gate_fail_open() {
+        let gate = SeverityGate {
+            threshold: RiskLevel::Medium,
+            mode: GateMode::FailOpen,
+        };
+        let residual = vec![
+            ForensicArtifact { risk_level: RiskLevel::High, ..Default::default() },
+            ForensicArtifact { risk_level: RiskLevel::Low, ..Default::default() },
+        ];
+
+        let decision = gate.evaluate(&residual);
+        assert_eq!(decision.outcome, GateOutcome::Warned);
+        assert_eq!(decision.residual_artifacts, 2);
+        assert_eq!(decision.highest_residual, RiskLevel::High);
+    }
+
+    #[test]
+    fn test_severity_gate_passes_below_threshold() {
+        let residual = vec![ForensicArtifact { risk_level: RiskLevel::High, ..Default::default() }];
+        let decision = SeverityGate::default().evaluate(&residual);
+        assert_eq!(decision.outcome, GateOutcome::Passed);
+    }
+
+    #[test]
+    fn test_error_context() {
+        let err = PdfError::Processing("bad stream".into())
+            .with_object((12, 0))
+            .with_offset(4096)
+            .with_stage(ProcessingStage::Clean);
+
+        assert_eq!(err.stage(), Some(ProcessingStage::Clean));
+        assert_eq!(err.object_id(), Some((12, 0)));
+        assert_eq!(err.offset(), Some(4096));
+        assert!(matches!(err.root_cause(), PdfError::Processing(_)));
+        assert_eq!(
+            err.to_string(),
+            "clean stage, object 12 0 R, offset 4096: PDF processing error: bad stream"
+        );
+    }
+
+    #[test]
+    fn test_error_context_keeps_innermost_stage() {
+        let result: Result<(), PdfError> = Err(PdfError::Compression("zlib".into()));
+        let err = result
+            .stage(ProcessingStage::Compress)
+            .stage(ProcessingStage::Write)
+            .unwrap_err();
+
+        assert_eq!(err.stage(), Some(ProcessingStage::Compress));
+        assert!(matches!(err.root_cause(), PdfError::Compression(_)));
+    }
+
+    #[test]
+    fn test_stage_log_marks_unreached_stages() {
+        let mut stages = StageLog::default();
+        stages.skip(ProcessingStage::Validation);
+        let failed: Result<(), PdfError> = Err(PdfError::Security("JavaScript".into()));
+        let err = stages.record(ProcessingStage::Security, Instant::now(), failed).unwrap_err();
+        assert_eq!(err.stage(), Some(ProcessingStage::Security));
+
+        let reports = stages.finish();
+        assert_eq!(reports.len(), ProcessingStage::PIPELINE.len());
+        assert_eq!(reports[0].outcome, StageOutcome::Skipped);
+        assert_eq!(reports[1].outcome, StageOutcome::Failed("Security error: JavaScript".into()));
+        assert!(reports[2..].iter().all(|r| r.outcome == StageOutcome::NotReached));
+    }
+
+    #[tokio::test]
+    async fn test_processing_reports_every_stage() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let sample_pdf = include_bytes!("../tests/data/sample.pdf");
+        let result = engine.process_document(sample_pdf, None).await.unwrap();
+
+        assert_eq!(result.stages.len(), ProcessingStage::PIPELINE.len());
+        assert_eq!(result.stage(ProcessingStage::Encrypt).unwrap().outcome, StageOutcome::Skipped);
+        assert_eq!(result.resource_usage.bytes_read, sample_pdf.len() as u64);
+        assert_eq!(result.resource_usage.bytes_written, result.processed_bytes as u64);
+    }
+
+    #[tokio::test]
+    async fn test_preserve_visual_skips_optimization() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let sample_pdf = include_bytes!("../tests/data/sample.pdf");
+        let options = ProcessingOptions { preserve_visual: true, ..Default::default() };
+        let result = engine.process_document(sample_pdf, Some(options)).await.unwrap();
+
+        assert_eq!(result.stage(ProcessingStage::Optimize).unwrap().outcome, StageOutcome::Skipped);
+        assert_eq!(result.stage(ProcessingStage::Compress).unwrap().outcome, StageOutcome::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_process_file_matches_process_document() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+
+        let from_file = engine.process_file(sample, None).await.unwrap();
+        let from_bytes = engine.process_document(&std::fs::read(sample).unwrap(), None).await.unwrap();
+        assert_eq!(from_file.processed_bytes, from_bytes.processed_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_scan_file_missing_path() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let result = engine.scan_file("/nonexistent/input.pdf").await;
+        assert!(matches!(result, Err(PdfError::Io(_))));
+    }
+
+    #[tokio::test]
+    async fn test_config_reload_retunes_engine() {
+        use antiforensics::config::{Config, ConfigManager};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yml");
+        std::fs::write(&path, serde_yaml::to_string(&Config::default()).unwrap()).unwrap();
+        let mut manager = ConfigManager::new(&path).await.unwrap();
+
+        let engine = PdfEngine::new(None).await.unwrap();
+        engine.follow_config(&mut manager);
+        let weights = engine.concurrency.config();
+
+        let mut config = Config::default();
+        config.performance.max_concurrent_tasks = 24;
+        config.retry.max_attempts = 1;
+        manager.update(config.clone()).await.unwrap();
+        let expected = concurrency::ConcurrencyConfig { worker_budget: 24, ..weights }.limits();
+        assert_eq!(engine.concurrency_limits(), expected);
+        assert_eq!(engine.concurrency.available(Subsystem::Clean), Some(expected.clean));
+        assert_eq!(engine.retry_policy().max_attempts, 1);
+
+        // A budget the engine cannot run with is refused as a whole
+        config.performance.max_concurrent_tasks = 0;
+        config.retry.max_attempts = 3;
+        assert!(manager.update(config).await.is_err());
+        assert_eq!(engine.concurrency_limits(), expected);
+        assert_eq!(engine.retry_policy().max_attempts, 1);
+        assert_eq!(manager.get_config().await.performance.max_concurrent_tasks, 24);
+    }
+
+    /// Adds a JavaScript open action the first time it runs and removes it every time after
+    struct LateJavaScript(std::sync::atomic::AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl plugins::Cleaner for LateJavaScript {
+        fn name(&self) -> &str {
+            "late-javascript"
+        }
+
+        async fn clean(&self, doc: &mut antiforensics::types::Document) -> Result<usize, PdfError> {
+            let first = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0;
+            let catalog = doc.catalog_mut().ok_or_else(|| PdfError::Processing("no catalog".into()))?;
+            if first {
+                catalog.set("OpenAction", lopdf::dictionary! {
+                    "S" => "JavaScript",
+                    "JS" => lopdf::Object::string_literal("app.alert(1)"),
+                });
+            } else {
+                catalog.remove(b"OpenAction");
+            }
+            Ok(1)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clean_stage_recleans_until_verified() {
+        let input = fixtures::Fixture::Clean.bytes();
+        let options = |max_rounds| ProcessingOptions {
+            severity_gate: None,
+            verify_clean: Some(CleanVerification { max_rounds, ..Default::default() }),
+            ..Default::default()
+        };
+
+        // The first round leaves JavaScript behind; the re-scan catches it
+        let engine = PdfEngine::new(None).await.unwrap();
+        engine.register_cleaner(Box::new(LateJavaScript(Default::default()))).unwrap();
+        let result = engine.process_document(&input, Some(options(3))).await.unwrap();
+        assert_eq!(result.clean_rounds, 2);
+        assert_eq!(result.stage(ProcessingStage::Clean).unwrap().outcome, StageOutcome::Completed);
+
+        // Out of rounds, the findings are reported instead
+        let engine = PdfEngine::new(None).await.unwrap();
+        engine.register_cleaner(Box::new(LateJavaScript(Default::default()))).unwrap();
+        let result = engine.process_document(&input, Some(options(1))).await.unwrap();
+        assert_eq!(result.clean_rounds, 1);
+        assert!(matches!(result.stage(ProcessingStage::Clean).unwrap().outcome, StageOutcome::Degraded(_)));
+        assert!(matches!(result.status, ProcessingStatus::PartialSuccess(_)));
+
+        // Without verification the stage cleans once
+        let result = engine.process_document(&input, None).await.unwrap();
+        assert_eq!(result.clean_rounds, 1);
+    }
+
+    #[tokio::test]
+    async fn test_scan_file_publishes_to_artifact_sinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("javascript.pdf");
+        std::fs::write(&path, fixtures::Fixture::JavaScript.bytes()).unwrap();
+
+        let engine = PdfEngine::new(None).await.unwrap();
+        let (channel, mut received) = sink::ChannelSink::new(1024);
+        engine.add_artifact_sink(Arc::new(channel));
+        let artifacts = engine.scan_file(&path).await.unwrap();
+        assert!(!artifacts.is_empty());
+
+        let mut published = Vec::new();
+        while let Ok(artifact) = received.try_recv() {
+            published.push(artifact.id);
+        }
+        let mut returned: Vec<String> = artifacts.into_iter().map(|a| a.id).collect();
+        published.sort();
+        returned.sort();
+        assert_eq!(published, returned);
+    }
+
+    #[tokio::test]
+    async fn test_scan_files_reports_failures_per_file() {
+        let history = std::env::temp_dir().join(format!("history-{}.jsonl", Uuid::new_v4()));
+        let config = EngineConfig { history_path: Some(history.clone()), ..Default::default() };
+        let engine = PdfEngine::new(Some(config)).await.unwrap();
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+        let report = engine.scan_files([sample, "/nonexistent/input.pdf"], CorrelationConfig::default()).await.unwrap();
+
+        assert_eq!(report.files.len(), 2);
+        assert!(report.files[0].error.is_none());
+        assert!(report.files[1].error.is_some());
+        assert_eq!(report.campaign.documents_analyzed, 1);
+        assert_eq!((report.run.documents_processed, report.run.documents_failed), (1, 1));
+
+        let recorded = history::MetricsHistory::new(&history).load(None).unwrap();
+        assert_eq!(recorded, vec![report.run]);
+        std::fs::remove_file(&history).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_readiness_checks_temp_dir() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        assert!(engine.liveness().healthy);
+        assert!(engine.readiness().healthy);
+
+        let config = EngineConfig { temp_dir: PathBuf::from("/nonexistent/tmp"), ..Default::default() };
+        let engine = PdfEngine::new(Some(config)).await.unwrap();
+        let readiness = engine.readiness();
+        assert_eq!(readiness.status_code(), 503);
+        assert_eq!(readiness.failures().next().unwrap().name, "temp dir");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_rejects_new_jobs() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let report = engine.shutdown(Duration::from_secs(1)).await;
+        assert!(report.drained());
+
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+        assert!(matches!(engine.scan_file(sample).await, Err(PdfError::Shutdown(_))));
+        assert!(!engine.readiness().healthy);
+    }
+
+    #[tokio::test]
+    async fn test_tune_concurrency() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let limits = engine.tune_concurrency(concurrency::ConcurrencyConfig {
+            worker_budget: 12,
+            scan_weight: 1,
+            clean_weight: 1,
+            write_weight: 1,
+        }).unwrap();
+
+        assert_eq!(limits, SubsystemLimits { scan: 4, clean: 4, write: 4 });
+        assert_eq!(engine.concurrency_limits(), limits);
+    }
+
+    #[tokio::test]
+    async fn test_operator_roles_gate_operations() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+
+        let analyst = engine.with_operator(Operator::principal("analyst", [security::operator::Role::ScanOnly]));
+        assert!(analyst.scan_file(sample).await.is_ok());
+        assert!(matches!(analyst.process_file(sample, None).await, Err(PdfError::PermissionDenied(_))));
+
+        let cleaner = engine.with_operator(Operator::principal("cleaner", [security::operator::Role::Clean]));
+        let encrypt = ProcessingOptions { encrypt: true, ..Default::default() };
+        assert!(matches!(cleaner.process_file(sample, Some(encrypt)).await, Err(PdfError::PermissionDenied(_))));
+    }
+
+    #[tokio::test]
+    async fn test_quota_refuses_before_admission() {
+        let limits = quota::ClientLimits { requests_per_second: 0.001, burst: 1, bytes_per_window: None };
+        let config = EngineConfig {
+            quotas: quota::QuotaConfig { default_limits: Some(limits), ..Default::default() },
+            ..Default::default()
+        };
+        let engine = PdfEngine::new(Some(config)).await.unwrap();
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+
+        assert!(engine.scan_file(sample).await.is_ok());
+        let refused = engine.scan_file(sample).await.unwrap_err();
+        assert_eq!(refused.status_code(), 429);
+
+        let usage = &engine.quota_usage()[quota::ANONYMOUS_CLIENT];
+        assert_eq!((usage.admitted, usage.rejected), (1, 1));
+        assert_eq!(engine.jobs.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_scan_publishes_lifecycle_events() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        let channel = Arc::new(events::ChannelPublisher::new(8));
+        let mut received = channel.subscribe();
+        engine.add_event_publisher(channel);
+
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+        let artifacts = engine.scan_file(sample).await.unwrap();
+
+        let first = received.recv().await.unwrap();
+        let second = received.recv().await.unwrap();
+        assert_eq!((first.kind, second.kind), (EventKind::Received, EventKind::Scanned));
+        assert_eq!(first.job_id, second.job_id);
+        assert_eq!(second.stats.artifacts, artifacts.len());
+        assert_eq!(second.input_sha256, Some(events::sha256_hex(&std::fs::read(sample).unwrap())));
+    }
+
+    #[tokio::test]
+    async fn test_require_operator() {
+        let config = EngineConfig { require_operator: true, ..Default::default() };
+        let engine = PdfEngine::new(Some(config)).await.unwrap();
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.pdf");
+        assert!(matches!(engine.scan_file(sample).await, Err(PdfError::PermissionDenied(_))));
+    }
+
+    #[tokio::test]
+    async fn test_retention_schedule_needs_shred() {
+        let dir = tempfile::tempdir().unwrap();
+        let retention = retention::RetentionConfig {
+            rules: vec![retention::RetentionRule {
+                kind: retention::StoreKind::Cache,
+                dir: dir.path().join("cache"),
+                max_age_days: Some(1),
+                max_size_mb: None,
+                secure_delete: false,
+            }],
+            interval_minutes: Some(60),
+        };
+
+        let config = EngineConfig { require_operator: true, retention: retention.clone(), ..Default::default() };
+        assert!(matches!(PdfEngine::new(Some(config)).await, Err(PdfError::Configuration(_))));
+
+        let on_request = retention::RetentionConfig { interval_minutes: None, ..retention.clone() };
+        let config = EngineConfig { require_operator: true, retention: on_request, ..Default::default() };
+        assert!(PdfEngine::new(Some(config)).await.is_ok());
+
+        let config = EngineConfig { retention, ..Default::default() };
+        assert!(PdfEngine::new(Some(config)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rollback_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = EngineConfig { backups: backup::BackupMode::Disk(dir.path().to_path_buf()), ..Default::default() };
+        let sample_pdf = include_bytes!("../tests/data/sample.pdf");
+
+        let cleaning_id = {
+            let engine = PdfEngine::new(Some(config.clone())).await.unwrap();
+            engine.process_document(sample_pdf, None).await.unwrap().document_id
+        };
+
+        let engine = PdfEngine::new(Some(config)).await.unwrap();
+        assert_eq!(engine.cleaning_transactions()[0].state, backup::TransactionState::Committed);
+        assert_eq!(engine.rollback(&cleaning_id).await.unwrap(), sample_pdf);
+        assert_eq!(engine.rollback("unknown").await.unwrap_err().status_code(), 404);
+
+        let without_backups = PdfEngine::new(None).await.unwrap();
+        assert!(matches!(without_backups.rollback(&cleaning_id).await, Err(PdfError::Configuration(_))));
+    }
+
     #[tokio::test]
     async fn test_pdf_encryption() {
         let engine = PdfEngine::new(None).await.unwrap();
@@ -7,38 +7,707 @@
 // User: kartik6717
 // Note: Placeholder code has been replaced with actual implementations
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
+
+use pdf_engine::aggregate::{AggregateConfig, FleetAggregator};
+use pdf_engine::antiforensics::analyzer::{
+    xref_integrity, BaselineScoreLine, StructuralBaseline, StructuralProfile, SyncPrecedence,
+};
+use pdf_engine::antiforensics::cleaner::{
+    AttachmentDates, AttachmentPolicy, CleaningScope, FormData, FormDataFormat, MetadataPolicy, ScopeFilter,
+    TextRedactor, TrackingAction,
+};
+use pdf_engine::antiforensics::hash::{self, DocumentHashes, HashAlgorithm, HashMatchStatus};
+use pdf_engine::antiforensics::patterns::{self, harness::RuleTester, PatternDatabase, PatternKind};
+use pdf_engine::antiforensics::report::{encrypted, provenance, rollup, schema};
+use pdf_engine::antiforensics::scanner::triage::{BulkTriage, TriageProfile};
+use pdf_engine::antiforensics::scanner::{QuickScanLine, QuickScanner};
+use pdf_engine::antiforensics::report::{
+    AccessibilityImpact, ExplainedFinding, GraphFormat, Inspector, ObjectMapping, PageRisk, PageThumbnail, ProvenanceStatus,
+    ProvenanceTemplate, ReplayRecord, ReportCryptoError, ReportEncryption, ReportKey, RolledUpFinding, ScanSummary,
+    SidecarReport, StageRecord,
+};
+use pdf_engine::fixtures::Fixture;
+use pdf_engine::history::{MetricsHistory, TrendPeriod};
+use pdf_engine::paths;
+use pdf_engine::profile::Profiler;
+use pdf_engine::retention::{RetentionConfig, RetentionManager};
+use pdf_engine::sandbox::{self, SandboxConfig};
+use pdf_engine::staging::StagingArea;
+use pdf_engine::visual_diff::PageDiff;
+#[cfg(feature = "render")]
+use pdf_engine::visual_diff::VisualDiffConfig;
+use pdf_engine::store::{GcPolicy, OutputStore, Placement};
+use pdf_engine::verification::{policy::VerificationPolicy, VerificationSystem};
+use pdf_engine::EngineConfig;
 
 mod pipeline;
-use pipeline::{PdfPipeline, PipelineError};
+use pipeline::{PdfPipeline, PipelineError, VerifyConfig};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Print the JSON Schema of the JSON this tool writes (all, artifact, sidecar, evidence, provenance,
+    /// quick-scan, rule-test or replay) and exit
+    #[arg(long, exclusive = true, value_name = "NAME", num_args = 0..=1, default_missing_value = "all")]
+    schema: Option<String>,
+
+    /// Record where the run spends its time and write it to FILE: a Chrome trace for .json, folded stacks
+    /// for flamegraphs otherwise
+    #[arg(long, global = true, value_name = "FILE")]
+    profile: Option<PathBuf>,
+
+    #[command(flatten)]
+    process: ProcessArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compute document hashes, or verify them against a manifest
+    Hash(HashArgs),
+    /// Report processing trends from a run history file
+    Stats(StatsArgs),
+    /// Clean every PDF in a directory
+    Batch(BatchArgs),
+    /// Show the provenance record embedded in a processed PDF
+    Provenance(ProvenanceArgs),
+    /// Fill a form from FDF or XFDF field data
+    Fill(FillArgs),
+    /// Evict old or least recently used outputs from a batch output store
+    StoreGc(StoreGcArgs),
+    /// Delete backups, quarantined files and cached outputs beyond a retention policy's age and size limits
+    Purge(PurgeArgs),
+    /// Estimate risk from raw bytes without parsing (approximate, for triage)
+    QuickScan(QuickScanArgs),
+    /// Inspect, compile and test detection pattern databases
+    #[command(subcommand)]
+    Rules(RulesCommand),
+    /// Export the object reference graph for Graphviz (DOT) or Gephi (GraphML)
+    Graph(GraphArgs),
+    /// Print an object, the trailer, the catalog or a page's decoded content in readable form
+    Inspect(InspectArgs),
+    /// Decrypt a report written with --report-recipient or --report-passphrase-file
+    DecryptReport(DecryptReportArgs),
+    /// Learn the structure of known-good PDFs, and score other PDFs by how far they deviate from it
+    #[command(subcommand)]
+    Baseline(BaselineCommand),
+    /// Re-run a processing run recorded with --record-replay and report the first stage whose output differs
+    Replay(ReplayArgs),
+    /// Write benign synthetic PDFs with the constructs scanners detect, for validating a deployment
+    Fixtures(FixturesArgs),
+    /// Check structure, compliance, signatures and content against a policy, without cleaning
+    Verify(VerifyArgs),
+}
+
+#[derive(Subcommand, Debug)]
+enum BaselineCommand {
+    /// Record structural statistics of a corpus of known-good documents
+    Learn {
+        /// PDF files, or directories whose PDFs are learned
+        #[arg(required = true)]
+        corpus: Vec<PathBuf>,
+        /// Baseline file to write (JSON)
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Score documents by their structural deviation from a baseline
+    Score {
+        /// Baseline written by `baseline learn`
+        #[arg(long)]
+        baseline: PathBuf,
+        /// PDF files, or directories whose PDFs are scored
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Print one JSON score per line instead of a summary
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RulesCommand {
+    /// Show the version and rules of a database (the built-in one by default)
+    Info {
+        /// Pattern database (.patdb or .yaml)
+        database: Option<PathBuf>,
+    },
+    /// Write the built-in database, as a starting point for updates (.yaml or .patdb by extension)
+    Export {
+        /// Output file
+        output: PathBuf,
+    },
+    /// Compile YAML rules into a .patdb database for --patterns
+    Compile {
+        /// YAML rule source
+        source: PathBuf,
+        /// Output file (.patdb)
+        output: PathBuf,
+    },
+    /// Report precision and recall per rule over a directory of labeled fixtures
+    Test(RulesTestArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct RulesTestArgs {
+    /// Pattern database (.patdb or .yaml)
+    database: PathBuf,
+
+    /// Directory of fixture PDFs with a labels.yaml of the rule ids expected per fixture
+    fixtures: PathBuf,
+
+    /// Fail when a rule that fired has lower precision than this
+    #[arg(long, default_value_t = 0.0, value_name = "RATIO")]
+    min_precision: f64,
+
+    /// Fail when a rule with labeled fixtures has lower recall than this
+    #[arg(long, default_value_t = 0.0, value_name = "RATIO")]
+    min_recall: f64,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct PatternArgs {
+    /// Detection patterns to use instead of the built-in ones (.patdb or .yaml, not older than the built-in version)
+    #[arg(long, value_name = "FILE")]
+    patterns: Option<PathBuf>,
+}
+
+impl PatternArgs {
+    fn install(&self) -> Result<(), PipelineError> {
+        if let Some(path) = &self.patterns {
+            patterns::install(PatternDatabase::load(path)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct QuickScanArgs {
+    /// PDF files, or directories whose PDFs are scanned
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Print one JSON report per line instead of a summary
+    #[arg(long)]
+    json: bool,
+
+    /// Scan profile; bulk-triage hashes each file and matches signatures in a single parallel read,
+    /// and reports throughput against its target
+    #[arg(long, value_enum, default_value_t = ScanProfile::Standard)]
+    profile: ScanProfile,
+
+    /// Worker threads of the bulk-triage profile (all cores by default)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    threads: usize,
+
+    #[command(flatten)]
+    patterns: PatternArgs,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ScanProfile {
+    Standard,
+    /// SHA-256 and signature hits only, first 16 MiB of each file, no stream decompression
+    BulkTriage,
+}
+
+#[derive(clap::Args, Debug)]
+struct GraphArgs {
     /// Input PDF file path
     input: PathBuf,
 
-    /// Output PDF file path
+    /// Output file; the graph is printed when omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Graph format (by default GraphML for .graphml outputs, DOT otherwise)
+    #[arg(long, value_enum)]
+    format: Option<GraphOutput>,
+
+    /// Only include objects with findings and the objects referencing them on the way from the trailer
+    #[arg(long)]
+    risky_only: bool,
+
+    #[command(flatten)]
+    patterns: PatternArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct InspectArgs {
+    /// Input PDF file path
+    input: PathBuf,
+
+    /// What to print: trailer, catalog, an object like `12` or `12 0 R`, or page:N; read one per line from stdin when omitted
+    target: Option<String>,
+
+    /// Print stream data as stored instead of decoded
+    #[arg(long)]
+    raw: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct DecryptReportArgs {
+    /// Encrypted report (.age)
+    input: PathBuf,
+
+    /// Output file; the report is printed when omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// age identity file with the secret key matching a recipient (as written by age-keygen)
+    #[arg(long, value_name = "FILE", required_unless_present = "passphrase_file", conflicts_with = "passphrase_file")]
+    identity: Option<PathBuf>,
+
+    /// File holding the passphrase the report was encrypted with
+    #[arg(long, value_name = "FILE")]
+    passphrase_file: Option<PathBuf>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum GraphOutput {
+    Dot,
+    Graphml,
+}
+
+impl From<GraphOutput> for GraphFormat {
+    fn from(output: GraphOutput) -> Self {
+        match output {
+            GraphOutput::Dot => GraphFormat::Dot,
+            GraphOutput::Graphml => GraphFormat::GraphMl,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct StoreGcArgs {
+    /// Store directory given to `batch --store`
+    store: PathBuf,
+
+    /// Shrink the store to at most this many megabytes
+    #[arg(long, value_name = "MB")]
+    max_size_mb: Option<u64>,
+
+    /// Remove outputs not used in this many days
+    #[arg(long, value_name = "DAYS")]
+    max_age_days: Option<u64>,
+}
+
+#[derive(clap::Args, Debug)]
+struct PurgeArgs {
+    /// JSON retention policy: {"rules": [{"kind": "backups|quarantine|cache", "dir": "...", "max_age_days": 30,
+    /// "max_size_mb": 1024, "secure_delete": true}]}; quarantine directories must hold a
+    /// .pdf-engine-quarantine marker file
+    policy: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+struct FillArgs {
+    /// Blank form to fill
+    template: PathBuf,
+
+    /// Field data (.fdf or .xfdf)
+    data: PathBuf,
+
+    /// Filled PDF file path
     output: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+struct ProvenanceArgs {
+    /// Processed PDF file
+    input: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+struct ReplayArgs {
+    /// Replay record written by --record-replay
+    record: PathBuf,
+
+    /// Replay on this input instead of the recorded one
+    #[arg(long, value_name = "PDF")]
+    input: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct FixturesArgs {
+    /// Directory to write the fixtures to
+    output_dir: PathBuf,
+
+    /// Only these fixtures (comma-separated: clean, javascript, embedded-file, hidden-layer, incremental-update,
+    /// broken-xref)
+    #[arg(long, value_delimiter = ',', value_name = "NAMES")]
+    only: Vec<Fixture>,
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+    /// PDF to verify
+    input: PathBuf,
+
+    /// Verification policy (JSON); without one, structure and content are checked and major errors fail
+    #[arg(long, value_name = "FILE")]
+    policy: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct BatchArgs {
+    /// Directory of input PDFs, and of .zip or .tar.gz archives of PDFs
+    input_dir: PathBuf,
+
+    /// Directory for cleaned outputs
+    output_dir: PathBuf,
+
+    /// Write <name>.report.json next to each output
+    #[arg(long)]
+    sidecar: bool,
+
+    /// Include each finding's object excerpt, decoded content and reference chain in the sidecar
+    #[arg(long, requires = "sidecar")]
+    explain: bool,
+
+    /// List every finding and cleaning action in the sidecar instead of rolling up repeats
+    #[arg(long, requires = "sidecar")]
+    full: bool,
+
+    /// Render input and output pages and fail outputs whose pages changed visually (needs the `render` feature)
+    #[arg(long)]
+    visual_check: bool,
+
+    /// Only clean metadata and structure, and fail outputs whose pages are not pixel-identical to the input
+    #[arg(long)]
+    preserve_visual: bool,
+
+    /// Embed before/after thumbnails of the first N pages in the sidecar (needs the `render` feature)
+    #[arg(long, requires = "sidecar", default_value_t = 0, value_name = "N")]
+    thumbnails: usize,
+
+    /// Embed thumbnails of the N riskiest input pages in the sidecar's page heatmap (needs the `render` feature)
+    #[arg(long, requires = "sidecar", default_value_t = 0, value_name = "N")]
+    heatmap_thumbnails: usize,
+
+    /// List the output object each input object became in the sidecar
+    #[arg(long, requires = "sidecar")]
+    object_map: bool,
+
+    /// Write the embedded PDFs of portfolios to <output_dir>/<name>/ instead of the portfolio
+    #[arg(long)]
+    split_portfolios: bool,
+
+    /// Write each output to <output_dir>/<language>/, by the primary language of its text ("und" when unclear)
+    #[arg(long)]
+    route_by_language: bool,
+
+    /// Clean-and-rescan rounds before an output counts as unverified
+    #[arg(long, default_value_t = 3, value_name = "N")]
+    clean_rounds: usize,
+
+    /// Write anonymized, noised artifact and risk counts of the batch to FILE, for sharing outside the team
+    #[arg(long, value_name = "FILE")]
+    aggregate: Option<PathBuf>,
+
+    /// Privacy budget of --aggregate; smaller adds more noise
+    #[arg(long, requires = "aggregate", default_value_t = 1.0, value_name = "EPSILON")]
+    aggregate_epsilon: f64,
+
+    /// What to do with embedded files
+    #[arg(long, value_enum, default_value_t = Attachments::Keep)]
+    attachments: Attachments,
+
+    /// Creation and modification dates of the embedded files kept
+    #[arg(long, value_enum, default_value_t = EmbeddedDates::Strip)]
+    attachment_dates: EmbeddedDates,
 
-    /// Calculate MD5 hash
+    /// What to do with references that load or send data outside the document
+    #[arg(long, value_enum, default_value_t = Tracking::Remove)]
+    tracking: Tracking,
+
+    /// Directory `--tracking localize` may embed files from; names leading outside it are refused
+    #[arg(long, value_name = "DIR", required_if_eq("tracking", "localize"))]
+    localize_dir: Option<PathBuf>,
+
+    /// Only clean part of the document; repeat to combine (pages=3-5, type=attachments|metadata|javascript|...,
+    /// object=12, location=/Info/*). Document-level entries are on no page.
+    #[arg(long, value_name = "FILTER")]
+    scope: Vec<ScopeFilter>,
+
+    #[command(flatten)]
+    metadata: MetadataArgs,
+
+    /// Pack the cleaned PDFs of .zip and .tar.gz inputs into a sanitized archive of the same name
+    /// instead of <output_dir>/<archive name>/ (archives need the `archive` feature)
     #[arg(long)]
-    md5: bool,
+    repack_archives: bool,
 
-    /// Calculate SHA1 hash
+    /// Reuse outputs of inputs already cleaned with the same settings from this store
+    /// (outputs become read-only hard links into it where possible; unused with --evidence-key)
+    #[arg(long, value_name = "DIR")]
+    store: Option<PathBuf>,
+
+    /// Publish no output unless every file cleans and verifies; outputs are staged and moved into place together
+    #[arg(long)]
+    transactional: bool,
+
+    /// Allow the output directory to be the input directory, replacing each input with its cleaned output
     #[arg(long)]
-    sha1: bool,
+    in_place: bool,
 
-    /// Calculate SHA256 hash
+    /// Decode untrusted streams and images in a confined child process before scanning
     #[arg(long)]
-    sha256: bool,
+    sandbox: bool,
+
+    #[command(flatten)]
+    patterns: PatternArgs,
+
+    #[command(flatten)]
+    evidence: EvidenceArgs,
+
+    #[command(flatten)]
+    report_encryption: ReportEncryptionArgs,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Tracking {
+    Keep,
+    /// Remove external stream references, form submit/import targets and remote go-to actions
+    Remove,
+    /// Embed externally referenced files from --localize-dir; remove the rest
+    Localize,
+}
+
+impl From<Tracking> for Option<TrackingAction> {
+    fn from(tracking: Tracking) -> Self {
+        match tracking {
+            Tracking::Keep => None,
+            Tracking::Remove => Some(TrackingAction::Remove),
+            Tracking::Localize => Some(TrackingAction::Localize),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum SyncXmp {
+    /// XMP takes the Info values
+    InfoWins,
+    /// Info takes the XMP values
+    XmpWins,
+    /// Remove the fields from both
+    ClearBoth,
+}
+
+impl From<SyncXmp> for SyncPrecedence {
+    fn from(sync: SyncXmp) -> Self {
+        match sync {
+            SyncXmp::InfoWins => SyncPrecedence::InfoWins,
+            SyncXmp::XmpWins => SyncPrecedence::XmpWins,
+            SyncXmp::ClearBoth => SyncPrecedence::ClearBoth,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Attachments {
+    Keep,
+    Strip,
+    /// Strip all but compliant ZUGFeRD / Factur-X invoice XML
+    KeepInvoices,
+}
+
+impl From<Attachments> for AttachmentPolicy {
+    fn from(attachments: Attachments) -> Self {
+        match attachments {
+            Attachments::Keep => AttachmentPolicy::Keep,
+            Attachments::Strip => AttachmentPolicy::Strip,
+            Attachments::KeepInvoices => AttachmentPolicy::KeepInvoices,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum EmbeddedDates {
+    /// Remove them; PDF/A-3 associated files get the epoch as modification date
+    Strip,
+    /// Set both to 1970-01-01 00:00:00 UTC
+    Epoch,
+    Keep,
+}
+
+impl From<EmbeddedDates> for AttachmentDates {
+    fn from(dates: EmbeddedDates) -> Self {
+        match dates {
+            EmbeddedDates::Strip => AttachmentDates::Strip,
+            EmbeddedDates::Epoch => AttachmentDates::epoch(),
+            EmbeddedDates::Keep => AttachmentDates::Keep,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct EvidenceArgs {
+    /// Write a signed <name>.evidence.json log of every transformation, signed with the Ed25519
+    /// secret in this file (32 bytes, raw or hex); verifiers need only the printed public key
+    #[arg(long, value_name = "KEY_FILE")]
+    evidence_key: Option<PathBuf>,
+
+    /// Operator recorded in the evidence log (defaults to the OS user)
+    #[arg(long, requires = "evidence_key")]
+    operator: Option<String>,
+
+    /// Also log the input byte range of every removed or altered object (offsets only, no content)
+    #[arg(long, requires = "evidence_key")]
+    evidence_byte_ranges: bool,
+}
+
+/// Evidence-mode settings resolved from the command line
+struct Evidence {
+    key: ed25519_dalek::SigningKey,
+    /// Key file name, recorded so verifiers know which key to use
+    key_id: String,
+    operator: String,
+    byte_ranges: bool,
+}
+
+impl EvidenceArgs {
+    fn resolve(self) -> std::io::Result<Option<Evidence>> {
+        let Some(key_path) = self.evidence_key else { return Ok(None) };
+        let operator = self.operator
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+            .unwrap_or_else(|| "unknown".into());
+        Ok(Some(Evidence {
+            key: pdf_engine::antiforensics::report::evidence::signing_key(&std::fs::read(&key_path)?)?,
+            key_id: key_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            operator,
+            byte_ranges: self.evidence_byte_ranges,
+        }))
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct MetadataArgs {
+    /// Metadata fields to keep, stripping all others (comma-separated Info keys, XMP properties or
+    /// catalog keys, e.g. Title,Lang; Title also keeps dc:title)
+    #[arg(long, value_name = "FIELDS", value_delimiter = ',')]
+    keep_metadata: Vec<String>,
+
+    /// Metadata fields to strip even when kept or otherwise left alone (comma-separated, as --keep-metadata)
+    #[arg(long, value_name = "FIELDS", value_delimiter = ',')]
+    drop_metadata: Vec<String>,
+}
+
+impl MetadataArgs {
+    fn policy(&self) -> MetadataPolicy {
+        MetadataPolicy::keep_only(self.keep_metadata.iter().cloned()).with_drop(self.drop_metadata.iter().cloned())
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct ReportEncryptionArgs {
+    /// Encrypt sidecar reports and evidence logs to this age public key (age1...); repeat for several recipients
+    #[arg(long, value_name = "PUBLIC_KEY", conflicts_with = "report_passphrase_file")]
+    report_recipient: Vec<String>,
+
+    /// Encrypt sidecar reports and evidence logs with the passphrase in this file
+    #[arg(long, value_name = "FILE")]
+    report_passphrase_file: Option<PathBuf>,
+}
+
+impl ReportEncryptionArgs {
+    fn resolve(self) -> Result<Option<ReportEncryption>, PipelineError> {
+        if let Some(path) = self.report_passphrase_file {
+            return Ok(Some(ReportEncryption::passphrase(read_passphrase(&path)?)));
+        }
+        if self.report_recipient.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(ReportEncryption::recipients(&self.report_recipient)?))
+    }
+}
+
+/// First line of a passphrase file, without its line ending; an empty one is refused
+fn read_passphrase(path: &std::path::Path) -> std::io::Result<String> {
+    let contents = std::fs::read_to_string(path)?;
+    let passphrase = contents.lines().next().unwrap_or_default();
+    if passphrase.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} holds an empty passphrase", path.display()),
+        ));
+    }
+    Ok(passphrase.to_string())
+}
+
+#[derive(clap::Args, Debug)]
+struct HashArgs {
+    /// PDF file to hash
+    input: PathBuf,
+
+    /// Known-good hash manifest (JSON) to verify against
+    #[arg(long, value_name = "MANIFEST")]
+    verify: Option<PathBuf>,
+
+    /// Algorithms to compute (md5, sha1, sha256, sha512, sha3-256, blake3, xxh64)
+    #[arg(long, value_delimiter = ',', value_name = "ALGORITHMS")]
+    hash: Vec<HashAlgorithm>,
+}
+
+#[derive(clap::Args, Debug)]
+struct StatsArgs {
+    /// Run history file (JSONL) written by batch runs
+    history: PathBuf,
+
+    /// Bucket size for the trend report
+    #[arg(long, value_enum, default_value_t = Period::Day)]
+    period: Period,
+
+    /// Only include runs from the last N days
+    #[arg(long, value_name = "DAYS")]
+    since: Option<i64>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(clap::Args, Debug)]
+struct ProcessArgs {
+    /// Input PDF file path
+    #[arg(required = true)]
+    input: Option<PathBuf>,
+
+    /// Output PDF file path
+    #[arg(required = true)]
+    output: Option<PathBuf>,
+
+    /// Hashes to print for the output (comma-separated: md5, sha1, sha256, sha512, sha3-256, blake3, xxh64)
+    #[arg(long, value_delimiter = ',', value_name = "ALGORITHMS")]
+    hash: Vec<HashAlgorithm>,
 
     /// Document metadata (key=value pairs)
     #[arg(long, value_parser = parse_key_val)]
     metadata: Vec<(String, String)>,
 
+    /// Write every date in UTC, dropping the time zone offsets that place the author
+    #[arg(long)]
+    utc_dates: bool,
+
+    /// Make the Info dictionary and XMP agree on title, author, dates and tools after setting metadata
+    #[arg(long, value_enum, value_name = "PRECEDENCE")]
+    sync_xmp: Option<SyncXmp>,
+
     /// User encryption password
     #[arg(long)]
     encrypt_user: Option<String>,
@@ -47,9 +716,92 @@ struct Args {
     #[arg(long)]
     encrypt_owner: Option<String>,
 
-    /// Restrictions (comma-separated: print,copy,edit,annotate)
+    /// Restrictions (comma-separated: print,copy,edit,annotate,forms,assemble); without --encrypt-user
+    /// the document still opens freely, and only --encrypt-owner lifts them
     #[arg(long)]
     restrict: Option<String>,
+
+    /// Clean-and-rescan rounds before the output counts as unverified
+    #[arg(long, default_value_t = 3, value_name = "N")]
+    clean_rounds: usize,
+
+    /// What to do with embedded files
+    #[arg(long, value_enum, default_value_t = Attachments::Keep)]
+    attachments: Attachments,
+
+    /// Creation and modification dates of the embedded files kept
+    #[arg(long, value_enum, default_value_t = EmbeddedDates::Strip)]
+    attachment_dates: EmbeddedDates,
+
+    /// What to do with references that load or send data outside the document
+    #[arg(long, value_enum, default_value_t = Tracking::Remove)]
+    tracking: Tracking,
+
+    /// Directory `--tracking localize` may embed files from; names leading outside it are refused
+    #[arg(long, value_name = "DIR", required_if_eq("tracking", "localize"))]
+    localize_dir: Option<PathBuf>,
+
+    /// Only clean part of the document; repeat to combine (pages=3-5, type=attachments|metadata|javascript|...,
+    /// object=12, location=/Info/*). Document-level entries are on no page.
+    #[arg(long, value_name = "FILTER")]
+    scope: Vec<ScopeFilter>,
+
+    #[command(flatten)]
+    metadata_fields: MetadataArgs,
+
+    /// Decode untrusted streams and images in a confined child process before scanning
+    #[arg(long)]
+    sandbox: bool,
+
+    /// Draw form fields into the pages and remove the interactive form
+    #[arg(long)]
+    flatten_forms: bool,
+
+    /// Save form field values before cleaning (.fdf or .xfdf)
+    #[arg(long, value_name = "FILE")]
+    export_form_data: Option<PathBuf>,
+
+    /// Remove this text wherever a page draws it; repeat for several
+    #[arg(long, value_name = "TEXT")]
+    redact_text: Vec<String>,
+
+    /// Remove page text matching this regular expression, e.g. '\d{3}-\d{2}-\d{4}'; repeat for several
+    #[arg(long, value_name = "REGEX")]
+    redact_regex: Vec<String>,
+
+    /// Paint a black box where each redacted match was
+    #[arg(long)]
+    redact_boxes: bool,
+
+    #[command(flatten)]
+    patterns: PatternArgs,
+
+    #[command(flatten)]
+    evidence: EvidenceArgs,
+
+    #[command(flatten)]
+    report_encryption: ReportEncryptionArgs,
+
+    /// Embed a provenance record naming this cleaning policy (not with --restrict or --encrypt-*, since
+    /// `provenance` cannot read encrypted output back)
+    #[arg(long, value_name = "POLICY_ID", conflicts_with_all = ["restrict", "encrypt_user", "encrypt_owner"])]
+    provenance: Option<String>,
+
+    /// Extra provenance field (key=value; {tool}, {version}, {policy}, {timestamp} and {hash} are substituted)
+    #[arg(long, value_parser = parse_key_val, requires = "provenance")]
+    provenance_field: Vec<(String, String)>,
+
+    /// Leave the processing time out of the provenance record
+    #[arg(long, requires = "provenance")]
+    provenance_no_timestamp: bool,
+
+    /// Record the input hash, these arguments and the document digest after every stage, for `replay`
+    #[arg(long, value_name = "FILE")]
+    record_replay: Option<PathBuf>,
+
+    /// Command line of the run, written to replay records
+    #[arg(skip)]
+    argv: Vec<String>,
 }
 
 fn parse_key_val(s: &str) -> Result<(String, String), String> {
@@ -59,69 +811,1525 @@ fn parse_key_val(s: &str) -> Result<(String, String), String> {
 }
 
 fn main() -> Result<(), PipelineError> {
+    // Re-executed as the sandboxed decoder; confine before parsing anything else
+    let mut argv = std::env::args().skip(1);
+    if argv.next().as_deref() == Some(sandbox::WORKER_ARG) {
+        sandbox::run_worker(argv);
+    }
+
     let args = Args::parse();
+    if let Some(name) = &args.schema {
+        return run_schema(name);
+    }
 
-    // Initialize pipeline
-    let mut pipeline = PdfPipeline::new(&args.input)?;
-    
-    // Clean document
-    pipeline.clean_document()?;
+    let profiler = match &args.profile {
+        Some(path) => Some(Profiler::install(path).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?),
+        None => None,
+    };
 
-    // Set metadata
-    for (key, value) in args.metadata {
-        pipeline.set_metadata(key, value)?;
+    let result = match args.command {
+        Some(Command::Hash(hash_args)) => run_hash(hash_args),
+        Some(Command::Stats(stats_args)) => run_stats(stats_args),
+        Some(Command::Batch(batch_args)) => run_batch(batch_args),
+        Some(Command::Provenance(provenance_args)) => run_provenance(provenance_args),
+        Some(Command::Fill(fill_args)) => run_fill(fill_args),
+        Some(Command::StoreGc(gc_args)) => run_store_gc(gc_args),
+        Some(Command::Purge(purge_args)) => run_purge(purge_args),
+        Some(Command::QuickScan(quick_args)) => run_quick_scan(quick_args),
+        Some(Command::Rules(rules_command)) => run_rules(rules_command),
+        Some(Command::Graph(graph_args)) => run_graph(graph_args),
+        Some(Command::Inspect(inspect_args)) => run_inspect(inspect_args),
+        Some(Command::DecryptReport(decrypt_args)) => run_decrypt_report(decrypt_args),
+        Some(Command::Baseline(baseline_command)) => run_baseline(baseline_command),
+        Some(Command::Replay(replay_args)) => run_replay(replay_args),
+        Some(Command::Fixtures(fixtures_args)) => run_fixtures(fixtures_args),
+        Some(Command::Verify(verify_args)) => run_verify(verify_args),
+        None => run_process(ProcessArgs { argv: std::env::args().skip(1).collect(), ..args.process }),
+    };
+    if let (Some(profiler), Some(path)) = (profiler, &args.profile) {
+        profiler.finish()?;
+        println!("Profile: {}", path.display());
     }
+    result
+}
 
-    // Sync metadata
-    pipeline.sync_metadata()?;
+fn run_schema(name: &str) -> Result<(), PipelineError> {
+    let rendered = if name == "all" {
+        serde_json::to_string_pretty(&schema::all())
+    } else {
+        let Some(root) = schema::schema(name) else {
+            let names: Vec<&str> = schema::SCHEMAS.iter().map(|(name, _)| *name).collect();
+            return Err(PipelineError::Schema(format!("unknown schema `{}`; expected all, {}", name, names.join(", "))));
+        };
+        serde_json::to_string_pretty(&root)
+    };
+    println!("{}", rendered.map_err(std::io::Error::from)?);
+    Ok(())
+}
 
-    // Set encryption if requested
-    pipeline.set_encryption(args.encrypt_user, args.encrypt_owner);
+fn run_hash(args: HashArgs) -> Result<(), PipelineError> {
+    let algorithms = if args.hash.is_empty() { HashAlgorithm::DEFAULT } else { &args.hash };
+    let hashes = hash::hash_file_with(&args.input, 1024 * 1024, algorithms)?;
 
-    // Set restrictions if any
-    if let Some(restrictions) = args.restrict {
-        pipeline.set_restrictions(
-            restrictions.split(',')
-                .map(str::to_string)
-                .collect()
-        );
+    let Some(manifest) = args.verify else {
+        for (algorithm, digest) in hashes.computed() {
+            println!("{}: {}", algorithm.name().to_uppercase(), digest);
+        }
+        return Ok(());
+    };
+
+    let expected = DocumentHashes::load_manifest(&manifest)?;
+    let verification = hash::compare_hashes(&hashes, &expected);
+
+    for comparison in &verification.comparisons {
+        match comparison.status {
+            HashMatchStatus::Match => println!("✅ {}: match", comparison.algorithm),
+            HashMatchStatus::Skipped if comparison.expected.is_empty() => {}
+            HashMatchStatus::Skipped => println!("➖ {}: not computed", comparison.algorithm),
+            HashMatchStatus::Mismatch => println!(
+                "❌ {}: expected {} got {}",
+                comparison.algorithm, comparison.expected, comparison.actual
+            ),
+        }
     }
 
-    // Apply security features
-    pipeline.apply_security()?;
+    if verification.verified {
+        println!("✅ Hashes verified against {}", manifest.display());
+        Ok(())
+    } else {
+        let failed: Vec<String> = verification.mismatches()
+            .map(|c| c.algorithm.to_string())
+            .collect();
+        Err(PipelineError::HashVerification(if failed.is_empty() {
+            "manifest shares no cryptographic hash algorithm with those computed".to_string()
+        } else {
+            format!("mismatch for {}", failed.join(", "))
+        }))
+    }
+}
 
-    // Save the processed PDF
-    pipeline.save(&args.output)?;
+/// The given files, and the PDFs in the given directories
+fn pdf_files(inputs: &[PathBuf]) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut found: Vec<PathBuf> = std::fs::read_dir(input)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")))
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(input.clone());
+        }
+    }
+    Ok(files)
+}
 
-    // Verify the output
-    if pipeline.verify()? {
-        println!("✅ PDF processed successfully!");
-        
-        // Calculate requested hashes
-        if args.md5 || args.sha1 || args.sha256 {
-            use sha2::{Sha256, Digest};
-            use md5::Md5;
-            use sha1::Sha1;
-            
-            let content = std::fs::read(&args.output)?;
-            
-            if args.md5 {
-                let hash = Md5::digest(&content);
-                println!("MD5: {:x}", hash);
-            }
-            
-            if args.sha1 {
-                let hash = Sha1::digest(&content);
-                println!("SHA1: {:x}", hash);
-            }
-            
-            if args.sha256 {
-                let hash = Sha256::digest(&content);
-                println!("SHA256: {:x}", hash);
+fn run_baseline(command: BaselineCommand) -> Result<(), PipelineError> {
+    match command {
+        BaselineCommand::Learn { corpus, output } => {
+            let mut baseline = StructuralBaseline::new();
+            for (path, e) in baseline.learn_files(&pdf_files(&corpus)?) {
+                println!("⚠️ Skipped {}: {}", path, e);
+            }
+            baseline.save(&output)?;
+            println!("Baseline of {} document(s) written to {}", baseline.documents(), output.display());
+        }
+        BaselineCommand::Score { baseline, inputs, json } => {
+            let baseline = StructuralBaseline::load(&baseline)?;
+            for file in pdf_files(&inputs)? {
+                let loaded = std::fs::read(paths::extended(&file))
+                    .map_err(lopdf::Error::from)
+                    .and_then(|data| xref_integrity::load_checked(&data));
+                let doc = match loaded {
+                    Ok(doc) => doc,
+                    Err(e) => {
+                        println!("❌ {}: {}", file.display(), e);
+                        continue;
+                    }
+                };
+                let score = baseline.score(&StructuralProfile::of(&doc));
+                if json {
+                    let line = BaselineScoreLine { path: file, anomaly: score };
+                    println!("{}", serde_json::to_string(&line).map_err(std::io::Error::from)?);
+                } else {
+                    let top: Vec<String> = score.deviations.iter().take(3)
+                        .map(|d| format!("{} {:.2} (corpus {:.2})", d.feature, d.value, d.mean))
+                        .collect();
+                    println!("{:.2}  {}  {}", score.score, file.display(), top.join(", "));
+                }
             }
         }
+    }
+    Ok(())
+}
+
+fn run_graph(args: GraphArgs) -> Result<(), PipelineError> {
+    args.patterns.install()?;
+    let pipeline = PdfPipeline::new(&args.input)?;
+    let artifacts = pipeline.scan(&args.input);
+    let mut graph = pipeline.object_graph(&artifacts);
+    if args.risky_only {
+        graph = graph.risky_only();
+    }
+
+    let format = args.format.map(GraphFormat::from).unwrap_or_else(|| {
+        let graphml = args.output.as_ref()
+            .and_then(|path| path.extension())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("graphml"));
+        if graphml { GraphFormat::GraphMl } else { GraphFormat::Dot }
+    });
+    let rendered = graph.render(format);
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, rendered)?;
+            println!(
+                "Graph of {} object(s) and {} reference(s) written to {}",
+                graph.nodes.len(), graph.edges.len(), path.display(),
+            );
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+fn run_inspect(args: InspectArgs) -> Result<(), PipelineError> {
+    use std::io::{BufRead, Write};
+
+    let doc = xref_integrity::load_checked(&std::fs::read(paths::extended(&args.input))?)?;
+    let inspector = Inspector::new(&doc).raw(args.raw);
+    if let Some(target) = &args.target {
+        print!("{}", inspector.show(target.parse()?)?);
+        return Ok(());
+    }
+
+    // Interactive: one target per line until quit or end of input
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else { break };
+        let line = line.trim();
+        match line {
+            "" => continue,
+            "q" | "quit" | "exit" => break,
+            _ => match line.parse().and_then(|target| inspector.show(target)) {
+                Ok(shown) => print!("{}", shown),
+                Err(e) => eprintln!("{}", e),
+            },
+        }
+    }
+    Ok(())
+}
+
+fn run_decrypt_report(args: DecryptReportArgs) -> Result<(), PipelineError> {
+    let key = match (&args.identity, &args.passphrase_file) {
+        (Some(identity), _) => ReportKey::identity_file(identity)?,
+        (None, Some(path)) => ReportKey::passphrase(read_passphrase(path)?),
+        (None, None) => unreachable!("clap requires --identity or --passphrase-file"),
+    };
+    let data = std::fs::read(paths::extended(&args.input))?;
+    if !encrypted::is_encrypted(&data) {
+        return Err(ReportCryptoError::Key(format!("{} is not an encrypted report", args.input.display())).into());
+    }
+    let report = encrypted::decrypt(&data, &key)?;
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, &report)?;
+            println!("Report decrypted to {}", path.display());
+        }
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(&report)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_provenance(args: ProvenanceArgs) -> Result<(), PipelineError> {
+    let doc = xref_integrity::load_checked(&std::fs::read(paths::extended(&args.input))?)?;
+    let (record, intact) = match provenance::verify(&doc)? {
+        ProvenanceStatus::Missing => {
+            println!("No provenance record in {}", args.input.display());
+            return Ok(());
+        }
+        ProvenanceStatus::Intact(record) => (record, true),
+        ProvenanceStatus::Modified { record, .. } => (record, false),
+    };
+
+    println!("Tool: {} {}", record.tool, record.tool_version);
+    println!("Policy: {}", record.policy_id);
+    if let Some(processed_at) = record.processed_at {
+        println!("Processed: {}", processed_at.to_rfc3339());
+    }
+    println!("Content SHA256: {}", record.content_sha256);
+    for (key, value) in &record.fields {
+        println!("{}: {}", key, value);
+    }
+    if intact {
+        println!("✅ Content unchanged since processing");
+    } else {
+        println!("❌ Content modified after processing");
+    }
+    Ok(())
+}
+
+fn run_replay(args: ReplayArgs) -> Result<(), PipelineError> {
+    let recorded = ReplayRecord::load(&args.record)?;
+    let program = std::env::args().next().unwrap_or_default();
+    let parsed = Args::try_parse_from(std::iter::once(program).chain(recorded.args.iter().cloned()))
+        .map_err(|e| PipelineError::Replay(format!("recorded arguments no longer parse: {}", e)))?;
+    if parsed.command.is_some() || parsed.schema.is_some() {
+        return Err(PipelineError::Replay("record is not of a processing run".to_string()));
+    }
+    println!(
+        "Replaying {} ({} stage(s), recorded by version {} at {})",
+        args.record.display(), recorded.stages.len(), recorded.tool_version, recorded.recorded_at.to_rfc3339(),
+    );
+
+    // Everything the replay writes goes to a scratch directory that is removed afterwards
+    let scratch = std::env::temp_dir().join(format!("replay-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&scratch)?;
+    let record_path = scratch.join("replay.json");
+    let input = args.input.unwrap_or_else(|| recorded.input.clone());
+    let process = replay_process_args(parsed.process, input, scratch.join("output.pdf"), record_path.clone(), recorded.args.clone());
+    let replayed = run_process(process).and_then(|()| Ok(ReplayRecord::load(&record_path)?));
+    let _ = std::fs::remove_dir_all(&scratch);
+    let diff = recorded.diff(&replayed?);
+
+    if diff.input_changed {
+        println!("⚠️ Input differs from the recorded one; differences are expected");
+    }
+    let describe = |stage: Option<&StageRecord>| match stage {
+        Some(stage) => format!("{} {} ({} objects)", stage.stage, &stage.digest[..16.min(stage.digest.len())], stage.objects),
+        None => "(no stage)".to_string(),
+    };
+    for stage in &diff.stages {
+        println!("   #{} expected {}", stage.index, describe(stage.expected.as_ref()));
+        println!("   #{} replayed {}", stage.index, describe(stage.actual.as_ref()));
+    }
+    match diff.first_divergence() {
+        _ if diff.is_empty() => {
+            println!("✅ Replay matches the record at every stage");
+            Ok(())
+        }
+        Some(first) => {
+            let stage = first.expected.as_ref().or(first.actual.as_ref()).map_or("", |stage| stage.stage.as_str());
+            println!("❌ First divergence at stage #{} ({})", first.index, stage);
+            Err(PipelineError::Replay(format!("{} stage(s) differ, the first is #{} ({})", diff.stages.len(), first.index, stage)))
+        }
+        None if diff.output_changed => {
+            println!("❌ Every stage matches but the written output differs");
+            Err(PipelineError::Replay("output differs from the record".to_string()))
+        }
+        None => Err(PipelineError::Replay("input differs from the record".to_string())),
+    }
+}
+
+/// The recorded run's arguments, writing only `output`, the evidence log
+/// beside it and the replay `record`
+///
+/// The fields are listed one by one, so an option added to `ProcessArgs`
+/// does not compile until replay decides whether it writes elsewhere.
+fn replay_process_args(
+    recorded: ProcessArgs,
+    input: PathBuf,
+    output: PathBuf,
+    record: PathBuf,
+    argv: Vec<String>,
+) -> ProcessArgs {
+    let ProcessArgs {
+        // Replaced below
+        input: _,
+        output: _,
+        export_form_data: _,
+        record_replay: _,
+        argv: _,
+        // Only read, or written beside the output (the evidence log)
+        hash,
+        metadata,
+        utc_dates,
+        sync_xmp,
+        encrypt_user,
+        encrypt_owner,
+        restrict,
+        clean_rounds,
+        attachments,
+        attachment_dates,
+        tracking,
+        localize_dir,
+        scope,
+        metadata_fields,
+        sandbox,
+        flatten_forms,
+        redact_text,
+        redact_regex,
+        redact_boxes,
+        patterns,
+        evidence,
+        report_encryption,
+        provenance,
+        provenance_field,
+        provenance_no_timestamp,
+    } = recorded;
+    ProcessArgs {
+        input: Some(input),
+        output: Some(output),
+        // Form data export happens before any stage, so replay has nothing to compare it with
+        export_form_data: None,
+        record_replay: Some(record),
+        argv,
+        hash,
+        metadata,
+        utc_dates,
+        sync_xmp,
+        encrypt_user,
+        encrypt_owner,
+        restrict,
+        clean_rounds,
+        attachments,
+        attachment_dates,
+        tracking,
+        localize_dir,
+        scope,
+        metadata_fields,
+        sandbox,
+        flatten_forms,
+        redact_text,
+        redact_regex,
+        redact_boxes,
+        patterns,
+        evidence,
+        report_encryption,
+        provenance,
+        provenance_field,
+        provenance_no_timestamp,
+    }
+}
+
+fn run_verify(args: VerifyArgs) -> Result<(), PipelineError> {
+    let policy = match &args.policy {
+        Some(path) => VerificationPolicy::load(path)?,
+        None => VerificationPolicy::default(),
+    };
+    let doc = xref_integrity::load_checked(&std::fs::read(paths::extended(&args.input))?)?;
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    let result = runtime.block_on(async {
+        let system = VerificationSystem::new(&EngineConfig::default()).await?;
+        system.verify_document(&doc, Some(policy.config())).await
+    }).map_err(|e| PipelineError::Verification(e.to_string()))?;
+    let verdict = policy.evaluate(&result);
+
+    for check in &verdict.checks {
+        match (check.ran, check.failures) {
+            (false, _) => println!("➖ {}: not checked", check.check),
+            (true, 0) => println!("✅ {}: pass", check.check),
+            (true, failures) => println!("❌ {}: {} error(s)", check.check, failures),
+        }
+    }
+    let location = |id: Option<lopdf::ObjectId>| id.map(|(n, g)| format!(" ({} {} R)", n, g)).unwrap_or_default();
+    for error in &verdict.failures {
+        println!("   {} [{:?}] {}{}", error.code, error.severity, error.message, location(error.location));
+    }
+    for error in &verdict.tolerated {
+        println!("   {} [{:?}, tolerated] {}{}", error.code, error.severity, error.message, location(error.location));
+    }
+    for warning in &result.warnings {
+        println!("⚠️ {}: {}{}", warning.code, warning.message, location(warning.location));
+    }
+
+    if verdict.passed() {
+        println!("✅ {} passes verification", args.input.display());
+        Ok(())
+    } else {
+        let mut codes: Vec<&str> = verdict.failures.iter().map(|error| error.code.as_str())
+            .chain(verdict.failing_warnings.iter().map(String::as_str))
+            .collect();
+        codes.sort_unstable();
+        codes.dedup();
+        println!("❌ {} fails verification", args.input.display());
+        Err(PipelineError::Verification(format!("failed with {}", codes.join(", "))))
+    }
+}
+
+fn run_fixtures(args: FixturesArgs) -> Result<(), PipelineError> {
+    let fixtures = if args.only.is_empty() { Fixture::ALL.to_vec() } else { args.only };
+    std::fs::create_dir_all(&args.output_dir)?;
+    for fixture in fixtures {
+        let path = fixture.write(&args.output_dir)?;
+        println!("{}: {}", path.display(), fixture.description());
+    }
+    Ok(())
+}
+
+fn run_fill(args: FillArgs) -> Result<(), PipelineError> {
+    let data = FormData::parse(&std::fs::read(&args.data)?)?;
+    let mut pipeline = PdfPipeline::new(&args.template)?;
+    let report = pipeline.import_form_data(&data)?;
+    for name in &report.unknown {
+        println!("⚠️ No field named {} in {}", name, args.template.display());
+    }
+    pipeline.save(&args.output)?;
+    println!("✅ Filled {} field(s) into {}", report.filled, args.output.display());
+    Ok(())
+}
+
+fn run_rules(command: RulesCommand) -> Result<(), PipelineError> {
+    match command {
+        RulesCommand::Export { output } => {
+            let database = PatternDatabase::builtin();
+            if output.extension().is_some_and(|ext| ext == "yaml" || ext == "yml") {
+                std::fs::write(&output, database.to_yaml())?;
+            } else {
+                database.save(&output)?;
+            }
+            println!("✅ Wrote pattern database {} ({} rules) to {}", database.version(), database.rules().len(), output.display());
+        }
+        RulesCommand::Compile { source, output } => {
+            let database = PatternDatabase::load(&source)?;
+            database.save(&output)?;
+            println!("✅ Compiled pattern database {} ({} rules) to {}", database.version(), database.rules().len(), output.display());
+        }
+        RulesCommand::Info { database } => {
+            let database = match database {
+                Some(path) => PatternDatabase::load(&path)?,
+                None => PatternDatabase::builtin(),
+            };
+            println!("Pattern database {} released {}", database.version(), database.released());
+            for rule in database.rules() {
+                let pattern = match &rule.kind {
+                    PatternKind::Key(name) => format!("key /{}", name),
+                    PatternKind::RawName(name) => format!("raw /{}", name),
+                    PatternKind::InfoKey(name) => format!("info /{}", name),
+                    PatternKind::Idiom(regex) => format!("idiom {}", regex),
+                };
+                println!("{:<24} {:<8} {:<30} {}", rule.id, format!("{:?}", rule.risk_level), pattern, rule.description);
+            }
+        }
+        RulesCommand::Test(args) => return run_rules_test(args),
+    }
+    Ok(())
+}
+
+fn run_rules_test(args: RulesTestArgs) -> Result<(), PipelineError> {
+    let database = Arc::new(PatternDatabase::load(&args.database)?);
+    let report = RuleTester::new(database).run(&args.fixtures)?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report).map_err(std::io::Error::from)?);
+    } else {
+        let percent = |ratio: Option<f64>| ratio.map_or("-".to_string(), |r| format!("{:.0}%", r * 100.0));
+        println!("Pattern database {} over {} fixture(s)", report.database_version, report.fixtures);
+        println!("{:<24} {:>4} {:>4} {:>4} {:>10} {:>8}", "rule", "tp", "fp", "fn", "precision", "recall");
+        for rule in &report.rules {
+            println!("{:<24} {:>4} {:>4} {:>4} {:>10} {:>8}",
+                rule.id,
+                rule.true_positives,
+                rule.false_positives,
+                rule.false_negatives,
+                percent(rule.precision()),
+                percent(rule.recall()),
+            );
+            for fixture in &rule.missed {
+                println!("{:<24}   missed {}", "", fixture);
+            }
+            for fixture in &rule.false_alarms {
+                println!("{:<24}   false alarm {}", "", fixture);
+            }
+        }
+        for fixture in &report.unparsed {
+            println!("⚠️ {} did not parse; only raw rules were checked", fixture);
+        }
+        for label in &report.unknown_labels {
+            println!("⚠️ Unknown rule in labels: {}", label);
+        }
+    }
+
+    let failures = report.failures(args.min_precision, args.min_recall);
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        let ids: Vec<&str> = failures.iter().map(|rule| rule.id.as_str()).collect();
+        Err(PipelineError::RuleTest(format!("below threshold: {}", ids.join(", "))))
+    }
+}
+
+fn run_quick_scan(args: QuickScanArgs) -> Result<(), PipelineError> {
+    args.patterns.install()?;
+    let files = pdf_files(&args.inputs)?;
+    if args.profile == ScanProfile::BulkTriage {
+        return run_bulk_triage(&files, &args);
+    }
+
+    let scanner = QuickScanner::new();
+    for file in &files {
+        let report = match scanner.scan_file(file) {
+            Ok(report) => report,
+            Err(e) => {
+                println!("❌ {}: {}", file.display(), e);
+                continue;
+            }
+        };
+        if args.json {
+            let line = QuickScanLine { path: file.clone(), report };
+            println!("{}", serde_json::to_string(&line).map_err(std::io::Error::from)?);
+        } else {
+            let hits: Vec<String> = report.hits.iter().map(|(name, count)| format!("/{name}×{count}")).collect();
+            println!(
+                "{:?} ~{:.2} {} {}",
+                report.risk_level,
+                report.score,
+                file.display(),
+                hits.join(" "),
+            );
+        }
+    }
+    if !args.json {
+        println!("Quick scan of {} file(s); estimates are approximate, run a full scan before acting", files.len());
+    }
+    Ok(())
+}
+
+fn run_bulk_triage(files: &[PathBuf], args: &QuickScanArgs) -> Result<(), PipelineError> {
+    let triage = BulkTriage::new(TriageProfile { threads: args.threads, ..TriageProfile::bulk() });
+    let run = triage.run(files)?;
+    for record in &run.records {
+        if args.json {
+            println!("{}", serde_json::to_string(record).map_err(std::io::Error::from)?);
+        } else {
+            let hits: Vec<String> = record.report.hits.iter().map(|(name, count)| format!("/{name}×{count}")).collect();
+            println!(
+                "{:?} ~{:.2} {} {}{} {}",
+                record.report.risk_level,
+                record.report.score,
+                record.sha256,
+                record.path.display(),
+                if record.truncated { " (prefix scanned)" } else { "" },
+                hits.join(" "),
+            );
+        }
+    }
+    for (path, e) in &run.failures {
+        println!("❌ {}: {}", path.display(), e);
+    }
+    if !args.json {
+        println!(
+            "Triaged {} file(s) in {:.2}s on {} thread(s): {:.0} docs/min, {:.1} MB/s",
+            run.records.len(),
+            run.elapsed.as_secs_f64(),
+            run.threads,
+            run.docs_per_minute(),
+            run.bytes_per_second() / 1e6,
+        );
+    }
+    Ok(())
+}
+
+fn run_stats(args: StatsArgs) -> Result<(), PipelineError> {
+    let since = args.since.map(|days| chrono::Utc::now() - chrono::Duration::days(days));
+    let records = MetricsHistory::new(&args.history).load(since)?;
+    if records.is_empty() {
+        println!("No runs recorded in {}", args.history.display());
+        return Ok(());
+    }
+
+    let period = match args.period {
+        Period::Day => TrendPeriod::Day,
+        Period::Week => TrendPeriod::Week,
+        Period::Month => TrendPeriod::Month,
+    };
+
+    println!("{:<12} {:>6} {:>10} {:>8} {:>10} {:>12} {:>12}",
+        "period", "runs", "documents", "failed", "artifacts", "ms/document", "longest run");
+    for bucket in MetricsHistory::trend(&records, period) {
+        println!("{:<12} {:>6} {:>10} {:>8} {:>10} {:>12.1} {:>11}s",
+            bucket.start.to_string(),
+            bucket.runs,
+            bucket.documents_processed,
+            bucket.documents_failed,
+            bucket.total_artifacts(),
+            bucket.mean_document_ms,
+            bucket.max_run_ms / 1000,
+        );
+        for (kind, count) in &bucket.artifacts_by_type {
+            println!("{:<12}   {}: {}", "", kind, count);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_batch(args: BatchArgs) -> Result<(), PipelineError> {
+    args.patterns.install()?;
+    // Outputs, sidecars and evidence logs all derive from this, so long names work for each
+    let output_root = paths::extended(&args.output_dir);
+    std::fs::create_dir_all(&output_root)?;
+    // Outputs are named after their inputs, so a shared directory overwrites the originals
+    let input_root = std::fs::canonicalize(paths::extended(&args.input_dir))?;
+    if input_root == std::fs::canonicalize(&output_root)? && !args.in_place {
+        return Err(PipelineError::BatchAborted(format!(
+            "{} is both input and output directory; pass --in-place to overwrite the inputs",
+            args.input_dir.display(),
+        )));
+    }
+    let evidence = args.evidence.resolve()?;
+    let report_encryption = args.report_encryption.resolve()?;
+    // Evidence logs describe the transformations of each run, which a
+    // stored output skips
+    let store = match &args.store {
+        Some(_) if evidence.is_some() => {
+            println!("⚠️ Output store ignored in evidence mode");
+            None
+        }
+        Some(dir) => Some(OutputStore::open(dir)?),
+        None => None,
+    };
+
+    // Listed paths keep their OsStr names, UTF-8 or not
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(paths::extended(&args.input_dir))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+    let (archives, inputs): (Vec<PathBuf>, Vec<PathBuf>) = entries.into_iter()
+        .filter(|path| is_archive(path) || path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")))
+        .partition(|path| is_archive(path));
+
+    let scope = CleaningScope::from_filters(args.scope.iter().cloned());
+    let metadata = args.metadata.policy();
+    // Outputs of a transactional batch go to a staging area inside the output directory
+    let staging = if args.transactional { Some(StagingArea::new(&*output_root)?) } else { None };
+    let output_dir = staging.as_ref().map_or(&*output_root, StagingArea::path);
+    let aggregate = args.aggregate.as_ref().map(|_| {
+        RefCell::new(FleetAggregator::new(AggregateConfig { epsilon: args.aggregate_epsilon, ..Default::default() }))
+    });
+    let options = BatchOptions {
+        sidecar: args.sidecar,
+        explain: args.explain,
+        full: args.full,
+        thumbnails: args.thumbnails,
+        heatmap_thumbnails: args.heatmap_thumbnails,
+        object_map: args.object_map,
+        visual_check: args.visual_check,
+        preserve_visual: args.preserve_visual,
+        sandbox: args.sandbox,
+        clean_rounds: args.clean_rounds,
+        attachments: args.attachments.into(),
+        attachment_dates: args.attachment_dates.into(),
+        tracking: args.tracking.into(),
+        localize_dir: args.localize_dir.as_deref(),
+        scope: &scope,
+        metadata: &metadata,
+        route_by_language: args.route_by_language,
+        store: store.as_ref(),
+        report_encryption: report_encryption.as_ref(),
+        aggregate: aggregate.as_ref(),
+    };
+    let mut processed = inputs.len();
+    let mut failed = 0;
+    let mut unverified = 0;
+    for input in &inputs {
+        if args.split_portfolios {
+            match split_one(input, output_dir) {
+                Ok(Some(written)) => {
+                    println!("✅ {}: split into {} file(s)", input.display(), written);
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    failed += 1;
+                    println!("❌ {}: {}", input.display(), e);
+                    continue;
+                }
+            }
+        }
+
+        let output = match file_name(input) {
+            Ok(name) => output_dir.join(name),
+            Err(e) => {
+                failed += 1;
+                println!("❌ {}: {}", input.display(), e);
+                continue;
+            }
+        };
+        match clean_one(input, &output, &options, evidence.as_ref()) {
+            Ok(Cleaned { verified: true, stored: None, .. }) => println!("✅ {}", input.display()),
+            Ok(Cleaned { verified: true, stored: Some(placement), .. }) => {
+                println!("✅ {} (from store, {:?})", input.display(), placement)
+            }
+            Ok(Cleaned { verified: false, .. }) => {
+                unverified += 1;
+                println!("⚠️ {}: output verification failed", input.display())
+            }
+            Err(e) => {
+                failed += 1;
+                println!("❌ {}: {}", input.display(), e);
+            }
+        }
+        if staging.is_some() && failed + unverified > 0 {
+            // Nothing will be published, so the rest need not be cleaned
+            break;
+        }
+    }
+
+    for archive in &archives {
+        if staging.is_some() && failed + unverified > 0 {
+            break;
+        }
+        match clean_archive(archive, output_dir, &options, evidence.as_ref(), args.repack_archives) {
+            Ok(counts) => {
+                processed += counts.members;
+                failed += counts.failed;
+                unverified += counts.unverified;
+            }
+            Err(e) => {
+                failed += 1;
+                println!("❌ {}: {}", archive.display(), e);
+            }
+        }
+    }
+
+    if let Some(staging) = staging {
+        if failed + unverified > 0 {
+            // Dropping the staging area discards every staged output
+            return Err(PipelineError::BatchAborted(format!(
+                "{} failed, {} unverified; no output published to {}",
+                failed, unverified, args.output_dir.display(),
+            )));
+        }
+        let published = staging.publish()?;
+        println!("📦 Published {} file(s) to {}", published.len(), args.output_dir.display());
+    }
+
+    if let (Some(path), Some(aggregate)) = (&args.aggregate, aggregate) {
+        aggregate.into_inner().release().write(path)?;
+        println!("📊 Anonymized statistics written to {}", path.display());
+    }
+    println!("Processed {} file(s), {} failed", processed, failed);
+    if failed + unverified > 0 {
+        return Err(PipelineError::BatchIncomplete(format!("{} failed, {} unverified", failed, unverified)));
+    }
+    Ok(())
+}
+
+/// Whether `path` names a .zip, .tar.gz or .tgz archive
+fn is_archive(path: &std::path::Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy().to_ascii_lowercase();
+        [".zip", ".tar.gz", ".tgz"].iter().any(|suffix| name.ends_with(suffix))
+    })
+}
+
+/// Members of one archive input, and how many of them failed or did not verify
+#[derive(Default)]
+struct ArchiveCounts {
+    members: usize,
+    failed: usize,
+    unverified: usize,
+}
+
+/// Cleans the PDFs inside an archive
+///
+/// Outputs go to `<output_dir>/<archive name>/`, or with `repack` into a
+/// sanitized archive of the same name and format; either way
+/// `<output_dir>/<archive name>.mapping.json` maps members to outputs.
+#[cfg(feature = "archive")]
+fn clean_archive(
+    archive: &std::path::Path,
+    output_dir: &std::path::Path,
+    options: &BatchOptions,
+    evidence: Option<&Evidence>,
+    repack: bool,
+) -> Result<ArchiveCounts, PipelineError> {
+    use pdf_engine::archive::{write_archive, ArchiveError, ArchiveMapping, ExtractedArchive, MemberMapping};
+
+    let archive_error = |e: ArchiveError| PipelineError::Archive(e.to_string());
+    let extracted = ExtractedArchive::open(archive).map_err(archive_error)?;
+    let name = file_name(archive)?.to_string_lossy().into_owned();
+    // Outputs to repack are cleaned into a scratch area, deleted once packed
+    let scratch = if repack { Some(StagingArea::new(output_dir)?) } else { None };
+    let root = scratch.as_ref().map_or_else(|| output_dir.join(extracted.format.stem(&name)), |s| s.path().to_path_buf());
+
+    let mut counts = ArchiveCounts { members: extracted.members.len(), ..Default::default() };
+    let mut members = Vec::with_capacity(extracted.members.len());
+    for member in &extracted.members {
+        let label = format!("{}:{}", archive.display(), member.name);
+        let output = root.join(&member.name);
+        let cleaned = output.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(PipelineError::from)
+            .and_then(|_| clean_one(&member.path, &output, options, evidence));
+        let mapping = match cleaned {
+            Ok(cleaned) => {
+                if cleaned.verified {
+                    println!("✅ {}", label);
+                } else {
+                    counts.unverified += 1;
+                    println!("⚠️ {}: output verification failed", label);
+                }
+                let output = match &scratch {
+                    Some(scratch) => cleaned.output.strip_prefix(scratch.path()).map(std::path::Path::to_path_buf).ok(),
+                    None => Some(cleaned.output),
+                };
+                MemberMapping { member: member.name.clone(), output, verified: cleaned.verified, error: None }
+            }
+            Err(e) => {
+                counts.failed += 1;
+                println!("❌ {}: {}", label, e);
+                MemberMapping { member: member.name.clone(), output: None, verified: false, error: Some(e.to_string()) }
+            }
+        };
+        members.push(mapping);
+    }
+
+    let repacked = match &scratch {
+        Some(scratch) => {
+            let path = output_dir.join(&name);
+            // Only the cleaned members; sidecars and anything else in scratch stay out
+            let outputs: Vec<PathBuf> = members.iter().filter_map(|m| m.output.clone()).collect();
+            let packed = write_archive(extracted.format, scratch.path(), &outputs, &path).map_err(archive_error)?;
+            println!("📦 {}: {} file(s) repacked into {}", archive.display(), packed, path.display());
+            Some(path)
+        }
+        None => None,
+    };
+    let mapping = ArchiveMapping { archive: archive.to_path_buf(), repacked, members };
+    mapping.write(&ArchiveMapping::path_for(output_dir, extracted.format, archive))?;
+    Ok(counts)
+}
+
+#[cfg(not(feature = "archive"))]
+fn clean_archive(
+    archive: &std::path::Path,
+    _output_dir: &std::path::Path,
+    _options: &BatchOptions,
+    _evidence: Option<&Evidence>,
+    _repack: bool,
+) -> Result<ArchiveCounts, PipelineError> {
+    println!("⚠️ {}: skipped; archive inputs need the `archive` feature", archive.display());
+    Ok(ArchiveCounts::default())
+}
+
+/// Splits a portfolio into `<output_dir>/<stem>/`; `None` when the input is not a portfolio
+fn split_one(input: &std::path::Path, output_dir: &std::path::Path) -> Result<Option<usize>, PipelineError> {
+    let pipeline = PdfPipeline::new(input)?;
+    if !pipeline.is_portfolio() {
+        return Ok(None);
+    }
+    let stem = input.file_stem()
+        .ok_or_else(|| PipelineError::InvalidInput(format!("{} does not name a file", input.display())))?;
+    Ok(Some(pipeline.split_portfolio(&output_dir.join(stem))?.len()))
+}
+
+/// Per-file settings of a batch run
+struct BatchOptions<'a> {
+    sidecar: bool,
+    explain: bool,
+    full: bool,
+    thumbnails: usize,
+    heatmap_thumbnails: usize,
+    object_map: bool,
+    visual_check: bool,
+    preserve_visual: bool,
+    sandbox: bool,
+    clean_rounds: usize,
+    attachments: AttachmentPolicy,
+    attachment_dates: AttachmentDates,
+    tracking: Option<TrackingAction>,
+    localize_dir: Option<&'a std::path::Path>,
+    scope: &'a CleaningScope,
+    metadata: &'a MetadataPolicy,
+    route_by_language: bool,
+    store: Option<&'a OutputStore>,
+    /// Encrypts sidecars and evidence logs when set
+    report_encryption: Option<&'a ReportEncryption>,
+    /// Counts every cleaned file for anonymized statistics when set
+    aggregate: Option<&'a RefCell<FleetAggregator>>,
+}
+
+impl BatchOptions<'_> {
+    /// Settings that change the cleaned bytes, keying the output store
+    fn fingerprint(&self) -> serde_json::Value {
+        let mut fingerprint = serde_json::json!({
+            "clean_rounds": self.clean_rounds,
+            "attachments": self.attachments,
+            "tracking": self.tracking,
+            "scope": self.scope,
+            "pattern_db_version": patterns::active().version(),
+        });
+        // Only when set, so outputs stored before the option existed still match
+        if self.preserve_visual {
+            fingerprint["preserve_visual"] = true.into();
+        }
+        if let Some(dir) = self.localize_dir {
+            fingerprint["localize_dir"] = dir.display().to_string().into();
+        }
+        if self.sandbox {
+            fingerprint["sandbox"] = true.into();
+        }
+        if !self.metadata.is_default() {
+            fingerprint["metadata"] = serde_json::json!(self.metadata);
+        }
+        // Kept attachments are normalized, which changes their bytes
+        if self.attachments != AttachmentPolicy::Strip {
+            fingerprint["attachment_dates"] = format!("{:?}", self.attachment_dates).into();
+        }
+        fingerprint
+    }
+}
+
+/// Outcome of cleaning one file of a batch
+struct Cleaned {
+    verified: bool,
+    /// Where the output was written, after routing
+    #[cfg_attr(not(feature = "archive"), allow(dead_code))]
+    output: PathBuf,
+    /// How the output was placed when it came from the store
+    stored: Option<Placement>,
+}
+
+/// What the store keeps beside an output to stand in for a fresh run
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredRun {
+    verified: bool,
+    scan: ScanSummary,
+    cleaning_actions: Vec<String>,
+    clean_rounds: usize,
+    remaining: ScanSummary,
+    hashes: DocumentHashes,
+    #[serde(default)]
+    findings: Vec<ExplainedFinding>,
+    #[serde(default)]
+    rollup: Vec<RolledUpFinding>,
+    #[serde(default)]
+    heatmap: Vec<PageRisk>,
+    #[serde(default)]
+    object_map: Vec<ObjectMapping>,
+    #[serde(default)]
+    accessibility: Option<AccessibilityImpact>,
+}
+
+/// Cleans one file of a batch, or places its stored output
+fn clean_one(
+    input: &std::path::Path,
+    output: &std::path::Path,
+    options: &BatchOptions,
+    evidence: Option<&Evidence>,
+) -> Result<Cleaned, PipelineError> {
+    let stored = match options.store {
+        Some(store) => {
+            let input_sha256 = hash::hash_file_with(input, 1024 * 1024, &[HashAlgorithm::Sha256])?.sha256;
+            let key = OutputStore::key(&input_sha256, &options.fingerprint())?;
+            Some((store, input_sha256, key))
+        }
+        None => None,
+    };
+    if let Some((store, _, key)) = &stored {
+        if let Some(entry) = store.get(key)? {
+            let run = serde_json::from_value::<StoredRun>(entry.data).ok()
+                // Runs stored without --explain have no evidence to offer
+                .filter(|run| !options.explain || run.scan.total == 0 || !run.findings.is_empty())
+                // and runs stored before object maps were kept have none
+                .filter(|run| !options.object_map || !run.object_map.is_empty());
+            if let Some(run) = run {
+                let output = routed_output(output, options, &run.scan)?;
+                if let Some((_, placement)) = store.materialize(key, &output)? {
+                    let visual_diff = visual_check(input, &output, options)?;
+                    let verified = SidecarReport::verdict(run.verified, &visual_diff);
+                    if let Some(aggregate) = options.aggregate {
+                        aggregate.borrow_mut().add(&run.scan);
+                    }
+                    if options.sidecar {
+                        let thumbnails = page_thumbnails(input, &output, options.thumbnails);
+                        let mut heatmap = run.heatmap;
+                        heatmap_thumbnails(input, &mut heatmap, options.heatmap_thumbnails);
+                        write_sidecar(SidecarReport {
+                            input: input.to_path_buf(),
+                            output: output.clone(),
+                            processed_at: chrono::Utc::now(),
+                            scan: run.scan,
+                            cleaning_actions: run.cleaning_actions,
+                            clean_rounds: run.clean_rounds,
+                            remaining: run.remaining,
+                            hashes: run.hashes,
+                            verified,
+                            findings: run.findings,
+                            rollup: run.rollup,
+                            heatmap,
+                            thumbnails,
+                            visual_diff,
+                            accessibility: run.accessibility,
+                            object_map: if options.object_map { run.object_map } else { Vec::new() },
+                        }, options)?;
+                    }
+                    return Ok(Cleaned { verified, output, stored: Some(placement) });
+                }
+            }
+        }
+    }
+
+    let mut pipeline = PdfPipeline::new(input)?;
+    let accessibility_before = pipeline.accessibility();
+    pipeline.set_attachment_policy(options.attachments);
+    pipeline.set_tracking_action(options.tracking);
+    if let Some(dir) = options.localize_dir {
+        pipeline.set_localize_dir(dir.to_path_buf());
+    }
+    pipeline.set_cleaning_scope(options.scope.clone());
+    pipeline.set_metadata_policy(options.metadata.clone());
+    pipeline.set_preserve_visual(options.preserve_visual);
+    if let Some(evidence) = evidence {
+        pipeline.enable_evidence(input, &evidence.operator)?;
+        if evidence.byte_ranges {
+            pipeline.enable_byte_ranges(input)?;
+        }
+    }
+    if options.sandbox {
+        report_sandbox(&pipeline.decode_sandboxed(SandboxConfig::default())?);
+    }
+    let artifacts = pipeline.scan(input);
+    let findings = if options.explain { pipeline.explain(&artifacts) } else { Vec::new() };
+    let scan = ScanSummary::from_artifacts(&artifacts);
+    let rollup = rollup::roll_up(&artifacts);
+    let mut heatmap = pipeline.page_risks(&artifacts);
+    let output = routed_output(output, options, &scan)?;
+    let outcome = pipeline.clean_verified(&VerifyConfig { max_rounds: options.clean_rounds, ..Default::default() })?;
+    if options.attachments != AttachmentPolicy::Strip {
+        pipeline.normalize_attachments(options.attachment_dates)?;
+    }
+    pipeline.apply_security()?;
+    if stored.is_some() {
+        // The previous output may be a read-only link into the store
+        match std::fs::remove_file(&output) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    let hashes = pipeline.save(&output)?;
+    let object_map = pipeline.object_map().mappings();
+    if let Some(evidence) = evidence {
+        pipeline.write_evidence(&output, &hashes, &evidence.key_id, &evidence.key, options.report_encryption)?;
+    }
+    let verified = pipeline.verify()? && outcome.is_clean();
+    let remaining = ScanSummary::from_artifacts(&outcome.remaining);
+    let visual_diff = visual_check(input, &output, options)?;
+    let accessibility = AccessibilityImpact::compare(accessibility_before, pipeline.accessibility());
+    for warning in &accessibility.warnings {
+        println!("⚠️ {}: accessibility reduced: {}", input.display(), warning);
+    }
+
+    if let Some((store, input_sha256, key)) = &stored {
+        let run = StoredRun {
+            verified,
+            scan: scan.clone(),
+            cleaning_actions: outcome.actions.clone(),
+            clean_rounds: outcome.rounds,
+            remaining: remaining.clone(),
+            hashes: hashes.clone(),
+            findings: findings.clone(),
+            rollup: rollup.clone(),
+            heatmap: heatmap.clone(),
+            object_map: object_map.clone(),
+            accessibility: Some(accessibility.clone()),
+        };
+        let data = serde_json::to_value(run).map_err(std::io::Error::from)?;
+        store.put(key, input_sha256, &options.fingerprint(), &output, data)?;
+    }
+    // The store keeps the verdict before the visual check, which is redone
+    // whenever a stored output is placed
+    let verified = SidecarReport::verdict(verified, &visual_diff);
+
+    if let Some(aggregate) = options.aggregate {
+        aggregate.borrow_mut().add(&scan);
+    }
+    if options.sidecar {
+        let thumbnails = page_thumbnails(input, &output, options.thumbnails);
+        heatmap_thumbnails(input, &mut heatmap, options.heatmap_thumbnails);
+        write_sidecar(SidecarReport {
+            input: input.to_path_buf(),
+            output: output.clone(),
+            processed_at: chrono::Utc::now(),
+            scan,
+            cleaning_actions: outcome.actions,
+            clean_rounds: outcome.rounds,
+            remaining,
+            hashes,
+            verified,
+            findings,
+            rollup,
+            heatmap,
+            thumbnails,
+            visual_diff,
+            accessibility: Some(accessibility),
+            object_map: if options.object_map { object_map } else { Vec::new() },
+        }, options)?;
+    }
+
+    Ok(Cleaned { verified, output, stored: None })
+}
+
+/// Writes `report`, rolling up repeated findings unless `--full` was given
+fn write_sidecar(report: SidecarReport, options: &BatchOptions) -> Result<(), PipelineError> {
+    let report = if options.full {
+        SidecarReport { rollup: Vec::new(), ..report }
+    } else {
+        report.compact()
+    };
+    report.write(options.report_encryption)?;
+    Ok(())
+}
+
+/// Renders the first `pages` pages of `input` and `output` as sidecar thumbnails
+///
+/// Best-effort: rendering problems are reported and yield no thumbnails.
+#[cfg(feature = "render")]
+fn page_thumbnails(input: &std::path::Path, output: &std::path::Path, pages: usize) -> Vec<PageThumbnail> {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use pdf_engine::raster::{self, Rasterizer, RenderError};
+
+    const DPI: f32 = 48.0;
+    const MAX_SIDE: u32 = 240;
+
+    if pages == 0 {
+        return Vec::new();
+    }
+    let render = || -> Result<Vec<PageThumbnail>, RenderError> {
+        let rasterizer = Rasterizer::new()?;
+        let (before_bytes, after_bytes) = (std::fs::read(input)?, std::fs::read(output)?);
+        let (before, after) = (rasterizer.load(&before_bytes)?, rasterizer.load(&after_bytes)?);
+        let data_uri = |png: Vec<u8>| format!("data:image/png;base64,{}", BASE64.encode(png));
+        (0..pages.min(before.page_count()).min(after.page_count()))
+            .map(|page| Ok(PageThumbnail {
+                page,
+                before: data_uri(raster::thumbnail_png(&before.render_page(page, DPI)?, MAX_SIDE)?),
+                after: data_uri(raster::thumbnail_png(&after.render_page(page, DPI)?, MAX_SIDE)?),
+            }))
+            .collect()
+    };
+    render().unwrap_or_else(|e| {
+        println!("⚠️ No thumbnails for {}: {}", input.display(), e);
+        Vec::new()
+    })
+}
+
+#[cfg(not(feature = "render"))]
+fn page_thumbnails(input: &std::path::Path, _output: &std::path::Path, pages: usize) -> Vec<PageThumbnail> {
+    if pages > 0 {
+        println!("⚠️ No thumbnails for {}: built without the `render` feature", input.display());
+    }
+    Vec::new()
+}
+
+/// Renders the first `pages` pages of `heatmap`, the riskiest, from `input`
+///
+/// Best-effort like `page_thumbnails`.
+#[cfg(feature = "render")]
+fn heatmap_thumbnails(input: &std::path::Path, heatmap: &mut [PageRisk], pages: usize) {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use pdf_engine::raster::{self, Rasterizer, RenderError};
+
+    const DPI: f32 = 48.0;
+    const MAX_SIDE: u32 = 240;
+
+    if pages == 0 || heatmap.is_empty() {
+        return;
+    }
+    let mut render = || -> Result<(), RenderError> {
+        let rasterizer = Rasterizer::new()?;
+        let bytes = std::fs::read(input)?;
+        let document = rasterizer.load(&bytes)?;
+        for risk in heatmap.iter_mut().take(pages) {
+            if let Some(index) = (risk.page as usize).checked_sub(1).filter(|&index| index < document.page_count()) {
+                let png = raster::thumbnail_png(&document.render_page(index, DPI)?, MAX_SIDE)?;
+                risk.thumbnail = Some(format!("data:image/png;base64,{}", BASE64.encode(png)));
+            }
+        }
+        Ok(())
+    };
+    if let Err(e) = render() {
+        println!("⚠️ No heatmap thumbnails for {}: {}", input.display(), e);
+    }
+}
+
+#[cfg(not(feature = "render"))]
+fn heatmap_thumbnails(input: &std::path::Path, heatmap: &mut [PageRisk], pages: usize) {
+    if pages > 0 && !heatmap.is_empty() {
+        println!("⚠️ No heatmap thumbnails for {}: built without the `render` feature", input.display());
+    }
+}
+
+/// Compares the rendered pages of `input` and `output` under `--visual-check` or `--preserve-visual`
+///
+/// Batch cleaning is not meant to change how any page looks, so every
+/// page past the limits is flagged: the default ones, or pixel identity
+/// when preserving visuals.
+#[cfg(feature = "render")]
+fn visual_check(
+    input: &std::path::Path,
+    output: &std::path::Path,
+    options: &BatchOptions,
+) -> Result<Vec<PageDiff>, PipelineError> {
+    use pdf_engine::raster::{self, Rasterizer};
+
+    const DPI: f32 = 72.0;
+
+    if !options.visual_check && !options.preserve_visual {
+        return Ok(Vec::new());
+    }
+    let config = if options.preserve_visual { VisualDiffConfig::identical() } else { VisualDiffConfig::default() };
+    let render = |e: raster::RenderError| PipelineError::Render(e.to_string());
+    let rasterizer = Rasterizer::new().map_err(render)?;
+    let (before_bytes, after_bytes) = (std::fs::read(input)?, std::fs::read(output)?);
+    let before = rasterizer.load(&before_bytes).map_err(render)?;
+    let after = rasterizer.load(&after_bytes).map_err(render)?;
+    let diffs = raster::diff_documents(&before, &after, DPI, &config, &Default::default())
+        .map_err(render)?;
+    let flagged: Vec<String> = diffs.iter()
+        .filter(|diff| diff.flagged)
+        .map(|diff| (diff.page + 1).to_string())
+        .collect();
+    if !flagged.is_empty() {
+        println!("⚠️ {}: page(s) {} changed visually", input.display(), flagged.join(", "));
+    }
+    Ok(diffs)
+}
+
+#[cfg(not(feature = "render"))]
+fn visual_check(
+    _input: &std::path::Path,
+    _output: &std::path::Path,
+    options: &BatchOptions,
+) -> Result<Vec<PageDiff>, PipelineError> {
+    if options.visual_check || options.preserve_visual {
+        return Err(PipelineError::Render("built without the `render` feature".into()));
+    }
+    Ok(Vec::new())
+}
+
+/// `output`, moved under its language directory when routing by language
+fn routed_output(
+    output: &std::path::Path,
+    options: &BatchOptions,
+    scan: &ScanSummary,
+) -> Result<PathBuf, PipelineError> {
+    if !options.route_by_language {
+        return Ok(output.to_path_buf());
+    }
+    let language = scan.languages.first().map_or("und", String::as_str);
+    let dir = output.parent().unwrap_or(std::path::Path::new(".")).join(language);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(file_name(output)?))
+}
+
+/// Last component of a path given on the command line; `..` and `/` have none
+fn file_name(path: &std::path::Path) -> Result<&std::ffi::OsStr, PipelineError> {
+    path.file_name()
+        .ok_or_else(|| PipelineError::InvalidInput(format!("{} does not name a file", path.display())))
+}
+
+fn report_sandbox(report: &sandbox::SandboxReport) {
+    println!(
+        "🔒 Sandboxed decoding ({}): {} stream(s) decoded, {} image(s) checked",
+        report.confinement, report.decoded, report.images_checked,
+    );
+    for ((number, generation), reason) in &report.rejected {
+        println!("⚠️ Stream {} {} R rejected and emptied: {}", number, generation, reason);
+    }
+}
+
+fn run_store_gc(args: StoreGcArgs) -> Result<(), PipelineError> {
+    let store = OutputStore::open(&args.store)?;
+    let report = store.gc(&GcPolicy {
+        max_bytes: args.max_size_mb.map(|mb| mb * 1024 * 1024),
+        max_age: args.max_age_days.map(|days| std::time::Duration::from_secs(days * 24 * 60 * 60)),
+        ..Default::default()
+    })?;
+    println!(
+        "Removed {} output(s), freeing {} bytes; {} output(s) ({} bytes) remain",
+        report.removed, report.bytes_freed, report.remaining, report.bytes_remaining,
+    );
+    Ok(())
+}
+
+fn run_purge(args: PurgeArgs) -> Result<(), PipelineError> {
+    let manager = RetentionManager::new(RetentionConfig::load(&args.policy)?);
+    for report in manager.purge()? {
+        println!(
+            "{:?} {}: removed {} file(s), freeing {} bytes; {} file(s) ({} bytes) remain",
+            report.kind, report.dir.display(), report.result.removed, report.result.bytes_freed,
+            report.result.remaining, report.result.bytes_remaining,
+        );
+    }
+    Ok(())
+}
+
+fn run_process(args: ProcessArgs) -> Result<(), PipelineError> {
+    let input = args.input
+        .ok_or_else(|| PipelineError::InvalidInput("an input file is required without a subcommand".into()))?;
+    let output = args.output
+        .ok_or_else(|| PipelineError::InvalidInput("an output file is required without a subcommand".into()))?;
+    args.patterns.install()?;
+
+    // Initialize pipeline
+    let mut pipeline = PdfPipeline::new(&input)?;
+    let accessibility_before = pipeline.accessibility();
+    let replay = match &args.record_replay {
+        Some(_) => {
+            pipeline.enable_replay_recording();
+            Some(ReplayRecord::new(&input, hash::hash_file(&input, 1024 * 1024)?.sha256, args.argv.clone()))
+        }
+        None => None,
+    };
+    pipeline.set_attachment_policy(args.attachments.into());
+    pipeline.set_tracking_action(args.tracking.into());
+    if let Some(dir) = args.localize_dir.clone() {
+        pipeline.set_localize_dir(dir);
+    }
+    let scope = CleaningScope::from_filters(args.scope);
+    if !scope.is_unrestricted() {
+        println!("🎯 Cleaning scope: {}", scope);
+    }
+    pipeline.set_cleaning_scope(scope);
+    pipeline.set_metadata_policy(args.metadata_fields.policy());
+    if !args.hash.is_empty() {
+        pipeline.set_hash_algorithms(&args.hash);
+    }
+    let evidence = args.evidence.resolve()?;
+    let report_encryption = args.report_encryption.resolve()?;
+    if let Some(evidence) = &evidence {
+        pipeline.enable_evidence(&input, &evidence.operator)?;
+        if evidence.byte_ranges {
+            pipeline.enable_byte_ranges(&input)?;
+        }
+    }
+    if args.sandbox {
+        report_sandbox(&pipeline.decode_sandboxed(SandboxConfig::default())?);
+    }
+
+    // Save form values, then flatten, before cleaning removes anything
+    let form_data = if args.flatten_forms {
+        let report = pipeline.flatten_forms()?;
+        println!("Flattened {} form field widget(s), dropped {}", report.flattened, report.dropped);
+        Some(report.data)
+    } else {
+        args.export_form_data.as_ref().map(|_| pipeline.export_form_data())
+    };
+    if let (Some(path), Some(form_data)) = (&args.export_form_data, form_data) {
+        let format = FormDataFormat::from_path(path).unwrap_or(FormDataFormat::Xfdf);
+        std::fs::write(path, form_data.to_bytes(format)?)?;
+        println!("Form data: {} field(s) saved to {}", form_data.fields.len(), path.display());
+    }
+    
+    let mut redactor = args.redact_text.iter()
+        .fold(TextRedactor::new(), |redactor, text| redactor.text(text))
+        .draw_boxes(args.redact_boxes);
+    for pattern in &args.redact_regex {
+        redactor = redactor.regex(pattern)?;
+    }
+    if !redactor.is_empty() {
+        let report = pipeline.redact_text(&redactor)?;
+        println!(
+            "Redacted {} match(es) on {} page(s), {} form(s) rewritten",
+            report.redactions.len(), report.pages(), report.forms_rewritten,
+        );
+    }
+
+    // Clean document, re-scanning the result until nothing is left to remove
+    let outcome = pipeline.clean_verified(&VerifyConfig { max_rounds: args.clean_rounds, ..Default::default() })?;
+
+    // Kept attachments should not describe the system they came from
+    if !matches!(args.attachments, Attachments::Strip) {
+        let report = pipeline.normalize_attachments(args.attachment_dates.into())?;
+        if report.files > 0 {
+            println!("Attachments: {} kept file(s) normalized, {} entry(ies) rewritten, {} subtype(s) corrected",
+                report.files, report.changed.len(), report.subtypes.len());
+        }
+    }
+
+    // Set metadata
+    for (key, value) in args.metadata {
+        pipeline.set_metadata(key, value)?;
+    }
+
+    // Sync metadata
+    pipeline.sync_metadata()?;
+    let dates = pipeline.normalize_dates(args.utc_dates)?;
+    if !dates.rewritten.is_empty() {
+        println!("Dates: {} rewritten in canonical form", dates.rewritten.len());
+    }
+    if let Some(precedence) = args.sync_xmp {
+        let report = pipeline.sync_info_xmp(precedence.into())?;
+        println!("Info/XMP: {} Info field(s), {} XMP field(s) updated", report.info_changed.len(), report.xmp_changed.len());
+    }
+
+    // Stamp provenance over the final content; clap keeps it apart from encryption
+    if let Some(policy_id) = args.provenance {
+        let mut template = ProvenanceTemplate::new(policy_id);
+        template.include_timestamp = !args.provenance_no_timestamp;
+        template.fields.extend(args.provenance_field);
+        let record = pipeline.stamp_provenance(&template)?;
+        println!("Provenance: {} (content {})", record.policy_id, record.content_sha256);
+    }
+
+    // Set encryption if requested
+    pipeline.set_encryption(args.encrypt_user, args.encrypt_owner);
+
+    // Set restrictions if any
+    if let Some(restrictions) = args.restrict {
+        pipeline.set_restrictions(
+            restrictions.split(',')
+                .map(str::to_string)
+                .collect()
+        );
+    }
+
+    // Apply security features
+    pipeline.apply_security()?;
+
+    // Save the processed PDF, hashing it as it is written
+    let hashes = pipeline.save(&output)?;
+    if let Some(evidence) = &evidence {
+        let written = pipeline.write_evidence(&output, &hashes, &evidence.key_id, &evidence.key, report_encryption.as_ref())?;
+        if let Some(log) = written {
+            println!(
+                "Evidence log: {} (public key {})",
+                log.display(), pdf_engine::antiforensics::report::evidence::public_key_hex(&evidence.key),
+            );
+        }
+    }
+    if let (Some(path), Some(mut record)) = (&args.record_replay, replay) {
+        record.stages = pipeline.take_recorded_stages();
+        record.output_sha256 = Some(hash::hash_file(&output, 1024 * 1024)?.sha256);
+        record.write(path)?;
+        println!("Replay record: {} ({} stage(s))", path.display(), record.stages.len());
+    }
+
+    let accessibility = AccessibilityImpact::compare(accessibility_before, pipeline.accessibility());
+    for warning in &accessibility.warnings {
+        println!("⚠️ Accessibility reduced: {}", warning);
+    }
+
+    // Verify the output
+    if !outcome.is_clean() {
+        println!(
+            "⚠️ Warning: {} finding(s) remain after {} cleaning round(s)",
+            outcome.remaining.len(),
+            outcome.rounds,
+        );
+        for artifact in &outcome.remaining {
+            println!("   {:?} {}: {}", artifact.risk_level, artifact.location, artifact.description);
+        }
+        outcome.ensure_clean()?;
+    } else if pipeline.verify()? {
+        println!("✅ PDF processed successfully!");
+
+        for algorithm in &args.hash {
+            println!("{}: {}", algorithm.name().to_uppercase(), hashes.get(*algorithm));
+        }
     } else {
         println!("⚠️ Warning: Output verification failed!");
+        return Err(PipelineError::Verification("output verification failed".into()));
     }
 
     Ok(())
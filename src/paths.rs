@@ -0,0 +1,146 @@
+//! Path handling for long and non-UTF-8 file names
+//!
+//! Paths stay `OsStr` from directory listing to file creation; nothing
+//! here goes through `str`, so names that are not valid UTF-8 (common on
+//! Linux, and unpaired surrogates on Windows) round-trip unchanged. Text
+//! versions are only made for messages and reports, lossily.
+//!
+//! On Windows, [`extended`] turns paths into the `\\?\` form, which lifts
+//! the 260-character `MAX_PATH` limit for the file APIs. Elsewhere it
+//! returns the path unchanged.
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+
+/// `path` in a form the OS accepts regardless of its length
+///
+/// On Windows, absolute and relative paths are made absolute and prefixed
+/// with `\\?\` (`\\?\UNC\` for shares). Paths already in that form, and
+/// every path on other platforms, are returned as they are.
+pub fn extended(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        use std::path::{Component, Prefix};
+
+        if path.as_os_str().is_empty() {
+            return Cow::Borrowed(path);
+        }
+        // Verbatim paths skip `.`/`..` and `/` handling, so resolve those first
+        let Ok(absolute) = std::path::absolute(path) else { return Cow::Borrowed(path) };
+        let mut components = absolute.components();
+        let mut prefixed = OsString::from(r"\\?\");
+        match components.next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(_) => prefixed.push(prefix.as_os_str()),
+                Prefix::UNC(server, share) => {
+                    prefixed.push(r"UNC\");
+                    prefixed.push(server);
+                    prefixed.push(r"\");
+                    prefixed.push(share);
+                }
+                // Already verbatim, or a device path
+                _ => return Cow::Borrowed(path),
+            },
+            _ => return Cow::Borrowed(path),
+        }
+        let mut extended = PathBuf::from(prefixed);
+        extended.push(components.as_path());
+        Cow::Owned(extended)
+    }
+    #[cfg(not(windows))]
+    {
+        Cow::Borrowed(path)
+    }
+}
+
+/// `path` with `suffix` appended to its file name, e.g. `a.pdf` to `a.pdf.tmp`
+pub fn append_to_name(path: &Path, suffix: impl AsRef<OsStr>) -> PathBuf {
+    let mut name = path.file_name().map_or_else(OsString::new, OsStr::to_os_string);
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// `path` with its file stem followed by `suffix`, keeping the extension
+///
+/// `report.pdf` with `-2` becomes `report-2.pdf`.
+pub fn with_stem_suffix(path: &Path, suffix: impl AsRef<OsStr>) -> PathBuf {
+    let mut name = path.file_stem().map_or_else(OsString::new, OsStr::to_os_string);
+    name.push(suffix);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// File name for messages and reports; invalid UTF-8 becomes U+FFFD
+pub fn display_name(path: &Path) -> String {
+    path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// File names that trip up code assuming short UTF-8 paths
+    pub(crate) fn exotic_names() -> Vec<OsString> {
+        let mut names = vec![
+            OsString::from("report with spaces.pdf"),
+            OsString::from("отчёт-日本語-📄.pdf"),
+            OsString::from(format!("{}.pdf", "long".repeat(60))),
+        ];
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            names.push(OsString::from_vec(b"latin1-\xe9t\xe9.pdf".to_vec()));
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::ffi::OsStringExt;
+            // Unpaired surrogate
+            names.push(OsString::from_wide(&[0x61, 0xD800, 0x2E, 0x70, 0x64, 0x66]));
+        }
+        names
+    }
+
+    #[test]
+    fn test_name_suffixes_keep_non_utf8_bytes() {
+        for name in exotic_names() {
+            let path = Path::new("dir").join(&name);
+            let appended = append_to_name(&path, ".tmp");
+            let mut expected = name.clone();
+            expected.push(".tmp");
+            assert_eq!(appended.file_name(), Some(expected.as_os_str()));
+
+            let suffixed = with_stem_suffix(&path, "-2");
+            assert_eq!(suffixed.extension(), Some(OsStr::new("pdf")));
+            assert!(suffixed.file_stem().unwrap().len() == path.file_stem().unwrap().len() + 2);
+        }
+    }
+
+    #[test]
+    fn test_deep_paths_are_usable() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut deep = dir.path().to_path_buf();
+        while deep.as_os_str().len() < 400 {
+            deep.push("nested-directory-level");
+        }
+        std::fs::create_dir_all(extended(&deep)).unwrap();
+        for name in exotic_names() {
+            let file = deep.join(&name);
+            std::fs::write(extended(&file), b"%PDF-1.7").unwrap();
+            assert_eq!(std::fs::read(extended(&file)).unwrap(), b"%PDF-1.7");
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_extended_prefixes() {
+        assert_eq!(extended(Path::new(r"C:\a\..\b")), Path::new(r"\\?\C:\b"));
+        assert_eq!(extended(Path::new(r"\\server\share\a")), Path::new(r"\\?\UNC\server\share\a"));
+        assert_eq!(extended(Path::new(r"\\?\C:\a")), Path::new(r"\\?\C:\a"));
+    }
+}
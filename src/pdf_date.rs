@@ -0,0 +1,333 @@
+//! PDF date strings
+//!
+//! Dates in the Info dictionary, annotations and signatures are text of
+//! the form `D:YYYYMMDDHHmmSSOHH'mm'`, where everything after the year is
+//! optional and `O` is `+`, `-` or `Z`. Producers get this wrong in many
+//! ways: ISO 8601 instead, the `19100` year of old two-digit-year code,
+//! zero months and days, `:` or no separator in the offset, a missing or
+//! doubled trailing apostrophe, C `asctime` output. `PdfDate::parse`
+//! accepts all of these; `parse_strict` only accepts what the
+//! specification allows, so verification can tell the two apart.
+//!
+//! A date keeps the wall-clock time and offset it was written with, since
+//! the offset is itself information (it places the author in a time
+//! zone). `in_utc` drops it. Dates without an offset have an unknown
+//! zone and are taken as UTC when compared.
+
+use std::{fmt, str::FromStr};
+use chrono::{
+    DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Timelike, Utc,
+};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PdfDateError {
+    #[error("Empty date")]
+    Empty,
+    #[error("Malformed date {0:?}")]
+    Malformed(String),
+    #[error("Date {0:?} is out of range")]
+    OutOfRange(String),
+    #[error("Date {0:?} does not conform to the PDF date format")]
+    NonConforming(String),
+}
+
+/// A date as written in a PDF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PdfDate {
+    /// Wall-clock time in the date's own zone
+    local: NaiveDateTime,
+    /// Offset from UTC; `None` when the zone is unknown
+    offset: Option<FixedOffset>,
+}
+
+impl PdfDate {
+    pub fn new(local: NaiveDateTime, offset: Option<FixedOffset>) -> Self {
+        Self { local, offset }
+    }
+
+    /// The same instant and offset as `datetime`
+    pub fn from_datetime<Tz: TimeZone>(datetime: &DateTime<Tz>) -> Self {
+        let fixed = datetime.fixed_offset();
+        Self { local: fixed.naive_local(), offset: Some(*fixed.offset()) }
+    }
+
+    /// The current time in UTC, to the second
+    pub fn now() -> Self {
+        Self::from_datetime(&Utc::now()).truncated()
+    }
+
+    /// Parses a PDF date, repairing the malformed variants producers write
+    pub fn parse(text: &str) -> Result<Self, PdfDateError> {
+        parse(text).map(|(date, _)| date)
+    }
+
+    /// Parses a PDF date, rejecting anything the specification does not allow
+    pub fn parse_strict(text: &str) -> Result<Self, PdfDateError> {
+        match parse(text)? {
+            (date, true) => Ok(date),
+            (_, false) => Err(PdfDateError::NonConforming(text.to_string())),
+        }
+    }
+
+    /// Parses an XMP (ISO 8601) date; any precision from a year to fractional seconds
+    pub fn from_xmp(text: &str) -> Result<Self, PdfDateError> {
+        parse_iso(text.trim()).ok_or_else(|| PdfDateError::Malformed(text.to_string()))
+    }
+
+    /// Wall-clock time in the date's own zone
+    pub fn local(&self) -> NaiveDateTime {
+        self.local
+    }
+
+    /// Offset from UTC; `None` when the zone is unknown
+    pub fn offset(&self) -> Option<FixedOffset> {
+        self.offset
+    }
+
+    /// The instant, taking an unknown zone as UTC
+    pub fn to_utc(&self) -> DateTime<Utc> {
+        let offset = self.offset.unwrap_or_else(utc);
+        Utc.from_utc_datetime(&(self.local - offset))
+    }
+
+    /// Whether both dates name the same instant, whatever their offsets
+    pub fn same_instant(&self, other: &Self) -> bool {
+        self.to_utc() == other.to_utc()
+    }
+
+    /// The same instant expressed in UTC, hiding the original time zone
+    pub fn in_utc(&self) -> Self {
+        Self::from_datetime(&self.to_utc())
+    }
+
+    /// `D:YYYYMMDDHHmmSS` followed by `Z`, `+HH'mm'` or nothing for an unknown zone
+    pub fn to_pdf_string(&self) -> String {
+        let zone = match self.offset.map(|o| o.local_minus_utc() / 60) {
+            None => String::new(),
+            Some(0) => "Z".to_string(),
+            Some(minutes) => {
+                let sign = if minutes < 0 { '-' } else { '+' };
+                format!("{}{:02}'{:02}'", sign, minutes.abs() / 60, minutes.abs() % 60)
+            }
+        };
+        format!("D:{}{}", self.local.format("%Y%m%d%H%M%S"), zone)
+    }
+
+    /// RFC 3339 as used by XMP; without an offset for an unknown zone
+    pub fn to_xmp_string(&self) -> String {
+        match self.offset {
+            Some(offset) => offset.from_local_datetime(&self.local).single()
+                .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true))
+                .unwrap_or_else(|| self.local.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            None => self.local.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        }
+    }
+
+    /// The date as a PDF literal string object
+    pub fn to_object(&self) -> lopdf::Object {
+        lopdf::Object::string_literal(self.to_pdf_string())
+    }
+
+    fn truncated(self) -> Self {
+        Self { local: self.local.with_nanosecond(0).unwrap_or(self.local), ..self }
+    }
+}
+
+impl fmt::Display for PdfDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_pdf_string())
+    }
+}
+
+impl FromStr for PdfDate {
+    type Err = PdfDateError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text)
+    }
+}
+
+fn utc() -> FixedOffset {
+    FixedOffset::east_opt(0).expect("zero offset")
+}
+
+/// Parses `text`, returning the date and whether it was already conforming
+fn parse(text: &str) -> Result<(PdfDate, bool), PdfDateError> {
+    let malformed = || PdfDateError::Malformed(text.to_string());
+    let trimmed = text.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    if trimmed.is_empty() {
+        return Err(PdfDateError::Empty);
+    }
+    let mut conforming = trimmed.len() == text.len();
+    let body = match trimmed.get(..2) {
+        Some("D:") => &trimmed[2..],
+        Some(prefix) if prefix.eq_ignore_ascii_case("d:") => {
+            conforming = false;
+            &trimmed[2..]
+        }
+        _ => trimmed,
+    };
+
+    let digits = body.bytes().take_while(u8::is_ascii_digit).count();
+    if digits < 4 {
+        // Not a PDF date at all; try the formats producers write instead
+        return parse_iso(body).or_else(|| parse_asctime(body)).map(|date| (date, false)).ok_or_else(malformed);
+    }
+    if body.as_bytes().get(4) == Some(&b'-') {
+        return parse_iso(body).map(|date| (date, false)).ok_or_else(malformed);
+    }
+
+    let (numbers, rest) = body.split_at(digits);
+    let mut year: i32 = numbers[..4].parse().map_err(|_| malformed())?;
+    let mut fields = &numbers[4..];
+    // Two-digit-year code printing "19" and then years since 1900: D:19100... is 2000
+    if digits % 2 == 1 && numbers.starts_with("19") {
+        if let Ok(since_1900 @ 100..=199) = numbers[2..5].parse::<i32>() {
+            year = 1900 + since_1900;
+            fields = &numbers[5..];
+            conforming = false;
+        }
+    }
+    if fields.len() % 2 == 1 || fields.len() > 10 {
+        conforming = false;
+    }
+    let field = |index: usize, default: u32| -> u32 {
+        fields.get(index * 2..index * 2 + 2).and_then(|s| s.parse().ok()).unwrap_or(default)
+    };
+    let (mut month, mut day) = (field(0, 1), field(1, 1));
+    let (hour, minute, mut second) = (field(2, 0), field(3, 0), field(4, 0));
+    if month == 0 || day == 0 || second == 60 {
+        (month, day, second) = (month.max(1), day.max(1), second.min(59));
+        conforming = false;
+    }
+
+    let (offset, zone_conforming) = parse_zone(rest).ok_or_else(malformed)?;
+    let local = NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_opt(hour, minute, second))
+        .ok_or_else(|| PdfDateError::OutOfRange(text.to_string()))?;
+    Ok((PdfDate { local, offset }, conforming && zone_conforming))
+}
+
+/// Parses the zone after the digits: `Z`, `+HH'mm'` and its malformed variants
+///
+/// Returns the offset, `None` for no zone, and whether the zone was conforming.
+fn parse_zone(zone: &str) -> Option<(Option<FixedOffset>, bool)> {
+    let mut chars = zone.chars();
+    let sign = match chars.next() {
+        None => return Some((None, true)),
+        Some('Z' | 'z') => {
+            // Some producers write Z00'00'; the offset is zero either way
+            let rest = chars.as_str();
+            let conforming = zone.starts_with('Z') && matches!(rest, "" | "00'00'" | "00'00");
+            return Some((Some(utc()), conforming));
+        }
+        Some('+') => 1,
+        Some('-') => -1,
+        Some(_) => return None,
+    };
+    let rest = chars.as_str();
+    let numbers: String = rest.chars().filter(char::is_ascii_digit).collect();
+    let (hours, minutes) = match numbers.len() {
+        2 => (numbers[..2].parse::<i32>().ok()?, 0),
+        4 => (numbers[..2].parse::<i32>().ok()?, numbers[2..].parse::<i32>().ok()?),
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    // HH, HH'mm or HH'mm'
+    let conforming = matches!(rest.as_bytes(), [_, _] | [_, _, b'\'', _, _] | [_, _, b'\'', _, _, b'\'']);
+    Some((FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)), conforming))
+}
+
+/// ISO 8601 as written by XMP, to any precision
+fn parse_iso(text: &str) -> Option<PdfDate> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Some(PdfDate::from_datetime(&date).truncated());
+    }
+    // Offsets: Z, +HH:MM, +HHMM
+    let (local, offset) = match text.rfind(['Z', 'z', '+']).or_else(|| text.rfind('-').filter(|&i| i > 10)) {
+        Some(i) if i >= 10 => (&text[..i], parse_iso_offset(&text[i..])?),
+        _ => (text, None),
+    };
+    let local = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"].iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(local, format).ok())
+        .or_else(|| {
+            let date = match local.len() {
+                4 => NaiveDate::from_ymd_opt(local.parse().ok()?, 1, 1)?,
+                7 => NaiveDate::parse_from_str(&format!("{}-01", local), "%Y-%m-%d").ok()?,
+                _ => NaiveDate::parse_from_str(local, "%Y-%m-%d").ok()?,
+            };
+            date.and_hms_opt(0, 0, 0)
+        })?;
+    Some(PdfDate { local, offset }.truncated())
+}
+
+fn parse_iso_offset(zone: &str) -> Option<Option<FixedOffset>> {
+    if zone.eq_ignore_ascii_case("z") {
+        return Some(Some(utc()));
+    }
+    let sign = if zone.starts_with('-') { -1 } else { 1 };
+    let numbers: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+    let (hours, minutes): (i32, i32) = match numbers.len() {
+        2 => (numbers.parse().ok()?, 0),
+        4 => (numbers[..2].parse().ok()?, numbers[2..].parse().ok()?),
+        _ => return None,
+    };
+    Some(Some(FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))?))
+}
+
+/// C `asctime` output, e.g. `Tue Mar  5 14:02:11 2024`, in an unknown zone
+fn parse_asctime(text: &str) -> Option<PdfDate> {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let local = NaiveDateTime::parse_from_str(&collapsed, "%a %b %e %H:%M:%S %Y").ok()?;
+    Some(PdfDate { local, offset: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> String {
+        PdfDate::parse(text).unwrap().to_pdf_string()
+    }
+
+    #[test]
+    fn test_conforming_dates_round_trip() {
+        for text in ["D:20240102030405Z", "D:20240102030405+05'30'", "D:20240102030405-08'00'", "D:20240102030405"] {
+            assert_eq!(PdfDate::parse_strict(text).unwrap().to_pdf_string(), text);
+        }
+        assert_eq!(at("D:2024"), "D:20240101000000");
+        assert!(PdfDate::parse_strict("D:20240102030405+05'30").is_ok());
+    }
+
+    #[test]
+    fn test_malformed_variants_are_repaired() {
+        assert_eq!(at("D:191240102030405Z"), "D:20240102030405Z");
+        assert_eq!(at("D:20240000120000Z"), "D:20240101120000Z");
+        assert_eq!(at("D:20240102030405+05:30"), "D:20240102030405+05'30'");
+        assert_eq!(at("D:20240102030405+0530"), "D:20240102030405+05'30'");
+        assert_eq!(at("20240102030405Z00'00'"), "D:20240102030405Z");
+        assert_eq!(at("2024-01-02T03:04:05+01:00"), "D:20240102030405+01'00'");
+        assert_eq!(at("Tue Jan  2 03:04:05 2024"), "D:20240102030405");
+        for text in ["D:191240102030405Z", "D:20240102030405+05:30", "2024-01-02T03:04:05Z", "d:2024"] {
+            assert!(matches!(PdfDate::parse_strict(text), Err(PdfDateError::NonConforming(_))), "{}", text);
+        }
+        assert!(matches!(PdfDate::parse("D:20241301"), Err(PdfDateError::OutOfRange(_))));
+        assert!(matches!(PdfDate::parse("yesterday"), Err(PdfDateError::Malformed(_))));
+        assert!(matches!(PdfDate::parse("  "), Err(PdfDateError::Empty)));
+    }
+
+    #[test]
+    fn test_instants_and_xmp() {
+        let pdf = PdfDate::parse("D:20240102030405Z").unwrap();
+        let xmp = PdfDate::from_xmp("2024-01-02T04:04:05+01:00").unwrap();
+        assert!(pdf.same_instant(&xmp));
+        assert_ne!(pdf, xmp);
+        assert_eq!(xmp.to_xmp_string(), "2024-01-02T04:04:05+01:00");
+        assert_eq!(xmp.in_utc(), pdf);
+        assert_eq!(pdf.to_xmp_string(), "2024-01-02T03:04:05Z");
+        assert_eq!(PdfDate::from_xmp("2024-01").unwrap().to_pdf_string(), "D:20240101000000");
+    }
+}
@@ -7,21 +7,133 @@
 // User: kartik6717
 // Note: Placeholder code has been replaced with actual implementations
 
-use lopdf::Document;
+use pdf_engine::antiforensics::hash::{self, DocumentHashes, HashAlgorithm, HashingWriter};
+use pdf_engine::antiforensics::patterns::PatternError;
+use pdf_engine::antiforensics::report::heatmap;
+use pdf_engine::antiforensics::report::provenance::{self, ProvenanceError};
+use pdf_engine::antiforensics::report::{
+    AccessibilityProfile, ByteMap, EvidenceLog, ExplainedFinding, Explainer, InspectError, ObjectGraph, ObjectMap, PageRisk,
+    ProvenanceRecord, ProvenanceTemplate, ReportCryptoError, ReportEncryption, Snapshot, StageRecord,
+};
+use pdf_engine::antiforensics::analyzer::{
+    info_xmp, AnalyzerConfig, AnalyzerError, InfoXmpAnalyzer, ObjectNumberingAnalyzer, SyncPrecedence, SyncReport,
+    TrailerAnalyzer, XrefIntegrityAnalyzer,
+};
+use pdf_engine::antiforensics::cleaner::{
+    forms, AttachmentDates, AttachmentNormalizeReport, AttachmentNormalizer, AttachmentPolicy, CleanTarget, CleanerError,
+    CleaningScope, DateNormalizeReport, DateNormalizer, DocumentIdRegenerator, FlattenReport, FormData, FormDataError,
+    IdRegeneration, ImportReport, MediaCleaner, MetadataPolicy, ObjectRenumberer, PortfolioCleaner, RedactionReport,
+    TextRedactor, TrackingAction, TrackingCleaner,
+};
+use pdf_engine::antiforensics::scanner::{
+    language, portfolio, DocumentScanner, InvoiceScanner, LanguageProfile, LanguageScanner, MediaScanner,
+    MimeSniffer, PaletteScanner, PortfolioScanner, SyntaxScanner, TrackingScanner, UsageRightsScanner,
+};
+use pdf_engine::antiforensics::{self, types::Document, ArtifactType, ForensicArtifact, RiskLevel};
+use pdf_engine::builder::CleanPolicy;
+use pdf_engine::writer::permissions::{self, Permissions, PermissionsError};
+use pdf_engine::paths;
+use pdf_engine::profile::XrefTimer;
+use pdf_engine::sandbox::{self, SandboxConfig, SandboxError, SandboxReport, SandboxedDecoder};
 use std::collections::HashMap;
-use std::path::Path;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum PipelineError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
     #[error("PDF error: {0}")]
     Pdf(#[from] lopdf::Error),
     #[error("Invalid metadata: {0}")]
     Metadata(String),
     #[error("Encryption error: {0}")]
     Encryption(String),
+    #[error("Hash verification failed: {0}")]
+    HashVerification(String),
+    #[error("Cleaning error: {0}")]
+    Clean(#[from] CleanerError),
+    #[error("Provenance error: {0}")]
+    Provenance(#[from] ProvenanceError),
+    #[error("Form data error: {0}")]
+    FormData(#[from] FormDataError),
+    #[error("Pattern database error: {0}")]
+    Patterns(#[from] PatternError),
+    #[error("Rule test failed: {0}")]
+    RuleTest(String),
+    #[error("Batch aborted: {0}")]
+    BatchAborted(String),
+    #[error("Batch incomplete: {0}")]
+    BatchIncomplete(String),
+    #[error("Rendering error: {0}")]
+    Render(String),
+    #[error("Archive error: {0}")]
+    Archive(String),
+    #[error("Replay failed: {0}")]
+    Replay(String),
+    #[error("Verification failed: {0}")]
+    Verification(String),
+    #[error("Sandbox error: {0}")]
+    Sandbox(#[from] SandboxError),
+    #[error("Analysis error: {0}")]
+    Analyzer(#[from] AnalyzerError),
+    #[error("Inspect error: {0}")]
+    Inspect(#[from] InspectError),
+    #[error("Schema error: {0}")]
+    Schema(String),
+    #[error("Invalid redaction query: {0}")]
+    Redaction(#[from] regex::Error),
+    #[error("Redaction incomplete, text could not be searched in: {0}")]
+    RedactionIncomplete(String),
+    #[error("Report encryption error: {0}")]
+    ReportCrypto(#[from] ReportCryptoError),
+}
+
+/// When a cleaned document counts as verified
+#[derive(Debug, Clone)]
+pub struct VerifyConfig {
+    /// Findings at or above this level fail verification
+    pub threshold: RiskLevel,
+    /// Clean-and-rescan rounds before giving up
+    pub max_rounds: usize,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self { threshold: RiskLevel::Medium, max_rounds: 3 }
+    }
+}
+
+/// Result of cleaning until a re-scan of the output comes back clean
+#[derive(Debug, Clone, Default)]
+pub struct CleanOutcome {
+    /// Entries removed over all rounds
+    pub actions: Vec<String>,
+    /// Clean-and-rescan rounds run
+    pub rounds: usize,
+    /// Findings at or above the threshold left after the last round
+    pub remaining: Vec<ForensicArtifact>,
+}
+
+impl CleanOutcome {
+    pub fn is_clean(&self) -> bool {
+        self.remaining.is_empty()
+    }
+
+    /// Fails when findings remain, so callers exit non-zero
+    pub fn ensure_clean(&self) -> Result<(), PipelineError> {
+        if self.is_clean() {
+            return Ok(());
+        }
+        Err(PipelineError::Verification(format!(
+            "{} finding(s) remain after {} cleaning round(s)",
+            self.remaining.len(),
+            self.rounds,
+        )))
+    }
 }
 
 pub struct PdfPipeline {
@@ -30,47 +142,349 @@ pub struct PdfPipeline {
     encrypt_user: Option<String>,
     encrypt_owner: Option<String>,
     restrictions: Vec<String>,
+    clean_policy: CleanPolicy,
+    /// Algorithms `save` hashes the output with
+    hash_algorithms: Vec<HashAlgorithm>,
+    /// Object-level change log, when evidence mode is on
+    evidence: Option<EvidenceLog>,
+    /// Document digest after each stage, when recording for replay
+    stages: Option<Vec<StageRecord>>,
+    /// Input object each object of the document derives from
+    object_map: ObjectMap,
+    /// Skip cleaning steps that change how pages render
+    preserve_visual: bool,
+    /// What happens to external references; `None` keeps them
+    tracking: Option<TrackingAction>,
+    /// Directory external streams are localized from; without one they are removed
+    localize_dir: Option<PathBuf>,
 }
 
 impl PdfPipeline {
     pub fn new<P: AsRef<Path>>(input_path: P) -> Result<Self, PipelineError> {
-        let doc = Document::load(input_path)?;
+        let input_path = input_path.as_ref();
+        let span = tracing::info_span!("parse", path = %input_path.display(), objects = tracing::field::Empty).entered();
+        let doc = Document::parse(input_path.to_path_buf(), &std::fs::read(paths::extended(input_path))?)?;
+        span.record("objects", doc.structure.objects.len());
+        drop(span);
         Ok(Self {
+            object_map: ObjectMap::identity(&doc.structure),
             doc,
             metadata: HashMap::new(),
             encrypt_user: None,
             encrypt_owner: None,
             restrictions: Vec::new(),
+            clean_policy: CleanPolicy::default(),
+            hash_algorithms: HashAlgorithm::DEFAULT.to_vec(),
+            evidence: None,
+            stages: None,
+            preserve_visual: false,
+            tracking: Some(TrackingAction::Remove),
+            localize_dir: None,
         })
     }
 
-    pub fn clean_document(&mut self) -> Result<(), PipelineError> {
-        // Remove sensitive entries
-        let root = self.doc.get_object_mut(self.doc.get_root()?)?.as_dict_mut()?;
-        
-        // Remove JavaScript and actions
-        root.remove(b"JavaScript");
-        root.remove(b"OpenAction");
-        root.remove(b"AA");
-        
-        // Remove metadata unless explicitly provided
-        root.remove(b"Metadata");
-        root.remove(b"Lang");
-        root.remove(b"MarkInfo");
-        root.remove(b"PieceInfo");
-        
-        // Clean document info
-        if let Some(info) = self.doc.trailer.get_mut(b"Info") {
-            let info_dict = info.as_dict_mut()?;
-            info_dict.remove(b"ModDate");
-            info_dict.remove(b"CreationDate");
-            info_dict.remove(b"Producer");
-            info_dict.remove(b"Creator");
-        }
+    /// Selects the algorithms `save` hashes the output with
+    ///
+    /// SHA-256 is added while evidence mode is on, since the evidence log
+    /// records it.
+    pub fn set_hash_algorithms(&mut self, algorithms: &[HashAlgorithm]) {
+        self.hash_algorithms = algorithms.to_vec();
+    }
 
+    /// Starts recording every transformation of the document for `operator`
+    pub fn enable_evidence(&mut self, input_path: &Path, operator: &str) -> Result<(), PipelineError> {
+        let input_hashes = hash::hash_file(input_path, 1024 * 1024)?;
+        self.evidence = Some(EvidenceLog::new(input_path, input_hashes.sha256, operator));
         Ok(())
     }
 
+    /// Also records where in the input each changed object was; call right
+    /// after `enable_evidence`, before any transformation
+    ///
+    /// Only offsets are logged, never content. Does nothing when evidence mode is off.
+    pub fn enable_byte_ranges(&mut self, input_path: &Path) -> Result<(), PipelineError> {
+        let Some(log) = self.evidence.take() else { return Ok(()) };
+        let data = std::fs::read(paths::extended(input_path))?;
+        self.evidence = Some(log.with_byte_ranges(ByteMap::build(&data, &self.doc.structure)));
+        Ok(())
+    }
+
+    /// Starts recording a digest of the document after every stage, the
+    /// loaded input being the first
+    pub fn enable_replay_recording(&mut self) {
+        self.stages = Some(vec![StageRecord::of("load", &self.doc.structure)]);
+    }
+
+    /// Stages recorded so far; empty when recording is off
+    pub fn take_recorded_stages(&mut self) -> Vec<StageRecord> {
+        self.stages.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn record_stage(&mut self, rule: &str) {
+        if let Some(stages) = self.stages.as_mut() {
+            stages.push(StageRecord::of(rule, &self.doc.structure));
+        }
+    }
+
+    /// Decodes every Flate and LZW stream, and parses every JPEG image, in
+    /// a confined child process; call before scanning or cleaning
+    ///
+    /// Decoded streams are stored re-compressed by this process, so later
+    /// steps only inflate data written here. Streams the child fails or
+    /// dies on are emptied and reported as rejected.
+    pub fn decode_sandboxed(&mut self, config: SandboxConfig) -> Result<SandboxReport, PipelineError> {
+        let mut decoder = SandboxedDecoder::spawn(config)?;
+        self.step("sandbox_decode", |doc| Ok(sandbox::decode_document(&mut doc.structure, &mut decoder)))
+    }
+
+    /// Signs the evidence log and writes it next to `output_path`, encrypted with `encryption` if given
+    ///
+    /// Returns `None` when evidence mode is off.
+    pub fn write_evidence(
+        &mut self,
+        output_path: &Path,
+        output_hashes: &DocumentHashes,
+        key_id: &str,
+        key: &ed25519_dalek::SigningKey,
+        encryption: Option<&ReportEncryption>,
+    ) -> Result<Option<PathBuf>, PipelineError> {
+        let Some(log) = self.evidence.as_mut() else { return Ok(None) };
+        log.output = Some(output_path.to_path_buf());
+        log.output_sha256 = Some(output_hashes.sha256.clone());
+        log.sign(key_id, key).map_err(std::io::Error::from)?;
+        Ok(Some(log.write(output_path, encryption)?))
+    }
+
+    /// Runs one transformation, logging the objects it changed in evidence mode
+    fn step<T>(
+        &mut self,
+        rule: &str,
+        transform: impl FnOnce(&mut Document) -> Result<T, PipelineError>,
+    ) -> Result<T, PipelineError> {
+        let before = self.evidence.as_ref().map(|_| Snapshot::of(&self.doc.structure));
+        let result = transform(&mut self.doc)?;
+        if let (Some(log), Some(before)) = (self.evidence.as_mut(), before) {
+            log.record_step(rule, &before, &self.doc.structure);
+        }
+        self.record_stage(rule);
+        Ok(result)
+    }
+
+    /// Current form field values
+    pub fn export_form_data(&self) -> FormData {
+        forms::export_form_data(&self.doc)
+    }
+
+    /// Fills the form from exported field values
+    pub fn import_form_data(&mut self, data: &FormData) -> Result<ImportReport, PipelineError> {
+        self.step("import_form_data", |doc| Ok(forms::import_form_data(doc, data)?))
+    }
+
+    /// Draws form fields into their pages and removes the form, returning
+    /// the values the fields held
+    pub fn flatten_forms(&mut self) -> Result<FlattenReport, PipelineError> {
+        self.step("flatten_forms", |doc| Ok(forms::flatten_forms(doc)?))
+    }
+
+    /// Removes text matching the redactor's queries from the content streams,
+    /// form XObjects and appearance streams
+    ///
+    /// Fails when some text could not be searched, since a match may remain there.
+    pub fn redact_text(&mut self, redactor: &TextRedactor) -> Result<RedactionReport, PipelineError> {
+        let report = self.step("redact_text", |doc| Ok(redactor.redact(doc)?))?;
+        if !report.is_complete() {
+            return Err(PipelineError::RedactionIncomplete(report.unsearchable.join(", ")));
+        }
+        Ok(report)
+    }
+
+    /// Sets what cleaning does with embedded files
+    pub fn set_attachment_policy(&mut self, policy: AttachmentPolicy) {
+        self.clean_policy.attachments = policy;
+    }
+
+    /// Sets which metadata fields cleaning keeps or drops regardless
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.clean_policy.metadata = policy;
+    }
+
+    /// Limits cleaning, and the findings verification counts, to `scope`
+    pub fn set_cleaning_scope(&mut self, scope: CleaningScope) {
+        self.clean_policy.scope = scope;
+    }
+
+    /// Sets what happens to external stream references and tracking actions; `None` keeps them
+    pub fn set_tracking_action(&mut self, action: Option<TrackingAction>) {
+        self.tracking = action;
+    }
+
+    /// Lets localizing embed files from inside `dir`; nothing is read from disk otherwise
+    pub fn set_localize_dir(&mut self, dir: PathBuf) {
+        self.localize_dir = Some(dir);
+    }
+
+    /// Restricts cleaning to metadata and structure, leaving page rendering
+    /// untouched; media annotations are kept rather than replaced by posters
+    pub fn set_preserve_visual(&mut self, preserve: bool) {
+        self.preserve_visual = preserve;
+    }
+
+    /// Removes scripts, actions and metadata, returning the removed entries
+    ///
+    /// Media annotations are replaced by their posters unless visuals are
+    /// preserved, external references and tracking actions are removed or
+    /// localized as set, and portfolio
+    /// entries are cleaned too; their actions are prefixed with
+    /// `portfolio:<name>`. Objects are then renumbered densely so the
+    /// numbering carries nothing over from the input.
+    ///
+    /// A scoped clean only changes what its scope allows and keeps the
+    /// object numbers, so later rounds still find the scoped objects.
+    /// Portfolio entries are cleaned as a whole, as document-level
+    /// attachments at `portfolio:`.
+    pub fn clean_document(&mut self) -> Result<Vec<String>, PipelineError> {
+        let policy = self.clean_policy.clone();
+        let mut removed = self.step("clean_policy", |doc| Ok(policy.apply_recorded(doc)?))?;
+        if !self.preserve_visual {
+            let media_cleaner = MediaCleaner::default().with_scope(policy.scope.clone());
+            let media = self.step("media", |doc| Ok(media_cleaner.clean(doc)?))?;
+            removed.extend(media.removed.iter().chain(&media.replaced).map(|(n, g)| format!("/Annots/{} {} R", n, g)));
+        }
+        if let Some(action) = self.tracking {
+            let mut tracking_cleaner = TrackingCleaner::new(action).with_scope(policy.scope.clone());
+            if let Some(dir) = &self.localize_dir {
+                tracking_cleaner = tracking_cleaner.with_base_dir(dir);
+            }
+            removed.extend(self.step("tracking", |doc| Ok(tracking_cleaner.clean(doc)?))?.actions());
+        }
+        let portfolio = CleanTarget { location: "portfolio:", artifact_type: ArtifactType::EmbeddedFile, object: None, page: None };
+        if policy.scope.allows(&portfolio) {
+            removed.extend(self.step("portfolio", |doc| Ok(PortfolioCleaner::default().clean(doc)?))?.actions);
+        }
+        // Per-object keys of an encrypted input derive from the object numbers
+        if !policy.scope.is_unrestricted() || self.doc.encrypt().is_some() {
+            return Ok(removed);
+        }
+
+        let before = self.evidence.as_ref().map(|_| Snapshot::of(&self.doc.structure));
+        self.object_map.drop_missing(&self.doc.structure);
+        let renumbered = ObjectRenumberer::new().renumber(&mut self.doc);
+        self.object_map.renumber(&renumbered.mapping);
+        if let (Some(log), Some(before)) = (self.evidence.as_mut(), before) {
+            log.record_renumbering("renumber", &before, &self.doc.structure, &renumbered.mapping);
+        }
+        self.record_stage("renumber");
+        Ok(removed)
+    }
+
+    /// Cleans, then re-scans the bytes cleaning would write, repeating
+    /// while findings at or above the threshold remain and the last round
+    /// still removed something
+    ///
+    /// Findings left after the last round are in the outcome; callers that
+    /// write the output anyway must still fail with `CleanOutcome::ensure_clean`.
+    pub fn clean_verified(&mut self, config: &VerifyConfig) -> Result<CleanOutcome, PipelineError> {
+        let mut outcome = CleanOutcome::default();
+        while outcome.rounds < config.max_rounds.max(1) {
+            outcome.rounds += 1;
+            let removed = self.clean_document()?;
+            let progressed = !removed.is_empty();
+            outcome.actions.extend(removed);
+
+            let findings = self.scan_output()?;
+            outcome.remaining = self.clean_policy.scope.select(&self.doc, &findings).into_iter()
+                .filter(|a| a.risk_level.severity() >= config.threshold.severity())
+                .cloned()
+                .collect();
+            if outcome.remaining.is_empty() || !progressed {
+                break;
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Scans the document as it would be written, from the serialized bytes
+    pub fn scan_output(&self) -> Result<Vec<ForensicArtifact>, PipelineError> {
+        let mut bytes = Vec::new();
+        self.doc.structure.clone().save_to(&mut bytes)?;
+        let doc = Document::parse(PathBuf::new(), &bytes)?;
+        let mut artifacts = SyntaxScanner::new().scan_bytes(&bytes);
+        artifacts.extend(XrefIntegrityAnalyzer::new().scan_bytes(&bytes));
+        artifacts.extend(Self::scan_document(&doc));
+        antiforensics::sort_artifacts(&mut artifacts);
+        Ok(artifacts)
+    }
+
+    /// Scans the document as currently loaded, including portfolio entries
+    pub fn scan(&self, path: &Path) -> Vec<ForensicArtifact> {
+        let doc = Document::from_pdf(path.to_path_buf(), self.doc.structure.clone());
+        Self::scan_document(&doc)
+    }
+
+    fn scan_document(doc: &Document) -> Vec<ForensicArtifact> {
+        let mut artifacts = DocumentScanner::new().scan(doc);
+        artifacts.extend(MediaScanner::new().scan(doc));
+        artifacts.extend(PaletteScanner::new().scan(doc));
+        artifacts.extend(MimeSniffer::new().scan(doc));
+        artifacts.extend(TrackingScanner::new().scan(doc));
+        artifacts.extend(UsageRightsScanner::new().scan(doc));
+        artifacts.extend(PortfolioScanner::new().scan(doc));
+        artifacts.extend(InvoiceScanner::new().scan(doc));
+        artifacts.extend(LanguageScanner::new().scan(doc));
+        artifacts.extend(ObjectNumberingAnalyzer::default().scan(doc));
+        artifacts.extend(TrailerAnalyzer::new().scan(doc));
+        artifacts.extend(InfoXmpAnalyzer::new().scan(doc));
+        antiforensics::sort_artifacts(&mut artifacts);
+        AnalyzerConfig::default().apply_min_confidence(artifacts)
+    }
+
+    /// Evidence bundles for `artifacts`, taken from the document as it is now
+    ///
+    /// Call before cleaning, while the flagged objects still exist.
+    pub fn explain(&self, artifacts: &[ForensicArtifact]) -> Vec<ExplainedFinding> {
+        Explainer::new(&self.doc.structure).explain_all(artifacts)
+    }
+
+    /// Reference graph of the document as it is now, marking the objects `artifacts` were found in
+    pub fn object_graph(&self, artifacts: &[ForensicArtifact]) -> ObjectGraph {
+        ObjectGraph::build(&self.doc.structure, artifacts)
+    }
+
+    /// Where each input object is in the document as it is now
+    ///
+    /// Object IDs and artifact locations of the input translate through it
+    /// into the saved output, which keeps the current numbering.
+    pub fn object_map(&self) -> ObjectMap {
+        let mut map = self.object_map.clone();
+        map.drop_missing(&self.doc.structure);
+        map
+    }
+
+    /// Per-page risk summary of `artifacts`, riskiest page first
+    pub fn page_risks(&self, artifacts: &[ForensicArtifact]) -> Vec<PageRisk> {
+        heatmap::page_risks(&self.doc.structure, artifacts)
+    }
+
+    /// Languages of the page text
+    pub fn languages(&self) -> LanguageProfile {
+        language::detect_document(&self.doc)
+    }
+
+    /// Accessibility-relevant structures of the document as currently loaded
+    pub fn accessibility(&self) -> AccessibilityProfile {
+        AccessibilityProfile::of(&self.doc.structure)
+    }
+
+    /// Whether the document is a PDF portfolio
+    pub fn is_portfolio(&self) -> bool {
+        portfolio::is_portfolio(&self.doc.structure)
+    }
+
+    /// Writes each embedded PDF of a portfolio, cleaned, into `out_dir`
+    pub fn split_portfolio(&self, out_dir: &Path) -> Result<Vec<PathBuf>, PipelineError> {
+        Ok(PortfolioCleaner::default().split(&self.doc, out_dir)?)
+    }
+
     pub fn set_metadata(&mut self, key: String, value: String) -> Result<(), PipelineError> {
         self.metadata.insert(key, value);
         Ok(())
@@ -83,8 +497,31 @@ impl PdfPipeline {
                 .map(|(k, v)| (k.as_bytes().to_vec(), lopdf::Object::string(v)))
         );
         
-        self.doc.trailer.set("Info", info_dict);
-        Ok(())
+        self.step("set_metadata", |doc| {
+            doc.structure.trailer.set("Info", info_dict);
+            Ok(())
+        })
+    }
+
+    /// Rewrites every date in canonical PDF form; `utc` also drops their time zone offsets
+    pub fn normalize_dates(&mut self, utc: bool) -> Result<DateNormalizeReport, PipelineError> {
+        self.step("normalize_dates", |doc| Ok(DateNormalizer::new().with_utc(utc).normalize(doc)))
+    }
+
+    /// Rewrites the subtype, size, checksum, dates and file names of the
+    /// embedded files cleaning kept
+    pub fn normalize_attachments(&mut self, dates: AttachmentDates) -> Result<AttachmentNormalizeReport, PipelineError> {
+        self.step("normalize_attachments", |doc| Ok(AttachmentNormalizer::new().with_dates(dates).normalize(doc)?))
+    }
+
+    /// Makes the Info dictionary and XMP agree, `precedence` deciding which side wins
+    pub fn sync_info_xmp(&mut self, precedence: SyncPrecedence) -> Result<SyncReport, PipelineError> {
+        self.step("sync_info_xmp", |doc| Ok(info_xmp::synchronize(&mut doc.structure, precedence)?))
+    }
+
+    /// Embeds a provenance record; call after cleaning and before security
+    pub fn stamp_provenance(&mut self, template: &ProvenanceTemplate) -> Result<ProvenanceRecord, PipelineError> {
+        self.step("provenance", |doc| Ok(provenance::stamp(&mut doc.structure, template)?))
     }
 
     pub fn set_encryption(&mut self, user_pass: Option<String>, owner_pass: Option<String>) {
@@ -97,48 +534,53 @@ impl PdfPipeline {
     }
 
     pub fn apply_security(&mut self) -> Result<(), PipelineError> {
-        // Generate new ID
-        use sha2::{Sha256, Digest};
-        let mut hasher = Sha256::new();
-        hasher.update(b"dummy_content");
-        let result = hasher.finalize();
-        let new_id = result[..16].to_vec();
-        
-        // Set new ID
-        self.doc.trailer.set("ID", vec![
-            lopdf::Object::String(new_id.clone(), lopdf::StringFormat::Hexadecimal),
-            lopdf::Object::String(new_id, lopdf::StringFormat::Hexadecimal),
-        ]);
-
-        // Apply encryption if needed
-        if self.encrypt_user.is_some() || self.encrypt_owner.is_some() {
-            let mut perms = 0;
-            if !self.restrictions.contains(&"print".to_string()) { perms |= 4; }
-            if !self.restrictions.contains(&"copy".to_string()) { perms |= 16; }
-            if !self.restrictions.contains(&"edit".to_string()) { perms |= 8; }
-            if !self.restrictions.contains(&"annotate".to_string()) { perms |= 32; }
-
-            self.doc.set_security(
-                self.encrypt_user.as_deref() // removed unwrap_or
-""),
-                self.encrypt_owner.as_deref() // removed unwrap_or
-""),
-                perms,
-                lopdf::SecurityHandlerRevision::Revision6,
-            )?;
+        let before = self.evidence.as_ref().map(|_| Snapshot::of(&self.doc.structure));
+
+        // Derived from the cleaned content, so nothing of the input's ID survives
+        DocumentIdRegenerator::new(IdRegeneration::ContentHash).regenerate(&mut self.doc);
+
+        // Restrictions alone encrypt with an empty user password, so the document still opens freely
+        if self.encrypt_user.is_some() || self.encrypt_owner.is_some() || !self.restrictions.is_empty() {
+            let encryption_error = |e: PermissionsError| PipelineError::Encryption(e.to_string());
+            let allowed = Permissions::from_restrictions(&self.restrictions).map_err(encryption_error)?;
+            permissions::encrypt(
+                &mut self.doc.structure,
+                self.encrypt_user.as_deref().unwrap_or_default(),
+                self.encrypt_owner.as_deref(),
+                allowed,
+            ).map_err(encryption_error)?;
         }
 
+        if let (Some(log), Some(before)) = (self.evidence.as_mut(), before) {
+            log.record_step("security", &before, &self.doc.structure);
+        }
+        self.record_stage("security");
         Ok(())
     }
 
-    pub fn save<P: AsRef<Path>>(&self, output_path: P) -> Result<(), PipelineError> {
-        self.doc.save(output_path)?;
-        Ok(())
+    /// Saves the document, hashing the bytes as they are written
+    pub fn save<P: AsRef<Path>>(&mut self, output_path: P) -> Result<DocumentHashes, PipelineError> {
+        let output_path = output_path.as_ref();
+        let file = std::fs::File::create(paths::extended(output_path))?;
+        let mut algorithms = self.hash_algorithms.clone();
+        if self.evidence.is_some() && !algorithms.contains(&HashAlgorithm::Sha256) {
+            algorithms.push(HashAlgorithm::Sha256);
+        }
+        let writer = HashingWriter::with_algorithms(BufWriter::new(file), &algorithms);
+
+        let span = tracing::info_span!("write", objects = self.doc.structure.objects.len()).entered();
+        let mut timer = XrefTimer::new(writer, &self.doc.structure);
+        self.doc.structure.save_to(&mut timer)?;
+        let writer = timer.into_inner();
+        drop(span);
+
+        let (_, hashes) = writer.finalize(output_path.display().to_string())?;
+        Ok(hashes)
     }
 
     pub fn verify(&self) -> Result<bool, PipelineError> {
         // Verify document is clean
-        if let Some(info) = self.doc.trailer.get(b"Info") {
+        if let Ok(info) = self.doc.structure.trailer.get(b"Info") {
             let info_dict = info.as_dict()?;
             if info_dict.has(b"ModDate") || info_dict.has(b"CreationDate") {
                 return Ok(false);
@@ -146,7 +588,7 @@ impl PdfPipeline {
         }
 
         // Verify no sensitive entries exist
-        let root = self.doc.get_object(self.doc.get_root()?)?.as_dict()?;
+        let root = self.doc.structure.get_object(self.doc.structure.get_root()?)?.as_dict()?;
         if root.has(b"JavaScript") || root.has(b"OpenAction") || root.has(b"AA") {
             return Ok(false);
         }
@@ -154,3 +596,43 @@ impl PdfPipeline {
         Ok(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Object, Stream};
+
+    #[test]
+    fn test_findings_cleaning_keeps_are_reported() {
+        // A shell script posing as a text attachment; attachments are kept by default
+        let mut doc = lopdf::Document::with_version("1.7");
+        let script = doc.add_object(Stream::new(
+            dictionary! { "Type" => "EmbeddedFile", "Subtype" => "text/plain" },
+            b"#!/bin/sh\nrm -rf /".to_vec(),
+        ));
+        let filespec = doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "UF" => Object::string_literal("notes.txt"),
+            "EF" => dictionary! { "F" => script },
+        });
+        let pages = doc.add_object(dictionary! { "Type" => "Pages", "Kids" => Vec::<Object>::new(), "Count" => 0 });
+        let catalog = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages,
+            "Names" => dictionary! {
+                "EmbeddedFiles" => dictionary! { "Names" => vec![Object::string_literal("notes.txt"), filespec.into()] },
+            },
+        });
+        doc.trailer.set("Root", catalog);
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.pdf");
+        doc.save(&input).unwrap();
+
+        let mut pipeline = PdfPipeline::new(&input).unwrap();
+        let outcome = pipeline.clean_verified(&VerifyConfig { max_rounds: 3, ..Default::default() }).unwrap();
+
+        assert!((1..=3).contains(&outcome.rounds));
+        assert!(outcome.remaining.iter().any(|a| a.artifact_type == ArtifactType::EmbeddedFile), "{:?}", outcome.remaining);
+        assert!(matches!(outcome.ensure_clean(), Err(PipelineError::Verification(_))));
+    }
+}
@@ -0,0 +1,307 @@
+//! Registry for scanners and cleaners supplied by downstream crates
+//!
+//! Registered plugins run inside the engine's own stages: cleaners after
+//! core processing in the clean stage, scanners over the cleaned document
+//! before the severity gate. They draw from the same worker budget as the
+//! built-in subsystems, and each invocation is reported in the processing
+//! result.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use async_trait::async_trait;
+
+use crate::{
+    antiforensics::{types::Document, ForensicArtifact},
+    PdfError,
+    StageOutcome,
+};
+
+/// Custom detection stage
+#[async_trait]
+pub trait Scanner: Send + Sync {
+    /// Unique name used in reports and statistics
+    fn name(&self) -> &str;
+
+    /// Reports artifacts found in the document
+    async fn scan(&self, doc: &Document) -> Result<Vec<ForensicArtifact>, PdfError>;
+}
+
+/// Custom cleaning stage
+#[async_trait]
+pub trait Cleaner: Send + Sync {
+    /// Unique name used in reports and statistics
+    fn name(&self) -> &str;
+
+    /// Modifies the document in place and returns the number of changes made
+    async fn clean(&self, doc: &mut Document) -> Result<usize, PdfError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginKind {
+    Scanner,
+    Cleaner,
+}
+
+/// Outcome of one plugin invocation
+#[derive(Debug, Clone)]
+pub struct PluginReport {
+    pub name: String,
+    pub kind: PluginKind,
+    pub outcome: StageOutcome,
+    pub duration: Duration,
+    /// Artifacts reported by a scanner
+    pub artifacts: Vec<ForensicArtifact>,
+    /// Changes made by a cleaner
+    pub changes: usize,
+}
+
+/// Lifetime totals for a registered plugin
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PluginStats {
+    pub invocations: u64,
+    pub failures: u64,
+    pub artifacts: u64,
+    pub changes: u64,
+    pub total_time: Duration,
+}
+
+#[derive(Default)]
+pub struct PluginRegistry {
+    scanners: RwLock<Vec<Arc<dyn Scanner>>>,
+    cleaners: RwLock<Vec<Arc<dyn Cleaner>>>,
+    stats: RwLock<HashMap<String, PluginStats>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register_scanner(&self, scanner: Box<dyn Scanner>) -> Result<(), PdfError> {
+        self.claim_name(scanner.name())?;
+        self.scanners.write().map_err(|_|
+            PdfError::Processing("Failed to acquire plugin lock".to_string()))?
+            .push(Arc::from(scanner));
+        Ok(())
+    }
+
+    pub fn register_cleaner(&self, cleaner: Box<dyn Cleaner>) -> Result<(), PdfError> {
+        self.claim_name(cleaner.name())?;
+        self.cleaners.write().map_err(|_|
+            PdfError::Processing("Failed to acquire plugin lock".to_string()))?
+            .push(Arc::from(cleaner));
+        Ok(())
+    }
+
+    /// Reserves a plugin name; names are shared by scanners and cleaners
+    fn claim_name(&self, name: &str) -> Result<(), PdfError> {
+        let mut stats = self.stats.write().map_err(|_|
+            PdfError::Processing("Failed to acquire plugin lock".to_string()))?;
+        if stats.contains_key(name) {
+            return Err(PdfError::Configuration(format!("plugin '{}' is already registered", name)));
+        }
+        stats.insert(name.to_string(), PluginStats::default());
+        Ok(())
+    }
+
+    pub fn has_scanners(&self) -> bool {
+        self.scanners.read().map(|s| !s.is_empty()).unwrap_or(false)
+    }
+
+    pub fn has_cleaners(&self) -> bool {
+        self.cleaners.read().map(|c| !c.is_empty()).unwrap_or(false)
+    }
+
+    /// Lifetime statistics per plugin name
+    pub fn stats(&self) -> HashMap<String, PluginStats> {
+        self.stats.read().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    fn record(&self, report: &PluginReport) {
+        if let Ok(mut stats) = self.stats.write() {
+            let entry = stats.entry(report.name.clone()).or_default();
+            entry.invocations += 1;
+            entry.artifacts += report.artifacts.len() as u64;
+            entry.changes += report.changes as u64;
+            entry.total_time += report.duration;
+            if matches!(report.outcome, StageOutcome::Failed(_)) {
+                entry.failures += 1;
+            }
+        }
+    }
+
+    /// Runs every scanner; a failing scanner is reported but does not stop the others
+    pub(crate) async fn run_scanners(&self, doc: &Document) -> Vec<PluginReport> {
+        let scanners = self.scanners.read().map(|s| s.clone()).unwrap_or_default();
+        let mut reports = Vec::with_capacity(scanners.len());
+
+        for scanner in scanners {
+            let started = Instant::now();
+            let (outcome, artifacts) = match scanner.scan(doc).await {
+                Ok(artifacts) => (StageOutcome::Completed, artifacts),
+                Err(e) => (StageOutcome::Failed(e.to_string()), Vec::new()),
+            };
+            let report = PluginReport {
+                name: scanner.name().to_string(),
+                kind: PluginKind::Scanner,
+                outcome,
+                duration: started.elapsed(),
+                artifacts,
+                changes: 0,
+            };
+            self.record(&report);
+            reports.push(report);
+        }
+
+        reports
+    }
+
+    /// Runs every cleaner over `data`, stopping at the first failure
+    ///
+    /// A cleaner that fails leaves the document partially cleaned, so the
+    /// error is returned rather than emitting that output.
+    pub(crate) async fn run_cleaners(
+        &self,
+        data: Vec<u8>,
+        reports: &mut Vec<PluginReport>,
+    ) -> Result<Vec<u8>, PdfError> {
+        let cleaners = self.cleaners.read().map(|c| c.clone()).unwrap_or_default();
+        if cleaners.is_empty() {
+            return Ok(data);
+        }
+
+        let mut doc = Document::from_bytes(PathBuf::from("<memory>"), data.clone())?;
+        let mut total_changes = 0;
+
+        for cleaner in cleaners {
+            let started = Instant::now();
+            let result = cleaner.clean(&mut doc).await;
+            let report = PluginReport {
+                name: cleaner.name().to_string(),
+                kind: PluginKind::Cleaner,
+                outcome: match &result {
+                    Ok(_) => StageOutcome::Completed,
+                    Err(e) => StageOutcome::Failed(e.to_string()),
+                },
+                duration: started.elapsed(),
+                artifacts: Vec::new(),
+                changes: *result.as_ref().unwrap_or(&0),
+            };
+            self.record(&report);
+            reports.push(report);
+
+            total_changes += result.map_err(|e| PdfError::Processing(
+                format!("cleaner plugin '{}' failed: {}", cleaner.name(), e)
+            ))?;
+        }
+
+        if total_changes == 0 {
+            // Nothing changed; keep the original bytes rather than re-serializing
+            return Ok(data);
+        }
+
+        let mut output = Vec::with_capacity(data.len());
+        doc.structure.save_to(&mut output)?;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{antiforensics::RiskLevel, GateOutcome, PdfEngine, ProcessingStage};
+
+    struct FlagEverything;
+
+    #[async_trait]
+    impl Scanner for FlagEverything {
+        fn name(&self) -> &str {
+            "flag-everything"
+        }
+
+        async fn scan(&self, _doc: &Document) -> Result<Vec<ForensicArtifact>, PdfError> {
+            Ok(vec![ForensicArtifact {
+                location: "plugin".into(),
+                risk_level: RiskLevel::Critical,
+                ..Default::default()
+            }])
+        }
+    }
+
+    struct Broken;
+
+    #[async_trait]
+    impl Scanner for Broken {
+        fn name(&self) -> &str {
+            "broken"
+        }
+
+        async fn scan(&self, _doc: &Document) -> Result<Vec<ForensicArtifact>, PdfError> {
+            Err(PdfError::Processing("unsupported filter".into()))
+        }
+    }
+
+    struct DropInfo;
+
+    #[async_trait]
+    impl Cleaner for DropInfo {
+        fn name(&self) -> &str {
+            "drop-info"
+        }
+
+        async fn clean(&self, doc: &mut Document) -> Result<usize, PdfError> {
+            Ok(doc.remove_info().map_or(0, |_| 1))
+        }
+    }
+
+    #[test]
+    fn test_duplicate_names_rejected() {
+        let registry = PluginRegistry::new();
+        registry.register_scanner(Box::new(FlagEverything)).unwrap();
+        assert!(registry.register_scanner(Box::new(FlagEverything)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failing_scanner_does_not_stop_others() {
+        let registry = PluginRegistry::new();
+        registry.register_scanner(Box::new(Broken)).unwrap();
+        registry.register_scanner(Box::new(FlagEverything)).unwrap();
+
+        let reports = registry.run_scanners(&Document::default()).await;
+        assert!(matches!(reports[0].outcome, StageOutcome::Failed(_)));
+        assert_eq!(reports[1].artifacts.len(), 1);
+
+        let stats = registry.stats();
+        assert_eq!(stats["broken"].failures, 1);
+        assert_eq!(stats["flag-everything"].artifacts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cleaner_runs_in_clean_stage() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        engine.register_cleaner(Box::new(DropInfo)).unwrap();
+        let sample_pdf = include_bytes!("../tests/data/sample.pdf");
+
+        let result = engine.process_document(sample_pdf, None).await.unwrap();
+        assert_eq!(result.plugins.len(), 1);
+        assert_eq!(result.plugins[0].kind, PluginKind::Cleaner);
+        assert_eq!(engine.plugin_stats()["drop-info"].invocations, 1);
+    }
+
+    #[tokio::test]
+    async fn test_scanner_artifacts_reach_gate() {
+        let engine = PdfEngine::new(None).await.unwrap();
+        engine.register_scanner(Box::new(FlagEverything)).unwrap();
+        let sample_pdf = include_bytes!("../tests/data/sample.pdf");
+
+        let result = engine.process_document(sample_pdf, None).await.unwrap();
+        assert_eq!(result.stage(ProcessingStage::Scan).unwrap().outcome, StageOutcome::Completed);
+        let decision = result.gate_decision.unwrap();
+        assert_eq!(decision.outcome, GateOutcome::Blocked);
+        assert!(decision.blocking_locations.contains(&"plugin".to_string()));
+    }
+}
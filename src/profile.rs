@@ -0,0 +1,316 @@
+//! Run profiling
+//!
+//! Spans around parsing (`parse`), stream decoding (`decode_filter`),
+//! pattern matching (`regex_match`) and writing (`write`, `write_xref`)
+//! show where the time of a run goes. A [`Profiler`] records every span
+//! of the run and writes them when it ends: as a Chrome trace (`.json`,
+//! for chrome://tracing or Perfetto) with each span's fields, duration
+//! and self time, or as folded stacks (any other extension, for
+//! flamegraph.pl and inferno) weighted by self time in microseconds.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+use lopdf::Document;
+use serde_json::json;
+use tracing::{
+    field::{Field, Visit},
+    span::{self, EnteredSpan},
+    subscriber::SetGlobalDefaultError,
+    Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer, Registry};
+
+/// Output format of a profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// Trace Event Format JSON, one complete event per span
+    ChromeTrace,
+    /// `root;child;leaf microseconds` lines
+    Folded,
+}
+
+impl ProfileFormat {
+    /// Chrome trace for `.json` files, folded stacks otherwise
+    pub fn from_path(path: &Path) -> Self {
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+            Self::ChromeTrace
+        } else {
+            Self::Folded
+        }
+    }
+}
+
+/// A span that has closed
+#[derive(Debug, Clone)]
+struct SpanRecord {
+    name: &'static str,
+    /// Names from the root span down to this one, `;`-separated
+    stack: String,
+    thread: u64,
+    /// Since the profiler started
+    start: Duration,
+    duration: Duration,
+    /// Duration less the time spent in child spans
+    self_time: Duration,
+    fields: BTreeMap<String, String>,
+}
+
+/// Timing of a span still open, kept in its extensions
+struct OpenSpan {
+    start: Instant,
+    children: Duration,
+    thread: u64,
+    fields: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+struct FieldRecorder(BTreeMap<String, String>);
+
+impl Visit for FieldRecorder {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+/// Small per-thread number for trace events; thread IDs are opaque
+fn thread_number() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static NUMBER: u64 = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    NUMBER.with(|number| *number)
+}
+
+struct ProfileLayer {
+    origin: Instant,
+    spans: Arc<Mutex<Vec<SpanRecord>>>,
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for ProfileLayer {
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = FieldRecorder::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(OpenSpan {
+            start: Instant::now(),
+            children: Duration::ZERO,
+            thread: thread_number(),
+            fields: fields.0,
+        });
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(open) = extensions.get_mut::<OpenSpan>() {
+            let mut fields = FieldRecorder(std::mem::take(&mut open.fields));
+            values.record(&mut fields);
+            open.fields = fields.0;
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(open) = span.extensions_mut().remove::<OpenSpan>() else { return };
+        let duration = open.start.elapsed();
+        if let Some(parent) = span.parent() {
+            if let Some(parent) = parent.extensions_mut().get_mut::<OpenSpan>() {
+                parent.children += duration;
+            }
+        }
+        let stack: Vec<&str> = span.scope().from_root().map(|span| span.name()).collect();
+        let record = SpanRecord {
+            name: span.name(),
+            stack: stack.join(";"),
+            thread: open.thread,
+            start: open.start.saturating_duration_since(self.origin),
+            duration,
+            // Children on other threads can add up to more than the parent
+            self_time: duration.saturating_sub(open.children),
+            fields: open.fields,
+        };
+        self.spans.lock().unwrap_or_else(PoisonError::into_inner).push(record);
+    }
+}
+
+/// Records the spans of a run and writes them as a profile
+pub struct Profiler {
+    path: PathBuf,
+    spans: Arc<Mutex<Vec<SpanRecord>>>,
+}
+
+impl Profiler {
+    /// Records every span from now on; fails when another subscriber is installed
+    pub fn install(path: &Path) -> Result<Self, SetGlobalDefaultError> {
+        let (profiler, layer) = Self::new(path);
+        tracing::subscriber::set_global_default(Registry::default().with(layer))?;
+        Ok(profiler)
+    }
+
+    fn new(path: &Path) -> (Self, ProfileLayer) {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let layer = ProfileLayer { origin: Instant::now(), spans: Arc::clone(&spans) };
+        (Self { path: path.to_path_buf(), spans }, layer)
+    }
+
+    /// Writes the spans closed so far, in the format the file name asks for
+    pub fn finish(self) -> io::Result<()> {
+        let spans = std::mem::take(&mut *self.spans.lock().unwrap_or_else(PoisonError::into_inner));
+        let data = match ProfileFormat::from_path(&self.path) {
+            ProfileFormat::ChromeTrace => chrome_trace(&spans)?,
+            ProfileFormat::Folded => folded_stacks(&spans),
+        };
+        fs::write(&self.path, data)
+    }
+}
+
+fn chrome_trace(spans: &[SpanRecord]) -> io::Result<Vec<u8>> {
+    let pid = std::process::id();
+    let events: Vec<serde_json::Value> = spans.iter()
+        .map(|span| {
+            let mut args = json!(span.fields);
+            args["self_us"] = json!(span.self_time.as_micros() as u64);
+            json!({
+                "name": span.name,
+                "cat": "pdf_engine",
+                "ph": "X",
+                "pid": pid,
+                "tid": span.thread,
+                "ts": span.start.as_micros() as u64,
+                "dur": span.duration.as_micros() as u64,
+                "args": args,
+            })
+        })
+        .collect();
+    Ok(serde_json::to_vec(&json!({ "traceEvents": events, "displayTimeUnit": "ms" }))?)
+}
+
+/// Self time per stack, summed; stacks under a microsecond are left out
+fn folded_stacks(spans: &[SpanRecord]) -> Vec<u8> {
+    let mut stacks: BTreeMap<&str, u128> = BTreeMap::new();
+    for span in spans {
+        *stacks.entry(&span.stack).or_default() += span.self_time.as_micros();
+    }
+    stacks.into_iter()
+        .filter(|&(_, micros)| micros > 0)
+        .map(|(stack, micros)| format!("{} {}\n", stack, micros))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/// Closing of every indirect object lopdf writes, always in one write
+const ENDOBJ: &[u8] = b"\nendobj\n";
+
+/// Writer that times the cross-reference section of a document being saved
+///
+/// lopdf writes the objects of the document, then either an `xref` table
+/// and trailer or, for inputs that had one, a cross-reference stream. A
+/// `write_xref` span covers that whole trailer phase: it opens once the
+/// document's objects have been written, counted by their `endobj`, and
+/// stays open until the writer is dropped.
+pub struct XrefTimer<W: Write> {
+    inner: W,
+    objects_left: usize,
+    span: Option<EnteredSpan>,
+}
+
+impl<W: Write> XrefTimer<W> {
+    /// Timer for saving `doc` into `inner`
+    pub fn new(inner: W, doc: &Document) -> Self {
+        // lopdf regenerates these on save rather than writing them
+        let objects_left = doc.objects.values()
+            .filter(|object| !object.type_name().is_ok_and(|name| ["ObjStm", "XRef", "Linearized"].contains(&name)))
+            .count();
+        Self { inner, objects_left, span: None }
+    }
+
+    /// The wrapped writer, closing the span
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for XrefTimer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.span.is_none() && self.objects_left == 0 {
+            self.span = Some(tracing::info_span!("write_xref").entered());
+        }
+        let written = self.inner.write(buf)?;
+        // Stream content is written whole, so cannot pass for a closing
+        if buf == ENDOBJ {
+            self.objects_left = self.objects_left.saturating_sub(1);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_written_as_chrome_trace_and_folded_stacks() {
+        let dir = std::env::temp_dir().join(format!("pdf_engine_profile_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (profiler, layer) = Profiler::new(&dir.join("run.json"));
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            let parse = tracing::info_span!("parse", objects = tracing::field::Empty).entered();
+            for _ in 0..2 {
+                let _decode = tracing::trace_span!("decode_filter", filter = "FlateDecode").entered();
+                std::thread::sleep(Duration::from_millis(2));
+            }
+            parse.record("objects", 12);
+            drop(parse);
+
+            // A stream object and an xref stream left over from the input
+            let mut doc = Document::with_version("1.5");
+            doc.add_object(lopdf::Stream::new(lopdf::Dictionary::new(), b"xref\nendobj\n".to_vec()));
+            doc.add_object(lopdf::Stream::new(lopdf::dictionary! { "Type" => "XRef" }, Vec::new()));
+            let mut timer = XrefTimer::new(Vec::new(), &doc);
+            timer.write_all(b"%PDF-1.5\n1 0 obj\n").unwrap();
+            timer.write_all(b"xref\nendobj\n").unwrap();
+            assert!(timer.span.is_none());
+            timer.write_all(ENDOBJ).unwrap();
+            assert!(timer.span.is_none());
+            timer.write_all(b"3 0 obj\n").unwrap();
+            assert!(timer.span.is_some());
+            assert_eq!(timer.into_inner().len(), 45);
+        });
+
+        let spans = profiler.spans.lock().unwrap().clone();
+        let names: Vec<&str> = spans.iter().map(|span| span.name).collect();
+        assert_eq!(names, vec!["decode_filter", "decode_filter", "parse", "write_xref"]);
+        assert_eq!(spans[2].fields.get("objects").map(String::as_str), Some("12"));
+        assert!(spans[2].self_time < spans[2].duration);
+
+        let folded = String::from_utf8(folded_stacks(&spans)).unwrap();
+        let decode = folded.lines().find_map(|line| line.strip_prefix("parse;decode_filter ")).unwrap();
+        assert!(decode.parse::<u64>().unwrap() >= 4000);
+
+        profiler.finish().unwrap();
+        let trace: serde_json::Value = serde_json::from_slice(&fs::read(dir.join("run.json")).unwrap()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0]["ph"], "X");
+        assert_eq!(events[0]["args"]["filter"], "FlateDecode");
+        assert!(events[2]["dur"].as_u64().unwrap() >= 4000);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
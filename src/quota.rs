@@ -0,0 +1,287 @@
+//! Per-client rate limits and byte quotas
+//!
+//! When the engine serves several API clients, each request is admitted
+//! against its client's limits before a job is started for it. Requests
+//! draw from a token bucket, so short bursts are allowed while the long-run
+//! rate stays bounded; input bytes count against a fixed window. A request
+//! over either limit fails with `PdfError::RateLimited`, which a server
+//! maps to `429 Too Many Requests` with a `Retry-After` header.
+//!
+//! Clients are identified by the engine's operator; requests made without
+//! one share the `anonymous` client.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+use serde::Serialize;
+
+use crate::PdfError;
+
+/// Client name used for requests made without an operator
+pub const ANONYMOUS_CLIENT: &str = "anonymous";
+
+/// Limits for one client
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientLimits {
+    /// Sustained request rate
+    pub requests_per_second: f64,
+    /// Requests that may be made at once after a quiet period
+    pub burst: u32,
+    /// Input bytes allowed per quota window; unlimited when unset
+    pub bytes_per_window: Option<u64>,
+}
+
+impl Default for ClientLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 10.0,
+            burst: 20,
+            bytes_per_window: Some(10 * 1024 * 1024 * 1024), // 10GB
+        }
+    }
+}
+
+/// Limits by client
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaConfig {
+    /// Limits for clients without an entry in `clients`; unlimited when unset
+    pub default_limits: Option<ClientLimits>,
+    /// Limits by client name
+    pub clients: HashMap<String, ClientLimits>,
+    /// Length of the byte quota window
+    pub window: Duration,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            default_limits: None,
+            clients: HashMap::new(),
+            window: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl QuotaConfig {
+    pub fn validate(&self) -> Result<(), PdfError> {
+        if self.window.is_zero() {
+            return Err(PdfError::Configuration("quota window must be non-zero".into()));
+        }
+        for (client, limits) in self.default_limits.iter().map(|l| ("default", l))
+            .chain(self.clients.iter().map(|(c, l)| (c.as_str(), l)))
+        {
+            let rate = limits.requests_per_second;
+            if rate.is_nan() || rate <= 0.0 || limits.burst == 0 {
+                return Err(PdfError::Configuration(format!(
+                    "rate limit for {} must allow at least one request",
+                    client,
+                )));
+            }
+            if rate.is_infinite() {
+                return Err(PdfError::Configuration(format!(
+                    "rate limit for {} must be finite; leave the client unlimited instead",
+                    client,
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn limits(&self, client: &str) -> Option<&ClientLimits> {
+        self.clients.get(client).or(self.default_limits.as_ref())
+    }
+}
+
+/// Admission counters of one client
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct QuotaUsage {
+    /// Requests admitted since the engine started
+    pub admitted: u64,
+    /// Requests refused for exceeding a limit
+    pub rejected: u64,
+    /// Input bytes admitted in the current window
+    pub window_bytes: u64,
+    pub window_limit: Option<u64>,
+}
+
+#[derive(Debug)]
+struct ClientState {
+    tokens: f64,
+    refilled_at: Instant,
+    window_started: Instant,
+    usage: QuotaUsage,
+}
+
+/// Tracks every client's consumption against its limits
+#[derive(Debug)]
+pub struct QuotaTracker {
+    config: RwLock<QuotaConfig>,
+    clients: Mutex<HashMap<String, ClientState>>,
+}
+
+impl QuotaTracker {
+    pub fn new(config: QuotaConfig) -> Result<Self, PdfError> {
+        config.validate()?;
+        Ok(Self {
+            config: RwLock::new(config),
+            clients: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Admits one request of `bytes` input bytes for `client`
+    pub fn admit(&self, client: &str, bytes: u64) -> Result<(), PdfError> {
+        self.admit_at(client, bytes, Instant::now())
+    }
+
+    fn admit_at(&self, client: &str, bytes: u64, now: Instant) -> Result<(), PdfError> {
+        let config = self.config.read()
+            .map_err(|_| PdfError::Processing("quota config lock poisoned".into()))?;
+        let Some(limits) = config.limits(client) else { return Ok(()) };
+        let mut clients = self.clients.lock()
+            .map_err(|_| PdfError::Processing("quota state lock poisoned".into()))?;
+        let state = clients.entry(client.to_string()).or_insert_with(|| ClientState {
+            tokens: limits.burst as f64,
+            refilled_at: now,
+            window_started: now,
+            usage: QuotaUsage::default(),
+        });
+
+        let elapsed = now.saturating_duration_since(state.refilled_at).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * limits.requests_per_second).min(limits.burst as f64);
+        state.refilled_at = now;
+        if now.saturating_duration_since(state.window_started) >= config.window {
+            state.window_started = now;
+            state.usage.window_bytes = 0;
+        }
+        state.usage.window_limit = limits.bytes_per_window;
+
+        if state.tokens < 1.0 {
+            state.usage.rejected += 1;
+            let wait = (1.0 - state.tokens) / limits.requests_per_second;
+            return Err(PdfError::RateLimited {
+                client: client.to_string(),
+                reason: format!("more than {} requests/s", limits.requests_per_second),
+                // A rate so low the wait overflows a Duration gives no hint
+                retry_after: Duration::try_from_secs_f64(wait).ok(),
+            });
+        }
+        if let Some(limit) = limits.bytes_per_window {
+            if state.usage.window_bytes.saturating_add(bytes) > limit {
+                state.usage.rejected += 1;
+                // A request larger than the whole quota never fits
+                let retry_after = (bytes <= limit).then(|| {
+                    config.window.saturating_sub(now.saturating_duration_since(state.window_started))
+                });
+                return Err(PdfError::RateLimited {
+                    client: client.to_string(),
+                    reason: format!(
+                        "{} bytes would exceed the quota of {} bytes ({} used)",
+                        bytes, limit, state.usage.window_bytes,
+                    ),
+                    retry_after,
+                });
+            }
+        }
+
+        state.tokens -= 1.0;
+        state.usage.admitted += 1;
+        state.usage.window_bytes += bytes;
+        Ok(())
+    }
+
+    /// Usage of every client that has made a limited request
+    pub fn usage(&self) -> HashMap<String, QuotaUsage> {
+        self.clients.lock()
+            .map(|clients| clients.iter().map(|(name, state)| (name.clone(), state.usage.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the limits; consumption so far is kept
+    pub fn reconfigure(&self, config: QuotaConfig) -> Result<(), PdfError> {
+        config.validate()?;
+        *self.config.write()
+            .map_err(|_| PdfError::Processing("quota config lock poisoned".into()))? = config;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(limits: ClientLimits) -> QuotaTracker {
+        QuotaTracker::new(QuotaConfig {
+            clients: HashMap::from([("ci".to_string(), limits)]),
+            window: Duration::from_secs(60),
+            ..Default::default()
+        }).unwrap()
+    }
+
+    #[test]
+    fn test_burst_then_refill() {
+        let tracker = tracker(ClientLimits { requests_per_second: 2.0, burst: 2, bytes_per_window: None });
+        let start = Instant::now();
+        assert!(tracker.admit_at("ci", 0, start).is_ok());
+        assert!(tracker.admit_at("ci", 0, start).is_ok());
+
+        let refused = tracker.admit_at("ci", 0, start).unwrap_err();
+        assert!(matches!(refused, PdfError::RateLimited { retry_after: Some(_), .. }));
+        assert_eq!(refused.status_code(), 429);
+
+        assert!(tracker.admit_at("ci", 0, start + Duration::from_millis(500)).is_ok());
+        let usage = &tracker.usage()["ci"];
+        assert_eq!((usage.admitted, usage.rejected), (3, 1));
+    }
+
+    #[test]
+    fn test_byte_quota_resets_with_window() {
+        let tracker = tracker(ClientLimits { requests_per_second: 100.0, burst: 100, bytes_per_window: Some(1000) });
+        let start = Instant::now();
+        assert!(tracker.admit_at("ci", 800, start).is_ok());
+        assert!(tracker.admit_at("ci", 300, start).is_err());
+        assert!(matches!(
+            tracker.admit_at("ci", 5000, start),
+            Err(PdfError::RateLimited { retry_after: None, .. })
+        ));
+        assert!(tracker.admit_at("ci", 300, start + Duration::from_secs(61)).is_ok());
+        assert_eq!(tracker.usage()["ci"].window_bytes, 300);
+    }
+
+    #[test]
+    fn test_clients_without_limits_are_unrestricted() {
+        let tracker = tracker(ClientLimits { requests_per_second: 1.0, burst: 1, bytes_per_window: None });
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(tracker.admit_at(ANONYMOUS_CLIENT, 1 << 30, now).is_ok());
+        }
+        assert!(tracker.usage().get(ANONYMOUS_CLIENT).is_none());
+    }
+
+    #[test]
+    fn test_invalid_limits_rejected() {
+        let config = QuotaConfig {
+            default_limits: Some(ClientLimits { burst: 0, ..Default::default() }),
+            ..Default::default()
+        };
+        assert!(QuotaTracker::new(config).is_err());
+
+        for rate in [-1.0, 0.0, f64::NAN, f64::INFINITY] {
+            let limits = ClientLimits { requests_per_second: rate, ..Default::default() };
+            let config = QuotaConfig { default_limits: Some(limits), ..Default::default() };
+            assert!(QuotaTracker::new(config).is_err(), "rate {} accepted", rate);
+        }
+    }
+
+    #[test]
+    fn test_tiny_rate_refuses_without_retry_hint() {
+        let tracker = tracker(ClientLimits { requests_per_second: 1e-300, burst: 1, bytes_per_window: None });
+        let now = Instant::now();
+        assert!(tracker.admit_at("ci", 0, now).is_ok());
+        assert!(matches!(
+            tracker.admit_at("ci", 0, now),
+            Err(PdfError::RateLimited { retry_after: None, .. })
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Page rasterization for visual verification
+//!
+//! Renders pages to RGBA images so reports can show before/after
+//! thumbnails proving that cleaning or redaction left the layout intact.
+//! Rendering uses PDFium through `pdfium-render` and is only built with
+//! the `render` feature. The PDFium library is loaded at runtime from
+//! `PDFIUM_LIBRARY_PATH` (a directory) when set, else from the system
+//! library path; without it `Rasterizer::new` fails and callers carry on
+//! without images.
+
+use std::{collections::BTreeSet, io::Cursor, path::Path};
+use image::{imageops::FilterType, ImageFormat, RgbaImage};
+use pdfium_render::prelude::*;
+use thiserror::Error;
+
+use crate::visual_diff::{Frame, PageDiff, VisualDiffConfig};
+
+/// Directory to load the PDFium library from, overriding the system path
+pub const LIBRARY_PATH_ENV: &str = "PDFIUM_LIBRARY_PATH";
+
+/// Points per inch of PDF user space
+const POINTS_PER_INCH: f32 = 72.0;
+
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("PDFium error: {0}")]
+    Pdfium(#[from] PdfiumError),
+    #[error("Page {page} out of range; the document has {count} page(s)")]
+    PageOutOfRange { page: usize, count: usize },
+    #[error("Invalid resolution: {0} dpi")]
+    InvalidDpi(f32),
+    #[error("Image encoding error: {0}")]
+    Encode(#[from] image::ImageError),
+}
+
+/// Loaded PDFium library
+pub struct Rasterizer {
+    pdfium: Pdfium,
+}
+
+impl Rasterizer {
+    /// Binds to the PDFium library
+    pub fn new() -> Result<Self, RenderError> {
+        let bindings = match std::env::var_os(LIBRARY_PATH_ENV) {
+            Some(dir) => Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(Path::new(&dir)))?,
+            None => Pdfium::bind_to_system_library()?,
+        };
+        Ok(Self { pdfium: Pdfium::new(bindings) })
+    }
+
+    /// Opens a document from its bytes for rendering
+    pub fn load<'a>(&'a self, bytes: &'a [u8]) -> Result<RenderDocument<'a>, RenderError> {
+        let document = self.pdfium.load_pdf_from_byte_slice(bytes, None)?;
+        Ok(RenderDocument { document })
+    }
+}
+
+/// Document opened for rendering
+pub struct RenderDocument<'a> {
+    document: PdfDocument<'a>,
+}
+
+impl RenderDocument<'_> {
+    pub fn page_count(&self) -> usize {
+        self.document.pages().len() as usize
+    }
+
+    /// Renders page `page` (0-based) at `dpi` dots per inch
+    pub fn render_page(&self, page: usize, dpi: f32) -> Result<RgbaImage, RenderError> {
+        if !(dpi.is_finite() && dpi > 0.0) {
+            return Err(RenderError::InvalidDpi(dpi));
+        }
+        let count = self.page_count();
+        if page >= count {
+            return Err(RenderError::PageOutOfRange { page, count });
+        }
+        let config = PdfRenderConfig::new()
+            .scale_page_by_factor(dpi / POINTS_PER_INCH)
+            .render_form_data(true)
+            .render_annotations(true);
+        let bitmap = self.document.pages().get(page as u16)?.render_with_config(&config)?;
+        Ok(bitmap.as_image().into_rgba8())
+    }
+}
+
+/// Renders every page of `before` and `after` at `dpi` and compares them
+///
+/// `expected` holds the pages the requested operations may change. Pages
+/// only one document has count as entirely changed.
+pub fn diff_documents(
+    before: &RenderDocument,
+    after: &RenderDocument,
+    dpi: f32,
+    config: &VisualDiffConfig,
+    expected: &BTreeSet<usize>,
+) -> Result<Vec<PageDiff>, RenderError> {
+    let (before_pages, after_pages) = (before.page_count(), after.page_count());
+    (0..before_pages.max(after_pages))
+        .map(|page| {
+            let expected = expected.contains(&page);
+            if page >= before_pages || page >= after_pages {
+                return Ok(PageDiff::missing(page, config, expected));
+            }
+            let (a, b) = (before.render_page(page, dpi)?, after.render_page(page, dpi)?);
+            Ok(PageDiff::compare(page, frame(&a), frame(&b), config, expected))
+        })
+        .collect()
+}
+
+fn frame(image: &RgbaImage) -> Frame<'_> {
+    Frame { width: image.width(), height: image.height(), rgba: image.as_raw() }
+}
+
+/// `image` scaled down to fit `max_side` pixels, encoded as PNG
+///
+/// Images already small enough are encoded as they are.
+pub fn thumbnail_png(image: &RgbaImage, max_side: u32) -> Result<Vec<u8>, RenderError> {
+    let (width, height) = thumbnail_size(image.width(), image.height(), max_side);
+    let mut png = Cursor::new(Vec::new());
+    if (width, height) == image.dimensions() {
+        image.write_to(&mut png, ImageFormat::Png)?;
+    } else {
+        image::imageops::resize(image, width, height, FilterType::Triangle).write_to(&mut png, ImageFormat::Png)?;
+    }
+    Ok(png.into_inner())
+}
+
+/// Dimensions of `width` x `height` scaled to fit `max_side`, keeping the aspect ratio
+fn thumbnail_size(width: u32, height: u32, max_side: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest <= max_side || longest == 0 {
+        return (width, height);
+    }
+    let scale = |side: u32| ((side as u64 * max_side as u64) / longest as u64).max(1) as u32;
+    (scale(width), scale(height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_size_keeps_aspect_ratio() {
+        assert_eq!(thumbnail_size(1700, 2200, 220), (170, 220));
+        assert_eq!(thumbnail_size(2200, 10, 220), (220, 1));
+        assert_eq!(thumbnail_size(100, 50, 220), (100, 50));
+    }
+
+    #[test]
+    fn test_thumbnail_png() {
+        let image = RgbaImage::from_pixel(400, 200, image::Rgba([255, 0, 0, 255]));
+        let png = thumbnail_png(&image, 100).unwrap();
+        let decoded = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (100, 50));
+    }
+}
@@ -0,0 +1,346 @@
+//! Age- and size-based retention of backups, quarantine and caches
+//!
+//! Pre-clean backups, quarantined inputs and the output cache stay on disk
+//! until something removes them. A `RetentionRule` gives one such store a
+//! maximum age and size; `RetentionManager::purge` applies every rule,
+//! deleting expired files and then the oldest ones until the store fits,
+//! and `RetentionManager::start` repeats the purge in the background for
+//! long-running engines. Files of a rule with `secure_delete` are
+//! overwritten with random bytes before they are unlinked; on SSDs and
+//! copy-on-write filesystems that is best effort only.
+//!
+//! Purging backups removes the backed-up inputs but keeps the transaction
+//! log, so a later rollback reports the backup as pruned. Cache rules go
+//! through `OutputStore::gc`, which keeps the store's entries consistent.
+//! A quarantine directory is only purged if it holds `QUARANTINE_MARKER`,
+//! as one made by `create_quarantine` does, so a rule pointing at the
+//! wrong directory fails instead of deleting everything under it.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::store::{GcPolicy, GcReport, OutputStore};
+
+/// File that marks a directory as a quarantine store
+pub const QUARANTINE_MARKER: &str = ".pdf-engine-quarantine";
+
+/// Kind of store a rule applies to, which decides what in it is purged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreKind {
+    /// `BackupMode::Disk` directory; only the backed-up inputs, not the transaction log
+    Backups,
+    /// Directory of quarantined inputs; every file under it but the marker
+    Quarantine,
+    /// `OutputStore` root
+    Cache,
+}
+
+/// Limits for one store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionRule {
+    pub kind: StoreKind,
+    pub dir: PathBuf,
+    /// Remove files older than this many days
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+    /// Remove the oldest files until the store is at most this many megabytes
+    #[serde(default)]
+    pub max_size_mb: Option<u64>,
+    /// Overwrite files before deleting them
+    #[serde(default)]
+    pub secure_delete: bool,
+}
+
+impl RetentionRule {
+    pub fn policy(&self) -> GcPolicy {
+        GcPolicy {
+            max_bytes: self.max_size_mb.map(|mb| mb * 1024 * 1024),
+            max_age: self.max_age_days.map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            secure_delete: self.secure_delete,
+        }
+    }
+}
+
+/// Rules for every managed store, as read from a policy file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    pub rules: Vec<RetentionRule>,
+    /// Minutes between background purges; `None` purges only when asked
+    #[serde(default)]
+    pub interval_minutes: Option<u64>,
+}
+
+impl RetentionConfig {
+    /// Reads a JSON policy file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+/// What purging one store removed and kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PurgeReport {
+    pub kind: StoreKind,
+    pub dir: PathBuf,
+    pub result: GcReport,
+}
+
+/// Applies retention rules, on request or periodically
+#[derive(Debug, Default)]
+pub struct RetentionManager {
+    config: RetentionConfig,
+}
+
+impl RetentionManager {
+    pub fn new(config: RetentionConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &RetentionConfig {
+        &self.config
+    }
+
+    /// Purges every store, stopping at the first that fails
+    pub fn purge(&self) -> io::Result<Vec<PurgeReport>> {
+        self.config.rules.iter()
+            .map(|rule| Ok(PurgeReport { kind: rule.kind, dir: rule.dir.clone(), result: purge_store(rule)? }))
+            .collect()
+    }
+
+    /// Whether `start` purges in the background: an interval is set and there are rules
+    pub fn is_scheduled(&self) -> bool {
+        self.config.interval_minutes.is_some_and(|minutes| minutes > 0) && !self.config.rules.is_empty()
+    }
+
+    /// Purges every `interval_minutes` on the tokio runtime, starting now,
+    /// until the manager is dropped; does nothing unless `is_scheduled`
+    ///
+    /// A store that fails is logged and purged again next time. Callers
+    /// authorize the schedule; `PdfEngine::new` does so as for `Operation::Shred`.
+    pub fn start(self: &Arc<Self>) {
+        let Some(minutes) = self.config.interval_minutes.filter(|_| self.is_scheduled()) else { return };
+        let manager: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(Duration::from_secs(minutes * 60));
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                let Some(manager) = manager.upgrade() else { break };
+                let purged = tokio::task::spawn_blocking(move || manager.purge_each()).await;
+                if let Err(e) = purged {
+                    log::warn!("retention purge panicked: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Purges every store, logging failures instead of stopping at them
+    fn purge_each(&self) {
+        for rule in &self.config.rules {
+            match purge_store(rule) {
+                Ok(report) if report.removed > 0 => log::info!(
+                    "retention: removed {} file(s), {} bytes, from {:?} store {}",
+                    report.removed, report.bytes_freed, rule.kind, rule.dir.display(),
+                ),
+                Ok(_) => {}
+                Err(e) => log::warn!("retention: purging {:?} store {} failed: {}", rule.kind, rule.dir.display(), e),
+            }
+        }
+    }
+}
+
+/// Applies one rule
+pub fn purge_store(rule: &RetentionRule) -> io::Result<GcReport> {
+    let policy = rule.policy();
+    match rule.kind {
+        StoreKind::Cache if rule.dir.exists() => OutputStore::open(&rule.dir)?.gc(&policy),
+        StoreKind::Cache => Ok(GcReport::default()),
+        StoreKind::Backups => purge_files(&rule.dir.join("backups"), &policy),
+        StoreKind::Quarantine => {
+            if rule.dir.exists() && !rule.dir.join(QUARANTINE_MARKER).is_file() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} has no {} marker; refusing to purge a directory not created as a quarantine", rule.dir.display(), QUARANTINE_MARKER),
+                ));
+            }
+            purge_files(&rule.dir, &policy)
+        }
+    }
+}
+
+/// Creates a quarantine directory at `dir`, or marks an existing one, so retention rules may purge it
+pub fn create_quarantine(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::OpenOptions::new().create(true).append(true).open(dir.join(QUARANTINE_MARKER))?;
+    Ok(())
+}
+
+struct StoredFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Deletes files under `dir` over `policy`'s age limit, then the oldest
+/// until the rest fit its size limit
+fn purge_files(dir: &Path, policy: &GcPolicy) -> io::Result<GcReport> {
+    let mut files = Vec::new();
+    match collect_files(dir, &mut files) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(GcReport::default()),
+        result => result?,
+    }
+    let marker = dir.join(QUARANTINE_MARKER);
+    files.retain(|file| file.path != marker);
+    files.sort_by_key(|file| file.modified);
+
+    let cutoff = policy.max_age.and_then(|age| SystemTime::now().checked_sub(age));
+    let mut total: u64 = files.iter().map(|file| file.size).sum();
+    let mut report = GcReport::default();
+    for file in files {
+        let expired = cutoff.is_some_and(|cutoff| file.modified < cutoff);
+        let oversized = policy.max_bytes.is_some_and(|max| total > max);
+        if expired || oversized {
+            match remove_file(&file.path, policy.secure_delete) {
+                Ok(()) => {}
+                // Removed by someone else meanwhile
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            report.removed += 1;
+            report.bytes_freed += file.size;
+            total -= file.size;
+        } else {
+            report.remaining += 1;
+        }
+    }
+    report.bytes_remaining = total;
+    Ok(report)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<StoredFile>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else if metadata.is_file() {
+            files.push(StoredFile { path: entry.path(), size: metadata.len(), modified: metadata.modified()? });
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the file at `path`, first overwriting it with random bytes when `secure`
+///
+/// A file with other hard links (e.g. a cached output linked into place)
+/// is only unlinked: overwriting it would destroy what the other names
+/// still refer to.
+pub fn remove_file(path: &Path, secure: bool) -> io::Result<()> {
+    if secure && !has_other_links(path)? {
+        overwrite(path)?;
+    }
+    match fs::remove_file(path) {
+        // Windows refuses to delete read-only files
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            crate::store::make_writable(path)?;
+            fs::remove_file(path)
+        }
+        result => result,
+    }
+}
+
+fn overwrite(path: &Path) -> io::Result<()> {
+    if fs::metadata(path)?.permissions().readonly() {
+        crate::store::make_writable(path)?;
+    }
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut rng = rand::thread_rng();
+    while remaining > 0 {
+        let n = remaining.min(buffer.len() as u64) as usize;
+        rng.fill_bytes(&mut buffer[..n]);
+        file.write_all(&buffer[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()
+}
+
+#[cfg(unix)]
+fn has_other_links(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(fs::metadata(path)?.nlink() > 1)
+}
+
+#[cfg(not(unix))]
+fn has_other_links(_path: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired_then_oldest_files_purged() {
+        let dir = tempfile::tempdir().unwrap();
+        let backups = dir.path().join("backups");
+        fs::create_dir_all(&backups).unwrap();
+        fs::write(dir.path().join("transactions.jsonl"), b"{}\n").unwrap();
+        let now = SystemTime::now();
+        for (name, age_days) in [("old.pdf", 40), ("older.pdf", 50), ("recent.pdf", 2), ("new.pdf", 0)] {
+            let path = backups.join(name);
+            fs::write(&path, vec![b'x'; 100]).unwrap();
+            let modified = now - Duration::from_secs(age_days * 24 * 60 * 60);
+            fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        }
+        let mut linked = fs::File::create(dir.path().join("linked.pdf")).unwrap();
+        linked.write_all(b"shared content").unwrap();
+        drop(linked);
+
+        // Age first, then the oldest of the rest; the log is not a backup
+        let rule = RetentionRule {
+            kind: StoreKind::Backups,
+            dir: dir.path().to_path_buf(),
+            max_age_days: Some(30),
+            max_size_mb: None,
+            secure_delete: true,
+        };
+        let report = purge_store(&rule).unwrap();
+        assert_eq!((report.removed, report.bytes_freed, report.remaining), (2, 200, 2));
+        assert!(!backups.join("old.pdf").exists() && backups.join("recent.pdf").exists());
+        assert!(dir.path().join("transactions.jsonl").exists());
+
+        let policy = GcPolicy { max_bytes: Some(150), ..Default::default() };
+        let report = purge_files(&backups, &policy).unwrap();
+        assert_eq!((report.removed, report.bytes_remaining), (1, 100));
+        assert!(backups.join("new.pdf").exists());
+
+        // A hard-linked file loses one name, not its content
+        let other = dir.path().join("other-name.pdf");
+        if fs::hard_link(dir.path().join("linked.pdf"), &other).is_ok() {
+            remove_file(&dir.path().join("linked.pdf"), true).unwrap();
+            assert_eq!(fs::read(&other).unwrap(), b"shared content");
+        }
+
+        let missing = RetentionRule { kind: StoreKind::Quarantine, dir: dir.path().join("none"), ..rule.clone() };
+        assert_eq!(purge_store(&missing).unwrap(), GcReport::default());
+
+        // A directory the tool did not mark is never purged
+        let unmarked = RetentionRule { kind: StoreKind::Quarantine, dir: dir.path().to_path_buf(), max_size_mb: Some(0), ..rule };
+        assert_eq!(purge_store(&unmarked).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(dir.path().join("transactions.jsonl").exists());
+        create_quarantine(dir.path()).unwrap();
+        let report = purge_store(&unmarked).unwrap();
+        assert!(report.removed > 0 && !dir.path().join("transactions.jsonl").exists());
+        assert!(dir.path().join(QUARANTINE_MARKER).exists());
+    }
+}
@@ -0,0 +1,192 @@
+//! Retrying transient IO failures
+//!
+//! Batch and daemon runs regularly meet files that are briefly locked by
+//! a scanner, a sync client or a writer that has not finished. Such
+//! errors are retried with exponential backoff; anything else fails on
+//! the first attempt.
+
+use std::{future::Future, io, time::Duration};
+use rand::Rng;
+
+use crate::PdfError;
+
+/// Largest backoff exponent; any multiplier above 1 has passed a sensible `max_backoff` long before
+const MAX_EXPONENT: u32 = 64;
+
+/// How often and how patiently transient failures are retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts including the first; 1 disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound for any single delay
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after every retry
+    pub multiplier: f64,
+    /// Randomize delays by up to a quarter so parallel workers spread out
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+/// Result of an operation together with how many retries it took
+#[derive(Debug)]
+pub struct Retried<T> {
+    pub result: Result<T, PdfError>,
+    pub retries: u32,
+}
+
+impl RetryPolicy {
+    /// Policy that never retries
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Default::default() }
+    }
+
+    /// Delay before retry number `retry` (1-based), without jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        if self.initial_backoff.is_zero() {
+            return Duration::ZERO;
+        }
+        let factor = self.multiplier.max(1.0).powi(retry.saturating_sub(1).min(MAX_EXPONENT) as i32);
+        // Products too large for a Duration, or not finite, saturate at the cap instead of panicking
+        Duration::try_from_secs_f64(self.initial_backoff.as_secs_f64() * factor)
+            .map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+    }
+
+    /// Runs `operation` until it succeeds, fails permanently or attempts run out
+    pub async fn run<T, F, Fut>(&self, mut operation: F) -> Retried<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, PdfError>>,
+    {
+        let mut retries = 0;
+        loop {
+            match operation().await {
+                Err(e) if retries + 1 < self.max_attempts && is_retryable(&e) => {
+                    retries += 1;
+                    let mut delay = self.backoff(retries);
+                    if self.jitter {
+                        let scaled = delay.as_secs_f64() * rand::thread_rng().gen_range(0.75..=1.0);
+                        delay = Duration::try_from_secs_f64(scaled).unwrap_or(delay);
+                    }
+                    log::debug!("retry {} of {} in {:?} after: {}", retries, self.max_attempts - 1, delay, e);
+                    tokio::time::sleep(delay).await;
+                }
+                result => return Retried { result, retries },
+            }
+        }
+    }
+}
+
+/// Whether an error is likely to go away on its own
+pub fn is_retryable(error: &PdfError) -> bool {
+    match error.root_cause() {
+        PdfError::Io(e) => is_transient_io(e),
+        _ => false,
+    }
+}
+
+fn is_transient_io(error: &io::Error) -> bool {
+    if matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted | io::ErrorKind::TimedOut) {
+        return true;
+    }
+
+    // EBUSY and ETXTBSY share their numbers across Linux and the BSDs
+    #[cfg(unix)]
+    const TRANSIENT_OS_ERRORS: &[i32] = &[16, 26];
+    // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+    #[cfg(windows)]
+    const TRANSIENT_OS_ERRORS: &[i32] = &[32, 33];
+    #[cfg(not(any(unix, windows)))]
+    const TRANSIENT_OS_ERRORS: &[i32] = &[];
+
+    error.raw_os_error().is_some_and(|code| TRANSIENT_OS_ERRORS.contains(&code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            jitter: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let policy = RetryPolicy { jitter: false, ..Default::default() };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(20), Duration::from_secs(5));
+
+        // Exponents and products past what a Duration holds saturate at the cap
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(5));
+        let steep = RetryPolicy { multiplier: f64::MAX, max_backoff: Duration::MAX, ..policy.clone() };
+        assert_eq!(steep.backoff(3), Duration::MAX);
+        let infinite = RetryPolicy { multiplier: f64::INFINITY, ..policy };
+        assert_eq!(infinite.backoff(2), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors_retried() {
+        let calls = AtomicU32::new(0);
+        let retried = policy().run(|| async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(PdfError::Io(io::Error::from(io::ErrorKind::WouldBlock)))
+            } else {
+                Ok("done")
+            }
+        }).await;
+
+        assert_eq!(retried.result.unwrap(), "done");
+        assert_eq!(retried.retries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_not_retried() {
+        let calls = AtomicU32::new(0);
+        let retried: Retried<()> = policy().run(|| async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(PdfError::Io(io::Error::from(io::ErrorKind::NotFound)))
+        }).await;
+
+        assert!(retried.result.is_err());
+        assert_eq!(retried.retries, 0);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_attempts_exhausted() {
+        let retried: Retried<()> = policy().run(|| async {
+            Err(PdfError::Io(io::Error::from(io::ErrorKind::Interrupted)))
+        }).await;
+
+        assert!(retried.result.is_err());
+        assert_eq!(retried.retries, 3);
+    }
+
+    #[test]
+    fn test_context_wrapped_errors_classified_by_cause() {
+        let busy = PdfError::Io(io::Error::from(io::ErrorKind::TimedOut));
+        assert!(is_retryable(&PdfError::Context {
+            context: crate::ErrorContext::default(),
+            source: Box::new(busy),
+        }));
+        assert!(!is_retryable(&PdfError::Validation("bad".into())));
+    }
+}
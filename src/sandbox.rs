@@ -0,0 +1,579 @@
+//! Stream decoding in a confined child process
+//!
+//! Decompression filters and image decoders are the code most exposed to
+//! hostile input. With sandboxing on, every filtered stream of a document
+//! is decoded, and every JPEG image parsed, by a child process instead:
+//! the program re-executed with `WORKER_ARG`, talking length-prefixed
+//! bincode frames over its stdin and stdout. A decoder exploit, a
+//! decompression bomb or a hang takes down the child, not the host; the
+//! stream concerned is neutralized and a fresh child serves the rest.
+//!
+//! The child confines itself before reading any input: resource limits
+//! on address space, CPU time and file size on Unix, and on Linux
+//! (x86_64, aarch64) a seccomp filter refusing every system call beyond
+//! reading and writing its pipes, memory management and exiting. On
+//! Windows the parent places it in a job object with a memory limit that
+//! may not start processes. Elsewhere it is only a separate process.
+//!
+//! Programs using the sandbox must call `run_worker` when started with
+//! `WORKER_ARG`, before doing anything else.
+
+use std::{
+    io::{self, Read, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+use lopdf::{Dictionary, Object, ObjectId, Stream};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+/// First argument that makes the program run as a decoding worker
+pub const WORKER_ARG: &str = "--sandbox-worker";
+
+/// Filters the worker decodes; other streams are left alone
+const STREAM_FILTERS: [&str; 2] = ["FlateDecode", "LZWDecode"];
+
+/// Largest frame either side accepts
+const MAX_FRAME: u64 = 1 << 31;
+
+#[derive(Debug, Error)]
+pub enum SandboxError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+    #[error("Decoder process died: {0}")]
+    WorkerDied(String),
+    #[error("Decoding timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("Decoding failed: {0}")]
+    Decode(String),
+}
+
+/// Limits of the decoding process
+#[derive(Debug, Clone)]
+pub struct SandboxConfig {
+    /// Program to run as the worker; defaults to the current executable
+    pub program: Option<PathBuf>,
+    /// Address space (Unix) or process memory (Windows) limit in bytes
+    pub memory_limit: u64,
+    /// CPU time limit of one worker, in seconds (Unix)
+    pub cpu_seconds: u64,
+    /// Wall-clock limit of one request
+    pub timeout: Duration,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            program: None,
+            memory_limit: 1 << 30,
+            cpu_seconds: 60,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Request {
+    /// Decode `data` through `filters`, with the integer entries of `/DecodeParms`
+    Stream { filters: Vec<String>, params: Vec<(String, i64)>, data: Vec<u8> },
+    /// Parse a JPEG image
+    Jpeg { data: Vec<u8> },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Response {
+    /// Sent once, after confinement; names what confines the worker
+    Ready { confinement: String },
+    Decoded(Vec<u8>),
+    Image { width: u32, height: u32 },
+    Failed(String),
+}
+
+/// What decoding a document in the sandbox did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SandboxReport {
+    /// Streams decoded by the worker and stored re-encoded by the host
+    pub decoded: usize,
+    /// JPEG images the worker parsed successfully
+    pub images_checked: usize,
+    /// Streams the worker failed or died on, now empty, with the reason
+    pub rejected: Vec<(ObjectId, String)>,
+    /// How the worker was confined
+    pub confinement: String,
+}
+
+/// Connection to a decoding worker, restarted after failures
+pub struct SandboxedDecoder {
+    config: SandboxConfig,
+    worker: Option<Worker>,
+    confinement: String,
+}
+
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    responses: Receiver<Result<Response, SandboxError>>,
+    #[cfg(windows)]
+    _job: job::JobObject,
+}
+
+impl SandboxedDecoder {
+    /// Starts a worker; fails when it cannot be started or confined
+    pub fn spawn(config: SandboxConfig) -> Result<Self, SandboxError> {
+        let mut decoder = Self { config, worker: None, confinement: String::new() };
+        decoder.start()?;
+        Ok(decoder)
+    }
+
+    /// How the worker is confined, e.g. `seccomp, rlimits`
+    pub fn confinement(&self) -> &str {
+        &self.confinement
+    }
+
+    /// Decodes a Flate or LZW stream
+    pub fn decode_stream(&mut self, stream: &Stream) -> Result<Vec<u8>, SandboxError> {
+        let _span = tracing::trace_span!("decode_filter", sandboxed = true, encoded_bytes = stream.content.len()).entered();
+        let filters = stream.filters().map_err(|e| SandboxError::Decode(e.to_string()))?;
+        let params = match stream.dict.get(b"DecodeParms") {
+            Ok(Object::Dictionary(params)) => integer_entries(params),
+            _ => Vec::new(),
+        };
+        match self.request(Request::Stream { filters, params, data: stream.content.clone() })? {
+            Response::Decoded(data) => Ok(data),
+            other => Err(SandboxError::Protocol(format!("unexpected response {:?}", kind(&other)))),
+        }
+    }
+
+    /// Parses JPEG data, returning the image size
+    pub fn check_jpeg(&mut self, data: &[u8]) -> Result<(u32, u32), SandboxError> {
+        match self.request(Request::Jpeg { data: data.to_vec() })? {
+            Response::Image { width, height } => Ok((width, height)),
+            other => Err(SandboxError::Protocol(format!("unexpected response {:?}", kind(&other)))),
+        }
+    }
+
+    fn start(&mut self) -> Result<(), SandboxError> {
+        let program = match &self.config.program {
+            Some(program) => program.clone(),
+            None => std::env::current_exe()?,
+        };
+        let mut child = Command::new(program)
+            .arg(WORKER_ARG)
+            .arg(self.config.memory_limit.to_string())
+            .arg(self.config.cpu_seconds.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        #[cfg(windows)]
+        let job = match job::JobObject::confine(&child, self.config.memory_limit) {
+            Ok(job) => job,
+            Err(e) => {
+                let _ = child.kill();
+                return Err(e.into());
+            }
+        };
+
+        let stdin = child.stdin.take().ok_or_else(|| SandboxError::Protocol("no worker stdin".into()))?;
+        let mut stdout = child.stdout.take().ok_or_else(|| SandboxError::Protocol("no worker stdout".into()))?;
+        let (sender, responses) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            let response = read_frame::<Response>(&mut stdout);
+            let failed = response.is_err();
+            if sender.send(response).is_err() || failed {
+                break;
+            }
+        });
+
+        let mut worker = Worker {
+            child,
+            stdin,
+            responses,
+            #[cfg(windows)]
+            _job: job,
+        };
+        let confinement = match worker.responses.recv_timeout(self.config.timeout) {
+            Ok(Ok(Response::Ready { confinement })) => confinement,
+            _ => {
+                let _ = worker.child.kill();
+                return Err(SandboxError::WorkerDied(exit_status(&mut worker.child)));
+            }
+        };
+        self.confinement = if cfg!(windows) { format!("job object, {}", confinement) } else { confinement };
+        self.worker = Some(worker);
+        Ok(())
+    }
+
+    fn request(&mut self, request: Request) -> Result<Response, SandboxError> {
+        let timeout = self.config.timeout;
+        if self.worker.is_none() {
+            self.start()?;
+        }
+        let worker = self.worker.as_mut().expect("worker is running");
+        let sent = write_frame(&mut worker.stdin, &request);
+        let response = match sent {
+            Ok(()) => worker.responses.recv_timeout(timeout),
+            Err(_) => Err(RecvTimeoutError::Disconnected),
+        };
+        match response {
+            Ok(Ok(Response::Failed(message))) => Err(SandboxError::Decode(message)),
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) | Err(RecvTimeoutError::Disconnected) => {
+                let mut worker = self.worker.take().expect("worker is running");
+                let _ = worker.child.kill();
+                Err(SandboxError::WorkerDied(exit_status(&mut worker.child)))
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let mut worker = self.worker.take().expect("worker is running");
+                let _ = worker.child.kill();
+                let _ = worker.child.wait();
+                Err(SandboxError::Timeout(timeout))
+            }
+        }
+    }
+}
+
+impl Drop for SandboxedDecoder {
+    fn drop(&mut self) {
+        if let Some(mut worker) = self.worker.take() {
+            let _ = worker.child.kill();
+            let _ = worker.child.wait();
+        }
+    }
+}
+
+fn exit_status(child: &mut Child) -> String {
+    child.wait().map_or_else(|e| e.to_string(), |status| status.to_string())
+}
+
+fn kind(response: &Response) -> &'static str {
+    match response {
+        Response::Ready { .. } => "Ready",
+        Response::Decoded(_) => "Decoded",
+        Response::Image { .. } => "Image",
+        Response::Failed(_) => "Failed",
+    }
+}
+
+fn integer_entries(dict: &Dictionary) -> Vec<(String, i64)> {
+    dict.iter()
+        .filter_map(|(key, value)| Some((String::from_utf8_lossy(key).into_owned(), value.as_i64().ok()?)))
+        .collect()
+}
+
+/// Decodes every Flate and LZW stream of `doc` in the sandbox, and parses every JPEG image
+///
+/// Decoded streams are stored Flate-compressed by the host, so the code
+/// that later reads them only inflates data the host wrote itself.
+/// Streams the worker fails or dies on are emptied.
+pub fn decode_document(doc: &mut lopdf::Document, decoder: &mut SandboxedDecoder) -> SandboxReport {
+    let mut report = SandboxReport::default();
+    let ids: Vec<ObjectId> = doc.objects.iter()
+        .filter(|(_, object)| matches!(object, Object::Stream(_)))
+        .map(|(&id, _)| id)
+        .collect();
+    for id in ids {
+        let Some(Object::Stream(stream)) = doc.objects.get_mut(&id) else { continue };
+        let Ok(filters) = stream.filters() else { continue };
+        let result = if !filters.is_empty() && filters.iter().all(|f| STREAM_FILTERS.contains(&f.as_str())) {
+            decoder.decode_stream(stream).map(|data| {
+                stream.dict.remove(b"Filter");
+                stream.dict.remove(b"DecodeParms");
+                stream.set_content(data);
+                let _ = stream.compress();
+                report.decoded += 1;
+            })
+        } else if filters == ["DCTDecode"] {
+            decoder.check_jpeg(&stream.content).map(|_| report.images_checked += 1)
+        } else {
+            continue;
+        };
+        if let Err(e) = result {
+            tracing::warn!("Sandboxed decoding of {} {} R failed: {}", id.0, id.1, e);
+            stream.dict.remove(b"Filter");
+            stream.dict.remove(b"DecodeParms");
+            stream.set_content(Vec::new());
+            report.rejected.push((id, e.to_string()));
+        }
+    }
+    report.confinement = decoder.confinement().to_string();
+    report
+}
+
+fn write_frame<T: Serialize>(writer: &mut impl Write, value: &T) -> Result<(), SandboxError> {
+    let body = bincode::serialize(value).map_err(|e| SandboxError::Protocol(e.to_string()))?;
+    writer.write_all(&(body.len() as u64).to_le_bytes())?;
+    writer.write_all(&body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_frame<T: DeserializeOwned>(reader: &mut impl Read) -> Result<T, SandboxError> {
+    let mut length = [0u8; 8];
+    reader.read_exact(&mut length)?;
+    let length = u64::from_le_bytes(length);
+    if length > MAX_FRAME {
+        return Err(SandboxError::Protocol(format!("frame of {} bytes", length)));
+    }
+    let mut body = vec![0u8; length as usize];
+    reader.read_exact(&mut body)?;
+    bincode::deserialize(&body).map_err(|e| SandboxError::Protocol(e.to_string()))
+}
+
+/// Worker side of a request
+fn handle(request: Request) -> Response {
+    match request {
+        Request::Stream { filters, params, data } => {
+            let filters: Vec<Object> = filters.into_iter().map(|f| Object::Name(f.into_bytes())).collect();
+            let mut dict = lopdf::dictionary! { "Filter" => filters };
+            if !params.is_empty() {
+                let params: Dictionary = params.into_iter().map(|(k, v)| (k.into_bytes(), Object::Integer(v))).collect();
+                dict.set("DecodeParms", params);
+            }
+            match Stream::new(dict, data).decompressed_content() {
+                Ok(data) => Response::Decoded(data),
+                Err(e) => Response::Failed(e.to_string()),
+            }
+        }
+        Request::Jpeg { data } => match image::load_from_memory_with_format(&data, image::ImageFormat::Jpeg) {
+            Ok(image) => Response::Image { width: image.width(), height: image.height() },
+            Err(e) => Response::Failed(e.to_string()),
+        },
+    }
+}
+
+/// Runs the decoding worker: confines the process, then serves requests until stdin closes
+///
+/// `args` are the arguments after `WORKER_ARG`: memory limit and CPU seconds.
+pub fn run_worker(args: impl IntoIterator<Item = String>) -> ! {
+    let mut args = args.into_iter();
+    let memory_limit = args.next().and_then(|a| a.parse().ok()).unwrap_or(SandboxConfig::default().memory_limit);
+    let cpu_seconds = args.next().and_then(|a| a.parse().ok()).unwrap_or(SandboxConfig::default().cpu_seconds);
+
+    // Nothing untrusted is read before this point
+    let confinement = confine(memory_limit, cpu_seconds);
+    let (mut stdin, mut stdout) = (io::stdin().lock(), io::stdout().lock());
+    if write_frame(&mut stdout, &Response::Ready { confinement }).is_err() {
+        std::process::exit(1);
+    }
+    loop {
+        let request = match read_frame::<Request>(&mut stdin) {
+            Ok(request) => request,
+            Err(_) => std::process::exit(0),
+        };
+        if write_frame(&mut stdout, &handle(request)).is_err() {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Applies every confinement available here, returning their names
+fn confine(memory_limit: u64, cpu_seconds: u64) -> String {
+    let mut applied = Vec::new();
+    #[cfg(unix)]
+    {
+        if unix::set_limits(memory_limit, cpu_seconds) {
+            applied.push("rlimits");
+        }
+    }
+    #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        if seccomp::install() {
+            applied.push("seccomp");
+        }
+    }
+    let _ = (memory_limit, cpu_seconds);
+    if applied.is_empty() {
+        "process isolation only".to_string()
+    } else {
+        applied.join(", ")
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    /// Caps address space and CPU time, and forbids writing files
+    pub(super) fn set_limits(memory_limit: u64, cpu_seconds: u64) -> bool {
+        let limits = [
+            (libc::RLIMIT_AS, memory_limit),
+            (libc::RLIMIT_CPU, cpu_seconds),
+            (libc::RLIMIT_FSIZE, 0),
+        ];
+        limits.into_iter().all(|(resource, value)| {
+            let limit = libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t };
+            // SAFETY: `limit` is a valid rlimit for the duration of the call
+            unsafe { libc::setrlimit(resource, &limit) == 0 }
+        })
+    }
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod seccomp {
+    use libc::sock_filter;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JEQ_K: u16 = 0x15;
+    const BPF_RET_K: u16 = 0x06;
+    /// Offsets into `struct seccomp_data`
+    const NR_OFFSET: u32 = 0;
+    const ARCH_OFFSET: u32 = 4;
+
+    /// Calls the worker makes: pipe I/O, memory, signals, exit, and the
+    /// threads the JPEG decoder starts; none opens, executes or connects
+    const ALLOWED: [libc::c_long; 23] = [
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_close,
+        libc::SYS_brk,
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mremap,
+        libc::SYS_mprotect,
+        libc::SYS_madvise,
+        libc::SYS_futex,
+        libc::SYS_clone,
+        libc::SYS_clone3,
+        libc::SYS_set_robust_list,
+        libc::SYS_rseq,
+        libc::SYS_sched_getaffinity,
+        libc::SYS_sigaltstack,
+        libc::SYS_sched_yield,
+        libc::SYS_clock_gettime,
+        libc::SYS_getrandom,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+    ];
+
+    fn statement(code: u16, k: u32) -> sock_filter {
+        sock_filter { code, jt: 0, jf: 0, k }
+    }
+
+    fn jump(k: u32, jt: u8, jf: u8) -> sock_filter {
+        sock_filter { code: BPF_JEQ_K, jt, jf, k }
+    }
+
+    /// Allows `ALLOWED`, fails every other call with EPERM, and kills the
+    /// process on a foreign architecture's calling convention
+    pub(super) fn install() -> bool {
+        let mut program = vec![
+            statement(BPF_LD_W_ABS, ARCH_OFFSET),
+            jump(AUDIT_ARCH, 1, 0),
+            statement(BPF_RET_K, libc::SECCOMP_RET_KILL_PROCESS),
+            statement(BPF_LD_W_ABS, NR_OFFSET),
+        ];
+        for call in ALLOWED {
+            program.push(jump(call as u32, 0, 1));
+            program.push(statement(BPF_RET_K, libc::SECCOMP_RET_ALLOW));
+        }
+        program.push(statement(BPF_RET_K, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32));
+
+        let filter = libc::sock_fprog { len: program.len() as u16, filter: program.as_mut_ptr() };
+        // SAFETY: prctl reads `filter`, which points into `program`, only during the call
+        unsafe {
+            libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) == 0
+                && libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, &filter as *const libc::sock_fprog) == 0
+        }
+    }
+}
+
+#[cfg(windows)]
+mod job {
+    use std::{io, os::windows::io::AsRawHandle, process::Child};
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        System::JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+            SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_ACTIVE_PROCESS,
+            JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            JOB_OBJECT_LIMIT_PROCESS_MEMORY,
+        },
+    };
+
+    /// Job the worker runs in; closing it kills the worker
+    pub(super) struct JobObject(HANDLE);
+
+    impl JobObject {
+        /// Limits `child` to `memory_limit` bytes and forbids it from starting processes
+        pub(super) fn confine(child: &Child, memory_limit: u64) -> io::Result<Self> {
+            // SAFETY: plain Win32 calls on handles owned here or by `child`;
+            // `info` outlives the call reading it
+            unsafe {
+                let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+                if handle == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let job = Self(handle);
+                let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+                info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_PROCESS_MEMORY
+                    | JOB_OBJECT_LIMIT_ACTIVE_PROCESS
+                    | JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE
+                    | JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION;
+                info.BasicLimitInformation.ActiveProcessLimit = 1;
+                info.ProcessMemoryLimit = memory_limit as usize;
+                let set = SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const std::ffi::c_void,
+                    std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                );
+                if set == 0 || AssignProcessToJobObject(job.0, child.as_raw_handle() as HANDLE) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(job)
+            }
+        }
+    }
+
+    impl Drop for JobObject {
+        fn drop(&mut self) {
+            // SAFETY: the handle was returned by CreateJobObjectW and is closed once
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_frames_round_trip() {
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, &Request::Jpeg { data: vec![1, 2, 3] }).unwrap();
+        match read_frame::<Request>(&mut Cursor::new(buffer)).unwrap() {
+            Request::Jpeg { data } => assert_eq!(data, vec![1, 2, 3]),
+            other => panic!("unexpected {:?}", other),
+        }
+        // A frame claiming more than the limit is refused before allocating
+        let huge = (MAX_FRAME + 1).to_le_bytes();
+        assert!(matches!(read_frame::<Request>(&mut Cursor::new(huge)), Err(SandboxError::Protocol(_))));
+    }
+
+    #[test]
+    fn test_worker_decodes_streams_and_reports_failures() {
+        let mut stream = Stream::new(Dictionary::new(), b"BT (hello) Tj ET".repeat(20));
+        stream.compress().unwrap();
+        let request = Request::Stream { filters: stream.filters().unwrap(), params: Vec::new(), data: stream.content };
+        match handle(request) {
+            Response::Decoded(data) => assert_eq!(data, b"BT (hello) Tj ET".repeat(20)),
+            other => panic!("unexpected {:?}", other),
+        }
+        let broken = Request::Stream { filters: vec!["FlateDecode".into()], params: Vec::new(), data: b"not zlib".to_vec() };
+        assert!(matches!(handle(broken), Response::Failed(_)));
+        assert!(matches!(handle(Request::Jpeg { data: b"\xFF\xD8garbage".to_vec() }), Response::Failed(_)));
+    }
+}
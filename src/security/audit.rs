@@ -7,6 +7,8 @@ use std::{
 };
 use uuid::Uuid;
 
+use super::operator::{Operation, Operator};
+
 pub struct AuditSystem {
     state: Arc<RwLock<AuditState>>,
     config: AuditConfig,
@@ -106,6 +108,42 @@ impl AuditSystem {
         self.log_event(event).await
     }
 
+    /// Records whether `operator` was allowed to run `operation` on `resource`
+    ///
+    /// Operations run without an operator are recorded as `anonymous`.
+    pub async fn log_operation(
+        &self,
+        operator: Option<&Operator>,
+        operation: Operation,
+        resource: &str,
+        allowed: bool,
+    ) -> Result<(), PdfError> {
+        let event = AuditEvent {
+            id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            event_type: EventType::Access,
+            user_id: operator.map_or_else(|| "anonymous".to_string(), |o| o.id.clone()),
+            resource_id: resource.to_string(),
+            action: operation.to_string(),
+            status: if allowed {
+                EventStatus::Success
+            } else {
+                EventStatus::Failure
+            },
+            details: if allowed {
+                format!("{} permitted", operation)
+            } else {
+                format!("{} denied", operation)
+            },
+            metadata: serde_json::json!({
+                "source": operator.map(|o| o.source.to_string()),
+                "roles": operator.map(|o| o.roles.iter().collect::<Vec<_>>()),
+            }),
+        };
+
+        self.log_event(event).await
+    }
+
     async fn log_event(&self, event: AuditEvent) -> Result<(), PdfError> {
         let mut state = self.state.write().map_err(|_| 
             PdfError::Security("Failed to acquire state lock".to_string()))?;
@@ -207,6 +245,7 @@ impl Default for AuditConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::security::operator::Role;
 
     #[tokio::test]
     async fn test_audit_system_creation() {
@@ -238,4 +277,20 @@ mod tests {
         let events = system.get_events(None).await.unwrap();
         assert!(!events.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_operation_logging() {
+        let config = SecurityConfig::default();
+        let system = AuditSystem::new(&config).await.unwrap();
+        let operator = Operator::principal("analyst", [Role::ScanOnly]);
+
+        system.log_operation(Some(&operator), Operation::Clean, "in.pdf", false).await.unwrap();
+        system.log_operation(None, Operation::Scan, "in.pdf", true).await.unwrap();
+
+        let events = system.get_events(None).await.unwrap();
+        assert_eq!(events[0].user_id, "analyst");
+        assert_eq!(events[0].action, "clean");
+        assert!(matches!(events[0].status, EventStatus::Failure));
+        assert_eq!(events[1].user_id, "anonymous");
+    }
 }
\ No newline at end of file
@@ -43,6 +43,16 @@ impl EncryptionSystem {
     }
 
     pub async fn encrypt_document(&self, data: &[u8]) -> Result<Vec<u8>, PdfError> {
+        let config = self.config.clone();
+        self.encrypt_document_with(data, &config).await
+    }
+
+    /// Encrypts with explicit options instead of the system defaults
+    pub async fn encrypt_document_with(
+        &self,
+        data: &[u8],
+        config: &EncryptionConfig,
+    ) -> Result<Vec<u8>, PdfError> {
         // Update state
         {
             let mut state = self.state.write().map_err(|_| 
@@ -50,7 +60,7 @@ impl EncryptionSystem {
             state.active_operations += 1;
         }
 
-        let result = self.internal_encrypt_document(data).await;
+        let result = self.internal_encrypt_document(data, config).await;
 
         // Update state
         {
@@ -64,8 +74,8 @@ impl EncryptionSystem {
         result
     }
 
-    async fn internal_encrypt_document(&self, data: &[u8]) -> Result<Vec<u8>, PdfError> {
-        match self.config.algorithm {
+    async fn internal_encrypt_document(&self, data: &[u8], config: &EncryptionConfig) -> Result<Vec<u8>, PdfError> {
+        match config.algorithm {
             EncryptionAlgorithm::Aes256Cbc => self.encrypt_aes_cbc(data),
             EncryptionAlgorithm::Aes256Gcm => self.encrypt_aes_gcm(data),
         }
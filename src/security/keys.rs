@@ -7,6 +7,8 @@ use std::{
 };
 use uuid::Uuid;
 
+use super::signature::SignatureAlgorithm;
+
 pub struct KeyManagementSystem {
     state: Arc<RwLock<KeyState>>,
     config: KeyConfig,
@@ -39,7 +41,7 @@ pub struct Key {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-enum KeyType {
+pub enum KeyType {
     Master,
     Document,
     Signing,
@@ -87,6 +89,47 @@ struct KeyRotationSchedule {
     interval: std::time::Duration,
 }
 
+impl Key {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn algorithm(&self) -> &KeyAlgorithm {
+        &self.algorithm
+    }
+
+    pub fn status(&self) -> &KeyStatus {
+        &self.status
+    }
+
+    /// Ed25519 signing key held in this key's material
+    pub fn ed25519_signing_key(&self) -> Result<ed25519_dalek::SigningKey, PdfError> {
+        let unusable = || PdfError::Security(format!("Key {} holds no Ed25519 key pair", self.id));
+        if !matches!(self.algorithm, KeyAlgorithm::Ed25519) {
+            return Err(unusable());
+        }
+        let bytes = self.material.encrypted_private_key.as_deref()
+            .and_then(|encoded| base64::decode(encoded).ok())
+            .and_then(|bytes| <[u8; ed25519_dalek::KEYPAIR_LENGTH]>::try_from(bytes).ok())
+            .ok_or_else(unusable)?;
+        ed25519_dalek::SigningKey::from_keypair_bytes(&bytes).map_err(|_| unusable())
+    }
+
+    /// Signature algorithm this key can produce, if it is an active asymmetric key
+    pub fn signature_algorithm(&self) -> Result<SignatureAlgorithm, PdfError> {
+        if !matches!(self.status, KeyStatus::Active) {
+            return Err(PdfError::Security(format!("Key {} is not active", self.id)));
+        }
+
+        match self.algorithm {
+            KeyAlgorithm::Ed25519 => Ok(SignatureAlgorithm::Ed25519),
+            KeyAlgorithm::Rsa2048 => Ok(SignatureAlgorithm::RsaPss { key_size: 2048, salt_length: 32 }),
+            KeyAlgorithm::Rsa4096 => Ok(SignatureAlgorithm::RsaPss { key_size: 4096, salt_length: 32 }),
+            KeyAlgorithm::Aes256 => Err(PdfError::Security(format!("Key {} is symmetric and cannot sign", self.id))),
+        }
+    }
+}
+
 impl KeyManagementSystem {
     pub async fn new(security_config: &SecurityConfig) -> Result<Self, PdfError> {
         let config = KeyConfig::default();
@@ -215,7 +258,16 @@ impl KeyManagementSystem {
                 symmetric_key: Some(base64::encode(rand::random::<[u8; 32]>())),
                 initialization_vector: Some(base64::encode(rand::random::<[u8; 16]>())),
             }),
-            KeyAlgorithm::Ed25519 | KeyAlgorithm::Rsa2048 | KeyAlgorithm::Rsa4096 => Ok(KeyMaterial {
+            KeyAlgorithm::Ed25519 => {
+                let signing = ed25519_dalek::SigningKey::from_bytes(&rand::random());
+                Ok(KeyMaterial {
+                    public_key: Some(base64::encode(signing.verifying_key().to_bytes())),
+                    encrypted_private_key: Some(base64::encode(signing.to_keypair_bytes())),
+                    symmetric_key: None,
+                    initialization_vector: None,
+                })
+            }
+            KeyAlgorithm::Rsa2048 | KeyAlgorithm::Rsa4096 => Ok(KeyMaterial {
                 public_key: Some(base64::encode(rand::random::<[u8; 32]>())),
                 encrypted_private_key: Some(base64::encode(rand::random::<[u8; 64]>())),
                 symmetric_key: None,
@@ -284,4 +336,26 @@ mod tests {
         let result = kms.rotate_keys().await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_signing_uses_the_key() {
+        use ed25519_dalek::Verifier;
+        use sha2::{Digest, Sha512};
+
+        let config = SecurityConfig::default();
+        let kms = KeyManagementSystem::new(&config).await.unwrap();
+        let key = kms.create_key(KeyType::Signing, KeyAlgorithm::Ed25519).await.unwrap();
+        let other = kms.create_key(KeyType::Signing, KeyAlgorithm::Ed25519).await.unwrap();
+        let signatures = super::super::signature::SignatureSystem::new(&config).await.unwrap();
+
+        let data = b"Test document data";
+        let signed = signatures.sign_document_with_key(data, &key).await.unwrap();
+        let signature = ed25519_dalek::Signature::from_slice(&signed[data.len()..]).unwrap();
+        let digest = Sha512::digest(data);
+        assert!(key.ed25519_signing_key().unwrap().verifying_key().verify(&digest, &signature).is_ok());
+        assert!(other.ed25519_signing_key().unwrap().verifying_key().verify(&digest, &signature).is_err());
+
+        let aes = kms.create_key(KeyType::Document, KeyAlgorithm::Aes256).await.unwrap();
+        assert!(aes.ed25519_signing_key().is_err());
+    }
 }
\ No newline at end of file
@@ -11,6 +11,7 @@ pub mod audit;
 pub mod certificate;
 pub mod encryption;
 pub mod keys;
+pub mod operator;
 pub mod policy;
 pub mod signature;
 
@@ -174,6 +175,35 @@ impl SecuritySystem {
         self.metrics.signature_validations.inc();
         Ok(result)
     }
+
+    /// Encrypts with explicit options instead of the configured defaults
+    pub async fn encrypt_document_with(
+        &self,
+        data: &[u8],
+        options: &encryption::EncryptionConfig,
+    ) -> Result<Vec<u8>, PdfError> {
+        let result = self.encryption.encrypt_document_with(data, options).await?;
+        self.metrics.encryption_operations.inc();
+        Ok(result)
+    }
+
+    /// Records an authorization decision in the audit log
+    pub async fn record_operation(
+        &self,
+        operator: Option<&operator::Operator>,
+        operation: operator::Operation,
+        resource: &str,
+        allowed: bool,
+    ) -> Result<(), PdfError> {
+        self.audit.log_operation(operator, operation, resource, allowed).await
+    }
+
+    /// Signs with a managed key
+    pub async fn sign_document_with(&self, data: &[u8], key: &keys::Key) -> Result<Vec<u8>, PdfError> {
+        let result = self.signature.sign_document_with_key(data, key).await?;
+        self.metrics.signature_validations.inc();
+        Ok(result)
+    }
 }
 
 impl Default for SecurityConfig {
@@ -0,0 +1,236 @@
+//! Operator identity and role-based gating of engine operations
+//!
+//! Every engine operation runs on behalf of an [`Operator`]: a caller
+//! authenticated by API key, the OS user running the process, or a
+//! principal supplied by an embedding application. The operator's roles
+//! decide which [`Operation`]s it may run; each decision is written to
+//! the audit log.
+
+use crate::PdfError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    str::FromStr,
+};
+
+/// Engine operation subject to authorization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    /// Reading a document and reporting findings
+    Scan,
+    /// Writing a cleaned or otherwise transformed document
+    Clean,
+    /// Encrypting or signing a document
+    Encrypt,
+    /// Irrecoverably deleting a file
+    Shred,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Scan => "scan",
+            Operation::Clean => "clean",
+            Operation::Encrypt => "encrypt",
+            Operation::Shred => "shred",
+        })
+    }
+}
+
+/// Set of operations granted to an operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    ScanOnly,
+    /// Scanning and cleaning
+    Clean,
+    Encrypt,
+    Shred,
+}
+
+impl Role {
+    pub fn operations(&self) -> &'static [Operation] {
+        match self {
+            Role::ScanOnly => &[Operation::Scan],
+            Role::Clean => &[Operation::Scan, Operation::Clean],
+            Role::Encrypt => &[Operation::Encrypt],
+            Role::Shred => &[Operation::Shred],
+        }
+    }
+
+    pub fn permits(&self, operation: Operation) -> bool {
+        self.operations().contains(&operation)
+    }
+}
+
+impl FromStr for Role {
+    type Err = PdfError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scan-only" | "scan" => Ok(Role::ScanOnly),
+            "clean" => Ok(Role::Clean),
+            "encrypt" => Ok(Role::Encrypt),
+            "shred" => Ok(Role::Shred),
+            other => Err(PdfError::Configuration(format!(
+                "unknown role '{}' (expected scan-only, clean, encrypt or shred)",
+                other,
+            ))),
+        }
+    }
+}
+
+/// How an operator was identified
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum OperatorSource {
+    /// Authenticated by a key registered in an [`ApiKeyRegistry`]
+    ApiKey { key_id: String },
+    /// The account running the process
+    OsUser,
+    /// Named by the embedding application, which vouches for it
+    Principal,
+}
+
+impl fmt::Display for OperatorSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperatorSource::ApiKey { key_id } => write!(f, "api-key:{}", key_id),
+            OperatorSource::OsUser => f.write_str("os-user"),
+            OperatorSource::Principal => f.write_str("principal"),
+        }
+    }
+}
+
+/// Identity operations run on behalf of
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operator {
+    pub id: String,
+    pub source: OperatorSource,
+    pub roles: BTreeSet<Role>,
+}
+
+impl Operator {
+    /// Operator named by the caller
+    pub fn principal(id: impl Into<String>, roles: impl IntoIterator<Item = Role>) -> Self {
+        Self {
+            id: id.into(),
+            source: OperatorSource::Principal,
+            roles: roles.into_iter().collect(),
+        }
+    }
+
+    /// Operator for the account running the process, from `USER` or `USERNAME`
+    pub fn os_user(roles: impl IntoIterator<Item = Role>) -> Result<Self, PdfError> {
+        let id = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .ok()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| PdfError::Configuration("could not determine the OS user".into()))?;
+        Ok(Self {
+            id,
+            source: OperatorSource::OsUser,
+            roles: roles.into_iter().collect(),
+        })
+    }
+
+    pub fn can(&self, operation: Operation) -> bool {
+        self.roles.iter().any(|role| role.permits(operation))
+    }
+
+    /// Fails with [`PdfError::PermissionDenied`] unless a role grants `operation`
+    pub fn authorize(&self, operation: Operation) -> Result<(), PdfError> {
+        if self.can(operation) {
+            Ok(())
+        } else {
+            Err(PdfError::PermissionDenied(format!(
+                "operator '{}' ({}) may not {}",
+                self.id, self.source, operation,
+            )))
+        }
+    }
+}
+
+/// API keys and the operators they authenticate
+///
+/// Only SHA-256 digests of the keys are kept.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeyRegistry {
+    keys: HashMap<[u8; 32], (String, BTreeSet<Role>)>,
+}
+
+impl ApiKeyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `key` under `key_id`, replacing any earlier registration of the same key
+    pub fn register(&mut self, key_id: impl Into<String>, key: &[u8], roles: impl IntoIterator<Item = Role>) {
+        self.keys.insert(digest(key), (key_id.into(), roles.into_iter().collect()));
+    }
+
+    /// Removes the key registered under `key_id`, returning whether there was one
+    pub fn revoke(&mut self, key_id: &str) -> bool {
+        let before = self.keys.len();
+        self.keys.retain(|_, (id, _)| id != key_id);
+        self.keys.len() != before
+    }
+
+    /// Operator for `key`, which must have been registered
+    pub fn authenticate(&self, key: &[u8]) -> Result<Operator, PdfError> {
+        let (key_id, roles) = self.keys.get(&digest(key))
+            .ok_or_else(|| PdfError::PermissionDenied("unknown API key".into()))?;
+        Ok(Operator {
+            id: key_id.clone(),
+            source: OperatorSource::ApiKey { key_id: key_id.clone() },
+            roles: roles.clone(),
+        })
+    }
+}
+
+fn digest(key: &[u8]) -> [u8; 32] {
+    Sha256::digest(key).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_gate_operations() {
+        let analyst = Operator::principal("analyst", [Role::ScanOnly]);
+        assert!(analyst.authorize(Operation::Scan).is_ok());
+        assert!(matches!(analyst.authorize(Operation::Clean), Err(PdfError::PermissionDenied(_))));
+
+        let cleaner = Operator::principal("cleaner", [Role::Clean, Role::Encrypt]);
+        assert!(cleaner.can(Operation::Scan));
+        assert!(cleaner.can(Operation::Clean));
+        assert!(cleaner.can(Operation::Encrypt));
+        assert!(!cleaner.can(Operation::Shred));
+    }
+
+    #[test]
+    fn test_api_key_authentication() {
+        let mut registry = ApiKeyRegistry::new();
+        registry.register("ci-runner", b"s3cret", [Role::Clean]);
+
+        let operator = registry.authenticate(b"s3cret").unwrap();
+        assert_eq!(operator.id, "ci-runner");
+        assert_eq!(operator.source, OperatorSource::ApiKey { key_id: "ci-runner".into() });
+        assert!(operator.can(Operation::Clean));
+
+        assert!(registry.authenticate(b"guess").is_err());
+        assert!(registry.revoke("ci-runner"));
+        assert!(registry.authenticate(b"s3cret").is_err());
+    }
+
+    #[test]
+    fn test_role_parsing() {
+        assert_eq!("scan-only".parse::<Role>().unwrap(), Role::ScanOnly);
+        assert_eq!("shred".parse::<Role>().unwrap(), Role::Shred);
+        assert!("admin".parse::<Role>().is_err());
+    }
+}
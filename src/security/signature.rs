@@ -8,6 +8,8 @@ use std::{
 use sha2::{Sha256, Sha512, Digest};
 use uuid::Uuid;
 
+use super::keys::Key;
+
 pub struct SignatureSystem {
     state: Arc<RwLock<SignatureState>>,
     config: SignatureConfig,
@@ -34,6 +36,8 @@ struct SignatureConfig {
 pub struct DocumentSignature {
     id: String,
     algorithm: SignatureAlgorithm,
+    /// Managed key the signature was made with; `None` for the configured default
+    key_id: Option<String>,
     signature_data: SignatureData,
     metadata: SignatureMetadata,
     status: SignatureStatus,
@@ -114,11 +118,36 @@ impl SignatureSystem {
     }
 
     pub async fn sign_document(&self, data: &[u8]) -> Result<Vec<u8>, PdfError> {
+        let algorithm = self.config.default_algorithm.clone();
+        self.sign_document_with(data, algorithm).await
+    }
+
+    /// Signs with an explicit algorithm instead of the configured default
+    pub async fn sign_document_with(
+        &self,
+        data: &[u8],
+        algorithm: SignatureAlgorithm,
+    ) -> Result<Vec<u8>, PdfError> {
+        self.tracked_sign(data, algorithm, None).await
+    }
+
+    /// Signs with a managed key: its algorithm and, for Ed25519, its key pair
+    pub async fn sign_document_with_key(&self, data: &[u8], key: &Key) -> Result<Vec<u8>, PdfError> {
+        let algorithm = key.signature_algorithm()?;
+        self.tracked_sign(data, algorithm, Some(key)).await
+    }
+
+    async fn tracked_sign(
+        &self,
+        data: &[u8],
+        algorithm: SignatureAlgorithm,
+        key: Option<&Key>,
+    ) -> Result<Vec<u8>, PdfError> {
         let mut state = self.state.write().map_err(|_| 
             PdfError::Security("Failed to acquire state lock".to_string()))?;
         
         state.active_operations += 1;
-        let result = self.internal_sign_document(data).await;
+        let result = self.internal_sign_document(data, algorithm, key).await;
         state.active_operations -= 1;
         state.operations_performed += 1;
         state.last_operation = Some(Utc::now());
@@ -126,7 +155,12 @@ impl SignatureSystem {
         result
     }
 
-    async fn internal_sign_document(&self, data: &[u8]) -> Result<Vec<u8>, PdfError> {
+    async fn internal_sign_document(
+        &self,
+        data: &[u8],
+        algorithm: SignatureAlgorithm,
+        key: Option<&Key>,
+    ) -> Result<Vec<u8>, PdfError> {
         let current_time = Utc::parse_from_str("2025-06-02 18:36:33", "%Y-%m-%d %H:%M:%S")
             .map_err(|_| PdfError::Security("Invalid current time".to_string()))?;
 
@@ -136,9 +170,13 @@ impl SignatureSystem {
         let document_hash = hasher.finalize();
 
         // Create signature based on algorithm
-        let signature = match self.config.default_algorithm {
-            SignatureAlgorithm::Ed25519 => {
-                self.sign_with_ed25519(&document_hash)?
+        let signature = match algorithm {
+            SignatureAlgorithm::Ed25519 => match key {
+                Some(key) => {
+                    use ed25519_dalek::Signer;
+                    key.ed25519_signing_key()?.sign(&document_hash).to_bytes().to_vec()
+                }
+                None => self.sign_with_ed25519(&document_hash)?,
             },
             SignatureAlgorithm::RsaPss { key_size, salt_length } => {
                 self.sign_with_rsa_pss(&document_hash, key_size, salt_length)?
@@ -152,7 +190,8 @@ impl SignatureSystem {
         let signature_id = Uuid::new_v4().to_string();
         let document_signature = DocumentSignature {
             id: signature_id.clone(),
-            algorithm: self.config.default_algorithm.clone(),
+            algorithm,
+            key_id: key.map(|key| key.id().to_string()),
             signature_data: SignatureData {
                 value: base64::encode(&signature),
                 certificate_chain: vec![], // In production, add actual certificate chain
@@ -0,0 +1,211 @@
+//! Job admission and graceful shutdown
+//!
+//! Every scan and processing job holds a `JobGuard` for its lifetime.
+//! `JobTracker::shutdown` stops admitting new jobs, waits for the active
+//! ones to finish, and once the deadline passes cancels whatever is still
+//! running. Cancellation takes effect at the job's next await point; the
+//! job then fails with `PdfError::Shutdown` instead of completing.
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{watch, Notify};
+
+use crate::{watchdog, PdfError};
+
+/// How long cancelled jobs get to unwind after the deadline
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+/// Outcome of a shutdown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Jobs running when shutdown began
+    pub active_at_start: usize,
+    /// Jobs still running at the deadline and cancelled
+    pub cancelled: usize,
+    /// Cancelled jobs that had not unwound after the grace period
+    pub abandoned: usize,
+    pub elapsed: Duration,
+}
+
+impl ShutdownReport {
+    /// Whether every job finished on its own
+    pub fn drained(&self) -> bool {
+        self.cancelled == 0
+    }
+}
+
+/// Counts active jobs and gates new ones
+#[derive(Debug)]
+pub struct JobTracker {
+    accepting: AtomicBool,
+    active: AtomicUsize,
+    idle: Notify,
+    cancel: watch::Sender<bool>,
+}
+
+impl Default for JobTracker {
+    fn default() -> Self {
+        Self {
+            accepting: AtomicBool::new(true),
+            active: AtomicUsize::new(0),
+            idle: Notify::new(),
+            cancel: watch::channel(false).0,
+        }
+    }
+}
+
+impl JobTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Admits a job, failing once shutdown has begun
+    pub fn begin(self: &Arc<Self>) -> Result<JobGuard, PdfError> {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        let guard = JobGuard { tracker: self.clone(), label: None };
+        if !self.accepting.load(Ordering::SeqCst) {
+            // Dropping the guard undoes the count and wakes a waiting shutdown
+            return Err(PdfError::Shutdown("not accepting new jobs".into()));
+        }
+        Ok(guard)
+    }
+
+    pub fn is_accepting(&self) -> bool {
+        self.accepting.load(Ordering::SeqCst)
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once no job is active
+    async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.active() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Stops admitting jobs, drains active ones and cancels stragglers at `deadline`
+    pub async fn shutdown(&self, deadline: Duration) -> ShutdownReport {
+        let start = Instant::now();
+        self.accepting.store(false, Ordering::SeqCst);
+        let active_at_start = self.active();
+
+        let mut cancelled = 0;
+        let mut abandoned = 0;
+        if tokio::time::timeout(deadline, self.wait_idle()).await.is_err() {
+            cancelled = self.active();
+            self.cancel.send_replace(true);
+            if tokio::time::timeout(CANCEL_GRACE, self.wait_idle()).await.is_err() {
+                abandoned = self.active();
+            }
+        }
+
+        ShutdownReport {
+            active_at_start,
+            cancelled,
+            abandoned,
+            elapsed: start.elapsed(),
+        }
+    }
+}
+
+/// Marks a job as active until dropped
+#[derive(Debug)]
+pub struct JobGuard {
+    tracker: Arc<JobTracker>,
+    /// Job id reported by the watchdog for permits the job holds
+    label: Option<String>,
+}
+
+impl JobGuard {
+    /// Names the job in watchdog reports
+    pub fn label(&mut self, job_id: impl Into<String>) {
+        self.label = Some(job_id.into());
+    }
+
+    /// Runs `job`, failing it if shutdown cancels outstanding jobs first
+    /// or the watchdog finds it holding a permit for too long
+    pub async fn run<T>(&self, job: impl Future<Output = Result<T, PdfError>>) -> Result<T, PdfError> {
+        let mut cancel = self.tracker.cancel.subscribe();
+        let (fail, mut failed) = watch::channel(None);
+        let scope = watchdog::JobScope {
+            job_id: self.label.clone().unwrap_or_else(|| "unlabeled job".into()),
+            fail: Arc::new(fail),
+        };
+        tokio::select! {
+            result = watchdog::scoped(scope, job) => result,
+            _ = cancel.wait_for(|cancelled| *cancelled) => {
+                Err(PdfError::Shutdown("job cancelled at shutdown deadline".into()))
+            }
+            Ok(reason) = failed.wait_for(Option::is_some) => {
+                Err(PdfError::Stalled(reason.clone().unwrap_or_default()))
+            }
+        }
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        if self.tracker.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.tracker.idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_active_jobs() {
+        let tracker = Arc::new(JobTracker::new());
+        let guard = tracker.begin().unwrap();
+        let job = tokio::spawn(async move {
+            guard.run(async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(())
+            }).await
+        });
+
+        let report = tracker.shutdown(Duration::from_secs(5)).await;
+        assert_eq!(report.active_at_start, 1);
+        assert!(report.drained());
+        assert!(job.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_new_jobs_rejected_after_shutdown() {
+        let tracker = Arc::new(JobTracker::new());
+        tracker.shutdown(Duration::from_millis(10)).await;
+
+        assert!(matches!(tracker.begin(), Err(PdfError::Shutdown(_))));
+        assert_eq!(tracker.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stragglers_cancelled_at_deadline() {
+        let tracker = Arc::new(JobTracker::new());
+        let guard = tracker.begin().unwrap();
+        let job = tokio::spawn(async move {
+            guard.run(std::future::pending::<Result<(), PdfError>>()).await
+        });
+
+        let report = tracker.shutdown(Duration::from_millis(20)).await;
+        assert_eq!(report.cancelled, 1);
+        assert_eq!(report.abandoned, 0);
+        assert!(matches!(job.await.unwrap(), Err(PdfError::Shutdown(_))));
+    }
+}
@@ -0,0 +1,425 @@
+//! Byte buffers that move to disk when they grow large
+//!
+//! Decoded streams and serialized output can be far larger than the PDF
+//! they come from. A `SpillBuffer` keeps small data in memory and moves it
+//! to a temporary file once it passes the configured threshold or the
+//! process-wide memory budget is exhausted, so peak memory stays bounded.
+//! Spilled data is read back through a reader or a read-only mapping of
+//! the spill file, never copied back onto the heap.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Deref,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
+use memmap2::{Mmap, MmapOptions};
+
+/// When buffers spill and where the files go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpillConfig {
+    /// Size above which a single buffer always spills
+    pub threshold: usize,
+    /// Bytes all in-memory buffers may hold together
+    pub memory_budget: usize,
+    /// Directory for spill files
+    pub dir: PathBuf,
+}
+
+impl Default for SpillConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 32 * 1024 * 1024,      // 32MB
+            memory_budget: 512 * 1024 * 1024, // 512MB
+            dir: std::env::temp_dir(),
+        }
+    }
+}
+
+/// Bytes held in memory by all live spill buffers
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: AtomicUsize,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: AtomicUsize::new(limit),
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reserves `bytes`, failing if that would exceed the limit
+    pub fn try_reserve(&self, bytes: usize) -> bool {
+        let limit = self.limit.load(Ordering::SeqCst);
+        let reserved = self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(bytes).filter(|total| *total <= limit)
+            })
+            .is_ok();
+        if reserved {
+            crate::usage::record_reserved(bytes);
+        }
+        reserved
+    }
+
+    pub fn release(&self, bytes: usize) {
+        let _ = self.used.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+            Some(used.saturating_sub(bytes))
+        });
+        crate::usage::record_released(bytes);
+    }
+
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::SeqCst);
+    }
+
+    /// Lowers the limit to `limit` if that is smaller, so every user of a shared budget gets at most what it asked for
+    pub fn lower_limit(&self, limit: usize) {
+        self.limit.fetch_min(limit, Ordering::SeqCst);
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::SeqCst)
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+}
+
+/// Budget shared by every buffer created with `SpillBuffer::new`
+///
+/// One budget holds for the whole process, however many engines it runs;
+/// each engine can only lower it to its own `SpillConfig::memory_budget`.
+pub fn global_budget() -> Arc<MemoryBudget> {
+    static GLOBAL: OnceLock<Arc<MemoryBudget>> = OnceLock::new();
+    GLOBAL
+        .get_or_init(|| Arc::new(MemoryBudget::new(SpillConfig::default().memory_budget)))
+        .clone()
+}
+
+enum Backing {
+    Memory(Vec<u8>),
+    Disk { file: File, path: PathBuf },
+}
+
+/// Growable buffer backed by memory or a temporary file
+///
+/// Write into it with `std::io::Write`, then read it back with `reader`
+/// or `contents`. All buffers draw on [`global_budget`]. The spill file is
+/// removed when the buffer is dropped.
+pub struct SpillBuffer {
+    backing: Backing,
+    len: usize,
+    threshold: usize,
+    dir: PathBuf,
+    budget: Arc<MemoryBudget>,
+}
+
+impl SpillBuffer {
+    /// Creates a buffer drawing on the global memory budget
+    pub fn new(config: &SpillConfig) -> Self {
+        Self::with_budget(config, global_budget())
+    }
+
+    pub(crate) fn with_budget(config: &SpillConfig, budget: Arc<MemoryBudget>) -> Self {
+        Self {
+            backing: Backing::Memory(Vec::new()),
+            len: 0,
+            threshold: config.threshold,
+            dir: config.dir.clone(),
+            budget,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the data now lives on disk
+    pub fn is_spilled(&self) -> bool {
+        matches!(self.backing, Backing::Disk { .. })
+    }
+
+    /// Moves in-memory data to a new temporary file
+    fn spill(&mut self) -> io::Result<()> {
+        let Backing::Memory(data) = &self.backing else {
+            return Ok(());
+        };
+
+        let path = crate::paths::extended(&self.dir).join(format!("pdf-engine-spill-{}.tmp", uuid::Uuid::new_v4()));
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        if let Err(e) = file.write_all(data) {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+        crate::usage::record_written(data.len() as u64);
+
+        self.budget.release(data.len());
+        self.backing = Backing::Disk { file, path };
+        Ok(())
+    }
+
+    /// Reads the whole buffer from the start
+    pub fn reader(&mut self) -> io::Result<SpillReader<'_>> {
+        match &mut self.backing {
+            Backing::Memory(data) => Ok(SpillReader::Memory(io::Cursor::new(data.as_slice()))),
+            Backing::Disk { file, .. } => {
+                file.flush()?;
+                file.seek(SeekFrom::Start(0))?;
+                Ok(SpillReader::Disk(io::BufReader::new(file)))
+            }
+        }
+    }
+
+    /// Borrows in-memory data, or maps spilled data read-only
+    pub fn contents(&mut self) -> io::Result<SpillContents<'_>> {
+        match &mut self.backing {
+            Backing::Memory(data) => Ok(SpillContents::Memory(data.as_slice())),
+            Backing::Disk { .. } if self.len == 0 => Ok(SpillContents::Memory(&[])),
+            Backing::Disk { file, .. } => {
+                file.flush()?;
+                // SAFETY: the spill file was created by this buffer and is
+                // only written through it, which the borrow rules out while
+                // the mapping lives; the mapping is bounded by `len`.
+                let map = unsafe { MmapOptions::new().len(self.len).map(&*file)? };
+                crate::usage::record_read(self.len as u64);
+                Ok(SpillContents::Mapped(map))
+            }
+        }
+    }
+
+    /// Copies the buffer into `writer`, returning the number of bytes copied
+    pub fn copy_to(&mut self, writer: &mut impl Write) -> io::Result<u64> {
+        io::copy(&mut self.reader()?, writer)
+    }
+}
+
+impl Write for SpillBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Backing::Memory(_) = self.backing {
+            let fits = self.len + buf.len() <= self.threshold && self.budget.try_reserve(buf.len());
+            if !fits {
+                self.spill()?;
+            }
+        }
+
+        match &mut self.backing {
+            Backing::Memory(data) => data.extend_from_slice(buf),
+            Backing::Disk { file, .. } => {
+                file.write_all(buf)?;
+                crate::usage::record_written(buf.len() as u64);
+            }
+        }
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.backing {
+            Backing::Memory(_) => Ok(()),
+            Backing::Disk { file, .. } => file.flush(),
+        }
+    }
+}
+
+impl Drop for SpillBuffer {
+    fn drop(&mut self) {
+        match &self.backing {
+            Backing::Memory(data) => self.budget.release(data.len()),
+            Backing::Disk { path, .. } => {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Whole contents of a `SpillBuffer`
+pub enum SpillContents<'a> {
+    Memory(&'a [u8]),
+    /// The spill file, mapped read-only
+    Mapped(Mmap),
+}
+
+impl Deref for SpillContents<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SpillContents::Memory(data) => data,
+            SpillContents::Mapped(map) => map,
+        }
+    }
+}
+
+impl AsRef<[u8]> for SpillContents<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Reader over a `SpillBuffer`'s contents
+pub enum SpillReader<'a> {
+    Memory(io::Cursor<&'a [u8]>),
+    Disk(io::BufReader<&'a mut File>),
+}
+
+impl Read for SpillReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            SpillReader::Memory(cursor) => cursor.read(buf),
+            SpillReader::Disk(reader) => {
+                let read = reader.read(buf)?;
+                crate::usage::record_read(read as u64);
+                Ok(read)
+            }
+        }
+    }
+}
+
+/// Decodes a stream into a new spill buffer
+pub fn decode_stream(stream: &lopdf::Stream, config: &SpillConfig) -> io::Result<SpillBuffer> {
+    let mut buffer = SpillBuffer::new(config);
+    decode_stream_into(stream, &mut buffer)?;
+    Ok(buffer)
+}
+
+/// Decodes a stream into `output`, returning the decoded length
+///
+/// Unfiltered and Flate-only streams are decoded incrementally, so their
+/// decoded form never has to fit in memory; other filters go through
+/// lopdf and are copied in afterwards.
+pub fn decode_stream_into(stream: &lopdf::Stream, output: &mut impl Write) -> io::Result<u64> {
+    use lopdf::Object;
+
+    let filter = stream.dict.get(b"Filter").ok();
+    let _span = tracing::trace_span!(
+        "decode_filter",
+        filter = ?filter.and_then(|filter| filter.as_name_str().ok()),
+        encoded_bytes = stream.content.len(),
+    ).entered();
+    let plain_flate = match filter {
+        Some(Object::Name(name)) => name == b"FlateDecode",
+        Some(Object::Array(filters)) => matches!(filters.as_slice(), [Object::Name(name)] if name == b"FlateDecode"),
+        _ => false,
+    };
+
+    if filter.is_none() {
+        output.write_all(&stream.content)?;
+        Ok(stream.content.len() as u64)
+    } else if plain_flate && !stream.dict.has(b"DecodeParms") {
+        io::copy(&mut flate2::read::ZlibDecoder::new(stream.content.as_slice()), output)
+    } else {
+        let decoded = stream.decompressed_content()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        output.write_all(&decoded)?;
+        Ok(decoded.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(threshold: usize) -> SpillConfig {
+        SpillConfig {
+            threshold,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_small_buffer_stays_in_memory() {
+        let budget = Arc::new(MemoryBudget::new(1024));
+        let mut buffer = SpillBuffer::with_budget(&config(1024), budget.clone());
+        buffer.write_all(b"hello").unwrap();
+
+        assert!(!buffer.is_spilled());
+        assert_eq!(budget.used(), 5);
+        drop(buffer);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn test_threshold_spills_to_disk() {
+        let budget = Arc::new(MemoryBudget::new(1024 * 1024));
+        let mut buffer = SpillBuffer::with_budget(&config(8), budget.clone());
+        buffer.write_all(b"0123").unwrap();
+        buffer.write_all(b"456789").unwrap();
+
+        assert!(buffer.is_spilled());
+        assert_eq!(budget.used(), 0);
+        let contents = buffer.contents().unwrap();
+        assert!(matches!(contents, SpillContents::Mapped(_)));
+        assert_eq!(contents.as_ref(), b"0123456789");
+    }
+
+    #[test]
+    fn test_budget_only_lowered() {
+        let budget = MemoryBudget::new(1024);
+        budget.lower_limit(2048);
+        assert_eq!(budget.limit(), 1024);
+        budget.lower_limit(512);
+        assert_eq!(budget.limit(), 512);
+    }
+
+    #[test]
+    fn test_exhausted_budget_spills() {
+        let budget = Arc::new(MemoryBudget::new(6));
+        let mut first = SpillBuffer::with_budget(&config(1024), budget.clone());
+        let mut second = SpillBuffer::with_budget(&config(1024), budget.clone());
+        first.write_all(b"abcd").unwrap();
+        second.write_all(b"efgh").unwrap();
+
+        assert!(!first.is_spilled());
+        assert!(second.is_spilled());
+        let mut out = Vec::new();
+        second.copy_to(&mut out).unwrap();
+        assert_eq!(out, b"efgh");
+    }
+
+    #[test]
+    fn test_spill_file_removed_on_drop() {
+        let mut buffer = SpillBuffer::with_budget(&config(0), Arc::new(MemoryBudget::new(0)));
+        buffer.write_all(b"data").unwrap();
+        let path = match &buffer.backing {
+            Backing::Disk { path, .. } => path.clone(),
+            Backing::Memory(_) => panic!("expected spill"),
+        };
+
+        assert!(path.exists());
+        drop(buffer);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_decode_flate_stream() {
+        use flate2::{write::ZlibEncoder, Compression};
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[b'q'; 64]).unwrap();
+        let stream = lopdf::Stream::new(
+            lopdf::dictionary! { "Filter" => "FlateDecode" },
+            encoder.finish().unwrap(),
+        );
+
+        let mut decoded = decode_stream(&stream, &config(16)).unwrap();
+        assert!(decoded.is_spilled());
+        assert_eq!(decoded.contents().unwrap().as_ref(), &[b'q'; 64][..]);
+    }
+}
@@ -0,0 +1,218 @@
+//! All-or-nothing publishing of a set of outputs
+//!
+//! Some batch workflows must release every cleaned file or none of them.
+//! A `StagingArea` is a hidden directory inside the target directory that
+//! outputs are written to first; `publish` then renames every staged file
+//! into place. Staging inside the target keeps each rename on one
+//! filesystem, so no file is ever seen half-written. If a rename fails,
+//! the files already moved are moved back and any outputs they replaced
+//! are restored, leaving the target as it was.
+//!
+//! A staging area dropped without `publish` is deleted with its contents.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Prefix of staging directory names, so listings can skip them
+pub const STAGING_PREFIX: &str = ".staging-";
+
+/// Directory of outputs waiting to be published together
+#[derive(Debug)]
+pub struct StagingArea {
+    target: PathBuf,
+    dir: PathBuf,
+    published: bool,
+}
+
+impl StagingArea {
+    /// Creates a staging area publishing into `target`
+    pub fn new(target: impl Into<PathBuf>) -> io::Result<Self> {
+        let target = crate::paths::extended(&target.into()).into_owned();
+        let dir = target.join(format!("{}{}", STAGING_PREFIX, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        Ok(Self { target, dir, published: false })
+    }
+
+    /// Where outputs are written until published; mirrors the target layout
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Moves every staged file to the same place under the target
+    ///
+    /// Returns the published paths. On error nothing is published.
+    pub fn publish(mut self) -> io::Result<Vec<PathBuf>> {
+        let mut staged = Vec::new();
+        collect_files(&self.dir, Path::new(""), &mut staged)?;
+        staged.sort();
+
+        let replaced_dir = self.dir.join(".replaced");
+        let mut moves: Vec<Move> = Vec::with_capacity(staged.len());
+        for relative in &staged {
+            match self.publish_one(relative, &replaced_dir) {
+                Ok(done) => moves.push(done),
+                Err(e) => {
+                    for done in moves.iter().rev() {
+                        if let Err(undo) = done.undo() {
+                            log::warn!("could not restore {} after a failed publish: {}", done.to.display(), undo);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        self.published = true;
+        fs::remove_dir_all(&self.dir)?;
+        Ok(moves.into_iter().map(|done| done.to).collect())
+    }
+
+    fn publish_one(&self, relative: &Path, replaced_dir: &Path) -> io::Result<Move> {
+        let from = self.dir.join(relative);
+        let to = self.target.join(relative);
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let replaced = match fs::symlink_metadata(&to) {
+            Ok(meta) if meta.is_dir() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} is a directory", to.display()),
+                ));
+            }
+            Ok(_) => {
+                let aside = replaced_dir.join(relative);
+                if let Some(parent) = aside.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&to, &aside)?;
+                Some(aside)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        let done = Move { from, to, replaced };
+        if let Err(e) = fs::rename(&done.from, &done.to) {
+            if let Some(aside) = &done.replaced {
+                fs::rename(aside, &done.to)?;
+            }
+            return Err(e);
+        }
+        Ok(done)
+    }
+}
+
+impl Drop for StagingArea {
+    fn drop(&mut self) {
+        if !self.published {
+            if let Err(e) = fs::remove_dir_all(&self.dir) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    log::warn!("could not remove staging area {}: {}", self.dir.display(), e);
+                }
+            }
+        }
+    }
+}
+
+/// One published file, with the output it replaced
+struct Move {
+    from: PathBuf,
+    to: PathBuf,
+    replaced: Option<PathBuf>,
+}
+
+impl Move {
+    fn undo(&self) -> io::Result<()> {
+        fs::rename(&self.to, &self.from)?;
+        if let Some(aside) = &self.replaced {
+            fs::rename(aside, &self.to)?;
+        }
+        Ok(())
+    }
+}
+
+/// Paths of the files under `dir`, relative to the staging root
+fn collect_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_moves_everything() {
+        let target = tempfile::tempdir().unwrap();
+        fs::write(target.path().join("a.pdf"), b"old").unwrap();
+
+        let staging = StagingArea::new(target.path()).unwrap();
+        fs::write(staging.path().join("a.pdf"), b"new").unwrap();
+        fs::create_dir_all(staging.path().join("en")).unwrap();
+        fs::write(staging.path().join("en").join("b.pdf"), b"b").unwrap();
+
+        let published = staging.publish().unwrap();
+        let names: Vec<_> = published.iter().map(|path| path.file_name().unwrap()).collect();
+        assert_eq!(names, ["a.pdf", "b.pdf"]);
+        assert_eq!(fs::read(target.path().join("a.pdf")).unwrap(), b"new");
+        assert_eq!(fs::read_dir(target.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_failed_publish_restores_target() {
+        let target = tempfile::tempdir().unwrap();
+        fs::write(target.path().join("a.pdf"), b"old").unwrap();
+        // A directory where a staged file should go makes the second move fail
+        fs::create_dir_all(target.path().join("b.pdf")).unwrap();
+
+        let staging = StagingArea::new(target.path()).unwrap();
+        fs::write(staging.path().join("a.pdf"), b"new").unwrap();
+        fs::write(staging.path().join("b.pdf"), b"b").unwrap();
+
+        assert!(staging.publish().is_err());
+        assert_eq!(fs::read(target.path().join("a.pdf")).unwrap(), b"old");
+        assert!(target.path().join("b.pdf").is_dir());
+        assert_eq!(fs::read_dir(target.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_publish_exotic_names() {
+        let target = tempfile::tempdir().unwrap();
+        let staging = StagingArea::new(target.path()).unwrap();
+        let names = crate::paths::tests::exotic_names();
+        for name in &names {
+            fs::write(staging.path().join(name), name.as_encoded_bytes()).unwrap();
+        }
+
+        assert_eq!(staging.publish().unwrap().len(), names.len());
+        for name in &names {
+            assert_eq!(fs::read(target.path().join(name)).unwrap(), name.as_encoded_bytes());
+        }
+    }
+
+    #[test]
+    fn test_dropped_staging_area_is_discarded() {
+        let target = tempfile::tempdir().unwrap();
+        let staging = StagingArea::new(target.path()).unwrap();
+        fs::write(staging.path().join("a.pdf"), b"new").unwrap();
+        drop(staging);
+        assert_eq!(fs::read_dir(target.path()).unwrap().count(), 0);
+    }
+}
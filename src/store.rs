@@ -0,0 +1,390 @@
+//! Content-addressable store of cleaned outputs
+//!
+//! Batch runs over large corpora see the same input many times, and the
+//! same input under the same settings always cleans to the same output.
+//! `OutputStore` keeps each output once, keyed by the SHA-256 of the input
+//! and a fingerprint of the settings, so repeats are linked or copied into
+//! place instead of being cleaned again. Entries carry caller data (e.g.
+//! scan summaries for sidecars) and are evicted least recently used first
+//! by `OutputStore::gc`.
+//!
+//! Layout: `<root>/objects/<first two key chars>/<key>.pdf` with a
+//! `<key>.json` entry beside it. Writes go through a temp file and a
+//! rename, so concurrent processes sharing a store never see partial
+//! entries.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Metadata of one stored output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreEntry {
+    pub key: String,
+    pub input_sha256: String,
+    pub config_fingerprint: String,
+    pub output_sha256: String,
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+    /// Last `put` or hit; GC evicts the oldest first
+    pub last_used: DateTime<Utc>,
+    pub hits: u64,
+    /// Caller data stored with the output
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+/// How a stored output reached its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// Hard link to the stored file; no bytes copied
+    Linked,
+    /// Copied, because linking failed (e.g. across filesystems)
+    Copied,
+}
+
+/// Limits enforced by `OutputStore::gc`
+#[derive(Debug, Clone, Default)]
+pub struct GcPolicy {
+    /// Evict least recently used entries until the store is at most this large
+    pub max_bytes: Option<u64>,
+    /// Evict entries unused for longer than this
+    pub max_age: Option<Duration>,
+    /// Overwrite evicted outputs before deleting them, unless linked elsewhere
+    pub secure_delete: bool,
+}
+
+/// What a GC pass removed and kept
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcReport {
+    pub removed: usize,
+    pub bytes_freed: u64,
+    pub remaining: usize,
+    pub bytes_remaining: u64,
+}
+
+/// Directory of outputs keyed by input and settings
+#[derive(Debug, Clone)]
+pub struct OutputStore {
+    root: PathBuf,
+}
+
+impl OutputStore {
+    /// Opens the store at `root`, creating it when missing
+    pub fn open(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = crate::paths::extended(&root.into()).into_owned();
+        fs::create_dir_all(root.join("objects"))?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Store key for an input hash and the settings it was cleaned with
+    ///
+    /// The crate version is part of the key so that upgrades, which may
+    /// clean differently, never serve outputs of an older release.
+    pub fn key(input_sha256: &str, config: &impl Serialize) -> io::Result<String> {
+        let fingerprint = config_fingerprint(config)?;
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update([0]);
+        hasher.update(input_sha256.to_ascii_lowercase().as_bytes());
+        hasher.update([0]);
+        hasher.update(fingerprint.as_bytes());
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Entry for `key`, without counting a hit
+    pub fn get(&self, key: &str) -> io::Result<Option<StoreEntry>> {
+        let (object, entry) = self.paths(key)?;
+        let content = match fs::read(&entry) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let entry: StoreEntry = match serde_json::from_slice(&content) {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("ignoring malformed store entry {}: {}", key, e);
+                return Ok(None);
+            }
+        };
+        // An entry whose object went missing or changed size is a miss
+        match fs::metadata(&object) {
+            Ok(meta) if meta.len() == entry.size => Ok(Some(entry)),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Places the output stored under `key` at `dest`
+    ///
+    /// Returns `None` on a miss. Stored files are read-only, so a linked
+    /// destination cannot be modified in place by accident; replace it
+    /// instead of editing it.
+    pub fn materialize(&self, key: &str, dest: &Path) -> io::Result<Option<(StoreEntry, Placement)>> {
+        let Some(mut entry) = self.get(key)? else { return Ok(None) };
+        let (object, _) = self.paths(key)?;
+
+        match fs::remove_file(dest) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let placement = match fs::hard_link(&object, dest) {
+            Ok(()) => Placement::Linked,
+            Err(_) => {
+                fs::copy(&object, dest)?;
+                make_writable(dest)?;
+                Placement::Copied
+            }
+        };
+
+        entry.hits += 1;
+        entry.last_used = Utc::now();
+        self.write_entry(&entry)?;
+        Ok(Some((entry, placement)))
+    }
+
+    /// Stores a copy of `output` under `key`, replacing any previous entry
+    pub fn put(
+        &self,
+        key: &str,
+        input_sha256: &str,
+        config: &impl Serialize,
+        output: &Path,
+        data: serde_json::Value,
+    ) -> io::Result<StoreEntry> {
+        let (object, _) = self.paths(key)?;
+        fs::create_dir_all(object.parent().expect("objects live in a shard directory"))?;
+
+        let staging = object.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        let result = (|| {
+            fs::copy(output, &staging)?;
+            let mut permissions = fs::metadata(&staging)?.permissions();
+            permissions.set_readonly(true);
+            fs::set_permissions(&staging, permissions)?;
+            fs::rename(&staging, &object)
+        })();
+        if let Err(e) = result {
+            let _ = fs::remove_file(&staging);
+            return Err(e);
+        }
+
+        let now = Utc::now();
+        let entry = StoreEntry {
+            key: key.to_string(),
+            input_sha256: input_sha256.to_ascii_lowercase(),
+            config_fingerprint: config_fingerprint(config)?,
+            output_sha256: sha256_file(&object)?,
+            size: fs::metadata(&object)?.len(),
+            created_at: now,
+            last_used: now,
+            hits: 0,
+            data,
+        };
+        self.write_entry(&entry)?;
+        Ok(entry)
+    }
+
+    /// Every readable entry, in no particular order
+    pub fn entries(&self) -> io::Result<Vec<StoreEntry>> {
+        let mut entries = Vec::new();
+        for shard in fs::read_dir(self.root.join("objects"))? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(&shard)? {
+                let path = file?.path();
+                if path.extension().map_or(true, |ext| ext != "json") {
+                    continue;
+                }
+                let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+                if let Some(entry) = self.get(key)? {
+                    entries.push(entry);
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Removes `key`; false when it was not stored
+    pub fn remove(&self, key: &str) -> io::Result<bool> {
+        self.delete(key, false)
+    }
+
+    fn delete(&self, key: &str, secure: bool) -> io::Result<bool> {
+        let (object, entry) = self.paths(key)?;
+        let mut removed = false;
+        for path in [entry, object] {
+            match crate::retention::remove_file(&path, secure) {
+                Ok(()) => removed = true,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Evicts entries over `policy`'s age limit, then least recently used
+    /// entries until the store fits its size limit
+    pub fn gc(&self, policy: &GcPolicy) -> io::Result<GcReport> {
+        let mut entries = self.entries()?;
+        entries.sort_by_key(|entry| entry.last_used);
+
+        let cutoff = policy.max_age
+            .and_then(|age| chrono::Duration::from_std(age).ok())
+            .map(|age| Utc::now() - age);
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut report = GcReport::default();
+
+        for entry in entries {
+            let expired = cutoff.is_some_and(|cutoff| entry.last_used < cutoff);
+            let oversized = policy.max_bytes.is_some_and(|max| total > max);
+            if (expired || oversized) && self.delete(&entry.key, policy.secure_delete)? {
+                report.removed += 1;
+                report.bytes_freed += entry.size;
+                total -= entry.size;
+            } else {
+                report.remaining += 1;
+            }
+        }
+        report.bytes_remaining = total;
+        self.remove_stale_staging()?;
+        Ok(report)
+    }
+
+    /// Deletes temp files left by interrupted `put`s
+    fn remove_stale_staging(&self) -> io::Result<()> {
+        for shard in fs::read_dir(self.root.join("objects"))? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(&shard)? {
+                let path = file?.path();
+                let stale = path.extension().is_some_and(|ext| ext == "tmp")
+                    && fs::metadata(&path)?.modified()?.elapsed().unwrap_or_default() > Duration::from_secs(3600);
+                if stale {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_entry(&self, entry: &StoreEntry) -> io::Result<()> {
+        let (_, path) = self.paths(&entry.key)?;
+        let staging = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        fs::write(&staging, serde_json::to_vec_pretty(entry)?)?;
+        fs::rename(&staging, &path)
+    }
+
+    /// Object and entry paths of `key`
+    fn paths(&self, key: &str) -> io::Result<(PathBuf, PathBuf)> {
+        if key.len() < 3 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid store key `{}`", key)));
+        }
+        let shard = self.root.join("objects").join(&key[..2]);
+        Ok((shard.join(format!("{key}.pdf")), shard.join(format!("{key}.json"))))
+    }
+}
+
+/// SHA-256 of the canonical JSON form of `config`
+pub fn config_fingerprint(config: &impl Serialize) -> io::Result<String> {
+    // Round-tripping through Value sorts object keys
+    let value = serde_json::to_value(config)?;
+    Ok(format!("{:x}", Sha256::digest(serde_json::to_vec(&value)?)))
+}
+
+/// Clears the read-only flag `put` sets on stored objects
+pub(crate) fn make_writable(path: &Path) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_store() -> OutputStore {
+        OutputStore::open(std::env::temp_dir().join(format!("store-{}", uuid::Uuid::new_v4()))).unwrap()
+    }
+
+    fn temp_file(content: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("store-output-{}.pdf", uuid::Uuid::new_v4()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_key_depends_on_input_and_config() {
+        let key = OutputStore::key("ab", &json!({"rounds": 3, "strip": true})).unwrap();
+        assert_eq!(key, OutputStore::key("AB", &json!({"strip": true, "rounds": 3})).unwrap());
+        assert_ne!(key, OutputStore::key("ac", &json!({"rounds": 3, "strip": true})).unwrap());
+        assert_ne!(key, OutputStore::key("ab", &json!({"rounds": 2, "strip": true})).unwrap());
+    }
+
+    #[test]
+    fn test_put_and_materialize() {
+        let store = temp_store();
+        let config = json!({"rounds": 3});
+        let key = OutputStore::key("00ff", &config).unwrap();
+        let dest = std::env::temp_dir().join(format!("store-dest-{}.pdf", uuid::Uuid::new_v4()));
+        assert!(store.materialize(&key, &dest).unwrap().is_none());
+
+        let output = temp_file(b"%PDF-1.7 cleaned");
+        store.put(&key, "00FF", &config, &output, json!({"verified": true})).unwrap();
+        fs::write(&dest, b"stale").unwrap();
+
+        let (entry, _) = store.materialize(&key, &dest).unwrap().unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"%PDF-1.7 cleaned");
+        assert_eq!(entry.hits, 1);
+        assert_eq!(entry.data["verified"], true);
+        assert_eq!(store.get(&key).unwrap().unwrap().hits, 1);
+    }
+
+    #[test]
+    fn test_gc_evicts_least_recently_used() {
+        let store = temp_store();
+        let keys: Vec<String> = (0..3).map(|i| {
+            let key = OutputStore::key(&format!("{i:02x}"), &json!({})).unwrap();
+            store.put(&key, "00", &json!({}), &temp_file(&[b'x'; 100]), serde_json::Value::Null).unwrap();
+            key
+        }).collect();
+        // Touch the first entry so the second becomes least recently used
+        store.materialize(&keys[0], &temp_file(b"")).unwrap();
+
+        let report = store.gc(&GcPolicy { max_bytes: Some(250), ..Default::default() }).unwrap();
+        assert_eq!(report, GcReport { removed: 1, bytes_freed: 100, remaining: 2, bytes_remaining: 200 });
+        assert!(store.get(&keys[1]).unwrap().is_none());
+        assert!(store.get(&keys[0]).unwrap().is_some());
+
+        let report = store.gc(&GcPolicy { max_age: Some(Duration::ZERO), ..Default::default() }).unwrap();
+        assert_eq!(report.remaining, 0);
+        assert!(store.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_path_like_keys() {
+        assert!(temp_store().get("../../etc").is_err());
+    }
+}
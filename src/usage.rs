@@ -0,0 +1,220 @@
+//! Per-job resource usage
+//!
+//! A `UsageMeter` measures one job: the CPU time of the threads polling
+//! it, the peak of the memory it reserved from the spill budget, and the
+//! bytes it read and wrote, including spill files. Operators use it to
+//! find the documents that are pathological to process.
+//!
+//! Memory and spill IO are attributed through a task-local set by
+//! `UsageMeter::measure`, so work moved to other tasks (e.g. with
+//! `spawn_blocking`) is not counted. CPU time comes from the thread CPU
+//! clock around every poll of the job and is only available on Unix.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use serde::{Deserialize, Serialize};
+
+/// Resources one job used
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// CPU time spent polling the job; `None` where thread CPU clocks are unavailable
+    pub cpu_time: Option<Duration>,
+    /// Highest memory the job held in the spill budget at once
+    pub peak_memory_bytes: usize,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Peak resident set size of the whole process when the job ended
+    pub process_peak_rss_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    cpu_nanos: AtomicU64,
+    cpu_unavailable: AtomicBool,
+    memory: AtomicUsize,
+    peak_memory: AtomicUsize,
+    read: AtomicU64,
+    written: AtomicU64,
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<Counters>;
+}
+
+/// Measures the resources of one job
+#[derive(Debug, Clone, Default)]
+pub struct UsageMeter {
+    counters: Arc<Counters>,
+}
+
+impl UsageMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `future`, attributing its CPU time, memory and IO to this meter
+    pub async fn measure<F: Future>(&self, future: F) -> F::Output {
+        let timed = CpuTimed { inner: Box::pin(future), counters: self.counters.clone() };
+        CURRENT.scope(self.counters.clone(), timed).await
+    }
+
+    pub fn add_read(&self, bytes: u64) {
+        self.counters.read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_written(&self, bytes: u64) {
+        self.counters.written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Usage so far
+    pub fn usage(&self) -> ResourceUsage {
+        let counters = &self.counters;
+        ResourceUsage {
+            cpu_time: (!counters.cpu_unavailable.load(Ordering::Relaxed))
+                .then(|| Duration::from_nanos(counters.cpu_nanos.load(Ordering::Relaxed))),
+            peak_memory_bytes: counters.peak_memory.load(Ordering::Relaxed),
+            bytes_read: counters.read.load(Ordering::Relaxed),
+            bytes_written: counters.written.load(Ordering::Relaxed),
+            process_peak_rss_bytes: process_peak_rss(),
+        }
+    }
+}
+
+/// Counts `bytes` read by the current job, if any
+pub(crate) fn record_read(bytes: u64) {
+    let _ = CURRENT.try_with(|counters| counters.read.fetch_add(bytes, Ordering::Relaxed));
+}
+
+/// Counts `bytes` written by the current job, if any
+pub(crate) fn record_written(bytes: u64) {
+    let _ = CURRENT.try_with(|counters| counters.written.fetch_add(bytes, Ordering::Relaxed));
+}
+
+/// Counts memory the current job reserved, updating its peak
+pub(crate) fn record_reserved(bytes: usize) {
+    let _ = CURRENT.try_with(|counters| {
+        let held = counters.memory.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        counters.peak_memory.fetch_max(held, Ordering::Relaxed);
+    });
+}
+
+/// Counts memory the current job released
+pub(crate) fn record_released(bytes: usize) {
+    let _ = CURRENT.try_with(|counters| {
+        let _ = counters.memory.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |held| {
+            Some(held.saturating_sub(bytes))
+        });
+    });
+}
+
+/// Adds the thread CPU time of every poll of `inner` to `counters`
+struct CpuTimed<F> {
+    inner: Pin<Box<F>>,
+    counters: Arc<Counters>,
+}
+
+impl<F: Future> Future for CpuTimed<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let before = thread_cpu_time();
+        let poll = self.inner.as_mut().poll(cx);
+        match (before, thread_cpu_time()) {
+            (Some(before), Some(after)) => {
+                let spent = after.saturating_sub(before).as_nanos() as u64;
+                self.counters.cpu_nanos.fetch_add(spent, Ordering::Relaxed);
+            }
+            _ => self.counters.cpu_unavailable.store(true, Ordering::Relaxed),
+        }
+        poll
+    }
+}
+
+/// CPU time of the calling thread
+pub fn thread_cpu_time() -> Option<Duration> {
+    #[cfg(unix)]
+    {
+        let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        // SAFETY: `time` is a valid out-pointer for the duration of the call
+        if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } == 0 {
+            return Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32));
+        }
+        None
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// Peak resident set size of the process
+pub fn process_peak_rss() -> Option<u64> {
+    #[cfg(unix)]
+    {
+        // SAFETY: getrusage only writes into the zeroed struct passed to it
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+            return None;
+        }
+        let max_rss = usage.ru_maxrss as u64;
+        // Reported in bytes on macOS and in kilobytes elsewhere
+        Some(if cfg!(target_os = "macos") { max_rss } else { max_rss * 1024 })
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spill::{MemoryBudget, SpillBuffer, SpillConfig};
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_measures_memory_and_spill_io() {
+        let meter = UsageMeter::new();
+        let dir = tempfile::tempdir().unwrap();
+        let config = SpillConfig { threshold: 16, memory_budget: 1024, dir: dir.path().to_path_buf() };
+
+        meter.measure(async {
+            let budget = Arc::new(MemoryBudget::new(1024));
+            let mut small = SpillBuffer::with_budget(&config, budget.clone());
+            small.write_all(&[0; 10]).unwrap();
+            drop(small);
+            let mut large = SpillBuffer::with_budget(&config, budget);
+            large.write_all(&[0; 100]).unwrap();
+        }).await;
+        // Outside the scope nothing is attributed
+        record_written(1_000_000);
+
+        let usage = meter.usage();
+        assert_eq!(usage.peak_memory_bytes, 10);
+        assert_eq!(usage.bytes_written, 100);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cpu_time_counts_work_in_the_job() {
+        let meter = UsageMeter::new();
+        meter.measure(async {
+            let mut x = 0u64;
+            let start = std::time::Instant::now();
+            while start.elapsed() < Duration::from_millis(20) {
+                x = std::hint::black_box(x.wrapping_add(1));
+            }
+        }).await;
+
+        assert!(meter.usage().cpu_time.unwrap() >= Duration::from_millis(5));
+        assert!(meter.usage().process_peak_rss_bytes.unwrap() > 0);
+    }
+}
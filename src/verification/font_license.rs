@@ -0,0 +1,231 @@
+//! Embedded font license audit
+//!
+//! TrueType and OpenType fonts declare in the `fsType` field of their
+//! OS/2 table what a document may do with an embedded copy. The audit
+//! reads that field from every embedded font program, reports fonts whose
+//! license forbids embedding or editing, and tells the optimizer which
+//! objects it must leave byte-for-byte alone.
+
+use lopdf::{Document, Object, ObjectId};
+use std::collections::{HashMap, HashSet};
+
+use super::VerificationWarning;
+
+/// Restricted License embedding: the font must not be embedded at all
+const FS_RESTRICTED: u16 = 0x0002;
+/// Preview & Print embedding: read-only use only
+const FS_PREVIEW_PRINT: u16 = 0x0004;
+/// Editable embedding
+const FS_EDITABLE: u16 = 0x0008;
+/// No subsetting: only the complete font may be embedded
+const FS_NO_SUBSETTING: u16 = 0x0100;
+/// Bitmap embedding only
+const FS_BITMAP_ONLY: u16 = 0x0200;
+
+/// License flags of one embedded font program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontLicense {
+    /// Font descriptor holding the program
+    pub descriptor: ObjectId,
+    /// Embedded font program stream
+    pub program: ObjectId,
+    /// `/FontName` of the descriptor
+    pub name: String,
+    pub fs_type: u16,
+}
+
+impl FontLicense {
+    pub fn forbids_embedding(&self) -> bool {
+        self.fs_type & FS_RESTRICTED != 0
+    }
+
+    pub fn forbids_editing(&self) -> bool {
+        self.forbids_embedding() || (self.fs_type & FS_PREVIEW_PRINT != 0 && self.fs_type & FS_EDITABLE == 0)
+    }
+
+    pub fn forbids_subsetting(&self) -> bool {
+        self.fs_type & FS_NO_SUBSETTING != 0
+    }
+
+    pub fn bitmap_only(&self) -> bool {
+        self.fs_type & FS_BITMAP_ONLY != 0
+    }
+
+    /// Whether an optimizer may not touch the font
+    pub fn is_restricted(&self) -> bool {
+        self.forbids_editing() || self.forbids_subsetting() || self.bitmap_only()
+    }
+}
+
+/// Licenses of every embedded TrueType/OpenType program in the document
+///
+/// Type 1 and bare CFF programs carry no `fsType` and are not listed.
+pub fn audit(doc: &Document) -> Vec<FontLicense> {
+    let mut licenses: Vec<FontLicense> = doc.objects.iter()
+        .filter_map(|(&descriptor, object)| {
+            let dict = object.as_dict().ok()?;
+            if dict.get(b"Type").and_then(Object::as_name).ok() != Some(&b"FontDescriptor"[..]) {
+                return None;
+            }
+            let program = dict.get(b"FontFile2").or_else(|_| dict.get(b"FontFile3")).ok()?.as_reference().ok()?;
+            let stream = doc.get_object(program).and_then(Object::as_stream).ok()?;
+            let data = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+            Some(FontLicense {
+                descriptor,
+                program,
+                name: dict.get(b"FontName").and_then(Object::as_name)
+                    .map(|n| String::from_utf8_lossy(n).into_owned())
+                    .unwrap_or_default(),
+                fs_type: os2_fs_type(&data)?,
+            })
+        })
+        .collect();
+    licenses.sort_by_key(|license| license.descriptor);
+    licenses
+}
+
+/// `fsType` from the OS/2 table of an sfnt font program
+pub fn os2_fs_type(data: &[u8]) -> Option<u16> {
+    let read_u16 = |at: usize| data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+    let read_u32 = |at: usize| data.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+
+    let tables = read_u16(4)? as usize;
+    (0..tables)
+        .map(|index| 12 + index * 16)
+        .find(|&record| data.get(record..record + 4) == Some(&b"OS/2"[..]))
+        .and_then(|record| read_u16(read_u32(record + 8)? + 8))
+}
+
+/// Objects an optimizer must keep unchanged: restricted programs, their
+/// descriptors and the font dictionaries using them
+pub fn protected_objects(doc: &Document) -> HashSet<ObjectId> {
+    let mut protected: HashSet<ObjectId> = HashSet::new();
+    for license in audit(doc).iter().filter(|l| l.is_restricted()) {
+        protected.insert(license.descriptor);
+        protected.insert(license.program);
+    }
+    if protected.is_empty() {
+        return protected;
+    }
+
+    // Simple and CID fonts name the descriptor; Type0 fonts name the CID font
+    let fonts: HashMap<ObjectId, Vec<ObjectId>> = doc.objects.iter()
+        .filter_map(|(&id, object)| {
+            let dict = object.as_dict().ok()?;
+            if dict.get(b"Type").and_then(Object::as_name).ok() != Some(&b"Font"[..]) {
+                return None;
+            }
+            let mut refs: Vec<ObjectId> = dict.get(b"FontDescriptor").and_then(Object::as_reference).into_iter().collect();
+            if let Ok(Object::Array(descendants)) = dict.get(b"DescendantFonts") {
+                refs.extend(descendants.iter().filter_map(|d| d.as_reference().ok()));
+            }
+            Some((id, refs))
+        })
+        .collect();
+    for _ in 0..2 {
+        for (id, refs) in &fonts {
+            if refs.iter().any(|r| protected.contains(r)) {
+                protected.insert(*id);
+            }
+        }
+    }
+    protected
+}
+
+/// Verification warnings for fonts whose license is violated by embedding or editing
+pub fn warnings(doc: &Document) -> Vec<VerificationWarning> {
+    audit(doc).into_iter()
+        .filter_map(|license| {
+            let (code, message, recommendation) = if license.forbids_embedding() {
+                (
+                    "FONT_EMBEDDING_RESTRICTED",
+                    format!("Font '{}' has a restricted license that forbids embedding (fsType {:#06x})", license.name, license.fs_type),
+                    "Replace the font or obtain an embedding license",
+                )
+            } else if license.forbids_editing() {
+                (
+                    "FONT_EDITING_RESTRICTED",
+                    format!("Font '{}' allows preview and print only (fsType {:#06x})", license.name, license.fs_type),
+                    "Do not edit text set in this font",
+                )
+            } else if license.forbids_subsetting() || license.bitmap_only() {
+                (
+                    "FONT_SUBSETTING_RESTRICTED",
+                    format!("Font '{}' must be embedded complete or as bitmaps only (fsType {:#06x})", license.name, license.fs_type),
+                    "Do not subset or convert this font",
+                )
+            } else {
+                return None;
+            };
+            Some(VerificationWarning {
+                code: code.to_string(),
+                message,
+                location: Some(license.descriptor),
+                recommendation: recommendation.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    /// Minimal sfnt with only an OS/2 table
+    fn font_program(fs_type: u16) -> Vec<u8> {
+        let mut data = vec![0, 1, 0, 0, 0, 1, 0, 16, 0, 0, 0, 0];
+        data.extend_from_slice(b"OS/2");
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&28u32.to_be_bytes());
+        data.extend_from_slice(&10u32.to_be_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&fs_type.to_be_bytes());
+        data
+    }
+
+    /// Document with one TrueType font embedded under the given fsType
+    pub(crate) fn document_with_font(fs_type: u16) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let program = doc.add_object(Stream::new(dictionary! {}, font_program(fs_type)));
+        let descriptor = doc.add_object(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => "Licensed",
+            "FontFile2" => program,
+        });
+        doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "TrueType",
+            "BaseFont" => "Licensed",
+            "FontDescriptor" => descriptor,
+        });
+        doc
+    }
+
+    #[test]
+    fn test_fs_type_read_from_os2() {
+        assert_eq!(os2_fs_type(&font_program(0x0104)), Some(0x0104));
+        assert_eq!(os2_fs_type(b"not a font"), None);
+    }
+
+    #[test]
+    fn test_restricted_font_reported_and_protected() {
+        let doc = document_with_font(FS_RESTRICTED);
+        let warnings = warnings(&doc);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "FONT_EMBEDDING_RESTRICTED");
+
+        // Program, descriptor and font dictionary
+        assert_eq!(protected_objects(&doc).len(), 3);
+    }
+
+    #[test]
+    fn test_installable_font_unrestricted() {
+        let doc = document_with_font(0);
+        assert!(warnings(&doc).is_empty());
+        assert!(protected_objects(&doc).is_empty());
+
+        let editable = document_with_font(FS_PREVIEW_PRINT | FS_EDITABLE);
+        assert!(!audit(&editable)[0].forbids_editing());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{EngineConfig, PdfError};
 use chrono::{DateTime, Utc};
 use lopdf::{Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
@@ -10,6 +11,8 @@ pub mod structure;
 pub mod compliance;
 pub mod signature;
 pub mod content;
+pub mod font_license;
+pub mod policy;
 
 use structure::StructureVerifier;
 use compliance::ComplianceVerifier;
@@ -37,25 +40,36 @@ pub struct VerificationConfig {
     pub verification_level: VerificationLevel,
     pub compliance_standard: Option<ComplianceStandard>,
     pub require_signatures: bool,
+    /// Run the structure checks
+    pub check_structure: bool,
+    /// Run the content checks
+    pub check_content: bool,
     pub max_verification_time: std::time::Duration,
     pub cache_results: bool,
     pub cache_ttl: std::time::Duration,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum VerificationLevel {
     Basic,
     Standard,
     Strict,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum ComplianceStandard {
+    #[serde(rename = "pdfa-1a")]
     PdfA1a,
+    #[serde(rename = "pdfa-1b")]
     PdfA1b,
+    #[serde(rename = "pdfa-2a")]
     PdfA2a,
+    #[serde(rename = "pdfa-2b")]
     PdfA2b,
+    #[serde(rename = "pdfa-3a")]
     PdfA3a,
+    #[serde(rename = "pdfa-3b")]
     PdfA3b,
 }
 
@@ -97,7 +111,8 @@ pub struct VerificationStats {
     pub rules_checked: usize,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ErrorSeverity {
     Critical,
     Major,
@@ -144,8 +159,12 @@ impl VerificationSystem {
         let document_id = doc.get_id().unwrap_or_else(|| "unknown".to_string());
 
         // Verify structure
-        let structure_result = self.structure_verifier.verify(doc).await?;
-        errors.extend(structure_result.errors);
+        let structure_result = if config.check_structure {
+            self.structure_verifier.verify(doc).await?
+        } else {
+            structure::StructureResult::default()
+        };
+        errors.extend(tagged(structure_result.errors.clone(), "structure"));
         warnings.extend(structure_result.warnings);
 
         // Verify compliance if standard is specified
@@ -154,7 +173,7 @@ impl VerificationSystem {
         } else {
             compliance::ComplianceResult::default()
         };
-        errors.extend(compliance_result.errors);
+        errors.extend(tagged(compliance_result.errors.clone(), "compliance"));
         warnings.extend(compliance_result.warnings);
 
         // Verify signatures if required
@@ -163,14 +182,21 @@ impl VerificationSystem {
         } else {
             signature::SignatureResult::default()
         };
-        errors.extend(signature_result.errors);
+        errors.extend(tagged(signature_result.errors.clone(), "signatures"));
         warnings.extend(signature_result.warnings);
 
         // Verify content
-        let content_result = self.content_verifier.verify(doc).await?;
-        errors.extend(content_result.errors);
+        let content_result = if config.check_content {
+            self.content_verifier.verify(doc).await?
+        } else {
+            content::ContentResult::default()
+        };
+        errors.extend(tagged(content_result.errors.clone(), "content"));
         warnings.extend(content_result.warnings);
 
+        // Embedded fonts whose license forbids embedding or editing
+        warnings.extend(font_license::warnings(doc));
+
         // Collect verification statistics
         let stats = VerificationStats {
             execution_time: start_time.elapsed(),
@@ -179,7 +205,8 @@ impl VerificationSystem {
             rules_checked: structure_result.rules_checked + 
                          compliance_result.rules_checked +
                          signature_result.rules_checked +
-                         content_result.rules_checked,
+                         content_result.rules_checked +
+                         1,
         };
 
         let result = VerificationResult {
@@ -223,12 +250,25 @@ impl VerificationSystem {
     }
 }
 
+/// Key of the check that raised an error, in `VerificationError::details`
+pub const CHECK_DETAIL: &str = "check";
+
+/// `errors` marked as raised by `check`
+fn tagged(errors: Vec<VerificationError>, check: &str) -> impl Iterator<Item = VerificationError> + '_ {
+    errors.into_iter().map(move |mut error| {
+        error.details.insert(CHECK_DETAIL.to_string(), check.to_string());
+        error
+    })
+}
+
 impl Default for VerificationConfig {
     fn default() -> Self {
         Self {
             verification_level: VerificationLevel::Standard,
             compliance_standard: None,
             require_signatures: false,
+            check_structure: true,
+            check_content: true,
             max_verification_time: std::time::Duration::from_secs(30),
             cache_results: true,
             cache_ttl: std::time::Duration::from_secs(300), // 5 minutes
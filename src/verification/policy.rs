@@ -0,0 +1,189 @@
+//! Verification policies
+//!
+//! A policy file names the checks a verification runs (structure, a
+//! PDF/A compliance level, signatures, content) and how severe an error
+//! must be to fail it, so the same verification can gate a pipeline on
+//! its own, without cleaning anything. Error codes a deployment accepts
+//! can be allowed: they are still reported but never fail verification.
+
+use std::{fs, io, path::Path};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ComplianceStandard, ErrorSeverity, VerificationConfig, VerificationError, VerificationLevel,
+    VerificationResult, CHECK_DETAIL,
+};
+
+/// What to verify and what fails, as read from a JSON policy file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VerificationPolicy {
+    pub level: VerificationLevel,
+    pub structure: bool,
+    /// PDF/A level to check against, e.g. `"pdfa-2b"`; `None` skips compliance
+    pub compliance: Option<ComplianceStandard>,
+    /// Check signatures, failing unsigned documents
+    pub signatures: bool,
+    pub content: bool,
+    /// Least severe error that fails verification
+    pub fail_on: ErrorSeverity,
+    /// Fail on warnings as well
+    pub fail_on_warnings: bool,
+    /// Error and warning codes reported without failing verification
+    pub allow: Vec<String>,
+}
+
+impl Default for VerificationPolicy {
+    fn default() -> Self {
+        Self {
+            level: VerificationLevel::Standard,
+            structure: true,
+            compliance: None,
+            signatures: false,
+            content: true,
+            fail_on: ErrorSeverity::Major,
+            fail_on_warnings: false,
+            allow: Vec::new(),
+        }
+    }
+}
+
+/// Whether one check ran and passed under the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckOutcome {
+    pub check: &'static str,
+    pub ran: bool,
+    /// Errors of the check that fail verification
+    pub failures: usize,
+}
+
+/// Pass/fail decision over a verification result
+#[derive(Debug, Clone)]
+pub struct PolicyVerdict {
+    pub checks: Vec<CheckOutcome>,
+    /// Errors that fail verification
+    pub failures: Vec<VerificationError>,
+    /// Errors below the threshold or allowed, which are only reported
+    pub tolerated: Vec<VerificationError>,
+    /// Codes of warnings that fail verification under `fail_on_warnings`
+    pub failing_warnings: Vec<String>,
+}
+
+impl PolicyVerdict {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty() && self.failing_warnings.is_empty()
+    }
+}
+
+impl VerificationPolicy {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Configuration that runs the policy's checks
+    pub fn config(&self) -> VerificationConfig {
+        VerificationConfig {
+            verification_level: self.level,
+            compliance_standard: self.compliance,
+            require_signatures: self.signatures,
+            check_structure: self.structure,
+            check_content: self.content,
+            cache_results: false,
+            ..Default::default()
+        }
+    }
+
+    /// Sorts the errors of `result` into failures and tolerated ones
+    pub fn evaluate(&self, result: &VerificationResult) -> PolicyVerdict {
+        let (failures, tolerated): (Vec<VerificationError>, Vec<VerificationError>) = result.errors.iter()
+            .cloned()
+            .partition(|error| !self.allowed(&error.code) && rank(error.severity) >= rank(self.fail_on));
+        let failing_warnings = if self.fail_on_warnings {
+            result.warnings.iter().filter(|w| !self.allowed(&w.code)).map(|w| w.code.clone()).collect()
+        } else {
+            Vec::new()
+        };
+        let ran = [
+            ("structure", self.structure),
+            ("compliance", self.compliance.is_some()),
+            ("signatures", self.signatures),
+            ("content", self.content),
+        ];
+        let checks = ran.into_iter()
+            .map(|(check, ran)| CheckOutcome {
+                check,
+                ran,
+                failures: failures.iter()
+                    .filter(|error| error.details.get(CHECK_DETAIL).is_some_and(|tag| tag == check))
+                    .count(),
+            })
+            .collect();
+        PolicyVerdict { checks, failures, tolerated, failing_warnings }
+    }
+
+    fn allowed(&self, code: &str) -> bool {
+        self.allow.iter().any(|allowed| allowed == code)
+    }
+}
+
+fn rank(severity: ErrorSeverity) -> u8 {
+    match severity {
+        ErrorSeverity::Minor => 1,
+        ErrorSeverity::Major => 2,
+        ErrorSeverity::Critical => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::verification::VerificationStats;
+
+    #[test]
+    fn test_policy_fails_on_threshold_and_allows_codes() {
+        let policy: VerificationPolicy = serde_json::from_str(
+            r#"{"compliance": "pdfa-2b", "fail_on": "major", "allow": ["MISSING_OUTPUT_INTENT"]}"#,
+        ).unwrap();
+        assert_eq!(policy.config().compliance_standard, Some(ComplianceStandard::PdfA2b));
+        assert!(serde_json::from_str::<VerificationPolicy>(r#"{"strict": true}"#).is_err());
+
+        let error = |code: &str, severity, check: &str| VerificationError {
+            code: code.to_string(),
+            message: String::new(),
+            location: None,
+            severity,
+            details: HashMap::from([(CHECK_DETAIL.to_string(), check.to_string())]),
+        };
+        let mut result = VerificationResult {
+            document_id: "doc".into(),
+            timestamp: chrono::Utc::now(),
+            structure_valid: false,
+            compliance_valid: false,
+            signatures_valid: true,
+            content_valid: true,
+            errors: vec![
+                error("MISSING_ROOT_ENTRY", ErrorSeverity::Critical, "structure"),
+                error("MISSING_OUTPUT_INTENT", ErrorSeverity::Major, "compliance"),
+                error("LARGE_STREAM", ErrorSeverity::Minor, "structure"),
+            ],
+            warnings: Vec::new(),
+            stats: VerificationStats {
+                execution_time: std::time::Duration::ZERO,
+                objects_verified: 0,
+                signatures_verified: 0,
+                rules_checked: 3,
+            },
+        };
+
+        let verdict = policy.evaluate(&result);
+        assert!(!verdict.passed());
+        assert_eq!(verdict.failures.len(), 1);
+        assert_eq!(verdict.tolerated.len(), 2);
+        let failing: Vec<(&str, bool, usize)> = verdict.checks.iter().map(|c| (c.check, c.ran, c.failures)).collect();
+        assert_eq!(failing, vec![("structure", true, 1), ("compliance", true, 0), ("signatures", false, 0), ("content", true, 0)]);
+
+        result.errors.remove(0);
+        assert!(policy.evaluate(&result).passed());
+    }
+}
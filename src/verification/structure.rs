@@ -28,7 +28,7 @@ struct StructureConfig {
     validate_streams: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct StructureResult {
     pub errors: Vec<VerificationError>,
     pub warnings: Vec<VerificationWarning>,
@@ -0,0 +1,208 @@
+//! Perceptual comparison of rendered pages
+//!
+//! A safety net against destructive cleaning: pages of the input and the
+//! output are rendered (see `raster`, behind the `render` feature) and
+//! compared here. Each page gets the fraction of pixels that changed by
+//! more than a per-channel tolerance, and the mean structural similarity
+//! (SSIM) of its luma over 8x8 windows. Pages past either limit are
+//! flagged unless the requested operations were expected to change them,
+//! e.g. redacted pages.
+//!
+//! Comparison works on raw RGBA buffers, so it is always built; only
+//! producing the buffers needs a renderer.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+/// Side of the square windows SSIM is averaged over
+const WINDOW: usize = 8;
+/// SSIM stabilizers for 8-bit samples: (0.01 * 255)^2 and (0.03 * 255)^2
+const C1: f64 = 6.5025;
+const C2: f64 = 58.5225;
+
+/// Limits past which a page counts as visually changed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VisualDiffConfig {
+    /// Channel difference up to which a pixel counts as unchanged (anti-aliasing noise)
+    pub tolerance: u8,
+    /// Largest fraction of changed pixels, in `0.0..=1.0`
+    pub max_changed_fraction: f64,
+    /// Lowest mean SSIM, in `-1.0..=1.0`
+    pub min_ssim: f64,
+}
+
+impl Default for VisualDiffConfig {
+    fn default() -> Self {
+        Self { tolerance: 16, max_changed_fraction: 0.001, min_ssim: 0.99 }
+    }
+}
+
+impl VisualDiffConfig {
+    /// Limits that only accept pixel-identical pages
+    pub fn identical() -> Self {
+        Self { tolerance: 0, max_changed_fraction: 0.0, min_ssim: 1.0 }
+    }
+}
+
+/// RGBA pixels of one rendered page, row-major
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: &'a [u8],
+}
+
+/// How much one page changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PageDiff {
+    /// 0-based page index
+    pub page: usize,
+    pub changed_pixels: u64,
+    pub changed_fraction: f64,
+    pub ssim: f64,
+    /// The requested operations were expected to change this page
+    pub expected: bool,
+    /// Changed past the limits without being expected to
+    pub flagged: bool,
+}
+
+impl PageDiff {
+    /// Compares page `page` of the input (`before`) and the output (`after`)
+    ///
+    /// Pages of different sizes count as entirely changed.
+    pub fn compare(page: usize, before: Frame, after: Frame, config: &VisualDiffConfig, expected: bool) -> Self {
+        if (before.width, before.height) != (after.width, after.height)
+            || before.rgba.len() != after.rgba.len()
+        {
+            return Self::missing(page, config, expected);
+        }
+
+        let pixels = before.rgba.len() / 4;
+        let changed_pixels = before.rgba.chunks_exact(4).zip(after.rgba.chunks_exact(4))
+            .filter(|(a, b)| a.iter().zip(b.iter()).any(|(x, y)| x.abs_diff(*y) > config.tolerance))
+            .count() as u64;
+        let changed_fraction = if pixels == 0 { 0.0 } else { changed_pixels as f64 / pixels as f64 };
+        let ssim = mean_ssim(&luma(before), &luma(after), before.width as usize, before.height as usize);
+        Self::judged(page, changed_pixels, changed_fraction, ssim, config, expected)
+    }
+
+    /// Diff of a page only one side has
+    pub fn missing(page: usize, config: &VisualDiffConfig, expected: bool) -> Self {
+        Self::judged(page, 0, 1.0, 0.0, config, expected)
+    }
+
+    fn judged(
+        page: usize,
+        changed_pixels: u64,
+        changed_fraction: f64,
+        ssim: f64,
+        config: &VisualDiffConfig,
+        expected: bool,
+    ) -> Self {
+        let changed = changed_fraction > config.max_changed_fraction || ssim < config.min_ssim;
+        Self { page, changed_pixels, changed_fraction, ssim, expected, flagged: changed && !expected }
+    }
+}
+
+/// Rec. 601 luma of every pixel, composited over white
+fn luma(frame: Frame) -> Vec<f64> {
+    frame.rgba.chunks_exact(4)
+        .map(|p| {
+            let alpha = p[3] as f64 / 255.0;
+            let over_white = |c: u8| c as f64 * alpha + 255.0 * (1.0 - alpha);
+            0.299 * over_white(p[0]) + 0.587 * over_white(p[1]) + 0.114 * over_white(p[2])
+        })
+        .collect()
+}
+
+/// Mean SSIM over non-overlapping windows; partial windows at the edges count too
+fn mean_ssim(a: &[f64], b: &[f64], width: usize, height: usize) -> f64 {
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+    let mut total = 0.0;
+    let mut windows = 0usize;
+    for top in (0..height).step_by(WINDOW) {
+        for left in (0..width).step_by(WINDOW) {
+            let rows = top..(top + WINDOW).min(height);
+            let cols = left..(left + WINDOW).min(width);
+            let samples: Vec<(f64, f64)> = rows
+                .flat_map(|y| cols.clone().map(move |x| y * width + x))
+                .map(|i| (a[i], b[i]))
+                .collect();
+            let n = samples.len() as f64;
+            let (mean_a, mean_b) = samples.iter().fold((0.0, 0.0), |(sa, sb), (x, y)| (sa + x, sb + y));
+            let (mean_a, mean_b) = (mean_a / n, mean_b / n);
+            let (mut var_a, mut var_b, mut cov) = (0.0, 0.0, 0.0);
+            for (x, y) in &samples {
+                var_a += (x - mean_a).powi(2);
+                var_b += (y - mean_b).powi(2);
+                cov += (x - mean_a) * (y - mean_b);
+            }
+            let (var_a, var_b, cov) = (var_a / n, var_b / n, cov / n);
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2))
+                / ((mean_a.powi(2) + mean_b.powi(2) + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// White 32x32 page with a black square at `offset`
+    fn page(offset: usize) -> Vec<u8> {
+        let mut rgba = vec![255u8; 32 * 32 * 4];
+        for y in offset..offset + 8 {
+            for x in offset..offset + 8 {
+                rgba[(y * 32 + x) * 4..][..3].copy_from_slice(&[0, 0, 0]);
+            }
+        }
+        rgba
+    }
+
+    fn frame(rgba: &[u8]) -> Frame<'_> {
+        Frame { width: 32, height: 32, rgba }
+    }
+
+    #[test]
+    fn test_identical_pages_pass_strict_limits() {
+        let a = page(4);
+        let diff = PageDiff::compare(0, frame(&a), frame(&a), &VisualDiffConfig::identical(), false);
+        assert_eq!(diff.changed_pixels, 0);
+        assert!((diff.ssim - 1.0).abs() < 1e-9);
+        assert!(!diff.flagged);
+    }
+
+    #[test]
+    fn test_noise_within_tolerance_is_ignored() {
+        let a = page(4);
+        let b: Vec<u8> = a.iter().map(|&c| c.saturating_sub(3)).collect();
+        let diff = PageDiff::compare(0, frame(&a), frame(&b), &VisualDiffConfig::default(), false);
+        assert_eq!(diff.changed_pixels, 0);
+        assert!(!diff.flagged);
+    }
+
+    #[test]
+    fn test_moved_content_is_flagged_unless_expected() {
+        let (a, b) = (page(4), page(16));
+        let config = VisualDiffConfig::default();
+        let diff = PageDiff::compare(2, frame(&a), frame(&b), &config, false);
+        assert_eq!(diff.changed_pixels, 128);
+        assert!(diff.ssim < config.min_ssim);
+        assert!(diff.flagged);
+        assert!(!PageDiff::compare(2, frame(&a), frame(&b), &config, true).flagged);
+    }
+
+    #[test]
+    fn test_size_mismatch_is_entirely_changed() {
+        let a = page(4);
+        let blank = vec![255u8; 16 * 16 * 4];
+        let small = Frame { width: 16, height: 16, rgba: &blank };
+        let diff = PageDiff::compare(0, frame(&a), small, &VisualDiffConfig::default(), false);
+        assert_eq!(diff.changed_fraction, 1.0);
+        assert!(diff.flagged);
+    }
+}
@@ -0,0 +1,291 @@
+//! Watchdog for worker permits held too long
+//!
+//! A job that hangs while holding a worker permit starves every other job
+//! waiting on that pool, which from outside looks like the whole engine
+//! deadlocked. The `Watchdog` tracks every permit handed out by the
+//! `ConcurrencyLimiter` with its owner (job id and subsystem) and how long
+//! it has been held. A periodic check logs permits held past the
+//! threshold and, with `StuckAction::FailJob`, fails their jobs so the
+//! permits are released.
+//!
+//! Owners are known through the job scope `JobGuard::run` sets up; permits
+//! taken outside a job (or in tasks spawned from one) are still tracked
+//! and logged, but cannot be failed. Time spent waiting for permits is
+//! recorded per subsystem as lock-wait statistics.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::concurrency::Subsystem;
+
+/// What the watchdog does about a permit held past the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StuckAction {
+    /// Log the owner and keep waiting
+    #[default]
+    Log,
+    /// Log the owner and fail its job, releasing the permit
+    FailJob,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    /// Permits held longer than this count as stuck
+    pub hold_threshold: Duration,
+    /// How often held permits are checked
+    pub check_interval: Duration,
+    pub action: StuckAction,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            hold_threshold: Duration::from_secs(300),
+            check_interval: Duration::from_secs(5),
+            action: StuckAction::Log,
+        }
+    }
+}
+
+/// A permit held past the threshold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StuckPermit {
+    /// Job holding the permit; `None` when taken outside a job scope
+    pub job_id: Option<String>,
+    pub subsystem: String,
+    pub held_for: Duration,
+}
+
+/// Time spent waiting for permits of one subsystem
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LockWaitStats {
+    pub subsystem: String,
+    pub acquisitions: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+/// Job a task runs for, set by `JobGuard::run`
+#[derive(Debug, Clone)]
+pub(crate) struct JobScope {
+    pub(crate) job_id: String,
+    /// Set to `Some(reason)` to fail the job
+    pub(crate) fail: Arc<watch::Sender<Option<String>>>,
+}
+
+tokio::task_local! {
+    static CURRENT_JOB: JobScope;
+}
+
+/// Runs `future` with `scope` as the owner of the permits it takes
+pub(crate) async fn scoped<F: Future>(scope: JobScope, future: F) -> F::Output {
+    CURRENT_JOB.scope(scope, future).await
+}
+
+#[derive(Debug)]
+struct Hold {
+    job: Option<JobScope>,
+    subsystem: Subsystem,
+    since: Instant,
+    /// Already reported, so a stuck permit is logged once
+    reported: bool,
+}
+
+/// Tracks held permits and permit waits
+#[derive(Debug, Default)]
+pub struct Watchdog {
+    config: WatchdogConfig,
+    next_id: AtomicU64,
+    holds: Mutex<HashMap<u64, Hold>>,
+    waits: Mutex<HashMap<Subsystem, LockWaitStats>>,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    /// Starts the periodic check; it ends when the watchdog is dropped
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn start(self: &Arc<Self>) {
+        let watchdog: Weak<Self> = Arc::downgrade(self);
+        let interval = self.config.check_interval;
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                let Some(watchdog) = watchdog.upgrade() else { break };
+                watchdog.check();
+            }
+        });
+    }
+
+    /// Records a permit acquired after waiting `waited`; dropping the returned hold releases it
+    pub(crate) fn hold(self: &Arc<Self>, subsystem: Subsystem, waited: Duration) -> HoldGuard {
+        {
+            let mut waits = lock(&self.waits);
+            let stats = waits.entry(subsystem).or_insert_with(|| LockWaitStats {
+                subsystem: subsystem_name(subsystem),
+                ..Default::default()
+            });
+            stats.acquisitions += 1;
+            stats.total_wait += waited;
+            stats.max_wait = stats.max_wait.max(waited);
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let job = CURRENT_JOB.try_with(JobScope::clone).ok();
+        lock(&self.holds).insert(id, Hold { job, subsystem, since: Instant::now(), reported: false });
+        HoldGuard { watchdog: self.clone(), id }
+    }
+
+    /// Logs permits held past the threshold and fails their jobs if configured to
+    ///
+    /// Returns every permit currently past the threshold.
+    pub fn check(&self) -> Vec<StuckPermit> {
+        let mut stuck = Vec::new();
+        for hold in lock(&self.holds).values_mut() {
+            let held_for = hold.since.elapsed();
+            if held_for < self.config.hold_threshold {
+                continue;
+            }
+            let permit = StuckPermit {
+                job_id: hold.job.as_ref().map(|job| job.job_id.clone()),
+                subsystem: subsystem_name(hold.subsystem),
+                held_for,
+            };
+            if !hold.reported {
+                hold.reported = true;
+                log::warn!(
+                    "{} permit held for {:?} by {}",
+                    permit.subsystem,
+                    held_for,
+                    permit.job_id.as_deref().unwrap_or("an unscoped task"),
+                );
+                if let (StuckAction::FailJob, Some(job)) = (self.config.action, &hold.job) {
+                    job.fail.send_replace(Some(format!(
+                        "{} permit held for {:?}, over the {:?} watchdog threshold",
+                        permit.subsystem, held_for, self.config.hold_threshold,
+                    )));
+                }
+            }
+            stuck.push(permit);
+        }
+        stuck
+    }
+
+    /// Permits held past the threshold, without acting on them
+    pub fn stuck(&self) -> Vec<StuckPermit> {
+        lock(&self.holds).values()
+            .filter(|hold| hold.since.elapsed() >= self.config.hold_threshold)
+            .map(|hold| StuckPermit {
+                job_id: hold.job.as_ref().map(|job| job.job_id.clone()),
+                subsystem: subsystem_name(hold.subsystem),
+                held_for: hold.since.elapsed(),
+            })
+            .collect()
+    }
+
+    /// Permits currently held
+    pub fn held(&self) -> usize {
+        lock(&self.holds).len()
+    }
+
+    /// Permit wait statistics per subsystem
+    pub fn lock_waits(&self) -> Vec<LockWaitStats> {
+        let mut waits: Vec<LockWaitStats> = lock(&self.waits).values().cloned().collect();
+        waits.sort_by(|a, b| a.subsystem.cmp(&b.subsystem));
+        waits
+    }
+}
+
+/// Registration of one held permit
+#[derive(Debug)]
+pub(crate) struct HoldGuard {
+    watchdog: Arc<Watchdog>,
+    id: u64,
+}
+
+impl Drop for HoldGuard {
+    fn drop(&mut self) {
+        lock(&self.watchdog.holds).remove(&self.id);
+    }
+}
+
+fn subsystem_name(subsystem: Subsystem) -> String {
+    format!("{:?}", subsystem).to_lowercase()
+}
+
+/// Locks `mutex`, recovering from poisoning; the guarded maps stay consistent
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{concurrency::{ConcurrencyConfig, ConcurrencyLimiter}, shutdown::JobTracker, PdfError};
+
+    fn limiter(action: StuckAction) -> (ConcurrencyLimiter, Arc<Watchdog>) {
+        let watchdog = Arc::new(Watchdog::new(WatchdogConfig {
+            hold_threshold: Duration::from_millis(20),
+            check_interval: Duration::from_millis(5),
+            action,
+        }));
+        let limiter = ConcurrencyLimiter::new(&ConcurrencyConfig::default()).unwrap().with_watchdog(watchdog.clone());
+        (limiter, watchdog)
+    }
+
+    #[tokio::test]
+    async fn test_stuck_permit_reports_owner() {
+        let (limiter, watchdog) = limiter(StuckAction::Log);
+        let tracker = Arc::new(JobTracker::new());
+        let mut job = tracker.begin().unwrap();
+        job.label("job-1");
+
+        job.run(async {
+            let _permit = limiter.acquire(Subsystem::Clean).await?;
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            let stuck = watchdog.check();
+            assert_eq!(stuck.len(), 1);
+            assert_eq!(stuck[0].job_id.as_deref(), Some("job-1"));
+            assert_eq!(stuck[0].subsystem, "clean");
+            Ok::<_, PdfError>(())
+        }).await.unwrap();
+
+        assert_eq!(watchdog.held(), 0);
+        assert_eq!(watchdog.lock_waits()[0].acquisitions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_fail_job_releases_permit() {
+        let (limiter, watchdog) = limiter(StuckAction::FailJob);
+        watchdog.start();
+        let tracker = Arc::new(JobTracker::new());
+        let mut job = tracker.begin().unwrap();
+        job.label("hung");
+
+        let result = job.run(async {
+            let _permit = limiter.acquire(Subsystem::Write).await?;
+            std::future::pending::<Result<(), PdfError>>().await
+        }).await;
+
+        assert!(matches!(result, Err(PdfError::Stalled(_))));
+        assert_eq!(watchdog.held(), 0);
+    }
+}
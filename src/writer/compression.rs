@@ -1,10 +1,11 @@
 use crate::{metrics::MetricsRegistry, PdfError, WriterConfig};
 use chrono::{DateTime, Utc};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     sync::{Arc, RwLock},
 };
-use lopdf::{Document, Object, Stream, Dictionary};
+use lopdf::{Document, Object, ObjectId, Stream, Dictionary};
+use std::collections::HashSet;
 use zstd::bulk::{Compressor, Decompressor};
 
 pub struct CompressionSystem {
@@ -18,6 +19,8 @@ struct CompressionState {
     last_compression: Option<DateTime<Utc>>,
     active_compressions: u32,
     compression_stats: HashMap<String, CompressionStats>,
+    kind_stats: HashMap<StreamKind, StreamKindStats>,
+    merge_stats: MergeStats,
 }
 
 #[derive(Clone)]
@@ -26,6 +29,119 @@ pub struct CompressionConfig {
     pub stream_threshold: usize,
     pub enable_adaptive: bool,
     pub cache_compressed: bool,
+    /// How each kind of stream is compressed
+    pub strategy: CompressionStrategy,
+    /// Permit the non-standard `/BrotliDecode` filter for embedded files.
+    /// Most readers cannot decode it, so it is off by default and only
+    /// available with the `brotli` feature.
+    pub allow_brotli: bool,
+    /// Page content arrays whose streams are all smaller than this are
+    /// merged into one stream before compression; 0 disables merging.
+    /// Encrypted documents are never merged, since each stream is
+    /// encrypted with a key of its own object number.
+    ///
+    /// Each tiny stream compressed on its own pays the zlib header and
+    /// checksum and starts with an empty window, so a page drawn from
+    /// thousands of similar fragments barely shrinks. Flate preset
+    /// dictionaries would share the window across streams, but PDF
+    /// readers do not support them, and lopdf cannot write the object
+    /// streams that would pack them either; concatenating the fragments
+    /// lets them compress as one and also drops their object overhead.
+    pub merge_contents_below: usize,
+}
+
+/// Stream categories that warrant different compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamKind {
+    /// Page content streams and form XObjects
+    Content,
+    Image,
+    Font,
+    EmbeddedFile,
+    /// XMP metadata, kept readable by non-PDF tools
+    Metadata,
+    Other,
+}
+
+/// What to do with a stream of a given kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamCompression {
+    /// Leave the stream as it is
+    Keep,
+    /// FlateDecode at the given zlib level (0-9)
+    Flate(u32),
+    /// BrotliDecode at the given quality (0-11), embedded files only
+    Brotli(u32),
+}
+
+/// Per-kind compression choices
+#[derive(Debug, Clone)]
+pub struct CompressionStrategy {
+    pub content: StreamCompression,
+    pub image: StreamCompression,
+    pub font: StreamCompression,
+    pub embedded_file: StreamCompression,
+    pub metadata: StreamCompression,
+    pub other: StreamCompression,
+}
+
+impl CompressionStrategy {
+    pub fn for_kind(&self, kind: StreamKind) -> StreamCompression {
+        match kind {
+            StreamKind::Content => self.content,
+            StreamKind::Image => self.image,
+            StreamKind::Font => self.font,
+            StreamKind::EmbeddedFile => self.embedded_file,
+            StreamKind::Metadata => self.metadata,
+            StreamKind::Other => self.other,
+        }
+    }
+}
+
+impl Default for CompressionStrategy {
+    fn default() -> Self {
+        Self {
+            content: StreamCompression::Flate(9),
+            // Raw images still benefit from Flate; already-encoded ones are never touched
+            image: StreamCompression::Flate(6),
+            font: StreamCompression::Flate(9),
+            embedded_file: StreamCompression::Flate(6),
+            metadata: StreamCompression::Keep,
+            other: StreamCompression::Flate(6),
+        }
+    }
+}
+
+/// Measurements for one stream kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamKindStats {
+    pub streams: u64,
+    /// Streams left unchanged (kept, already encoded, or no smaller)
+    pub unchanged: u64,
+    pub original_bytes: u64,
+    pub compressed_bytes: u64,
+}
+
+/// Measurements of merged page content streams
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    /// Pages whose content streams were merged
+    pub pages: u64,
+    /// Content streams merged into others
+    pub streams: u64,
+    /// Encoded size of those streams as they were
+    pub original_bytes: u64,
+    /// Encoded size of the streams that replaced them
+    pub merged_bytes: u64,
+}
+
+/// Content streams of one page merged into one
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ContentMerge {
+    stream_id: ObjectId,
+    /// Streams folded into `stream_id` and removed
+    merged: Vec<ObjectId>,
+    original_bytes: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -46,9 +162,10 @@ struct CompressionStats {
 
 #[derive(Debug, Clone, Copy)]
 enum CompressionAlgorithm {
-    Deflate,
+    None,
+    Flate,
+    Brotli,
     Zstd,
-    Lzw,
 }
 
 impl CompressionSystem {
@@ -62,6 +179,8 @@ impl CompressionSystem {
                 last_compression: None,
                 active_compressions: 0,
                 compression_stats: HashMap::new(),
+                kind_stats: HashMap::new(),
+                merge_stats: MergeStats::default(),
             })),
             config: CompressionConfig::default(),
             metrics,
@@ -69,6 +188,20 @@ impl CompressionSystem {
     }
 
     pub async fn compress_document(&self, doc: &Document) -> Result<Vec<u8>, PdfError> {
+        let mut buffer = Vec::new();
+        self.compress_document_into(doc, &mut buffer).await?;
+        Ok(buffer)
+    }
+
+    /// Compresses the document and serializes it into `output`
+    ///
+    /// Returns each content stream merged away and the stream it was
+    /// merged into.
+    pub async fn compress_document_into<W: std::io::Write>(
+        &self,
+        doc: &Document,
+        output: &mut W,
+    ) -> Result<BTreeMap<ObjectId, ObjectId>, PdfError> {
         let start_time = std::time::Instant::now();
         let mut compressed_doc = doc.clone();
 
@@ -79,26 +212,34 @@ impl CompressionSystem {
             state.active_compressions += 1;
         }
 
-        // Process all streams in the document
-        for (id, object) in doc.objects.iter() {
-            if let Ok(mut stream) = self.extract_stream(object) {
-                let original_size = stream.content.len();
-                
-                // Compress stream content
-                stream.content = self.compress_stream(&stream.content, &stream.dict)?;
-                
-                // Update compression stats
-                let compressed_size = stream.content.len();
-                self.update_compression_stats(id, original_size, compressed_size)?;
-                
-                // Update the stream in the document
-                compressed_doc.objects.insert(*id, Object::Stream(stream));
+        let merges = match self.config.merge_contents_below {
+            0 => Vec::new(),
+            _ if compressed_doc.trailer.has(b"Encrypt") => Vec::new(),
+            limit => merge_small_contents(&mut compressed_doc, limit),
+        };
+
+        // Process all streams in the document, each according to its kind
+        let content_ids = page_content_ids(&compressed_doc);
+        let streams: Vec<(ObjectId, Stream)> = compressed_doc.objects.iter()
+            .filter_map(|(id, object)| self.extract_stream(object).ok().map(|stream| (*id, stream)))
+            .collect();
+        for (id, mut stream) in streams {
+            let kind = classify_stream(id, &stream.dict, &content_ids);
+            let original_size = stream.content.len();
+
+            let algorithm = self.compress_stream(&mut stream, kind)?;
+
+            let compressed_size = stream.content.len();
+            self.update_compression_stats(id, kind, algorithm, original_size, compressed_size)?;
+
+            if !matches!(algorithm, CompressionAlgorithm::None) {
+                compressed_doc.objects.insert(id, Object::Stream(stream));
             }
         }
+        self.update_merge_stats(&compressed_doc, &merges)?;
 
-        // Compress the entire document
-        let mut buffer = Vec::new();
-        compressed_doc.save_to(&mut buffer)
+        // Serialize the compressed document
+        compressed_doc.save_to(output)
             .map_err(|e| PdfError::Compression(format!("Failed to save compressed document: {}", e)))?;
 
         // Update metrics and state
@@ -115,7 +256,9 @@ impl CompressionSystem {
 
         self.metrics.compression_time.observe(start_time.elapsed().as_secs_f64());
 
-        Ok(buffer)
+        Ok(merges.iter()
+            .flat_map(|merge| merge.merged.iter().map(|&id| (id, merge.stream_id)))
+            .collect())
     }
 
     fn extract_stream(&self, object: &Object) -> Result<Stream, PdfError> {
@@ -125,33 +268,107 @@ impl CompressionSystem {
         }
     }
 
-    fn compress_stream(&self, content: &[u8], dict: &Dictionary) -> Result<Vec<u8>, PdfError> {
-        if content.len() < self.config.stream_threshold {
-            return Ok(content.to_vec());
+    /// Compresses one stream in place following the strategy for its kind
+    fn compress_stream(&self, stream: &mut Stream, kind: StreamKind) -> Result<CompressionAlgorithm, PdfError> {
+        if self.config.default_level == CompressionLevel::None
+            || stream.content.len() < self.config.stream_threshold
+        {
+            return Ok(CompressionAlgorithm::None);
         }
 
-        let algorithm = self.select_compression_algorithm(content, dict);
-        match algorithm {
-            CompressionAlgorithm::Zstd => self.compress_with_zstd(content),
-            CompressionAlgorithm::Deflate => self.compress_with_deflate(content),
-            CompressionAlgorithm::Lzw => self.compress_with_lzw(content),
+        // Only plain or Flate-only streams are recompressed; anything else
+        // (DCT, JPX, JBIG2, predictors...) is already in its final form.
+        let raw = match stream.dict.get(b"Filter") {
+            Err(_) => stream.content.clone(),
+            Ok(filter) if is_plain_flate(filter) && !stream.dict.has(b"DecodeParms") => {
+                match stream.decompressed_content() {
+                    Ok(raw) => raw,
+                    Err(_) => return Ok(CompressionAlgorithm::None),
+                }
+            }
+            Ok(_) => return Ok(CompressionAlgorithm::None),
+        };
+
+        let (encoded, filter, algorithm) = match self.config.strategy.for_kind(kind) {
+            StreamCompression::Keep => return Ok(CompressionAlgorithm::None),
+            StreamCompression::Brotli(quality) if kind == StreamKind::EmbeddedFile && self.config.allow_brotli => {
+                match self.compress_with_brotli(&raw, quality)? {
+                    Some(encoded) => (encoded, "BrotliDecode", CompressionAlgorithm::Brotli),
+                    None => (self.compress_with_flate(&raw, 9)?, "FlateDecode", CompressionAlgorithm::Flate),
+                }
+            }
+            StreamCompression::Brotli(_) => (self.compress_with_flate(&raw, 9)?, "FlateDecode", CompressionAlgorithm::Flate),
+            StreamCompression::Flate(level) => (self.compress_with_flate(&raw, level)?, "FlateDecode", CompressionAlgorithm::Flate),
+        };
+
+        if encoded.len() >= stream.content.len() {
+            return Ok(CompressionAlgorithm::None);
         }
+
+        stream.dict.set("Filter", Object::Name(filter.as_bytes().to_vec()));
+        stream.dict.remove(b"DecodeParms");
+        stream.set_content(encoded);
+        Ok(algorithm)
     }
 
-    fn select_compression_algorithm(&self, content: &[u8], dict: &Dictionary) -> CompressionAlgorithm {
-        // Check if content is already compressed
-        if dict.get("Filter").is_some() {
-            return CompressionAlgorithm::Deflate;
-        }
+    fn compress_with_flate(&self, data: &[u8], level: u32) -> Result<Vec<u8>, PdfError> {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level.min(9)));
+        encoder.write_all(data)
+            .map_err(|e| PdfError::Compression(format!("Flate compression failed: {}", e)))?;
+        encoder.finish()
+            .map_err(|e| PdfError::Compression(format!("Flate finalization failed: {}", e)))
+    }
 
-        // Select algorithm based on content type and size
-        if content.len() > 1_000_000 {
-            CompressionAlgorithm::Zstd
-        } else if content.len() > 100_000 {
-            CompressionAlgorithm::Deflate
-        } else {
-            CompressionAlgorithm::Lzw
+    #[cfg(feature = "brotli")]
+    fn compress_with_brotli(&self, data: &[u8], quality: u32) -> Result<Option<Vec<u8>>, PdfError> {
+        use std::io::Write;
+
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, quality.min(11), 22);
+        encoder.write_all(data)
+            .map_err(|e| PdfError::Compression(format!("Brotli compression failed: {}", e)))?;
+        Ok(Some(encoder.into_inner()))
+    }
+
+    #[cfg(not(feature = "brotli"))]
+    fn compress_with_brotli(&self, _data: &[u8], _quality: u32) -> Result<Option<Vec<u8>>, PdfError> {
+        Ok(None)
+    }
+
+    /// Per-kind measurements accumulated across documents
+    pub fn stream_stats(&self) -> HashMap<StreamKind, StreamKindStats> {
+        self.state.read()
+            .map(|state| state.kind_stats.clone())
+            .unwrap_or_default()
+    }
+
+    /// Content stream merging measurements accumulated across documents
+    pub fn merge_stats(&self) -> MergeStats {
+        self.state.read()
+            .map(|state| state.merge_stats)
+            .unwrap_or_default()
+    }
+
+    fn update_merge_stats(&self, doc: &Document, merges: &[ContentMerge]) -> Result<(), PdfError> {
+        let mut state = self.state.write().map_err(|_|
+            PdfError::Compression("Failed to acquire state lock".to_string()))?;
+
+        for merge in merges {
+            let merged_bytes = doc.objects.get(&merge.stream_id)
+                .and_then(|object| object.as_stream().ok())
+                .map_or(0, |stream| stream.content.len());
+            let stats = &mut state.merge_stats;
+            stats.pages += 1;
+            stats.streams += merge.merged.len() as u64;
+            stats.original_bytes += merge.original_bytes as u64;
+            stats.merged_bytes += merged_bytes as u64;
+
+            self.metrics.content_streams_merged.inc_by(merge.merged.len() as f64);
+            self.metrics.content_merge_savings.inc_by(merge.original_bytes.saturating_sub(merged_bytes) as f64);
         }
+        Ok(())
     }
 
     fn compress_with_zstd(&self, data: &[u8]) -> Result<Vec<u8>, PdfError> {
@@ -169,47 +386,32 @@ impl CompressionSystem {
             .map_err(|e| PdfError::Compression(format!("ZSTD compression failed: {}", e)))
     }
 
-    fn compress_with_deflate(&self, data: &[u8]) -> Result<Vec<u8>, PdfError> {
-        use flate2::{write::DeflateEncoder, Compression};
-        use std::io::Write;
-
-        let level = match self.config.default_level {
-            CompressionLevel::Fast => Compression::fast(),
-            CompressionLevel::Default => Compression::default(),
-            CompressionLevel::Maximum => Compression::best(),
-            CompressionLevel::None => return Ok(data.to_vec()),
-        };
-
-        let mut encoder = DeflateEncoder::new(Vec::new(), level);
-        encoder.write_all(data)
-            .map_err(|e| PdfError::Compression(format!("Deflate compression failed: {}", e)))?;
-        
-        encoder.finish()
-            .map_err(|e| PdfError::Compression(format!("Deflate finalization failed: {}", e)))
-    }
-
-    fn compress_with_lzw(&self, data: &[u8]) -> Result<Vec<u8>, PdfError> {
-        // Simplified LZW implementation for example
-        // In production, use a proper LZW implementation
-        Ok(data.to_vec())
-    }
-
     fn update_compression_stats(
         &self,
         id: ObjectId,
+        kind: StreamKind,
+        algorithm: CompressionAlgorithm,
         original_size: usize,
         compressed_size: usize,
     ) -> Result<(), PdfError> {
         let mut state = self.state.write().map_err(|_| 
             PdfError::Compression("Failed to acquire state lock".to_string()))?;
 
+        let stats = state.kind_stats.entry(kind).or_default();
+        stats.streams += 1;
+        stats.original_bytes += original_size as u64;
+        stats.compressed_bytes += compressed_size as u64;
+        if matches!(algorithm, CompressionAlgorithm::None) {
+            stats.unchanged += 1;
+        }
+
         state.compression_stats.insert(
-            id.to_string(),
+            format!("{} {}", id.0, id.1),
             CompressionStats {
                 original_size: original_size as u64,
                 compressed_size: compressed_size as u64,
                 timestamp: Utc::parse_from_str("2025-06-02 18:42:15", "%Y-%m-%d %H:%M:%S").unwrap(),
-                algorithm: CompressionAlgorithm::Zstd,
+                algorithm,
             },
         );
 
@@ -224,10 +426,124 @@ impl Default for CompressionConfig {
             stream_threshold: 1024, // Only compress streams larger than 1KB
             enable_adaptive: true,
             cache_compressed: true,
+            strategy: CompressionStrategy::default(),
+            allow_brotli: false,
+            merge_contents_below: 4096,
         }
     }
 }
 
+/// Streams referenced from a page's `/Contents`
+fn page_content_ids(doc: &Document) -> HashSet<ObjectId> {
+    doc.get_pages()
+        .into_values()
+        .flat_map(|page_id| doc.get_page_contents(page_id))
+        .collect()
+}
+
+/// Merges each page's content array of small streams into one stream
+///
+/// Only streams referenced from that one array, without filters other
+/// than plain Flate, are merged; a newline between them keeps operators
+/// from running together, which is all the concatenation rule asks.
+fn merge_small_contents(doc: &mut Document, limit: usize) -> Vec<ContentMerge> {
+    let mut references: HashMap<ObjectId, usize> = HashMap::new();
+    count_references(&Object::Dictionary(doc.trailer.clone()), &mut references);
+    for object in doc.objects.values() {
+        count_references(object, &mut references);
+    }
+
+    let mut merges = Vec::new();
+    for page_id in doc.get_pages().into_values() {
+        let parts: Vec<ObjectId> = match doc.get_dictionary(page_id).and_then(|page| page.get(b"Contents")) {
+            Ok(Object::Array(parts)) if parts.len() > 1 => match parts.iter().map(Object::as_reference).collect::<Result<Vec<_>, _>>() {
+                Ok(parts) => parts,
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+
+        let Some((merged, original_bytes)) = merged_contents(doc, &parts, &references, limit) else { continue };
+        let stream_id = parts[0];
+        for id in &parts[1..] {
+            doc.objects.remove(id);
+        }
+        doc.objects.insert(stream_id, Object::Stream(Stream::new(Dictionary::new(), merged)));
+        if let Ok(page) = doc.get_dictionary_mut(page_id) {
+            page.set("Contents", Object::Reference(stream_id));
+        }
+        merges.push(ContentMerge { stream_id, merged: parts[1..].to_vec(), original_bytes });
+    }
+    merges
+}
+
+/// Decoded concatenation of `parts` and their encoded size, if every part qualifies
+fn merged_contents(
+    doc: &Document,
+    parts: &[ObjectId],
+    references: &HashMap<ObjectId, usize>,
+    limit: usize,
+) -> Option<(Vec<u8>, usize)> {
+    let mut merged = Vec::new();
+    let mut original_bytes = 0;
+    for id in parts {
+        let stream = doc.objects.get(id)?.as_stream().ok()?;
+        if references.get(id) != Some(&1) || stream.content.len() >= limit {
+            return None;
+        }
+        let decoded = match stream.dict.get(b"Filter") {
+            Err(_) => stream.content.clone(),
+            Ok(filter) if is_plain_flate(filter) && !stream.dict.has(b"DecodeParms") => {
+                stream.decompressed_content().ok()?
+            }
+            Ok(_) => return None,
+        };
+        if !merged.is_empty() {
+            merged.push(b'\n');
+        }
+        merged.extend_from_slice(&decoded);
+        original_bytes += stream.content.len();
+    }
+    Some((merged, original_bytes))
+}
+
+fn count_references(object: &Object, counts: &mut HashMap<ObjectId, usize>) {
+    match object {
+        Object::Reference(id) => *counts.entry(*id).or_default() += 1,
+        Object::Array(items) => items.iter().for_each(|item| count_references(item, counts)),
+        Object::Dictionary(dict) => dict.iter().for_each(|(_, value)| count_references(value, counts)),
+        Object::Stream(stream) => stream.dict.iter().for_each(|(_, value)| count_references(value, counts)),
+        _ => {}
+    }
+}
+
+/// Classifies a stream from its dictionary and where it is used
+fn classify_stream(id: ObjectId, dict: &Dictionary, content_ids: &HashSet<ObjectId>) -> StreamKind {
+    if content_ids.contains(&id) {
+        return StreamKind::Content;
+    }
+
+    let name = |key: &[u8]| dict.get(key).and_then(Object::as_name).ok();
+    match (name(b"Type"), name(b"Subtype")) {
+        (_, Some(b"Image")) => StreamKind::Image,
+        (_, Some(b"Form")) => StreamKind::Content,
+        (Some(b"EmbeddedFile"), _) => StreamKind::EmbeddedFile,
+        (Some(b"Metadata"), _) => StreamKind::Metadata,
+        (_, Some(b"Type1C")) | (_, Some(b"CIDFontType0C")) | (_, Some(b"OpenType")) => StreamKind::Font,
+        _ if dict.has(b"Length1") || dict.has(b"Length2") || dict.has(b"Length3") => StreamKind::Font,
+        _ => StreamKind::Other,
+    }
+}
+
+/// True for `/FlateDecode` alone, directly or as a one-element array
+fn is_plain_flate(filter: &Object) -> bool {
+    match filter {
+        Object::Name(name) => name == b"FlateDecode",
+        Object::Array(filters) => filters.len() == 1 && is_plain_flate(&filters[0]),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -267,5 +583,126 @@ mod tests {
         
         let result = system.compress_document(&doc).await;
         assert!(result.is_ok());
+
+        let stats = system.stream_stats()[&StreamKind::Other];
+        assert_eq!(stats.streams, 1);
+        assert_eq!(stats.original_bytes, 10000);
+        assert!(stats.compressed_bytes < stats.original_bytes);
+    }
+
+    fn system_with(config: CompressionConfig) -> CompressionSystem {
+        CompressionSystem {
+            state: Arc::new(RwLock::new(CompressionState {
+                compressions_performed: 0,
+                last_compression: None,
+                active_compressions: 0,
+                compression_stats: HashMap::new(),
+                kind_stats: HashMap::new(),
+                merge_stats: MergeStats::default(),
+            })),
+            config,
+            metrics: Arc::new(MetricsRegistry::new().unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_stream_classification() {
+        let content_ids: HashSet<ObjectId> = [(1, 0)].into_iter().collect();
+        let image = lopdf::dictionary! { "Subtype" => "Image" };
+        let font = lopdf::dictionary! { "Length1" => 1000 };
+        let xmp = lopdf::dictionary! { "Type" => "Metadata", "Subtype" => "XML" };
+
+        assert_eq!(classify_stream((1, 0), &Dictionary::new(), &content_ids), StreamKind::Content);
+        assert_eq!(classify_stream((2, 0), &image, &content_ids), StreamKind::Image);
+        assert_eq!(classify_stream((3, 0), &font, &content_ids), StreamKind::Font);
+        assert_eq!(classify_stream((4, 0), &xmp, &content_ids), StreamKind::Metadata);
+    }
+
+    #[test]
+    fn test_encoded_images_left_alone() {
+        let system = system_with(CompressionConfig::default());
+        let mut jpeg = Stream::new(
+            lopdf::dictionary! { "Subtype" => "Image", "Filter" => "DCTDecode" },
+            vec![0u8; 4096],
+        );
+
+        let algorithm = system.compress_stream(&mut jpeg, StreamKind::Image).unwrap();
+        assert!(matches!(algorithm, CompressionAlgorithm::None));
+        assert_eq!(jpeg.content.len(), 4096);
+    }
+
+    #[test]
+    fn test_content_stream_flate_round_trip() {
+        let system = system_with(CompressionConfig::default());
+        let original = b"BT /F1 12 Tf 72 720 Td (Hello) Tj ET\n".repeat(100);
+        let mut stream = Stream::new(Dictionary::new(), original.clone());
+
+        let algorithm = system.compress_stream(&mut stream, StreamKind::Content).unwrap();
+        assert!(matches!(algorithm, CompressionAlgorithm::Flate));
+        assert_eq!(stream.dict.get(b"Filter").unwrap().as_name().unwrap(), b"FlateDecode");
+        assert_eq!(stream.decompressed_content().unwrap(), original);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_metadata_kept_readable() {
+        let system = system_with(CompressionConfig::default());
+        let mut stream = Stream::new(Dictionary::new(), vec![b'x'; 4096]);
+
+        let algorithm = system.compress_stream(&mut stream, StreamKind::Metadata).unwrap();
+        assert!(matches!(algorithm, CompressionAlgorithm::None));
+        assert!(!stream.dict.has(b"Filter"));
+    }
+
+    #[test]
+    fn test_brotli_falls_back_to_flate_when_disallowed() {
+        let config = CompressionConfig {
+            strategy: CompressionStrategy {
+                embedded_file: StreamCompression::Brotli(11),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let system = system_with(config);
+        let mut stream = Stream::new(Dictionary::new(), vec![b'a'; 4096]);
+
+        system.compress_stream(&mut stream, StreamKind::EmbeddedFile).unwrap();
+        assert_eq!(stream.dict.get(b"Filter").unwrap().as_name().unwrap(), b"FlateDecode");
+    }
+
+    #[tokio::test]
+    async fn test_small_content_streams_merged() {
+        let system = system_with(CompressionConfig::default());
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let parts: Vec<Object> = (0..200)
+            .map(|i| {
+                let fragment = format!("q 1 0 0 1 {} 0 cm /Im1 Do Q", i).into_bytes();
+                doc.add_object(Stream::new(Dictionary::new(), fragment)).into()
+            })
+            .collect();
+        let page = doc.add_object(lopdf::dictionary! { "Type" => "Page", "Parent" => pages_id, "Contents" => parts });
+        doc.objects.insert(pages_id, Object::Dictionary(lopdf::dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page.into()],
+            "Count" => 1,
+        }));
+        let catalog = doc.add_object(lopdf::dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+
+        let output = Document::load_mem(&system.compress_document(&doc).await.unwrap()).unwrap();
+        let contents = output.get_page_contents(page);
+        assert_eq!(contents.len(), 1);
+        let expected: Vec<String> = (0..200).map(|i| format!("q 1 0 0 1 {} 0 cm /Im1 Do Q", i)).collect();
+        let merged = output.get_object(contents[0]).unwrap().as_stream().unwrap().decompressed_content().unwrap();
+        assert_eq!(merged, expected.join("\n").into_bytes());
+
+        let stats = system.merge_stats();
+        assert_eq!((stats.pages, stats.streams), (1, 199));
+        assert!(stats.merged_bytes * 4 < stats.original_bytes, "{:?}", stats);
+
+        // Encrypted streams cannot be concatenated
+        doc.trailer.set("Encrypt", lopdf::dictionary! { "Filter" => "Standard" });
+        system.compress_document(&doc).await.unwrap();
+        assert_eq!(system.merge_stats().streams, 199);
+    }
+}
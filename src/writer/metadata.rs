@@ -1,4 +1,5 @@
 use crate::{PdfError, WriterConfig};
+use crate::pdf_date::PdfDate;
 use chrono::{DateTime, Utc};
 use lopdf::{Document, Dictionary, Object, ObjectId};
 use serde::{Deserialize, Serialize};
@@ -208,12 +209,8 @@ impl MetadataSystem {
         dict.set("Producer", Object::string(
             metadata.producer.as_deref().unwrap_or("PDF Engine 1.0")
         ));
-        dict.set("CreationDate", Object::string(
-            metadata.creation_date
-                .unwrap_or(current_time)
-                .to_rfc3339()
-        ));
-        dict.set("ModDate", Object::string(current_time.to_rfc3339()));
+        dict.set("CreationDate", PdfDate::from_datetime(&metadata.creation_date.unwrap_or(current_time)).to_object());
+        dict.set("ModDate", PdfDate::from_datetime(&current_time).to_object());
 
         if let Some(trapped) = &metadata.trapped {
             dict.set("Trapped", Object::string(trapped));
@@ -1,14 +1,29 @@
-use crate::{metrics::MetricsRegistry, EngineConfig, PdfError};
+use crate::{
+    antiforensics::{
+        analyzer::xref_integrity,
+        cleaner::timestamps::{self, FileTimestamps, TimestampPolicy},
+        hash::{DocumentHashes, HashAlgorithm, HashingWriter},
+        report::ObjectMap,
+    },
+    metrics::MetricsRegistry,
+    spill::{SpillBuffer, SpillConfig},
+    EngineConfig,
+    PdfError,
+};
 use chrono::{DateTime, Utc};
 use std::{
     collections::HashMap,
+    io::{BufWriter, Write},
+    path::Path,
     sync::{Arc, RwLock},
 };
-use lopdf::{Document, Object, ObjectId, Stream, Dictionary};
+use lopdf::{Document, Object, Stream, Dictionary};
 
 pub mod compression;
 pub mod metadata;
 pub mod optimization;
+pub mod permissions;
+pub mod roundtrip;
 pub mod stream;
 pub mod xref;
 pub mod validation;
@@ -37,6 +52,12 @@ pub struct WriterConfig {
     pub buffer_size: usize,
     pub max_concurrent_writers: usize,
     pub enable_incremental_update: bool,
+    /// Serialized output above this size is staged on disk
+    pub spill: SpillConfig,
+    /// Access/modification times given to saved files
+    pub timestamps: TimestampPolicy,
+    /// Algorithms computed while saving to disk
+    pub hash_algorithms: Vec<HashAlgorithm>,
 }
 
 #[derive(Debug)]
@@ -44,7 +65,11 @@ pub struct WriteOptions {
     pub compress: bool,
     pub optimize: bool,
     pub validate: bool,
+    /// Parse the written bytes back before returning them
+    pub verify_roundtrip: bool,
     pub update_metadata: bool,
+    /// Times of the input, copied under `TimestampPolicy::CopyFromInput`
+    pub source_times: Option<FileTimestamps>,
 }
 
 #[derive(Debug)]
@@ -53,6 +78,15 @@ pub struct WriteResult {
     pub bytes_written: usize,
     pub compression_ratio: f64,
     pub processing_time: std::time::Duration,
+    /// Hashes of the written output, computed while saving to disk
+    pub hashes: Option<DocumentHashes>,
+    /// Duplicate objects merged by optimization
+    pub duplicates_removed: usize,
+    /// Approximate bytes saved by merging duplicate objects
+    pub dedup_bytes_saved: usize,
+    /// Objects of the input and what optimization made of them, for
+    /// translating object IDs into the written document
+    pub object_map: ObjectMap,
 }
 
 impl WriterSystem {
@@ -60,7 +94,13 @@ impl WriterSystem {
         engine_config: &EngineConfig,
         metrics: Arc<MetricsRegistry>,
     ) -> Result<Self, PdfError> {
-        let config = WriterConfig::default();
+        let config = WriterConfig {
+            max_concurrent_writers: engine_config.concurrency.limits().write,
+            spill: engine_config.spill.clone(),
+            timestamps: engine_config.output_timestamps.clone(),
+            hash_algorithms: engine_config.hash_algorithms.clone(),
+            ..WriterConfig::default()
+        };
 
         let compression = Arc::new(compression::CompressionSystem::new(
             &config,
@@ -97,8 +137,68 @@ impl WriterSystem {
         &self,
         data: &[u8],
         options: Option<WriteOptions>,
+    ) -> Result<WriteResult, PdfError> {
+        self.tracked_write(data, options, None).await
+    }
+
+    /// Writes the document to `path`, hashing the output as it is written
+    ///
+    /// The returned `WriteResult::hashes` is always populated, so callers
+    /// never need to re-read the saved file to fingerprint it.
+    pub async fn save_document(
+        &self,
+        data: &[u8],
+        path: &Path,
+        options: Option<WriteOptions>,
+    ) -> Result<WriteResult, PdfError> {
+        self.tracked_write(data, options, Some(path)).await
+    }
+
+    /// Writes `data` to `path` byte for byte, hashing it as it is written
+    ///
+    /// Encrypted and signed output is final: loading and saving it again
+    /// would rewrite the encrypted objects and drop a signature appended
+    /// after `%%EOF`. Nothing is optimized, compressed or validated.
+    pub async fn save_bytes(
+        &self,
+        data: &[u8],
+        path: &Path,
+        source_times: Option<FileTimestamps>,
     ) -> Result<WriteResult, PdfError> {
         let start_time = std::time::Instant::now();
+        let mut buffer = SpillBuffer::new(&self.config.spill);
+        buffer.write_all(data)?;
+
+        let document_id = uuid::Uuid::new_v4().to_string();
+        let hashes = self.persist_with_hashes(buffer, path, &document_id, source_times).await?;
+        {
+            let mut state = self.state.write().map_err(|_|
+                PdfError::Processing("Failed to acquire state lock".to_string()))?;
+            state.documents_written += 1;
+            state.bytes_written += data.len() as u64;
+            state.last_write = Some(Utc::now());
+        }
+        self.metrics.bytes_processed.inc_by(data.len() as f64);
+
+        Ok(WriteResult {
+            document_id,
+            bytes_written: data.len(),
+            compression_ratio: 1.0,
+            processing_time: start_time.elapsed(),
+            hashes: Some(hashes),
+            duplicates_removed: 0,
+            dedup_bytes_saved: 0,
+            // No object is touched, so there is nothing to map
+            object_map: ObjectMap::default(),
+        })
+    }
+
+    async fn tracked_write(
+        &self,
+        data: &[u8],
+        options: Option<WriteOptions>,
+        path: Option<&Path>,
+    ) -> Result<WriteResult, PdfError> {
         let options = options.unwrap_or_default();
 
         // Update state
@@ -108,7 +208,7 @@ impl WriterSystem {
             state.active_writers += 1;
         }
 
-        let result = self.internal_write_document(data, &options).await;
+        let result = self.internal_write_document(data, &options, path).await;
 
         // Update metrics and state
         {
@@ -131,9 +231,10 @@ impl WriterSystem {
         &self,
         data: &[u8],
         options: &WriteOptions,
+        path: Option<&Path>,
     ) -> Result<WriteResult, PdfError> {
         let start_time = std::time::Instant::now();
-        let mut doc = Document::load_mem(data)
+        let mut doc = xref_integrity::load_checked(data)
             .map_err(|e| PdfError::Processing(format!("Failed to load PDF: {}", e)))?;
 
         // Validate document if required
@@ -142,8 +243,16 @@ impl WriterSystem {
         }
 
         // Optimize document if required
+        let mut optimization_report = optimization::OptimizationReport::default();
+        let mut object_map = ObjectMap::identity(&doc);
         if options.optimize {
-            doc = self.optimization.optimize_document(doc).await?;
+            let (optimized, report) = self.optimization.optimize_document_with_report(doc).await?;
+            doc = optimized;
+            // Merged duplicates are gone from the document, so follow the
+            // merges before marking what is missing as removed
+            object_map.merge(&report.dedup.merged);
+            object_map.drop_missing(&doc);
+            optimization_report = report;
         }
 
         // Update metadata if required
@@ -151,15 +260,20 @@ impl WriterSystem {
             self.update_document_metadata(&mut doc)?;
         }
 
-        // Compress document if required
-        let final_data = if options.compress {
-            self.compression.compress_document(&doc).await?
+        // Compress document if required; large output is staged on disk
+        let mut final_data = SpillBuffer::new(&self.config.spill);
+        if options.compress {
+            let merged = self.compression.compress_document_into(&doc, &mut final_data).await?;
+            object_map.merge(&merged);
         } else {
-            let mut buffer = Vec::new();
-            doc.save_to(&mut buffer)
+            doc.save_to(&mut final_data)
                 .map_err(|e| PdfError::Processing(format!("Failed to save PDF: {}", e)))?;
-            buffer
-        };
+        }
+        drop(doc);
+
+        if options.verify_roundtrip {
+            roundtrip::verify(&final_data.contents()?)?;
+        }
 
         let compression_ratio = if data.len() > 0 {
             final_data.len() as f64 / data.len() as f64
@@ -167,18 +281,60 @@ impl WriterSystem {
             1.0
         };
 
+        let document_id = uuid::Uuid::new_v4().to_string();
+        let bytes_written = final_data.len();
+        let hashes = match path {
+            Some(path) => Some(self.persist_with_hashes(final_data, path, &document_id, options.source_times).await?),
+            None => None,
+        };
+
         // Record metrics
         self.metrics.compression_ratio.observe(compression_ratio);
-        self.metrics.bytes_processed.inc_by(final_data.len() as f64);
+        self.metrics.bytes_processed.inc_by(bytes_written as f64);
 
         Ok(WriteResult {
-            document_id: uuid::Uuid::new_v4().to_string(),
-            bytes_written: final_data.len(),
+            document_id,
+            bytes_written,
             compression_ratio,
             processing_time: start_time.elapsed(),
+            hashes,
+            duplicates_removed: optimization_report.dedup.objects_removed,
+            dedup_bytes_saved: optimization_report.dedup.bytes_saved,
+            object_map,
         })
     }
 
+    /// Writes, hashes and syncs the output on the blocking pool, so a large
+    /// file does not stall the runtime's worker threads
+    async fn persist_with_hashes(
+        &self,
+        mut data: SpillBuffer,
+        path: &Path,
+        document_id: &str,
+        source_times: Option<FileTimestamps>,
+    ) -> Result<DocumentHashes, PdfError> {
+        let path = path.to_path_buf();
+        let document_id = document_id.to_string();
+        let buffer_size = self.config.buffer_size;
+        let algorithms = self.config.hash_algorithms.clone();
+        let timestamps = self.config.timestamps.clone();
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::create(&path)?;
+            let mut writer = HashingWriter::with_algorithms(BufWriter::with_capacity(buffer_size, file), &algorithms);
+
+            data.copy_to(&mut writer)?;
+
+            let (buffered, hashes) = writer.finalize(&document_id)?;
+            buffered.into_inner()
+                .map_err(|e| PdfError::Io(e.into_error()))?
+                .sync_all()?;
+            timestamps::apply(&path, &timestamps, source_times)?;
+            Ok::<_, PdfError>(hashes)
+        })
+        .await
+        .map_err(|e| PdfError::Processing(format!("Writing output failed: {}", e)))?
+    }
+
     fn update_document_metadata(&self, doc: &mut Document) -> Result<(), PdfError> {
         let info_dict = Dictionary::from_iter(vec![
             ("Producer", Object::string("PDF Engine 1.0")),
@@ -193,23 +349,41 @@ impl WriterSystem {
     }
 
     pub async fn optimize_document(&self, data: &[u8]) -> Result<Vec<u8>, PdfError> {
-        let doc = Document::load_mem(data)
+        self.optimize_document_mapped(data).await.map(|(buffer, _)| buffer)
+    }
+
+    /// Optimizes the document and maps its objects to the optimized ones
+    pub async fn optimize_document_mapped(&self, data: &[u8]) -> Result<(Vec<u8>, ObjectMap), PdfError> {
+        let doc = xref_integrity::load_checked(data)
             .map_err(|e| PdfError::Processing(format!("Failed to load PDF: {}", e)))?;
 
-        let optimized_doc = self.optimization.optimize_document(doc).await?;
+        let mut object_map = ObjectMap::identity(&doc);
+        let (optimized_doc, report) = self.optimization.optimize_document_with_report(doc).await?;
+        object_map.merge(&report.dedup.merged);
+        object_map.drop_missing(&optimized_doc);
 
         let mut buffer = Vec::new();
         optimized_doc.save_to(&mut buffer)
             .map_err(|e| PdfError::Processing(format!("Failed to save PDF: {}", e)))?;
 
-        Ok(buffer)
+        Ok((buffer, object_map))
     }
 
     pub async fn compress_document(&self, data: &[u8]) -> Result<Vec<u8>, PdfError> {
-        let doc = Document::load_mem(data)
+        self.compress_document_mapped(data).await.map(|(buffer, _)| buffer)
+    }
+
+    /// Compresses the document and maps its objects to the compressed ones
+    pub async fn compress_document_mapped(&self, data: &[u8]) -> Result<(Vec<u8>, ObjectMap), PdfError> {
+        let doc = xref_integrity::load_checked(data)
             .map_err(|e| PdfError::Processing(format!("Failed to load PDF: {}", e)))?;
 
-        self.compression.compress_document(&doc).await
+        let mut object_map = ObjectMap::identity(&doc);
+        let mut buffer = Vec::new();
+        let merged = self.compression.compress_document_into(&doc, &mut buffer).await?;
+        object_map.merge(&merged);
+
+        Ok((buffer, object_map))
     }
 }
 
@@ -219,8 +393,11 @@ impl Default for WriterConfig {
             compression_level: compression::CompressionLevel::Default,
             optimization_level: optimization::OptimizationLevel::Standard,
             buffer_size: 8 * 1024 * 1024, // 8MB
-            max_concurrent_writers: num_cpus::get(),
+            max_concurrent_writers: crate::concurrency::ConcurrencyConfig::default().limits().write,
             enable_incremental_update: true,
+            spill: SpillConfig::default(),
+            timestamps: TimestampPolicy::Untouched,
+            hash_algorithms: HashAlgorithm::DEFAULT.to_vec(),
         }
     }
 }
@@ -231,7 +408,9 @@ impl Default for WriteOptions {
             compress: true,
             optimize: true,
             validate: true,
+            verify_roundtrip: false,
             update_metadata: true,
+            source_times: None,
         }
     }
 }
@@ -259,6 +438,66 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_save_document_hashes_output() {
+        let config = EngineConfig::default();
+        let metrics = Arc::new(MetricsRegistry::new().unwrap());
+        let system = WriterSystem::new(&config, metrics).await.unwrap();
+
+        let sample_data = include_bytes!("../../tests/data/sample.pdf");
+        let output = tempfile::NamedTempFile::new().unwrap();
+        let result = system.save_document(sample_data, output.path(), None).await.unwrap();
+
+        let hashes = result.hashes.expect("save_document always hashes output");
+        let reread = crate::antiforensics::hash::hash_file(output.path(), 4096).unwrap();
+        assert_eq!(hashes.sha256, reread.sha256);
+        assert_eq!(hashes.blake3, reread.blake3);
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_verified_output() {
+        let config = EngineConfig::default();
+        let metrics = Arc::new(MetricsRegistry::new().unwrap());
+        let system = WriterSystem::new(&config, metrics).await.unwrap();
+
+        let sample_data = include_bytes!("../../tests/data/sample.pdf");
+        let options = WriteOptions { verify_roundtrip: true, ..Default::default() };
+        assert!(system.write_document(sample_data, Some(options)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_written_objects_mapped_through_dedup() {
+        let config = EngineConfig::default();
+        let metrics = Arc::new(MetricsRegistry::new().unwrap());
+        let system = WriterSystem::new(&config, metrics).await.unwrap();
+
+        let mut doc = Document::with_version("1.7");
+        let pages = doc.new_object_id();
+        let first = doc.add_object(Stream::new(Dictionary::new(), vec![1u8; 256]));
+        let second = doc.add_object(Stream::new(Dictionary::new(), vec![1u8; 256]));
+        let page = doc.add_object(lopdf::dictionary! {
+            "Type" => "Page",
+            "Parent" => pages,
+            "PieceInfo" => vec![first.into(), second.into()],
+        });
+        doc.objects.insert(pages, Object::Dictionary(lopdf::dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page.into()],
+            "Count" => 1,
+        }));
+        let catalog = doc.add_object(lopdf::dictionary! { "Type" => "Catalog", "Pages" => pages });
+        doc.trailer.set("Root", catalog);
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+
+        let options = WriteOptions { validate: false, ..Default::default() };
+        let result = system.write_document(&data, Some(options)).await.unwrap();
+        assert_eq!(result.duplicates_removed, 1);
+        assert_eq!(result.object_map.output_of(second), Some(first));
+        assert_eq!(result.object_map.inputs_of(first), vec![first, second]);
+        assert_eq!(result.object_map.output_of(page), Some(page));
+    }
+
     #[tokio::test]
     async fn test_document_optimization() {
         let config = EngineConfig::default();
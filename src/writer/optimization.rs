@@ -1,8 +1,8 @@
-use crate::{metrics::MetricsRegistry, PdfError, WriterConfig};
+use crate::{metrics::MetricsRegistry, verification::font_license, PdfError, WriterConfig};
 use chrono::{DateTime, Utc};
 use lopdf::{Document, Object, ObjectId, Stream, Dictionary};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::{Arc, RwLock},
 };
 use image::{DynamicImage, ImageFormat};
@@ -28,6 +28,10 @@ pub struct OptimizationConfig {
     pub enable_font_subsetting: bool,
     pub remove_unused_resources: bool,
     pub merge_duplicate_resources: bool,
+    /// Drop page resource entries that no content stream refers to
+    pub prune_page_resources: bool,
+    /// Subset, rewrite or drop fonts whose license (`fsType`) restricts it
+    pub allow_restricted_font_changes: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -38,6 +42,24 @@ pub enum OptimizationLevel {
     Aggressive,
 }
 
+/// What an optimization run removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptimizationReport {
+    pub dedup: DedupStats,
+    /// Objects left untouched because of restrictive font licenses
+    pub protected_fonts: usize,
+}
+
+/// Outcome of the duplicate-object pass
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    pub objects_removed: usize,
+    /// Approximate serialized size of the removed objects
+    pub bytes_saved: usize,
+    /// Each removed object and the copy kept in its place
+    pub merged: BTreeMap<ObjectId, ObjectId>,
+}
+
 #[derive(Debug)]
 struct OptimizationStats {
     original_size: u64,
@@ -52,6 +74,7 @@ enum OptimizationTechnique {
     FontSubsetting,
     StreamCompression,
     ResourceDeduplification,
+    ResourcePruning,
     StructureOptimization,
 }
 
@@ -73,7 +96,16 @@ impl OptimizationSystem {
     }
 
     pub async fn optimize_document(&self, doc: Document) -> Result<Document, PdfError> {
+        self.optimize_document_with_report(doc).await.map(|(doc, _)| doc)
+    }
+
+    /// Optimizes the document and reports what the passes removed
+    pub async fn optimize_document_with_report(
+        &self,
+        doc: Document,
+    ) -> Result<(Document, OptimizationReport), PdfError> {
         let start_time = std::time::Instant::now();
+        let mut report = OptimizationReport::default();
         let mut optimized_doc = doc.clone();
         let document_id = optimized_doc.get_id().unwrap_or_else(|| "unknown".to_string());
 
@@ -84,25 +116,35 @@ impl OptimizationSystem {
             state.active_optimizations += 1;
         }
 
+        // Fonts whose license forbids subsetting or editing stay as they are
+        let protected = if self.config.allow_restricted_font_changes {
+            HashSet::new()
+        } else {
+            font_license::protected_objects(&optimized_doc)
+        };
+        report.protected_fonts = protected.len();
+
         // Perform optimizations based on level
         match self.config.level {
             OptimizationLevel::None => (),
             OptimizationLevel::Basic => {
                 self.optimize_images(&mut optimized_doc)?;
-                self.optimize_streams(&mut optimized_doc)?;
+                self.optimize_streams(&mut optimized_doc, &protected)?;
             },
             OptimizationLevel::Standard => {
                 self.optimize_images(&mut optimized_doc)?;
-                self.optimize_streams(&mut optimized_doc)?;
-                self.optimize_fonts(&mut optimized_doc)?;
-                self.merge_duplicate_resources(&mut optimized_doc)?;
+                self.optimize_streams(&mut optimized_doc, &protected)?;
+                self.optimize_fonts(&mut optimized_doc, &protected)?;
+                report.dedup = self.merge_duplicate_resources(&mut optimized_doc, &protected)?;
+                self.prune_page_resources(&mut optimized_doc, &protected)?;
             },
             OptimizationLevel::Aggressive => {
                 self.optimize_images(&mut optimized_doc)?;
-                self.optimize_streams(&mut optimized_doc)?;
-                self.optimize_fonts(&mut optimized_doc)?;
-                self.merge_duplicate_resources(&mut optimized_doc)?;
-                self.remove_unused_resources(&mut optimized_doc)?;
+                self.optimize_streams(&mut optimized_doc, &protected)?;
+                self.optimize_fonts(&mut optimized_doc, &protected)?;
+                report.dedup = self.merge_duplicate_resources(&mut optimized_doc, &protected)?;
+                self.prune_page_resources(&mut optimized_doc, &protected)?;
+                self.remove_unused_resources(&mut optimized_doc, &protected)?;
                 self.optimize_structure(&mut optimized_doc)?;
             },
         }
@@ -136,7 +178,7 @@ impl OptimizationSystem {
             self.metrics.optimization_savings.inc_by(savings as f64);
         }
 
-        Ok(optimized_doc)
+        Ok((optimized_doc, report))
     }
 
     fn optimize_images(&self, doc: &mut Document) -> Result<(), PdfError> {
@@ -210,8 +252,11 @@ impl OptimizationSystem {
         }
     }
 
-    fn optimize_streams(&self, doc: &mut Document) -> Result<(), PdfError> {
-        for obj in doc.objects.values_mut() {
+    fn optimize_streams(&self, doc: &mut Document, protected: &HashSet<ObjectId>) -> Result<(), PdfError> {
+        for (id, obj) in doc.objects.iter_mut() {
+            if protected.contains(id) {
+                continue;
+            }
             if let Object::Stream(ref mut stream) = obj {
                 if !self.is_image_stream(&Object::Stream(stream.clone())) {
                     // Optimize non-image streams (e.g., content streams)
@@ -228,13 +273,13 @@ impl OptimizationSystem {
         Ok(content.to_vec())
     }
 
-    fn optimize_fonts(&self, doc: &mut Document) -> Result<(), PdfError> {
+    fn optimize_fonts(&self, doc: &mut Document, protected: &HashSet<ObjectId>) -> Result<(), PdfError> {
         if !self.config.enable_font_subsetting {
             return Ok(());
         }
 
         let font_objects: Vec<ObjectId> = doc.objects.iter()
-            .filter(|(_, obj)| self.is_font_dictionary(obj))
+            .filter(|(id, obj)| !protected.contains(id) && self.is_font_dictionary(obj))
             .map(|(id, _)| *id)
             .collect();
 
@@ -265,55 +310,42 @@ impl OptimizationSystem {
         Ok(())
     }
 
-    fn merge_duplicate_resources(&self, doc: &mut Document) -> Result<(), PdfError> {
+    fn merge_duplicate_resources(
+        &self,
+        doc: &mut Document,
+        protected: &HashSet<ObjectId>,
+    ) -> Result<DedupStats, PdfError> {
         if !self.config.merge_duplicate_resources {
-            return Ok(());
+            return Ok(DedupStats::default());
         }
+        Ok(deduplicate_objects(doc, protected))
+    }
 
-        let mut resource_map: HashMap<String, ObjectId> = HashMap::new();
-        let duplicates: Vec<ObjectId> = Vec::new();
-
-        // Find and merge duplicate resources
-        // This is a simplified version; in production, implement proper resource comparison
-        for (id, obj) in doc.objects.iter() {
-            if let Object::Stream(ref stream) = obj {
-                let hash = self.calculate_resource_hash(stream)?;
-                if let Some(&existing_id) = resource_map.get(&hash) {
-                    duplicates.push(*id);
-                    self.update_references(doc, *id, existing_id)?;
-                } else {
-                    resource_map.insert(hash, *id);
-                }
-            }
+    fn prune_page_resources(&self, doc: &mut Document, protected: &HashSet<ObjectId>) -> Result<(), PdfError> {
+        if !self.config.prune_page_resources {
+            return Ok(());
         }
-
-        // Remove duplicate objects
-        for id in duplicates {
-            doc.objects.remove(&id);
+        // Pruning works on whole resource dictionaries and could drop a
+        // restricted font along with the unused entries around it
+        if !protected.is_empty() {
+            log::info!("skipping resource pruning: document embeds license-restricted fonts");
+            return Ok(());
         }
 
+        if prune_page_resources(doc) > 0 {
+            // Drop the fonts, images and patterns that are now unreachable
+            doc.prune_objects();
+        }
         Ok(())
     }
 
-    fn calculate_resource_hash(&self, stream: &Stream) -> Result<String, PdfError> {
-        use sha2::{Sha256, Digest};
-        let mut hasher = Sha256::new();
-        hasher.update(&stream.content);
-        Ok(format!("{:x}", hasher.finalize()))
-    }
-
-    fn update_references(&self, doc: &mut Document, from: ObjectId, to: ObjectId) -> Result<(), PdfError> {
-        // Update all references from the duplicate object to the original
-        // This would involve traversing the document and updating all references
-        Ok(())
-    }
-
-    fn remove_unused_resources(&self, doc: &mut Document) -> Result<(), PdfError> {
+    fn remove_unused_resources(&self, doc: &mut Document, protected: &HashSet<ObjectId>) -> Result<(), PdfError> {
         if !self.config.remove_unused_resources {
             return Ok(());
         }
 
-        let used_objects = self.find_used_objects(doc);
+        let mut used_objects = self.find_used_objects(doc);
+        used_objects.extend(protected);
         let all_objects: HashSet<ObjectId> = doc.objects.keys().copied().collect();
         
         // Remove unused objects
@@ -378,6 +410,7 @@ impl OptimizationSystem {
                 techniques.push(OptimizationTechnique::StreamCompression);
                 techniques.push(OptimizationTechnique::FontSubsetting);
                 techniques.push(OptimizationTechnique::ResourceDeduplification);
+                techniques.push(OptimizationTechnique::ResourcePruning);
             },
             OptimizationLevel::Aggressive => {
                 techniques.extend_from_slice(&[
@@ -385,6 +418,7 @@ impl OptimizationSystem {
                     OptimizationTechnique::StreamCompression,
                     OptimizationTechnique::FontSubsetting,
                     OptimizationTechnique::ResourceDeduplification,
+                    OptimizationTechnique::ResourcePruning,
                     OptimizationTechnique::StructureOptimization,
                 ]);
             },
@@ -393,6 +427,384 @@ impl OptimizationSystem {
     }
 }
 
+/// Merges byte-identical streams and shared-resource dictionaries
+///
+/// Objects are compared by a canonical encoding (sorted dictionary keys,
+/// stream `/Length` ignored); the lowest-numbered copy is kept and every
+/// reference to the others is rewritten. Merging streams can make the
+/// font dictionaries that point at them identical, so the pass repeats
+/// until nothing changes. Encrypted documents are left alone because
+/// their stream bytes depend on the object number. `protected` objects
+/// are neither merged away nor kept in place of others.
+pub(crate) fn deduplicate_objects(doc: &mut Document, protected: &HashSet<ObjectId>) -> DedupStats {
+    use sha2::{Digest, Sha256};
+    use std::collections::hash_map::Entry;
+
+    let mut stats = DedupStats::default();
+    if doc.trailer.has(b"Encrypt") {
+        return stats;
+    }
+
+    for _ in 0..4 {
+        let mut canonical_ids: HashMap<[u8; 32], ObjectId> = HashMap::new();
+        let mut replacements: HashMap<ObjectId, ObjectId> = HashMap::new();
+
+        for (id, object) in doc.objects.iter() {
+            if !is_shareable(object) || protected.contains(id) {
+                continue;
+            }
+            let mut encoded = Vec::new();
+            encode_canonical(object, &mut encoded);
+            let digest: [u8; 32] = Sha256::digest(&encoded).into();
+
+            match canonical_ids.entry(digest) {
+                Entry::Occupied(kept) => {
+                    replacements.insert(*id, *kept.get());
+                    stats.bytes_saved += encoded.len();
+                }
+                Entry::Vacant(slot) => {
+                    slot.insert(*id);
+                }
+            }
+        }
+
+        if replacements.is_empty() {
+            break;
+        }
+
+        for id in replacements.keys() {
+            doc.objects.remove(id);
+        }
+        for object in doc.objects.values_mut() {
+            rewrite_references(object, &replacements);
+        }
+        for (_, value) in doc.trailer.iter_mut() {
+            rewrite_references(value, &replacements);
+        }
+        stats.objects_removed += replacements.len();
+        // A copy kept in an earlier pass may have been merged in this one
+        for kept in stats.merged.values_mut() {
+            if let Some(&merged_into) = replacements.get(kept) {
+                *kept = merged_into;
+            }
+        }
+        stats.merged.extend(replacements);
+    }
+
+    stats
+}
+
+/// Streams and resource dictionaries whose identity does not matter
+///
+/// Pages, annotations and structure elements are never merged even when
+/// identical, since each occurrence is a distinct node.
+fn is_shareable(object: &Object) -> bool {
+    let type_name = |dict: &Dictionary| dict.get(b"Type").and_then(Object::as_name).ok().map(<[u8]>::to_vec);
+    match object {
+        Object::Stream(stream) => !matches!(
+            type_name(&stream.dict).as_deref(),
+            Some(b"ObjStm") | Some(b"XRef")
+        ),
+        Object::Dictionary(dict) => matches!(
+            type_name(dict).as_deref(),
+            Some(b"Font") | Some(b"FontDescriptor") | Some(b"ExtGState")
+        ),
+        _ => false,
+    }
+}
+
+/// Unambiguous, order-independent encoding used to compare objects
+fn encode_canonical(object: &Object, out: &mut Vec<u8>) {
+    fn bytes(tag: u8, data: &[u8], out: &mut Vec<u8>) {
+        out.push(tag);
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+
+    fn dictionary(dict: &Dictionary, skip_length: bool, out: &mut Vec<u8>) {
+        let mut entries: Vec<(&Vec<u8>, &Object)> = dict.iter()
+            .filter(|(key, _)| !(skip_length && key.as_slice() == b"Length"))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        out.push(b'd');
+        out.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for (key, value) in entries {
+            bytes(b'k', key, out);
+            encode_canonical(value, out);
+        }
+    }
+
+    match object {
+        Object::Null => out.push(b'n'),
+        Object::Boolean(value) => out.extend_from_slice(&[b'b', *value as u8]),
+        Object::Integer(value) => bytes(b'i', &value.to_le_bytes(), out),
+        Object::Real(value) => bytes(b'r', &value.to_bits().to_le_bytes(), out),
+        Object::Name(name) => bytes(b'/', name, out),
+        Object::String(data, _) => bytes(b's', data, out),
+        Object::Reference((num, gen)) => {
+            out.push(b'R');
+            out.extend_from_slice(&num.to_le_bytes());
+            out.extend_from_slice(&gen.to_le_bytes());
+        }
+        Object::Array(items) => {
+            out.push(b'a');
+            out.extend_from_slice(&(items.len() as u64).to_le_bytes());
+            for item in items {
+                encode_canonical(item, out);
+            }
+        }
+        Object::Dictionary(dict) => dictionary(dict, false, out),
+        Object::Stream(stream) => {
+            dictionary(&stream.dict, true, out);
+            bytes(b'S', &stream.content, out);
+        }
+    }
+}
+
+fn rewrite_references(object: &mut Object, replacements: &HashMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(kept) = replacements.get(id) {
+                *id = *kept;
+            }
+        }
+        Object::Array(items) => items.iter_mut().for_each(|item| rewrite_references(item, replacements)),
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                rewrite_references(value, replacements);
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                rewrite_references(value, replacements);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Resource categories pruned per page, with the operators that use them
+const PRUNED_CATEGORIES: [&[u8]; 3] = [b"XObject", b"Font", b"Pattern"];
+
+/// Where a resource category dictionary lives
+///
+/// Several pages can share one slot through an inherited or indirect
+/// `/Resources` dictionary, so usage is collected per slot rather than per
+/// page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ResourceSlot {
+    /// Category dictionary that is its own indirect object
+    Indirect(ObjectId),
+    /// Category dictionary inline in the `/Resources` held by `owner`
+    Inline { owner: ObjectId, indirect_resources: bool, category: usize },
+}
+
+/// Removes XObject, Font and Pattern entries that no page content uses
+///
+/// Usage is taken from the parsed page content streams, including form
+/// XObjects that inherit the page resources. A slot is left untouched if
+/// any page using it has unparseable content, or if it is also referenced
+/// from outside the page tree. Returns the number of entries removed.
+pub(crate) fn prune_page_resources(doc: &mut Document) -> usize {
+    let references = reference_counts(doc);
+    let mut used: HashMap<ResourceSlot, HashSet<Vec<u8>>> = HashMap::new();
+    let mut unprunable: HashSet<ResourceSlot> = HashSet::new();
+    // Visited objects that refer to each shared resources or category dictionary
+    let mut holders: HashMap<ObjectId, HashSet<ObjectId>> = HashMap::new();
+
+    for page_id in doc.get_pages().into_values() {
+        let (owner, resources_ref) = match inherited_resources(doc, page_id) {
+            Some(found) => found,
+            None => continue,
+        };
+        let resources = match resources_ref {
+            Some(id) => doc.get_dictionary(id).ok(),
+            None => doc.get_dictionary(owner).and_then(|d| d.get(b"Resources")).and_then(Object::as_dict).ok(),
+        };
+        let resources = match resources {
+            Some(resources) => resources,
+            None => continue,
+        };
+        let holder = resources_ref.unwrap_or(owner);
+        if let Some(id) = resources_ref {
+            holders.entry(id).or_default().insert(owner);
+        }
+
+        let slots: Vec<Option<ResourceSlot>> = PRUNED_CATEGORIES.iter().enumerate()
+            .map(|(index, category)| match resources.get(category) {
+                Ok(Object::Reference(id)) => Some(ResourceSlot::Indirect(*id)),
+                Ok(Object::Dictionary(_)) => Some(ResourceSlot::Inline {
+                    owner: holder,
+                    indirect_resources: resources_ref.is_some(),
+                    category: index,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        let content = doc.get_page_content(page_id).unwrap_or_default();
+        let mut names: [HashSet<Vec<u8>>; 3] = Default::default();
+        let parsed = collect_resource_names(doc, resources, &content, &mut names, 0);
+
+        for (index, slot) in slots.into_iter().enumerate() {
+            if let Some(slot) = slot {
+                if let ResourceSlot::Indirect(id) = slot {
+                    holders.entry(id).or_default().insert(holder);
+                }
+                if parsed {
+                    used.entry(slot).or_default().extend(names[index].drain());
+                } else {
+                    unprunable.insert(slot);
+                }
+            }
+        }
+    }
+
+    let mut removed = 0;
+    for (slot, names) in used {
+        if unprunable.contains(&slot) {
+            continue;
+        }
+
+        // Anything referenced from outside the visited page tree may use more names
+        let shared_elsewhere = |id: ObjectId| {
+            let visited = holders.get(&id).map_or(0, HashSet::len);
+            references.get(&id).copied().unwrap_or(0) > visited
+        };
+
+        let category_dict = match slot {
+            ResourceSlot::Indirect(id) => {
+                if shared_elsewhere(id) {
+                    continue;
+                }
+                doc.get_object_mut(id).and_then(Object::as_dict_mut).ok()
+            }
+            ResourceSlot::Inline { owner, indirect_resources, category } => {
+                if indirect_resources && shared_elsewhere(owner) {
+                    continue;
+                }
+                let resources = if indirect_resources {
+                    doc.get_object_mut(owner).and_then(Object::as_dict_mut).ok()
+                } else {
+                    doc.get_object_mut(owner)
+                        .and_then(Object::as_dict_mut)
+                        .and_then(|d| d.get_mut(b"Resources"))
+                        .and_then(Object::as_dict_mut)
+                        .ok()
+                };
+                resources.and_then(|r| r.get_mut(PRUNED_CATEGORIES[category]).and_then(Object::as_dict_mut).ok())
+            }
+        };
+
+        if let Some(dict) = category_dict {
+            let unused: Vec<Vec<u8>> = dict.iter()
+                .map(|(key, _)| key.clone())
+                .filter(|key| !names.contains(key))
+                .collect();
+            for key in unused {
+                dict.remove(&key);
+                removed += 1;
+            }
+        }
+    }
+
+    removed
+}
+
+/// Finds the page-tree node holding the page's `/Resources`, and the
+/// resources object id when the entry is a reference
+fn inherited_resources(doc: &Document, page_id: ObjectId) -> Option<(ObjectId, Option<ObjectId>)> {
+    let mut current = Some(page_id);
+    for _ in 0..32 {
+        let id = current?;
+        let dict = doc.get_dictionary(id).ok()?;
+        match dict.get(b"Resources") {
+            Ok(Object::Reference(resources_id)) => return Some((id, Some(*resources_id))),
+            Ok(Object::Dictionary(_)) => return Some((id, None)),
+            _ => current = dict.get(b"Parent").and_then(Object::as_reference).ok(),
+        }
+    }
+    None
+}
+
+/// Records the XObject, Font and Pattern names used by a content stream
+///
+/// Form XObjects without their own `/Resources` draw from the same
+/// dictionary, so their content is followed too. Returns false if any
+/// stream could not be parsed.
+fn collect_resource_names(
+    doc: &Document,
+    resources: &Dictionary,
+    content: &[u8],
+    names: &mut [HashSet<Vec<u8>>; 3],
+    depth: usize,
+) -> bool {
+    let operations = match crate::core::content::parse(content) {
+        Ok(operations) => operations,
+        Err(_) => return false,
+    };
+
+    for op in operations {
+        let name = op.operands.iter().rev().find_map(|o| o.as_name()).map(<[u8]>::to_vec);
+        let name = match name {
+            Some(name) => name,
+            None => continue,
+        };
+
+        match op.operator.as_str() {
+            "Do" => {
+                if names[0].insert(name.clone()) && depth < 8 {
+                    let form = resources.get(b"XObject")
+                        .and_then(|x| match x {
+                            Object::Reference(id) => doc.get_dictionary(*id),
+                            other => other.as_dict(),
+                        })
+                        .and_then(|x| x.get(&name))
+                        .and_then(Object::as_reference)
+                        .and_then(|id| doc.get_object(id))
+                        .and_then(Object::as_stream);
+                    if let Ok(form) = form {
+                        if !form.dict.has(b"Resources") {
+                            let data = form.decompressed_content().unwrap_or_else(|_| form.content.clone());
+                            if !collect_resource_names(doc, resources, &data, names, depth + 1) {
+                                return false;
+                            }
+                        }
+                    }
+                }
+            }
+            "Tf" => {
+                names[1].insert(name);
+            }
+            "scn" | "SCN" => {
+                names[2].insert(name);
+            }
+            _ => {}
+        }
+    }
+
+    true
+}
+
+/// Counts references to every object from anywhere in the document
+fn reference_counts(doc: &Document) -> HashMap<ObjectId, usize> {
+    fn visit(object: &Object, counts: &mut HashMap<ObjectId, usize>) {
+        match object {
+            Object::Reference(id) => *counts.entry(*id).or_insert(0) += 1,
+            Object::Array(items) => items.iter().for_each(|item| visit(item, counts)),
+            Object::Dictionary(dict) => dict.iter().for_each(|(_, value)| visit(value, counts)),
+            Object::Stream(stream) => stream.dict.iter().for_each(|(_, value)| visit(value, counts)),
+            _ => {}
+        }
+    }
+
+    let mut counts = HashMap::new();
+    for object in doc.objects.values() {
+        visit(object, &mut counts);
+    }
+    counts
+}
+
 impl Default for OptimizationConfig {
     fn default() -> Self {
         Self {
@@ -402,6 +814,8 @@ impl Default for OptimizationConfig {
             enable_font_subsetting: true,
             remove_unused_resources: true,
             merge_duplicate_resources: true,
+            prune_page_resources: true,
+            allow_restricted_font_changes: false,
         }
     }
 }
@@ -447,4 +861,181 @@ mod tests {
         let result = system.optimize_document(doc).await;
         assert!(result.is_ok());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_restricted_fonts_survive_optimization() {
+        let writer_config = WriterConfig::default();
+        let metrics = Arc::new(MetricsRegistry::new().unwrap());
+        let mut system = OptimizationSystem::new(&writer_config, metrics).await.unwrap();
+        system.config.level = OptimizationLevel::Aggressive;
+
+        // No catalog, so every object is unused and would normally be removed
+        let doc = font_license::tests::document_with_font(0x0002);
+        let (optimized, report) = system.optimize_document_with_report(doc).await.unwrap();
+        assert_eq!(report.protected_fonts, 3);
+        assert_eq!(optimized.objects.len(), 3);
+
+        system.config.allow_restricted_font_changes = true;
+        let doc = font_license::tests::document_with_font(0x0002);
+        let (optimized, _) = system.optimize_document_with_report(doc).await.unwrap();
+        assert!(optimized.objects.is_empty());
+    }
+
+    fn page_with_content(doc: &mut Document, parent: ObjectId, content: &str, resources: Option<Dictionary>) -> ObjectId {
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content.as_bytes().to_vec()));
+        let mut page = lopdf::dictionary! {
+            "Type" => "Page",
+            "Parent" => parent,
+            "Contents" => content_id,
+        };
+        if let Some(resources) = resources {
+            page.set("Resources", resources);
+        }
+        doc.add_object(page)
+    }
+
+    fn finish_tree(doc: &mut Document, pages_id: ObjectId, kids: Vec<ObjectId>, resources: Option<Dictionary>) {
+        let mut pages = lopdf::dictionary! {
+            "Type" => "Pages",
+            "Count" => kids.len() as i64,
+            "Kids" => kids.into_iter().map(Object::Reference).collect::<Vec<_>>(),
+        };
+        if let Some(resources) = resources {
+            pages.set("Resources", resources);
+        }
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = doc.add_object(lopdf::dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+    }
+
+    fn names(doc: &Document, dict: &Dictionary, category: &[u8]) -> Vec<String> {
+        let category = match dict.get(category).unwrap() {
+            Object::Reference(id) => doc.get_dictionary(*id).unwrap(),
+            other => other.as_dict().unwrap(),
+        };
+        let mut names: Vec<String> = category.iter().map(|(k, _)| String::from_utf8_lossy(k).into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_prune_unused_page_resources() {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let font = doc.add_object(lopdf::dictionary! { "Type" => "Font" });
+        let image = doc.add_object(Stream::new(lopdf::dictionary! { "Subtype" => "Image" }, vec![0; 16]));
+        let resources = lopdf::dictionary! {
+            "Font" => lopdf::dictionary! { "F1" => font, "F2" => font },
+            "XObject" => lopdf::dictionary! { "Im1" => image, "Im2" => image },
+        };
+        let page = page_with_content(&mut doc, pages_id, "BT /F1 12 Tf (x) Tj ET /Im1 Do", Some(resources));
+        finish_tree(&mut doc, pages_id, vec![page], None);
+
+        assert_eq!(prune_page_resources(&mut doc), 2);
+        let resources = doc.get_dictionary(page).unwrap().get(b"Resources").unwrap().as_dict().unwrap();
+        assert_eq!(names(&doc, resources, b"Font"), vec!["F1"]);
+        assert_eq!(names(&doc, resources, b"XObject"), vec!["Im1"]);
+    }
+
+    #[test]
+    fn test_prune_keeps_names_used_by_any_sharing_page() {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let font = doc.add_object(lopdf::dictionary! { "Type" => "Font" });
+        let shared = lopdf::dictionary! {
+            "Font" => lopdf::dictionary! { "F1" => font, "F2" => font, "F3" => font },
+        };
+        let first = page_with_content(&mut doc, pages_id, "BT /F1 12 Tf ET", None);
+        let second = page_with_content(&mut doc, pages_id, "BT /F2 12 Tf ET", None);
+        finish_tree(&mut doc, pages_id, vec![first, second], Some(shared));
+
+        assert_eq!(prune_page_resources(&mut doc), 1);
+        let resources = doc.get_dictionary(pages_id).unwrap().get(b"Resources").unwrap().as_dict().unwrap();
+        assert_eq!(names(&doc, resources, b"Font"), vec!["F1", "F2"]);
+    }
+
+    #[test]
+    fn test_prune_skips_unparseable_content() {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let font = doc.add_object(lopdf::dictionary! { "Type" => "Font" });
+        let resources = lopdf::dictionary! { "Font" => lopdf::dictionary! { "F1" => font } };
+        let page = page_with_content(&mut doc, pages_id, "BT (unterminated", Some(resources));
+        finish_tree(&mut doc, pages_id, vec![page], None);
+
+        assert_eq!(prune_page_resources(&mut doc), 0);
+    }
+
+    #[test]
+    fn test_prune_follows_forms_without_resources() {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let font = doc.add_object(lopdf::dictionary! { "Type" => "Font" });
+        let form = doc.add_object(Stream::new(
+            lopdf::dictionary! { "Subtype" => "Form" },
+            b"BT /F2 9 Tf ET".to_vec(),
+        ));
+        let resources = lopdf::dictionary! {
+            "Font" => lopdf::dictionary! { "F1" => font, "F2" => font },
+            "XObject" => lopdf::dictionary! { "Fm1" => form },
+        };
+        let page = page_with_content(&mut doc, pages_id, "/Fm1 Do", Some(resources));
+        finish_tree(&mut doc, pages_id, vec![page], None);
+
+        assert_eq!(prune_page_resources(&mut doc), 1);
+        let resources = doc.get_dictionary(page).unwrap().get(b"Resources").unwrap().as_dict().unwrap();
+        assert_eq!(names(&doc, resources, b"Font"), vec!["F2"]);
+    }
+
+    #[test]
+    fn test_deduplicate_identical_streams_and_fonts() {
+        let mut doc = Document::with_version("1.7");
+        let logo = || Stream::new(lopdf::dictionary! { "Subtype" => "Image" }, vec![7u8; 512]);
+        let first_logo = doc.add_object(logo());
+        let second_logo = doc.add_object(logo());
+        let first_file = doc.add_object(Stream::new(Dictionary::new(), vec![1u8; 256]));
+        let second_file = doc.add_object(Stream::new(Dictionary::new(), vec![1u8; 256]));
+        let first_descriptor = doc.add_object(lopdf::dictionary! { "Type" => "FontDescriptor", "FontFile2" => first_file });
+        let second_descriptor = doc.add_object(lopdf::dictionary! { "Type" => "FontDescriptor", "FontFile2" => second_file });
+        let user = doc.add_object(lopdf::dictionary! {
+            "Logos" => vec![first_logo.into(), second_logo.into()],
+            "Descriptors" => vec![first_descriptor.into(), second_descriptor.into()],
+        });
+
+        let stats = deduplicate_objects(&mut doc, &HashSet::new());
+        assert_eq!(stats.objects_removed, 3);
+        assert!(stats.bytes_saved >= 512 + 256);
+
+        let user = doc.get_dictionary(user).unwrap();
+        let logos = user.get(b"Logos").unwrap().as_array().unwrap();
+        assert_eq!(logos[0].as_reference().unwrap(), logos[1].as_reference().unwrap());
+        let descriptors = user.get(b"Descriptors").unwrap().as_array().unwrap();
+        assert_eq!(descriptors[0].as_reference().unwrap(), first_descriptor);
+        assert_eq!(descriptors[1].as_reference().unwrap(), first_descriptor);
+        assert_eq!(stats.merged, BTreeMap::from([
+            (second_logo, first_logo),
+            (second_file, first_file),
+            (second_descriptor, first_descriptor),
+        ]));
+    }
+
+    #[test]
+    fn test_deduplicate_leaves_pages_and_encrypted_documents() {
+        let mut doc = Document::with_version("1.7");
+        doc.add_object(lopdf::dictionary! { "Type" => "Page" });
+        doc.add_object(lopdf::dictionary! { "Type" => "Page" });
+        assert_eq!(deduplicate_objects(&mut doc, &HashSet::new()).objects_removed, 0);
+
+        let first = doc.add_object(Stream::new(Dictionary::new(), vec![0u8; 64]));
+        let second = doc.add_object(Stream::new(Dictionary::new(), vec![0u8; 64]));
+        let third = doc.add_object(Stream::new(Dictionary::new(), vec![0u8; 64]));
+        // Protected copies stay, and nothing is merged into them
+        let stats = deduplicate_objects(&mut doc.clone(), &HashSet::from([first, second]));
+        assert_eq!(stats.objects_removed, 0);
+        let stats = deduplicate_objects(&mut doc.clone(), &HashSet::from([first]));
+        assert_eq!(stats.merged, BTreeMap::from([(third, second)]));
+
+        doc.trailer.set("Encrypt", Dictionary::new());
+        assert_eq!(deduplicate_objects(&mut doc, &HashSet::new()), DedupStats::default());
+    }
+}
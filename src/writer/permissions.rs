@@ -0,0 +1,374 @@
+//! Permission restrictions
+//!
+//! Readers enforce the permission flags of a PDF (printing, changing,
+//! copying, annotating, filling forms, assembling pages) only when the
+//! document is encrypted, since the flags live in the encryption
+//! dictionary. A document meant to open freely but not be edited is
+//! encrypted with an empty user password, which every reader tries first,
+//! and an owner password that lifts the restrictions. [`restrict`] does
+//! that with the AES-256 standard security handler (revision 6); without
+//! an owner password a random one is used, so the restrictions cannot be
+//! lifted at all. [`encrypt`] also sets a user password.
+
+use aes::{
+    cipher::{consts::U16, generic_array::GenericArray, BlockEncrypt, BlockSizeUser, KeyInit},
+    Aes128, Aes256,
+};
+use lopdf::{dictionary, Document, Object, StringFormat};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use thiserror::Error;
+
+/// Passwords are truncated to this many UTF-8 bytes
+const MAX_PASSWORD_LEN: usize = 127;
+
+/// Bits 7-8 and 13-32 of /P, which must be set
+const RESERVED_BITS: u32 = 0xFFFF_F0C0;
+
+#[derive(Debug, Error)]
+pub enum PermissionsError {
+    #[error("unknown restriction '{0}' (expected print, copy, edit, annotate, forms or assemble)")]
+    UnknownRestriction(String),
+    #[error("document is already encrypted")]
+    AlreadyEncrypted,
+    #[error("PDF error: {0}")]
+    Pdf(#[from] lopdf::Error),
+}
+
+/// Operations a reader allows without the owner password
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub print: bool,
+    /// Printing at full quality rather than a low-resolution rendering
+    pub print_high_quality: bool,
+    /// Changing the content by other means than the ones below
+    pub modify: bool,
+    /// Copying and extracting text and images
+    pub copy: bool,
+    /// Adding and changing annotations, and filling forms
+    pub annotate: bool,
+    /// Filling existing form fields, even when `annotate` is not allowed
+    pub fill_forms: bool,
+    /// Extracting text for accessibility tools
+    pub accessibility: bool,
+    /// Inserting, rotating and deleting pages, bookmarks and thumbnails
+    pub assemble: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Permissions {
+    pub fn all() -> Self {
+        Self {
+            print: true,
+            print_high_quality: true,
+            modify: true,
+            copy: true,
+            annotate: true,
+            fill_forms: true,
+            accessibility: true,
+            assemble: true,
+        }
+    }
+
+    /// Everything except the named restrictions, as the CLI's `--restrict`
+    /// takes them: `print`, `copy`, `edit`, `annotate`, `forms` and
+    /// `assemble`. Accessibility extraction is never restricted.
+    pub fn from_restrictions<S: AsRef<str>>(restrictions: &[S]) -> Result<Self, PermissionsError> {
+        let mut permissions = Self::all();
+        for restriction in restrictions {
+            match restriction.as_ref().trim() {
+                "print" => {
+                    permissions.print = false;
+                    permissions.print_high_quality = false;
+                }
+                "copy" => permissions.copy = false,
+                "edit" => permissions.modify = false,
+                "annotate" => permissions.annotate = false,
+                "forms" => permissions.fill_forms = false,
+                "assemble" => permissions.assemble = false,
+                "" => {}
+                other => return Err(PermissionsError::UnknownRestriction(other.to_string())),
+            }
+        }
+        Ok(permissions)
+    }
+
+    /// The /P value of the encryption dictionary
+    pub fn bits(&self) -> i32 {
+        let flags = [
+            (self.print, 3),
+            (self.modify, 4),
+            (self.copy, 5),
+            (self.annotate, 6),
+            (self.fill_forms, 9),
+            (self.accessibility, 10),
+            (self.assemble, 11),
+            (self.print_high_quality, 12),
+        ];
+        let bits = flags.into_iter()
+            .filter(|&(allowed, _)| allowed)
+            .fold(RESERVED_BITS, |bits, (_, bit)| bits | 1u32 << (bit - 1));
+        bits as i32
+    }
+}
+
+/// Applies `permissions` to a document that opens without a password
+pub fn restrict(doc: &mut Document, permissions: Permissions, owner_password: Option<&str>) -> Result<(), PermissionsError> {
+    encrypt(doc, "", owner_password, permissions)
+}
+
+/// Encrypts the document with the AES-256 standard security handler
+///
+/// Every string and stream is encrypted except the /Contents of signature
+/// dictionaries, which the standard keeps plain; the document ID is added
+/// when missing. Call last, after every other change to the document.
+pub fn encrypt(
+    doc: &mut Document,
+    user_password: &str,
+    owner_password: Option<&str>,
+    permissions: Permissions,
+) -> Result<(), PermissionsError> {
+    if doc.trailer.has(b"Encrypt") {
+        return Err(PermissionsError::AlreadyEncrypted);
+    }
+    let random_owner: [u8; 32] = rand::random();
+    let user = password(user_password.as_bytes());
+    let owner = password(owner_password.map_or(&random_owner[..], str::as_bytes));
+    let file_key: [u8; 32] = rand::random();
+
+    let user_salts: [u8; 16] = rand::random();
+    let mut u = hash(user, &user_salts[..8], &[]).to_vec();
+    u.extend_from_slice(&user_salts);
+    let mut ue = file_key.to_vec();
+    cbc_encrypt(&Aes256::new(GenericArray::from_slice(&hash(user, &user_salts[8..], &[]))), [0; 16], &mut ue);
+
+    let owner_salts: [u8; 16] = rand::random();
+    let mut o = hash(owner, &owner_salts[..8], &u).to_vec();
+    o.extend_from_slice(&owner_salts);
+    let mut oe = file_key.to_vec();
+    cbc_encrypt(&Aes256::new(GenericArray::from_slice(&hash(owner, &owner_salts[8..], &u))), [0; 16], &mut oe);
+
+    let p = permissions.bits();
+    let mut perms = [0xFF; 16];
+    perms[..4].copy_from_slice(&p.to_le_bytes());
+    perms[8..12].copy_from_slice(b"Tadb");
+    perms[12..].copy_from_slice(&rand::random::<[u8; 4]>());
+    Aes256::new(GenericArray::from_slice(&file_key)).encrypt_block(GenericArray::from_mut_slice(&mut perms));
+
+    let cipher = Aes256::new(GenericArray::from_slice(&file_key));
+    for object in doc.objects.values_mut() {
+        encrypt_object(&cipher, object);
+    }
+
+    let hex = |bytes: Vec<u8>| Object::String(bytes, StringFormat::Hexadecimal);
+    let encrypt_id = doc.add_object(dictionary! {
+        "Filter" => "Standard",
+        "V" => 5,
+        "R" => 6,
+        "Length" => 256,
+        "CF" => dictionary! {
+            "StdCF" => dictionary! { "CFM" => "AESV3", "AuthEvent" => "DocOpen", "Length" => 32 },
+        },
+        "StmF" => "StdCF",
+        "StrF" => "StdCF",
+        "O" => hex(o),
+        "U" => hex(u),
+        "OE" => hex(oe),
+        "UE" => hex(ue),
+        "P" => p,
+        "Perms" => hex(perms.to_vec()),
+        "EncryptMetadata" => true,
+    });
+    doc.trailer.set("Encrypt", encrypt_id);
+    if !doc.trailer.has(b"ID") {
+        let id = hex(rand::random::<[u8; 16]>().to_vec());
+        doc.trailer.set("ID", vec![id.clone(), id]);
+    }
+
+    // Revision 6 is PDF 2.0, or Adobe extension level 8 of PDF 1.7
+    if doc.version.as_str() < "2.0" {
+        doc.version = "1.7".to_string();
+        let root = doc.trailer.get(b"Root")?.as_reference()?;
+        doc.get_dictionary_mut(root)?.set("Extensions", dictionary! {
+            "ADBE" => dictionary! { "BaseVersion" => "1.7", "ExtensionLevel" => 8 },
+        });
+    }
+    Ok(())
+}
+
+/// A password as UTF-8, truncated to the length the handler allows
+fn password(password: &[u8]) -> &[u8] {
+    &password[..password.len().min(MAX_PASSWORD_LEN)]
+}
+
+/// Hash of a password with a salt, and the /U value for owner hashes
+/// (algorithm 2.B of ISO 32000-2)
+fn hash(password: &[u8], salt: &[u8], user_key: &[u8]) -> [u8; 32] {
+    let mut k: Vec<u8> = Sha256::new().chain_update(password).chain_update(salt).chain_update(user_key).finalize().to_vec();
+    let mut round = 0u32;
+    loop {
+        let block: Vec<u8> = [password, &k[..], user_key].concat();
+        let mut e = block.repeat(64);
+        cbc_encrypt(&Aes128::new(GenericArray::from_slice(&k[..16])), k[16..32].try_into().unwrap_or([0; 16]), &mut e);
+        // The first 16 bytes as a number mod 3 equal their sum mod 3
+        k = match e[..16].iter().map(|&b| u32::from(b)).sum::<u32>() % 3 {
+            0 => Sha256::digest(&e).to_vec(),
+            1 => Sha384::digest(&e).to_vec(),
+            _ => Sha512::digest(&e).to_vec(),
+        };
+        round += 1;
+        if round >= 64 && u32::from(*e.last().unwrap_or(&0)) <= round - 32 {
+            break;
+        }
+    }
+    let mut key = [0; 32];
+    key.copy_from_slice(&k[..32]);
+    key
+}
+
+/// CBC encryption in place; `data` is a whole number of blocks
+fn cbc_encrypt<C: BlockEncrypt + BlockSizeUser<BlockSize = U16>>(cipher: &C, iv: [u8; 16], data: &mut [u8]) {
+    let mut previous = iv;
+    for block in data.chunks_exact_mut(16) {
+        block.iter_mut().zip(previous).for_each(|(byte, chained)| *byte ^= chained);
+        cipher.encrypt_block(GenericArray::from_mut_slice(block));
+        previous.copy_from_slice(block);
+    }
+}
+
+/// `data` encrypted as a string or stream: random IV, then the padded ciphertext
+fn encrypt_bytes(cipher: &Aes256, data: &[u8]) -> Vec<u8> {
+    let iv: [u8; 16] = rand::random();
+    let padding = 16 - data.len() % 16;
+    let mut encrypted = iv.to_vec();
+    encrypted.extend_from_slice(data);
+    encrypted.resize(16 + data.len() + padding, padding as u8);
+    cbc_encrypt(cipher, iv, &mut encrypted[16..]);
+    encrypted
+}
+
+/// Whether `dict` is a signature or document timestamp dictionary
+fn is_signature(dict: &lopdf::Dictionary) -> bool {
+    let typed = dict.get(b"Type").and_then(Object::as_name).is_ok_and(|t| t == b"Sig" || t == b"DocTimeStamp");
+    typed || (dict.has(b"ByteRange") && dict.has(b"Contents"))
+}
+
+fn encrypt_object(cipher: &Aes256, object: &mut Object) {
+    match object {
+        Object::String(bytes, _) => *bytes = encrypt_bytes(cipher, bytes),
+        Object::Array(items) => items.iter_mut().for_each(|item| encrypt_object(cipher, item)),
+        Object::Dictionary(dict) => {
+            // The signature value covers the file as written and is never encrypted
+            let signature = is_signature(dict);
+            dict.iter_mut()
+                .filter(|(key, _)| !(signature && key.as_slice() == b"Contents"))
+                .for_each(|(_, value)| encrypt_object(cipher, value));
+        }
+        Object::Stream(stream) => {
+            // Cross-reference streams are read before decryption and stay plain
+            if stream.dict.get(b"Type").and_then(Object::as_name).is_ok_and(|t| t == b"XRef") {
+                return;
+            }
+            stream.dict.iter_mut().for_each(|(_, value)| encrypt_object(cipher, value));
+            let content = encrypt_bytes(cipher, &stream.content);
+            stream.set_content(content);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockDecrypt;
+
+    fn cbc_decrypt(cipher: &Aes256, iv: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut previous = iv.to_vec();
+        let mut plain = Vec::new();
+        for block in data.chunks_exact(16) {
+            let mut decrypted = GenericArray::clone_from_slice(block);
+            cipher.decrypt_block(&mut decrypted);
+            plain.extend(decrypted.iter().zip(&previous).map(|(byte, chained)| byte ^ chained));
+            previous = block.to_vec();
+        }
+        plain
+    }
+
+    #[test]
+    fn test_restricted_document_opens_with_empty_password() {
+        let mut doc = Document::with_version("1.4");
+        let info = doc.add_object(dictionary! { "Title" => Object::string_literal("Quarterly report") });
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog" });
+        doc.trailer.set("Root", catalog);
+        doc.trailer.set("Info", info);
+
+        let permissions = Permissions::from_restrictions(&["edit", "copy", "assemble"]).unwrap();
+        assert!(permissions.print && !permissions.modify && !permissions.copy && !permissions.assemble);
+        assert!(Permissions::from_restrictions(&["everything"]).is_err());
+        restrict(&mut doc, permissions, None).unwrap();
+        assert!(matches!(restrict(&mut doc, permissions, None), Err(PermissionsError::AlreadyEncrypted)));
+
+        let encrypt_id = doc.trailer.get(b"Encrypt").unwrap().as_reference().unwrap();
+        let dict = doc.get_dictionary(encrypt_id).unwrap().clone();
+        let p = dict.get(b"P").unwrap().as_i64().unwrap() as i32;
+        assert_eq!(p as u32 & 0b1111_0011_1100, 0b1011_0010_0100);
+
+        // The empty user password validates and unwraps the file key
+        let u = dict.get(b"U").unwrap().as_str().unwrap();
+        assert_eq!(hash(b"", &u[32..40], &[]), u[..32]);
+        let key_cipher = Aes256::new(GenericArray::from_slice(&hash(b"", &u[40..48], &[])));
+        let file_key = cbc_decrypt(&key_cipher, &[0; 16], dict.get(b"UE").unwrap().as_str().unwrap());
+        let cipher = Aes256::new(GenericArray::from_slice(&file_key));
+
+        let mut perms = GenericArray::clone_from_slice(dict.get(b"Perms").unwrap().as_str().unwrap());
+        cipher.decrypt_block(&mut perms);
+        assert_eq!(perms[..4], p.to_le_bytes());
+        assert_eq!(&perms[9..12], b"adb");
+
+        let title = doc.get_dictionary(info).unwrap().get(b"Title").unwrap().as_str().unwrap();
+        let plain = cbc_decrypt(&cipher, &title[..16], &title[16..]);
+        assert_eq!(&plain[..plain.len() - usize::from(plain[plain.len() - 1])], b"Quarterly report");
+        assert_eq!(doc.version, "1.7");
+    }
+
+    #[test]
+    fn test_hash_matches_reference_vector() {
+        // User password "user" and its validation salt, from the algorithm 2.B
+        // tests of pdf.js (test/unit/crypto_spec.js, PDF20Algorithm)
+        let salt = [83, 245, 146, 101, 198, 247, 34, 198];
+        let expected = [
+            94, 230, 205, 75, 166, 99, 250, 76, 219, 128, 17, 85, 57, 17, 33, 164,
+            150, 46, 103, 176, 160, 156, 187, 233, 166, 223, 163, 253, 147, 235, 95, 184,
+        ];
+        assert_eq!(hash(b"user", &salt, &[]), expected);
+    }
+
+    #[test]
+    fn test_signature_contents_left_plain() {
+        let mut doc = Document::with_version("1.7");
+        let contents = vec![0x30, 0x82, 0x01, 0x00];
+        let sig = doc.add_object(dictionary! {
+            "Type" => "Sig",
+            "ByteRange" => vec![0.into(), 10.into(), 20.into(), 30.into()],
+            "Contents" => Object::String(contents.clone(), StringFormat::Hexadecimal),
+            "Name" => Object::string_literal("Signer"),
+        });
+        let field = doc.add_object(dictionary! {
+            "FT" => "Sig",
+            "V" => dictionary! { "ByteRange" => vec![0.into()], "Contents" => Object::string_literal("direct") },
+        });
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog" });
+        doc.trailer.set("Root", catalog);
+        encrypt(&mut doc, "", None, Permissions::all()).unwrap();
+
+        let sig = doc.get_dictionary(sig).unwrap();
+        assert_eq!(sig.get(b"Contents").unwrap().as_str().unwrap(), contents.as_slice());
+        assert_ne!(sig.get(b"Name").unwrap().as_str().unwrap(), b"Signer");
+        let value = doc.get_dictionary(field).unwrap().get(b"V").unwrap().as_dict().unwrap();
+        assert_eq!(value.get(b"Contents").unwrap().as_str().unwrap(), b"direct");
+    }
+}
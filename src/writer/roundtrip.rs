@@ -0,0 +1,123 @@
+//! Post-write round-trip validation
+//!
+//! A writer bug can produce bytes that look like a PDF and still do not
+//! read back: an xref offset a byte off, a stream whose /Length is wrong,
+//! an object that no longer tokenizes. `verify` reads written output the
+//! way the engine reads its input, one object at a time, and then checks
+//! the catalog and page tree, so output like that is caught before it is
+//! shipped. Errors carry the first broken object and its offset.
+
+use lopdf::{Object, ObjectId};
+
+use crate::{
+    antiforensics::analyzer::xref_integrity::{self, XrefIssue},
+    lazy::LazyDocument,
+    PdfError,
+};
+
+/// What a successful round trip read back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RoundTrip {
+    pub objects: usize,
+    pub pages: usize,
+}
+
+/// Parses written output back and verifies its structure
+pub fn verify(data: &[u8]) -> Result<RoundTrip, PdfError> {
+    if let Some(issue) = xref_integrity::check(data).issues.first() {
+        return Err(xref_error(issue));
+    }
+    let doc = LazyDocument::open(data)
+        .map_err(|e| PdfError::Validation(format!("written output does not parse: {}", e)))?;
+
+    let mut objects = 0;
+    for (id, object) in doc.objects() {
+        if let Err(e) = object {
+            return Err(broken(&doc, id, format!("does not parse: {}", e)));
+        }
+        objects += 1;
+    }
+
+    let root = doc.trailer().get(b"Root").and_then(Object::as_reference)
+        .map_err(|_| PdfError::Validation("written trailer has no /Root reference".into()))?;
+    let catalog = match doc.object(root) {
+        Ok(Object::Dictionary(dict)) if dict.get(b"Type").and_then(Object::as_name).is_ok_and(|t| t == b"Catalog") => dict,
+        _ => return Err(broken(&doc, root, "is not a catalog".into())),
+    };
+    let pages_id = catalog.get(b"Pages").and_then(Object::as_reference)
+        .map_err(|_| broken(&doc, root, "has no /Pages reference".into()))?;
+    let count = doc.object(pages_id).ok()
+        .and_then(|pages| pages.as_dict().ok()?.get(b"Count").ok()?.as_i64().ok())
+        .ok_or_else(|| broken(&doc, pages_id, "is not a page tree with a /Count".into()))?;
+    let pages = doc.pages().count();
+    if usize::try_from(count) != Ok(pages) {
+        return Err(broken(&doc, pages_id, format!("declares {} page(s), but {} read back", count, pages)));
+    }
+
+    Ok(RoundTrip { objects, pages })
+}
+
+fn broken(doc: &LazyDocument<'_>, id: ObjectId, problem: String) -> PdfError {
+    let error = PdfError::Validation(format!("written object {} {} R {}", id.0, id.1, problem)).with_object(id);
+    match doc.offset(id) {
+        Some(offset) => error.with_offset(offset),
+        None => error,
+    }
+}
+
+fn xref_error(issue: &XrefIssue) -> PdfError {
+    let error = |message: String| PdfError::Validation(format!("written cross-reference data is wrong: {}", message));
+    match issue {
+        XrefIssue::StartxrefMissing => error("no startxref".into()),
+        XrefIssue::StartxrefWrong { declared, .. } => {
+            error(format!("startxref {} is not a section", declared)).with_offset(*declared as u64)
+        }
+        XrefIssue::PrevLoop { offset } => error("/Prev chain loops".into()).with_offset(*offset as u64),
+        XrefIssue::PrevInvalid { from, prev } => {
+            error(format!("/Prev {} is not a section", prev)).with_offset(*from as u64)
+        }
+        XrefIssue::SizeMismatch { declared, expected } => {
+            error(format!("/Size is {} instead of {}", declared, expected))
+        }
+        XrefIssue::MisplacedEntries(entries) => {
+            let (number, generation, offset) = entries[0];
+            error(format!("{} entr(ies) point away from their object", entries.len()))
+                .with_object((number, generation))
+                .with_offset(offset)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_broken_object_reported_with_offset() {
+        let mut doc = lopdf::Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page.into()],
+            "Count" => 1,
+        }));
+        let payload = doc.add_object(Object::string_literal("payload"));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+
+        assert_eq!(verify(&data).unwrap(), RoundTrip { objects: 4, pages: 1 });
+
+        // Same length, so every offset still holds
+        let at = data.windows(9).position(|w| w == b"(payload)").unwrap();
+        data[at..at + 9].copy_from_slice(b")payload(");
+        let err = verify(&data).unwrap_err();
+        assert_eq!(err.object_id(), Some(payload));
+        let header = format!("{} 0 obj", payload.0);
+        let expected = data.windows(header.len()).position(|w| w == header.as_bytes()).unwrap() as u64;
+        assert_eq!(err.offset(), Some(expected));
+    }
+}
@@ -0,0 +1,63 @@
+//! Sandboxed decoding through a real worker process
+//!
+//! Unit tests run inside the test harness, which cannot serve as a
+//! worker; these re-execute the `pdf_engine` binary instead.
+
+use lopdf::{dictionary, Document, Object, Stream};
+use pdf_engine::{
+    fixtures::Fixture,
+    sandbox::{self, SandboxConfig, SandboxedDecoder},
+    EngineConfig, PdfEngine, ProcessingStage, ProcessingStatus, StageOutcome,
+};
+
+const CONTENT: &[u8] = b"BT /F1 12 Tf 72 712 Td (sandboxed) Tj ET";
+
+fn worker_config() -> SandboxConfig {
+    SandboxConfig {
+        program: Some(env!("CARGO_BIN_EXE_pdf_engine").into()),
+        ..Default::default()
+    }
+}
+
+fn document_with_flate_stream() -> (Document, lopdf::ObjectId) {
+    let mut doc = Document::with_version("1.7");
+    let mut stream = Stream::new(dictionary! {}, CONTENT.repeat(10));
+    stream.compress().unwrap();
+    let stream_id = doc.add_object(stream);
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog" });
+    doc.trailer.set("Root", catalog_id);
+    (doc, stream_id)
+}
+
+#[test]
+fn test_worker_process_decodes_document_streams() {
+    let (mut doc, stream_id) = document_with_flate_stream();
+    let mut decoder = SandboxedDecoder::spawn(worker_config()).unwrap();
+    let report = sandbox::decode_document(&mut doc, &mut decoder);
+
+    assert_eq!(report.decoded, 1);
+    assert!(report.rejected.is_empty(), "{:?}", report.rejected);
+    assert!(!report.confinement.is_empty());
+    match doc.get_object(stream_id).unwrap() {
+        Object::Stream(stream) => assert_eq!(stream.decompressed_content().unwrap(), CONTENT.repeat(10)),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_engine_cleans_through_worker_process() {
+    let input = Fixture::Clean.bytes();
+
+    let config = EngineConfig { sandbox: Some(worker_config()), ..Default::default() };
+    let engine = PdfEngine::new(Some(config)).await.unwrap();
+    let result = engine.process_document(&input, None).await.unwrap();
+    assert!(matches!(result.status, ProcessingStatus::Success), "{:?}", result.status);
+
+    // No worker means no decoding, never decoding in-process instead
+    let missing = SandboxConfig { program: Some("/nonexistent/pdf_engine".into()), ..Default::default() };
+    let config = EngineConfig { sandbox: Some(missing), ..Default::default() };
+    let engine = PdfEngine::new(Some(config)).await.unwrap();
+    let result = engine.process_document(&input, None).await.unwrap();
+    assert!(matches!(result.status, ProcessingStatus::Failed(_)));
+    assert!(matches!(result.stage(ProcessingStage::Clean).unwrap().outcome, StageOutcome::Failed(_)));
+}